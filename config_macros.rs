@@ -104,7 +104,7 @@ use super::*;
                         .iter()
                         .filter_map(|f| {
                             let mut new_attr = f.clone();
-                            if let quote::__private::TokenTree::Group(g) =
+                            if let proc_macro2::TokenTree::Group(g) =
                                 f.tokens.clone().into_iter().next().unwrap()
                             {
                                 let attr_inner_value = f.tokens.to_string();
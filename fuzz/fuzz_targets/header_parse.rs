@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+extern crate melib;
+
+use melib::email::parser;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parser::headers::headers(data);
+    let _ = parser::address::rfc2822address_list(data);
+    let _ = parser::address::msg_id_list(data);
+    let _ = parser::dates::rfc5322_date(data);
+    let _ = parser::encodings::phrase(data, false);
+    let _ = parser::attachments::content_type(data);
+});
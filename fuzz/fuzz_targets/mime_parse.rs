@@ -0,0 +1,25 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+extern crate melib;
+
+use melib::email::attachment_types::ContentType;
+use melib::email::attachments::decode_rec;
+use melib::{Attachment, AttachmentBuilder};
+
+fn walk(attachment: &Attachment) {
+    let _ = attachment.text();
+    let _ = attachment.filename();
+    let _ = attachment.into_raw();
+    let _ = attachment.part_boundaries();
+    let _ = decode_rec(attachment, None);
+    if let ContentType::Multipart { ref parts, .. } = attachment.content_type() {
+        for part in parts {
+            walk(part);
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    walk(&AttachmentBuilder::new(data).build());
+});
@@ -0,0 +1,262 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Synthetic corpora for the benchmarks. Everything is generated from a fixed seed, so runs are
+//! comparable with each other.
+
+#![allow(dead_code)]
+
+use melib::{Envelope, EnvelopeHash};
+use std::collections::HashMap;
+
+/// xorshift64, the same generator `tests/parser_corpus.rs` uses.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            v.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+const WORDS: &[&str] = &[
+    "patch", "review", "build", "release", "question", "bug", "meeting", "draft", "proposal",
+    "update", "schedule", "budget", "report", "invoice", "crash", "support",
+];
+
+const SEED: u64 = 0x6d65_6c69;
+
+fn message_id(i: usize) -> String {
+    format!(
+        "<{}.{}@bench.example.com>",
+        i,
+        i.wrapping_mul(2_654_435_761)
+    )
+}
+
+/// Thread structure of a mailing list: each message starts a new thread or replies to one of
+/// the recent messages. Returns the parent of each message, which always comes before it.
+fn parents(count: usize, rng: &mut Rng) -> Vec<Option<usize>> {
+    (0..count)
+        .map(|i| {
+            if i == 0 || rng.below(4) == 0 {
+                None
+            } else {
+                Some(i - 1 - rng.below(std::cmp::min(i, 200)))
+            }
+        })
+        .collect()
+}
+
+fn references(i: usize, parents: &[Option<usize>]) -> Vec<usize> {
+    let mut ret = vec![];
+    let mut cur = parents[i];
+    /* Clients keep a limited number of references. */
+    while let Some(p) = cur {
+        if ret.len() == 10 {
+            break;
+        }
+        ret.push(p);
+        cur = parents[p];
+    }
+    ret.reverse();
+    ret
+}
+
+/// `count` envelopes with Message-ID, In-Reply-To, References, subject and date set, without
+/// parsing anything. With `shuffle`, the order of the hashes returned is random, so that replies
+/// are often inserted before their parents.
+pub fn envelopes(
+    count: usize,
+    shuffle: bool,
+) -> (HashMap<EnvelopeHash, Envelope>, Vec<EnvelopeHash>) {
+    let mut rng = Rng(SEED);
+    let parents = parents(count, &mut rng);
+    let mut envelopes = HashMap::with_capacity(count);
+    let mut order = Vec::with_capacity(count);
+    for i in 0..count {
+        let hash = i as EnvelopeHash + 1;
+        let mut env = Envelope::new(hash);
+        env.set_message_id(message_id(i).as_bytes());
+        let refs = references(i, &parents);
+        if let Some(&parent) = refs.last() {
+            env.set_in_reply_to(message_id(parent).as_bytes());
+            env.set_references(
+                refs.iter()
+                    .map(|&r| message_id(r))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+                    .as_bytes(),
+            );
+        }
+        let root = refs.first().cloned().unwrap_or(i);
+        env.set_subject(
+            format!(
+                "{}{} {}",
+                if parents[i].is_some() { "Re: " } else { "" },
+                WORDS[root % WORDS.len()],
+                root
+            )
+            .into_bytes(),
+        );
+        env.set_datetime(1_577_836_800 + (i as u64) * 60 + rng.below(60) as u64);
+        envelopes.insert(hash, env);
+        order.push(hash);
+    }
+    if shuffle {
+        rng.shuffle(&mut order);
+    }
+    (envelopes, order)
+}
+
+fn body(rng: &mut Rng, paragraphs: usize) -> String {
+    let mut ret = String::new();
+    for _ in 0..paragraphs {
+        for j in 0..(20 + rng.below(60)) {
+            if j > 0 {
+                ret.push_str(if j % 12 == 0 { "\r\n" } else { " " });
+            }
+            ret.push_str(WORDS[rng.below(WORDS.len())]);
+        }
+        ret.push_str("\r\n\r\n");
+    }
+    ret
+}
+
+fn base64(rng: &mut Rng, len: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::with_capacity(len + len / 76 * 2);
+    for i in 0..len {
+        if i > 0 && i % 76 == 0 {
+            ret.push_str("\r\n");
+        }
+        ret.push(ALPHABET[rng.below(64)] as char);
+    }
+    ret
+}
+
+/// `count` raw messages. Most are plain text, every fifth one is a multipart message with an
+/// HTML alternative and a base64 attachment and every seventh one has an encoded-word subject.
+pub fn messages(count: usize) -> Vec<Vec<u8>> {
+    let mut rng = Rng(SEED);
+    let parents = parents(count, &mut rng);
+    (0..count)
+        .map(|i| {
+            let mut ret = format!(
+                "From: User {n} <user{n}@example.com>\r\nTo: list@example.com\r\n",
+                n = rng.below(500)
+            );
+            let refs = references(i, &parents);
+            let root = refs.first().cloned().unwrap_or(i);
+            let subject = if i % 7 == 0 {
+                "=?utf-8?B?zrHPgc+HzrXOr86/IOKAlCDmlofku7Y=?=".to_string()
+            } else {
+                format!("{} {}", WORDS[root % WORDS.len()], root)
+            };
+            ret.push_str(&format!(
+                "Subject: {}{}\r\nDate: {}\r\nMessage-ID: {}\r\n",
+                if refs.is_empty() { "" } else { "Re: " },
+                subject,
+                melib::datetime::timestamp_to_string(
+                    1_577_836_800 + (i as u64) * 60,
+                    Some("%a, %d %b %Y %H:%M:%S +0000"),
+                    true
+                ),
+                message_id(i)
+            ));
+            if let Some(&parent) = refs.last() {
+                ret.push_str(&format!(
+                    "In-Reply-To: {}\r\nReferences: {}\r\n",
+                    message_id(parent),
+                    refs.iter()
+                        .map(|&r| message_id(r))
+                        .collect::<Vec<String>>()
+                        .join("\r\n ")
+                ));
+            }
+            ret.push_str("MIME-Version: 1.0\r\n");
+            let paragraphs = 1 + rng.below(6);
+            let text = body(&mut rng, paragraphs);
+            if i % 5 == 0 {
+                let attachment_len = 4096 + rng.below(16384);
+                ret.push_str(&format!(
+                    "Content-Type: multipart/mixed; boundary=\"outer{i}\"\r\n\r\n\
+--outer{i}\r\n\
+Content-Type: multipart/alternative; boundary=\"inner{i}\"\r\n\r\n\
+--inner{i}\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\r\n\
+{text}\
+--inner{i}\r\n\
+Content-Type: text/html; charset=utf-8\r\n\r\n\
+<html><body><p>{text}</p></body></html>\r\n\
+--inner{i}--\r\n\
+--outer{i}\r\n\
+Content-Type: application/octet-stream; name=\"data{i}.bin\"\r\n\
+Content-Disposition: attachment; filename=\"data{i}.bin\"\r\n\
+Content-Transfer-Encoding: base64\r\n\r\n\
+{attachment}\r\n\
+--outer{i}--\r\n",
+                    i = i,
+                    text = text,
+                    attachment = base64(&mut rng, attachment_len),
+                ));
+            } else {
+                ret.push_str("Content-Type: text/plain; charset=utf-8\r\n\r\n");
+                ret.push_str(&text);
+            }
+            ret.into_bytes()
+        })
+        .collect()
+}
+
+/// Text of about `len` bytes mixing ASCII, accented latin, CJK, emoji and combining characters,
+/// like subjects and senders in a multilingual mailbox.
+pub fn text(len: usize) -> String {
+    const PIECES: &[&str] = &[
+        "Re: meeting notes ",
+        "Ελληνικά κείμενα ",
+        "日本語のテキスト ",
+        "중요한 업데이트 ",
+        "😀👍🏽 ",
+        "👨‍👩‍👧 ",
+        "e\u{301}te\u{301} ",
+        "Çà et là ",
+        "\t",
+    ];
+    let mut rng = Rng(SEED);
+    let mut ret = String::with_capacity(len + 32);
+    while ret.len() < len {
+        ret.push_str(PIECES[rng.below(PIECES.len())]);
+    }
+    ret
+}
@@ -0,0 +1,64 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! MIME parsing throughput on synthetic messages.
+
+mod corpus;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use melib::email::attachments::decode_rec;
+use melib::Envelope;
+
+fn parse(c: &mut Criterion) {
+    let messages = corpus::messages(1_000);
+    let bytes = messages.iter().map(Vec::len).sum::<usize>() as u64;
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("envelope_headers", |b| {
+        b.iter(|| {
+            for m in &messages {
+                let _ = Envelope::from_bytes(m, None);
+            }
+        })
+    });
+    let envelopes = messages
+        .iter()
+        .map(|m| Envelope::from_bytes(m, None).unwrap())
+        .collect::<Vec<Envelope>>();
+    group.bench_function("body_structure", |b| {
+        b.iter(|| {
+            for (env, m) in envelopes.iter().zip(messages.iter()) {
+                let _ = env.body_bytes(m);
+            }
+        })
+    });
+    group.bench_function("decode_text", |b| {
+        b.iter(|| {
+            for (env, m) in envelopes.iter().zip(messages.iter()) {
+                let _ = decode_rec(&env.body_bytes(m), None);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Grapheme segmentation, display width and line breaking of multilingual text.
+
+mod corpus;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use melib::text_processing::{Reflow, TextProcessing, Truncate};
+
+fn text(c: &mut Criterion) {
+    let mut group = c.benchmark_group("text");
+    for &len in &[80, 4_096, 65_536] {
+        let text = corpus::text(len);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::new("split_graphemes", len), &text, |b, t| {
+            b.iter(|| t.split_graphemes().len())
+        });
+        group.bench_with_input(BenchmarkId::new("grapheme_width", len), &text, |b, t| {
+            b.iter(|| t.grapheme_width())
+        });
+        group.bench_with_input(BenchmarkId::new("trim_at_width", len), &text, |b, t| {
+            b.iter(|| t.trim_at_width(len / 4).len())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("trim_with_ellipsis", len),
+            &text,
+            |b, t| b.iter(|| t.trim_with_ellipsis(len / 4).len()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("split_lines_reflow", len),
+            &text,
+            |b, t| b.iter(|| t.split_lines_reflow(Reflow::All, Some(72)).len()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, text);
+criterion_main!(benches);
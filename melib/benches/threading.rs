@@ -0,0 +1,87 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Thread building on synthetic mailing list corpora.
+
+mod corpus;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use melib::{Collator, SortField, SortOrder, Threads};
+use std::sync::{Arc, RwLock};
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("threads_insert");
+    group.sample_size(10);
+    for &count in &[10_000, 100_000] {
+        for &shuffle in &[false, true] {
+            let (envelopes, order) = corpus::envelopes(count, shuffle);
+            let id = BenchmarkId::new(if shuffle { "shuffled" } else { "in_order" }, count);
+            group.bench_with_input(id, &order, |b, order| {
+                b.iter_batched(
+                    || Arc::new(RwLock::new(envelopes.clone())),
+                    |envelopes| {
+                        let mut threads = Threads::new(order.len());
+                        for &env_hash in order {
+                            threads.insert(&envelopes, env_hash);
+                        }
+                        threads
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("threads_sort");
+    group.sample_size(10);
+    for &count in &[10_000, 100_000] {
+        let (envelopes, order) = corpus::envelopes(count, false);
+        let envelopes = Arc::new(RwLock::new(envelopes));
+        let mut threads = Threads::new(count);
+        for &env_hash in &order {
+            threads.insert(&envelopes, env_hash);
+        }
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| {
+                /* Sorting is skipped when the order is unchanged, so alternate between two. */
+                threads.sort_by(
+                    (SortField::Date, SortOrder::Asc),
+                    (SortField::Date, SortOrder::Asc),
+                    &envelopes,
+                    &Collator::default(),
+                );
+                threads.sort_by(
+                    (SortField::Subject, SortOrder::Desc),
+                    (SortField::Subject, SortOrder::Desc),
+                    &envelopes,
+                    &Collator::default(),
+                );
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build, sort);
+criterion_main!(benches);
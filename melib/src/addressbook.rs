@@ -62,6 +62,9 @@ pub struct AddressBook {
     created: UnixTimestamp,
     last_edited: UnixTimestamp,
     pub cards: HashMap<CardId, Card>,
+    /// Personal aliases, e.g. `team`, that expand to a list of addresses.
+    #[serde(default)]
+    pub aliases: HashMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -93,6 +96,7 @@ impl AddressBook {
             created: datetime::now(),
             last_edited: datetime::now(),
             cards: HashMap::default(),
+            aliases: HashMap::default(),
         }
     }
 
@@ -125,11 +129,54 @@ impl AddressBook {
         self.cards.contains_key(&card_id)
     }
     pub fn search(&self, term: &str) -> Vec<String> {
+        self.aliases
+            .keys()
+            .filter(|a| a.contains(term))
+            .cloned()
+            .chain(
+                self.cards
+                    .values()
+                    .filter(|c| c.email.contains(term))
+                    .map(|c| format!("{} <{}>", &c.name, &c.email)),
+            )
+            .collect()
+    }
+    pub fn add_alias(&mut self, alias: String, addresses: Vec<String>) {
+        self.aliases.insert(alias, addresses);
+    }
+    pub fn remove_alias(&mut self, alias: &str) {
+        self.aliases.remove(alias);
+    }
+    /// The address of the contact whose nickname is `nickname`, case-insensitively. The
+    /// nickname of a contact is its vCard `NICKNAME`, kept as its additional name.
+    pub fn nickname(&self, nickname: &str) -> Option<String> {
         self.cards
             .values()
-            .filter(|c| c.email.contains(term))
-            .map(|c| format!("{} <{}>", &c.name, &c.email))
-            .collect()
+            .find(|c| {
+                !c.additionalname.is_empty()
+                    && !c.email.is_empty()
+                    && c.additionalname.eq_ignore_ascii_case(nickname)
+            })
+            .map(|c| {
+                if c.name.is_empty() {
+                    c.email.clone()
+                } else {
+                    format!("{} <{}>", &c.name, &c.email)
+                }
+            })
+    }
+    /// Whether `address` is the e-mail address of a contact, case-insensitively.
+    pub fn contains_address(&self, address: &str) -> bool {
+        self.cards
+            .values()
+            .any(|c| c.email.eq_ignore_ascii_case(address))
+    }
+    /// Get the addresses an alias expands to, if it exists. Aliases are case-insensitive.
+    pub fn alias(&self, alias: &str) -> Option<&[String]> {
+        self.aliases
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(alias))
+            .map(|(_, v)| v.as_slice())
     }
 }
 
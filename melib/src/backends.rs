@@ -31,6 +31,8 @@ macro_rules! tag_hash {
     }};
 }
 
+pub mod byte_cache;
+pub use self::byte_cache::ByteCache;
 #[cfg(feature = "imap_backend")]
 pub mod imap;
 #[cfg(feature = "imap_backend")]
@@ -39,12 +41,18 @@ pub mod nntp;
 pub mod notmuch;
 #[cfg(feature = "notmuch_backend")]
 pub use self::notmuch::NotmuchDb;
+#[cfg(feature = "feeds_backend")]
+pub mod feeds;
+#[cfg(feature = "graph_backend")]
+pub mod graph;
 #[cfg(feature = "jmap_backend")]
 pub mod jmap;
 #[cfg(feature = "maildir_backend")]
 pub mod maildir;
 #[cfg(feature = "mbox_backend")]
 pub mod mbox;
+#[cfg(feature = "mh_backend")]
+pub mod mh;
 #[cfg(feature = "imap_backend")]
 pub use self::imap::ImapType;
 #[cfg(feature = "imap_backend")]
@@ -56,6 +64,8 @@ use crate::error::{MeliError, Result};
 use self::maildir::MaildirType;
 #[cfg(feature = "mbox_backend")]
 use self::mbox::MboxType;
+#[cfg(feature = "mh_backend")]
+use self::mh::MhType;
 use super::email::{Envelope, EnvelopeHash, Flag};
 use std::any::Any;
 use std::collections::BTreeSet;
@@ -137,6 +147,16 @@ impl Backends {
                 },
             );
         }
+        #[cfg(feature = "mh_backend")]
+        {
+            b.register(
+                "mh".to_string(),
+                Backend {
+                    create_fn: Box::new(|| Box::new(|f, i, ev| MhType::new(f, i, ev))),
+                    validate_conf_fn: Box::new(MhType::validate_config),
+                },
+            );
+        }
         #[cfg(feature = "imap_backend")]
         {
             b.register(
@@ -176,6 +196,26 @@ impl Backends {
                 },
             );
         }
+        #[cfg(feature = "feeds_backend")]
+        {
+            b.register(
+                "feeds".to_string(),
+                Backend {
+                    create_fn: Box::new(|| Box::new(|f, i, ev| feeds::FeedsType::new(f, i, ev))),
+                    validate_conf_fn: Box::new(feeds::FeedsType::validate_config),
+                },
+            );
+        }
+        #[cfg(feature = "graph_backend")]
+        {
+            b.register(
+                "graph".to_string(),
+                Backend {
+                    create_fn: Box::new(|| Box::new(|f, i, ev| graph::GraphType::new(f, i, ev))),
+                    validate_conf_fn: Box::new(graph::GraphType::validate_config),
+                },
+            );
+        }
         b
     }
 
@@ -223,6 +263,11 @@ pub enum BackendEvent {
         level: crate::LoggingLevel,
     },
     Refresh(RefreshEvent),
+    /// The server presented a certificate that isn't trusted; ask the user whether to accept it.
+    UntrustedCertificate(crate::connections::certificates::UntrustedCertificate),
+    /// The connection was lost or restored outside of a job, eg while reconnecting in the
+    /// background. An error describes the failed attempt and when the next one is due.
+    ConnectionStatus(Result<()>),
     //Job(Box<Future<Output = Result<()>> + Send + 'static>)
 }
 
@@ -290,11 +335,22 @@ pub struct MailBackendCapabilities {
     pub is_async: bool,
     pub is_remote: bool,
     pub extensions: Option<Vec<(String, MailBackendExtensionStatus)>>,
+    /// Backend specific actions, which are run with `MailBackend::perform_action`.
+    pub actions: &'static [BackendAction],
     pub supports_search: bool,
     pub supports_tags: bool,
     pub supports_submission: bool,
 }
 
+/// An operation only some backends have, such as compacting a notmuch database.
+#[derive(Debug, Copy, Clone)]
+pub struct BackendAction {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Names of the arguments the action takes, in order.
+    pub args: &'static [&'static str],
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum MailBackendExtensionStatus {
     Unsupported { comment: Option<&'static str> },
@@ -302,6 +358,62 @@ pub enum MailBackendExtensionStatus {
     Enabled { comment: Option<&'static str> },
 }
 
+/// Storage usage and limit of an account, in kibibytes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Quota {
+    pub usage: u64,
+    pub limit: u64,
+}
+
+impl Quota {
+    /// Usage as a percentage of the limit.
+    pub fn percentage(&self) -> u8 {
+        if self.limit == 0 {
+            return 0;
+        }
+        std::cmp::min(100, self.usage * 100 / self.limit) as u8
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} ({}%)",
+            crate::Bytes(self.usage as usize * 1024),
+            crate::Bytes(self.limit as usize * 1024),
+            self.percentage()
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagnosticStatus {
+    Ok,
+    Warning,
+    Failed,
+    /// The check was not run, eg because an earlier one failed.
+    Skipped,
+}
+
+/// The outcome of one of the checks of [`MailBackend::diagnose`].
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub name: &'static str,
+    pub status: DiagnosticStatus,
+    pub details: String,
+}
+
+impl Diagnostic {
+    pub fn new(name: &'static str, status: DiagnosticStatus, details: impl Into<String>) -> Self {
+        Diagnostic {
+            name,
+            status,
+            details: details.into(),
+        }
+    }
+}
+
 pub type ResultFuture<T> = Result<Pin<Box<dyn Future<Output = Result<T>> + Send + 'static>>>;
 
 pub trait MailBackend: ::std::fmt::Debug + Send + Sync {
@@ -390,6 +502,12 @@ pub trait MailBackend: ::std::fmt::Debug + Send + Sync {
         Err(MeliError::new("Unimplemented."))
     }
 
+    /// Run one of the `actions` advertised in the backend's capabilities, with as many `args`
+    /// as it takes. Returns a message for the user.
+    fn perform_action(&mut self, action: &str, _args: Vec<String>) -> ResultFuture<String> {
+        Err(MeliError::new(format!("Unknown action `{}`.", action)))
+    }
+
     fn search(
         &self,
         _query: crate::search::Query,
@@ -397,6 +515,28 @@ pub trait MailBackend: ::std::fmt::Debug + Send + Sync {
     ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
         Err(MeliError::new("Unimplemented."))
     }
+    /// Return the size in bytes of every message in `mailbox_hash`, for backends that can do so
+    /// without fetching the messages themselves.
+    fn message_sizes(
+        &self,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<EnvelopeHash, usize>> {
+        Err(MeliError::new("Unimplemented."))
+    }
+    /// Return the storage quota of the account, for backends that support it (eg the IMAP
+    /// `QUOTA` extension).
+    fn quota(&self) -> ResultFuture<Quota> {
+        Err(MeliError::new("Unimplemented."))
+    }
+    /// Check the connection to the server step by step (name resolution, connection, TLS,
+    /// authentication, listing and writing to mailboxes) and report the outcome of each step.
+    fn diagnose(&self) -> ResultFuture<Vec<Diagnostic>> {
+        Err(MeliError::new("Unimplemented."))
+    }
+    /// Send a message through the server, for backends with `supports_submission`.
+    fn submit(&self, _bytes: Vec<u8>) -> ResultFuture<()> {
+        Err(MeliError::new("Unimplemented."))
+    }
 }
 
 /// A `BackendOp` manages common operations for the various mail backends. They only live for the
@@ -463,6 +603,152 @@ impl BackendOp for ReadOnlyOp {
     }
 }
 
+/// Wrapper for the backend of a `read_only` account, which refuses every change to messages and
+/// mailboxes before it reaches the backend.
+///
+/// Operations are wrapped in `ReadOnlyOp`, with the same caveat.
+#[derive(Debug)]
+pub struct ReadOnlyBackend {
+    backend: Box<dyn MailBackend>,
+}
+
+impl ReadOnlyBackend {
+    pub fn new(backend: Box<dyn MailBackend>) -> Box<dyn MailBackend> {
+        Box::new(ReadOnlyBackend { backend })
+    }
+
+    fn refuse<T>() -> ResultFuture<T> {
+        Err(MeliError::new("Account is read-only."))
+    }
+}
+
+impl MailBackend for ReadOnlyBackend {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        self.backend.capabilities()
+    }
+    fn is_online(&self) -> ResultFuture<()> {
+        self.backend.is_online()
+    }
+    fn fetch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        self.backend.fetch(mailbox_hash)
+    }
+    fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        self.backend.refresh(mailbox_hash)
+    }
+    fn watch(&self) -> ResultFuture<()> {
+        self.backend.watch()
+    }
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        self.backend.mailboxes()
+    }
+    fn operation(&self, hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        Ok(ReadOnlyOp::new(self.backend.operation(hash)?))
+    }
+    fn save(
+        &self,
+        _bytes: Vec<u8>,
+        _mailbox_hash: MailboxHash,
+        _flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn copy_messages(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _source_mailbox_hash: MailboxHash,
+        _destination_mailbox_hash: MailboxHash,
+        _move_: bool,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn set_flags(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+        _flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn delete_messages(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn collection(&self) -> crate::Collection {
+        self.backend.collection()
+    }
+    /* Downcasting sees through the wrapper, to reach backend specific methods. */
+    fn as_any(&self) -> &dyn Any {
+        self.backend.as_any()
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self.backend.as_any_mut()
+    }
+    fn create_mailbox(
+        &mut self,
+        _path: String,
+    ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
+        Self::refuse()
+    }
+    fn delete_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        Self::refuse()
+    }
+    fn set_mailbox_subscription(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _val: bool,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn rename_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _new_path: String,
+    ) -> ResultFuture<Mailbox> {
+        Self::refuse()
+    }
+    fn set_mailbox_permissions(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _val: MailboxPermissions,
+    ) -> ResultFuture<()> {
+        Self::refuse()
+    }
+    fn perform_action(&mut self, _action: &str, _args: Vec<String>) -> ResultFuture<String> {
+        Self::refuse()
+    }
+    fn search(
+        &self,
+        query: crate::search::Query,
+        mailbox_hash: Option<MailboxHash>,
+    ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
+        self.backend.search(query, mailbox_hash)
+    }
+    fn message_sizes(
+        &self,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<EnvelopeHash, usize>> {
+        self.backend.message_sizes(mailbox_hash)
+    }
+    fn quota(&self) -> ResultFuture<Quota> {
+        self.backend.quota()
+    }
+    fn diagnose(&self) -> ResultFuture<Vec<Diagnostic>> {
+        self.backend.diagnose()
+    }
+    fn submit(&self, bytes: Vec<u8>) -> ResultFuture<()> {
+        self.backend.submit(bytes)
+    }
+}
+
 #[derive(Debug, Copy, Hash, Eq, Clone, Serialize, Deserialize, PartialEq)]
 pub enum SpecialUsageMailbox {
     Normal,
@@ -711,3 +997,157 @@ impl std::ops::Deref for IsSubscribedFn {
         &self.0
     }
 }
+
+/// When each mailbox of a backend that polls for changes is due to be checked, following the
+/// `refresh_interval_secs` settings of the account and of its mailboxes.
+///
+/// Intervals are randomized by up to a tenth so that mailboxes and accounts don't all wake up
+/// together, and are never shorter than `RefreshSchedule::MIN_INTERVAL`.
+#[derive(Debug, Clone)]
+pub struct RefreshSchedule {
+    default: std::time::Duration,
+    mailboxes: HashMap<String, u64>,
+    /// Next check and interval of each polled mailbox.
+    next: HashMap<MailboxHash, (std::time::Instant, std::time::Duration)>,
+}
+
+impl RefreshSchedule {
+    pub const MIN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+    /// `default` is the interval of the backend, used if the account doesn't set one.
+    pub fn new(settings: &AccountSettings, default: std::time::Duration) -> Self {
+        RefreshSchedule {
+            default: settings
+                .refresh_interval_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(default)
+                .max(Self::MIN_INTERVAL),
+            mailboxes: settings
+                .mailboxes
+                .iter()
+                .filter_map(|(path, conf)| Some((path.clone(), conf.refresh_interval_secs?)))
+                .collect(),
+            next: HashMap::default(),
+        }
+    }
+
+    /// Polling interval of the mailbox at `path`, `None` if it is not polled.
+    pub fn interval(&self, path: &str) -> Option<std::time::Duration> {
+        match self.mailboxes.get(path) {
+            Some(0) => None,
+            Some(secs) => Some(std::time::Duration::from_secs(*secs).max(Self::MIN_INTERVAL)),
+            None => Some(self.default),
+        }
+    }
+
+    /// Schedule the first check of mailboxes that are new in `mailboxes`, forget those that
+    /// are gone, and return how long to wait until the next check is due.
+    pub fn wait<'a>(
+        &mut self,
+        mailboxes: impl IntoIterator<Item = (MailboxHash, &'a str)>,
+    ) -> std::time::Duration {
+        let now = std::time::Instant::now();
+        let mut next = HashMap::default();
+        for (hash, path) in mailboxes {
+            if let Some(interval) = self.interval(path) {
+                let entry = self
+                    .next
+                    .get(&hash)
+                    .cloned()
+                    .unwrap_or_else(|| (now + jitter(interval), interval));
+                next.insert(hash, entry);
+            }
+        }
+        self.next = next;
+        self.next
+            .values()
+            .map(|(t, _)| t.saturating_duration_since(now))
+            .min()
+            .unwrap_or(self.default)
+    }
+
+    /// Mailboxes that are due to be checked now. Their next check is scheduled.
+    pub fn due(&mut self) -> SmallVec<[MailboxHash; 16]> {
+        let now = std::time::Instant::now();
+        let mut ret = SmallVec::new();
+        for (hash, (t, interval)) in self.next.iter_mut() {
+            if *t <= now {
+                *t = now + jitter(*interval);
+                ret.push(*hash);
+            }
+        }
+        ret
+    }
+}
+
+/// Factor by which all `RefreshSchedule` intervals are stretched, eg while on battery power.
+static REFRESH_INTERVAL_FACTOR: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(1);
+
+/// Stretch the polling intervals of all accounts by `factor`. Checks that are already scheduled
+/// are not moved.
+pub fn set_refresh_interval_factor(factor: u32) {
+    REFRESH_INTERVAL_FACTOR.store(factor.max(1), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// `interval` stretched by the refresh interval factor and changed randomly by up to a tenth.
+fn jitter(interval: std::time::Duration) -> std::time::Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(interval.as_nanos());
+    let jitter = (hasher.finish() % 201) as u32;
+    interval * REFRESH_INTERVAL_FACTOR.load(std::sync::atomic::Ordering::Relaxed) * (900 + jitter)
+        / 1000
+}
+
+#[test]
+fn test_refresh_schedule() {
+    let mut settings = AccountSettings {
+        refresh_interval_secs: Some(300),
+        ..Default::default()
+    };
+    for (path, secs) in &[("INBOX", 60), ("Archive", 0), ("Lists", 1)] {
+        settings.mailboxes.insert(
+            path.to_string(),
+            crate::conf::MailboxConf {
+                refresh_interval_secs: Some(*secs),
+                ..Default::default()
+            },
+        );
+    }
+    let mut schedule = RefreshSchedule::new(&settings, std::time::Duration::from_secs(180));
+    assert_eq!(
+        schedule.interval("INBOX"),
+        Some(std::time::Duration::from_secs(60))
+    );
+    assert_eq!(schedule.interval("Archive"), None);
+    assert_eq!(
+        schedule.interval("Lists"),
+        Some(RefreshSchedule::MIN_INTERVAL)
+    );
+    assert_eq!(
+        schedule.interval("Sent"),
+        Some(std::time::Duration::from_secs(300))
+    );
+
+    let wait = schedule.wait(vec![
+        (1, "INBOX"),
+        (2, "Archive"),
+        (3, "Lists"),
+        (4, "Sent"),
+    ]);
+    assert!(wait >= RefreshSchedule::MIN_INTERVAL * 9 / 10);
+    assert!(wait <= RefreshSchedule::MIN_INTERVAL * 11 / 10);
+    assert_eq!(schedule.next.len(), 3);
+    assert!(schedule.due().is_empty());
+    /* Removed mailboxes are forgotten. */
+    schedule.wait(vec![(1, "INBOX")]);
+    assert_eq!(schedule.next.len(), 1);
+
+    settings.refresh_interval_secs = None;
+    let schedule = RefreshSchedule::new(&settings, std::time::Duration::from_secs(180));
+    assert_eq!(
+        schedule.interval("Sent"),
+        Some(std::time::Duration::from_secs(180))
+    );
+}
@@ -0,0 +1,215 @@
+/*
+ * meli - backends module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Message bytes of remote accounts kept in memory.
+ *
+ * All accounts share one memory budget. When it is exceeded the least recently used messages
+ * are evicted and downloaded again the next time they are opened. Flags and headers are kept by
+ * the backends themselves and are not affected.
+ */
+
+use super::AccountHash;
+use crate::email::EnvelopeHash;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+type Key = (AccountHash, EnvelopeHash);
+
+#[derive(Debug)]
+struct State {
+    budget: usize,
+    used: usize,
+    /// Bytes of each message and their position in `order`.
+    entries: BTreeMap<Key, (Vec<u8>, u64)>,
+    /// Least recently used first.
+    order: BTreeMap<u64, Key>,
+    next_use: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+static STATE: Lazy<Mutex<State>> = Lazy::new(|| {
+    Mutex::new(State {
+        budget: ByteCache::DEFAULT_BUDGET,
+        used: 0,
+        entries: BTreeMap::new(),
+        order: BTreeMap::new(),
+        next_use: 0,
+        hits: 0,
+        misses: 0,
+        evictions: 0,
+    })
+});
+
+/// Usage of the cache, for the `cache stats` command.
+#[derive(Debug, Default, Clone)]
+pub struct ByteCacheStats {
+    pub budget: usize,
+    pub used: usize,
+    pub messages: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Bytes and number of messages of each account.
+    pub accounts: HashMap<AccountHash, (usize, usize)>,
+}
+
+/// The cache shared by all accounts.
+pub struct ByteCache;
+
+impl ByteCache {
+    pub const DEFAULT_BUDGET: usize = 256 * 1024 * 1024;
+
+    fn state() -> std::sync::MutexGuard<'static, State> {
+        STATE.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Set the memory budget in bytes, evicting messages if it is already exceeded.
+    pub fn set_budget(budget: usize) {
+        let mut state = Self::state();
+        state.budget = budget;
+        state.evict();
+    }
+
+    pub fn get(account_hash: AccountHash, env_hash: EnvelopeHash) -> Option<Vec<u8>> {
+        let mut state = Self::state();
+        let state = &mut *state;
+        let next_use = state.next_use;
+        match state.entries.get_mut(&(account_hash, env_hash)) {
+            Some((bytes, last_use)) => {
+                state.order.remove(last_use);
+                state.order.insert(next_use, (account_hash, env_hash));
+                *last_use = next_use;
+                state.next_use += 1;
+                state.hits += 1;
+                Some(bytes.clone())
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(account_hash: AccountHash, env_hash: EnvelopeHash, bytes: Vec<u8>) {
+        let mut state = Self::state();
+        state.remove((account_hash, env_hash));
+        /* A message larger than the budget would evict everything else and then itself. */
+        if bytes.len() > state.budget {
+            return;
+        }
+        let next_use = state.next_use;
+        state.next_use += 1;
+        state.used += bytes.len();
+        state.order.insert(next_use, (account_hash, env_hash));
+        state
+            .entries
+            .insert((account_hash, env_hash), (bytes, next_use));
+        state.evict();
+    }
+
+    pub fn remove(account_hash: AccountHash, env_hash: EnvelopeHash) {
+        Self::state().remove((account_hash, env_hash));
+    }
+
+    /// Remove all messages of `account_hash`.
+    pub fn clear(account_hash: AccountHash) {
+        let mut state = Self::state();
+        let keys = state
+            .entries
+            .keys()
+            .filter(|(a, _)| *a == account_hash)
+            .cloned()
+            .collect::<Vec<Key>>();
+        for key in keys {
+            state.remove(key);
+        }
+    }
+
+    pub fn stats() -> ByteCacheStats {
+        let state = Self::state();
+        let mut accounts: HashMap<AccountHash, (usize, usize)> = HashMap::default();
+        for ((account_hash, _), (bytes, _)) in state.entries.iter() {
+            let entry = accounts.entry(*account_hash).or_default();
+            entry.0 += bytes.len();
+            entry.1 += 1;
+        }
+        ByteCacheStats {
+            budget: state.budget,
+            used: state.used,
+            messages: state.entries.len(),
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+            accounts,
+        }
+    }
+}
+
+impl State {
+    fn remove(&mut self, key: Key) {
+        if let Some((bytes, last_use)) = self.entries.remove(&key) {
+            self.order.remove(&last_use);
+            self.used -= bytes.len();
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.used > self.budget {
+            let key = match self.order.values().next() {
+                Some(key) => *key,
+                None => break,
+            };
+            self.remove(key);
+            self.evictions += 1;
+        }
+    }
+}
+
+#[test]
+fn test_byte_cache() {
+    /* Hashes that no real account has. */
+    const A: AccountHash = u64::MAX;
+    const B: AccountHash = u64::MAX - 1;
+    ByteCache::clear(A);
+    ByteCache::clear(B);
+    ByteCache::set_budget(10);
+    ByteCache::insert(A, 1, vec![0; 4]);
+    ByteCache::insert(B, 1, vec![0; 4]);
+    assert_eq!(ByteCache::get(A, 1), Some(vec![0; 4]));
+    /* B's message is now the least recently used one. */
+    ByteCache::insert(A, 2, vec![0; 4]);
+    assert_eq!(ByteCache::get(B, 1), None);
+    assert!(ByteCache::get(A, 1).is_some());
+    assert!(ByteCache::get(A, 2).is_some());
+    let stats = ByteCache::stats();
+    assert_eq!(stats.used, 8);
+    assert_eq!(stats.accounts[&A], (8, 2));
+    /* Too large to keep at all. */
+    ByteCache::insert(B, 2, vec![0; 11]);
+    assert_eq!(ByteCache::get(B, 2), None);
+    assert_eq!(ByteCache::stats().used, 8);
+    ByteCache::clear(A);
+    assert_eq!(ByteCache::stats().used, 0);
+    ByteCache::set_budget(ByteCache::DEFAULT_BUDGET);
+}
@@ -0,0 +1,536 @@
+/*
+ * meli - feeds module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # Feeds backend
+//!
+//! A read-only backend that maps RSS and Atom feeds to mailboxes. Every feed listed in the
+//! `feeds` account setting becomes a mailbox named after the feed's title, and every feed item
+//! becomes an HTML message. Items are kept in memory and feeds are polled every
+//! `poll_interval` seconds.
+
+use crate::backends::*;
+use crate::conf::AccountSettings;
+use crate::datetime::{now, rfc3339_to_timestamp, rfc822_to_timestamp, timestamp_to_string};
+use crate::email::compose::mime::encode_header;
+use crate::email::*;
+use crate::error::{MeliError, Result, ResultIntoMeliError};
+use crate::get_path_hash;
+use crate::Collection;
+use isahc::config::{Configurable, RedirectPolicy};
+use isahc::prelude::HttpClient;
+use isahc::ResponseExt;
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+pub mod parser;
+use parser::*;
+
+pub mod mailbox;
+use mailbox::*;
+
+macro_rules! get_conf_val {
+    ($s:ident[$var:literal]) => {
+        $s.extra.get($var).ok_or_else(|| {
+            MeliError::new(format!(
+                "Configuration error ({}): feeds backend requires the field `{}` set",
+                $s.name.as_str(),
+                $var
+            ))
+        })
+    };
+    ($s:ident[$var:literal], $default:expr) => {
+        $s.extra
+            .get($var)
+            .map(|v| {
+                <_>::from_str(v).map_err(|e| {
+                    MeliError::new(format!(
+                        "Configuration error ({}): Invalid value for field `{}`: {}\n{}",
+                        $s.name.as_str(),
+                        $var,
+                        v,
+                        e
+                    ))
+                })
+            })
+            .unwrap_or_else(|| Ok($default))
+    };
+}
+
+/// A feed item converted to a message.
+#[derive(Debug)]
+struct FeedEntry {
+    mailbox_hash: MailboxHash,
+    bytes: Vec<u8>,
+    flags: Flag,
+}
+
+#[derive(Debug)]
+pub struct Store {
+    account_hash: AccountHash,
+    feed_urls: Vec<String>,
+    client: HttpClient,
+    mailboxes: RwLock<HashMap<MailboxHash, FeedsMailbox>>,
+    entries: Mutex<HashMap<EnvelopeHash, FeedEntry>>,
+    collection: Collection,
+    is_subscribed: IsSubscribedFn,
+    event_consumer: BackendEventConsumer,
+}
+
+impl Store {
+    async fn fetch_feed(&self, url: &str) -> Result<Feed> {
+        let mut res = self.client.get_async(url).await?;
+        if !res.status().is_success() {
+            return Err(MeliError::new(format!(
+                "Could not fetch feed {}: {}",
+                url,
+                res.status()
+            )));
+        }
+        let text = res.text_async().await?;
+        Feed::parse(&text).chain_err_summary(|| format!("Could not parse feed {}", url))
+    }
+
+    fn mailbox_url(&self, mailbox_hash: MailboxHash) -> Result<String> {
+        self.mailboxes
+            .read()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| m.url.clone())
+            .ok_or_else(|| MeliError::new(format!("Mailbox with hash {} not found", mailbox_hash)))
+    }
+
+    /// Convert the items of `feed` to envelopes. If `only_new` is set, items that have already
+    /// been seen are skipped.
+    fn envelopes(&self, mailbox_hash: MailboxHash, feed: &Feed, only_new: bool) -> Vec<Envelope> {
+        let mailboxes_lck = self.mailboxes.read().unwrap();
+        let mailbox = &mailboxes_lck[&mailbox_hash];
+        let mut entries_lck = self.entries.lock().unwrap();
+        let mut ret = Vec::with_capacity(feed.items.len());
+        for item in &feed.items {
+            let env_hash = {
+                let mut hasher = EnvelopeHasher::new();
+                mailbox.url.hash(&mut hasher);
+                item.id.hash(&mut hasher);
+                hasher.envelope_hash()
+            };
+            let is_new = !entries_lck.contains_key(&env_hash);
+            if only_new && !is_new {
+                continue;
+            }
+            let entry = entries_lck.entry(env_hash).or_insert_with(|| FeedEntry {
+                mailbox_hash,
+                bytes: item_to_message(item, &feed.title, &mailbox.url, env_hash),
+                flags: Flag::default(),
+            });
+            match Envelope::from_bytes(&entry.bytes, Some(entry.flags)) {
+                Ok(mut env) => {
+                    env.set_hash(env_hash);
+                    mailbox.total.lock().unwrap().insert_new(env_hash);
+                    if !env.is_seen() {
+                        mailbox.unseen.lock().unwrap().insert_new(env_hash);
+                    }
+                    ret.push(env);
+                }
+                Err(err) => {
+                    debug!("Could not parse feed item {}: {}", &item.id, err);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Fetch the feed of `mailbox_hash` and send a `Create` event for every new item.
+    async fn refresh_mailbox(&self, mailbox_hash: MailboxHash) -> Result<()> {
+        let url = self.mailbox_url(mailbox_hash)?;
+        let feed = self.fetch_feed(&url).await?;
+        for env in self.envelopes(mailbox_hash, &feed, true) {
+            (self.event_consumer)(
+                self.account_hash,
+                BackendEvent::Refresh(RefreshEvent {
+                    account_hash: self.account_hash,
+                    mailbox_hash,
+                    kind: RefreshEventKind::Create(Box::new(env)),
+                }),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Build an RFC 5322 message out of a feed item. The body is the item's HTML content, so it is
+/// displayed with the pager's `html_filter` like any other HTML message.
+fn item_to_message(
+    item: &FeedItem,
+    feed_title: &str,
+    url: &str,
+    env_hash: EnvelopeHash,
+) -> Vec<u8> {
+    let timestamp = item
+        .date
+        .as_ref()
+        .and_then(|d| {
+            rfc822_to_timestamp(d.as_str())
+                .ok()
+                .filter(|t| *t != 0)
+                .or_else(|| rfc3339_to_timestamp(d.as_str()).ok().filter(|t| *t != 0))
+        })
+        .unwrap_or_else(now);
+    let host = item
+        .link
+        .as_deref()
+        .unwrap_or(url)
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split(|c| c == '/' || c == ':').next())
+        .filter(|h| !h.is_empty())
+        .unwrap_or("localhost");
+    let author = item.author.as_deref().unwrap_or(feed_title);
+    let author = if author.is_ascii() {
+        format!("\"{}\"", author.replace('"', ""))
+    } else {
+        encode_header(author)
+    };
+    let title = if item.title.is_empty() {
+        "(no title)"
+    } else {
+        item.title.as_str()
+    };
+    let heading = if let Some(link) = item.link.as_ref() {
+        format!("<a href=\"{}\">{}</a>", escape(link), escape(title))
+    } else {
+        escape(title)
+    };
+    format!(
+        "Date: {date}\r\nFrom: {author} <feeds@{host}>\r\nSubject: {subject}\r\nMessage-ID: <{hash:x}@{host}>\r\nMIME-Version: 1.0\r\nContent-Type: text/html; charset=\"utf-8\"\r\nContent-Transfer-Encoding: 8bit\r\n\r\n<h1>{heading}</h1>\r\n{content}\r\n",
+        date = timestamp_to_string(timestamp, Some("%a, %d %b %Y %H:%M:%S %z"), true),
+        author = author,
+        host = host,
+        subject = encode_header(title),
+        hash = env_hash,
+        heading = heading,
+        content = item.content,
+    )
+    .into_bytes()
+}
+
+#[derive(Debug)]
+pub struct FeedsType {
+    store: Arc<Store>,
+    refresh_schedule: RefreshSchedule,
+}
+
+impl MailBackend for FeedsType {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        const CAPABILITIES: MailBackendCapabilities = MailBackendCapabilities {
+            is_async: true,
+            is_remote: true,
+            supports_search: false,
+            extensions: None,
+            actions: &[],
+            supports_tags: false,
+            supports_submission: false,
+        };
+        CAPABILITIES
+    }
+
+    fn fetch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let store = self.store.clone();
+        Ok(Box::pin(async_stream::try_stream! {
+            let url = store.mailbox_url(mailbox_hash)?;
+            let feed = store.fetch_feed(&url).await?;
+            yield store.envelopes(mailbox_hash, &feed, false);
+        }))
+    }
+
+    fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let store = self.store.clone();
+        Ok(Box::pin(async move {
+            store.refresh_mailbox(mailbox_hash).await
+        }))
+    }
+
+    fn watch(&self) -> ResultFuture<()> {
+        let store = self.store.clone();
+        let mut schedule = self.refresh_schedule.clone();
+        Ok(Box::pin(async move {
+            loop {
+                let wait = schedule.wait(
+                    store
+                        .mailboxes
+                        .read()
+                        .unwrap()
+                        .values()
+                        .filter(|m| m.is_subscribed)
+                        .map(|m| (m.hash, m.name.as_str())),
+                );
+                crate::connections::sleep(wait).await;
+                let mailbox_hashes = schedule.due();
+                for mailbox_hash in mailbox_hashes {
+                    /* A feed that is temporarily unreachable shouldn't stop polling the others. */
+                    if let Err(err) = store.refresh_mailbox(mailbox_hash).await {
+                        (store.event_consumer)(store.account_hash, err.into());
+                    }
+                }
+            }
+        }))
+    }
+
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let store = self.store.clone();
+        Ok(Box::pin(async move {
+            if store.mailboxes.read().unwrap().is_empty() {
+                let mut new_mailboxes = HashMap::default();
+                for url in &store.feed_urls {
+                    let hash = get_path_hash!(url);
+                    /* Name the mailbox after the feed's title, or its URL if it is unreachable. */
+                    let name = match store.fetch_feed(url).await {
+                        Ok(feed) if !feed.title.is_empty() => feed.title.replace('/', "-"),
+                        Ok(_) => url.clone(),
+                        Err(err) => {
+                            debug!("Could not fetch feed {}: {}", url, err);
+                            url.clone()
+                        }
+                    };
+                    new_mailboxes.insert(
+                        hash,
+                        FeedsMailbox {
+                            hash,
+                            is_subscribed: (store.is_subscribed)(&name),
+                            name,
+                            url: url.clone(),
+                            total: Default::default(),
+                            unseen: Default::default(),
+                        },
+                    );
+                }
+                *store.mailboxes.write().unwrap() = new_mailboxes;
+            }
+            Ok(store
+                .mailboxes
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|(_, f)| f.is_subscribed)
+                .map(|(&h, f)| (h, BackendMailbox::clone(f) as Mailbox))
+                .collect())
+        }))
+    }
+
+    fn operation(&self, hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        Ok(Box::new(FeedsOp {
+            hash,
+            store: self.store.clone(),
+        }))
+    }
+
+    fn save(
+        &self,
+        _bytes: Vec<u8>,
+        _mailbox_hash: MailboxHash,
+        _flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        Err(MeliError::new("Feeds backend is read-only."))
+    }
+
+    fn copy_messages(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _source_mailbox_hash: MailboxHash,
+        _destination_mailbox_hash: MailboxHash,
+        _move_: bool,
+    ) -> ResultFuture<()> {
+        Err(MeliError::new("Feeds backend is read-only."))
+    }
+
+    fn set_flags(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        if flags.iter().any(|(f, _)| f.is_err()) {
+            return Err(MeliError::new("Feeds backend does not support tags."));
+        }
+        let mailboxes_lck = self.store.mailboxes.read().unwrap();
+        let mut entries_lck = self.store.entries.lock().unwrap();
+        for env_hash in env_hashes.iter() {
+            if let Some(entry) = entries_lck.get_mut(&env_hash) {
+                for (f, value) in flags.iter() {
+                    entry.flags.set(*f.as_ref().unwrap(), *value);
+                }
+                if let Some(mailbox) = mailboxes_lck.get(&entry.mailbox_hash) {
+                    if entry.flags.contains(Flag::SEEN) {
+                        mailbox.unseen.lock().unwrap().remove(env_hash);
+                    } else {
+                        mailbox.unseen.lock().unwrap().insert_new(env_hash);
+                    }
+                }
+            }
+        }
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn delete_messages(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        Err(MeliError::new("Feeds backend is read-only."))
+    }
+
+    fn collection(&self) -> Collection {
+        self.store.collection.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl FeedsType {
+    pub fn new(
+        s: &AccountSettings,
+        is_subscribed: Box<dyn Fn(&str) -> bool + Send + Sync>,
+        event_consumer: BackendEventConsumer,
+    ) -> Result<Box<dyn MailBackend>> {
+        let client = HttpClient::builder()
+            .timeout(Duration::from_secs(30))
+            .redirect_policy(RedirectPolicy::Limit(10))
+            .build()?;
+        let account_hash = {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(s.name.as_bytes());
+            hasher.finish()
+        };
+        Ok(Box::new(FeedsType {
+            store: Arc::new(Store {
+                account_hash,
+                feed_urls: Self::feed_urls(s)?,
+                client,
+                mailboxes: Default::default(),
+                entries: Default::default(),
+                collection: Collection::default(),
+                is_subscribed: IsSubscribedFn(is_subscribed),
+                event_consumer,
+            }),
+            refresh_schedule: RefreshSchedule::new(
+                s,
+                Duration::from_secs(get_conf_val!(s["poll_interval"], 1800)?),
+            ),
+        }))
+    }
+
+    fn feed_urls(s: &AccountSettings) -> Result<Vec<String>> {
+        let ret = get_conf_val!(s["feeds"])?
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        if let Some(url) = ret
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err(MeliError::new(format!(
+                "Configuration error ({}): `{}` is not an http or https URL",
+                s.name.as_str(),
+                url
+            )));
+        }
+        Ok(ret)
+    }
+
+    pub fn validate_config(s: &AccountSettings) -> Result<()> {
+        Self::feed_urls(s)?;
+        let poll_interval: u64 = get_conf_val!(s["poll_interval"], 1800)?;
+        if poll_interval == 0 {
+            return Err(MeliError::new(format!(
+                "Configuration error ({}): `poll_interval` must be greater than zero",
+                s.name.as_str(),
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `BackendOp` implementor for feeds. Items are kept in memory, so this never blocks.
+#[derive(Debug)]
+pub struct FeedsOp {
+    hash: EnvelopeHash,
+    store: Arc<Store>,
+}
+
+impl BackendOp for FeedsOp {
+    fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
+        let ret = self
+            .store
+            .entries
+            .lock()
+            .unwrap()
+            .get(&self.hash)
+            .map(|e| e.bytes.clone())
+            .ok_or_else(|| MeliError::new(format!("Feed item {} not found", self.hash)))?;
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+
+    fn fetch_flags(&self) -> ResultFuture<Flag> {
+        let ret = self
+            .store
+            .entries
+            .lock()
+            .unwrap()
+            .get(&self.hash)
+            .map(|e| e.flags)
+            .unwrap_or_default();
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+}
+
+#[test]
+fn test_feeds_item_to_message() {
+    let item = FeedItem {
+        id: "urn:1".to_string(),
+        title: "Hello <world>".to_string(),
+        link: Some("https://example.com/post".to_string()),
+        author: None,
+        date: Some("Wed, 8 Jan 2020 10:44:03 -0800".to_string()),
+        content: "<p>content</p>".to_string(),
+    };
+    let bytes = item_to_message(&item, "Blog", "https://example.com/feed.xml", 0xabc);
+    let env = Envelope::from_bytes(&bytes, None).unwrap();
+    assert_eq!(env.subject().as_ref(), "Hello <world>");
+    assert_eq!(env.date(), 1578509043);
+    assert!(env.field_from_to_string().contains("feeds@example.com"));
+    let text = String::from_utf8(bytes).unwrap();
+    assert!(text.ends_with(
+        "<h1><a href=\"https://example.com/post\">Hello &lt;world&gt;</a></h1>\r\n<p>content</p>\r\n"
+    ));
+}
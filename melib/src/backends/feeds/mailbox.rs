@@ -0,0 +1,96 @@
+/*
+ * meli - feeds module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::backends::{
+    BackendMailbox, LazyCountSet, Mailbox, MailboxHash, MailboxPermissions, SpecialUsageMailbox,
+};
+use crate::error::Result;
+use std::sync::{Arc, Mutex};
+
+/// A mailbox holding the items of a single feed.
+#[derive(Debug, Clone)]
+pub struct FeedsMailbox {
+    pub hash: MailboxHash,
+    pub name: String,
+    pub url: String,
+    pub is_subscribed: bool,
+    pub total: Arc<Mutex<LazyCountSet>>,
+    pub unseen: Arc<Mutex<LazyCountSet>>,
+}
+
+impl BackendMailbox for FeedsMailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.name
+    }
+
+    fn change_name(&mut self, s: &str) {
+        self.name = s.to_string();
+    }
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &[]
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        None
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        MailboxPermissions {
+            set_flags: true,
+            delete_mailbox: false,
+            ..MailboxPermissions::default()
+        }
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        SpecialUsageMailbox::Normal
+    }
+
+    fn is_subscribed(&self) -> bool {
+        self.is_subscribed
+    }
+
+    fn set_is_subscribed(&mut self, new_val: bool) -> Result<()> {
+        self.is_subscribed = new_val;
+        Ok(())
+    }
+
+    fn set_special_usage(&mut self, _new_val: SpecialUsageMailbox) -> Result<()> {
+        Ok(())
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok((self.unseen.lock()?.len(), self.total.lock()?.len()))
+    }
+}
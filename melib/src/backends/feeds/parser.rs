@@ -0,0 +1,500 @@
+/*
+ * meli - feeds module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A small XML reader, just enough to parse RSS 1.0, RSS 2.0 and Atom documents.
+
+use crate::error::{MeliError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// Element name without its namespace prefix, eg `encoded` for `content:encoded`.
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|c| match c {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, local: &str) -> Option<&Element> {
+        self.elements().find(|e| e.local_name() == local)
+    }
+
+    /// Concatenated text content of this element and its descendants.
+    pub fn text(&self) -> String {
+        let mut ret = String::new();
+        for c in &self.children {
+            match c {
+                Node::Text(t) => ret.push_str(t),
+                Node::Element(e) => ret.push_str(&e.text()),
+            }
+        }
+        ret
+    }
+
+    /// Serialize the children of this element back to markup, used for Atom `xhtml` content.
+    pub fn inner_xml(&self) -> String {
+        let mut ret = String::new();
+        for c in &self.children {
+            match c {
+                Node::Text(t) => ret.push_str(&escape(t)),
+                Node::Element(e) => {
+                    ret.push('<');
+                    ret.push_str(e.local_name());
+                    for (k, v) in &e.attributes {
+                        if k == "xmlns" || k.starts_with("xmlns:") {
+                            continue;
+                        }
+                        ret.push_str(&format!(" {}=\"{}\"", k, escape(v)));
+                    }
+                    ret.push('>');
+                    ret.push_str(&e.inner_xml());
+                    ret.push_str(&format!("</{}>", e.local_name()));
+                }
+            }
+        }
+        ret
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Escape text so that it can be embedded in HTML.
+pub fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut ret = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        ret.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => {
+                ret.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..]
+                .parse::<u32>()
+                .ok()
+                .and_then(std::char::from_u32),
+            _ => None,
+        };
+        if let Some(c) = decoded {
+            ret.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            ret.push('&');
+            rest = &rest[1..];
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// Parse an XML document and return its root element.
+pub fn parse_document(input: &str) -> Result<Element> {
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = input;
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = &rest[..end];
+            if !text.trim().is_empty() {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Node::Text(unescape(text)));
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest
+                .find("]]>")
+                .ok_or_else(|| MeliError::new("Unterminated CDATA section."))?;
+            stack
+                .last_mut()
+                .unwrap()
+                .children
+                .push(Node::Text(rest["<![CDATA[".len()..end].to_string()));
+            rest = &rest[end + "]]>".len()..];
+        } else if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| MeliError::new("Unterminated comment."))?;
+            rest = &rest[end + "-->".len()..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| MeliError::new("Unterminated declaration."))?;
+            rest = &rest[end + 1..];
+        } else if rest.starts_with("</") {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| MeliError::new("Unterminated end tag."))?;
+            let name = rest[2..end].trim();
+            rest = &rest[end + 1..];
+            if stack.len() < 2 || stack.last().unwrap().name != name {
+                return Err(MeliError::new(format!("Unexpected end tag `{}`.", name)));
+            }
+            let element = stack.pop().unwrap();
+            stack
+                .last_mut()
+                .unwrap()
+                .children
+                .push(Node::Element(element));
+        } else {
+            let (element, self_closing, tag_len) = parse_start_tag(rest)?;
+            rest = &rest[tag_len..];
+            if self_closing {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Node::Element(element));
+            } else {
+                stack.push(element);
+            }
+        }
+    }
+    if stack.len() != 1 {
+        return Err(MeliError::new(format!(
+            "Unterminated element `{}`.",
+            stack.last().unwrap().name
+        )));
+    }
+    stack
+        .pop()
+        .unwrap()
+        .children
+        .into_iter()
+        .find_map(|c| match c {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+        .ok_or_else(|| MeliError::new("Document has no root element."))
+}
+
+/// Parse a start tag at the beginning of `input`. Returns the element, whether it was
+/// self-closing and the length of the tag in bytes.
+fn parse_start_tag(input: &str) -> Result<(Element, bool, usize)> {
+    let bytes = input.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/'
+    {
+        i += 1;
+    }
+    let mut element = Element {
+        name: input[1..i].to_string(),
+        ..Element::default()
+    };
+    if element.name.is_empty() {
+        return Err(MeliError::new("Empty tag name."));
+    }
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        match bytes.get(i) {
+            None => return Err(MeliError::new("Unterminated start tag.")),
+            Some(b'>') => return Ok((element, false, i + 1)),
+            Some(b'/') if bytes.get(i + 1) == Some(&b'>') => return Ok((element, true, i + 2)),
+            Some(_) => {}
+        }
+        let key_start = i;
+        while i < bytes.len()
+            && !bytes[i].is_ascii_whitespace()
+            && bytes[i] != b'='
+            && bytes[i] != b'>'
+            && bytes[i] != b'/'
+        {
+            i += 1;
+        }
+        let key = input[key_start..i].to_string();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'=') {
+            /* Attribute without a value, tolerated for sloppy feeds. */
+            if key.is_empty() {
+                i += 1;
+            } else {
+                element.attributes.push((key, String::new()));
+            }
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let quote = match bytes.get(i) {
+            Some(q @ b'"') | Some(q @ b'\'') => *q,
+            _ => return Err(MeliError::new(format!("Unquoted value for `{}`.", key))),
+        };
+        let value_start = i + 1;
+        let value_end = input[value_start..]
+            .find(quote as char)
+            .map(|p| p + value_start)
+            .ok_or_else(|| MeliError::new("Unterminated attribute value."))?;
+        element
+            .attributes
+            .push((key, unescape(&input[value_start..value_end])));
+        i = value_end + 1;
+    }
+}
+
+/// A parsed RSS or Atom feed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Feed {
+    pub title: String,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedItem {
+    /// Unique identifier of the item: its `guid`/`id`, falling back to its link and title.
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    /// Item content as HTML.
+    pub content: String,
+}
+
+impl Feed {
+    pub fn parse(input: &str) -> Result<Feed> {
+        let root = parse_document(input)?;
+        match root.local_name() {
+            "rss" => {
+                let channel = root
+                    .child("channel")
+                    .ok_or_else(|| MeliError::new("RSS feed has no channel."))?;
+                Ok(Feed {
+                    title: child_text(channel, "title").unwrap_or_default(),
+                    items: channel
+                        .elements()
+                        .filter(|e| e.local_name() == "item")
+                        .map(FeedItem::from_rss)
+                        .collect(),
+                })
+            }
+            /* RSS 1.0: items are siblings of the channel element */
+            "RDF" => Ok(Feed {
+                title: root
+                    .child("channel")
+                    .and_then(|c| child_text(c, "title"))
+                    .unwrap_or_default(),
+                items: root
+                    .elements()
+                    .filter(|e| e.local_name() == "item")
+                    .map(FeedItem::from_rss)
+                    .collect(),
+            }),
+            "feed" => Ok(Feed {
+                title: child_text(&root, "title").unwrap_or_default(),
+                items: root
+                    .elements()
+                    .filter(|e| e.local_name() == "entry")
+                    .map(FeedItem::from_atom)
+                    .collect(),
+            }),
+            other => Err(MeliError::new(format!(
+                "Unrecognised feed format: root element is `{}`.",
+                other
+            ))),
+        }
+    }
+}
+
+impl FeedItem {
+    fn from_rss(item: &Element) -> FeedItem {
+        let title = child_text(item, "title").unwrap_or_default();
+        let link = child_text(item, "link");
+        let content = child_text(item, "encoded")
+            .or_else(|| child_text(item, "description"))
+            .unwrap_or_default();
+        FeedItem {
+            id: child_text(item, "guid")
+                .or_else(|| item.attr("rdf:about").map(str::to_string))
+                .or_else(|| link.clone())
+                .unwrap_or_else(|| title.clone()),
+            author: child_text(item, "author").or_else(|| child_text(item, "creator")),
+            date: child_text(item, "pubDate").or_else(|| child_text(item, "date")),
+            title,
+            link,
+            content,
+        }
+    }
+
+    fn from_atom(entry: &Element) -> FeedItem {
+        let title = child_text(entry, "title").unwrap_or_default();
+        let link = entry
+            .elements()
+            .filter(|e| e.local_name() == "link")
+            .find(|e| e.attr("rel").map(|r| r == "alternate").unwrap_or(true))
+            .and_then(|e| e.attr("href"))
+            .map(str::to_string);
+        let content = entry
+            .child("content")
+            .or_else(|| entry.child("summary"))
+            .map(|c| match c.attr("type") {
+                Some("xhtml") => c.inner_xml(),
+                Some("html") | Some("text/html") => c.text(),
+                _ => format!("<pre>{}</pre>", escape(&c.text())),
+            })
+            .unwrap_or_default();
+        FeedItem {
+            id: child_text(entry, "id")
+                .or_else(|| link.clone())
+                .unwrap_or_else(|| title.clone()),
+            author: entry.child("author").and_then(|a| child_text(a, "name")),
+            date: child_text(entry, "updated").or_else(|| child_text(entry, "published")),
+            title,
+            link,
+            content,
+        }
+    }
+}
+
+fn child_text(element: &Element, local: &str) -> Option<String> {
+    element
+        .child(local)
+        .map(|e| e.text().trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+#[test]
+fn test_feeds_parse_document() {
+    let doc = parse_document(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- comment -->
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+<channel><title>Tom &amp; Jerry</title>
+<item><title>First</title><content:encoded><![CDATA[<p>Hi & bye</p>]]></content:encoded>
+<enclosure url='http://a/b.mp3' length="1"/></item>
+</channel></rss>"#,
+    )
+    .unwrap();
+    assert_eq!(doc.local_name(), "rss");
+    assert_eq!(doc.attr("version"), Some("2.0"));
+    let channel = doc.child("channel").unwrap();
+    assert_eq!(channel.child("title").unwrap().text(), "Tom & Jerry");
+    let item = channel.child("item").unwrap();
+    assert_eq!(item.child("encoded").unwrap().text(), "<p>Hi & bye</p>");
+    assert_eq!(
+        item.child("enclosure").unwrap().attr("url"),
+        Some("http://a/b.mp3")
+    );
+    assert_eq!(unescape("&#65;&#x42;&unknown; &"), "AB&unknown; &");
+    assert!(parse_document("<a><b></a>").is_err());
+}
+
+#[test]
+fn test_feeds_parse_feed() {
+    let rss = Feed::parse(
+        r#"<rss version="2.0"><channel><title>News</title>
+<item><title>One</title><link>https://example.com/1</link><description>&lt;b&gt;bold&lt;/b&gt;</description>
+<pubDate>Wed, 8 Jan 2020 10:44:03 -0800</pubDate></item>
+<item><title>Two</title><guid>tag:2</guid></item>
+</channel></rss>"#,
+    )
+    .unwrap();
+    assert_eq!(rss.title, "News");
+    assert_eq!(rss.items.len(), 2);
+    assert_eq!(rss.items[0].id, "https://example.com/1");
+    assert_eq!(rss.items[0].content, "<b>bold</b>");
+    assert_eq!(
+        rss.items[0].date.as_deref(),
+        Some("Wed, 8 Jan 2020 10:44:03 -0800")
+    );
+    assert_eq!(rss.items[1].id, "tag:2");
+
+    let atom = Feed::parse(
+        r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title>
+<entry><title>Post</title><id>urn:1</id>
+<link rel="self" href="https://example.com/self"/><link href="https://example.com/post"/>
+<author><name>Jane</name></author><updated>2020-01-08T10:44:03Z</updated>
+<content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>Hello</p></div></content>
+</entry></feed>"#,
+    )
+    .unwrap();
+    assert_eq!(atom.title, "Blog");
+    let entry = &atom.items[0];
+    assert_eq!(entry.id, "urn:1");
+    assert_eq!(entry.link.as_deref(), Some("https://example.com/post"));
+    assert_eq!(entry.author.as_deref(), Some("Jane"));
+    assert_eq!(entry.content, "<div><p>Hello</p></div>");
+    assert!(Feed::parse("<html></html>").is_err());
+}
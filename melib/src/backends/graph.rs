@@ -0,0 +1,609 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # Microsoft Graph backend
+//!
+//! Access Office365 and Exchange Online mailboxes through the Microsoft Graph REST API, for
+//! tenants where IMAP is disabled. Signing in uses the OAuth2 device code flow; the refresh
+//! token is kept in the data directory so that it's only needed once.
+
+use crate::backends::*;
+use crate::conf::AccountSettings;
+use crate::email::*;
+use crate::error::{MeliError, Result};
+use crate::get_path_hash;
+use crate::Collection;
+use futures::lock::Mutex as FutureMutex;
+use isahc::config::RedirectPolicy;
+use isahc::prelude::HttpClient;
+use isahc::ResponseExt;
+use serde_json::{json, Value};
+use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
+use std::hash::Hasher;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+pub mod operations;
+use operations::*;
+
+pub mod connection;
+use connection::*;
+
+pub mod protocol;
+
+pub mod objects;
+use objects::*;
+
+pub mod mailbox;
+use mailbox::*;
+
+macro_rules! get_conf_val {
+    ($s:ident[$var:literal]) => {
+        $s.extra.get($var).ok_or_else(|| {
+            MeliError::new(format!(
+                "Configuration error ({}): Graph connection requires the field `{}` set",
+                $s.name.as_str(),
+                $var
+            ))
+        })
+    };
+    ($s:ident[$var:literal], $default:expr) => {
+        $s.extra
+            .get($var)
+            .map(|v| {
+                <_>::from_str(v).map_err(|e| {
+                    MeliError::new(format!(
+                        "Configuration error ({}): Invalid value for field `{}`: {}\n{}",
+                        $s.name.as_str(),
+                        $var,
+                        v,
+                        e
+                    ))
+                })
+            })
+            .unwrap_or_else(|| Ok($default))
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphServerConf {
+    /// Application (client) id of an Azure AD app registration that allows public client flows.
+    pub client_id: String,
+    /// Directory (tenant) id or domain, or `common`/`organizations`.
+    pub tenant: String,
+    pub poll_interval: u64,
+}
+
+impl GraphServerConf {
+    pub fn new(s: &AccountSettings) -> Result<Self> {
+        Ok(GraphServerConf {
+            client_id: get_conf_val!(s["client_id"])?.to_string(),
+            tenant: get_conf_val!(s["tenant"], "organizations".to_string())?,
+            poll_interval: get_conf_val!(s["poll_interval"], 60)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Store {
+    pub account_name: Arc<String>,
+    pub account_hash: AccountHash,
+    /// Graph message id of every envelope.
+    pub id_store: Arc<Mutex<HashMap<EnvelopeHash, String>>>,
+    /// Flags of every envelope as last seen on the server.
+    pub flags_store: Arc<Mutex<HashMap<EnvelopeHash, Flag>>>,
+    pub collection: Collection,
+    pub mailboxes: Arc<RwLock<HashMap<MailboxHash, GraphMailbox>>>,
+    pub mailboxes_index: Arc<RwLock<HashMap<MailboxHash, HashSet<EnvelopeHash>>>>,
+    pub online_status: Arc<FutureMutex<(Instant, Result<()>)>>,
+    pub is_subscribed: Arc<IsSubscribedFn>,
+    pub event_consumer: BackendEventConsumer,
+}
+
+impl Store {
+    pub fn mailbox_id(&self, mailbox_hash: MailboxHash) -> Result<String> {
+        self.mailboxes
+            .read()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| m.id.clone())
+            .ok_or_else(|| MeliError::new(format!("Mailbox with hash {} not found", mailbox_hash)))
+    }
+
+    pub fn message_id(&self, env_hash: EnvelopeHash) -> Result<String> {
+        self.id_store
+            .lock()
+            .unwrap()
+            .get(&env_hash)
+            .cloned()
+            .ok_or_else(|| MeliError::new(format!("Message with hash {} not found", env_hash)))
+    }
+
+    /// Record a message of `mailbox_hash`. `is_new` is set for messages that arrived after the
+    /// mailbox was first fetched, and so aren't included in the mailbox's initial counts.
+    pub fn add_envelope(
+        &self,
+        mailbox_hash: MailboxHash,
+        obj: MessageObject,
+        is_new: bool,
+    ) -> Envelope {
+        let env_hash = obj.env_hash();
+        let flags = obj.flags();
+        self.id_store
+            .lock()
+            .unwrap()
+            .insert(env_hash, obj.id.clone());
+        self.flags_store.lock().unwrap().insert(env_hash, flags);
+        self.mailboxes_index
+            .write()
+            .unwrap()
+            .entry(mailbox_hash)
+            .or_default()
+            .insert(env_hash);
+        if let Some(mailbox) = self.mailboxes.read().unwrap().get(&mailbox_hash) {
+            if is_new {
+                mailbox.total_emails.lock().unwrap().insert_new(env_hash);
+            } else {
+                mailbox
+                    .total_emails
+                    .lock()
+                    .unwrap()
+                    .insert_existing(env_hash);
+            }
+            if !flags.contains(Flag::SEEN) {
+                if is_new {
+                    mailbox.unread_emails.lock().unwrap().insert_new(env_hash);
+                } else {
+                    mailbox
+                        .unread_emails
+                        .lock()
+                        .unwrap()
+                        .insert_existing(env_hash);
+                }
+            }
+        }
+        obj.into()
+    }
+
+    pub fn remove_envelope(&self, env_hash: EnvelopeHash) {
+        self.id_store.lock().unwrap().remove(&env_hash);
+        self.flags_store.lock().unwrap().remove(&env_hash);
+        ByteCache::remove(self.account_hash, env_hash);
+        let mailboxes_lck = self.mailboxes.read().unwrap();
+        for (mailbox_hash, set) in self.mailboxes_index.write().unwrap().iter_mut() {
+            if set.remove(&env_hash) {
+                if let Some(mailbox) = mailboxes_lck.get(mailbox_hash) {
+                    mailbox.total_emails.lock().unwrap().remove(env_hash);
+                    mailbox.unread_emails.lock().unwrap().remove(env_hash);
+                }
+            }
+        }
+    }
+
+    pub fn update_unseen(&self, mailbox_hash: MailboxHash, env_hash: EnvelopeHash, flags: Flag) {
+        if let Some(mailbox) = self.mailboxes.read().unwrap().get(&mailbox_hash) {
+            if flags.contains(Flag::SEEN) {
+                mailbox.unread_emails.lock().unwrap().remove(env_hash);
+            } else {
+                mailbox.unread_emails.lock().unwrap().insert_new(env_hash);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GraphType {
+    connection: Arc<FutureMutex<GraphConnection>>,
+    store: Arc<Store>,
+    refresh_schedule: RefreshSchedule,
+}
+
+impl MailBackend for GraphType {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        const CAPABILITIES: MailBackendCapabilities = MailBackendCapabilities {
+            is_async: true,
+            is_remote: true,
+            supports_search: true,
+            extensions: None,
+            actions: &[],
+            supports_tags: false,
+            supports_submission: true,
+        };
+        CAPABILITIES
+    }
+
+    fn is_online(&self) -> ResultFuture<()> {
+        let online = self.store.online_status.clone();
+        Ok(Box::pin(async move { online.lock().await.1.clone() }))
+    }
+
+    fn fetch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        let mut url = format!(
+            "/me/mailFolders/{}/messages?$select={}&$top=100",
+            url_encode(&store.mailbox_id(mailbox_hash)?),
+            MESSAGE_PROPERTIES
+        );
+        Ok(Box::pin(async_stream::try_stream! {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            loop {
+                let page: ListResponse<MessageObject> = conn.get(&url).await?;
+                yield page
+                    .value
+                    .into_iter()
+                    .map(|obj| store.add_envelope(mailbox_hash, obj, false))
+                    .collect::<Vec<Envelope>>();
+                match page.next_link {
+                    Some(next_link) => url = next_link,
+                    None => break,
+                }
+            }
+        }))
+    }
+
+    fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            protocol::refresh(&conn, mailbox_hash).await
+        }))
+    }
+
+    fn watch(&self) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        let store = self.store.clone();
+        let mut schedule = self.refresh_schedule.clone();
+        Ok(Box::pin(async move {
+            loop {
+                let wait = schedule.wait(
+                    store
+                        .mailboxes
+                        .read()
+                        .unwrap()
+                        .values()
+                        .filter(|m| m.is_subscribed)
+                        .map(|m| (m.hash, m.path.as_str())),
+                );
+                crate::connections::sleep(wait).await;
+                let mailbox_hashes = schedule.due();
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                for mailbox_hash in mailbox_hashes {
+                    protocol::refresh(&conn, mailbox_hash).await?;
+                }
+            }
+        }))
+    }
+
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            if store.mailboxes.read().unwrap().is_empty() {
+                let new_mailboxes = debug!(protocol::get_mailboxes(&conn).await)?;
+                *store.mailboxes.write().unwrap() = new_mailboxes;
+            }
+
+            let ret = store
+                .mailboxes
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|(_, f)| f.is_subscribed)
+                .map(|(&h, f)| (h, BackendMailbox::clone(f) as Mailbox))
+                .collect();
+
+            Ok(ret)
+        }))
+    }
+
+    fn operation(&self, hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        Ok(Box::new(GraphOp::new(
+            hash,
+            self.connection.clone(),
+            self.store.clone(),
+        )))
+    }
+
+    fn save(
+        &self,
+        bytes: Vec<u8>,
+        mailbox_hash: MailboxHash,
+        flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            let mailbox_id = store.mailbox_id(mailbox_hash)?;
+            /* Messages created from MIME are always put in Drafts first. */
+            let text = conn.send_mime("/me/messages", &bytes).await?;
+            let obj: MessageObject = serde_json::from_str(&text)?;
+            let text = conn
+                .send_json(
+                    "POST",
+                    &format!("/me/messages/{}/move", url_encode(&obj.id)),
+                    &json!({ "destinationId": mailbox_id }),
+                )
+                .await?;
+            let obj: MessageObject = serde_json::from_str(&text)?;
+            if flags.map(|f| f.contains(Flag::SEEN)).unwrap_or(false) {
+                conn.send_json(
+                    "PATCH",
+                    &format!("/me/messages/{}", url_encode(&obj.id)),
+                    &json!({ "isRead": true }),
+                )
+                .await?;
+            }
+            Ok(())
+        }))
+    }
+
+    fn submit(&self, bytes: Vec<u8>) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            /* The server saves a copy in Sent Items. */
+            conn.send_mime("/me/sendMail", &bytes).await?;
+            Ok(())
+        }))
+    }
+
+    fn copy_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        source_mailbox_hash: MailboxHash,
+        destination_mailbox_hash: MailboxHash,
+        move_: bool,
+    ) -> ResultFuture<()> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            let destination_id = store.mailbox_id(destination_mailbox_hash)?;
+            for env_hash in env_hashes.iter() {
+                let id = store.message_id(env_hash)?;
+                let text = conn
+                    .send_json(
+                        "POST",
+                        &format!(
+                            "/me/messages/{}/{}",
+                            url_encode(&id),
+                            if move_ { "move" } else { "copy" }
+                        ),
+                        &json!({ "destinationId": destination_id }),
+                    )
+                    .await?;
+                let obj: MessageObject = serde_json::from_str(&text)?;
+                if move_ {
+                    store.remove_envelope(env_hash);
+                    conn.add_refresh_event(RefreshEvent {
+                        account_hash: store.account_hash,
+                        mailbox_hash: source_mailbox_hash,
+                        kind: RefreshEventKind::Remove(env_hash),
+                    });
+                }
+                let env = store.add_envelope(destination_mailbox_hash, obj, true);
+                conn.add_refresh_event(RefreshEvent {
+                    account_hash: store.account_hash,
+                    mailbox_hash: destination_mailbox_hash,
+                    kind: RefreshEventKind::Create(Box::new(env)),
+                });
+            }
+            Ok(())
+        }))
+    }
+
+    fn set_flags(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        if flags.iter().any(|(f, _)| f.is_err()) {
+            return Err(MeliError::new(
+                "Microsoft Graph backend does not support tags.",
+            ));
+        }
+        let mut patch = serde_json::Map::new();
+        for (f, value) in flags.iter() {
+            let f = *f.as_ref().unwrap();
+            if f == Flag::SEEN {
+                patch.insert("isRead".to_string(), Value::Bool(*value));
+            } else if f == Flag::FLAGGED {
+                patch.insert(
+                    "flag".to_string(),
+                    json!({ "flagStatus": if *value { "flagged" } else { "notFlagged" } }),
+                );
+            } else {
+                return Err(MeliError::new(format!(
+                    "Microsoft Graph backend cannot set flag {:?}.",
+                    f
+                )));
+            }
+        }
+        let patch = Value::Object(patch);
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            for env_hash in env_hashes.iter() {
+                let id = store.message_id(env_hash)?;
+                conn.send_json(
+                    "PATCH",
+                    &format!("/me/messages/{}", url_encode(&id)),
+                    &patch,
+                )
+                .await?;
+                let mut new_flags = store
+                    .flags_store
+                    .lock()
+                    .unwrap()
+                    .get(&env_hash)
+                    .cloned()
+                    .unwrap_or_default();
+                for (f, value) in flags.iter() {
+                    new_flags.set(*f.as_ref().unwrap(), *value);
+                }
+                store
+                    .flags_store
+                    .lock()
+                    .unwrap()
+                    .insert(env_hash, new_flags);
+                store.update_unseen(mailbox_hash, env_hash, new_flags);
+                conn.add_refresh_event(RefreshEvent {
+                    account_hash: store.account_hash,
+                    mailbox_hash,
+                    kind: RefreshEventKind::NewFlags(env_hash, (new_flags, vec![])),
+                });
+            }
+            Ok(())
+        }))
+    }
+
+    fn delete_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            for env_hash in env_hashes.iter() {
+                let id = store.message_id(env_hash)?;
+                conn.request(
+                    "DELETE",
+                    &format!("/me/messages/{}", url_encode(&id)),
+                    "application/json",
+                    String::new(),
+                )
+                .await?;
+                store.remove_envelope(env_hash);
+                conn.add_refresh_event(RefreshEvent {
+                    account_hash: store.account_hash,
+                    mailbox_hash,
+                    kind: RefreshEventKind::Remove(env_hash),
+                });
+            }
+            Ok(())
+        }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn collection(&self) -> Collection {
+        self.store.collection.clone()
+    }
+
+    fn search(
+        &self,
+        q: crate::search::Query,
+        mailbox_hash: Option<MailboxHash>,
+    ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
+        /* Fail early on unsupported terms. */
+        protocol::query_to_kql(&q)?;
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            protocol::search(&conn, &q, mailbox_hash).await
+        }))
+    }
+}
+
+impl GraphType {
+    pub fn new(
+        s: &AccountSettings,
+        is_subscribed: Box<dyn Fn(&str) -> bool + Send + Sync>,
+        event_consumer: BackendEventConsumer,
+    ) -> Result<Box<dyn MailBackend>> {
+        let online_status = Arc::new(FutureMutex::new((
+            std::time::Instant::now(),
+            Err(MeliError::new("Account is uninitialised.")),
+        )));
+        let server_conf = GraphServerConf::new(s)?;
+
+        let account_hash = {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(s.name.as_bytes());
+            hasher.finish()
+        };
+        let store = Arc::new(Store {
+            account_name: Arc::new(s.name.clone()),
+            account_hash,
+            online_status,
+            event_consumer,
+            is_subscribed: Arc::new(IsSubscribedFn(is_subscribed)),
+            collection: Collection::default(),
+
+            id_store: Default::default(),
+            flags_store: Default::default(),
+            mailboxes: Default::default(),
+            mailboxes_index: Default::default(),
+        });
+
+        Ok(Box::new(GraphType {
+            connection: Arc::new(FutureMutex::new(GraphConnection::new(
+                &server_conf,
+                store.clone(),
+            )?)),
+            store,
+            refresh_schedule: RefreshSchedule::new(
+                s,
+                std::time::Duration::from_secs(server_conf.poll_interval),
+            ),
+        }))
+    }
+
+    pub fn validate_config(s: &AccountSettings) -> Result<()> {
+        get_conf_val!(s["client_id"])?;
+        get_conf_val!(s["tenant"], "organizations".to_string())?;
+        let poll_interval: u64 = get_conf_val!(s["poll_interval"], 60)?;
+        if poll_interval == 0 {
+            return Err(MeliError::new(format!(
+                "Configuration error ({}): `poll_interval` must be greater than zero",
+                s.name.as_str(),
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use futures::io::AsyncReadExt;
+use isahc::config::Configurable;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+pub const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0";
+const LOGIN_URL: &str = "https://login.microsoftonline.com";
+const SCOPES: &str = "offline_access https://graph.microsoft.com/Mail.ReadWrite https://graph.microsoft.com/Mail.Send";
+
+#[derive(Deserialize, Debug)]
+struct DeviceCodeResponse {
+    device_code: String,
+    message: String,
+    expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    expires_in: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct GraphConnection {
+    pub client: Arc<HttpClient>,
+    pub server_conf: GraphServerConf,
+    /// Current access token and the instant it expires.
+    pub access_token: Option<(String, Instant)>,
+    pub store: Arc<Store>,
+}
+
+impl GraphConnection {
+    pub fn new(server_conf: &GraphServerConf, store: Arc<Store>) -> Result<Self> {
+        let client = HttpClient::builder()
+            .timeout(Duration::from_secs(30))
+            .redirect_policy(RedirectPolicy::Limit(10))
+            .build()?;
+        Ok(GraphConnection {
+            client: Arc::new(client),
+            server_conf: server_conf.clone(),
+            access_token: None,
+            store,
+        })
+    }
+
+    pub fn add_refresh_event(&self, event: RefreshEvent) {
+        (self.store.event_consumer)(self.store.account_hash, BackendEvent::Refresh(event));
+    }
+
+    /// Make sure there's a valid access token, signing in if needed.
+    pub async fn connect(&mut self) -> Result<()> {
+        if let Some((_, expires)) = self.access_token.as_ref() {
+            /* Renew the token a minute early so that it doesn't expire mid-request. */
+            if Instant::now() + Duration::from_secs(60) < *expires {
+                return Ok(());
+            }
+        }
+        let res = self.authenticate().await;
+        *self.store.online_status.lock().await = (Instant::now(), res.clone());
+        res
+    }
+
+    async fn authenticate(&mut self) -> Result<()> {
+        let token = match self.read_refresh_token() {
+            Some(refresh_token) => {
+                match self
+                    .token_request(&format!(
+                        "grant_type=refresh_token&client_id={}&refresh_token={}&scope={}",
+                        url_encode(&self.server_conf.client_id),
+                        url_encode(&refresh_token),
+                        url_encode(SCOPES)
+                    ))
+                    .await?
+                {
+                    Ok(token) => token,
+                    Err(err) => {
+                        debug!("Graph refresh token rejected: {:?}", err);
+                        self.device_code_flow().await?
+                    }
+                }
+            }
+            None => self.device_code_flow().await?,
+        };
+        if let Some(ref refresh_token) = token.refresh_token {
+            if let Err(err) = self.write_refresh_token(refresh_token) {
+                debug!("Could not save Graph refresh token: {}", err);
+            }
+        }
+        self.access_token = Some((
+            token.access_token,
+            Instant::now() + Duration::from_secs(token.expires_in),
+        ));
+        Ok(())
+    }
+
+    /// OAuth2 device authorization grant (RFC 8628): the user signs in with a web browser on any
+    /// device while we poll for the token.
+    async fn device_code_flow(&self) -> Result<TokenResponse> {
+        let text = self
+            .form_post(
+                &format!(
+                    "{}/{}/oauth2/v2.0/devicecode",
+                    LOGIN_URL, self.server_conf.tenant
+                ),
+                format!(
+                    "client_id={}&scope={}",
+                    url_encode(&self.server_conf.client_id),
+                    url_encode(SCOPES)
+                ),
+            )
+            .await?;
+        let device_code: DeviceCodeResponse = match serde_json::from_str(&text) {
+            Ok(v) => v,
+            Err(_) => {
+                let err: TokenErrorResponse = serde_json::from_str(&text)?;
+                return Err(MeliError::new(err.error_description.unwrap_or(err.error))
+                    .set_summary("Could not start Microsoft sign in"));
+            }
+        };
+        (self.store.event_consumer)(
+            self.store.account_hash,
+            BackendEvent::Notice {
+                description: Some("Microsoft sign in required".to_string()),
+                content: device_code.message.clone(),
+                level: crate::LoggingLevel::WARN,
+            },
+        );
+        let deadline = Instant::now() + Duration::from_secs(device_code.expires_in);
+        let mut interval = device_code.interval;
+        loop {
+            crate::connections::sleep(Duration::from_secs(interval)).await;
+            if Instant::now() > deadline {
+                return Err(MeliError::new(
+                    "The sign in code expired before sign in was completed.",
+                ));
+            }
+            match self
+                .token_request(&format!(
+                    "grant_type=urn:ietf:params:oauth:grant-type:device_code&client_id={}&device_code={}",
+                    url_encode(&self.server_conf.client_id),
+                    url_encode(&device_code.device_code)
+                ))
+                .await?
+            {
+                Ok(token) => return Ok(token),
+                Err(err) if err.error == "authorization_pending" => {}
+                Err(err) if err.error == "slow_down" => {
+                    interval += 5;
+                }
+                Err(err) => {
+                    return Err(MeliError::new(err.error_description.unwrap_or(err.error))
+                        .set_summary("Could not sign in to Microsoft"));
+                }
+            }
+        }
+    }
+
+    async fn token_request(
+        &self,
+        body: &str,
+    ) -> Result<std::result::Result<TokenResponse, TokenErrorResponse>> {
+        let text = self
+            .form_post(
+                &format!(
+                    "{}/{}/oauth2/v2.0/token",
+                    LOGIN_URL, self.server_conf.tenant
+                ),
+                body.to_string(),
+            )
+            .await?;
+        if let Ok(token) = serde_json::from_str::<TokenResponse>(&text) {
+            return Ok(Ok(token));
+        }
+        Ok(Err(serde_json::from_str::<TokenErrorResponse>(&text)?))
+    }
+
+    async fn form_post(&self, url: &str, body: String) -> Result<String> {
+        let req = isahc::http::Request::post(url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+        let mut res = self.client.send_async(req).await?;
+        Ok(res.text_async().await?)
+    }
+
+    fn refresh_token_path(&self) -> Result<std::path::PathBuf> {
+        crate::paths::moved_state_file(
+            Some(&self.store.account_name),
+            "graph_refresh_token",
+            None,
+            &format!(
+                "{}_graph_refresh_token",
+                self.store.account_name.replace('/', "_")
+            ),
+        )
+    }
+
+    fn read_refresh_token(&self) -> Option<String> {
+        let token = std::fs::read_to_string(self.refresh_token_path().ok()?).ok()?;
+        let token = token.trim();
+        if token.is_empty() {
+            None
+        } else {
+            Some(token.to_string())
+        }
+    }
+
+    fn write_refresh_token(&self, token: &str) -> Result<()> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(self.refresh_token_path()?)?;
+        file.write_all(token.as_bytes())?;
+        Ok(())
+    }
+
+    /// Send an authenticated request to the Graph API. `url` is either relative to the API root
+    /// or a full URL, such as a `@odata.nextLink`.
+    pub async fn request_bytes(
+        &self,
+        method: &str,
+        url: &str,
+        content_type: &str,
+        body: String,
+    ) -> Result<Vec<u8>> {
+        let url = if url.starts_with("https://") {
+            url.to_string()
+        } else {
+            format!("{}{}", GRAPH_URL, url)
+        };
+        let access_token = self
+            .access_token
+            .as_ref()
+            .map(|(t, _)| t.as_str())
+            .ok_or_else(|| MeliError::new("Not signed in."))?;
+        let req = isahc::http::Request::builder()
+            .method(method)
+            .uri(url.as_str())
+            .header("Authorization", format!("Bearer {}", access_token))
+            .header("Prefer", "IdType=\"ImmutableId\"")
+            .header("Content-Type", content_type)
+            .body(body)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+        let mut res = self.client.send_async(req).await?;
+        let mut bytes = vec![];
+        res.body_mut().read_to_end(&mut bytes).await?;
+        if !res.status().is_success() {
+            let text = String::from_utf8_lossy(&bytes);
+            let message = serde_json::from_str::<ErrorResponse>(&text)
+                .map(|r| format!("{}: {}", r.error.code, r.error.message))
+                .unwrap_or_else(|_| text.to_string());
+            return Err(MeliError::new(message).set_summary(format!(
+                "Graph request {} {} failed: {}",
+                method,
+                url,
+                res.status()
+            )));
+        }
+        Ok(bytes)
+    }
+
+    pub async fn request(
+        &self,
+        method: &str,
+        url: &str,
+        content_type: &str,
+        body: String,
+    ) -> Result<String> {
+        Ok(String::from_utf8(
+            self.request_bytes(method, url, content_type, body).await?,
+        )?)
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let text = self
+            .request("GET", url, "application/json", String::new())
+            .await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Fetch every page of a collection.
+    pub async fn get_all<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>> {
+        let mut ret = vec![];
+        let mut url = url.to_string();
+        loop {
+            let page: ListResponse<T> = self.get(&url).await?;
+            ret.extend(page.value);
+            match page.next_link {
+                Some(next_link) => url = next_link,
+                None => break,
+            }
+        }
+        Ok(ret)
+    }
+
+    pub async fn send_json(&self, method: &str, url: &str, body: &Value) -> Result<String> {
+        self.request(method, url, "application/json", body.to_string())
+            .await
+    }
+
+    /// Send a message to the mail store in MIME format, which Graph expects base64 encoded.
+    pub async fn send_mime(&self, url: &str, bytes: &[u8]) -> Result<String> {
+        self.request(
+            "POST",
+            url,
+            "text/plain",
+            data_encoding::BASE64.encode(bytes),
+        )
+        .await
+    }
+}
+
+/// Percent-encode a string for use in a URL query or a form body.
+pub fn url_encode(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                ret.push(b as char)
+            }
+            b => ret.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    ret
+}
@@ -0,0 +1,102 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::backends::{LazyCountSet, MailboxPermissions, SpecialUsageMailbox};
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Debug, Clone)]
+pub struct GraphMailbox {
+    pub name: String,
+    pub path: String,
+    pub hash: MailboxHash,
+    pub id: String,
+    pub children: Vec<MailboxHash>,
+    pub parent_hash: Option<MailboxHash>,
+    pub is_subscribed: bool,
+    pub total_emails: Arc<Mutex<LazyCountSet>>,
+    pub unread_emails: Arc<Mutex<LazyCountSet>>,
+    pub usage: Arc<RwLock<SpecialUsageMailbox>>,
+}
+
+impl BackendMailbox for GraphMailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn change_name(&mut self, _s: &str) {}
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &self.children
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        self.parent_hash
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        MailboxPermissions {
+            create_messages: true,
+            remove_messages: true,
+            set_flags: true,
+            rename_messages: true,
+            delete_messages: true,
+            ..MailboxPermissions::default()
+        }
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        *self.usage.read().unwrap()
+    }
+
+    fn is_subscribed(&self) -> bool {
+        self.is_subscribed
+    }
+
+    fn set_is_subscribed(&mut self, new_val: bool) -> Result<()> {
+        self.is_subscribed = new_val;
+        Ok(())
+    }
+
+    fn set_special_usage(&mut self, new_val: SpecialUsageMailbox) -> Result<()> {
+        *self.usage.write()? = new_val;
+        Ok(())
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok((
+            self.unread_emails.lock()?.len(),
+            self.total_emails.lock()?.len(),
+        ))
+    }
+}
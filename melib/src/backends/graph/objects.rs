@@ -0,0 +1,282 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Microsoft Graph resources, as returned by the REST API.
+
+use super::*;
+use crate::email::address::Address;
+
+/// Properties requested for every message, enough to build an `Envelope` without fetching the
+/// message source.
+pub const MESSAGE_PROPERTIES: &str = "id,parentFolderId,subject,from,toRecipients,ccRecipients,bccRecipients,sentDateTime,receivedDateTime,internetMessageId,internetMessageHeaders,isRead,isDraft,hasAttachments,flag";
+
+/// Properties requested when checking a mailbox for changes.
+pub const MESSAGE_STATE_PROPERTIES: &str = "id,isRead,isDraft,flag";
+
+/// A page of a collection. Further pages are fetched from `next_link`.
+#[derive(Deserialize, Debug)]
+pub struct ListResponse<T> {
+    pub value: Vec<T>,
+    #[serde(rename = "@odata.nextLink", default)]
+    pub next_link: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorResponse {
+    pub error: ErrorObject,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorObject {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MailFolderObject {
+    pub id: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub parent_folder_id: Option<String>,
+    #[serde(default)]
+    pub child_folder_count: usize,
+    #[serde(default)]
+    pub unread_item_count: usize,
+    #[serde(default)]
+    pub total_item_count: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailAddressObject {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipientObject {
+    pub email_address: EmailAddressObject,
+}
+
+impl From<RecipientObject> for Address {
+    fn from(val: RecipientObject) -> Address {
+        let EmailAddressObject { name, address } = val.email_address;
+        Address::new(
+            name.filter(|n| !n.is_empty() && Some(n) != address.as_ref()),
+            address.unwrap_or_default(),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowupFlagObject {
+    pub flag_status: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessageHeaderObject {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageObject {
+    pub id: String,
+    #[serde(default)]
+    pub parent_folder_id: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub from: Option<RecipientObject>,
+    #[serde(default)]
+    pub to_recipients: Vec<RecipientObject>,
+    #[serde(default)]
+    pub cc_recipients: Vec<RecipientObject>,
+    #[serde(default)]
+    pub bcc_recipients: Vec<RecipientObject>,
+    #[serde(default)]
+    pub sent_date_time: Option<String>,
+    #[serde(default)]
+    pub received_date_time: Option<String>,
+    #[serde(default)]
+    pub internet_message_id: Option<String>,
+    #[serde(default)]
+    pub internet_message_headers: Vec<MessageHeaderObject>,
+    #[serde(default)]
+    pub is_read: bool,
+    #[serde(default)]
+    pub is_draft: bool,
+    #[serde(default)]
+    pub has_attachments: bool,
+    #[serde(default)]
+    pub flag: Option<FollowupFlagObject>,
+}
+
+impl MessageObject {
+    pub fn env_hash(&self) -> EnvelopeHash {
+        message_id_hash(&self.id)
+    }
+
+    pub fn flags(&self) -> Flag {
+        let mut ret = Flag::default();
+        ret.set(Flag::SEEN, self.is_read);
+        ret.set(Flag::DRAFT, self.is_draft);
+        ret.set(
+            Flag::FLAGGED,
+            self.flag
+                .as_ref()
+                .map(|f| f.flag_status == "flagged")
+                .unwrap_or(false),
+        );
+        ret
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.internet_message_headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+}
+
+/// Envelope hash of a Graph message id. Messages are requested with immutable ids, so the hash
+/// doesn't change when a message is moved.
+pub fn message_id_hash(id: &str) -> EnvelopeHash {
+    let mut h = EnvelopeHasher::new();
+    h.write(id.as_bytes());
+    h.envelope_hash()
+}
+
+impl From<MessageObject> for Envelope {
+    fn from(mut t: MessageObject) -> Envelope {
+        let mut env = Envelope::new(t.env_hash());
+        let date = t
+            .sent_date_time
+            .as_ref()
+            .or_else(|| t.received_date_time.as_ref());
+        if let Some(date) = date {
+            env.set_datetime(
+                crate::datetime::rfc3339_to_timestamp(date.as_bytes().to_vec()).unwrap_or(0),
+            );
+            env.set_date(date.as_bytes());
+        }
+        if let Some(v) = t.header("Date").map(str::to_string) {
+            env.set_date(v.as_bytes());
+            if let Ok(d) = crate::email::parser::dates::rfc5322_date(v.as_bytes()) {
+                env.set_datetime(d);
+            }
+        }
+        if let Some(ref v) = t.internet_message_id {
+            env.set_message_id(v.as_bytes());
+        }
+        if let Some(v) = t.header("In-Reply-To").map(str::to_string) {
+            env.set_in_reply_to(v.as_bytes());
+            if let Some(in_reply_to) = env.in_reply_to().cloned() {
+                env.push_references(in_reply_to);
+            }
+        }
+        if let Some(v) = t.header("References").map(str::to_string) {
+            env.set_references(v.as_bytes());
+        }
+        env.set_has_attachments(t.has_attachments);
+        env.set_flags(t.flags());
+        if let Some(subject) = t.subject.take() {
+            env.set_subject(subject.into_bytes());
+        }
+        if let Some(from) = t.from.take() {
+            env.set_from(smallvec::smallvec![from.into()]);
+        }
+        env.set_to(
+            std::mem::replace(&mut t.to_recipients, Vec::new())
+                .into_iter()
+                .map(Address::from)
+                .collect(),
+        );
+        env.set_cc(
+            std::mem::replace(&mut t.cc_recipients, Vec::new())
+                .into_iter()
+                .map(Address::from)
+                .collect(),
+        );
+        env.set_bcc(
+            std::mem::replace(&mut t.bcc_recipients, Vec::new())
+                .into_iter()
+                .map(Address::from)
+                .collect(),
+        );
+        if let Some(ref r) = env.references {
+            if let Some(pos) = r.refs.iter().position(|r| r == env.message_id()) {
+                env.references.as_mut().unwrap().refs.remove(pos);
+            }
+        }
+        env
+    }
+}
+
+#[test]
+fn test_graph_message_object() {
+    let obj: MessageObject = serde_json::from_str(
+        r#"{
+            "id": "AAMkAGI2",
+            "subject": "Quarterly report",
+            "from": {"emailAddress": {"name": "Adele Vance", "address": "adele@contoso.com"}},
+            "toRecipients": [{"emailAddress": {"address": "alex@contoso.com"}}],
+            "sentDateTime": "2020-01-08T18:44:03Z",
+            "internetMessageId": "<abc@contoso.com>",
+            "internetMessageHeaders": [{"name": "In-Reply-To", "value": "<def@contoso.com>"}],
+            "isRead": true,
+            "hasAttachments": true,
+            "flag": {"flagStatus": "flagged"}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(obj.flags(), Flag::SEEN | Flag::FLAGGED);
+    let env: Envelope = obj.into();
+    assert_eq!(env.hash(), message_id_hash("AAMkAGI2"));
+    assert_eq!(env.subject().as_ref(), "Quarterly report");
+    assert_eq!(env.datetime(), 1578509043);
+    assert_eq!(
+        env.field_from_to_string(),
+        "Adele Vance <adele@contoso.com>"
+    );
+    assert_eq!(env.field_to_to_string(), "alex@contoso.com");
+    assert!(env.has_attachments());
+    assert_eq!(
+        env.in_reply_to_display().as_deref(),
+        Some("<def@contoso.com>")
+    );
+
+    let page: ListResponse<MailFolderObject> = serde_json::from_str(
+        r#"{"@odata.nextLink": "https://graph.microsoft.com/v1.0/me/mailFolders?$skip=10",
+            "value": [{"id": "AQMk", "displayName": "Inbox", "childFolderCount": 1,
+                       "unreadItemCount": 3, "totalItemCount": 10}]}"#,
+    )
+    .unwrap();
+    assert!(page.next_link.is_some());
+    assert_eq!(page.value[0].display_name, "Inbox");
+    assert_eq!(page.value[0].total_item_count, 10);
+}
@@ -0,0 +1,89 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use std::sync::Arc;
+
+/// `BackendOp` implementor for Microsoft Graph
+#[derive(Debug, Clone)]
+pub struct GraphOp {
+    hash: EnvelopeHash,
+    connection: Arc<FutureMutex<GraphConnection>>,
+    store: Arc<Store>,
+}
+
+impl GraphOp {
+    pub fn new(
+        hash: EnvelopeHash,
+        connection: Arc<FutureMutex<GraphConnection>>,
+        store: Arc<Store>,
+    ) -> Self {
+        GraphOp {
+            hash,
+            connection,
+            store,
+        }
+    }
+}
+
+impl BackendOp for GraphOp {
+    fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
+        if let Some(ret) = ByteCache::get(self.store.account_hash, self.hash) {
+            return Ok(Box::pin(async move { Ok(ret) }));
+        }
+        let store = self.store.clone();
+        let hash = self.hash;
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let id = store
+                .id_store
+                .lock()
+                .unwrap()
+                .get(&hash)
+                .cloned()
+                .ok_or_else(|| MeliError::new(format!("Message with hash {} not found", hash)))?;
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            let bytes = conn
+                .request_bytes(
+                    "GET",
+                    &format!("/me/messages/{}/$value", url_encode(&id)),
+                    "application/json",
+                    String::new(),
+                )
+                .await?;
+            ByteCache::insert(store.account_hash, hash, bytes.clone());
+            Ok(bytes)
+        }))
+    }
+
+    fn fetch_flags(&self) -> ResultFuture<Flag> {
+        let ret = self
+            .store
+            .flags_store
+            .lock()
+            .unwrap()
+            .get(&self.hash)
+            .cloned()
+            .unwrap_or_default();
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * meli - graph module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::mailbox::GraphMailbox;
+use super::*;
+use crate::search::Query;
+
+/// Map Exchange's well-known folder names to their special usage.
+fn folder_usage(display_name: &str) -> SpecialUsageMailbox {
+    match display_name {
+        "Sent Items" => SpecialUsageMailbox::Sent,
+        "Deleted Items" => SpecialUsageMailbox::Trash,
+        "Junk Email" => SpecialUsageMailbox::Junk,
+        other => SpecialUsageMailbox::detect_usage(other).unwrap_or_default(),
+    }
+}
+
+pub async fn get_mailboxes(conn: &GraphConnection) -> Result<HashMap<MailboxHash, GraphMailbox>> {
+    let mut ret: HashMap<MailboxHash, GraphMailbox> = HashMap::default();
+    /* (parent hash, parent path, url of the folder list) */
+    let mut queue: Vec<(Option<MailboxHash>, String, String)> =
+        vec![(None, String::new(), "/me/mailFolders?$top=100".to_string())];
+    while let Some((parent_hash, parent_path, url)) = queue.pop() {
+        for folder in conn.get_all::<MailFolderObject>(&url).await? {
+            let hash = get_path_hash!(&folder.id);
+            let path = if parent_path.is_empty() {
+                folder.display_name.clone()
+            } else {
+                format!("{}/{}", parent_path, folder.display_name)
+            };
+            if folder.child_folder_count > 0 {
+                queue.push((
+                    Some(hash),
+                    path.clone(),
+                    format!(
+                        "/me/mailFolders/{}/childFolders?$top=100",
+                        url_encode(&folder.id)
+                    ),
+                ));
+            }
+            if let Some(parent) = parent_hash.and_then(|h| ret.get_mut(&h)) {
+                parent.children.push(hash);
+            }
+            let usage = if parent_hash.is_none() {
+                folder_usage(&folder.display_name)
+            } else {
+                SpecialUsageMailbox::Normal
+            };
+            let mut total_emails = LazyCountSet::default();
+            total_emails.set_not_yet_seen(folder.total_item_count);
+            let mut unread_emails = LazyCountSet::default();
+            unread_emails.set_not_yet_seen(folder.unread_item_count);
+            ret.insert(
+                hash,
+                GraphMailbox {
+                    is_subscribed: (conn.store.is_subscribed)(&path),
+                    name: folder.display_name,
+                    path,
+                    hash,
+                    id: folder.id,
+                    children: vec![],
+                    parent_hash,
+                    total_emails: Arc::new(Mutex::new(total_emails)),
+                    unread_emails: Arc::new(Mutex::new(unread_emails)),
+                    usage: Arc::new(RwLock::new(usage)),
+                },
+            );
+        }
+    }
+    Ok(ret)
+}
+
+/// Compare the messages of a mailbox on the server with the ones we know of and send refresh
+/// events for the differences.
+pub async fn refresh(conn: &GraphConnection, mailbox_hash: MailboxHash) -> Result<()> {
+    let store = &conn.store;
+    let mailbox_id = store.mailbox_id(mailbox_hash)?;
+    let current = conn
+        .get_all::<MessageObject>(&format!(
+            "/me/mailFolders/{}/messages?$select={}&$top=500",
+            url_encode(&mailbox_id),
+            MESSAGE_STATE_PROPERTIES
+        ))
+        .await?;
+    let mut known = store
+        .mailboxes_index
+        .read()
+        .unwrap()
+        .get(&mailbox_hash)
+        .cloned()
+        .unwrap_or_default();
+    for obj in current {
+        let env_hash = obj.env_hash();
+        if known.remove(&env_hash) {
+            let flags = obj.flags();
+            if store.flags_store.lock().unwrap().insert(env_hash, flags) != Some(flags) {
+                store.update_unseen(mailbox_hash, env_hash, flags);
+                conn.add_refresh_event(RefreshEvent {
+                    account_hash: store.account_hash,
+                    mailbox_hash,
+                    kind: RefreshEventKind::NewFlags(env_hash, (flags, vec![])),
+                });
+            }
+            continue;
+        }
+        let obj: MessageObject = conn
+            .get(&format!(
+                "/me/messages/{}?$select={}",
+                url_encode(&obj.id),
+                MESSAGE_PROPERTIES
+            ))
+            .await?;
+        let env = store.add_envelope(mailbox_hash, obj, true);
+        conn.add_refresh_event(RefreshEvent {
+            account_hash: store.account_hash,
+            mailbox_hash,
+            kind: RefreshEventKind::Create(Box::new(env)),
+        });
+    }
+    for env_hash in known {
+        store.remove_envelope(env_hash);
+        conn.add_refresh_event(RefreshEvent {
+            account_hash: store.account_hash,
+            mailbox_hash,
+            kind: RefreshEventKind::Remove(env_hash),
+        });
+    }
+    Ok(())
+}
+
+/// Translate a query to the KQL syntax of the `$search` query parameter.
+pub fn query_to_kql(q: &Query) -> Result<String> {
+    use Query::*;
+    fn quote(s: &str) -> String {
+        format!("\"{}\"", s.replace('"', ""))
+    }
+    fn date(t: crate::UnixTimestamp) -> String {
+        crate::datetime::timestamp_to_string(t, Some("%Y-%m-%d"), true)
+    }
+    Ok(match q {
+        Before(t) => format!("received<{}", date(*t)),
+        After(t) => format!("received>{}", date(*t)),
+        Between(a, b) => format!("received>={} AND received<={}", date(*a), date(*b)),
+        On(t) => format!("received={}", date(*t)),
+        From(s) => format!("from:{}", quote(s)),
+        To(s) => format!("to:{}", quote(s)),
+        Cc(s) => format!("cc:{}", quote(s)),
+        Bcc(s) => format!("bcc:{}", quote(s)),
+        AllAddresses(s) => format!("participants:{}", quote(s)),
+        Body(s) => format!("body:{}", quote(s)),
+        Subject(s) => format!("subject:{}", quote(s)),
+        AllText(s) => quote(s),
+        HasAttachment => "hasAttachments:true".to_string(),
+        Larger(n) => format!("size>{}", n),
+        Smaller(n) => format!("size<{}", n),
+        And(a, b) => format!("({} AND {})", query_to_kql(a)?, query_to_kql(b)?),
+        Or(a, b) => format!("({} OR {})", query_to_kql(a)?, query_to_kql(b)?),
+        Not(a) => format!("NOT {}", query_to_kql(a)?),
+        InReplyTo(_) | References(_) | Flags(_) | Note(_) => {
+            return Err(MeliError::new(
+                "Microsoft Graph search does not support this search term.",
+            ));
+        }
+    })
+}
+
+pub async fn search(
+    conn: &GraphConnection,
+    q: &Query,
+    mailbox_hash: Option<MailboxHash>,
+) -> Result<SmallVec<[EnvelopeHash; 512]>> {
+    let kql = query_to_kql(q)?;
+    let prefix = if let Some(mailbox_hash) = mailbox_hash {
+        format!(
+            "/me/mailFolders/{}/messages",
+            url_encode(&conn.store.mailbox_id(mailbox_hash)?)
+        )
+    } else {
+        "/me/messages".to_string()
+    };
+    Ok(conn
+        .get_all::<MessageObject>(&format!(
+            "{}?$search={}&$select=id&$top=1000",
+            prefix,
+            url_encode(&format!("\"{}\"", kql.replace('"', "\\\"")))
+        ))
+        .await?
+        .into_iter()
+        .map(|obj| obj.env_hash())
+        .collect())
+}
+
+#[test]
+fn test_graph_query_to_kql() {
+    use Query::*;
+    let q = And(
+        Box::new(From("Adele Vance".to_string())),
+        Box::new(Or(
+            Box::new(Subject("report".to_string())),
+            Box::new(Not(Box::new(HasAttachment))),
+        )),
+    );
+    assert_eq!(
+        query_to_kql(&q).unwrap(),
+        "(from:\"Adele Vance\" AND (subject:\"report\" OR NOT hasAttachments:true))"
+    );
+    assert!(query_to_kql(&Flags(vec!["seen".to_string()])).is_err());
+    assert_eq!(url_encode("a b/\"c\""), "a%20b%2F%22c%22");
+}
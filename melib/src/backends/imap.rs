@@ -34,6 +34,7 @@ mod watch;
 pub use watch::*;
 mod cache;
 use cache::ModSequence;
+mod diagnostics;
 pub mod managesieve;
 mod untagged;
 
@@ -44,13 +45,14 @@ use crate::backends::{
 
 use crate::collection::Collection;
 use crate::conf::AccountSettings;
-use crate::connections::timeout;
+use crate::connections::{proxy::Proxy, sleep, timeout, tls::TlsSettings, Backoff};
 use crate::email::{parser::BytesExt, *};
 use crate::error::{MeliError, Result, ResultIntoMeliError};
+use crate::secrets::{Secret, SecretSource};
 use futures::lock::Mutex as FutureMutex;
 use futures::stream::Stream;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::hash::Hasher;
@@ -78,13 +80,14 @@ pub static SUPPORTED_CAPABILITIES: &[&str] = &[
     "LOGIN",
     "LOGINDISABLED",
     "MOVE",
+    "QUOTA",
     "SPECIAL-USE",
     "UNSELECT",
 ];
 
 #[derive(Debug, Default)]
+/// Flags of a message. Its bytes are kept in the `ByteCache` shared by all accounts.
 pub struct EnvelopeCache {
-    bytes: Option<Vec<u8>>,
     flags: Option<Flag>,
 }
 
@@ -92,13 +95,16 @@ pub struct EnvelopeCache {
 pub struct ImapServerConf {
     pub server_hostname: String,
     pub server_username: String,
-    pub server_password: String,
+    pub server_password: Secret,
     pub server_port: u16,
     pub use_starttls: bool,
     pub use_tls: bool,
-    pub danger_accept_invalid_certs: bool,
+    pub tls: TlsSettings,
+    pub proxy: Option<Proxy>,
     pub protocol: ImapProtocol,
     pub timeout: Option<Duration>,
+    /// How long to wait for the server to accept a connection.
+    pub connect_timeout: Option<Duration>,
 }
 
 type Capabilities = HashSet<Vec<u8>>;
@@ -153,6 +159,7 @@ pub struct UIDStore {
     highestmodseqs: Arc<Mutex<HashMap<MailboxHash, std::result::Result<ModSequence, ()>>>>,
     mailboxes: Arc<FutureMutex<HashMap<MailboxHash, ImapMailbox>>>,
     is_online: Arc<Mutex<(SystemTime, Result<()>)>>,
+    reconnect_backoff: Arc<Mutex<Backoff>>,
     event_consumer: BackendEventConsumer,
     timeout: Option<Duration>,
 }
@@ -184,6 +191,7 @@ impl UIDStore {
                 SystemTime::now(),
                 Err(MeliError::new("Account is uninitialised.")),
             ))),
+            reconnect_backoff: Default::default(),
             event_consumer,
             timeout,
         }
@@ -196,6 +204,10 @@ pub struct ImapType {
     connection: Arc<FutureMutex<ImapConnection>>,
     server_conf: ImapServerConf,
     uid_store: Arc<UIDStore>,
+    /// Used when the server doesn't support IDLE.
+    refresh_schedule: RefreshSchedule,
+    /// The account's `read_only` setting, so that diagnostics don't write either.
+    read_only: bool,
 }
 
 impl MailBackend for ImapType {
@@ -221,6 +233,7 @@ impl MailBackend for ImapType {
                     deflate,
                     condstore,
                     oauth2,
+                    sort,
                 },
         } = self.server_conf.protocol
         {
@@ -262,6 +275,15 @@ impl MailBackend for ImapType {
                             };
                         }
                     }
+                    "SORT" | "THREAD=REFERENCES" => {
+                        if sort {
+                            *status = MailBackendExtensionStatus::Enabled { comment: None };
+                        } else {
+                            *status = MailBackendExtensionStatus::Supported {
+                                comment: Some("Disabled by user configuration"),
+                            };
+                        }
+                    }
                     "AUTH=OAUTH2" => {
                         if oauth2 {
                             *status = MailBackendExtensionStatus::Enabled { comment: None };
@@ -288,6 +310,11 @@ impl MailBackend for ImapType {
             is_remote: true,
             supports_search: true,
             extensions: Some(extensions),
+            actions: &[BackendAction {
+                name: "expunge",
+                description: "permanently remove the messages flagged as deleted from MAILBOX",
+                args: &["MAILBOX"],
+            }],
             supports_tags: true,
             supports_submission: false,
         }
@@ -328,6 +355,14 @@ impl MailBackend for ImapType {
             mailbox_hash,
             uid_store: self.uid_store.clone(),
             cache_handle,
+            use_sort: match self.server_conf.protocol {
+                ImapProtocol::IMAP {
+                    extension_use: ImapExtensionUse { sort, .. },
+                } => sort,
+                _ => false,
+            },
+            server_order: VecDeque::new(),
+            thread_parents: HashMap::default(),
         };
 
         /* do this in a closure to prevent recursion limit error in async_stream macro */
@@ -433,7 +468,13 @@ impl MailBackend for ImapType {
     fn is_online(&self) -> ResultFuture<()> {
         let connection = self.connection.clone();
         let timeout_dur = self.server_conf.timeout;
+        let uid_store = self.uid_store.clone();
         Ok(Box::pin(async move {
+            /* Don't retry before the backoff delay of the last failed connection has passed. */
+            let remaining = uid_store.reconnect_backoff.lock().unwrap().remaining();
+            if let Some(remaining) = remaining {
+                sleep(remaining).await;
+            }
             match timeout(timeout_dur, connection.lock()).await {
                 Ok(mut conn) => {
                     debug!("is_online");
@@ -454,6 +495,7 @@ impl MailBackend for ImapType {
         let server_conf = self.server_conf.clone();
         let main_conn = self.connection.clone();
         let uid_store = self.uid_store.clone();
+        let refresh_schedule = self.refresh_schedule.clone();
         Ok(Box::pin(async move {
             let has_idle: bool = match server_conf.protocol {
                 ImapProtocol::IMAP {
@@ -476,39 +518,56 @@ impl MailBackend for ImapType {
                 })
                 .await
             } else {
-                poll_with_examine(ImapWatchKit {
-                    conn: ImapConnection::new_connection(&server_conf, uid_store.clone()),
-                    main_conn: main_conn.clone(),
-                    uid_store: uid_store.clone(),
-                })
+                poll_with_examine(
+                    ImapWatchKit {
+                        conn: ImapConnection::new_connection(&server_conf, uid_store.clone()),
+                        main_conn: main_conn.clone(),
+                        uid_store: uid_store.clone(),
+                    },
+                    refresh_schedule.clone(),
+                )
                 .await
             } {
-                let mut main_conn_lck = timeout(uid_store.timeout, main_conn.lock()).await?;
-                if err.kind.is_network() {
+                if err.kind.is_network() || err.kind.is_timeout() {
                     uid_store.is_online.lock().unwrap().1 = Err(err.clone());
                 } else {
                     return Err(err);
                 }
                 debug!("Watch failure: {}", err.to_string());
-                match timeout(uid_store.timeout, main_conn_lck.connect())
-                    .await
-                    .and_then(|res| res)
-                {
-                    Err(err2) => {
-                        debug!("Watch reconnect attempt failed: {}", err2.to_string());
+                /* Keep reconnecting, waiting longer after every failed attempt. */
+                loop {
+                    let remaining = uid_store.reconnect_backoff.lock().unwrap().remaining();
+                    if let Some(remaining) = remaining {
+                        sleep(remaining).await;
                     }
-                    Ok(()) => {
-                        debug!("Watch reconnect attempt succesful");
-                        continue;
+                    let mut main_conn_lck = timeout(uid_store.timeout, main_conn.lock()).await?;
+                    match timeout(uid_store.timeout, main_conn_lck.connect())
+                        .await
+                        .and_then(|res| res)
+                    {
+                        Ok(()) => {
+                            debug!("Watch reconnect attempt succesful");
+                            break;
+                        }
+                        Err(err2) if err2.kind.is_authentication() => {
+                            let account_hash = uid_store.account_hash;
+                            main_conn_lck.add_refresh_event(RefreshEvent {
+                                account_hash,
+                                mailbox_hash: 0,
+                                kind: RefreshEventKind::Failure(err2.clone()),
+                            });
+                            return Err(err2);
+                        }
+                        Err(err2) => {
+                            debug!("Watch reconnect attempt failed: {}", err2.to_string());
+                            let mut backoff = uid_store.reconnect_backoff.lock().unwrap();
+                            /* A timed out attempt didn't get to schedule the next one. */
+                            if backoff.remaining().is_none() {
+                                backoff.failed();
+                            }
+                        }
                     }
                 }
-                let account_hash = uid_store.account_hash;
-                main_conn_lck.add_refresh_event(RefreshEvent {
-                    account_hash,
-                    mailbox_hash: 0,
-                    kind: RefreshEventKind::Failure(err.clone()),
-                });
-                return Err(err);
             }
             debug!("watch future returning");
             Ok(())
@@ -707,6 +766,39 @@ impl MailBackend for ImapType {
             let mut conn = connection.lock().await;
             conn.select_mailbox(mailbox_hash, &mut response, false)
                 .await?;
+            {
+                let mailboxes_lck = uid_store.mailboxes.lock().await;
+                let select_lck = mailboxes_lck[&mailbox_hash].select.read().unwrap();
+                for (tag, value) in flags
+                    .iter()
+                    .filter_map(|(f, v)| f.as_ref().err().map(|t| (t, v)))
+                {
+                    if !protocol_parser::is_valid_keyword(tag) {
+                        return Err(MeliError::new(format!(
+                            "`{}` is not a valid IMAP keyword: keywords cannot contain spaces or any of (){{%*\"\\] and must not begin with \\.",
+                            tag
+                        )));
+                    }
+                    /* Keywords the server doesn't list in PERMANENTFLAGS would only be stored for
+                     * the current session. */
+                    if let Some(ref select_response) = *select_lck {
+                        if *value
+                            && !select_response.can_create_flags
+                            && !select_response
+                                .permanentflags
+                                .1
+                                .iter()
+                                .any(|k| k.eq_ignore_ascii_case(tag))
+                        {
+                            return Err(MeliError::new(format!(
+                                "Mailbox `{}` does not allow creating new keywords and `{}` is not one of its permanent flags.",
+                                mailboxes_lck[&mailbox_hash].name,
+                                tag
+                            )));
+                        }
+                    }
+                }
+            }
             if flags.iter().any(|(_, b)| *b) {
                 /* Set flags/tags to true */
                 let mut set_seen = false;
@@ -739,7 +831,7 @@ impl MailBackend for ImapType {
                                 cmd.push_str("\\Draft ");
                             }
                             Ok(_) => {
-                                crate::log(format!("Application error: more than one flag bit set in set_flags: {:?}", flags), crate::ERROR);
+                                crate::log_target("imap", format!("Application error: more than one flag bit set in set_flags: {:?}", flags), crate::ERROR);
                                 return Err(MeliError::new(format!("Application error: more than one flag bit set in set_flags: {:?}", flags)).set_kind(crate::ErrorKind::Bug));
                             }
                             Err(tag) => {
@@ -800,7 +892,8 @@ impl MailBackend for ImapType {
                                 cmd.push_str("\\Draft ");
                             }
                             Ok(_) => {
-                                crate::log(
+                                crate::log_target(
+                                    "imap",
                                     format!(
                         "Application error: more than one flag bit set in set_flags: {:?}", flags
                     ),
@@ -860,6 +953,37 @@ impl MailBackend for ImapType {
         }))
     }
 
+    fn perform_action(&mut self, action: &str, args: Vec<String>) -> ResultFuture<String> {
+        if action != "expunge" {
+            return Err(MeliError::new(format!("Unknown action `{}`.", action)));
+        }
+        let path = args
+            .into_iter()
+            .next()
+            .ok_or_else(|| MeliError::new("`expunge` needs a mailbox."))?;
+        let uid_store = self.uid_store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mailbox_hash = uid_store
+                .mailboxes
+                .lock()
+                .await
+                .values()
+                .find(|f| f.path == path)
+                .map(|f| f.hash)
+                .ok_or_else(|| MeliError::new(format!("Mailbox `{}` not found.", &path)))?;
+            let mut response = Vec::with_capacity(8 * 1024);
+            let mut conn = connection.lock().await;
+            conn.select_mailbox(mailbox_hash, &mut response, true)
+                .await?;
+            conn.send_command(b"EXPUNGE").await?;
+            conn.read_response(&mut response, RequiredResponses::empty())
+                .await?;
+            debug!("EXPUNGE response: {}", &String::from_utf8_lossy(&response));
+            Ok(format!("Expunged `{}`.", path))
+        }))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1183,6 +1307,12 @@ impl MailBackend for ImapType {
                         }
                     }
                 }
+                Larger(n) => {
+                    s.push_str(&format!(" LARGER {}", n));
+                }
+                Smaller(n) => {
+                    s.push_str(&format!(" SMALLER {}", n));
+                }
                 And(q1, q2) => {
                     rec(q1, s);
                     s.push_str(" ");
@@ -1240,6 +1370,85 @@ impl MailBackend for ImapType {
             ))
         }))
     }
+
+    fn message_sizes(
+        &self,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<EnvelopeHash, usize>> {
+        let connection = self.connection.clone();
+        let uid_store = self.uid_store.clone();
+        Ok(Box::pin(async move {
+            let mut response = Vec::with_capacity(8 * 1024);
+            let mut conn = connection.lock().await;
+            let select_response = conn
+                .examine_mailbox(mailbox_hash, &mut response, true)
+                .await?;
+            if select_response.map(|r| r.exists == 0).unwrap_or(false) {
+                return Ok(HashMap::default());
+            }
+            conn.send_command(b"UID FETCH 1:* (UID RFC822.SIZE)")
+                .await?;
+            conn.read_response(&mut response, RequiredResponses::FETCH_REQUIRED)
+                .await?;
+            let (_, v, _) = protocol_parser::fetch_responses(&response)?;
+            let uid_index = uid_store.uid_index.lock()?;
+            Ok(v.into_iter()
+                .filter_map(|r| {
+                    let env_hash = uid_index.get(&(mailbox_hash, r.uid?))?;
+                    Some((*env_hash, r.size?))
+                })
+                .collect())
+        }))
+    }
+
+    fn quota(&self) -> ResultFuture<Quota> {
+        let connection = self.connection.clone();
+        let uid_store = self.uid_store.clone();
+        Ok(Box::pin(async move {
+            let mut response = Vec::with_capacity(8 * 1024);
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            if !uid_store
+                .capabilities
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|cap| cap.eq_ignore_ascii_case(b"QUOTA"))
+            {
+                return Err(MeliError::new(format!(
+                    "Server of account {} does not support the QUOTA extension.",
+                    uid_store.account_name
+                )));
+            }
+            conn.send_command(b"GETQUOTAROOT INBOX").await?;
+            conn.read_response(&mut response, RequiredResponses::empty())
+                .await?;
+            /* A mailbox can have more than one quota root; report the one closest to its limit. */
+            response
+                .split_rn()
+                .filter_map(|l| protocol_parser::quota_response(l).ok())
+                .flat_map(|(_, quota)| quota.resources.into_iter())
+                .filter(|(resource, _, _)| resource == "STORAGE")
+                .map(|(_, usage, limit)| Quota { usage, limit })
+                .max_by_key(|quota| quota.percentage())
+                .ok_or_else(|| {
+                    MeliError::new(format!(
+                        "Server of account {} did not report a storage quota.",
+                        uid_store.account_name
+                    ))
+                })
+        }))
+    }
+
+    fn diagnose(&self) -> ResultFuture<Vec<Diagnostic>> {
+        let server_conf = self.server_conf.clone();
+        let account_hash = self.uid_store.account_hash;
+        let account_name = self.uid_store.account_name.clone();
+        let read_only = self.read_only;
+        Ok(Box::pin(async move {
+            Ok(diagnostics::diagnose(server_conf, account_hash, account_name, read_only).await)
+        }))
+    }
 }
 
 impl ImapType {
@@ -1251,38 +1460,35 @@ impl ImapType {
         let server_hostname = get_conf_val!(s["server_hostname"])?;
         let server_username = get_conf_val!(s["server_username"])?;
         let use_oauth2: bool = get_conf_val!(s["use_oauth2"], false)?;
-        let server_password = if !s.extra.contains_key("server_password_command") {
-            if use_oauth2 {
-                return Err(MeliError::new(format!(
-                    "({}) `use_oauth2` use requires `server_password_command` set with a command that returns an OAUTH2 token. Consult documentation for guidance.",
-                    s.name,
-                )));
+        let password_source = if let Some(command) = s.extra.get("server_password_command") {
+            SecretSource::Command(command.to_string())
+        } else if use_oauth2 {
+            return Err(MeliError::new(format!(
+                "({}) `use_oauth2` use requires `server_password_command` set with a command that returns an OAUTH2 token. Consult documentation for guidance.",
+                s.name,
+            )));
+        } else if let Some(service) = s.extra.get("server_password_keyring") {
+            SecretSource::Keyring {
+                service: service.to_string(),
+                username: server_username.to_string(),
             }
-            get_conf_val!(s["server_password"])?.to_string()
         } else {
-            let invocation = get_conf_val!(s["server_password_command"])?;
-            let output = std::process::Command::new("sh")
-                .args(&["-c", invocation])
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped())
-                .output()?;
-            if !output.status.success() {
-                return Err(MeliError::new(format!(
-                    "({}) server_password_command `{}` returned {}: {}",
-                    s.name,
-                    get_conf_val!(s["server_password_command"])?,
-                    output.status,
-                    String::from_utf8_lossy(&output.stderr)
-                )));
-            }
-            std::str::from_utf8(&output.stdout)?.trim_end().to_string()
+            SecretSource::Raw(get_conf_val!(s["server_password"])?.to_string())
         };
+        let password_cache_timeout = if s.extra.contains_key("password_cache_timeout") {
+            Some(Duration::from_secs(get_conf_val!(
+                s["password_cache_timeout"],
+                0_u64
+            )?))
+        } else {
+            None
+        };
+        let server_password = Secret::new(password_source, password_cache_timeout);
         let server_port = get_conf_val!(s["server_port"], 143)?;
         let use_tls = get_conf_val!(s["use_tls"], true)?;
         let use_starttls = use_tls && get_conf_val!(s["use_starttls"], !(server_port == 993))?;
-        let danger_accept_invalid_certs: bool =
-            get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        let tls = TlsSettings::new(s)?;
+        let proxy = Proxy::new(s)?;
         #[cfg(feature = "sqlite3")]
         let keep_offline_cache = get_conf_val!(s["offline_cache"], true)?;
         #[cfg(not(feature = "sqlite3"))]
@@ -1300,6 +1506,10 @@ impl ImapType {
         } else {
             Some(Duration::from_secs(timeout))
         };
+        let connect_timeout = match get_conf_val!(s["connect_timeout"], 0_u64)? {
+            0 => timeout,
+            secs => Some(Duration::from_secs(secs)),
+        };
         let server_conf = ImapServerConf {
             server_hostname: server_hostname.to_string(),
             server_username: server_username.to_string(),
@@ -1307,7 +1517,8 @@ impl ImapType {
             server_port,
             use_tls,
             use_starttls,
-            danger_accept_invalid_certs,
+            tls,
+            proxy,
             protocol: ImapProtocol::IMAP {
                 extension_use: ImapExtensionUse {
                     idle: get_conf_val!(s["use_idle"], true)?,
@@ -1315,9 +1526,11 @@ impl ImapType {
                     #[cfg(feature = "deflate_compression")]
                     deflate: get_conf_val!(s["use_deflate"], true)?,
                     oauth2: use_oauth2,
+                    sort: get_conf_val!(s["use_sort"], true)?,
                 },
             },
             timeout,
+            connect_timeout,
         };
         let account_hash = {
             let mut hasher = DefaultHasher::new();
@@ -1341,6 +1554,8 @@ impl ImapType {
             is_subscribed: Arc::new(IsSubscribedFn(is_subscribed)),
             connection: Arc::new(FutureMutex::new(connection)),
             uid_store,
+            refresh_schedule: RefreshSchedule::new(s, Duration::from_secs(3 * 60)),
+            read_only: s.read_only(),
         }))
     }
 
@@ -1476,6 +1691,16 @@ impl ImapType {
             }
         }
         mailboxes.retain(|_, v| v.hash != 0);
+        if !has_list_status {
+            let selectable = mailboxes
+                .values()
+                .filter(|m| !m.no_select)
+                .cloned()
+                .collect::<Vec<ImapMailbox>>();
+            if let Err(err) = Self::imap_mailbox_counts(&mut conn, &selectable).await {
+                debug!("Could not get mailbox counts: {}", err);
+            }
+        }
         conn.send_command(b"LSUB \"\" \"*\"").await?;
         conn.read_response(&mut res, RequiredResponses::LSUB_REQUIRED)
             .await?;
@@ -1500,10 +1725,98 @@ impl ImapType {
         Ok(mailboxes)
     }
 
+    /// Set the total and unseen counts of `mailboxes` in one round-trip: with a single LIST
+    /// command if the server supports LIST-STATUS, or otherwise with pipelined STATUS commands.
+    pub async fn imap_mailbox_counts(
+        conn: &mut ImapConnection,
+        mailboxes: &[ImapMailbox],
+    ) -> Result<()> {
+        /* Pipeline in batches, so that neither side blocks on a full socket buffer while the
+         * other is still writing. */
+        const STATUS_BATCH: usize = 50;
+        if mailboxes.is_empty() {
+            return Ok(());
+        }
+        let has_list_status: bool = conn
+            .uid_store
+            .capabilities
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|cap| cap.eq_ignore_ascii_case(b"LIST-STATUS"));
+        let mut response = Vec::with_capacity(8 * 1024);
+        let mut statuses = vec![];
+        if has_list_status {
+            conn.send_command(b"LIST \"\" \"*\" RETURN (STATUS (MESSAGES UNSEEN))")
+                .await?;
+            conn.read_response(
+                &mut response,
+                RequiredResponses::LIST_REQUIRED | RequiredResponses::STATUS,
+            )
+            .await?;
+            statuses.extend(
+                response
+                    .split_rn()
+                    .filter_map(|l| protocol_parser::status_response(l).map(|(_, v)| v).ok()),
+            );
+        } else {
+            for batch in mailboxes.chunks(STATUS_BATCH) {
+                for mailbox in batch {
+                    conn.send_command(
+                        format!("STATUS \"{}\" (MESSAGES UNSEEN)", mailbox.imap_path()).as_bytes(),
+                    )
+                    .await?;
+                }
+                /* A mailbox that can't be examined anymore shouldn't fail the whole batch. */
+                conn.read_response(
+                    &mut response,
+                    RequiredResponses::STATUS | RequiredResponses::NO_REQUIRED,
+                )
+                .await?;
+                statuses.extend(
+                    response
+                        .split_rn()
+                        .filter_map(|l| protocol_parser::status_response(l).map(|(_, v)| v).ok()),
+                );
+            }
+        }
+        for status in statuses {
+            if let Some(mailbox) = mailboxes.iter().find(|m| status.mailbox == Some(m.hash())) {
+                if let Some(total) = status.messages {
+                    let mut exists_lck = mailbox.exists.lock().unwrap();
+                    exists_lck.clear();
+                    exists_lck.set_not_yet_seen(total);
+                }
+                if let Some(total) = status.unseen {
+                    let mut unseen_lck = mailbox.unseen.lock().unwrap();
+                    unseen_lck.clear();
+                    unseen_lck.set_not_yet_seen(total);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn validate_config(s: &AccountSettings) -> Result<()> {
         get_conf_val!(s["server_hostname"])?;
         get_conf_val!(s["server_username"])?;
         let use_oauth2: bool = get_conf_val!(s["use_oauth2"], false)?;
+        let password_keys = [
+            "server_password",
+            "server_password_command",
+            "server_password_keyring",
+        ]
+        .iter()
+        .filter(|k| s.extra.contains_key(**k))
+        .cloned()
+        .collect::<Vec<&str>>();
+        if password_keys.len() > 1 {
+            return Err(MeliError::new(format!(
+                "Configuration error ({}): {} are set, cannot choose",
+                s.name.as_str(),
+                password_keys.join(" and "),
+            )));
+        }
         if !s.extra.contains_key("server_password_command") {
             if use_oauth2 {
                 return Err(MeliError::new(format!(
@@ -1511,13 +1824,11 @@ impl ImapType {
                     s.name,
                 )));
             }
-            get_conf_val!(s["server_password"])?;
-        } else if s.extra.contains_key("server_password") {
-            return Err(MeliError::new(format!(
-                "Configuration error ({}): both server_password and server_password_command are set, cannot choose",
-                s.name.as_str(),
-            )));
+            if !s.extra.contains_key("server_password_keyring") {
+                get_conf_val!(s["server_password"])?;
+            }
         }
+        get_conf_val!(s["password_cache_timeout"], 0_u64)?;
         let server_port = get_conf_val!(s["server_port"], 143)?;
         let use_tls = get_conf_val!(s["use_tls"], true)?;
         let use_starttls = get_conf_val!(s["use_starttls"], !(server_port == 993))?;
@@ -1528,6 +1839,8 @@ impl ImapType {
             )));
         }
         get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        TlsSettings::new(s)?;
+        Proxy::new(s)?;
         #[cfg(feature = "sqlite3")]
         get_conf_val!(s["offline_cache"], true)?;
         #[cfg(not(feature = "sqlite3"))]
@@ -1542,6 +1855,7 @@ impl ImapType {
         }
         get_conf_val!(s["use_idle"], true)?;
         get_conf_val!(s["use_condstore"], true)?;
+        get_conf_val!(s["use_sort"], true)?;
         #[cfg(feature = "deflate_compression")]
         get_conf_val!(s["use_deflate"], true)?;
         #[cfg(not(feature = "deflate_compression"))]
@@ -1552,6 +1866,7 @@ impl ImapType {
             )));
         }
         let _timeout = get_conf_val!(s["timeout"], 16_u64)?;
+        get_conf_val!(s["connect_timeout"], 0_u64)?;
         Ok(())
     }
 
@@ -1571,7 +1886,11 @@ enum FetchStage {
     InitialFresh,
     InitialCache,
     ResyncCache,
-    FreshFetch { max_uid: UID },
+    FreshFetch {
+        max_uid: UID,
+    },
+    /// Fetch `FetchState::server_order` from the front.
+    ServerOrderFetch,
     Finished,
 }
 
@@ -1582,6 +1901,68 @@ struct FetchState {
     mailbox_hash: MailboxHash,
     uid_store: Arc<UIDStore>,
     cache_handle: Option<Box<dyn cache::ImapCache>>,
+    /// Whether SORT and THREAD can be used to get the order the messages are fetched in.
+    use_sort: bool,
+    /// UIDs of the messages left to fetch, grouped by conversation, newest first.
+    server_order: VecDeque<Vec<UID>>,
+    /// The parent of each message according to the server's THREAD response.
+    thread_parents: HashMap<UID, UID>,
+}
+
+/// Ask the server for the order to fetch the messages of the mailbox in: the newest conversations
+/// first with THREAD=REFERENCES, otherwise the newest messages first with SORT. Returns `false`
+/// if the server supports neither.
+async fn server_order(state: &mut FetchState) -> Result<bool> {
+    let (thread, sort) = {
+        let capabilities = state.uid_store.capabilities.lock().unwrap();
+        let has = |name: &[u8]| capabilities.iter().any(|c| c.eq_ignore_ascii_case(name));
+        (has(b"THREAD=REFERENCES"), has(b"SORT"))
+    };
+    if !state.use_sort || !(thread || sort) {
+        return Ok(false);
+    }
+    let mut conn = state.connection.lock().await;
+    let mut response = Vec::with_capacity(8 * 1024);
+    conn.examine_mailbox(state.mailbox_hash, &mut response, false)
+        .await?;
+    if thread {
+        conn.send_command(b"UID THREAD REFERENCES UTF-8 ALL")
+            .await?;
+        conn.read_response(
+            &mut response,
+            RequiredResponses::THREAD | RequiredResponses::NO_REQUIRED,
+        )
+        .await?;
+        for l in response.split_rn() {
+            if let Ok((_, threads)) = protocol_parser::thread_results(l) {
+                /* Threads are in ascending order of their first message. */
+                for t in threads.iter().rev() {
+                    let mut uids = vec![];
+                    t.uids(&mut uids);
+                    t.parents(&mut state.thread_parents);
+                    state.server_order.push_back(uids);
+                }
+                return Ok(true);
+            }
+        }
+    } else {
+        conn.send_command(b"UID SORT (REVERSE DATE) UTF-8 ALL")
+            .await?;
+        conn.read_response(
+            &mut response,
+            RequiredResponses::SORT | RequiredResponses::NO_REQUIRED,
+        )
+        .await?;
+        for l in response.split_rn() {
+            if let Ok((_, uids)) = protocol_parser::sort_results(l) {
+                state
+                    .server_order
+                    .extend(uids.into_iter().map(|uid| vec![uid]));
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
 }
 
 async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
@@ -1610,6 +1991,25 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                     state.stage = FetchStage::Finished;
                     return Ok(Vec::new());
                 }
+                match server_order(state).await {
+                    Ok(true) if !state.server_order.is_empty() => {
+                        state.stage = FetchStage::ServerOrderFetch;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        crate::log_target(
+                            "imap",
+                            format!(
+                                "Could not get the order of mailbox {} from the server, fetching by UID: {}",
+                                state.mailbox_hash, err
+                            ),
+                            crate::DEBUG,
+                        );
+                    }
+                }
+                state.server_order.clear();
+                state.thread_parents.clear();
                 state.stage = FetchStage::FreshFetch {
                     max_uid: select_response.uidnext - 1,
                 };
@@ -1618,7 +2018,8 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
             FetchStage::InitialCache => {
                 match cache::fetch_cached_envs(state).await {
                     Err(err) => {
-                        crate::log(
+                        crate::log_target(
+                            "imap",
                             format!(
                                 "IMAP cache error: could not fetch cache for {}. Reason: {}",
                                 state.uid_store.account_name, err
@@ -1628,7 +2029,7 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                         /* Try resetting the database */
                         if let Some(ref mut cache_handle) = state.cache_handle {
                             if let Err(err) = cache_handle.reset() {
-                                crate::log(format!("IMAP cache error: could not reset cache for {}. Reason: {}", state.uid_store.account_name, err), crate::ERROR);
+                                crate::log_target("imap", format!("IMAP cache error: could not reset cache for {}. Reason: {}", state.uid_store.account_name, err), crate::ERROR);
                             }
                         }
                         state.stage = FetchStage::InitialFresh;
@@ -1679,14 +2080,58 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                 state.stage = FetchStage::InitialFresh;
                 continue;
             }
-            FetchStage::FreshFetch { max_uid } => {
+            FetchStage::FreshFetch { .. } | FetchStage::ServerOrderFetch => {
+                let chunk_size = 250;
                 let FetchState {
                     ref mut stage,
                     ref connection,
                     mailbox_hash,
                     ref uid_store,
                     ref mut cache_handle,
+                    use_sort: _,
+                    ref mut server_order,
+                    ref thread_parents,
                 } = state;
+                let (uid_set, next_stage) = match *stage {
+                    FetchStage::FreshFetch { max_uid: 0 } => (None, FetchStage::Finished),
+                    FetchStage::FreshFetch { max_uid: 1 } => {
+                        (Some("1".to_string()), FetchStage::Finished)
+                    }
+                    FetchStage::FreshFetch { max_uid } => (
+                        Some(format!(
+                            "{}:{}",
+                            std::cmp::max(max_uid.saturating_sub(chunk_size), 1),
+                            max_uid
+                        )),
+                        FetchStage::FreshFetch {
+                            max_uid: max_uid.saturating_sub(chunk_size + 1),
+                        },
+                    ),
+                    _ => {
+                        /* Fetch whole conversations, so that the messages the server threaded
+                         * together arrive in the same chunk. */
+                        let mut uids = vec![];
+                        while uids.len() < chunk_size {
+                            match server_order.pop_front() {
+                                Some(group) => uids.extend(group),
+                                None => break,
+                            }
+                        }
+                        let uid_set = uids
+                            .iter()
+                            .map(|uid| uid.to_string())
+                            .collect::<Vec<String>>()
+                            .join(",");
+                        (
+                            if uids.is_empty() { None } else { Some(uid_set) },
+                            if server_order.is_empty() {
+                                FetchStage::Finished
+                            } else {
+                                FetchStage::ServerOrderFetch
+                            },
+                        )
+                    }
+                };
                 let mailbox_hash = *mailbox_hash;
                 let mut our_unseen: BTreeSet<EnvelopeHash> = BTreeSet::default();
                 let (mailbox_path, mailbox_exists, no_select, unseen) = {
@@ -1704,23 +2149,16 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                 }
                 let mut conn = connection.lock().await;
                 let mut response = Vec::with_capacity(8 * 1024);
-                let max_uid_left = max_uid;
-                let chunk_size = 250;
 
                 let mut envelopes = Vec::with_capacity(chunk_size);
                 conn.examine_mailbox(mailbox_hash, &mut response, false)
                     .await?;
-                if max_uid_left > 0 {
-                    debug!("{} max_uid_left= {}", mailbox_hash, max_uid_left);
-                    let command = if max_uid_left == 1 {
-                        "UID FETCH 1 (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)".to_string()
-                    } else {
-                        format!(
-                            "UID FETCH {}:{} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
-                                std::cmp::max(max_uid_left.saturating_sub(chunk_size), 1),
-                            max_uid_left
-                        )
-                    };
+                if let Some(uid_set) = uid_set {
+                    debug!("{} uid_set= {}", mailbox_hash, uid_set);
+                    let command = format!(
+                        "UID FETCH {} (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
+                        uid_set
+                    );
                     debug!("sending {:?}", &command);
                     conn.send_command(command.as_bytes()).await?;
                     conn.read_response(&mut response, RequiredResponses::FETCH_REQUIRED)
@@ -1744,6 +2182,7 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                         ref mut flags,
                         ref raw_fetch_value,
                         ref references,
+                        ref size,
                         ..
                     } in v.iter_mut()
                     {
@@ -1762,6 +2201,9 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                         if let Some(value) = references {
                             env.set_references(value);
                         }
+                        if let Some(size) = size {
+                            env.set_size(*size);
+                        }
                         let mut tag_lck = uid_store.collection.tag_index.write().unwrap();
                         if let Some((flags, keywords)) = flags {
                             env.set_flags(*flags);
@@ -1777,6 +2219,29 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                             }
                         }
                     }
+                    if !thread_parents.is_empty() {
+                        /* Messages without References or In-Reply-To headers that the server
+                         * threaded, e.g. by subject, are linked to their parent here. */
+                        let message_ids: HashMap<UID, MessageID> = v
+                            .iter()
+                            .filter_map(|r| {
+                                Some((r.uid?, r.envelope.as_ref()?.message_id().clone()))
+                            })
+                            .collect();
+                        for r in v.iter_mut() {
+                            if let (Some(uid), Some(env)) = (r.uid, r.envelope.as_mut()) {
+                                if env.in_reply_to().is_some() || !env.references().is_empty() {
+                                    continue;
+                                }
+                                if let Some(parent) =
+                                    thread_parents.get(&uid).and_then(|p| message_ids.get(p))
+                                {
+                                    env.set_in_reply_to(parent.raw());
+                                    env.push_references(parent.clone());
+                                }
+                            }
+                        }
+                    }
                     if let Some(ref mut cache_handle) = cache_handle {
                         if let Err(err) = cache_handle
                             .insert_envelopes(mailbox_hash, &v)
@@ -1838,15 +2303,11 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                         .insert_existing_set(envelopes.iter().map(|env| env.hash()).collect::<_>());
                     drop(conn);
                 }
-                if max_uid_left <= 1 {
+                if next_stage == FetchStage::Finished {
                     unseen.lock().unwrap().set_not_yet_seen(0);
                     mailbox_exists.lock().unwrap().set_not_yet_seen(0);
-                    *stage = FetchStage::Finished;
-                } else {
-                    *stage = FetchStage::FreshFetch {
-                        max_uid: std::cmp::max(max_uid_left.saturating_sub(chunk_size + 1), 1),
-                    };
                 }
+                *stage = next_stage;
                 return Ok(envelopes);
             }
             FetchStage::Finished => {
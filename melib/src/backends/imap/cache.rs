@@ -120,7 +120,7 @@ mod sqlite3_m {
     PRAGMA encoding = 'UTF-8';
 
     CREATE TABLE IF NOT EXISTS envelopes (
-                    hash             INTEGER NOT NULL,
+                    hash             BLOB NOT NULL,
                     mailbox_hash     INTEGER NOT NULL,
                     uid              INTEGER NOT NULL,
                     modsequence      INTEGER,
@@ -140,7 +140,7 @@ mod sqlite3_m {
     CREATE INDEX IF NOT EXISTS envelope_idx ON envelopes(hash);
     CREATE INDEX IF NOT EXISTS mailbox_idx ON mailbox(mailbox_hash);",
         ),
-        version: 2,
+        version: 4,
     };
 
     impl ToSql for ModSequence {
@@ -443,6 +443,7 @@ mod sqlite3_m {
                     message_sequence_number: _,
                     modseq,
                     flags: _,
+                    size: _,
                     body: _,
                     references: _,
                     envelope: Some(envelope),
@@ -452,7 +453,7 @@ mod sqlite3_m {
                     max_uid = std::cmp::max(max_uid, *uid);
                     tx.execute(
                 "INSERT OR REPLACE INTO envelopes (hash, uid, mailbox_hash, modsequence, envelope) VALUES (?1, ?2, ?3, ?4, ?5)",
-                sqlite3::params![envelope.hash() as i64, *uid as Sqlite3UID, mailbox_hash as i64, modseq, &envelope],
+                sqlite3::params![envelope.hash().to_be_bytes().to_vec(), *uid as Sqlite3UID, mailbox_hash as i64, modseq, &envelope],
             ).chain_err_summary(|| format!("Could not insert envelope {} {} in header_cache of account {}", envelope.message_id(), envelope.hash(), uid_store.account_name))?;
                 }
             }
@@ -575,7 +576,7 @@ mod sqlite3_m {
 
                     let x = stmt
                         .query_map(
-                            sqlite3::params![mailbox_hash as i64, env_hash as i64],
+                            sqlite3::params![mailbox_hash as i64, env_hash.to_be_bytes().to_vec()],
                             |row| {
                                 Ok((
                                     row.get(0).map(|u: Sqlite3UID| u as UID)?,
@@ -624,7 +625,7 @@ mod sqlite3_m {
                     )?;
                     let x = stmt
                         .query_map(
-                            sqlite3::params![mailbox_hash as i64, env_hash as i64],
+                            sqlite3::params![mailbox_hash as i64, env_hash.to_be_bytes().to_vec()],
                             |row| Ok(row.get(0)?),
                         )?
                         .collect::<std::result::Result<_, _>>()?;
@@ -647,6 +648,7 @@ pub(super) async fn fetch_cached_envs(state: &mut FetchState) -> Result<Option<V
         mailbox_hash,
         ref uid_store,
         cache_handle: _,
+        ..
     } = state;
     let mailbox_hash = *mailbox_hash;
     if !uid_store.keep_offline_cache {
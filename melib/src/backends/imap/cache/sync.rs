@@ -130,7 +130,7 @@ impl ImapConnection {
         // 2.  tag1 UID FETCH <lastseenuid+1>:* <descriptors>
         self.send_command(
             format!(
-                "UID FETCH {}:* (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
+                "UID FETCH {}:* (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
                 max_uid + 1
             )
             .as_bytes(),
@@ -150,6 +150,7 @@ impl ImapConnection {
             ref mut envelope,
             ref mut flags,
             ref references,
+            ref size,
             ..
         } in v.iter_mut()
         {
@@ -159,6 +160,9 @@ impl ImapConnection {
             if let Some(value) = references {
                 env.set_references(value);
             }
+            if let Some(size) = size {
+                env.set_size(*size);
+            }
             let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
             if let Some((flags, keywords)) = flags {
                 env.set_flags(*flags);
@@ -415,7 +419,7 @@ impl ImapConnection {
             // 2.  tag1 UID FETCH <lastseenuid+1>:* <descriptors>
             self.send_command(
                 format!(
-                    "UID FETCH {}:* (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE) (CHANGEDSINCE {})",
+                    "UID FETCH {}:* (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE) (CHANGEDSINCE {})",
                     cached_max_uid + 1,
                     cached_highestmodseq,
                 )
@@ -436,6 +440,7 @@ impl ImapConnection {
                 ref mut envelope,
                 ref mut flags,
                 ref references,
+                ref size,
                 ..
             } in v.iter_mut()
             {
@@ -445,6 +450,9 @@ impl ImapConnection {
                 if let Some(value) = references {
                     env.set_references(value);
                 }
+                if let Some(size) = size {
+                    env.set_size(*size);
+                }
                 let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
                 if let Some((flags, keywords)) = flags {
                     env.set_flags(*flags);
@@ -21,12 +21,13 @@
 
 use super::protocol_parser::{ImapLineSplit, ImapResponse, RequiredResponses, SelectResponse};
 use crate::backends::{MailboxHash, RefreshEvent};
-use crate::connections::{lookup_ipv4, timeout, Connection};
+use crate::connections::{
+    certificates::UntrustedCertificate, happy_eyeballs_connect, timeout, Connection,
+};
 use crate::email::parser::BytesExt;
 use crate::error::*;
 extern crate native_tls;
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 pub use smol::Async as AsyncWrapper;
 use std::collections::HashSet;
 use std::convert::TryFrom;
@@ -64,6 +65,8 @@ pub struct ImapExtensionUse {
     #[cfg(feature = "deflate_compression")]
     pub deflate: bool,
     pub oauth2: bool,
+    /// Use SORT and THREAD=REFERENCES to fetch the newest conversations first.
+    pub sort: bool,
 }
 
 impl Default for ImapExtensionUse {
@@ -74,6 +77,7 @@ impl Default for ImapExtensionUse {
             #[cfg(feature = "deflate_compression")]
             deflate: true,
             oauth2: false,
+            sort: true,
         }
     }
 }
@@ -113,172 +117,134 @@ pub struct ImapConnection {
 }
 
 impl ImapStream {
-    pub async fn new_connection(
+    /// Open the TCP connection to the server, and issue STARTTLS if it's configured.
+    pub(super) async fn open_socket(
         server_conf: &ImapServerConf,
-    ) -> Result<(Capabilities, ImapStream)> {
-        use std::net::TcpStream;
+    ) -> Result<AsyncWrapper<Connection>> {
         let path = &server_conf.server_hostname;
+        let stream = if let Some(ref proxy) = server_conf.proxy {
+            proxy
+                .connect(path, server_conf.server_port, server_conf.connect_timeout)
+                .await?
+        } else {
+            happy_eyeballs_connect(path, server_conf.server_port, server_conf.connect_timeout)
+                .await?
+        };
 
-        let cmd_id = 1;
-        let stream = if server_conf.use_tls {
-            let mut connector = TlsConnector::builder();
-            if server_conf.danger_accept_invalid_certs {
-                connector.danger_accept_invalid_certs(true);
-            }
-            let connector = connector
-                .build()
-                .chain_err_kind(crate::error::ErrorKind::Network)?;
-
-            let addr = if let Ok(a) = lookup_ipv4(path, server_conf.server_port) {
-                a
-            } else {
-                return Err(MeliError::new(format!(
-                    "Could not lookup address {}",
-                    &path
-                )));
-            };
-
-            let mut socket = AsyncWrapper::new(Connection::Tcp(
-                if let Some(timeout) = server_conf.timeout {
-                    TcpStream::connect_timeout(&addr, timeout)
-                        .chain_err_kind(crate::error::ErrorKind::Network)?
-                } else {
-                    TcpStream::connect(&addr).chain_err_kind(crate::error::ErrorKind::Network)?
-                },
-            ))
+        let mut socket = AsyncWrapper::new(Connection::Tcp(stream))
             .chain_err_kind(crate::error::ErrorKind::Network)?;
-            if server_conf.use_starttls {
-                let err_fn = || {
-                    if server_conf.server_port == 993 {
-                        "STARTTLS failed. Server port is set to 993, which normally uses TLS. Maybe try disabling use_starttls."
-                    } else {
-                        "STARTTLS failed. Is the connection already encrypted?"
-                    }
-                };
-                let mut buf = vec![0; Connection::IO_BUF_SIZE];
-                match server_conf.protocol {
-                    ImapProtocol::IMAP { .. } => socket
-                        .write_all(format!("M{} STARTTLS\r\n", cmd_id).as_bytes())
-                        .await
-                        .chain_err_summary(err_fn)
-                        .chain_err_kind(crate::error::ErrorKind::Network)?,
-                    ImapProtocol::ManageSieve => {
-                        socket
-                            .read(&mut buf)
-                            .await
-                            .chain_err_summary(err_fn)
-                            .chain_err_kind(crate::error::ErrorKind::Network)?;
-                        socket
-                            .write_all(b"STARTTLS\r\n")
-                            .await
-                            .chain_err_summary(err_fn)
-                            .chain_err_kind(crate::error::ErrorKind::Network)?;
-                    }
+        if server_conf.use_tls && server_conf.use_starttls {
+            let err_fn = || {
+                if server_conf.server_port == 993 {
+                    "STARTTLS failed. Server port is set to 993, which normally uses TLS. Maybe try disabling use_starttls."
+                } else {
+                    "STARTTLS failed. Is the connection already encrypted?"
                 }
-                socket
-                    .flush()
+            };
+            let mut buf = vec![0; Connection::IO_BUF_SIZE];
+            match server_conf.protocol {
+                ImapProtocol::IMAP { .. } => socket
+                    .write_all(b"M1 STARTTLS\r\n")
                     .await
                     .chain_err_summary(err_fn)
-                    .chain_err_kind(crate::error::ErrorKind::Network)?;
-                let mut response = Vec::with_capacity(1024);
-                let mut broken = false;
-                let now = Instant::now();
-
-                while now.elapsed().as_secs() < 3 {
-                    let len = socket
+                    .chain_err_kind(crate::error::ErrorKind::Network)?,
+                ImapProtocol::ManageSieve => {
+                    socket
                         .read(&mut buf)
                         .await
                         .chain_err_summary(err_fn)
                         .chain_err_kind(crate::error::ErrorKind::Network)?;
-                    response.extend_from_slice(&buf[0..len]);
-                    match server_conf.protocol {
-                        ImapProtocol::IMAP { .. } => {
-                            if response.starts_with(b"* OK ") && response.find(b"\r\n").is_some() {
-                                if let Some(pos) = response.find(b"\r\n") {
-                                    response.drain(0..pos + 2);
-                                }
-                            }
-                        }
-                        ImapProtocol::ManageSieve => {
-                            if response.starts_with(b"OK ") && response.find(b"\r\n").is_some() {
-                                response.clear();
-                                broken = true;
-                                break;
-                            }
-                        }
-                    }
-                    if response.starts_with(b"M1 OK") {
-                        broken = true;
-                        break;
-                    }
-                }
-                if !broken {
-                    return Err(MeliError::new(format!(
-                        "Could not initiate STARTTLS negotiation to {}.",
-                        path
-                    )));
+                    socket
+                        .write_all(b"STARTTLS\r\n")
+                        .await
+                        .chain_err_summary(err_fn)
+                        .chain_err_kind(crate::error::ErrorKind::Network)?;
                 }
             }
+            socket
+                .flush()
+                .await
+                .chain_err_summary(err_fn)
+                .chain_err_kind(crate::error::ErrorKind::Network)?;
+            let mut response = Vec::with_capacity(1024);
+            let mut broken = false;
+            let now = Instant::now();
 
-            {
-                // FIXME: This is blocking
-                let socket = socket
-                    .into_inner()
+            while now.elapsed().as_secs() < 3 {
+                let len = socket
+                    .read(&mut buf)
+                    .await
+                    .chain_err_summary(err_fn)
                     .chain_err_kind(crate::error::ErrorKind::Network)?;
-                let mut conn_result = connector.connect(path, socket);
-                if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
-                    conn_result
-                {
-                    let mut midhandshake_stream = Some(midhandshake_stream);
-                    loop {
-                        match midhandshake_stream.take().unwrap().handshake() {
-                            Ok(r) => {
-                                conn_result = Ok(r);
-                                break;
-                            }
-                            Err(native_tls::HandshakeError::WouldBlock(stream)) => {
-                                midhandshake_stream = Some(stream);
-                            }
-                            p => {
-                                p.chain_err_kind(crate::error::ErrorKind::Network)?;
+                response.extend_from_slice(&buf[0..len]);
+                match server_conf.protocol {
+                    ImapProtocol::IMAP { .. } => {
+                        if response.starts_with(b"* OK ") && response.find(b"\r\n").is_some() {
+                            if let Some(pos) = response.find(b"\r\n") {
+                                response.drain(0..pos + 2);
                             }
                         }
                     }
+                    ImapProtocol::ManageSieve => {
+                        if response.starts_with(b"OK ") && response.find(b"\r\n").is_some() {
+                            response.clear();
+                            broken = true;
+                            break;
+                        }
+                    }
+                }
+                if response.starts_with(b"M1 OK") {
+                    broken = true;
+                    break;
                 }
-                AsyncWrapper::new(Connection::Tls(
-                    conn_result
-                        .chain_err_summary(|| {
-                            format!("Could not initiate TLS negotiation to {}.", path)
-                        })
-                        .chain_err_kind(crate::error::ErrorKind::Network)?,
-                ))
-                .chain_err_summary(|| format!("Could not initiate TLS negotiation to {}.", path))
-                .chain_err_kind(crate::error::ErrorKind::Network)?
             }
-        } else {
-            let addr = if let Ok(a) = lookup_ipv4(path, server_conf.server_port) {
-                a
-            } else {
+            if !broken {
                 return Err(MeliError::new(format!(
-                    "Could not lookup address {}",
-                    &path
+                    "Could not initiate STARTTLS negotiation to {}.",
+                    path
                 )));
+            }
+        }
+        Ok(socket)
+    }
+
+    pub async fn new_connection(
+        server_conf: &ImapServerConf,
+    ) -> Result<(Capabilities, ImapStream)> {
+        let path = &server_conf.server_hostname;
+
+        let cmd_id = 1;
+        let stream = if server_conf.use_tls {
+            let socket = Self::open_socket(server_conf)
+                .await?
+                .into_inner()
+                .chain_err_kind(crate::error::ErrorKind::Network)?;
+            let reconnect = async {
+                Self::open_socket(server_conf)
+                    .await?
+                    .into_inner()
+                    .chain_err_kind(crate::error::ErrorKind::Network)
             };
-            AsyncWrapper::new(Connection::Tcp(
-                if let Some(timeout) = server_conf.timeout {
-                    TcpStream::connect_timeout(&addr, timeout)
-                        .chain_err_kind(crate::error::ErrorKind::Network)?
-                } else {
-                    TcpStream::connect(&addr).chain_err_kind(crate::error::ErrorKind::Network)?
-                },
+            AsyncWrapper::new(Connection::Tls(
+                server_conf
+                    .tls
+                    .connect(path, server_conf.server_port, socket, reconnect)
+                    .await
+                    .chain_err_summary(|| {
+                        format!("Could not initiate TLS negotiation to {}.", path)
+                    })?,
             ))
+            .chain_err_summary(|| format!("Could not initiate TLS negotiation to {}.", path))
             .chain_err_kind(crate::error::ErrorKind::Network)?
+        } else {
+            Self::open_socket(server_conf).await?
         };
         if let Err(err) = stream
             .get_ref()
             .set_keepalive(Some(Duration::new(60 * 9, 0)))
         {
-            crate::log(
+            crate::log_target(
+                "imap",
                 format!("Could not set TCP keepalive in IMAP connection: {}", err),
                 crate::LoggingLevel::WARN,
             );
@@ -291,6 +257,12 @@ impl ImapStream {
             current_mailbox: MailboxSelection::None,
             timeout: server_conf.timeout,
         };
+        let server_password = {
+            let secret = server_conf.server_password.clone();
+            smol::unblock(move || secret.get())
+                .await
+                .chain_err_kind(crate::error::ErrorKind::Authentication)?
+        };
         if let ImapProtocol::ManageSieve = server_conf.protocol {
             use data_encoding::BASE64;
             ret.read_response(&mut res).await?;
@@ -298,11 +270,8 @@ impl ImapStream {
                 format!(
                     "AUTHENTICATE \"PLAIN\" \"{}\"",
                     BASE64.encode(
-                        format!(
-                            "\0{}\0{}",
-                            &server_conf.server_username, &server_conf.server_password
-                        )
-                        .as_bytes()
+                        format!("\0{}\0{}", &server_conf.server_username, &server_password)
+                            .as_bytes()
                     )
                 )
                 .as_bytes(),
@@ -368,16 +337,14 @@ impl ImapStream {
                                     String::from_utf8_lossy(capability).to_string()).collect::<Vec<String>>().join(" ")
                     )));
                 }
-                ret.send_command(
-                    format!("AUTHENTICATE XOAUTH2 {}", &server_conf.server_password).as_bytes(),
-                )
-                .await?;
+                ret.send_command(format!("AUTHENTICATE XOAUTH2 {}", &server_password).as_bytes())
+                    .await?;
             }
             _ => {
                 ret.send_command(
                     format!(
                         "LOGIN \"{}\" \"{}\"",
-                        &server_conf.server_username, &server_conf.server_password
+                        &server_conf.server_username, &server_password
                     )
                     .as_bytes(),
                 )
@@ -401,6 +368,8 @@ impl ImapStream {
 
                 if l.starts_with(tag_start.as_bytes()) {
                     if !l[tag_start.len()..].trim().starts_with(b"OK ") {
+                        /* Ask for the password again next time. */
+                        server_conf.server_password.clear();
                         return Err(MeliError::new(format!(
                             "Could not connect. Server replied with '{}'",
                             String::from_utf8_lossy(l[tag_start.len()..].trim())
@@ -612,9 +581,61 @@ impl ImapConnection {
                     return Ok(());
                 }
             }
+            let backoff_remaining = self.uid_store.reconnect_backoff.lock().unwrap().remaining();
+            if backoff_remaining.is_some() {
+                /* Fail right away with the last attempt's error, which says when the next attempt
+                 * is due, instead of keeping jobs waiting on reconnection attempts. */
+                if let Err(ref err) = self.uid_store.is_online.lock().unwrap().1 {
+                    return Err(err.clone());
+                }
+            }
             let new_stream = ImapStream::new_connection(&self.server_conf).await;
+            let new_stream = match new_stream {
+                Err(err) if !err.kind.is_authentication() => {
+                    let mut backoff = self.uid_store.reconnect_backoff.lock().unwrap();
+                    let delay = backoff.failed();
+                    let err = err.set_summary(format!(
+                        "Connection attempt {} failed, retrying in {} seconds",
+                        backoff.attempts(),
+                        delay.as_secs()
+                    ));
+                    (self.uid_store.event_consumer)(
+                        self.uid_store.account_hash,
+                        crate::backends::BackendEvent::ConnectionStatus(Err(err.clone())),
+                    );
+                    Err(err)
+                }
+                Ok(new_stream) => {
+                    let mut backoff = self.uid_store.reconnect_backoff.lock().unwrap();
+                    if backoff.attempts() > 0 {
+                        (self.uid_store.event_consumer)(
+                            self.uid_store.account_hash,
+                            crate::backends::BackendEvent::ConnectionStatus(Ok(())),
+                        );
+                    }
+                    backoff.succeeded();
+                    Ok(new_stream)
+                }
+                err => err,
+            };
             if let Err(err) = new_stream.as_ref() {
-                self.uid_store.is_online.lock().unwrap().1 = Err(err.clone());
+                let mut is_online = self.uid_store.is_online.lock().unwrap();
+                if let Some(cert) = UntrustedCertificate::from_error(err) {
+                    /* Only ask once, not on every reconnection attempt */
+                    if is_online
+                        .1
+                        .as_ref()
+                        .err()
+                        .and_then(UntrustedCertificate::from_error)
+                        != Some(cert)
+                    {
+                        (self.uid_store.event_consumer)(
+                            self.uid_store.account_hash,
+                            crate::backends::BackendEvent::UntrustedCertificate(cert.clone()),
+                        );
+                    }
+                }
+                is_online.1 = Err(err.clone());
             } else {
                 *self.uid_store.is_online.lock().unwrap() = (SystemTime::now(), Ok(()));
             }
@@ -629,6 +650,7 @@ impl ImapConnection {
                             deflate,
                             idle: _idle,
                             oauth2: _,
+                            sort: _,
                         },
                 } => {
                     if capabilities.contains(&b"CONDSTORE"[..]) && condstore {
@@ -664,7 +686,7 @@ impl ImapConnection {
                             | ImapResponse::Bad(code)
                             | ImapResponse::Preauth(code)
                             | ImapResponse::Bye(code) => {
-                                crate::log(format!("Could not use COMPRESS=DEFLATE in account `{}`: server replied with `{}`", self.uid_store.account_name, code), crate::LoggingLevel::WARN);
+                                crate::log_target("imap", format!("Could not use COMPRESS=DEFLATE in account `{}`: server replied with `{}`", self.uid_store.account_name, code), crate::LoggingLevel::WARN);
                             }
                             ImapResponse::Ok(_) => {
                                 let ImapStream {
@@ -905,6 +927,18 @@ impl ImapConnection {
                 .and_modify(|entry| {
                     *entry.select.write().unwrap() = Some(select_response.clone());
                 });
+            /* Make the mailbox's keywords known as tags even before any message using them is
+             * fetched. */
+            let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
+            for keyword in select_response.flags.1.iter() {
+                if !protocol_parser::is_valid_keyword(keyword) {
+                    continue;
+                }
+                let hash = tag_hash!(keyword);
+                if !tag_lck.contains_key(&hash) {
+                    tag_lck.insert(hash, keyword.to_string());
+                }
+            }
         }
         {
             let mut permissions = permissions.lock().unwrap();
@@ -0,0 +1,360 @@
+/*
+ * meli - imap module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Step by step connection checks for the `doctor` command.
+
+use super::*;
+use crate::backends::{Diagnostic, DiagnosticStatus, SpecialUsageMailbox};
+use crate::connections::{certificates, Connection};
+use std::net::ToSocketAddrs;
+use std::time::Instant;
+
+const TEST_MESSAGE: &[u8] = b"From: meli <meli@localhost>\r\nSubject: meli connection test\r\n\r\nThis message was appended by the doctor command and should have been removed.\r\n";
+
+/// Run every check in order, skipping the rest once one of the steps a connection depends on
+/// fails. The checks use their own connection, so they don't disturb the account's. Nothing is
+/// appended to the mailboxes of a `read_only` account.
+pub async fn diagnose(
+    server_conf: ImapServerConf,
+    account_hash: AccountHash,
+    account_name: Arc<String>,
+    read_only: bool,
+) -> Vec<Diagnostic> {
+    let mut ret = vec![];
+    ret.push(resolve(&server_conf).await);
+    if ret[0].status == DiagnosticStatus::Failed {
+        skip(
+            &mut ret,
+            &["Connection", "TLS", "Authentication", "Mailboxes", "Append"],
+        );
+        return ret;
+    }
+    ret.push(connection(&server_conf).await);
+    ret.push(tls_handshake(&server_conf).await);
+    if ret.iter().any(|d| d.status == DiagnosticStatus::Failed) {
+        skip(&mut ret, &["Authentication", "Mailboxes", "Append"]);
+        return ret;
+    }
+    let uid_store = Arc::new(UIDStore::new(
+        account_hash,
+        account_name,
+        BackendEventConsumer::new(Arc::new(|_, _| {})),
+        server_conf.timeout,
+    ));
+    let connection = Arc::new(FutureMutex::new(ImapConnection::new_connection(
+        &server_conf,
+        uid_store.clone(),
+    )));
+    let now = Instant::now();
+    if let Err(err) = connection.lock().await.connect().await {
+        ret.push(Diagnostic::new(
+            "Authentication",
+            DiagnosticStatus::Failed,
+            err.to_string(),
+        ));
+        skip(&mut ret, &["Mailboxes", "Append"]);
+        return ret;
+    }
+    let mut capabilities = uid_store
+        .capabilities
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .collect::<Vec<String>>();
+    capabilities.sort();
+    ret.push(Diagnostic::new(
+        "Authentication",
+        DiagnosticStatus::Ok,
+        format!(
+            "Logged in as {} in {} ms. Server capabilities: {}",
+            server_conf.server_username,
+            now.elapsed().as_millis(),
+            capabilities.join(" ")
+        ),
+    ));
+    let mailboxes = match ImapType::imap_mailboxes(&connection).await {
+        Ok(mailboxes) => {
+            ret.push(Diagnostic::new(
+                "Mailboxes",
+                DiagnosticStatus::Ok,
+                format!("Listed {} mailboxes.", mailboxes.len()),
+            ));
+            mailboxes
+        }
+        Err(err) => {
+            ret.push(Diagnostic::new(
+                "Mailboxes",
+                DiagnosticStatus::Failed,
+                err.to_string(),
+            ));
+            skip(&mut ret, &["Append"]);
+            return ret;
+        }
+    };
+    let drafts = mailboxes
+        .values()
+        .find(|m| m.special_usage() == SpecialUsageMailbox::Drafts)
+        .or_else(|| {
+            mailboxes
+                .values()
+                .find(|m| m.name().eq_ignore_ascii_case("drafts"))
+        })
+        .map(|m| m.imap_path().to_string());
+    ret.push(match drafts {
+        _ if read_only => Diagnostic::new(
+            "Append",
+            DiagnosticStatus::Skipped,
+            "The account is read-only.",
+        ),
+        Some(path) => append(&mut *connection.lock().await, &path).await,
+        None => Diagnostic::new(
+            "Append",
+            DiagnosticStatus::Skipped,
+            "No Drafts mailbox found.",
+        ),
+    });
+    ret
+}
+
+fn skip(ret: &mut Vec<Diagnostic>, names: &[&'static str]) {
+    for name in names {
+        ret.push(Diagnostic::new(
+            *name,
+            DiagnosticStatus::Skipped,
+            "Skipped because of the previous failure.",
+        ));
+    }
+}
+
+async fn resolve(server_conf: &ImapServerConf) -> Diagnostic {
+    if let Some(ref proxy) = server_conf.proxy {
+        return Diagnostic::new(
+            "DNS",
+            DiagnosticStatus::Skipped,
+            format!("The server's address is resolved by proxy {}.", proxy.url()),
+        );
+    }
+    let host = server_conf.server_hostname.clone();
+    let port = server_conf.server_port;
+    let now = Instant::now();
+    match smol::unblock(move || (host.as_str(), port).to_socket_addrs()).await {
+        Ok(addrs) => {
+            let addrs = addrs.map(|a| a.ip().to_string()).collect::<Vec<String>>();
+            Diagnostic::new(
+                "DNS",
+                if addrs.is_empty() {
+                    DiagnosticStatus::Failed
+                } else {
+                    DiagnosticStatus::Ok
+                },
+                format!(
+                    "{} resolved to {} in {} ms.",
+                    server_conf.server_hostname,
+                    addrs.join(", "),
+                    now.elapsed().as_millis()
+                ),
+            )
+        }
+        Err(err) => Diagnostic::new(
+            "DNS",
+            DiagnosticStatus::Failed,
+            format!("Could not resolve {}: {}", server_conf.server_hostname, err),
+        ),
+    }
+}
+
+async fn connection(server_conf: &ImapServerConf) -> Diagnostic {
+    let now = Instant::now();
+    match ImapStream::open_socket(server_conf).await {
+        Ok(socket) => {
+            let peer = match socket.get_ref() {
+                Connection::Tcp(ref stream) => stream
+                    .peer_addr()
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|_| server_conf.server_hostname.clone()),
+                _ => server_conf.server_hostname.clone(),
+            };
+            Diagnostic::new(
+                "Connection",
+                DiagnosticStatus::Ok,
+                format!(
+                    "Connected to {}{}{} in {} ms.",
+                    peer,
+                    if server_conf.proxy.is_some() {
+                        " (proxy)"
+                    } else {
+                        ""
+                    },
+                    if server_conf.use_tls && server_conf.use_starttls {
+                        " and issued STARTTLS"
+                    } else {
+                        ""
+                    },
+                    now.elapsed().as_millis()
+                ),
+            )
+        }
+        Err(err) => Diagnostic::new("Connection", DiagnosticStatus::Failed, err.to_string()),
+    }
+}
+
+async fn tls_handshake(server_conf: &ImapServerConf) -> Diagnostic {
+    if !server_conf.use_tls {
+        return Diagnostic::new(
+            "TLS",
+            DiagnosticStatus::Warning,
+            "TLS is disabled; the password and all messages are sent unencrypted.",
+        );
+    }
+    let open = || async move {
+        ImapStream::open_socket(server_conf)
+            .await?
+            .into_inner()
+            .chain_err_kind(crate::error::ErrorKind::Network)
+    };
+    let socket = match open().await {
+        Ok(socket) => socket,
+        Err(err) => return Diagnostic::new("TLS", DiagnosticStatus::Failed, err.to_string()),
+    };
+    let now = Instant::now();
+    let stream = match server_conf
+        .tls
+        .connect(
+            &server_conf.server_hostname,
+            server_conf.server_port,
+            socket,
+            open(),
+        )
+        .await
+    {
+        Ok(stream) => stream,
+        Err(err) => {
+            return Diagnostic::new(
+                "TLS",
+                DiagnosticStatus::Failed,
+                match certificates::UntrustedCertificate::from_error(&err) {
+                    Some(untrusted) => format!("{} ({})", err, untrusted),
+                    None => err.to_string(),
+                },
+            );
+        }
+    };
+    let elapsed = now.elapsed().as_millis();
+    let certificate = match stream.peer_certificate().and_then(|c| match c {
+        Some(c) => c.to_der().map(Some),
+        None => Ok(None),
+    }) {
+        Ok(Some(der)) => der,
+        _ => {
+            return Diagnostic::new(
+                "TLS",
+                DiagnosticStatus::Warning,
+                "Handshake succeeded but the server did not present a certificate.",
+            );
+        }
+    };
+    let fingerprint = certificates::fingerprint(&certificate);
+    Diagnostic::new(
+        "TLS",
+        if server_conf.tls.danger_accept_invalid_certs {
+            DiagnosticStatus::Warning
+        } else {
+            DiagnosticStatus::Ok
+        },
+        format!(
+            "Handshake completed in {} ms{}. Certificate {}, SHA-256 fingerprint {}",
+            elapsed,
+            if server_conf.tls.danger_accept_invalid_certs {
+                " without validating the certificate (danger_accept_invalid_certs is set)"
+            } else {
+                ""
+            },
+            certificates::certificate_details(&certificate)
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| "could not be parsed".to_string()),
+            certificates::format_fingerprint(&fingerprint)
+        ),
+    )
+}
+
+/// Append a message flagged `\Deleted` to `path` and remove it again.
+async fn append(conn: &mut ImapConnection, path: &str) -> Diagnostic {
+    let mut response = Vec::with_capacity(8 * 1024);
+    let res: Result<Option<String>> = async {
+        conn.send_command(
+            format!(
+                "APPEND \"{}\" (\\Seen \\Deleted) {{{}}}",
+                path,
+                TEST_MESSAGE.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+        conn.wait_for_continuation_request().await?;
+        conn.send_literal(TEST_MESSAGE).await?;
+        conn.read_response(&mut response, RequiredResponses::empty())
+            .await?;
+        /* `[APPENDUID <uidvalidity> <uid>]` is only returned by servers with UIDPLUS */
+        let uid = response
+            .find(b"[APPENDUID ")
+            .and_then(|pos| {
+                String::from_utf8_lossy(&response[pos + b"[APPENDUID ".len()..])
+                    .split(|c: char| c == ' ' || c == ']')
+                    .nth(1)
+                    .map(str::to_string)
+            })
+            .filter(|uid| uid.parse::<UID>().is_ok());
+        let uid = match uid {
+            Some(uid) => uid,
+            None => return Ok(None),
+        };
+        conn.send_command(format!("SELECT \"{}\"", path).as_bytes())
+            .await?;
+        conn.read_response(&mut response, RequiredResponses::SELECT_REQUIRED)
+            .await?;
+        conn.send_command(format!("UID EXPUNGE {}", uid).as_bytes())
+            .await?;
+        conn.read_response(&mut response, RequiredResponses::empty())
+            .await?;
+        Ok(Some(uid))
+    }
+    .await;
+    match res {
+        Ok(Some(uid)) => Diagnostic::new(
+            "Append",
+            DiagnosticStatus::Ok,
+            format!(
+                "Appended a test message to {} with UID {} and removed it.",
+                path, uid
+            ),
+        ),
+        Ok(None) => Diagnostic::new(
+            "Append",
+            DiagnosticStatus::Warning,
+            format!(
+                "Appended a test message to {}, but the server did not report its UID so it could not be removed. It is flagged as deleted.",
+                path
+            ),
+        ),
+        Err(err) => Diagnostic::new("Append", DiagnosticStatus::Failed, err.to_string()),
+    }
+}
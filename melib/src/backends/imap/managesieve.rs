@@ -95,7 +95,8 @@ pub fn new_managesieve_connection(
     let server_username = get_conf_val!(s["server_username"])?;
     let server_password = get_conf_val!(s["server_password"])?;
     let server_port = get_conf_val!(s["server_port"], 4190)?;
-    let danger_accept_invalid_certs: bool = get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+    let tls = crate::connections::tls::TlsSettings::new(s)?;
+    let proxy = crate::connections::proxy::Proxy::new(s)?;
     let timeout = get_conf_val!(s["timeout"], 16_u64)?;
     let timeout = if timeout == 0 {
         None
@@ -105,13 +106,18 @@ pub fn new_managesieve_connection(
     let server_conf = ImapServerConf {
         server_hostname: server_hostname.to_string(),
         server_username: server_username.to_string(),
-        server_password: server_password.to_string(),
+        server_password: crate::secrets::Secret::new(
+            crate::secrets::SecretSource::Raw(server_password.to_string()),
+            None,
+        ),
         server_port,
         use_starttls: true,
         use_tls: true,
-        danger_accept_invalid_certs,
+        tls,
+        proxy,
         protocol: ImapProtocol::ManageSieve,
         timeout,
+        connect_timeout: timeout,
     };
     let uid_store = Arc::new(UIDStore {
         is_online: Arc::new(Mutex::new((
@@ -58,12 +58,18 @@ impl BackendOp for ImapOp {
         let uid = self.uid;
         let uid_store = self.uid_store.clone();
         Ok(Box::pin(async move {
-            let exists_in_cache = {
-                let mut bytes_cache = uid_store.byte_cache.lock()?;
-                let cache = bytes_cache.entry(uid).or_default();
-                cache.bytes.is_some()
-            };
-            if !exists_in_cache {
+            let env_hash = uid_store
+                .uid_index
+                .lock()
+                .unwrap()
+                .get(&(mailbox_hash, uid))
+                .cloned();
+            if let Some(bytes) =
+                env_hash.and_then(|env_hash| ByteCache::get(uid_store.account_hash, env_hash))
+            {
+                return Ok(bytes);
+            }
+            {
                 let mut response = Vec::with_capacity(8 * 1024);
                 {
                     let mut conn = timeout(uid_store.timeout, connection.lock()).await?;
@@ -94,21 +100,25 @@ impl BackendOp for ImapOp {
                     body,
                     ..
                 } = results.pop().unwrap();
-                let _uid = _uid.unwrap();
-                assert_eq!(_uid, uid);
-                assert!(body.is_some());
-                let mut bytes_cache = uid_store.byte_cache.lock()?;
-                let cache = bytes_cache.entry(uid).or_default();
+                if _uid != Some(uid) || body.is_none() {
+                    return Err(MeliError::new(format!(
+                        "Invalid/unexpected response: {:?}",
+                        response
+                    ))
+                    .set_summary(format!("server didn't send message with UID {}", uid)));
+                }
                 if let Some((_flags, _)) = _flags {
+                    let mut bytes_cache = uid_store.byte_cache.lock()?;
+                    let cache = bytes_cache.entry(uid).or_default();
                     //flags.lock().await.set(Some(_flags));
                     cache.flags = Some(_flags);
                 }
-                cache.bytes = Some(body.unwrap().to_vec());
+                let ret = body.unwrap().to_vec();
+                if let Some(env_hash) = env_hash {
+                    ByteCache::insert(uid_store.account_hash, env_hash, ret.clone());
+                }
+                Ok(ret)
             }
-            let mut bytes_cache = uid_store.byte_cache.lock()?;
-            let cache = bytes_cache.entry(uid).or_default();
-            let ret = cache.bytes.clone().unwrap();
-            Ok(ret)
         }))
     }
 
@@ -33,7 +33,7 @@ use nom::{
     character::complete::digit1,
     character::is_digit,
     combinator::{map, map_res, opt},
-    multi::{fold_many1, length_data, many0, many1, separated_nonempty_list},
+    multi::{fold_many1, length_data, many0, many1, separated_list, separated_nonempty_list},
     sequence::{delimited, preceded},
 };
 use std::convert::TryFrom;
@@ -58,6 +58,8 @@ bitflags! {
         const SEARCH              = 0b0010_0000_0000_0000;
         const FETCH               = 0b0100_0000_0000_0000;
         const NO_REQUIRED         = 0b1000_0000_0000_0000;
+        const SORT                = 0b0001_0000_0000_0000_0000;
+        const THREAD              = 0b0010_0000_0000_0000_0000;
         const CAPABILITY_REQUIRED = Self::CAPABILITY.bits;
         const LOGOUT_REQUIRED     = Self::BYE.bits;
         const SELECT_REQUIRED     = Self::FLAGS.bits | Self::EXISTS.bits | Self::RECENT.bits | Self::UNSEEN.bits | Self::PERMANENTFLAGS.bits | Self::UIDNEXT.bits | Self::UIDVALIDITY.bits;
@@ -117,6 +119,12 @@ impl RequiredResponses {
         if self.intersects(RequiredResponses::SEARCH) {
             ret |= line.starts_with(b"SEARCH");
         }
+        if self.intersects(RequiredResponses::SORT) {
+            ret |= line.starts_with(b"SORT");
+        }
+        if self.intersects(RequiredResponses::THREAD) {
+            ret |= line.starts_with(b"THREAD");
+        }
         if self.intersects(RequiredResponses::FETCH) {
             let mut ptr = 0;
             for i in 0..line.len() {
@@ -504,6 +512,8 @@ pub struct FetchResponse<'a> {
     pub message_sequence_number: MessageSequenceNumber,
     pub modseq: Option<ModSequence>,
     pub flags: Option<(Flag, Vec<String>)>,
+    /// `RFC822.SIZE` of the message in octets.
+    pub size: Option<usize>,
     pub body: Option<&'a [u8]>,
     pub references: Option<&'a [u8]>,
     pub envelope: Option<Envelope>,
@@ -561,6 +571,7 @@ pub fn fetch_response(input: &[u8]) -> ImapParseResult<FetchResponse<'_>> {
         message_sequence_number: 0,
         modseq: None,
         flags: None,
+        size: None,
         body: None,
         references: None,
         envelope: None,
@@ -624,6 +635,19 @@ pub fn fetch_response(input: &[u8]) -> ImapParseResult<FetchResponse<'_>> {
                     String::from_utf8_lossy(&input)
                 ))));
             }
+        } else if input[i..].starts_with(b"RFC822.SIZE ") {
+            i += b"RFC822.SIZE ".len();
+            if let Ok((rest, size)) =
+                take_while::<_, &[u8], (&[u8], nom::error::ErrorKind)>(is_digit)(&input[i..])
+            {
+                i += input.len() - i - rest.len();
+                ret.size = usize::from_str(to_str!(size)).ok();
+            } else {
+                return debug!(Err(MeliError::new(format!(
+                    "Unexpected input while parsing RFC822.SIZE in UID FETCH response. Got: `{:.40}`",
+                    String::from_utf8_lossy(&input)
+                ))));
+            }
         } else if input[i..].starts_with(b"RFC822 {") {
             i += b"RFC822 ".len();
             if let Ok((rest, body)) =
@@ -937,12 +961,30 @@ fn test_untagged_responses() {
             message_sequence_number: 1079,
             modseq: Some(ModSequence(std::num::NonZeroU64::new(1365_u64).unwrap())),
             flags: Some((Flag::SEEN, vec![])),
+            size: None,
             body: None,
             references: None,
             envelope: None,
             raw_fetch_value: &b"* 1079 FETCH (UID 1103 MODSEQ (1365) FLAGS (\\Seen))\r\n"[..],
         })
     );
+    assert_eq!(
+        untagged_responses(b"* 23 FETCH (UID 42 RFC822.SIZE 44827)\r\n")
+            .map(|(_, v, _)| v)
+            .unwrap()
+            .unwrap(),
+        Fetch(FetchResponse {
+            uid: Some(42),
+            message_sequence_number: 23,
+            modseq: None,
+            flags: None,
+            size: Some(44827),
+            body: None,
+            references: None,
+            envelope: None,
+            raw_fetch_value: &b"* 23 FETCH (UID 42 RFC822.SIZE 44827)\r\n"[..],
+        })
+    );
     assert_eq!(
         untagged_responses(b"* 1 FETCH (FLAGS (\\Seen))\r\n")
             .map(|(_, v, _)| v)
@@ -953,6 +995,7 @@ fn test_untagged_responses() {
             message_sequence_number: 1,
             modseq: None,
             flags: Some((Flag::SEEN, vec![])),
+            size: None,
             body: None,
             references: None,
             envelope: None,
@@ -1013,6 +1056,145 @@ fn test_imap_search() {
     );
 }
 
+/// `* SORT 2 84 882\r\n`, RFC 5256.
+pub fn sort_results<'a>(input: &'a [u8]) -> IResult<&'a [u8], Vec<ImapNum>> {
+    alt((
+        |input: &'a [u8]| -> IResult<&'a [u8], Vec<ImapNum>> {
+            let (input, _) = tag("* SORT ")(input)?;
+            let (input, list) = separated_nonempty_list(
+                tag(b" "),
+                map_res(is_not(" \r\n"), |s: &[u8]| {
+                    ImapNum::from_str(unsafe { std::str::from_utf8_unchecked(s) })
+                }),
+            )(input)?;
+            let (input, _) = tag("\r\n")(input)?;
+            Ok((input, list))
+        },
+        |input: &'a [u8]| -> IResult<&'a [u8], Vec<ImapNum>> {
+            let (input, _) = tag("* SORT\r\n")(input)?;
+            Ok((input, vec![]))
+        },
+    ))(input)
+}
+
+/// A conversation of a `THREAD` response. `uid` is `None` for the common root of messages whose
+/// parent is not in the mailbox.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImapThread {
+    pub uid: Option<UID>,
+    pub children: Vec<ImapThread>,
+}
+
+impl ImapThread {
+    /// UIDs of the conversation, each one before its replies.
+    pub fn uids(&self, ret: &mut Vec<UID>) {
+        ret.extend(self.uid);
+        for c in &self.children {
+            c.uids(ret);
+        }
+    }
+
+    /// Insert the parent of each message of the conversation in `parents`.
+    pub fn parents(&self, parents: &mut HashMap<UID, UID>) {
+        for c in &self.children {
+            if let (Some(parent), Some(uid)) = (self.uid, c.uid) {
+                parents.insert(uid, parent);
+            }
+            c.parents(parents);
+        }
+    }
+}
+
+/// `* THREAD (2)(3 6 (4 23)(44 7 96))\r\n`, RFC 5256.
+///
+/// In each parenthesized list, the messages are replies to the one before them and the nested
+/// lists are replies to the last one.
+pub fn thread_results(input: &[u8]) -> IResult<&[u8], Vec<ImapThread>> {
+    fn thread_list(input: &[u8]) -> IResult<&[u8], ImapThread> {
+        let (input, _) = tag("(")(input)?;
+        let (input, uids) = separated_list(
+            tag(b" "),
+            map_res(digit1, |s: &[u8]| {
+                UID::from_str(unsafe { std::str::from_utf8_unchecked(s) })
+            }),
+        )(input)?;
+        let (input, _) = opt(tag(" "))(input)?;
+        let (input, nested) = many0(thread_list)(input)?;
+        let (input, _) = tag(")")(input)?;
+        let mut children = nested;
+        for uid in uids.into_iter().rev() {
+            children = vec![ImapThread {
+                uid: Some(uid),
+                children,
+            }];
+        }
+        let ret = if children.len() == 1 && children[0].uid.is_some() {
+            children.remove(0)
+        } else {
+            ImapThread {
+                uid: None,
+                children,
+            }
+        };
+        Ok((input, ret))
+    }
+    let (input, _) = tag("* THREAD")(input)?;
+    let (input, _) = opt(tag(" "))(input)?;
+    let (input, threads) = many0(thread_list)(input)?;
+    let (input, _) = tag("\r\n")(input)?;
+    Ok((input, threads))
+}
+
+#[test]
+fn test_imap_sort_thread() {
+    assert_eq!(sort_results(b"* SORT\r\n").map(|(_, v)| v), Ok(vec![]));
+    assert_eq!(
+        sort_results(b"* SORT 2 84 882\r\n").map(|(_, v)| v),
+        Ok(vec![2, 84, 882])
+    );
+    assert_eq!(thread_results(b"* THREAD\r\n").map(|(_, v)| v), Ok(vec![]));
+    let leaf = |uid| ImapThread {
+        uid: Some(uid),
+        children: vec![],
+    };
+    let node = |uid, children| ImapThread {
+        uid: Some(uid),
+        children,
+    };
+    let (_, threads) = thread_results(b"* THREAD (2)(3 6 (4 23)(44 7 96))((1)(5))\r\n").unwrap();
+    assert_eq!(
+        threads,
+        vec![
+            leaf(2),
+            node(
+                3,
+                vec![node(
+                    6,
+                    vec![
+                        node(4, vec![leaf(23)]),
+                        node(44, vec![node(7, vec![leaf(96)])])
+                    ]
+                )]
+            ),
+            ImapThread {
+                uid: None,
+                children: vec![leaf(1), leaf(5)],
+            },
+        ]
+    );
+    let mut uids = vec![];
+    threads[1].uids(&mut uids);
+    assert_eq!(uids, vec![3, 6, 4, 23, 44, 7, 96]);
+    let mut parents = HashMap::default();
+    for t in &threads {
+        t.parents(&mut parents);
+    }
+    assert_eq!(parents.len(), 6);
+    assert_eq!(parents[&23], 4);
+    assert_eq!(parents[&44], 6);
+    assert!(!parents.contains_key(&1));
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct SelectResponse {
     pub exists: ImapNum,
@@ -1224,6 +1406,31 @@ pub fn flags(input: &[u8]) -> IResult<&[u8], (Flag, Vec<String>)> {
     Ok((input, (ret, keywords)))
 }
 
+/// Whether `keyword` can be used as a flag keyword in a `STORE` command.
+///
+/// Keywords are `atom`s: non-empty strings of 7-bit characters excluding `atom-specials`. They
+/// must not begin with `\`, which is reserved for system flags.
+pub fn is_valid_keyword(keyword: &str) -> bool {
+    !keyword.is_empty()
+        && keyword
+            .bytes()
+            .all(|b| b.is_ascii() && !b.is_ascii_control() && !b" (){%*\"\\]".contains(&b))
+}
+
+#[test]
+fn test_imap_is_valid_keyword() {
+    assert!(is_valid_keyword("$Forwarded"));
+    assert!(is_valid_keyword("work"));
+    assert!(is_valid_keyword("to-do"));
+    assert!(!is_valid_keyword(""));
+    assert!(!is_valid_keyword("\\Seen"));
+    assert!(!is_valid_keyword("two words"));
+    assert!(!is_valid_keyword("(paren"));
+    assert!(!is_valid_keyword("wild*"));
+    assert!(!is_valid_keyword("quote\""));
+    assert!(!is_valid_keyword("λ"));
+}
+
 pub fn byte_flags(input: &[u8]) -> IResult<&[u8], (Flag, Vec<String>)> {
     match flags(input) {
         Ok((rest, ret)) => Ok((rest, ret)),
@@ -1593,6 +1800,84 @@ pub fn status_response(input: &[u8]) -> IResult<&[u8], StatusResponse> {
     ))
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QuotaResponse {
+    pub root: String,
+    /// `(resource, usage, limit)` triplets, eg `("STORAGE", 10, 512)`.
+    pub resources: Vec<(String, u64, u64)>,
+}
+
+// quota_response ::= "QUOTA" SP astring SP quota_list
+// quota_list ::= "(" #quota_resource ")"
+// quota_resource ::= atom SP number SP number
+//* QUOTA "" (STORAGE 10 512)
+pub fn quota_response(input: &[u8]) -> IResult<&[u8], QuotaResponse> {
+    let (input, _) = tag("* QUOTA ")(input)?;
+    let (input, root) = astring_token(input)?;
+    let (input, _) = tag(" (")(input)?;
+    let (input, resources) = many0(preceded(opt(tag(" ")), quota_resource))(input)?;
+    let (input, _) = tag(")\r\n")(input)?;
+    Ok((
+        input,
+        QuotaResponse {
+            root: String::from_utf8_lossy(root).to_string(),
+            resources,
+        },
+    ))
+}
+
+fn quota_resource(input: &[u8]) -> IResult<&[u8], (String, u64, u64)> {
+    let (input, resource) = astring_char(input)?;
+    let (input, _) = tag(" ")(input)?;
+    let (input, usage) = map_res(digit1, |s| {
+        u64::from_str(unsafe { std::str::from_utf8_unchecked(s) })
+    })(input)?;
+    let (input, _) = tag(" ")(input)?;
+    let (input, limit) = map_res(digit1, |s| {
+        u64::from_str(unsafe { std::str::from_utf8_unchecked(s) })
+    })(input)?;
+    Ok((
+        input,
+        (
+            String::from_utf8_lossy(resource).to_uppercase(),
+            usage,
+            limit,
+        ),
+    ))
+}
+
+#[test]
+fn test_quota_response() {
+    assert_eq!(
+        quota_response(b"* QUOTA \"\" (STORAGE 10 512)\r\n")
+            .unwrap()
+            .1,
+        QuotaResponse {
+            root: String::new(),
+            resources: vec![("STORAGE".to_string(), 10, 512)],
+        }
+    );
+    assert_eq!(
+        quota_response(b"* QUOTA \"User quota\" (STORAGE 4500 10240 MESSAGE 372 5000)\r\n")
+            .unwrap()
+            .1,
+        QuotaResponse {
+            root: "User quota".to_string(),
+            resources: vec![
+                ("STORAGE".to_string(), 4500, 10240),
+                ("MESSAGE".to_string(), 372, 5000)
+            ],
+        }
+    );
+    assert_eq!(
+        quota_response(b"* QUOTA INBOX ()\r\n").unwrap().1,
+        QuotaResponse {
+            root: "INBOX".to_string(),
+            resources: vec![],
+        }
+    );
+}
+
 // mailbox = "INBOX" / astring
 //           ; INBOX is case-insensitive. All case variants of
 //           ; INBOX (e.g., "iNbOx") MUST be interpreted as INBOX
@@ -1703,8 +1988,8 @@ fn ctl(input: &[u8]) -> IResult<&[u8], u8> {
 }
 
 pub fn generate_envelope_hash(mailbox_path: &str, uid: &UID) -> EnvelopeHash {
-    let mut h = DefaultHasher::new();
+    let mut h = EnvelopeHasher::new();
     h.write_usize(*uid);
     h.write(mailbox_path.as_bytes());
-    h.finish()
+    h.envelope_hash()
 }
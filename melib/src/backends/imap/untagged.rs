@@ -195,7 +195,7 @@ impl ImapConnection {
                 debug!("exists {}", n);
                 try_fail!(
                     mailbox_hash,
-                    self.send_command(format!("FETCH {} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)", n).as_bytes()).await
+                    self.send_command(format!("FETCH {} (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)", n).as_bytes()).await
                     self.read_response(&mut response, RequiredResponses::FETCH_REQUIRED).await
                 );
                 let mut v = match super::protocol_parser::fetch_responses(&response) {
@@ -214,6 +214,7 @@ impl ImapConnection {
                     ref mut envelope,
                     ref mut flags,
                     ref references,
+                    ref size,
                     ..
                 } in &mut v
                 {
@@ -226,6 +227,9 @@ impl ImapConnection {
                     if let Some(value) = references {
                         env.set_references(value);
                     }
+                    if let Some(size) = size {
+                        env.set_size(*size);
+                    }
                     let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
                     if let Some((flags, keywords)) = flags {
                         env.set_flags(*flags);
@@ -283,7 +287,7 @@ impl ImapConnection {
                             )
                         })
                     {
-                        crate::log(err.to_string(), crate::INFO);
+                        crate::log_target("imap", err.to_string(), crate::INFO);
                     }
                 }
                 for response in v {
@@ -321,7 +325,7 @@ impl ImapConnection {
                             for ms in iter {
                                 accum = format!("{},{}", accum, to_str!(ms).trim());
                             }
-                            format!("UID FETCH {} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)", accum)
+                            format!("UID FETCH {} (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)", accum)
                         };
                         try_fail!(
                             mailbox_hash,
@@ -344,6 +348,7 @@ impl ImapConnection {
                             ref mut envelope,
                             ref mut flags,
                             ref references,
+                            ref size,
                             ..
                         } in &mut v
                         {
@@ -356,6 +361,9 @@ impl ImapConnection {
                             if let Some(value) = references {
                                 env.set_references(value);
                             }
+                            if let Some(size) = size {
+                                env.set_size(*size);
+                            }
                             let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
                             if let Some((flags, keywords)) = flags {
                                 env.set_flags(*flags);
@@ -382,7 +390,7 @@ impl ImapConnection {
                                     )
                                 })
                             {
-                                crate::log(err.to_string(), crate::INFO);
+                                crate::log_target("imap", err.to_string(), crate::INFO);
                             }
                         }
                         for response in v {
@@ -445,6 +453,7 @@ impl ImapConnection {
                 message_sequence_number: msg_seq,
                 modseq,
                 flags,
+                size: _,
                 body: _,
                 references: _,
                 envelope: _,
@@ -29,7 +29,7 @@ pub struct ImapWatchKit {
     pub uid_store: Arc<UIDStore>,
 }
 
-pub async fn poll_with_examine(kit: ImapWatchKit) -> Result<()> {
+pub async fn poll_with_examine(kit: ImapWatchKit, mut schedule: RefreshSchedule) -> Result<()> {
     debug!("poll with examine");
     let ImapWatchKit {
         mut conn,
@@ -37,16 +37,36 @@ pub async fn poll_with_examine(kit: ImapWatchKit) -> Result<()> {
         uid_store,
     } = kit;
     conn.connect().await?;
-    let mailboxes: HashMap<MailboxHash, ImapMailbox> = {
+    let mut mailboxes: HashMap<MailboxHash, ImapMailbox> = {
         let mailboxes_lck = timeout(uid_store.timeout, uid_store.mailboxes.lock()).await?;
         mailboxes_lck.clone()
     };
+    examine_all(
+        mailboxes
+            .values()
+            .filter(|m| schedule.interval(m.path()).is_some())
+            .cloned(),
+        &mut conn,
+        &uid_store,
+    )
+    .await?;
     loop {
-        for (_, mailbox) in mailboxes.clone() {
-            examine_updates(mailbox, &mut conn, &uid_store).await?;
-        }
-        //FIXME: make sleep duration configurable
-        smol::Timer::after(std::time::Duration::from_secs(3 * 60)).await;
+        let wait = schedule.wait(mailboxes.values().map(|m| (m.hash(), m.path())));
+        smol::Timer::after(wait).await;
+        let due = schedule.due();
+        mailboxes = {
+            let mailboxes_lck = timeout(uid_store.timeout, uid_store.mailboxes.lock()).await?;
+            mailboxes_lck.clone()
+        };
+        examine_all(
+            mailboxes
+                .values()
+                .filter(|m| due.contains(&m.hash()))
+                .cloned(),
+            &mut conn,
+            &uid_store,
+        )
+        .await?;
     }
 }
 
@@ -110,12 +130,15 @@ pub async fn idle(kit: ImapWatchKit) -> Result<()> {
         let mailboxes_lck = timeout(uid_store.timeout, uid_store.mailboxes.lock()).await?;
         mailboxes_lck.clone()
     };
-    for (h, mailbox) in mailboxes.clone() {
-        if mailbox_hash == h {
-            continue;
-        }
-        examine_updates(mailbox, &mut conn, &uid_store).await?;
-    }
+    examine_all(
+        mailboxes
+            .values()
+            .filter(|m| m.hash() != mailbox_hash)
+            .cloned(),
+        &mut conn,
+        &uid_store,
+    )
+    .await?;
     conn.send_command(b"IDLE").await?;
     let mut blockn = ImapBlockingConnection::from(conn);
     let mut watch = std::time::Instant::now();
@@ -127,11 +150,14 @@ pub async fn idle(kit: ImapWatchKit) -> Result<()> {
         let line = match timeout(Some(_10_MINS), blockn.as_stream()).await {
             Ok(Some(line)) => line,
             Ok(None) => {
-                debug!("IDLE connection dropped: {:?}", &blockn.err());
-                blockn.conn.connect().await?;
-                let mut main_conn_lck = timeout(uid_store.timeout, main_conn.lock()).await?;
-                main_conn_lck.connect().await?;
-                continue;
+                /* Let `watch()` reconnect and start over, since the new connection would have
+                 * to select INBOX and IDLE again. */
+                let err = blockn.err().unwrap_or_else(|| {
+                    MeliError::new("IDLE connection closed by server")
+                        .set_kind(crate::error::ErrorKind::Network)
+                });
+                debug!("IDLE connection dropped: {:?}", &err);
+                return Err(err);
             }
             Err(_) => {
                 /* Timeout */
@@ -150,9 +176,7 @@ pub async fn idle(kit: ImapWatchKit) -> Result<()> {
         if now.duration_since(watch) >= _5_MINS {
             /* Time to poll all inboxes */
             let mut conn = timeout(uid_store.timeout, main_conn.lock()).await?;
-            for (_h, mailbox) in mailboxes.clone() {
-                examine_updates(mailbox, &mut conn, &uid_store).await?;
-            }
+            examine_all(mailboxes.values().cloned(), &mut conn, &uid_store).await?;
             watch = now;
         }
         if line
@@ -193,6 +217,26 @@ pub async fn idle(kit: ImapWatchKit) -> Result<()> {
     }
 }
 
+/// Examine `mailboxes` for updates. Only the counts of mailboxes that haven't been loaded yet are
+/// needed, so they are fetched for all of them at once instead of examining each one.
+pub async fn examine_all(
+    mailboxes: impl Iterator<Item = ImapMailbox>,
+    conn: &mut ImapConnection,
+    uid_store: &Arc<UIDStore>,
+) -> Result<()> {
+    let (cold, warm): (Vec<ImapMailbox>, Vec<ImapMailbox>) = mailboxes
+        .filter(|m| !m.no_select)
+        .partition(|m| m.is_cold());
+    ImapType::imap_mailbox_counts(conn, &cold).await?;
+    for mailbox in cold {
+        mailbox.set_warm(true);
+    }
+    for mailbox in warm {
+        examine_updates(mailbox, conn, uid_store).await?;
+    }
+    Ok(())
+}
+
 pub async fn examine_updates(
     mailbox: ImapMailbox,
     conn: &mut ImapConnection,
@@ -336,7 +380,7 @@ pub async fn examine_updates(
                 }
             }
             cmd.push_str(
-                " (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
+                " (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
             );
             conn.send_command(cmd.as_bytes()).await?;
             conn.read_response(&mut response, RequiredResponses::FETCH_REQUIRED)
@@ -344,7 +388,7 @@ pub async fn examine_updates(
         } else if select_response.exists > mailbox.exists.lock().unwrap().len() {
             conn.send_command(
                 format!(
-                    "FETCH {}:* (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
+                    "FETCH {}:* (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
                     std::cmp::max(mailbox.exists.lock().unwrap().len(), 1)
                 )
                 .as_bytes(),
@@ -367,6 +411,7 @@ pub async fn examine_updates(
             ref mut envelope,
             ref mut flags,
             ref references,
+            ref size,
             ..
         } in v.iter_mut()
         {
@@ -376,6 +421,9 @@ pub async fn examine_updates(
             if let Some(value) = references {
                 env.set_references(value);
             }
+            if let Some(size) = size {
+                env.set_size(*size);
+            }
             let mut tag_lck = uid_store.collection.tag_index.write().unwrap();
             if let Some((flags, keywords)) = flags {
                 env.set_flags(*flags);
@@ -21,6 +21,7 @@
 
 use crate::backends::*;
 use crate::conf::AccountSettings;
+use crate::connections::proxy::Proxy;
 use crate::email::*;
 use crate::error::{MeliError, Result};
 use crate::Collection;
@@ -90,9 +91,9 @@ use objects::*;
 pub mod mailbox;
 use mailbox::*;
 
+/// Message bytes are kept in the `ByteCache` shared by all accounts.
 #[derive(Debug, Default)]
 pub struct EnvelopeCache {
-    bytes: Option<String>,
     headers: Option<String>,
     body: Option<String>,
     flags: Option<Flag>,
@@ -105,6 +106,7 @@ pub struct JmapServerConf {
     pub server_password: String,
     pub server_port: u16,
     pub danger_accept_invalid_certs: bool,
+    pub proxy: Option<Proxy>,
 }
 
 macro_rules! get_conf_val {
@@ -143,6 +145,7 @@ impl JmapServerConf {
             server_password: get_conf_val!(s["server_password"])?.to_string(),
             server_port: get_conf_val!(s["server_port"], 443)?,
             danger_accept_invalid_certs: get_conf_val!(s["danger_accept_invalid_certs"], false)?,
+            proxy: Proxy::new(s)?,
         })
     }
 }
@@ -269,6 +272,7 @@ impl Store {
         self.id_store.lock().unwrap().remove(&env_hash);
         self.blob_id_store.lock().unwrap().remove(&env_hash);
         self.byte_cache.lock().unwrap().remove(&env_hash);
+        ByteCache::remove(self.account_hash, env_hash);
         let mut mailbox_hashes = SmallVec::new();
         for (k, set) in self.mailboxes_index.write().unwrap().iter_mut() {
             if set.remove(&env_hash) {
@@ -284,6 +288,7 @@ pub struct JmapType {
     server_conf: JmapServerConf,
     connection: Arc<FutureMutex<JmapConnection>>,
     store: Arc<Store>,
+    refresh_schedule: RefreshSchedule,
 }
 
 impl MailBackend for JmapType {
@@ -293,6 +298,18 @@ impl MailBackend for JmapType {
             is_remote: true,
             supports_search: true,
             extensions: None,
+            actions: &[
+                BackendAction {
+                    name: "vacation-response-on",
+                    description: "reply to incoming messages with MESSAGE",
+                    args: &["MESSAGE"],
+                },
+                BackendAction {
+                    name: "vacation-response-off",
+                    description: "stop replying to incoming messages",
+                    args: &[],
+                },
+            ],
             supports_tags: true,
             supports_submission: false,
         };
@@ -344,32 +361,54 @@ impl MailBackend for JmapType {
     fn watch(&self) -> ResultFuture<()> {
         let connection = self.connection.clone();
         let store = self.store.clone();
+        let mut schedule = self.refresh_schedule.clone();
         Ok(Box::pin(async move {
             {
                 let mut conn = connection.lock().await;
                 conn.connect().await?;
             }
             loop {
-                {
-                    let mailbox_hashes = {
-                        store
-                            .mailboxes
-                            .read()
-                            .unwrap()
-                            .keys()
-                            .cloned()
-                            .collect::<SmallVec<[MailboxHash; 16]>>()
-                    };
-                    let conn = connection.lock().await;
-                    for mailbox_hash in mailbox_hashes {
-                        conn.email_changes(mailbox_hash).await?;
-                    }
+                let wait = schedule.wait(
+                    store
+                        .mailboxes
+                        .read()
+                        .unwrap()
+                        .values()
+                        .map(|m| (m.hash, m.path.as_str())),
+                );
+                crate::connections::sleep(wait).await;
+                let mailbox_hashes = schedule.due();
+                let conn = connection.lock().await;
+                for mailbox_hash in mailbox_hashes {
+                    conn.email_changes(mailbox_hash).await?;
                 }
-                crate::connections::sleep(std::time::Duration::from_secs(60)).await;
             }
         }))
     }
 
+    fn perform_action(&mut self, action: &str, args: Vec<String>) -> ResultFuture<String> {
+        let text_body = match action {
+            "vacation-response-on" => Some(
+                args.into_iter()
+                    .next()
+                    .ok_or_else(|| MeliError::new("`vacation-response-on` needs a message."))?,
+            ),
+            "vacation-response-off" => None,
+            _ => return Err(MeliError::new(format!("Unknown action `{}`.", action))),
+        };
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            protocol::set_vacation_response(&conn, text_body.as_deref()).await?;
+            Ok(if text_body.is_some() {
+                "Vacation response turned on.".to_string()
+            } else {
+                "Vacation response turned off.".to_string()
+            })
+        }))
+    }
+
     fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
         let store = self.store.clone();
         let connection = self.connection.clone();
@@ -860,6 +899,7 @@ impl JmapType {
             )?)),
             store,
             server_conf,
+            refresh_schedule: RefreshSchedule::new(s, std::time::Duration::from_secs(60)),
         }))
     }
 
@@ -869,6 +909,7 @@ impl JmapType {
         get_conf_val!(s["server_password"])?;
         get_conf_val!(s["server_port"], 443)?;
         get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        Proxy::new(s)?;
         Ok(())
     }
 }
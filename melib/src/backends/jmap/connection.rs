@@ -33,9 +33,19 @@ pub struct JmapConnection {
 
 impl JmapConnection {
     pub fn new(server_conf: &JmapServerConf, store: Arc<Store>) -> Result<Self> {
+        let proxy = server_conf
+            .proxy
+            .as_ref()
+            .map(|p| {
+                p.url()
+                    .parse::<isahc::http::Uri>()
+                    .map_err(|err| MeliError::new(format!("Invalid proxy: {}", err)))
+            })
+            .transpose()?;
         let client = HttpClient::builder()
             .timeout(std::time::Duration::from_secs(10))
             .redirect_policy(RedirectPolicy::Limit(10))
+            .proxy(proxy)
             .authentication(isahc::auth::Authentication::basic())
             .credentials(isahc::auth::Credentials::new(
                 &server_conf.server_username,
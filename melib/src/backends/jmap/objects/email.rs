@@ -26,7 +26,6 @@ use core::marker::PhantomData;
 use serde::de::{Deserialize, Deserializer};
 use serde_json::value::RawValue;
 use serde_json::Value;
-use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::Hasher;
 
@@ -42,9 +41,9 @@ impl Object for ThreadObject {
 
 impl Id<EmailObject> {
     pub fn into_hash(&self) -> EnvelopeHash {
-        let mut h = DefaultHasher::new();
+        let mut h = EnvelopeHasher::new();
         h.write(self.inner.as_bytes());
-        h.finish()
+        h.envelope_hash()
     }
 }
 
@@ -282,6 +281,7 @@ impl std::convert::From<EmailObject> for crate::Envelope {
             env.set_datetime(d);
         }
         env.set_has_attachments(t.has_attachment);
+        env.set_size(t.size as usize);
         if let Some(ref mut subject) = t.subject {
             env.set_subject(std::mem::replace(subject, String::new()).into_bytes());
         }
@@ -649,6 +649,9 @@ impl From<crate::search::Query> for Filter<EmailFilterCondition, EmailObject> {
                 AllAddresses(_) => {
                     //TODO
                 }
+                Note(_) => {
+                    /* Notes are stored locally, the server can't filter on them. */
+                }
                 Flags(v) => {
                     fn flag_to_filter(f: &str) -> Filter<EmailFilterCondition, EmailObject> {
                         match f {
@@ -706,6 +709,20 @@ impl From<crate::search::Query> for Filter<EmailFilterCondition, EmailObject> {
                             .into(),
                     );
                 }
+                Larger(n) => {
+                    *f = Filter::Condition(
+                        EmailFilterCondition::new()
+                            .min_size(Some(*n as u64 + 1))
+                            .into(),
+                    );
+                }
+                Smaller(n) => {
+                    *f = Filter::Condition(
+                        EmailFilterCondition::new()
+                            .max_size(Some(*n as u64))
+                            .into(),
+                    );
+                }
                 And(q1, q2) => {
                     let mut rhs = Filter::Condition(EmailFilterCondition::new().into());
                     let mut lhs = Filter::Condition(EmailFilterCondition::new().into());
@@ -46,12 +46,8 @@ impl JmapOp {
 
 impl BackendOp for JmapOp {
     fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
-        {
-            let byte_lck = self.store.byte_cache.lock().unwrap();
-            if byte_lck.contains_key(&self.hash) && byte_lck[&self.hash].bytes.is_some() {
-                let ret = byte_lck[&self.hash].bytes.clone().unwrap();
-                return Ok(Box::pin(async move { Ok(ret.into_bytes()) }));
-            }
+        if let Some(ret) = ByteCache::get(self.store.account_hash, self.hash) {
+            return Ok(Box::pin(async move { Ok(ret) }));
         }
         let store = self.store.clone();
         let hash = self.hash;
@@ -73,14 +69,9 @@ impl BackendOp for JmapOp {
 
             let res_text = res.text_async().await?;
 
-            store
-                .byte_cache
-                .lock()
-                .unwrap()
-                .entry(hash)
-                .or_default()
-                .bytes = Some(res_text.clone());
-            Ok(res_text.into_bytes())
+            let ret = res_text.into_bytes();
+            ByteCache::insert(store.account_hash, hash, ret.clone());
+            Ok(ret)
         }))
     }
 
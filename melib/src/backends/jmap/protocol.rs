@@ -323,6 +323,63 @@ pub async fn fetch(
     Ok(ret)
 }
 
+/// Turn the vacation response (RFC 8621 section 8) on with `text_body`, or off.
+pub async fn set_vacation_response(conn: &JmapConnection, text_body: Option<&str>) -> Result<()> {
+    const CAPABILITY: &str = "urn:ietf:params:jmap:vacationresponse";
+    let (api_url, account_id) = {
+        let session = conn.session.lock().unwrap();
+        if !session.capabilities.contains_key(CAPABILITY) {
+            return Err(MeliError::new(format!(
+                "Server {} does not support vacation responses ({}).",
+                &conn.server_conf.server_hostname, CAPABILITY
+            )));
+        }
+        (
+            session.api_url.clone(),
+            session.primary_accounts.get(CAPABILITY).cloned(),
+        )
+    };
+    let account_id = account_id.unwrap_or_else(|| conn.mail_account_id());
+    let seq = get_request_no!(conn.request_no);
+    let mut res = conn
+        .client
+        .post_async(
+            api_url.as_str(),
+            serde_json::to_string(&json!({
+                "using": ["urn:ietf:params:jmap:core", CAPABILITY],
+                "methodCalls": [["VacationResponse/set", {
+                    "accountId": account_id,
+                    "update": {
+                        "singleton": {
+                            "isEnabled": text_body.is_some(),
+                            "textBody": text_body,
+                        }
+                    }
+                },
+                 format!("#m{}",seq).as_str()]],
+            }))?,
+        )
+        .await?;
+
+    let res_text = res.text_async().await?;
+    let v: Value = serde_json::from_str(&res_text)?;
+    *conn.store.online_status.lock().await = (std::time::Instant::now(), Ok(()));
+    let response = &v["methodResponses"][0];
+    if response[0] == "error" {
+        return Err(MeliError::new(format!(
+            "Could not set vacation response: {}",
+            response[1]
+        )));
+    }
+    if let Some(err) = response[1]["notUpdated"].get("singleton") {
+        return Err(MeliError::new(format!(
+            "Could not set vacation response: {}",
+            err
+        )));
+    }
+    Ok(())
+}
+
 pub fn keywords_to_flags(keywords: Vec<String>) -> (Flag, Vec<String>) {
     let mut f = Flag::default();
     let mut tags = vec![];
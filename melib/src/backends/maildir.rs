@@ -26,6 +26,9 @@ pub use self::backend::*;
 mod stream;
 pub use stream::*;
 
+mod layout;
+pub use layout::*;
+
 use crate::backends::*;
 use crate::email::Flag;
 use crate::error::{MeliError, Result};
@@ -78,6 +81,10 @@ impl MaildirOp {
             }
             return Err(MeliError::new("File not found"));
         }
+        if map[&self.hash].removed && map[&self.hash].modified.is_none() {
+            /* The watcher noticed the file was removed but not where it went, if anywhere. */
+            return Err(MeliError::new("File not found"));
+        }
 
         Ok(if let Some(modif) = &map[&self.hash].modified {
             match modif {
@@ -152,31 +159,16 @@ impl MaildirMailbox {
             )
             .ok();
 
-        let read_only = if let Ok(metadata) = std::fs::metadata(&pathbuf) {
-            metadata.permissions().readonly()
-        } else {
-            true
-        };
-
         let ret = MaildirMailbox {
             hash: h.finish(),
             name: file_name,
             path: fname.unwrap().to_path_buf(),
+            permissions: Self::permissions(&pathbuf),
             fs_path: pathbuf,
             parent,
             children,
             usage: Arc::new(RwLock::new(SpecialUsageMailbox::Normal)),
             is_subscribed: false,
-            permissions: MailboxPermissions {
-                create_messages: !read_only,
-                remove_messages: !read_only,
-                set_flags: !read_only,
-                create_child: !read_only,
-                rename_messages: !read_only,
-                delete_messages: !read_only,
-                delete_mailbox: !read_only,
-                change_permissions: false,
-            },
             unseen: Arc::new(Mutex::new(0)),
             total: Arc::new(Mutex::new(0)),
         };
@@ -186,6 +178,25 @@ impl MaildirMailbox {
         Ok(ret)
     }
 
+    /// Everything is allowed unless the directory at `path` is read-only.
+    fn permissions(path: &Path) -> MailboxPermissions {
+        let read_only = if let Ok(metadata) = std::fs::metadata(path) {
+            metadata.permissions().readonly()
+        } else {
+            true
+        };
+        MailboxPermissions {
+            create_messages: !read_only,
+            remove_messages: !read_only,
+            set_flags: !read_only,
+            create_child: !read_only,
+            rename_messages: !read_only,
+            delete_messages: !read_only,
+            delete_mailbox: !read_only,
+            change_permissions: false,
+        }
+    }
+
     pub fn fs_path(&self) -> &Path {
         self.fs_path.as_path()
     }
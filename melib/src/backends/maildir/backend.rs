@@ -19,10 +19,13 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::{MaildirMailbox, MaildirOp, MaildirPathTrait};
+use super::{
+    maildirpp_dir_name, maildirpp_folder_components, MaildirLayout, MaildirMailbox, MaildirOp,
+    MaildirPathTrait, SubscriptionsFile,
+};
 use crate::backends::{RefreshEventKind::*, *};
 use crate::conf::AccountSettings;
-use crate::email::{Envelope, EnvelopeHash, Flag};
+use crate::email::{Envelope, EnvelopeHash, EnvelopeHasher, Flag};
 use crate::error::{ErrorKind, MeliError, Result};
 use crate::shellexpand::ShellExpandTrait;
 use crate::Collection;
@@ -102,6 +105,10 @@ impl DerefMut for HashIndex {
 
 pub type HashIndexes = Arc<Mutex<HashMap<MailboxHash, HashIndex>>>;
 
+/// Unseen and total message counts of each mailbox, shared with the watcher so that it knows of
+/// mailboxes created after it started.
+type MailboxCounts = Arc<Mutex<HashMap<MailboxHash, (Arc<Mutex<usize>>, Arc<Mutex<usize>>)>>>;
+
 /// Maildir backend https://cr.yp.to/proto/maildir.html
 #[derive(Debug)]
 pub struct MaildirType {
@@ -109,9 +116,12 @@ pub struct MaildirType {
     mailboxes: HashMap<MailboxHash, MaildirMailbox>,
     mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
     hash_indexes: HashIndexes,
+    mailbox_counts: MailboxCounts,
     event_consumer: BackendEventConsumer,
     collection: Collection,
     path: PathBuf,
+    layout: MaildirLayout,
+    subscriptions: Option<SubscriptionsFile>,
 }
 
 macro_rules! path_is_new {
@@ -143,9 +153,9 @@ macro_rules! get_path_hash {
 }
 
 pub(super) fn get_file_hash(file: &Path) -> EnvelopeHash {
-    let mut hasher = DefaultHasher::default();
+    let mut hasher = EnvelopeHasher::new();
     file.hash(&mut hasher);
-    hasher.finish()
+    hasher.envelope_hash()
 }
 
 pub fn move_to_cur(p: PathBuf) -> Result<PathBuf> {
@@ -172,6 +182,7 @@ impl MailBackend for MaildirType {
             is_remote: false,
             supports_search: false,
             extensions: None,
+            actions: &[],
             supports_tags: false,
             supports_submission: false,
         };
@@ -216,7 +227,7 @@ impl MailBackend for MaildirType {
     }
 
     fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
-        let cache_dir = xdg::BaseDirectories::with_profile("meli", &self.name).unwrap();
+        let account_name = self.name.clone();
         let account_hash = {
             let mut hasher = DefaultHasher::default();
             hasher.write(self.name.as_bytes());
@@ -281,7 +292,7 @@ impl MailBackend for MaildirType {
                             .unwrap()
                             .insert(env.hash(), mailbox_hash);
                         let file_name = file.strip_prefix(&root_path).unwrap().to_path_buf();
-                        if let Ok(cached) = cache_dir.place_cache_file(file_name) {
+                        if let Ok(cached) = cache_file(&account_name, &file_name) {
                             /* place result in cache directory */
                             let f = fs::File::create(cached)?;
                             let metadata = f.metadata()?;
@@ -350,8 +361,14 @@ impl MailBackend for MaildirType {
         };
         let root_path = self.path.to_path_buf();
         watcher.watch(&root_path, RecursiveMode::Recursive).unwrap();
-        let cache_dir = xdg::BaseDirectories::with_profile("meli", &self.name).unwrap();
+        let account_name = self.name.clone();
         debug!("watching {:?}", root_path);
+        crate::log_fields(
+            "maildir",
+            crate::DEBUG,
+            "watching for changes",
+            &[("account", &self.name), ("path", &root_path.display())],
+        );
         let hash_indexes = self.hash_indexes.clone();
         let mailbox_index = self.mailbox_index.clone();
         let root_mailbox_hash: MailboxHash = self
@@ -360,17 +377,15 @@ impl MailBackend for MaildirType {
             .find(|m| m.parent.is_none())
             .map(|m| m.hash())
             .unwrap();
-        let mailbox_counts = self
-            .mailboxes
-            .iter()
-            .map(|(&k, v)| (k, (v.unseen.clone(), v.total.clone())))
-            .collect::<HashMap<MailboxHash, (Arc<Mutex<usize>>, Arc<Mutex<usize>>)>>();
+        let all_mailbox_counts = self.mailbox_counts.clone();
         Ok(Box::pin(async move {
             // Move `watcher` in the closure's scope so that it doesn't get dropped.
             let _watcher = watcher;
             let mut buf = Vec::with_capacity(4096);
             loop {
-                match rx.recv() {
+                let event = rx.recv();
+                let mailbox_counts = all_mailbox_counts.lock().unwrap().clone();
+                match event {
                     /*
                      * Event types:
                      *
@@ -392,6 +407,11 @@ impl MailBackend for MaildirType {
                                     Ok(p) => p,
                                     Err(e) => {
                                         debug!("error: {}", e.to_string());
+                                        crate::log_target(
+                                            "maildir",
+                                            format!("Could not move new message to cur: {}", e),
+                                            crate::ERROR,
+                                        );
                                         continue;
                                     }
                                 };
@@ -406,7 +426,7 @@ impl MailBackend for MaildirType {
                                 &hash_indexes,
                                 mailbox_hash,
                                 pathbuf.as_path(),
-                                &cache_dir,
+                                &account_name,
                                 file_name,
                                 &mut buf,
                             ) {
@@ -461,7 +481,7 @@ impl MailBackend for MaildirType {
                                         &hash_indexes,
                                         mailbox_hash,
                                         pathbuf.as_path(),
-                                        &cache_dir,
+                                        &account_name,
                                         file_name,
                                         &mut buf,
                                     ) {
@@ -568,6 +588,10 @@ impl MailBackend for MaildirType {
                         /* Envelope hasn't changed */
                         DebouncedEvent::Rename(src, dest) => {
                             debug!("DebouncedEvent::Rename(src = {:?}, dest = {:?})", src, dest);
+                            if dest.is_dir() {
+                                /* Renamed mailboxes are handled in `rename_mailbox`. */
+                                continue;
+                            }
                             let mailbox_hash = get_path_hash!(src);
                             let dest_mailbox = {
                                 let dest_mailbox = get_path_hash!(dest);
@@ -613,7 +637,7 @@ impl MailBackend for MaildirType {
                                         &hash_indexes,
                                         dest_mailbox,
                                         dest.as_path(),
-                                        &cache_dir,
+                                        &account_name,
                                         file_name,
                                         &mut buf,
                                     ) {
@@ -704,7 +728,7 @@ impl MailBackend for MaildirType {
                                     &hash_indexes,
                                     dest_mailbox.unwrap_or(mailbox_hash),
                                     dest.as_path(),
-                                    &cache_dir,
+                                    &account_name,
                                     file_name,
                                     &mut buf,
                                 ) {
@@ -751,7 +775,7 @@ impl MailBackend for MaildirType {
                                     &hash_indexes,
                                     dest_mailbox,
                                     dest.as_path(),
-                                    &cache_dir,
+                                    &account_name,
                                     file_name,
                                     &mut buf,
                                 ) {
@@ -824,7 +848,14 @@ impl MailBackend for MaildirType {
                         }
                         _ => {}
                     },
-                    Err(e) => debug!("watch error: {:?}", e),
+                    Err(e) => {
+                        debug!("watch error: {:?}", e);
+                        crate::log_target(
+                            "maildir",
+                            format!("Watching {} failed: {}", root_path.display(), e),
+                            crate::ERROR,
+                        );
+                    }
                 }
             }
         }))
@@ -870,6 +901,10 @@ impl MailBackend for MaildirType {
                     if !hash_index.contains_key(&env_hash) {
                         continue;
                     }
+                    if hash_index[&env_hash].removed && hash_index[&env_hash].modified.is_none() {
+                        /* Removed by another client, there is no file left to act on. */
+                        continue;
+                    }
                     if let Some(modif) = &hash_index[&env_hash].modified {
                         match modif {
                             PathMod::Path(ref path) => path.clone(),
@@ -935,6 +970,10 @@ impl MailBackend for MaildirType {
                     if !hash_index.contains_key(&env_hash) {
                         continue;
                     }
+                    if hash_index[&env_hash].removed && hash_index[&env_hash].modified.is_none() {
+                        /* Removed by another client, there is no file left to act on. */
+                        continue;
+                    }
                     if let Some(modif) = &hash_index[&env_hash].modified {
                         match modif {
                             PathMod::Path(ref path) => path.clone(),
@@ -975,6 +1014,10 @@ impl MailBackend for MaildirType {
                     if !hash_index.contains_key(&env_hash) {
                         continue;
                     }
+                    if hash_index[&env_hash].removed && hash_index[&env_hash].modified.is_none() {
+                        /* Removed by another client, there is no file left to act on. */
+                        continue;
+                    }
                     if let Some(modif) = &hash_index[&env_hash].modified {
                         match modif {
                             PathMod::Path(ref path) => path.clone(),
@@ -1013,69 +1056,103 @@ impl MailBackend for MaildirType {
         &mut self,
         new_path: String,
     ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
-        let mut path = self.path.clone();
-        path.push(&new_path);
-        if !path.starts_with(&self.path) {
-            return Err(MeliError::new(format!("Path given (`{}`) is absolute. Please provide a path relative to the account's root mailbox.", &new_path)));
+        let path = self.mailbox_fs_path(&new_path)?;
+        if path.exists() {
+            return Err(MeliError::new(format!(
+                "Mailbox `{}` already exists.",
+                &new_path
+            )));
         }
-
+        /* create_dir does not create intermediate directories (like `mkdir -p`), so the parent
+         * must exist at this point. */
         std::fs::create_dir(&path)?;
-        /* create_dir does not create intermediate directories (like `mkdir -p`), so the parent must be a valid
-         * mailbox at this point. */
-
-        let parent = path.parent().and_then(|p| {
-            self.mailboxes
-                .iter()
-                .find(|(_, f)| f.fs_path == p)
-                .map(|item| *item.0)
-        });
-
-        let mailbox_hash = get_path_hash!(&path);
-        if let Some(parent) = parent {
-            self.mailboxes
-                .entry(parent)
-                .and_modify(|entry| entry.children.push(mailbox_hash));
+        for d in &["cur", "new", "tmp"] {
+            std::fs::create_dir(path.join(d))?;
+        }
+        if self.layout == MaildirLayout::MaildirPlusPlus {
+            /* Marks the directory as a subfolder for Courier and Dovecot. */
+            fs::File::create(path.join("maildirfolder"))?;
         }
-        let new_mailbox = MaildirMailbox {
-            hash: mailbox_hash,
-            path: PathBuf::from(&new_path),
-            name: new_path,
-            fs_path: path,
-            parent,
-            children: vec![],
-            usage: Default::default(),
-            is_subscribed: true,
-            permissions: Default::default(),
-            unseen: Default::default(),
-            total: Default::default(),
-        };
 
-        self.mailboxes.insert(mailbox_hash, new_mailbox);
+        let mailbox_hash = self.insert_mailbox(path)?;
+        let name = self.subscription_name(mailbox_hash)?;
+        if let Some(ref mut subscriptions) = self.subscriptions {
+            subscriptions.set(&name, true)?;
+        }
         let ret = self.mailboxes()?;
         Ok(Box::pin(async move { Ok((mailbox_hash, ret.await?)) }))
     }
 
     fn delete_mailbox(
         &mut self,
-        _mailbox_hash: MailboxHash,
+        mailbox_hash: MailboxHash,
     ) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
-        Err(MeliError::new("Unimplemented."))
+        let mailbox = self.removable_mailbox(mailbox_hash)?;
+        if !mailbox.permissions.delete_mailbox {
+            return Err(MeliError::new(format!("You do not have permission to delete `{}`. Set permissions for this mailbox are {}", mailbox.name(), mailbox.permissions)));
+        }
+        let fs_path = mailbox.fs_path.clone();
+        let name = self.subscription_name(mailbox_hash)?;
+        fs::remove_dir_all(&fs_path)?;
+        self.remove_mailbox(mailbox_hash);
+        if let Some(ref mut subscriptions) = self.subscriptions {
+            subscriptions.set(&name, false)?;
+        }
+        let ret = self.mailboxes()?;
+        Ok(Box::pin(async move { ret.await }))
     }
 
     fn set_mailbox_subscription(
         &mut self,
-        _mailbox_hash: MailboxHash,
-        _val: bool,
+        mailbox_hash: MailboxHash,
+        val: bool,
     ) -> ResultFuture<()> {
-        Err(MeliError::new("Unimplemented."))
+        if self.subscriptions.is_none() {
+            return Err(MeliError::new(
+                "Set `subscriptions_file = true` in the account's settings to change maildir subscriptions.",
+            ));
+        }
+        let name = self.subscription_name(mailbox_hash)?;
+        self.subscriptions.as_mut().unwrap().set(&name, val)?;
+        if let Some(f) = self.mailboxes.get_mut(&mailbox_hash) {
+            f.is_subscribed = val;
+        }
+        Ok(Box::pin(async { Ok(()) }))
     }
 
     fn rename_mailbox(
         &mut self,
-        _mailbox_hash: MailboxHash,
-        _new_path: String,
+        mailbox_hash: MailboxHash,
+        new_path: String,
     ) -> ResultFuture<Mailbox> {
-        Err(MeliError::new("Unimplemented."))
+        let mailbox = self.removable_mailbox(mailbox_hash)?;
+        let (fs_path, is_subscribed, usage) = (
+            mailbox.fs_path.clone(),
+            mailbox.is_subscribed,
+            mailbox.usage.clone(),
+        );
+        let new_fs_path = self.mailbox_fs_path(&new_path)?;
+        if new_fs_path.exists() {
+            return Err(MeliError::new(format!(
+                "Mailbox `{}` already exists.",
+                &new_path
+            )));
+        }
+        let old_name = self.subscription_name(mailbox_hash)?;
+        fs::rename(&fs_path, &new_fs_path)?;
+        self.remove_mailbox(mailbox_hash);
+        let new_hash = self.insert_mailbox(new_fs_path)?;
+        if let Some(f) = self.mailboxes.get_mut(&new_hash) {
+            f.is_subscribed = is_subscribed;
+            f.usage = usage;
+        }
+        let new_name = self.subscription_name(new_hash)?;
+        if let Some(ref mut subscriptions) = self.subscriptions {
+            subscriptions.set(&old_name, false)?;
+            subscriptions.set(&new_name, is_subscribed)?;
+        }
+        let ret = BackendMailbox::clone(&self.mailboxes[&new_hash]);
+        Ok(Box::pin(async move { Ok(ret) }))
     }
 
     fn set_mailbox_permissions(
@@ -1174,6 +1251,69 @@ impl MaildirType {
             }
             Ok(children)
         };
+        /* Maildir++ subfolders are all directly under the root maildir, which is the inbox. */
+        fn maildirpp_mailboxes(
+            mailboxes: &mut HashMap<MailboxHash, MaildirMailbox>,
+            settings: &AccountSettings,
+            root_path: &Path,
+        ) -> Result<()> {
+            let (root_hash, root_mailbox_path) = match mailboxes.values().next() {
+                Some(root) => (root.hash, root.path.clone()),
+                None => {
+                    return Err(MeliError::new(format!(
+                        "Configuration error ({}): root_path `{}` is not a valid maildir. The root of a Maildir++ store is the inbox.",
+                        settings.name(),
+                        settings.root_mailbox.as_str()
+                    )));
+                }
+            };
+            let mut folders: Vec<(Vec<String>, PathBuf)> = Vec::new();
+            for entry in fs::read_dir(root_path)? {
+                let path = entry?.path();
+                if !path.is_dir() {
+                    continue;
+                }
+                if let Some(components) = path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .and_then(maildirpp_folder_components)
+                {
+                    let components = components.into_iter().map(str::to_string).collect();
+                    folders.push((components, path));
+                }
+            }
+            /* Parents come before their children. */
+            folders.sort_by(|a, b| a.0.len().cmp(&b.0.len()));
+            let mut hashes: HashMap<Vec<String>, MailboxHash> = HashMap::default();
+            for (components, path) in folders {
+                let mut f = match MaildirMailbox::new(
+                    path.to_str().unwrap().to_string(),
+                    components.last().unwrap().to_string(),
+                    None,
+                    Vec::new(),
+                    false,
+                    settings,
+                ) {
+                    Ok(f) => f,
+                    Err(_) => continue,
+                };
+                f.path = root_mailbox_path.join(components.join("/"));
+                /* Intermediate folders may not exist, eg `.Lists.rust` without `.Lists`, so
+                 * attach to the closest existing ancestor. */
+                let parent = (1..components.len())
+                    .rev()
+                    .find_map(|i| hashes.get(&components[..i]).cloned())
+                    .unwrap_or(root_hash);
+                f.parent = Some(parent);
+                if let Some(p) = mailboxes.get_mut(&parent) {
+                    p.children.push(f.hash);
+                }
+                hashes.insert(components, f.hash);
+                mailboxes.insert(f.hash, f);
+            }
+            Ok(())
+        }
+        let layout = MaildirLayout::new(settings)?;
         let root_path = PathBuf::from(settings.root_mailbox()).expand();
         if !root_path.exists() {
             return Err(MeliError::new(format!(
@@ -1200,7 +1340,9 @@ impl MaildirType {
             mailboxes.insert(f.hash, f);
         }
 
-        if mailboxes.is_empty() {
+        if layout == MaildirLayout::MaildirPlusPlus {
+            maildirpp_mailboxes(&mut mailboxes, settings, &root_path)?;
+        } else if mailboxes.is_empty() {
             let children = recurse_mailboxes(&mut mailboxes, settings, &root_path)?;
             for c in &children {
                 if let Some(f) = mailboxes.get_mut(c) {
@@ -1224,6 +1366,17 @@ impl MaildirType {
                 f.is_subscribed = true;
             }
         }
+        /* When the store is shared with an IMAP server, use its subscriptions instead of the
+         * configuration's. */
+        let subscriptions = if get_subscriptions_file(settings)? {
+            let subscriptions = SubscriptionsFile::open(&root_path, layout)?;
+            for f in mailboxes.values_mut() {
+                f.is_subscribed = subscriptions.contains(&subscription_name(&root_path, &f.path));
+            }
+            Some(subscriptions)
+        } else {
+            None
+        };
 
         let mut hash_indexes =
             HashMap::with_capacity_and_hasher(mailboxes.len(), Default::default());
@@ -1236,17 +1389,164 @@ impl MaildirType {
                 },
             );
         }
+        let mailbox_counts = mailboxes
+            .iter()
+            .map(|(&k, v)| (k, (v.unseen.clone(), v.total.clone())))
+            .collect::<HashMap<MailboxHash, (Arc<Mutex<usize>>, Arc<Mutex<usize>>)>>();
         Ok(Box::new(MaildirType {
             name: settings.name().to_string(),
             mailboxes,
             hash_indexes: Arc::new(Mutex::new(hash_indexes)),
+            mailbox_counts: Arc::new(Mutex::new(mailbox_counts)),
             mailbox_index: Default::default(),
             event_consumer,
             collection: Default::default(),
             path: root_path,
+            layout,
+            subscriptions,
         }))
     }
 
+    /// Where the mailbox with `path`, relative to the root mailbox, is stored.
+    fn mailbox_fs_path(&self, path: &str) -> Result<PathBuf> {
+        if self.layout == MaildirLayout::MaildirPlusPlus {
+            return Ok(self.path.join(maildirpp_dir_name(path)?));
+        }
+        let relative = Path::new(path);
+        if relative.is_absolute() || relative.components().any(|c| c == Component::ParentDir) {
+            return Err(MeliError::new(format!("Path given (`{}`) is not relative to the account's root mailbox. Please provide a path relative to the account's root mailbox.", path)));
+        }
+        Ok(self.path.join(relative))
+    }
+
+    /// Add the maildir at `fs_path` to the mailbox tree, under its closest existing ancestor.
+    fn insert_mailbox(&mut self, fs_path: PathBuf) -> Result<MailboxHash> {
+        let (parent, path, name) = if self.layout == MaildirLayout::MaildirPlusPlus {
+            let components = fs_path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(maildirpp_folder_components)
+                .ok_or_else(|| {
+                    MeliError::new(format!(
+                        "{} is not a Maildir++ subfolder.",
+                        fs_path.display()
+                    ))
+                })?;
+            let root = self
+                .mailboxes
+                .values()
+                .find(|f| f.parent.is_none())
+                .ok_or_else(|| MeliError::new("Root mailbox not found."))?;
+            let (root_hash, root_mailbox_path) = (root.hash, root.path.clone());
+            let parent = (1..components.len())
+                .rev()
+                .find_map(|i| {
+                    let ancestor = root_mailbox_path.join(components[..i].join("/"));
+                    self.mailboxes
+                        .values()
+                        .find(|f| f.path == ancestor)
+                        .map(|f| f.hash)
+                })
+                .unwrap_or(root_hash);
+            (
+                Some(parent),
+                root_mailbox_path.join(components.join("/")),
+                components.last().unwrap().to_string(),
+            )
+        } else {
+            let parent = fs_path.parent().and_then(|p| {
+                self.mailboxes
+                    .values()
+                    .find(|f| f.fs_path == p)
+                    .map(|f| f.hash)
+            });
+            /* Same as the paths of mailboxes found on startup, eg `Mail/Lists/rust`. */
+            let path = fs_path
+                .strip_prefix(self.path.parent().unwrap_or_else(|| Path::new("/")))
+                .unwrap_or(&fs_path)
+                .to_path_buf();
+            let name = fs_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (parent, path, name)
+        };
+
+        let mailbox_hash = get_path_hash!(&fs_path);
+        if let Some(parent) = parent {
+            self.mailboxes
+                .entry(parent)
+                .and_modify(|entry| entry.children.push(mailbox_hash));
+        }
+        let new_mailbox = MaildirMailbox {
+            hash: mailbox_hash,
+            path,
+            name,
+            permissions: MaildirMailbox::permissions(&fs_path),
+            fs_path,
+            parent,
+            children: vec![],
+            usage: Default::default(),
+            is_subscribed: true,
+            unseen: Default::default(),
+            total: Default::default(),
+        };
+        self.mailbox_counts.lock().unwrap().insert(
+            mailbox_hash,
+            (new_mailbox.unseen.clone(), new_mailbox.total.clone()),
+        );
+        self.hash_indexes.lock().unwrap().insert(
+            mailbox_hash,
+            HashIndex {
+                index: HashMap::with_capacity_and_hasher(0, Default::default()),
+                hash: mailbox_hash,
+            },
+        );
+        self.mailboxes.insert(mailbox_hash, new_mailbox);
+        Ok(mailbox_hash)
+    }
+
+    /// A mailbox that can be deleted or renamed: neither the root nor one with children.
+    fn removable_mailbox(&self, mailbox_hash: MailboxHash) -> Result<&MaildirMailbox> {
+        let mailbox = self.mailboxes.get(&mailbox_hash).ok_or_else(|| {
+            MeliError::new(format!("Mailbox with hash {} not found", mailbox_hash))
+        })?;
+        if mailbox.fs_path == self.path {
+            return Err(MeliError::new(
+                "The root mailbox cannot be deleted or renamed.",
+            ));
+        }
+        if !mailbox.children.is_empty() {
+            return Err(MeliError::new(format!(
+                "Mailbox `{}` has children. Delete or move them first.",
+                mailbox.path.display()
+            )));
+        }
+        Ok(mailbox)
+    }
+
+    /// Forget the mailbox and its messages. Its counts are kept, since the watcher may still
+    /// receive events for its files.
+    fn remove_mailbox(&mut self, mailbox_hash: MailboxHash) {
+        if let Some(mailbox) = self.mailboxes.remove(&mailbox_hash) {
+            if let Some(parent) = mailbox.parent.and_then(|p| self.mailboxes.get_mut(&p)) {
+                parent.children.retain(|c| *c != mailbox_hash);
+            }
+        }
+        self.hash_indexes.lock().unwrap().remove(&mailbox_hash);
+        self.mailbox_index
+            .lock()
+            .unwrap()
+            .retain(|_, m| *m != mailbox_hash);
+    }
+
+    fn subscription_name(&self, mailbox_hash: MailboxHash) -> Result<String> {
+        let f = self.mailboxes.get(&mailbox_hash).ok_or_else(|| {
+            MeliError::new(format!("Mailbox with hash {} not found", mailbox_hash))
+        })?;
+        Ok(subscription_name(&self.path, &f.path))
+    }
+
     pub fn save_to_mailbox(mut path: PathBuf, bytes: Vec<u8>, flags: Option<Flag>) -> Result<()> {
         for d in &["cur", "new", "tmp"] {
             path.push(d);
@@ -1331,16 +1631,56 @@ impl MaildirType {
                 s.root_mailbox.as_str()
             )));
         }
+        MaildirLayout::new(s)?;
+        get_subscriptions_file(s)?;
 
         Ok(())
     }
 }
 
+fn get_subscriptions_file(s: &AccountSettings) -> Result<bool> {
+    s.extra
+        .get("subscriptions_file")
+        .map(|v| {
+            v.parse::<bool>().map_err(|e| {
+                MeliError::new(format!(
+                    "Configuration error ({}): Invalid value for field `subscriptions_file`: {}\n{}",
+                    s.name.as_str(),
+                    v,
+                    e
+                ))
+            })
+        })
+        .unwrap_or(Ok(false))
+}
+
+/// Name of a mailbox in subscription files: its path relative to the root maildir, which is
+/// `INBOX`.
+fn subscription_name(root_path: &Path, mailbox_path: &Path) -> String {
+    let root_name = root_path.file_name().map(Path::new).unwrap_or(root_path);
+    match mailbox_path.strip_prefix(root_name) {
+        Ok(p) if p.as_os_str().is_empty() => "INBOX".to_string(),
+        Ok(p) => p.to_string_lossy().to_string(),
+        Err(_) => mailbox_path.to_string_lossy().to_string(),
+    }
+}
+
+/// The path of the cached envelope of message `file_name`, relative to the root of account
+/// `account_name`.
+pub(super) fn cache_file(account_name: &str, file_name: &Path) -> Result<PathBuf> {
+    crate::paths::moved_state_file(
+        Some(account_name),
+        Path::new("maildir_cache").join(file_name),
+        Some(account_name),
+        file_name,
+    )
+}
+
 fn add_path_to_index(
     hash_index: &HashIndexes,
     mailbox_hash: MailboxHash,
     path: &Path,
-    cache_dir: &xdg::BaseDirectories,
+    account_name: &str,
     file_name: PathBuf,
     buf: &mut Vec<u8>,
 ) -> Result<Envelope> {
@@ -1367,7 +1707,7 @@ fn add_path_to_index(
         env_hash,
         file_name.display()
     );
-    if let Ok(cached) = cache_dir.place_cache_file(file_name) {
+    if let Ok(cached) = cache_file(account_name, &file_name) {
         debug!("putting in cache");
         /* place result in cache directory */
         let f = fs::File::create(cached)?;
@@ -1381,3 +1721,67 @@ fn add_path_to_index(
     }
     Ok(env)
 }
+
+#[test]
+fn test_maildir_mailbox_operations() {
+    use futures::executor::block_on;
+    for layout in &["fs", "maildir++"] {
+        let root = std::env::temp_dir().join(format!(
+            "meli-test-maildir-{}-{}",
+            std::process::id(),
+            layout
+        ));
+        let _ = fs::remove_dir_all(&root);
+        for d in &["cur", "new", "tmp"] {
+            fs::create_dir_all(root.join(d)).unwrap();
+        }
+        let mut settings = AccountSettings {
+            name: "test".to_string(),
+            root_mailbox: root.display().to_string(),
+            format: "maildir".to_string(),
+            ..Default::default()
+        };
+        settings
+            .extra
+            .insert("layout".to_string(), layout.to_string());
+        let mut backend = MaildirType::new(
+            &settings,
+            Box::new(|_| true),
+            BackendEventConsumer::new(Arc::new(|_, _| {})),
+        )
+        .unwrap();
+
+        let (hash, mailboxes) =
+            block_on(backend.create_mailbox("Lists".to_string()).unwrap()).unwrap();
+        assert_eq!(mailboxes[&hash].name(), "Lists");
+        let fs_path = if *layout == "fs" {
+            root.join("Lists")
+        } else {
+            root.join(".Lists")
+        };
+        for d in &["cur", "new", "tmp"] {
+            assert!(fs_path.join(d).is_dir());
+        }
+        assert!(backend.create_mailbox("Lists".to_string()).is_err());
+        assert!(backend.create_mailbox("../Lists".to_string()).is_err());
+
+        let renamed =
+            block_on(backend.rename_mailbox(hash, "Archive".to_string()).unwrap()).unwrap();
+        assert_eq!(renamed.name(), "Archive");
+        assert!(!fs_path.exists());
+        let mailboxes = block_on(backend.mailboxes().unwrap()).unwrap();
+        assert!(!mailboxes.contains_key(&hash));
+        assert!(mailboxes.contains_key(&renamed.hash()));
+
+        let root_hash = mailboxes
+            .values()
+            .find(|m| m.parent().is_none())
+            .unwrap()
+            .hash();
+        assert!(backend.delete_mailbox(root_hash).is_err());
+        let mailboxes = block_on(backend.delete_mailbox(renamed.hash()).unwrap()).unwrap();
+        assert_eq!(mailboxes.len(), 1);
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
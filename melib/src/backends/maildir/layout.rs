@@ -0,0 +1,364 @@
+/*
+ * meli - mailbox module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Folder layouts and metadata files of Maildir++ (Courier) and Dovecot maildir stores.
+
+use crate::conf::AccountSettings;
+use crate::error::{MeliError, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How mailboxes are laid out under the root maildir.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaildirLayout {
+    /// Subfolders are nested directories, eg `Lists/rust/cur`, as in Dovecot's `LAYOUT=fs`.
+    Fs,
+    /// Subfolders are dot-separated directories directly under the root maildir, which is the
+    /// inbox, eg `.Lists.rust/cur`.
+    MaildirPlusPlus,
+}
+
+impl Default for MaildirLayout {
+    fn default() -> Self {
+        MaildirLayout::Fs
+    }
+}
+
+impl MaildirLayout {
+    pub fn new(s: &AccountSettings) -> Result<Self> {
+        match s.extra.get("layout").map(String::as_str) {
+            None | Some("fs") => Ok(MaildirLayout::Fs),
+            Some("maildir++") => Ok(MaildirLayout::MaildirPlusPlus),
+            Some(other) => Err(MeliError::new(format!(
+                "Configuration error ({}): Invalid value for field `layout`: {}. Valid values are `fs` and `maildir++`.",
+                s.name.as_str(),
+                other
+            ))),
+        }
+    }
+
+    /// Hierarchy separator used by IMAP servers for this layout, for example in subscription
+    /// files.
+    pub fn separator(self) -> char {
+        match self {
+            MaildirLayout::Fs => '/',
+            MaildirLayout::MaildirPlusPlus => '.',
+        }
+    }
+}
+
+/// Hierarchy of a Maildir++ subfolder from its directory name, eg `.Lists.rust` becomes
+/// `["Lists", "rust"]`. Returns `None` for directories that are not subfolders.
+pub fn maildirpp_folder_components(dir_name: &str) -> Option<Vec<&str>> {
+    if !dir_name.starts_with('.') || dir_name == "." || dir_name == ".." {
+        return None;
+    }
+    let components = dir_name[1..].split('.').collect::<Vec<&str>>();
+    if components.iter().any(|c| c.is_empty()) {
+        return None;
+    }
+    Some(components)
+}
+
+/// Directory name of a Maildir++ subfolder from its `/` separated path relative to the inbox,
+/// eg `Lists/rust` becomes `.Lists.rust`.
+pub fn maildirpp_dir_name(path: &str) -> Result<String> {
+    let components = path
+        .split('/')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<&str>>();
+    if components.is_empty() {
+        return Err(MeliError::new("Mailbox name is empty."));
+    }
+    if components.iter().any(|c| c.contains('.')) {
+        return Err(MeliError::new(format!(
+            "`{}` contains a `.`, which is the hierarchy separator in Maildir++ stores.",
+            path
+        )));
+    }
+    Ok(format!(".{}", components.join(".")))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SubscriptionsFormat {
+    /// Dovecot's `subscriptions` file, one mailbox per line.
+    DovecotV1,
+    /// Dovecot's `subscriptions` file with a `V\t2` header and tab separated hierarchies.
+    DovecotV2,
+    /// Courier's `courierimapsubscribed` file, one `INBOX.` prefixed mailbox per line.
+    Courier,
+}
+
+/// Subscribed mailboxes as recorded by the IMAP server sharing the maildir store. Mailbox names
+/// are kept `/` separated and relative to the root maildir, which is named `INBOX`.
+#[derive(Debug, Clone)]
+pub struct SubscriptionsFile {
+    path: PathBuf,
+    format: SubscriptionsFormat,
+    separator: char,
+    names: Vec<String>,
+}
+
+impl SubscriptionsFile {
+    /// Read the subscriptions file of the store at `root`. If there isn't one, a Dovecot
+    /// subscriptions file is created on the first change.
+    pub fn open(root: &Path, layout: MaildirLayout) -> Result<Self> {
+        let courier = root.join("courierimapsubscribed");
+        let (path, format) = if !root.join("subscriptions").exists() && courier.exists() {
+            (courier, SubscriptionsFormat::Courier)
+        } else {
+            (root.join("subscriptions"), SubscriptionsFormat::DovecotV2)
+        };
+        let mut ret = SubscriptionsFile {
+            path,
+            format,
+            separator: layout.separator(),
+            names: vec![],
+        };
+        if ret.path.exists() {
+            ret.parse(&fs::read_to_string(&ret.path)?);
+        }
+        Ok(ret)
+    }
+
+    fn parse(&mut self, s: &str) {
+        let mut lines = s.lines().peekable();
+        if self.format != SubscriptionsFormat::Courier {
+            if lines.peek() == Some(&"V\t2") {
+                self.format = SubscriptionsFormat::DovecotV2;
+                lines.next();
+            } else {
+                self.format = SubscriptionsFormat::DovecotV1;
+            }
+        }
+        for line in lines {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let name = match self.format {
+                SubscriptionsFormat::DovecotV2 => line.replace('\t', "/"),
+                SubscriptionsFormat::DovecotV1 => line.replace(self.separator, "/"),
+                SubscriptionsFormat::Courier => {
+                    if line == "INBOX" {
+                        line.to_string()
+                    } else if line.starts_with("INBOX.") {
+                        line["INBOX.".len()..].replace('.', "/")
+                    } else {
+                        continue;
+                    }
+                }
+            };
+            if !self.names.contains(&name) {
+                self.names.push(name);
+            }
+        }
+    }
+
+    fn serialize(&self) -> String {
+        let mut ret = String::new();
+        if self.format == SubscriptionsFormat::DovecotV2 {
+            ret.push_str("V\t2\n\n");
+        }
+        for name in &self.names {
+            match self.format {
+                SubscriptionsFormat::DovecotV2 => ret.push_str(&name.replace('/', "\t")),
+                SubscriptionsFormat::DovecotV1 => {
+                    ret.push_str(&name.replace('/', &self.separator.to_string()))
+                }
+                SubscriptionsFormat::Courier if name == "INBOX" => ret.push_str(name),
+                SubscriptionsFormat::Courier => {
+                    ret.push_str("INBOX.");
+                    ret.push_str(&name.replace('/', "."));
+                }
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name == "INBOX" || self.names.iter().any(|n| n == name)
+    }
+
+    /// Add or remove `name` and write the file back.
+    pub fn set(&mut self, name: &str, val: bool) -> Result<()> {
+        if val == self.names.iter().any(|n| n == name) {
+            return Ok(());
+        }
+        if val {
+            self.names.push(name.to_string());
+        } else {
+            self.names.retain(|n| n != name);
+        }
+        /* Write to a temporary file first so that a concurrent reader never sees a partial
+         * file. */
+        let mut tmp = self.path.clone();
+        tmp.set_extension("lock");
+        {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(self.serialize().as_bytes())?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Dovecot's `dovecot-uidlist` file, which assigns IMAP UIDs to the messages of a maildir.
+///
+/// Unlike file names, UIDs don't change when a message's flags change, so they can be used as
+/// stable keys for cached envelopes.
+#[derive(Debug, Default, Clone)]
+pub struct Uidlist {
+    pub uid_validity: u64,
+    /// Base file name (without the `:2,` info suffix) to UID.
+    pub uids: HashMap<String, u64>,
+}
+
+impl Uidlist {
+    /// Read the uidlist of the maildir at `path`, if it has one.
+    pub fn open(path: &Path) -> Option<Self> {
+        let s = fs::read_to_string(path.join("dovecot-uidlist")).ok()?;
+        Self::parse(&s)
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut lines = s.lines();
+        let mut header = lines.next()?.split_whitespace();
+        let version = header.next()?;
+        let uid_validity = match version {
+            "1" | "2" => header.next()?.parse::<u64>().ok()?,
+            "3" => header
+                .find(|f| f.starts_with('V'))
+                .and_then(|f| f[1..].parse::<u64>().ok())?,
+            _ => return None,
+        };
+        let mut uids = HashMap::default();
+        for line in lines {
+            let mut fields = line.splitn(2, ' ');
+            let uid = match fields.next().and_then(|uid| uid.parse::<u64>().ok()) {
+                Some(uid) => uid,
+                None => continue,
+            };
+            let rest = fields.next().unwrap_or_default();
+            /* Version 3 lines may have extension fields before the file name, which is prefixed
+             * with a colon. */
+            let file_name = if version == "3" {
+                match rest.find(':') {
+                    Some(pos) => &rest[pos + 1..],
+                    None => continue,
+                }
+            } else {
+                rest.trim()
+            };
+            let base = file_name.splitn(2, ':').next().unwrap_or_default();
+            if !base.is_empty() {
+                uids.insert(base.to_string(), uid);
+            }
+        }
+        Some(Uidlist { uid_validity, uids })
+    }
+
+    /// UID of the message at `path`, if it's known.
+    pub fn uid(&self, path: &Path) -> Option<u64> {
+        let file_name = path.file_name()?.to_str()?;
+        let base = file_name.splitn(2, ':').next()?;
+        self.uids.get(base).cloned()
+    }
+}
+
+#[test]
+fn test_maildir_layout_maildirpp_names() {
+    assert_eq!(
+        maildirpp_folder_components(".Lists.rust"),
+        Some(vec!["Lists", "rust"])
+    );
+    assert_eq!(maildirpp_folder_components(".Sent"), Some(vec!["Sent"]));
+    assert_eq!(maildirpp_folder_components("cur"), None);
+    assert_eq!(maildirpp_folder_components(".."), None);
+    assert_eq!(maildirpp_folder_components(".a..b"), None);
+    assert_eq!(maildirpp_dir_name("Lists/rust").unwrap(), ".Lists.rust");
+    assert!(maildirpp_dir_name("Lists/v1.0").is_err());
+}
+
+#[test]
+fn test_maildir_layout_subscriptions() {
+    let mut subs = SubscriptionsFile {
+        path: PathBuf::new(),
+        format: SubscriptionsFormat::DovecotV2,
+        separator: '.',
+        names: vec![],
+    };
+    subs.parse("V\t2\n\nSent\nLists\trust\n");
+    assert_eq!(subs.format, SubscriptionsFormat::DovecotV2);
+    assert!(subs.contains("Lists/rust"));
+    assert!(subs.contains("INBOX"));
+    assert!(!subs.contains("Lists"));
+    assert_eq!(subs.serialize(), "V\t2\n\nSent\nLists\trust\n");
+
+    let mut subs = SubscriptionsFile {
+        path: PathBuf::new(),
+        format: SubscriptionsFormat::DovecotV2,
+        separator: '.',
+        names: vec![],
+    };
+    subs.parse("Sent\nLists.rust\n");
+    assert_eq!(subs.format, SubscriptionsFormat::DovecotV1);
+    assert!(subs.contains("Lists/rust"));
+
+    let mut subs = SubscriptionsFile {
+        path: PathBuf::new(),
+        format: SubscriptionsFormat::Courier,
+        separator: '.',
+        names: vec![],
+    };
+    subs.parse("INBOX\nINBOX.Lists.rust\n");
+    assert!(subs.contains("Lists/rust"));
+    assert_eq!(subs.serialize(), "INBOX\nINBOX.Lists.rust\n");
+}
+
+#[test]
+fn test_maildir_layout_uidlist() {
+    let list = Uidlist::parse(
+        "3 V1275660208 N25022 G3085f01b7f11094c501100008c4a11c1\n\
+         25006 :1276528487.M364837P9451.kurkku,S=1355,W=1394:2,S\n\
+         25017 W2481 :1276533073.M242911P3632.kurkku:2,F\n",
+    )
+    .unwrap();
+    assert_eq!(list.uid_validity, 1275660208);
+    assert_eq!(
+        list.uid(Path::new(
+            "/Mail/cur/1276528487.M364837P9451.kurkku,S=1355,W=1394:2,RS"
+        )),
+        Some(25006)
+    );
+    assert_eq!(
+        list.uid(Path::new("/Mail/new/1276533073.M242911P3632.kurkku")),
+        Some(25017)
+    );
+    assert_eq!(list.uid(Path::new("/Mail/cur/unknown:2,")), None);
+
+    let list = Uidlist::parse("1 1000 3\n1 a.b.c:2,S\n2 d.e.f\n").unwrap();
+    assert_eq!(list.uid_validity, 1000);
+    assert_eq!(list.uid(Path::new("d.e.f:2,S")), Some(2));
+}
@@ -51,6 +51,7 @@ impl MaildirStream {
         mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
         let chunk_size = 2048;
+        let uidlist = Uidlist::open(&path).map(Arc::new);
         path.push("new");
         for d in path.read_dir()? {
             if let Ok(p) = d {
@@ -73,16 +74,16 @@ impl MaildirStream {
             files
                 .chunks(chunk_size)
                 .map(|chunk| {
-                    let cache_dir = xdg::BaseDirectories::with_profile("meli", &name).unwrap();
                     Box::pin(Self::chunk(
                         SmallVec::from(chunk),
-                        cache_dir,
+                        name.to_string(),
                         mailbox_hash,
                         unseen.clone(),
                         total.clone(),
                         root_path.clone(),
                         map.clone(),
                         mailbox_index.clone(),
+                        uidlist.clone(),
                     )) as Pin<Box<dyn Future<Output = _> + Send + 'static>>
                 })
                 .collect::<_>()
@@ -94,13 +95,14 @@ impl MaildirStream {
 
     async fn chunk(
         chunk: SmallVec<[std::path::PathBuf; 2048]>,
-        cache_dir: xdg::BaseDirectories,
+        account_name: String,
         mailbox_hash: MailboxHash,
         unseen: Arc<Mutex<usize>>,
         total: Arc<Mutex<usize>>,
         root_path: PathBuf,
         map: HashIndexes,
         mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
+        uidlist: Option<Arc<Uidlist>>,
     ) -> Result<Vec<Envelope>> {
         let mut local_r: Vec<Envelope> = Vec::with_capacity(chunk.len());
         let mut unseen_total: usize = 0;
@@ -108,11 +110,24 @@ impl MaildirStream {
         for file in chunk {
             /* Check if we have a cache file with this email's
              * filename */
-            let file_name = PathBuf::from(&file)
+            let mut file_name = PathBuf::from(&file)
                 .strip_prefix(&root_path)
                 .unwrap()
                 .to_path_buf();
-            if let Some(cached) = cache_dir.find_cache_file(&file_name) {
+            /* Messages known to Dovecot are cached by their UID instead, which unlike the
+             * filename does not change when their flags do. */
+            let uid = uidlist
+                .as_ref()
+                .and_then(|l| l.uid(&file).map(|uid| (l.uid_validity, uid)));
+            if let Some((uid_validity, uid)) = uid {
+                file_name.pop();
+                file_name.pop();
+                file_name.push(format!("dovecot-uid.{}.{}", uid_validity, uid));
+            }
+            if let Some(cached) = cache_file(&account_name, &file_name)
+                .ok()
+                .filter(|cached| cached.exists())
+            {
                 /* Cached struct exists, try to load it */
                 let cached_file = fs::File::open(&cached)?;
                 let filesize = cached_file.metadata()?.len();
@@ -124,7 +139,11 @@ impl MaildirStream {
                     ),
                     reader,
                 );
-                if let Ok(env) = result {
+                if let Ok(mut env) = result {
+                    if uid.is_some() {
+                        env.set_hash(get_file_hash(&file));
+                        env.set_flags(file.flags());
+                    }
                     let mut map = map.lock().unwrap();
                     let map = map.entry(mailbox_hash).or_default();
                     let hash = env.hash();
@@ -152,7 +171,7 @@ impl MaildirStream {
                 Ok(mut env) => {
                     env.set_hash(env_hash);
                     mailbox_index.lock().unwrap().insert(env_hash, mailbox_hash);
-                    if let Ok(cached) = cache_dir.place_cache_file(file_name) {
+                    if let Ok(cached) = cache_file(&account_name, &file_name) {
                         /* place result in cache directory */
                         let f = fs::File::create(cached)?;
                         let metadata = f.metadata()?;
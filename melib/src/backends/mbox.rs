@@ -838,6 +838,7 @@ impl MailBackend for MboxType {
             is_remote: false,
             supports_search: false,
             extensions: None,
+            actions: &[],
             supports_tags: false,
             supports_submission: false,
         };
@@ -0,0 +1,821 @@
+/*
+ * meli - mailbox module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # MH mail directories
+//!
+//! The mail store of `nmh` and `mh-e`: every folder is a directory and every message a file
+//! named after its number in the folder. Message state is kept in the public sequences of the
+//! folder's `.mh_sequences` file; unread messages are in the unseen sequence.
+//!
+//! ## Resources
+//!
+//! - [0] <https://www.nongnu.org/nmh/>
+//! - [1] `mh-sequence(5)`
+
+mod sequences;
+pub use self::sequences::*;
+
+use crate::backends::*;
+use crate::collection::Collection;
+use crate::conf::AccountSettings;
+use crate::email::*;
+use crate::error::{MeliError, Result};
+use crate::get_path_hash;
+use crate::shellexpand::ShellExpandTrait;
+
+extern crate notify;
+use self::notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::collections::HashSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex, RwLock};
+
+#[derive(Debug, Clone)]
+struct MhMailbox {
+    hash: MailboxHash,
+    name: String,
+    path: PathBuf,
+    fs_path: PathBuf,
+    children: Vec<MailboxHash>,
+    parent: Option<MailboxHash>,
+    usage: Arc<RwLock<SpecialUsageMailbox>>,
+    is_subscribed: bool,
+    permissions: MailboxPermissions,
+    total: Arc<Mutex<usize>>,
+    unseen: Arc<Mutex<usize>>,
+    /// Message number and flags of every loaded message.
+    index: Arc<Mutex<HashMap<EnvelopeHash, (u32, Flag)>>>,
+    /// Changes are only reported for folders whose messages have been loaded.
+    loaded: bool,
+}
+
+impl BackendMailbox for MhMailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn path(&self) -> &str {
+        self.path.to_str().unwrap_or(self.name())
+    }
+
+    fn change_name(&mut self, s: &str) {
+        self.name = s.to_string();
+    }
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &self.children
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        self.parent
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        *self.usage.read().unwrap()
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        self.permissions
+    }
+    fn is_subscribed(&self) -> bool {
+        self.is_subscribed
+    }
+    fn set_is_subscribed(&mut self, new_val: bool) -> Result<()> {
+        self.is_subscribed = new_val;
+        Ok(())
+    }
+    fn set_special_usage(&mut self, new_val: SpecialUsageMailbox) -> Result<()> {
+        *self.usage.write()? = new_val;
+        Ok(())
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok((*self.unseen.lock()?, *self.total.lock()?))
+    }
+}
+
+/// `BackendOp` implementor for MH
+#[derive(Debug, Clone)]
+pub struct MhOp {
+    folder: PathBuf,
+    number: u32,
+    unseen_sequence: String,
+}
+
+impl MhOp {
+    pub fn new(folder: PathBuf, number: u32, unseen_sequence: String) -> Self {
+        MhOp {
+            folder,
+            number,
+            unseen_sequence,
+        }
+    }
+}
+
+impl BackendOp for MhOp {
+    fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
+        let ret = fs::read(self.folder.join(self.number.to_string()))?;
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+
+    fn fetch_flags(&self) -> ResultFuture<Flag> {
+        let ret = Sequences::open(&self.folder)?.flags(&self.unseen_sequence, self.number);
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+}
+
+/// Numbers of the messages in the folder at `path`, in ascending order.
+fn message_numbers(path: &Path) -> Result<Vec<u32>> {
+    let mut ret = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        /* Skips subfolders as well as messages deleted by nmh, which are renamed to `,N`. */
+        if let Some(n) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            if entry.path().is_file() {
+                ret.push(n);
+            }
+        }
+    }
+    ret.sort_unstable();
+    Ok(ret)
+}
+
+/// The hash of the message at `path`.
+fn envelope_hash(path: &Path) -> EnvelopeHash {
+    let mut hasher = EnvelopeHasher::new();
+    path.hash(&mut hasher);
+    hasher.envelope_hash()
+}
+
+fn read_envelope(folder: &Path, number: u32, flags: Flag) -> Result<Envelope> {
+    let path = folder.join(number.to_string());
+    let bytes = fs::read(&path)?;
+    let mut env = Envelope::from_bytes(&bytes, Some(flags))?;
+    env.set_hash(envelope_hash(&path));
+    Ok(env)
+}
+
+/// Add a message to the folder at `path` as the one after the highest numbered message.
+fn add_message(path: &Path, bytes: &[u8], flags: Flag, unseen_sequence: &str) -> Result<u32> {
+    let mut number = message_numbers(path)?.last().cloned().unwrap_or(0) + 1;
+    /* Another program may be adding messages at the same time, so never overwrite one. */
+    let mut file = loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path.join(number.to_string()))
+        {
+            Ok(file) => break file,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => number += 1,
+            Err(err) => return Err(err.into()),
+        }
+    };
+    file.write_all(bytes)?;
+    let mut sequences = Sequences::open(path)?;
+    sequences.set_flags(unseen_sequence, number, flags);
+    sequences.save(path)?;
+    Ok(number)
+}
+
+/// Compare the messages of a loaded folder with its index and return the changes.
+fn rescan(
+    mailbox: &MhMailbox,
+    mailbox_index: &Mutex<HashMap<EnvelopeHash, MailboxHash>>,
+    unseen_sequence: &str,
+) -> Result<Vec<RefreshEventKind>> {
+    if !mailbox.loaded {
+        return Ok(vec![]);
+    }
+    let sequences = Sequences::open(&mailbox.fs_path)?;
+    let mut index = mailbox.index.lock().unwrap();
+    let mut current: HashSet<EnvelopeHash> = HashSet::default();
+    let mut ret = vec![];
+    for number in message_numbers(&mailbox.fs_path)? {
+        let env_hash = envelope_hash(&mailbox.fs_path.join(number.to_string()));
+        let flags = sequences.flags(unseen_sequence, number);
+        current.insert(env_hash);
+        if let Some((_, old_flags)) = index.get_mut(&env_hash) {
+            if *old_flags != flags {
+                *old_flags = flags;
+                ret.push(RefreshEventKind::NewFlags(env_hash, (flags, vec![])));
+            }
+            continue;
+        }
+        match read_envelope(&mailbox.fs_path, number, flags) {
+            Ok(env) => {
+                index.insert(env_hash, (number, flags));
+                mailbox_index.lock().unwrap().insert(env_hash, mailbox.hash);
+                ret.push(RefreshEventKind::Create(Box::new(env)));
+            }
+            Err(err) => {
+                debug!(
+                    "Could not parse MH message {} in {}: {}",
+                    number,
+                    mailbox.fs_path.display(),
+                    err
+                );
+            }
+        }
+    }
+    index.retain(|env_hash, _| {
+        if current.contains(env_hash) {
+            true
+        } else {
+            ret.push(RefreshEventKind::Remove(*env_hash));
+            false
+        }
+    });
+    *mailbox.total.lock().unwrap() = index.len();
+    *mailbox.unseen.lock().unwrap() = index
+        .values()
+        .filter(|(_, flags)| !flags.contains(Flag::SEEN))
+        .count();
+    Ok(ret)
+}
+
+/// MH mail directory backend
+#[derive(Debug)]
+pub struct MhType {
+    account_hash: AccountHash,
+    path: PathBuf,
+    unseen_sequence: String,
+    collection: Collection,
+    mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
+    mailboxes: Arc<Mutex<HashMap<MailboxHash, MhMailbox>>>,
+    event_consumer: BackendEventConsumer,
+}
+
+impl MailBackend for MhType {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        const CAPABILITIES: MailBackendCapabilities = MailBackendCapabilities {
+            is_async: false,
+            is_remote: false,
+            supports_search: false,
+            extensions: None,
+            actions: &[],
+            supports_tags: false,
+            supports_submission: false,
+        };
+        CAPABILITIES
+    }
+
+    fn is_online(&self) -> ResultFuture<()> {
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn fetch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let mailboxes = self.mailboxes.clone();
+        let mailbox_index = self.mailbox_index.clone();
+        let unseen_sequence = self.unseen_sequence.clone();
+        let (fs_path, index, total, unseen) = {
+            let mailboxes_lck = mailboxes.lock().unwrap();
+            let mailbox = mailboxes_lck
+                .get(&mailbox_hash)
+                .ok_or_else(|| MeliError::new("Invalid mailbox hash"))?;
+            (
+                mailbox.fs_path.clone(),
+                mailbox.index.clone(),
+                mailbox.total.clone(),
+                mailbox.unseen.clone(),
+            )
+        };
+        let sequences = Sequences::open(&fs_path)?;
+        let numbers = message_numbers(&fs_path)?;
+        index.lock().unwrap().clear();
+        *total.lock().unwrap() = 0;
+        *unseen.lock().unwrap() = 0;
+        let load_chunk = move |chunk: &[u32]| {
+            let mut payload = Vec::with_capacity(chunk.len());
+            for &number in chunk {
+                let flags = sequences.flags(&unseen_sequence, number);
+                match read_envelope(&fs_path, number, flags) {
+                    Ok(env) => {
+                        index.lock().unwrap().insert(env.hash(), (number, flags));
+                        mailbox_index
+                            .lock()
+                            .unwrap()
+                            .insert(env.hash(), mailbox_hash);
+                        if !flags.contains(Flag::SEEN) {
+                            *unseen.lock().unwrap() += 1;
+                        }
+                        payload.push(env);
+                    }
+                    Err(err) => {
+                        debug!(
+                            "Could not parse MH message {} in {}: {}",
+                            number,
+                            fs_path.display(),
+                            err
+                        );
+                    }
+                }
+            }
+            payload
+        };
+        Ok(Box::pin(async_stream::try_stream! {
+            for chunk in numbers.chunks(250) {
+                let payload = load_chunk(chunk);
+                *total.lock().unwrap() += payload.len();
+                yield payload;
+            }
+            if let Some(mailbox) = mailboxes.lock().unwrap().get_mut(&mailbox_hash) {
+                mailbox.loaded = true;
+            }
+        }))
+    }
+
+    fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let sender = self.event_consumer.clone();
+        let account_hash = self.account_hash;
+        let mailboxes = self.mailboxes.clone();
+        let mailbox_index = self.mailbox_index.clone();
+        let unseen_sequence = self.unseen_sequence.clone();
+        Ok(Box::pin(async move {
+            let events = {
+                let mailboxes_lck = mailboxes.lock().unwrap();
+                let mailbox = mailboxes_lck
+                    .get(&mailbox_hash)
+                    .ok_or_else(|| MeliError::new("Invalid mailbox hash"))?;
+                rescan(mailbox, &mailbox_index, &unseen_sequence)?
+            };
+            for kind in events {
+                (sender)(
+                    account_hash,
+                    BackendEvent::Refresh(RefreshEvent {
+                        account_hash,
+                        mailbox_hash,
+                        kind,
+                    }),
+                );
+            }
+            Ok(())
+        }))
+    }
+
+    fn watch(&self) -> ResultFuture<()> {
+        let sender = self.event_consumer.clone();
+        let (tx, rx) = channel();
+        let mut watcher = watcher(tx, std::time::Duration::from_secs(2))
+            .map_err(|e| e.to_string())
+            .map_err(MeliError::new)?;
+        watcher
+            .watch(&self.path, RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())
+            .map_err(MeliError::new)?;
+        debug!("watching {:?}", self.path.as_path());
+        let account_hash = self.account_hash;
+        let mailboxes = self.mailboxes.clone();
+        let mailbox_index = self.mailbox_index.clone();
+        let unseen_sequence = self.unseen_sequence.clone();
+        Ok(Box::pin(async move {
+            // Move `watcher` in the closure's scope so that it doesn't get dropped.
+            let _watcher = watcher;
+            loop {
+                /* Rather than interpreting each event, find the folders that changed and compare
+                 * their contents with what we know. This also catches changes to
+                 * `.mh_sequences`. */
+                let paths = match rx.recv() {
+                    Ok(DebouncedEvent::Create(pathbuf))
+                    | Ok(DebouncedEvent::Write(pathbuf))
+                    | Ok(DebouncedEvent::NoticeRemove(pathbuf))
+                    | Ok(DebouncedEvent::Remove(pathbuf)) => vec![pathbuf],
+                    Ok(DebouncedEvent::Rename(src, dest)) => vec![src, dest],
+                    Ok(DebouncedEvent::Rescan) => mailboxes
+                        .lock()
+                        .unwrap()
+                        .values()
+                        .map(|m| m.fs_path.clone())
+                        .collect(),
+                    Ok(_) => continue,
+                    Err(e) => {
+                        debug!("watch error: {:?}", e);
+                        return Err(MeliError::new(e.to_string()));
+                    }
+                };
+                let mailboxes_lck = mailboxes.lock().unwrap();
+                let mut changed: Vec<MailboxHash> = vec![];
+                for pathbuf in paths {
+                    if let Some(mailbox) = mailboxes_lck.values().find(|m| {
+                        m.fs_path == pathbuf || Some(m.fs_path.as_path()) == pathbuf.parent()
+                    }) {
+                        if !changed.contains(&mailbox.hash) {
+                            changed.push(mailbox.hash);
+                        }
+                    }
+                }
+                for mailbox_hash in changed {
+                    match rescan(
+                        &mailboxes_lck[&mailbox_hash],
+                        &mailbox_index,
+                        &unseen_sequence,
+                    ) {
+                        Ok(events) => {
+                            for kind in events {
+                                (sender)(
+                                    account_hash,
+                                    BackendEvent::Refresh(RefreshEvent {
+                                        account_hash,
+                                        mailbox_hash,
+                                        kind,
+                                    }),
+                                );
+                            }
+                        }
+                        Err(err) => {
+                            debug!("Could not rescan MH folder: {}", err);
+                        }
+                    }
+                }
+            }
+        }))
+    }
+
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let ret = Ok(self
+            .mailboxes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(h, f)| (*h, BackendMailbox::clone(f)))
+            .collect());
+        Ok(Box::pin(async { ret }))
+    }
+
+    fn operation(&self, env_hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        let mailbox_hash = *self
+            .mailbox_index
+            .lock()
+            .unwrap()
+            .get(&env_hash)
+            .ok_or_else(|| MeliError::new(format!("Message with hash {} not found", env_hash)))?;
+        let mailboxes_lck = self.mailboxes.lock().unwrap();
+        let mailbox = &mailboxes_lck[&mailbox_hash];
+        let number = mailbox
+            .index
+            .lock()
+            .unwrap()
+            .get(&env_hash)
+            .map(|(number, _)| *number)
+            .ok_or_else(|| MeliError::new(format!("Message with hash {} not found", env_hash)))?;
+        Ok(Box::new(MhOp::new(
+            mailbox.fs_path.clone(),
+            number,
+            self.unseen_sequence.clone(),
+        )))
+    }
+
+    fn save(
+        &self,
+        bytes: Vec<u8>,
+        mailbox_hash: MailboxHash,
+        flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        let path = self
+            .mailboxes
+            .lock()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| m.fs_path.clone())
+            .ok_or_else(|| MeliError::new("Invalid mailbox hash"))?;
+        let unseen_sequence = self.unseen_sequence.clone();
+        Ok(Box::pin(async move {
+            add_message(&path, &bytes, flags.unwrap_or_default(), &unseen_sequence)?;
+            Ok(())
+        }))
+    }
+
+    fn copy_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        source_mailbox_hash: MailboxHash,
+        destination_mailbox_hash: MailboxHash,
+        move_: bool,
+    ) -> ResultFuture<()> {
+        let mailboxes_lck = self.mailboxes.lock().unwrap();
+        let (src_path, src_index) = mailboxes_lck
+            .get(&source_mailbox_hash)
+            .map(|m| (m.fs_path.clone(), m.index.clone()))
+            .ok_or_else(|| MeliError::new("Invalid source mailbox hash"))?;
+        let dest_path = mailboxes_lck
+            .get(&destination_mailbox_hash)
+            .map(|m| m.fs_path.clone())
+            .ok_or_else(|| MeliError::new("Invalid destination mailbox hash"))?;
+        let unseen_sequence = self.unseen_sequence.clone();
+        Ok(Box::pin(async move {
+            let mut src_sequences = Sequences::open(&src_path)?;
+            for env_hash in env_hashes.iter() {
+                let number = match src_index.lock().unwrap().get(&env_hash) {
+                    Some((number, _)) => *number,
+                    None => continue,
+                };
+                let src_file = src_path.join(number.to_string());
+                let bytes = fs::read(&src_file)?;
+                add_message(
+                    &dest_path,
+                    &bytes,
+                    src_sequences.flags(&unseen_sequence, number),
+                    &unseen_sequence,
+                )?;
+                if move_ {
+                    fs::remove_file(&src_file)?;
+                    src_sequences.remove_message(number);
+                }
+            }
+            if move_ {
+                src_sequences.save(&src_path)?;
+            }
+            Ok(())
+        }))
+    }
+
+    fn set_flags(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        if flags.iter().any(|(f, _)| f.is_err()) {
+            return Err(MeliError::new("MH doesn't support tags."));
+        }
+        let (path, index, unseen) = self
+            .mailboxes
+            .lock()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| (m.fs_path.clone(), m.index.clone(), m.unseen.clone()))
+            .ok_or_else(|| MeliError::new("Invalid mailbox hash"))?;
+        let unseen_sequence = self.unseen_sequence.clone();
+        Ok(Box::pin(async move {
+            let mut sequences = Sequences::open(&path)?;
+            let mut index = index.lock().unwrap();
+            for env_hash in env_hashes.iter() {
+                let (number, env_flags) = match index.get_mut(&env_hash) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                for (f, value) in flags.iter() {
+                    env_flags.set(*f.as_ref().unwrap(), *value);
+                }
+                sequences.set_flags(&unseen_sequence, *number, *env_flags);
+            }
+            sequences.save(&path)?;
+            *unseen.lock().unwrap() = index
+                .values()
+                .filter(|(_, flags)| !flags.contains(Flag::SEEN))
+                .count();
+            Ok(())
+        }))
+    }
+
+    fn delete_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        let (path, index) = self
+            .mailboxes
+            .lock()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| (m.fs_path.clone(), m.index.clone()))
+            .ok_or_else(|| MeliError::new("Invalid mailbox hash"))?;
+        Ok(Box::pin(async move {
+            let mut sequences = Sequences::open(&path)?;
+            for env_hash in env_hashes.iter() {
+                let number = match index.lock().unwrap().get(&env_hash) {
+                    Some((number, _)) => *number,
+                    None => continue,
+                };
+                fs::remove_file(path.join(number.to_string()))?;
+                sequences.remove_message(number);
+            }
+            sequences.save(&path)?;
+            Ok(())
+        }))
+    }
+
+    fn create_mailbox(
+        &mut self,
+        new_path: String,
+    ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
+        let path = self.path.join(&new_path);
+        if !path.starts_with(&self.path) {
+            return Err(MeliError::new(format!("Path given (`{}`) is absolute. Please provide a path relative to the account's root mailbox.", &new_path)));
+        }
+        /* Like `folder -create`, the parent folder must already exist. */
+        fs::create_dir(&path)?;
+        let mailbox_hash = get_path_hash!(&path);
+        {
+            let mut mailboxes_lck = self.mailboxes.lock().unwrap();
+            let parent = mailboxes_lck
+                .values()
+                .find(|m| Some(m.fs_path.as_path()) == path.parent())
+                .map(|m| m.hash);
+            if let Some(parent) = parent {
+                mailboxes_lck
+                    .entry(parent)
+                    .and_modify(|m| m.children.push(mailbox_hash));
+            }
+            mailboxes_lck.insert(
+                mailbox_hash,
+                MhMailbox {
+                    hash: mailbox_hash,
+                    name: path
+                        .file_name()
+                        .map(|f| f.to_string_lossy().into())
+                        .unwrap_or_default(),
+                    path: PathBuf::from(&new_path),
+                    fs_path: path,
+                    children: vec![],
+                    parent,
+                    usage: Default::default(),
+                    is_subscribed: true,
+                    permissions: Default::default(),
+                    total: Default::default(),
+                    unseen: Default::default(),
+                    index: Default::default(),
+                    loaded: false,
+                },
+            );
+        }
+        let ret = self.mailboxes()?;
+        Ok(Box::pin(async move { Ok((mailbox_hash, ret.await?)) }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn collection(&self) -> Collection {
+        self.collection.clone()
+    }
+}
+
+macro_rules! get_conf_val {
+    ($s:ident[$var:literal], $default:expr) => {
+        $s.extra
+            .get($var)
+            .map(|v| {
+                <_>::from_str(v).map_err(|e| {
+                    MeliError::new(format!(
+                        "Configuration error ({}): Invalid value for field `{}`: {}\n{}",
+                        $s.name.as_str(),
+                        $var,
+                        v,
+                        e
+                    ))
+                })
+            })
+            .unwrap_or_else(|| Ok($default))
+    };
+}
+
+impl MhType {
+    pub fn new(
+        s: &AccountSettings,
+        is_subscribed: Box<dyn Fn(&str) -> bool>,
+        event_consumer: BackendEventConsumer,
+    ) -> Result<Box<dyn MailBackend>> {
+        fn recurse_folders(
+            mailboxes: &mut HashMap<MailboxHash, MhMailbox>,
+            root: &Path,
+            dir: &Path,
+            parent: Option<MailboxHash>,
+            is_subscribed: &dyn Fn(&str) -> bool,
+        ) -> Result<Vec<MailboxHash>> {
+            let mut children = vec![];
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let name: String = match path.file_name().and_then(|f| f.to_str()) {
+                    Some(name) if !name.starts_with('.') => name.to_string(),
+                    _ => continue,
+                };
+                if !path.is_dir() {
+                    continue;
+                }
+                let hash = get_path_hash!(&path);
+                /* Folders are named relative to the mail directory, eg `+lists/rust`. */
+                let folder_path = path.strip_prefix(root).unwrap().to_path_buf();
+                let read_only = if let Ok(metadata) = fs::metadata(&path) {
+                    metadata.permissions().readonly()
+                } else {
+                    true
+                };
+                let usage = if parent.is_none() {
+                    SpecialUsageMailbox::detect_usage(&name).unwrap_or_default()
+                } else {
+                    SpecialUsageMailbox::Normal
+                };
+                let grandchildren =
+                    recurse_folders(mailboxes, root, &path, Some(hash), is_subscribed)?;
+                mailboxes.insert(
+                    hash,
+                    MhMailbox {
+                        hash,
+                        is_subscribed: is_subscribed(folder_path.to_str().unwrap_or_default()),
+                        name,
+                        path: folder_path,
+                        fs_path: path,
+                        children: grandchildren,
+                        parent,
+                        usage: Arc::new(RwLock::new(usage)),
+                        permissions: MailboxPermissions {
+                            create_messages: !read_only,
+                            remove_messages: !read_only,
+                            set_flags: !read_only,
+                            create_child: !read_only,
+                            rename_messages: !read_only,
+                            delete_messages: !read_only,
+                            delete_mailbox: !read_only,
+                            change_permissions: false,
+                        },
+                        total: Arc::new(Mutex::new(0)),
+                        unseen: Arc::new(Mutex::new(0)),
+                        index: Default::default(),
+                        loaded: false,
+                    },
+                );
+                children.push(hash);
+            }
+            Ok(children)
+        }
+
+        Self::validate_config(s)?;
+        let path = Path::new(s.root_mailbox.as_str()).expand();
+        let mut mailboxes = HashMap::default();
+        recurse_folders(&mut mailboxes, &path, &path, None, &is_subscribed)?;
+        let account_hash = {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(s.name().as_bytes());
+            hasher.finish()
+        };
+        Ok(Box::new(MhType {
+            account_hash,
+            path,
+            unseen_sequence: get_conf_val!(s["unseen_sequence"], "unseen".to_string())?,
+            collection: Collection::default(),
+            mailbox_index: Default::default(),
+            mailboxes: Arc::new(Mutex::new(mailboxes)),
+            event_consumer,
+        }))
+    }
+
+    pub fn validate_config(s: &AccountSettings) -> Result<()> {
+        let path = Path::new(s.root_mailbox.as_str()).expand();
+        if !path.is_dir() {
+            return Err(MeliError::new(format!(
+                "Configuration error ({}): root_mailbox `{}` is not a directory.",
+                s.name(),
+                s.root_mailbox.as_str()
+            )));
+        }
+        let unseen_sequence: Result<String> =
+            get_conf_val!(s["unseen_sequence"], "unseen".to_string());
+        unseen_sequence?;
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * meli - mailbox module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Public sequences of an MH folder, kept in its `.mh_sequences` file:
+//!
+//! ```text
+//! cur: 12
+//! unseen: 3-5 9 11-14
+//! ```
+
+use crate::email::Flag;
+use crate::error::Result;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+pub const SEQUENCES_FILE: &str = ".mh_sequences";
+pub const FLAGGED_SEQUENCE: &str = "flagged";
+pub const REPLIED_SEQUENCE: &str = "replied";
+pub const FORWARDED_SEQUENCE: &str = "forwarded";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sequences {
+    /* Kept in file order so that rewriting the file doesn't shuffle other programs' sequences. */
+    sequences: Vec<(String, BTreeSet<u32>)>,
+}
+
+impl Sequences {
+    /// Read the sequences of the folder at `path`. A missing file means there are no sequences.
+    pub fn open(path: &Path) -> Result<Self> {
+        let path = path.join(SEQUENCES_FILE);
+        if !path.exists() {
+            return Ok(Sequences::default());
+        }
+        Ok(Self::parse(&fs::read_to_string(&path)?))
+    }
+
+    pub fn parse(s: &str) -> Self {
+        let mut ret = Sequences::default();
+        /* Long sequences may be folded on continuation lines that start with whitespace. */
+        let mut unfolded: Vec<String> = vec![];
+        for line in s.lines() {
+            if line.starts_with(|c: char| c.is_ascii_whitespace()) {
+                if let Some(last) = unfolded.last_mut() {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                }
+            } else if !line.trim().is_empty() {
+                unfolded.push(line.to_string());
+            }
+        }
+        for line in unfolded {
+            let colon = match line.find(':') {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let name = line[..colon].trim().to_string();
+            let mut members = BTreeSet::new();
+            for item in line[colon + 1..].split_whitespace() {
+                let mut range = item.splitn(2, '-');
+                let start = match range.next().and_then(|n| n.parse::<u32>().ok()) {
+                    Some(n) => n,
+                    None => continue,
+                };
+                let end = match range.next() {
+                    Some(n) => match n.parse::<u32>() {
+                        Ok(n) if n >= start => n,
+                        _ => continue,
+                    },
+                    None => start,
+                };
+                members.extend(start..=end);
+            }
+            ret.sequences.push((name, members));
+        }
+        ret
+    }
+
+    /// Write the sequences back to the folder at `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.join(SEQUENCES_FILE);
+        tmp.set_extension("lock");
+        {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(self.serialize().as_bytes())?;
+        }
+        fs::rename(&tmp, path.join(SEQUENCES_FILE))?;
+        Ok(())
+    }
+
+    fn serialize(&self) -> String {
+        let mut ret = String::new();
+        for (name, members) in &self.sequences {
+            if members.is_empty() {
+                continue;
+            }
+            ret.push_str(name);
+            ret.push(':');
+            let mut iter = members.iter().cloned().peekable();
+            while let Some(start) = iter.next() {
+                let mut end = start;
+                while iter.peek() == Some(&(end + 1)) {
+                    end = iter.next().unwrap();
+                }
+                if start == end {
+                    ret.push_str(&format!(" {}", start));
+                } else {
+                    ret.push_str(&format!(" {}-{}", start, end));
+                }
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    pub fn contains(&self, name: &str, n: u32) -> bool {
+        self.sequences
+            .iter()
+            .any(|(s, members)| s == name && members.contains(&n))
+    }
+
+    pub fn set(&mut self, name: &str, n: u32, val: bool) {
+        if let Some((_, members)) = self.sequences.iter_mut().find(|(s, _)| s == name) {
+            if val {
+                members.insert(n);
+            } else {
+                members.remove(&n);
+            }
+        } else if val {
+            let mut members = BTreeSet::new();
+            members.insert(n);
+            self.sequences.push((name.to_string(), members));
+        }
+    }
+
+    /// Remove message `n` from every sequence, eg after it's deleted.
+    pub fn remove_message(&mut self, n: u32) {
+        for (_, members) in self.sequences.iter_mut() {
+            members.remove(&n);
+        }
+    }
+
+    pub fn flags(&self, unseen_sequence: &str, n: u32) -> Flag {
+        let mut ret = Flag::default();
+        ret.set(Flag::SEEN, !self.contains(unseen_sequence, n));
+        ret.set(Flag::FLAGGED, self.contains(FLAGGED_SEQUENCE, n));
+        ret.set(Flag::REPLIED, self.contains(REPLIED_SEQUENCE, n));
+        ret.set(Flag::PASSED, self.contains(FORWARDED_SEQUENCE, n));
+        ret
+    }
+
+    pub fn set_flags(&mut self, unseen_sequence: &str, n: u32, flags: Flag) {
+        self.set(unseen_sequence, n, !flags.contains(Flag::SEEN));
+        self.set(FLAGGED_SEQUENCE, n, flags.contains(Flag::FLAGGED));
+        self.set(REPLIED_SEQUENCE, n, flags.contains(Flag::REPLIED));
+        self.set(FORWARDED_SEQUENCE, n, flags.contains(Flag::PASSED));
+    }
+}
+
+#[test]
+fn test_mh_sequences() {
+    let mut seqs = Sequences::parse("cur: 12\nunseen: 3-5 9\n 11-12\nflagged: 4\n");
+    assert!(seqs.contains("unseen", 4));
+    assert!(seqs.contains("unseen", 11));
+    assert!(!seqs.contains("unseen", 6));
+    assert_eq!(seqs.flags("unseen", 4), Flag::FLAGGED);
+    assert_eq!(seqs.flags("unseen", 2), Flag::SEEN);
+
+    seqs.set_flags("unseen", 10, Flag::REPLIED);
+    seqs.remove_message(4);
+    assert_eq!(seqs.serialize(), "cur: 12\nunseen: 3 5 9-12\nreplied: 10\n");
+    assert_eq!(
+        Sequences::parse(&seqs.serialize()).serialize(),
+        seqs.serialize()
+    );
+}
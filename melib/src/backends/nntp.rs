@@ -33,7 +33,7 @@ mod connection;
 pub use connection::*;
 
 use crate::conf::AccountSettings;
-use crate::connections::timeout;
+use crate::connections::{timeout, tls::TlsSettings};
 use crate::email::*;
 use crate::error::{MeliError, Result, ResultIntoMeliError};
 use crate::{backends::*, Collection};
@@ -62,7 +62,7 @@ pub struct NntpServerConf {
     pub use_starttls: bool,
     pub use_tls: bool,
     pub require_auth: bool,
-    pub danger_accept_invalid_certs: bool,
+    pub tls: TlsSettings,
     pub extension_use: NntpExtensionUse,
 }
 
@@ -170,6 +170,7 @@ impl MailBackend for NntpType {
             is_remote: true,
             supports_search: false,
             extensions: Some(extensions),
+            actions: &[],
             supports_tags: false,
             supports_submission: false,
         }
@@ -389,8 +390,7 @@ impl NntpType {
         let server_port = get_conf_val!(s["server_port"], 119)?;
         let use_tls = get_conf_val!(s["use_tls"], server_port == 563)?;
         let use_starttls = use_tls && get_conf_val!(s["use_starttls"], !(server_port == 563))?;
-        let danger_accept_invalid_certs: bool =
-            get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        let tls = TlsSettings::new(s)?;
         let require_auth = get_conf_val!(s["require_auth"], true)?;
         let server_conf = NntpServerConf {
             server_hostname: server_hostname.to_string(),
@@ -408,7 +408,7 @@ impl NntpType {
             server_port,
             use_tls,
             use_starttls,
-            danger_accept_invalid_certs,
+            tls,
             extension_use: NntpExtensionUse {
                 #[cfg(feature = "deflate_compression")]
                 deflate: get_conf_val!(s["use_deflate"], true)?,
@@ -527,6 +527,7 @@ impl NntpType {
             )));
         }
         get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        TlsSettings::new(s)?;
         Ok(())
     }
 
@@ -20,12 +20,11 @@
  */
 
 use crate::backends::{BackendMailbox, MailboxHash};
-use crate::connections::{lookup_ipv4, Connection};
+use crate::connections::{certificates::UntrustedCertificate, happy_eyeballs_connect, Connection};
 use crate::email::parser::BytesExt;
 use crate::error::*;
 extern crate native_tls;
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 pub use smol::Async as AsyncWrapper;
 use std::collections::HashSet;
 use std::future::Future;
@@ -81,118 +80,108 @@ pub struct NntpConnection {
 }
 
 impl NntpStream {
-    pub async fn new_connection(
-        server_conf: &NntpServerConf,
-    ) -> Result<(Capabilities, NntpStream)> {
-        use std::net::TcpStream;
+    /// Open the TCP connection to the server, and issue STARTTLS if it's configured.
+    async fn open_socket(server_conf: &NntpServerConf) -> Result<NntpStream> {
         let path = &server_conf.server_hostname;
 
-        let stream = {
-            let addr = lookup_ipv4(path, server_conf.server_port)?;
-            AsyncWrapper::new(Connection::Tcp(
-                TcpStream::connect_timeout(&addr, std::time::Duration::new(16, 0))
-                    .chain_err_kind(crate::error::ErrorKind::Network)?,
-            ))
-            .chain_err_kind(crate::error::ErrorKind::Network)?
-        };
+        let stream = AsyncWrapper::new(Connection::Tcp(
+            happy_eyeballs_connect(
+                path,
+                server_conf.server_port,
+                Some(std::time::Duration::new(16, 0)),
+            )
+            .await?,
+        ))
+        .chain_err_kind(crate::error::ErrorKind::Network)?;
         let mut res = String::with_capacity(8 * 1024);
         let mut ret = NntpStream {
             stream,
             extension_use: server_conf.extension_use,
             current_mailbox: MailboxSelection::None,
         };
+        if server_conf.use_tls && server_conf.use_starttls {
+            ret.read_response(&mut res, false, &["200 ", "201 "])
+                .await?;
+            ret.send_command(b"CAPABILITIES").await?;
+            ret.read_response(&mut res, true, command_to_replycodes("CAPABILITIES"))
+                .await?;
+            if !res.starts_with("101 ") {
+                return Err(MeliError::new(format!(
+                    "Could not connect to {}: expected CAPABILITIES response but got:{}",
+                    &server_conf.server_hostname, res
+                )));
+            }
+            let capabilities: Vec<&str> = res.lines().skip(1).collect();
 
-        if server_conf.use_tls {
-            let mut connector = TlsConnector::builder();
-            if server_conf.danger_accept_invalid_certs {
-                connector.danger_accept_invalid_certs(true);
+            if !capabilities
+                .iter()
+                .any(|cap| cap.eq_ignore_ascii_case("VERSION 2"))
+            {
+                return Err(MeliError::new(format!(
+                    "Could not connect to {}: server is not NNTP VERSION 2 compliant",
+                    &server_conf.server_hostname
+                )));
             }
-            let connector = connector
-                .build()
+            if !capabilities
+                .iter()
+                .any(|cap| cap.eq_ignore_ascii_case("STARTTLS"))
+            {
+                return Err(MeliError::new(format!(
+                    "Could not connect to {}: server does not support STARTTLS",
+                    &server_conf.server_hostname
+                )));
+            }
+            ret.stream
+                .write_all(b"STARTTLS\r\n")
+                .await
                 .chain_err_kind(crate::error::ErrorKind::Network)?;
-
-            if server_conf.use_starttls {
-                ret.read_response(&mut res, false, &["200 ", "201 "])
-                    .await?;
-                ret.send_command(b"CAPABILITIES").await?;
-                ret.read_response(&mut res, true, command_to_replycodes("CAPABILITIES"))
-                    .await?;
-                if !res.starts_with("101 ") {
-                    return Err(MeliError::new(format!(
-                        "Could not connect to {}: expected CAPABILITIES response but got:{}",
-                        &server_conf.server_hostname, res
-                    )));
-                }
-                let capabilities: Vec<&str> = res.lines().skip(1).collect();
-
-                if !capabilities
-                    .iter()
-                    .any(|cap| cap.eq_ignore_ascii_case("VERSION 2"))
-                {
-                    return Err(MeliError::new(format!(
-                        "Could not connect to {}: server is not NNTP VERSION 2 compliant",
-                        &server_conf.server_hostname
-                    )));
-                }
-                if !capabilities
-                    .iter()
-                    .any(|cap| cap.eq_ignore_ascii_case("STARTTLS"))
-                {
-                    return Err(MeliError::new(format!(
-                        "Could not connect to {}: server does not support STARTTLS",
-                        &server_conf.server_hostname
-                    )));
-                }
-                ret.stream
-                    .write_all(b"STARTTLS\r\n")
-                    .await
-                    .chain_err_kind(crate::error::ErrorKind::Network)?;
-                ret.stream
-                    .flush()
-                    .await
-                    .chain_err_kind(crate::error::ErrorKind::Network)?;
-                ret.read_response(&mut res, false, command_to_replycodes("STARTTLS"))
-                    .await?;
-                if !res.starts_with("382 ") {
-                    return Err(MeliError::new(format!(
-                        "Could not connect to {}: could not begin TLS negotiation, got: {}",
-                        &server_conf.server_hostname, res
-                    )));
-                }
+            ret.stream
+                .flush()
+                .await
+                .chain_err_kind(crate::error::ErrorKind::Network)?;
+            ret.read_response(&mut res, false, command_to_replycodes("STARTTLS"))
+                .await?;
+            if !res.starts_with("382 ") {
+                return Err(MeliError::new(format!(
+                    "Could not connect to {}: could not begin TLS negotiation, got: {}",
+                    &server_conf.server_hostname, res
+                )));
             }
+        }
+        Ok(ret)
+    }
 
-            {
-                // FIXME: This is blocking
-                let socket = ret
+    pub async fn new_connection(
+        server_conf: &NntpServerConf,
+    ) -> Result<(Capabilities, NntpStream)> {
+        let path = &server_conf.server_hostname;
+
+        let mut res = String::with_capacity(8 * 1024);
+        let mut ret = Self::open_socket(server_conf).await?;
+
+        if server_conf.use_tls {
+            let socket = ret
+                .stream
+                .into_inner()
+                .chain_err_kind(crate::error::ErrorKind::Network)?;
+            let reconnect = async {
+                Self::open_socket(server_conf)
+                    .await?
                     .stream
                     .into_inner()
-                    .chain_err_kind(crate::error::ErrorKind::Network)?;
-                let mut conn_result = connector.connect(path, socket);
-                if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
-                    conn_result
-                {
-                    let mut midhandshake_stream = Some(midhandshake_stream);
-                    loop {
-                        match midhandshake_stream.take().unwrap().handshake() {
-                            Ok(r) => {
-                                conn_result = Ok(r);
-                                break;
-                            }
-                            Err(native_tls::HandshakeError::WouldBlock(stream)) => {
-                                midhandshake_stream = Some(stream);
-                            }
-                            p => {
-                                p.chain_err_kind(crate::error::ErrorKind::Network)?;
-                            }
-                        }
-                    }
-                }
-                ret.stream = AsyncWrapper::new(Connection::Tls(
-                    conn_result.chain_err_kind(crate::error::ErrorKind::Network)?,
-                ))
-                .chain_err_summary(|| format!("Could not initiate TLS negotiation to {}.", path))
-                .chain_err_kind(crate::error::ErrorKind::Network)?;
-            }
+                    .chain_err_kind(crate::error::ErrorKind::Network)
+            };
+            ret.stream = AsyncWrapper::new(Connection::Tls(
+                server_conf
+                    .tls
+                    .connect(path, server_conf.server_port, socket, reconnect)
+                    .await
+                    .chain_err_summary(|| {
+                        format!("Could not initiate TLS negotiation to {}.", path)
+                    })?,
+            ))
+            .chain_err_summary(|| format!("Could not initiate TLS negotiation to {}.", path))
+            .chain_err_kind(crate::error::ErrorKind::Network)?;
         } else {
             ret.read_response(&mut res, false, &["200 ", "201 "])
                 .await?;
@@ -210,7 +199,8 @@ impl NntpStream {
             .get_ref()
             .set_keepalive(Some(std::time::Duration::new(60 * 9, 0)))
         {
-            crate::log(
+            crate::log_target(
+                "nntp",
                 format!("Could not set TCP keepalive in NNTP connection: {}", err),
                 crate::LoggingLevel::WARN,
             );
@@ -433,7 +423,23 @@ impl NntpConnection {
             }
             let new_stream = NntpStream::new_connection(&self.server_conf).await;
             if let Err(err) = new_stream.as_ref() {
-                *self.uid_store.is_online.lock().unwrap() = (Instant::now(), Err(err.clone()));
+                let mut is_online = self.uid_store.is_online.lock().unwrap();
+                if let Some(cert) = UntrustedCertificate::from_error(err) {
+                    /* Only ask once, not on every reconnection attempt */
+                    if is_online
+                        .1
+                        .as_ref()
+                        .err()
+                        .and_then(UntrustedCertificate::from_error)
+                        != Some(cert)
+                    {
+                        (self.uid_store.event_consumer)(
+                            self.uid_store.account_hash,
+                            crate::backends::BackendEvent::UntrustedCertificate(cert.clone()),
+                        );
+                    }
+                }
+                *is_online = (Instant::now(), Err(err.clone()));
             } else {
                 *self.uid_store.is_online.lock().unwrap() = (Instant::now(), Ok(()));
             }
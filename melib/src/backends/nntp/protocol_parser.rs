@@ -107,7 +107,7 @@ pub fn over_article(input: &str) -> IResult<&str, (UID, Envelope)> {
     let (input, _) = tag("\t")(input)?;
     let (input, references) = opt(is_not("\t"))(input)?;
     let (input, _) = tag("\t")(input)?;
-    let (input, _bytes) = opt(is_not("\t"))(input)?;
+    let (input, bytes) = opt(is_not("\t"))(input)?;
     let (input, _) = tag("\t")(input)?;
     let (input, _lines) = opt(is_not("\t\r\n"))(input)?;
     let (input, _other_headers) = opt(is_not("\r\n"))(input)?;
@@ -116,10 +116,10 @@ pub fn over_article(input: &str) -> IResult<&str, (UID, Envelope)> {
         input,
         ({
             let env_hash = {
-                let mut hasher = DefaultHasher::new();
+                let mut hasher = EnvelopeHasher::new();
                 hasher.write(num.as_bytes());
                 hasher.write(message_id.unwrap_or_default().as_bytes());
-                hasher.finish()
+                hasher.envelope_hash()
             };
             let mut env = Envelope::new(env_hash);
             if let Some(date) = date {
@@ -147,6 +147,10 @@ pub fn over_article(input: &str) -> IResult<&str, (UID, Envelope)> {
                 env.set_references(references.as_bytes());
             }
 
+            if let Some(size) = bytes.and_then(|b| b.parse().ok()) {
+                env.set_size(size);
+            }
+
             if let Some(message_id) = message_id {
                 env.set_message_id(message_id.as_bytes());
             }
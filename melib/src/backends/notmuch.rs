@@ -67,6 +67,9 @@ pub use tags::*;
 mod thread;
 pub use thread::*;
 
+/// Prefix of the database configuration keys of saved queries.
+const QUERY_PREFIX: &str = "query.";
+
 #[derive(Debug)]
 pub struct DbConnection {
     pub lib: Arc<libloading::Library>,
@@ -178,6 +181,63 @@ impl DbConnection {
         });
         Ok(())
     }
+
+    /// Saved searches, which are stored in the database as `query.NAME` configuration items by
+    /// `notmuch config set query.NAME`.
+    fn saved_queries(&self) -> Result<Vec<(String, String)>> {
+        let prefix = CString::new(QUERY_PREFIX)?;
+        let mut list: *mut notmuch_config_list_t = std::ptr::null_mut();
+        let mut ret = vec![];
+        unsafe {
+            try_call!(
+                self.lib,
+                call!(self.lib, notmuch_database_get_config_list)(
+                    *self.inner.read().unwrap(),
+                    prefix.as_ptr(),
+                    &mut list as *mut _
+                )
+            )
+            .map_err(|err| {
+                MeliError::new("Could not read saved queries.").set_source(Some(Arc::new(err)))
+            })?;
+            while call!(self.lib, notmuch_config_list_valid)(list) != 0 {
+                let key = CStr::from_ptr(call!(self.lib, notmuch_config_list_key)(list));
+                let value = CStr::from_ptr(call!(self.lib, notmuch_config_list_value)(list));
+                let key = key.to_string_lossy();
+                /* Removed queries are left with an empty value. */
+                if key.starts_with(QUERY_PREFIX) && !value.to_bytes().is_empty() {
+                    ret.push((
+                        key[QUERY_PREFIX.len()..].to_string(),
+                        value.to_string_lossy().into_owned(),
+                    ));
+                }
+                call!(self.lib, notmuch_config_list_move_to_next)(list);
+            }
+            call!(self.lib, notmuch_config_list_destroy)(list);
+        }
+        Ok(ret)
+    }
+
+    /// Save `query_str` as `name`, or remove the saved query if `query_str` is empty.
+    fn set_saved_query(&self, name: &str, query_str: &str) -> Result<()> {
+        let key = CString::new(format!("{}{}", QUERY_PREFIX, name))?;
+        let value = CString::new(query_str)?;
+        unsafe {
+            try_call!(
+                self.lib,
+                call!(self.lib, notmuch_database_set_config)(
+                    *self.inner.read().unwrap(),
+                    key.as_ptr(),
+                    value.as_ptr()
+                )
+            )
+            .map_err(|err| {
+                MeliError::new(format!("Could not save query `{}`.", name))
+                    .set_source(Some(Arc::new(err)))
+            })?;
+        }
+        Ok(())
+    }
 }
 
 unsafe impl Send for DbConnection {}
@@ -239,12 +299,38 @@ struct NotmuchMailbox {
     name: String,
     path: String,
     query_str: String,
+    /// Whether the mailbox is a saved query in the database, as opposed to an entry in the
+    /// configuration file. Only saved queries can be renamed or deleted.
+    saved_query: bool,
     usage: Arc<RwLock<SpecialUsageMailbox>>,
 
     total: Arc<Mutex<usize>>,
     unseen: Arc<Mutex<usize>>,
 }
 
+impl NotmuchMailbox {
+    fn new(name: String, query_str: String, saved_query: bool) -> Self {
+        NotmuchMailbox {
+            hash: Self::hash_of(&name),
+            path: name.clone(),
+            name,
+            children: vec![],
+            parent: None,
+            query_str,
+            saved_query,
+            usage: Arc::new(RwLock::new(SpecialUsageMailbox::Normal)),
+            total: Arc::new(Mutex::new(0)),
+            unseen: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn hash_of(name: &str) -> MailboxHash {
+        let mut h = DefaultHasher::new();
+        name.hash(&mut h);
+        h.finish()
+    }
+}
+
 impl BackendMailbox for NotmuchMailbox {
     fn hash(&self) -> MailboxHash {
         self.hash
@@ -320,25 +406,8 @@ impl NotmuchDb {
         let mut mailboxes = HashMap::default();
         for (k, f) in s.mailboxes.iter() {
             if let Some(query_str) = f.extra.get("query") {
-                let hash = {
-                    let mut h = DefaultHasher::new();
-                    k.hash(&mut h);
-                    h.finish()
-                };
-                mailboxes.insert(
-                    hash,
-                    NotmuchMailbox {
-                        hash,
-                        name: k.to_string(),
-                        path: k.to_string(),
-                        children: vec![],
-                        parent: None,
-                        query_str: query_str.to_string(),
-                        usage: Arc::new(RwLock::new(SpecialUsageMailbox::Normal)),
-                        total: Arc::new(Mutex::new(0)),
-                        unseen: Arc::new(Mutex::new(0)),
-                    },
-                );
+                let mailbox = NotmuchMailbox::new(k.to_string(), query_str.to_string(), false);
+                mailboxes.insert(mailbox.hash, mailbox);
             } else {
                 return Err(MeliError::new(format!(
                     "notmuch mailbox configuration entry \"{}\" should have a \"query\" value set.",
@@ -346,6 +415,28 @@ impl NotmuchDb {
                 )));
             }
         }
+        /* Configuration entries take precedence over saved queries of the same name. */
+        match Self::new_connection(path.as_path(), Arc::new(RwLock::new(0)), lib.clone(), false)
+            .and_then(|database| database.saved_queries())
+        {
+            Ok(queries) => {
+                for (name, query_str) in queries {
+                    let mailbox = NotmuchMailbox::new(name, query_str, true);
+                    mailboxes.entry(mailbox.hash).or_insert(mailbox);
+                }
+            }
+            Err(err) => {
+                crate::log_target(
+                    "notmuch",
+                    format!(
+                        "Could not load saved queries of account {}: {}",
+                        s.name(),
+                        err
+                    ),
+                    crate::WARN,
+                );
+            }
+        }
 
         let account_hash = {
             let mut hasher = DefaultHasher::new();
@@ -388,6 +479,29 @@ impl NotmuchDb {
         Ok(())
     }
 
+    /// Name of the saved query behind `mailbox_hash`. Mailboxes from the configuration file can
+    /// only be changed there.
+    fn saved_query_name(&self, mailbox_hash: MailboxHash) -> Result<String> {
+        match self.mailboxes.read().unwrap().get(&mailbox_hash) {
+            Some(f) if f.saved_query => Ok(f.name.clone()),
+            Some(f) => Err(MeliError::new(format!(
+                "Mailbox `{}` is defined in the configuration file and can only be changed there.",
+                f.name
+            ))),
+            None => Err(MeliError::new(format!(
+                "Mailbox with hash {} not found",
+                mailbox_hash
+            ))),
+        }
+    }
+
+    fn forget_mailbox(&mut self, mailbox_hash: MailboxHash) {
+        self.mailboxes.write().unwrap().remove(&mailbox_hash);
+        for mailboxes in self.mailbox_index.write().unwrap().values_mut() {
+            mailboxes.retain(|h| *h != mailbox_hash);
+        }
+    }
+
     fn new_connection(
         path: &Path,
         revision_uuid: Arc<RwLock<u64>>,
@@ -437,6 +551,11 @@ impl MailBackend for NotmuchDb {
             is_remote: false,
             supports_search: true,
             extensions: None,
+            actions: &[BackendAction {
+                name: "compact",
+                description: "compact the database, reclaiming the space of removed messages",
+                args: &[],
+            }],
             supports_tags: true,
             supports_submission: false,
         };
@@ -842,6 +961,130 @@ impl MailBackend for NotmuchDb {
         self.collection.clone()
     }
 
+    fn create_mailbox(
+        &mut self,
+        new_path: String,
+    ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
+        if new_path.is_empty() {
+            return Err(MeliError::new("Mailbox name is empty."));
+        }
+        if self
+            .mailboxes
+            .read()
+            .unwrap()
+            .contains_key(&NotmuchMailbox::hash_of(&new_path))
+        {
+            return Err(MeliError::new(format!(
+                "Mailbox `{}` already exists.",
+                &new_path
+            )));
+        }
+        /* The new mailbox shows messages tagged with its name. */
+        let query_str = if new_path
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '(' || c == ')')
+        {
+            format!("tag:\"{}\"", new_path.replace('"', "\"\""))
+        } else {
+            format!("tag:{}", new_path)
+        };
+        let database = Self::new_connection(
+            self.path.as_path(),
+            self.revision_uuid.clone(),
+            self.lib.clone(),
+            true,
+        )?;
+        database.set_saved_query(&new_path, &query_str)?;
+        let mailbox = NotmuchMailbox::new(new_path, query_str, true);
+        let mailbox_hash = mailbox.hash;
+        self.mailboxes
+            .write()
+            .unwrap()
+            .insert(mailbox_hash, mailbox);
+        let ret = self.mailboxes()?;
+        Ok(Box::pin(async move { Ok((mailbox_hash, ret.await?)) }))
+    }
+
+    fn delete_mailbox(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let name = self.saved_query_name(mailbox_hash)?;
+        let database = Self::new_connection(
+            self.path.as_path(),
+            self.revision_uuid.clone(),
+            self.lib.clone(),
+            true,
+        )?;
+        database.set_saved_query(&name, "")?;
+        self.forget_mailbox(mailbox_hash);
+        let ret = self.mailboxes()?;
+        Ok(Box::pin(async move { ret.await }))
+    }
+
+    fn rename_mailbox(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        new_path: String,
+    ) -> ResultFuture<Mailbox> {
+        let name = self.saved_query_name(mailbox_hash)?;
+        let new_hash = NotmuchMailbox::hash_of(&new_path);
+        if new_path.is_empty() {
+            return Err(MeliError::new("Mailbox name is empty."));
+        }
+        if self.mailboxes.read().unwrap().contains_key(&new_hash) {
+            return Err(MeliError::new(format!(
+                "Mailbox `{}` already exists.",
+                &new_path
+            )));
+        }
+        /* Only the name changes, the messages the query matches stay the same. */
+        let mut mailbox = std::clone::Clone::clone(&self.mailboxes.read().unwrap()[&mailbox_hash]);
+        let database = Self::new_connection(
+            self.path.as_path(),
+            self.revision_uuid.clone(),
+            self.lib.clone(),
+            true,
+        )?;
+        database.set_saved_query(&new_path, &mailbox.query_str)?;
+        database.set_saved_query(&name, "")?;
+        self.forget_mailbox(mailbox_hash);
+        mailbox.hash = new_hash;
+        mailbox.name = new_path.clone();
+        mailbox.path = new_path;
+        let ret = BackendMailbox::clone(&mailbox);
+        self.mailboxes.write().unwrap().insert(new_hash, mailbox);
+        Ok(Box::pin(async move { Ok(ret) }))
+    }
+
+    fn perform_action(&mut self, action: &str, _args: Vec<String>) -> ResultFuture<String> {
+        if action != "compact" {
+            return Err(MeliError::new(format!("Unknown action `{}`.", action)));
+        }
+        let path = CString::new(self.path.as_os_str().as_bytes())?;
+        let lib = self.lib.clone();
+        Ok(Box::pin(async move {
+            /* Without a backup path the old database is removed once the compacted one is in
+             * place. */
+            unsafe {
+                try_call!(
+                    lib,
+                    call!(lib, notmuch_database_compact)(
+                        path.as_ptr(),
+                        std::ptr::null(),
+                        None,
+                        std::ptr::null_mut()
+                    )
+                )
+                .map_err(|err| {
+                    MeliError::new("Could not compact the database.")
+                        .set_source(Some(Arc::new(err)))
+                })?;
+            }
+            Ok("Database compacted.".to_string())
+        }))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1050,6 +1293,11 @@ impl MelibQueryToNotmuchQuery for crate::search::Query {
             HasAttachment => {
                 ret.push_str("tag:attachment");
             }
+            Note(_) | Larger(_) | Smaller(_) => {
+                /* Notes are stored by the client and notmuch doesn't index message sizes, match
+                 * everything and let the client filter. */
+                ret.push_str("*");
+            }
             And(q1, q2) => {
                 ret.push_str("(");
                 q1.query_to_string(ret);
@@ -20,6 +20,7 @@
  */
 
 use super::*;
+use crate::email::EnvelopeHasher;
 use crate::thread::{ThreadHash, ThreadNode, ThreadNodeHash};
 
 #[derive(Clone)]
@@ -59,9 +60,9 @@ impl<'m> Message<'m> {
         let msg_id = unsafe { call!(self.lib, notmuch_message_get_message_id)(self.message) };
         let c_str = unsafe { CStr::from_ptr(msg_id) };
         {
-            let mut hasher = DefaultHasher::default();
+            let mut hasher = EnvelopeHasher::new();
             c_str.hash(&mut hasher);
-            hasher.finish()
+            hasher.envelope_hash()
         }
     }
 
@@ -167,6 +168,9 @@ impl<'m> Message<'m> {
                 .set_datetime(self.date())
                 .set_flags(flags);
         }
+        if let Ok(metadata) = std::fs::metadata(self.get_filename()) {
+            env.set_size(metadata.len() as usize);
+        }
         env
     }
 
@@ -0,0 +1,233 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Locale-aware string comparison for sorting.
+ *
+ * This is a small subset of the Unicode Collation Algorithm: strings are compared first by their
+ * letters ignoring case and accents, then by accents, then by case. Letters are decomposed with
+ * NFKD, so `é` sorts with `e` and `ﬁ` with `fi`. A few languages order some letters differently;
+ * their tailorings are in `TAILORINGS`.
+ */
+
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+
+/// Letters that a language sorts right after another one, in order, instead of as an accented
+/// variant of it.
+type Tailoring = &'static [(char, char)];
+
+const TAILORINGS: &[(&[&str], Tailoring)] = &[
+    (
+        &["da", "nb", "nn", "no"],
+        &[('æ', 'z'), ('ä', 'z'), ('ø', 'z'), ('ö', 'z'), ('å', 'z')],
+    ),
+    (
+        &["sv", "fi"],
+        &[('å', 'z'), ('ä', 'z'), ('æ', 'z'), ('ö', 'z'), ('ø', 'z')],
+    ),
+    (&["es"], &[('ñ', 'n')]),
+];
+
+/// Letters that don't decompose but sort as other letters, with the accent they count as.
+const EXPANSIONS: &[(char, &str, Option<char>)] = &[
+    ('ß', "ss", None),
+    ('æ', "ae", None),
+    ('œ', "oe", None),
+    ('ø', "o", Some('\u{338}')),
+    ('đ', "d", Some('\u{335}')),
+    ('ł', "l", Some('\u{337}')),
+];
+
+/// Compares strings in the order of a locale.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Collator {
+    /// Compare bytes, for the `C` and `POSIX` locales.
+    bytes: bool,
+    tailoring: Tailoring,
+}
+
+/// A letter, an accent or a character that is neither.
+#[derive(Debug, Clone, Copy)]
+struct Element {
+    /// Zero for accents, which are ignored when comparing letters.
+    primary: u32,
+    secondary: u32,
+    uppercase: bool,
+}
+
+impl Collator {
+    /// The collation of `locale`, such as `sv_SE.UTF-8` or `de`. Only the language matters.
+    pub fn new(locale: &str) -> Self {
+        let language = locale
+            .split(&['_', '-', '.', '@'][..])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if language == "c" || language == "posix" {
+            return Collator {
+                bytes: true,
+                tailoring: &[],
+            };
+        }
+        Collator {
+            bytes: false,
+            tailoring: TAILORINGS
+                .iter()
+                .find(|(languages, _)| languages.contains(&language.as_str()))
+                .map(|(_, tailoring)| *tailoring)
+                .unwrap_or(&[]),
+        }
+    }
+
+    /// The collation of the locale in `LC_ALL`, `LC_COLLATE` or `LANG`, whichever is set first.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_COLLATE", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|val| !val.is_empty())
+            .map(|locale| Self::new(&locale))
+            .unwrap_or_default()
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        if self.bytes {
+            return a.cmp(b);
+        }
+        self.elements(a)
+            .filter(|e| e.primary != 0)
+            .map(|e| e.primary)
+            .cmp(
+                self.elements(b)
+                    .filter(|e| e.primary != 0)
+                    .map(|e| e.primary),
+            )
+            .then_with(|| {
+                self.elements(a)
+                    .map(|e| e.secondary)
+                    .cmp(self.elements(b).map(|e| e.secondary))
+            })
+            /* Lowercase first. */
+            .then_with(|| {
+                self.elements(a)
+                    .map(|e| e.uppercase)
+                    .cmp(self.elements(b).map(|e| e.uppercase))
+            })
+            .then_with(|| a.cmp(b))
+    }
+
+    fn elements<'s>(&'s self, s: &'s str) -> impl Iterator<Item = Element> + 's {
+        s.chars().flat_map(move |c| {
+            let mut ret: SmallVec<[Element; 4]> = SmallVec::new();
+            let uppercase = c.is_uppercase();
+            for c in c.to_lowercase() {
+                self.push_elements(c, uppercase, &mut ret);
+            }
+            ret.into_iter()
+        })
+    }
+
+    fn push_elements(&self, c: char, uppercase: bool, ret: &mut SmallVec<[Element; 4]>) {
+        let letter = |c: char| Element {
+            primary: (c as u32) << 8,
+            secondary: 0,
+            uppercase,
+        };
+        let accent = |c: char| Element {
+            primary: 0,
+            secondary: c as u32,
+            uppercase,
+        };
+        if let Some(i) = self.tailoring.iter().position(|(t, _)| *t == c) {
+            let after = self.tailoring[i].1;
+            ret.push(Element {
+                primary: ((after as u32) << 8) + i as u32 + 1,
+                ..letter(c)
+            });
+            return;
+        }
+        if let Some((_, expansion, mark)) = EXPANSIONS.iter().find(|(e, _, _)| *e == c) {
+            ret.extend(expansion.chars().map(letter));
+            ret.extend(mark.map(accent));
+            return;
+        }
+        #[cfg(feature = "unicode_algorithms")]
+        {
+            use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+            decompose_compatible(c, |d| {
+                if is_combining_mark(d) {
+                    ret.push(accent(d));
+                } else {
+                    /* Compatibility decompositions can have capitals, as in `㎆`. */
+                    ret.extend(d.to_lowercase().map(letter));
+                }
+            });
+        }
+        #[cfg(not(feature = "unicode_algorithms"))]
+        {
+            ret.push(letter(c));
+        }
+    }
+}
+
+#[test]
+fn test_collation() {
+    fn sorted<'a>(locale: &str, words: &[&'a str]) -> Vec<&'a str> {
+        let collator = Collator::new(locale);
+        let mut ret = words.to_vec();
+        ret.sort_by(|a, b| collator.compare(a, b));
+        ret
+    }
+    /* Case and accents only matter between otherwise equal strings. */
+    assert_eq!(
+        sorted(
+            "en_US.UTF-8",
+            &["b", "B", "Äpfel", "apple", "äb", "ab", "Ab"]
+        ),
+        vec!["ab", "Ab", "äb", "Äpfel", "apple", "b", "B"]
+    );
+    assert_eq!(
+        sorted(
+            "en",
+            &["Zürich", "zebra", "Straße", "Strasse", "Strast", "Øre", "ore"]
+        ),
+        vec!["ore", "Øre", "Strasse", "Straße", "Strast", "zebra", "Zürich"]
+    );
+    /* Swedish sorts å, ä and ö after z. */
+    assert_eq!(
+        sorted("sv_SE.UTF-8", &["öl", "Åsa", "zon", "ärt", "apa"]),
+        vec!["apa", "zon", "Åsa", "ärt", "öl"]
+    );
+    assert_eq!(
+        sorted("de_DE", &["öl", "Åsa", "zon", "ärt", "apa"]),
+        vec!["apa", "ärt", "Åsa", "öl", "zon"]
+    );
+    assert_eq!(
+        sorted("es", &["nube", "ñu", "oso"]),
+        vec!["nube", "ñu", "oso"]
+    );
+    assert_eq!(
+        sorted("en", &["nube", "ñu", "nv"]),
+        vec!["ñu", "nube", "nv"]
+    );
+    assert_eq!(sorted("C", &["b", "B", "a", "á"]), vec!["B", "a", "b", "á"]);
+    assert_eq!(Collator::new("en").compare("ﬁle", "File"), Ordering::Less);
+}
@@ -220,6 +220,7 @@ impl Collection {
             ..
         } = self;
 
+        new_envelopes.retain(|_, e| !self.is_collision(e));
         let mut threads_lck = threads.write().unwrap();
         let mut mailboxes_lck = mailboxes.write().unwrap();
         if !threads_lck.contains_key(&mailbox_hash) {
@@ -421,8 +422,13 @@ impl Collection {
         }
     }
 
+    /// Insert `envelope` in `mailbox_hash`. Returns `true` if it wasn't inserted, because
+    /// another message already has its hash.
     pub fn insert(&self, envelope: Envelope, mailbox_hash: MailboxHash) -> bool {
         let hash = envelope.hash();
+        if self.is_collision(&envelope) {
+            return true;
+        }
         self.mailboxes
             .write()
             .unwrap()
@@ -449,6 +455,35 @@ impl Collection {
         false
     }
 
+    /// Describes the collision if a different message than `envelope` is already stored under
+    /// its hash. Envelopes are looked up by hash everywhere, so `merge` and `insert` leave the
+    /// new one out instead of replacing the other; callers should let the user know.
+    pub fn collision(&self, envelope: &Envelope) -> Option<String> {
+        let envelopes_lck = self.envelopes.read().unwrap();
+        match envelopes_lck.get(&envelope.hash()) {
+            Some(old) if old.message_id() != envelope.message_id() => {
+                let description = format!(
+                    "{} and {} both have hash {}, {} could not be loaded.",
+                    old.message_id_display(),
+                    envelope.message_id_display(),
+                    envelope.hash(),
+                    envelope.message_id_display(),
+                );
+                crate::log_target(
+                    "collection",
+                    format!("Envelope hash collision: {}", &description),
+                    crate::ERROR,
+                );
+                Some(description)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_collision(&self, envelope: &Envelope) -> bool {
+        self.collision(envelope).is_some()
+    }
+
     pub fn insert_reply(&self, env_hash: EnvelopeHash) {
         debug_assert!(self.envelopes.read().unwrap().contains_key(&env_hash));
         for (_, t) in self.threads.write().unwrap().iter_mut() {
@@ -483,6 +518,99 @@ impl Collection {
         self.envelopes.read().unwrap().contains_key(env_hash)
     }
 
+    /// A mailbox that contains `env_hash`, `mailbox_hash` if it does.
+    pub fn mailbox_of(
+        &self,
+        env_hash: EnvelopeHash,
+        mailbox_hash: MailboxHash,
+    ) -> Option<MailboxHash> {
+        let mailboxes = self.mailboxes.read().unwrap();
+        if mailboxes
+            .get(&mailbox_hash)
+            .map(|m| m.contains(&env_hash))
+            .unwrap_or(false)
+        {
+            return Some(mailbox_hash);
+        }
+        mailboxes
+            .iter()
+            .find(|(_, m)| m.contains(&env_hash))
+            .map(|(h, _)| *h)
+    }
+
+    /// The envelope with Message-ID `message_id` in any mailbox, with its mailbox. A copy in
+    /// `mailbox_hash` is preferred.
+    pub fn find_message_id(
+        &self,
+        message_id: &MessageID,
+        mailbox_hash: MailboxHash,
+    ) -> Option<(MailboxHash, EnvelopeHash)> {
+        let hashes = self
+            .envelopes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|e| e.message_id() == message_id)
+            .map(|e| e.hash())
+            .collect::<SmallVec<[EnvelopeHash; 8]>>();
+        hashes
+            .iter()
+            .filter_map(|h| Some((self.mailbox_of(*h, mailbox_hash)?, *h)))
+            .min_by_key(|(m, _)| *m != mailbox_hash)
+    }
+
+    /// The envelope `env_hash` replies to: the one of its In-Reply-To header, or else the
+    /// closest of its References that is known.
+    pub fn parent_of(
+        &self,
+        env_hash: EnvelopeHash,
+        mailbox_hash: MailboxHash,
+    ) -> Option<(MailboxHash, EnvelopeHash)> {
+        let ids = {
+            let env = self.get_env(env_hash);
+            env.in_reply_to()
+                .into_iter()
+                .chain(env.references().into_iter().rev())
+                .cloned()
+                .collect::<Vec<MessageID>>()
+        };
+        ids.iter()
+            .find_map(|id| self.find_message_id(id, mailbox_hash))
+    }
+
+    /// The envelopes that reply to `env_hash` in any mailbox, with their mailboxes, oldest
+    /// first.
+    pub fn replies_to(
+        &self,
+        env_hash: EnvelopeHash,
+        mailbox_hash: MailboxHash,
+    ) -> Vec<(MailboxHash, EnvelopeHash)> {
+        let mut hashes = {
+            let envelopes = self.envelopes.read().unwrap();
+            let message_id = match envelopes.get(&env_hash) {
+                Some(env) => env.message_id().clone(),
+                None => return vec![],
+            };
+            let mut hashes = envelopes
+                .values()
+                .filter(|e| {
+                    e.hash() != env_hash
+                        && match e.in_reply_to() {
+                            Some(id) => *id == message_id,
+                            None => e.references().last() == Some(&&message_id),
+                        }
+                })
+                .map(|e| (e.date(), e.hash()))
+                .collect::<Vec<(UnixTimestamp, EnvelopeHash)>>();
+            hashes.sort();
+            hashes
+        };
+        hashes
+            .drain(..)
+            .filter_map(|(_, h)| Some((self.mailbox_of(h, mailbox_hash)?, h)))
+            .collect()
+    }
+
     pub fn new_mailbox(&self, mailbox_hash: MailboxHash) {
         let mut mailboxes_lck = self.mailboxes.write().unwrap();
         if !mailboxes_lck.contains_key(&mailbox_hash) {
@@ -507,3 +635,200 @@ impl<K: std::cmp::Eq + std::hash::Hash, V> Deref for RwRef<'_, K, V> {
         self.guard.get(&self.hash).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAILBOX: MailboxHash = 1;
+
+    fn envelope(headers: &str, body: &str) -> Envelope {
+        Envelope::from_bytes(
+            format!(
+                "From: a@example.com\r\nTo: b@example.com\r\nDate: Mon, 1 Jun 2020 10:00:00 +0000\r\n{}\r\n{}\r\n",
+                headers, body
+            )
+            .as_bytes(),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// The envelope hashes of the messages in the threads of `MAILBOX`.
+    fn threaded(collection: &Collection) -> HashSet<EnvelopeHash> {
+        let threads = collection.get_threads(MAILBOX);
+        threads
+            .roots()
+            .into_iter()
+            .flat_map(|group| threads.thread_group_iter(group).collect::<Vec<_>>())
+            .filter_map(|(_, node)| threads.thread_nodes()[&node].message())
+            .collect()
+    }
+
+    #[test]
+    fn test_envelope_hash_is_stable() {
+        let first = envelope("Message-ID: <first@example.com>\r\n", "first");
+        let second = envelope("Message-ID: <second@example.com>\r\n", "second");
+        assert_eq!(
+            first.hash(),
+            envelope("Message-ID: <first@example.com>\r\n", "first").hash()
+        );
+        assert_ne!(first.hash(), second.hash());
+        assert!(first.hash() > u128::from(u64::MAX) || second.hash() > u128::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_hash_collision() {
+        let collection = Collection::new();
+        collection.new_mailbox(MAILBOX);
+        let first = envelope("Message-ID: <first@example.com>\r\n", "first");
+        let mut second = envelope("Message-ID: <second@example.com>\r\n", "second");
+        second.set_hash(first.hash());
+        assert!(!collection.is_collision(&second));
+        assert!(!collection.insert(first.clone(), MAILBOX));
+        assert!(collection.is_collision(&second));
+        assert!(!collection.is_collision(&first));
+        assert!(collection.insert(second.clone(), MAILBOX));
+        assert_eq!(
+            collection.get_env(first.hash()).message_id(),
+            first.message_id()
+        );
+        /* The same message again is not a collision. */
+        assert!(!collection.insert(first.clone(), MAILBOX));
+
+        let collection = Collection::new();
+        collection.merge(
+            vec![(first.hash(), first.clone())].into_iter().collect(),
+            MAILBOX,
+            None,
+        );
+        collection.merge(
+            vec![(second.hash(), second)].into_iter().collect(),
+            MAILBOX,
+            None,
+        );
+        assert_eq!(collection.len(), 1);
+        assert_eq!(
+            collection.get_env(first.hash()).message_id(),
+            first.message_id()
+        );
+    }
+
+    #[test]
+    fn test_parent_and_replies() {
+        const OTHER: MailboxHash = 2;
+        let collection = Collection::new();
+        collection.new_mailbox(MAILBOX);
+        collection.new_mailbox(OTHER);
+        let root = envelope("Message-ID: <root@example.com>\r\n", "root");
+        let reply = envelope(
+            "Message-ID: <reply@example.com>\r\nIn-Reply-To: <root@example.com>\r\nReferences: <root@example.com>\r\nDate: Tue, 2 Jun 2020 10:00:00 +0000\r\n",
+            "reply",
+        );
+        /* Only References, as some clients send. */
+        let other_reply = envelope(
+            "Message-ID: <other@example.com>\r\nReferences: <gone@example.com> <root@example.com>\r\n",
+            "other reply",
+        );
+        let nested = envelope(
+            "Message-ID: <nested@example.com>\r\nIn-Reply-To: <missing@example.com>\r\nReferences: <root@example.com> <reply@example.com> <missing@example.com>\r\n",
+            "nested",
+        );
+        collection.insert(root.clone(), MAILBOX);
+        collection.insert(reply.clone(), OTHER);
+        collection.insert(other_reply.clone(), MAILBOX);
+        collection.insert(nested.clone(), MAILBOX);
+
+        assert_eq!(
+            collection.parent_of(reply.hash(), OTHER),
+            Some((MAILBOX, root.hash()))
+        );
+        assert_eq!(
+            collection.parent_of(other_reply.hash(), MAILBOX),
+            Some((MAILBOX, root.hash()))
+        );
+        /* The closest known message of References when In-Reply-To isn't known. */
+        assert_eq!(
+            collection.parent_of(nested.hash(), MAILBOX),
+            Some((OTHER, reply.hash()))
+        );
+        assert_eq!(collection.parent_of(root.hash(), MAILBOX), None);
+        assert_eq!(
+            collection.replies_to(root.hash(), MAILBOX),
+            vec![(MAILBOX, other_reply.hash()), (OTHER, reply.hash())]
+        );
+        assert_eq!(collection.replies_to(nested.hash(), MAILBOX), vec![]);
+    }
+
+    #[test]
+    fn test_duplicate_message_ids() {
+        let collection = Collection::new();
+        collection.new_mailbox(MAILBOX);
+        let first = envelope("Message-ID: <dup@example.com>\r\n", "first copy");
+        let second = envelope("Message-ID: <dup@example.com>\r\n", "second copy");
+        let reply = envelope(
+            "Message-ID: <reply@example.com>\r\nIn-Reply-To: <dup@example.com>\r\n",
+            "reply",
+        );
+        assert_ne!(first.hash(), second.hash());
+        for env in [&first, &second, &reply].iter() {
+            assert!(!collection.insert((*env).clone(), MAILBOX));
+        }
+        /* Inserting again doesn't add anything. */
+        assert!(!collection.insert(second.clone(), MAILBOX));
+        assert_eq!(
+            threaded(&collection),
+            [first.hash(), second.hash(), reply.hash()]
+                .iter()
+                .cloned()
+                .collect()
+        );
+        let threads = collection.get_threads(MAILBOX);
+        assert_eq!(
+            threads
+                .roots()
+                .iter()
+                .map(|g| threads.thread_ref(*g).len())
+                .sum::<usize>(),
+            3
+        );
+        assert_ne!(
+            collection.get_env(first.hash()).thread(),
+            collection.get_env(second.hash()).thread()
+        );
+    }
+
+    #[test]
+    fn test_missing_message_ids() {
+        let a = envelope("Subject: a\r\n", "body");
+        let a_copy = envelope("Subject: a\r\n", "different body");
+        /* With an mbox From line. */
+        let a_mbox = Envelope::from_bytes(
+            b"From a@example.com Mon Jun  1 10:00:00 2020\r\nFrom: a@example.com\r\nTo: b@example.com\r\nDate: Mon, 1 Jun 2020 10:00:00 +0000\r\nSubject: a\r\n\r\nbody\r\n",
+            None,
+        )
+        .unwrap();
+        let b = envelope("Subject: b\r\n", "body");
+        assert!(!a.message_id().raw().is_empty());
+        assert_eq!(a.message_id(), a_copy.message_id());
+        assert_eq!(a.message_id(), a_mbox.message_id());
+        assert_ne!(a.message_id(), b.message_id());
+        /* The id doesn't change between runs or Rust releases. */
+        assert_eq!(
+            a.message_id_display().to_string(),
+            format!(
+                "<{:016x}@meli.invalid>",
+                stable_hash(b"From: a@example.com\r\nTo: b@example.com\r\nDate: Mon, 1 Jun 2020 10:00:00 +0000\r\nSubject: a")
+            )
+        );
+
+        let collection = Collection::new();
+        collection.new_mailbox(MAILBOX);
+        assert!(!collection.insert(a.clone(), MAILBOX));
+        assert!(!collection.insert(b.clone(), MAILBOX));
+        assert_eq!(
+            threaded(&collection),
+            [a.hash(), b.hash()].iter().cloned().collect()
+        );
+    }
+}
@@ -37,6 +37,9 @@ pub struct AccountSettings {
     pub mailboxes: HashMap<String, MailboxConf>,
     #[serde(default)]
     pub manual_refresh: bool,
+    /// How often backends that poll for changes check the account's mailboxes, in seconds.
+    #[serde(default)]
+    pub refresh_interval_secs: Option<u64>,
     #[serde(flatten)]
     pub extra: HashMap<String, String>,
 }
@@ -74,19 +77,27 @@ impl AccountSettings {
     }
 }
 
-#[serde(default)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MailboxConf {
     #[serde(alias = "rename")]
     pub alias: Option<String>,
-    #[serde(default = "false_val")]
-    pub autoload: bool,
+    /// Whether to fetch the mailbox on startup. If false it's fetched once the user opens it, if
+    /// unset as soon as it's shown.
+    #[serde(default)]
+    pub autoload: ToggleFlag,
     #[serde(default)]
     pub subscribe: ToggleFlag,
     #[serde(default)]
     pub ignore: ToggleFlag,
     #[serde(default = "none")]
     pub usage: Option<SpecialUsageMailbox>,
+    /// Delete messages older than this many days.
+    #[serde(default = "none")]
+    pub retention_days: Option<u64>,
+    /// Overrides the account's `refresh_interval_secs` for this mailbox, `0` disables polling.
+    #[serde(default = "none")]
+    pub refresh_interval_secs: Option<u64>,
     #[serde(flatten)]
     pub extra: HashMap<String, String>,
 }
@@ -95,10 +106,12 @@ impl Default for MailboxConf {
     fn default() -> Self {
         MailboxConf {
             alias: None,
-            autoload: false,
+            autoload: ToggleFlag::Unset,
             subscribe: ToggleFlag::Unset,
             ignore: ToggleFlag::Unset,
             usage: None,
+            retention_days: None,
+            refresh_interval_secs: None,
             extra: HashMap::default(),
         }
     }
@@ -191,12 +204,20 @@ impl<'de> Deserialize<'de> for ToggleFlag {
     where
         D: Deserializer<'de>,
     {
-        let s = <String>::deserialize(deserializer);
-        Ok(match s? {
-            s if s.eq_ignore_ascii_case("true") => ToggleFlag::True,
-            s if s.eq_ignore_ascii_case("false") => ToggleFlag::False,
-            s if s.eq_ignore_ascii_case("ask") => ToggleFlag::Ask,
-            s => {
+        /* Accept both `true` and `"true"`. */
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum BoolOrString {
+            Bool(bool),
+            String(String),
+        }
+        Ok(match BoolOrString::deserialize(deserializer)? {
+            BoolOrString::Bool(true) => ToggleFlag::True,
+            BoolOrString::Bool(false) => ToggleFlag::False,
+            BoolOrString::String(s) if s.eq_ignore_ascii_case("true") => ToggleFlag::True,
+            BoolOrString::String(s) if s.eq_ignore_ascii_case("false") => ToggleFlag::False,
+            BoolOrString::String(s) if s.eq_ignore_ascii_case("ask") => ToggleFlag::Ask,
+            BoolOrString::String(s) => {
                 return Err(serde::de::Error::custom(format!(
                     r#"expected one of "true", "false", "ask", found `{}`"#,
                     s
@@ -20,6 +20,10 @@
  */
 
 //! Connections layers (TCP/fd/TLS/Deflate) to use with remote backends.
+pub mod certificates;
+pub mod proxy;
+#[cfg(feature = "tls")]
+pub mod tls;
 #[cfg(feature = "deflate_compression")]
 use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
 #[cfg(any(target_os = "openbsd", target_os = "netbsd", target_os = "haiku"))]
@@ -261,10 +265,17 @@ impl std::os::unix::io::AsRawFd for Connection {
     }
 }
 
-pub fn lookup_ipv4(host: &str, port: u16) -> crate::Result<std::net::SocketAddr> {
-    use std::net::ToSocketAddrs;
+/// Resolve `host` on a blocking thread pool, so that slow DNS doesn't stall the executor.
+pub async fn resolve(host: &str, port: u16) -> crate::Result<Vec<std::net::SocketAddr>> {
+    smol::net::resolve((host, port)).await.map_err(|err| {
+        crate::error::MeliError::new(format!("Could not lookup address {}:{}", host, port))
+            .set_source(Some(std::sync::Arc::new(err)))
+            .set_kind(crate::error::ErrorKind::Network)
+    })
+}
 
-    let addrs = (host, port).to_socket_addrs()?;
+pub async fn lookup_ipv4(host: &str, port: u16) -> crate::Result<std::net::SocketAddr> {
+    let addrs = resolve(host, port).await?;
     for addr in addrs {
         if let std::net::SocketAddr::V4(_) = addr {
             return Ok(addr);
@@ -277,6 +288,142 @@ pub fn lookup_ipv4(host: &str, port: u16) -> crate::Result<std::net::SocketAddr>
     )
 }
 
+/// Delay before trying the next address of a host while a connection attempt is pending (RFC
+/// 8305).
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Connect to `host`, alternating between its IPv6 and IPv4 addresses. Every
+/// `CONNECTION_ATTEMPT_DELAY`, or as soon as an attempt fails, a new attempt is started in
+/// parallel with the pending ones and the first to succeed is returned ("Happy Eyeballs", RFC
+/// 8305), so that an unreachable address family doesn't stall the connection.
+///
+/// Attempts run on their own threads, so that waiting for them doesn't block the executor.
+pub async fn happy_eyeballs_connect(
+    host: &str,
+    port: u16,
+    timeout: Option<Duration>,
+) -> crate::Result<std::net::TcpStream> {
+    use std::net::{SocketAddr, TcpStream};
+
+    let (v6, v4): (Vec<SocketAddr>, Vec<SocketAddr>) = resolve(host, port)
+        .await?
+        .into_iter()
+        .partition(SocketAddr::is_ipv6);
+    let mut addrs = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => break,
+            (a, b) => {
+                addrs.extend(a);
+                addrs.extend(b);
+            }
+        }
+    }
+    if addrs.is_empty() {
+        return Err(crate::error::MeliError::new(format!(
+            "Could not lookup address {}:{}",
+            host, port
+        ))
+        .set_kind(crate::error::ErrorKind::Network));
+    }
+
+    let (tx, rx) = smol::channel::unbounded();
+    let mut addrs = addrs.into_iter();
+    let mut pending = 0;
+    let mut last_err = None;
+    loop {
+        if let Some(addr) = addrs.next() {
+            let tx = tx.clone();
+            pending += 1;
+            std::thread::spawn(move || {
+                let res = if let Some(timeout) = timeout {
+                    TcpStream::connect_timeout(&addr, timeout)
+                } else {
+                    TcpStream::connect(&addr)
+                };
+                /* If another attempt has won, the receiver is gone and the stream is dropped. */
+                let _ = tx.try_send((addr, res));
+            });
+        }
+        if pending == 0 {
+            break;
+        }
+        let received = if addrs.len() > 0 {
+            match self::timeout(Some(CONNECTION_ATTEMPT_DELAY), rx.recv()).await {
+                Ok(Ok(r)) => Some(r),
+                _ => None,
+            }
+        } else {
+            rx.recv().await.ok()
+        };
+        match received {
+            Some((_, Ok(stream))) => return Ok(stream),
+            Some((addr, Err(err))) => {
+                debug!("Could not connect to {}: {}", addr, err);
+                pending -= 1;
+                last_err = Some((addr, err));
+            }
+            None => {}
+        }
+    }
+    let (addr, err) = last_err.unwrap();
+    Err(crate::error::MeliError::new(format!(
+        "Could not connect to {}:{} ({}): {}",
+        host, port, addr, err
+    ))
+    .set_source(Some(std::sync::Arc::new(err)))
+    .set_kind(crate::error::ErrorKind::Network))
+}
+
+/// Delays between reconnection attempts: they double after every failed attempt up to
+/// `Backoff::MAX_DELAY`, and are randomized so that accounts on the same server don't retry in
+/// lockstep.
+#[derive(Debug, Clone, Default)]
+pub struct Backoff {
+    attempts: u32,
+    next_attempt: Option<std::time::Instant>,
+}
+
+impl Backoff {
+    pub const INITIAL_DELAY: Duration = Duration::from_secs(2);
+    pub const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+    /// Failed attempts since the last successful one.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Time left until the next attempt should be made, if it's not yet due.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.next_attempt
+            .and_then(|t| t.checked_duration_since(std::time::Instant::now()))
+            .filter(|d| *d > Duration::from_secs(0))
+    }
+
+    /// Record a failed attempt and return the delay until the next one.
+    pub fn failed(&mut self) -> Duration {
+        use std::hash::{BuildHasher, Hasher};
+
+        let delay = Self::INITIAL_DELAY
+            .checked_mul(1 << self.attempts.min(16))
+            .unwrap_or(Self::MAX_DELAY)
+            .min(Self::MAX_DELAY);
+        self.attempts += 1;
+        /* Wait between half and all of the delay. */
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u32(self.attempts);
+        let jitter = (hasher.finish() % 1000) as u32;
+        let delay = delay / 2 + delay / 2 * jitter / 1000;
+        self.next_attempt = Some(std::time::Instant::now() + delay);
+        delay
+    }
+
+    pub fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+}
+
 use futures::future::{self, Either, Future};
 
 pub async fn timeout<O>(dur: Option<Duration>, f: impl Future<Output = O>) -> crate::Result<O> {
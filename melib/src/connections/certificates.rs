@@ -0,0 +1,367 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2020  Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Server certificate fingerprints and the certificates the user has chosen to trust.
+//!
+//! Fingerprints are SHA-256 digests of the DER encoding of a certificate. They are kept as
+//! lowercase hex strings and shown to the user in the `AB:CD:..` form `openssl x509 -fingerprint
+//! -sha256` prints.
+
+use crate::error::{ErrorKind, MeliError, Result};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A server presented a certificate that isn't trusted: either it failed validation and hasn't
+/// been accepted before, or it doesn't match the pinned/accepted fingerprints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UntrustedCertificate {
+    pub host: String,
+    pub port: u16,
+    pub fingerprint: String,
+    /// The host has pinned or accepted fingerprints, and this isn't one of them.
+    pub changed: bool,
+}
+
+impl UntrustedCertificate {
+    /// Returns the certificate if `err` was caused by an untrusted certificate.
+    pub fn from_error(err: &MeliError) -> Option<&UntrustedCertificate> {
+        err.source
+            .as_ref()
+            .and_then(|source| source.downcast_ref::<UntrustedCertificate>())
+    }
+
+    /// The `host:port` the certificate was presented on.
+    pub fn address(&self) -> String {
+        address(&self.host, self.port)
+    }
+}
+
+impl fmt::Display for UntrustedCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} certificate with SHA-256 fingerprint {}",
+            if self.changed { "changed" } else { "unknown" },
+            format_fingerprint(&self.fingerprint)
+        )
+    }
+}
+
+impl std::error::Error for UntrustedCertificate {}
+
+impl From<UntrustedCertificate> for MeliError {
+    fn from(val: UntrustedCertificate) -> MeliError {
+        MeliError::new(format!(
+            "The certificate of {} is not trusted. Accept it when prompted, or add its fingerprint to `tls_pinned_certificates`.",
+            val.address()
+        ))
+        .set_source(Some(Arc::new(val)))
+        .set_kind(ErrorKind::Network)
+    }
+}
+
+/// Lowercase hex SHA-256 fingerprint of a DER encoded certificate.
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Parse a user supplied fingerprint, with or without colons.
+pub fn parse_fingerprint(s: &str) -> Option<String> {
+    let ret = s
+        .chars()
+        .filter(|c| *c != ':' && !c.is_ascii_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if ret.len() == 64 && ret.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(ret)
+    } else {
+        None
+    }
+}
+
+pub fn format_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .as_bytes()
+        .chunks(2)
+        .map(|b| String::from_utf8_lossy(b).to_ascii_uppercase())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn trusted_certificates_path(account_name: &str) -> Result<PathBuf> {
+    crate::paths::moved_state_file(
+        Some(account_name),
+        "trusted_certificates",
+        None,
+        &format!("{}_trusted_certificates", account_name.replace('/', "_")),
+    )
+}
+
+/// `host:port`, with IPv6 addresses in brackets.
+pub fn address(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Fingerprints accepted for `address` in the contents of a trusted certificates store, which
+/// holds one `host:port fingerprint` pair per line.
+fn stored_fingerprints(contents: &str, address: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            match (fields.next(), fields.next().and_then(parse_fingerprint)) {
+                (Some(a), Some(fingerprint)) if a == address => Some(fingerprint),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// `contents` with the fingerprint accepted for `address` replaced by `fingerprint`.
+fn store_fingerprint(contents: &str, address: &str, fingerprint: &str) -> String {
+    let mut ret = contents
+        .lines()
+        .filter(|l| l.split_whitespace().next() != Some(address))
+        .map(|l| format!("{}\n", l))
+        .collect::<String>();
+    ret.push_str(&format!("{} {}\n", address, fingerprint));
+    ret
+}
+
+/// Fingerprints the user has accepted for `host` on `port`.
+pub fn trusted_fingerprints(account_name: &str, host: &str, port: u16) -> Vec<String> {
+    let contents = match trusted_certificates_path(account_name).and_then(|p| {
+        if p.exists() {
+            Ok(fs::read_to_string(&p)?)
+        } else {
+            Ok(String::new())
+        }
+    }) {
+        Ok(c) => c,
+        Err(err) => {
+            debug!("Could not read trusted certificates: {}", err);
+            return vec![];
+        }
+    };
+    stored_fingerprints(&contents, &address(host, port))
+}
+
+/// Trust the certificate with `fingerprint` for `host` on `port`, replacing any certificate
+/// accepted before for the same address.
+pub fn trust_certificate(
+    account_name: &str,
+    host: &str,
+    port: u16,
+    fingerprint: &str,
+) -> Result<()> {
+    let path = trusted_certificates_path(account_name)?;
+    let contents = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    crate::paths::write_atomically(
+        &path,
+        store_fingerprint(&contents, &address(host, port), fingerprint).as_bytes(),
+    )
+}
+
+/// Subject, issuer and validity period of a certificate, as shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateDetails {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+}
+
+impl fmt::Display for CertificateDetails {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "subject {}, issued by {}, valid from {} to {}",
+            self.subject, self.issuer, self.not_before, self.not_after
+        )
+    }
+}
+
+/// Split DER encoded `input` into its top-level (tag, contents) elements.
+fn der_elements(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut ret = vec![];
+    while !input.is_empty() {
+        let tag = input[0];
+        let (len, header) = match *input.get(1)? {
+            l if l < 0x80 => (l as usize, 2),
+            l if (0x81..=0x84).contains(&l) => {
+                let n = (l & 0x7f) as usize;
+                let len = input
+                    .get(2..2 + n)?
+                    .iter()
+                    .fold(0_usize, |acc, b| (acc << 8) | *b as usize);
+                (len, 2 + n)
+            }
+            _ => return None,
+        };
+        ret.push((tag, input.get(header..header.checked_add(len)?)?));
+        input = &input[header + len..];
+    }
+    Some(ret)
+}
+
+/// Format a X.501 Name as eg `C=GR, O=meli, CN=mail.example.com`.
+fn der_name(name: &[u8]) -> Option<String> {
+    let mut ret = vec![];
+    for (_, set) in der_elements(name)? {
+        for (_, attribute) in der_elements(set)? {
+            let attribute = der_elements(attribute)?;
+            let (oid, value) = (attribute.get(0)?.1, attribute.get(1)?.1);
+            let key = match oid {
+                [0x55, 0x04, 0x03] => "CN",
+                [0x55, 0x04, 0x06] => "C",
+                [0x55, 0x04, 0x07] => "L",
+                [0x55, 0x04, 0x08] => "ST",
+                [0x55, 0x04, 0x0a] => "O",
+                [0x55, 0x04, 0x0b] => "OU",
+                _ => continue,
+            };
+            ret.push(format!("{}={}", key, String::from_utf8_lossy(value)));
+        }
+    }
+    Some(ret.join(", "))
+}
+
+/// Format a UTCTime or GeneralizedTime as `YYYY-MM-DD HH:MM:SS UTC`.
+fn der_time(tag: u8, time: &[u8]) -> Option<String> {
+    let time = std::str::from_utf8(time).ok()?;
+    let time = match tag {
+        /* UTCTime years 50-99 are 1950-1999 */
+        0x17 if time.get(0..2)? >= "50" => format!("19{}", time),
+        0x17 => format!("20{}", time),
+        0x18 => time.to_string(),
+        _ => return None,
+    };
+    Some(format!(
+        "{}-{}-{} {}:{}:{} UTC",
+        time.get(0..4)?,
+        time.get(4..6)?,
+        time.get(6..8)?,
+        time.get(8..10)?,
+        time.get(10..12)?,
+        time.get(12..14)?
+    ))
+}
+
+/// Extract the details of a DER encoded X.509 certificate.
+pub fn certificate_details(der: &[u8]) -> Option<CertificateDetails> {
+    let certificate = der_elements(der_elements(der)?.get(0)?.1)?;
+    let tbs_certificate = der_elements(certificate.get(0)?.1)?;
+    /* Skip the optional version */
+    let offset = if tbs_certificate.get(0)?.0 == 0xa0 {
+        1
+    } else {
+        0
+    };
+    let issuer = der_name(tbs_certificate.get(offset + 2)?.1)?;
+    let validity = der_elements(tbs_certificate.get(offset + 3)?.1)?;
+    let subject = der_name(tbs_certificate.get(offset + 4)?.1)?;
+    Some(CertificateDetails {
+        subject,
+        issuer,
+        not_before: der_time(validity.get(0)?.0, validity.get(0)?.1)?,
+        not_after: der_time(validity.get(1)?.0, validity.get(1)?.1)?,
+    })
+}
+
+#[test]
+fn test_certificate_fingerprints() {
+    assert_eq!(
+        fingerprint(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        fingerprint(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    /* Two blocks of padding */
+    assert_eq!(
+        fingerprint(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    let formatted = format_fingerprint(&fingerprint(b"abc"));
+    assert!(formatted.starts_with("BA:78:16:BF:"));
+    assert_eq!(parse_fingerprint(&formatted), Some(fingerprint(b"abc")));
+    assert_eq!(parse_fingerprint("BA:78:16"), None);
+}
+
+#[test]
+fn test_certificate_details() {
+    use data_encoding::BASE64;
+    let der = BASE64.decode(b"MIIBsDCCAVagAwIBAgIBATAKBggqhkjOPQQDAjA3MQswCQYDVQQGEwJHUjENMAsGA1UECgwEbWVsaTEZMBcGA1UEAwwQbWFpbC5leGFtcGxlLmNvbTAeFw0yNjEwMTYxMjUwNTdaFw0zNjEwMTMxMjUwNTdaMDcxCzAJBgNVBAYTAkdSMQ0wCwYDVQQKDARtZWxpMRkwFwYDVQQDDBBtYWlsLmV4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEv4LTT9Cm92iKr8yz9WsjNoez15ItW+7SfviE7iT66lPeuG2PDH6wO28X8k8qVonc7RHQVo+03zYQZ8wzc0ul96NTMFEwHQYDVR0OBBYEFHaE5r1HjgWylvVJQ+HKzn2+uYpvMB8GA1UdIwQYMBaAFHaE5r1HjgWylvVJQ+HKzn2+uYpvMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSAAwRQIgCOBdY2J0x/j/Gom+5SQmgAAaTakpgSkA+Zk97Y+sH6oCIQCZgewDM7u74oTQXYTkW/Olk7ybgX1uqGxntnMCOx4vXg==").unwrap();
+    assert_eq!(
+        certificate_details(&der),
+        Some(CertificateDetails {
+            subject: "C=GR, O=meli, CN=mail.example.com".to_string(),
+            issuer: "C=GR, O=meli, CN=mail.example.com".to_string(),
+            not_before: "2026-10-16 12:50:57 UTC".to_string(),
+            not_after: "2036-10-13 12:50:57 UTC".to_string(),
+        })
+    );
+    assert_eq!(certificate_details(&der[..100]), None);
+}
+
+#[test]
+fn test_trusted_certificates() {
+    let a = "a".repeat(64);
+    let b = "b".repeat(64);
+    assert_eq!(address("mail.example.com", 993), "mail.example.com:993");
+    assert_eq!(address("::1", 993), "[::1]:993");
+    let contents = store_fingerprint("", "mail.example.com:993", &a);
+    let contents = store_fingerprint(&contents, "mail.example.com:465", &b);
+    assert_eq!(
+        stored_fingerprints(&contents, "mail.example.com:993"),
+        vec![a.clone()]
+    );
+    assert_eq!(
+        stored_fingerprints(&contents, "mail.example.com:465"),
+        vec![b.clone()]
+    );
+    assert!(stored_fingerprints(&contents, "mail.example.com:143").is_empty());
+    /* Accepting a new certificate replaces the old one of the same address only. */
+    let contents = store_fingerprint(&contents, "mail.example.com:993", &b);
+    assert_eq!(
+        stored_fingerprints(&contents, "mail.example.com:993"),
+        vec![b.clone()]
+    );
+    assert_eq!(
+        stored_fingerprints(&contents, "mail.example.com:465"),
+        vec![b]
+    );
+}
@@ -0,0 +1,246 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2020  Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Per-account TLS settings: certificate pinning, client certificates and the minimum protocol
+//! version.
+
+use super::certificates::{self, UntrustedCertificate};
+use super::Connection;
+use crate::conf::AccountSettings;
+use crate::error::*;
+use crate::shellexpand::ShellExpandTrait;
+use native_tls::{Identity, Protocol, TlsConnector, TlsStream};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsSettings {
+    /// Account whose interactively accepted certificates are also trusted. If empty, only the
+    /// pinned certificates are.
+    pub account_name: String,
+    pub danger_accept_invalid_certs: bool,
+    /// SHA-256 fingerprints of the certificates the server may present.
+    pub pinned_certificates: Vec<String>,
+    /// PKCS#12 archive with the certificate and key to authenticate with.
+    pub client_certificate: Option<PathBuf>,
+    pub client_certificate_password: String,
+    pub min_version: Option<Protocol>,
+}
+
+impl TlsSettings {
+    pub fn new(s: &AccountSettings) -> Result<Self> {
+        let conf_err = |field: &str, value: &str| {
+            MeliError::new(format!(
+                "Configuration error ({}): invalid value for field `{}`: {}",
+                s.name.as_str(),
+                field,
+                value
+            ))
+        };
+        let danger_accept_invalid_certs = match s.extra.get("danger_accept_invalid_certs") {
+            Some(v) => v
+                .parse::<bool>()
+                .map_err(|_| conf_err("danger_accept_invalid_certs", v))?,
+            None => false,
+        };
+        let pinned_certificates = match s.extra.get("tls_pinned_certificates") {
+            Some(v) => {
+                parse_pinned_certificates(v).map_err(|f| conf_err("tls_pinned_certificates", &f))?
+            }
+            None => vec![],
+        };
+        let client_certificate = s
+            .extra
+            .get("tls_client_certificate")
+            .map(|p| Path::new(p).expand());
+        let client_certificate_password = s
+            .extra
+            .get("tls_client_certificate_password")
+            .cloned()
+            .unwrap_or_default();
+        let min_version = match s.extra.get("tls_min_version") {
+            Some(v) => Some(parse_min_version(v).ok_or_else(|| conf_err("tls_min_version", v))?),
+            None => None,
+        };
+        Ok(TlsSettings {
+            account_name: s.name.to_string(),
+            danger_accept_invalid_certs,
+            pinned_certificates,
+            client_certificate,
+            client_certificate_password,
+            min_version,
+        })
+    }
+
+    /// Fingerprints that are accepted for `host` on `port`: the pinned ones and those the user has
+    /// accepted interactively. If there are any, the certificate is checked against them instead
+    /// of being validated.
+    pub fn accepted_fingerprints(&self, host: &str, port: u16) -> Vec<String> {
+        let mut ret = self.pinned_certificates.clone();
+        if !self.account_name.is_empty() {
+            ret.extend(certificates::trusted_fingerprints(
+                &self.account_name,
+                host,
+                port,
+            ));
+        }
+        ret
+    }
+
+    pub fn connector(&self, skip_validation: bool) -> Result<TlsConnector> {
+        let mut connector = TlsConnector::builder();
+        if self.danger_accept_invalid_certs || skip_validation {
+            connector.danger_accept_invalid_certs(true);
+        }
+        if skip_validation {
+            /* The fingerprint identifies the server, the name in the certificate doesn't matter. */
+            connector.danger_accept_invalid_hostnames(true);
+        }
+        if let Some(min_version) = self.min_version {
+            connector.min_protocol_version(Some(min_version));
+        }
+        if let Some(ref path) = self.client_certificate {
+            let der = std::fs::read(path).chain_err_summary(|| {
+                format!("Could not read client certificate {}", path.display())
+            })?;
+            connector.identity(
+                Identity::from_pkcs12(&der, &self.client_certificate_password).chain_err_summary(
+                    || format!("Could not load client certificate {}", path.display()),
+                )?,
+            );
+        }
+        connector
+            .build()
+            .chain_err_kind(crate::error::ErrorKind::Network)
+    }
+
+    /// Negotiate TLS with `host` over `stream`, connected to `port`.
+    ///
+    /// If validation fails, `reconnect` is used to open a new connection and fetch the server's
+    /// certificate so that the returned error can carry an [`UntrustedCertificate`] for the user
+    /// to accept.
+    pub async fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        stream: Connection,
+        reconnect: impl Future<Output = Result<Connection>>,
+    ) -> Result<TlsStream<Connection>> {
+        let accepted = if self.danger_accept_invalid_certs {
+            vec![]
+        } else {
+            self.accepted_fingerprints(host, port)
+        };
+        let connector = self.connector(!accepted.is_empty())?;
+        match handshake(&connector, host, stream) {
+            Ok(stream) => {
+                verify(host, port, &stream, &accepted)?;
+                Ok(stream)
+            }
+            Err(err) if self.danger_accept_invalid_certs || !accepted.is_empty() => {
+                Err(MeliError::from(err).set_kind(crate::error::ErrorKind::Network))
+            }
+            Err(err) => {
+                let probe = async {
+                    let stream = handshake(&self.connector(true)?, host, reconnect.await?)?;
+                    peer_fingerprint(&stream)
+                }
+                .await;
+                match probe {
+                    Ok(fingerprint) => Err(MeliError::from(UntrustedCertificate {
+                        host: host.to_string(),
+                        port,
+                        fingerprint,
+                        changed: false,
+                    })
+                    .set_summary(err.to_string())),
+                    Err(_) => Err(MeliError::from(err).set_kind(crate::error::ErrorKind::Network)),
+                }
+            }
+        }
+    }
+}
+
+/// Parse a comma separated list of fingerprints. Returns the first invalid one on error.
+pub fn parse_pinned_certificates(s: &str) -> std::result::Result<Vec<String>, String> {
+    s.split(',')
+        .filter(|f| !f.trim().is_empty())
+        .map(|f| certificates::parse_fingerprint(f).ok_or_else(|| f.to_string()))
+        .collect()
+}
+
+pub fn parse_min_version(s: &str) -> Option<Protocol> {
+    match s {
+        "1.0" => Some(Protocol::Tlsv10),
+        "1.1" => Some(Protocol::Tlsv11),
+        "1.2" => Some(Protocol::Tlsv12),
+        _ => None,
+    }
+}
+
+/// Check the certificate of an established connection against the `accepted` fingerprints, if
+/// there are any.
+pub fn verify(
+    host: &str,
+    port: u16,
+    stream: &TlsStream<Connection>,
+    accepted: &[String],
+) -> Result<()> {
+    if accepted.is_empty() {
+        return Ok(());
+    }
+    let fingerprint = peer_fingerprint(stream)?;
+    if !accepted.contains(&fingerprint) {
+        return Err(UntrustedCertificate {
+            host: host.to_string(),
+            port,
+            fingerprint,
+            changed: true,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+// FIXME: This is blocking
+pub fn handshake(
+    connector: &TlsConnector,
+    host: &str,
+    stream: Connection,
+) -> std::result::Result<TlsStream<Connection>, native_tls::Error> {
+    let mut conn_result = connector.connect(host, stream);
+    loop {
+        match conn_result {
+            Ok(stream) => return Ok(stream),
+            Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) => {
+                conn_result = midhandshake_stream.handshake();
+            }
+            Err(native_tls::HandshakeError::Failure(err)) => return Err(err),
+        }
+    }
+}
+
+pub fn peer_fingerprint(stream: &TlsStream<Connection>) -> Result<String> {
+    let certificate = stream
+        .peer_certificate()?
+        .ok_or_else(|| MeliError::new("Server did not present a certificate."))?;
+    Ok(certificates::fingerprint(&certificate.to_der()?))
+}
@@ -37,7 +37,7 @@
 //! let s = timestamp_to_string(timestamp, Some("%Y-%m-%d"), true);
 //! assert_eq!(s, "2020-01-08");
 //! ```
-use crate::error::{Result, ResultIntoMeliError};
+use crate::error::{MeliError, Result, ResultIntoMeliError};
 use std::borrow::Cow;
 use std::convert::TryInto;
 use std::ffi::{CStr, CString};
@@ -50,6 +50,7 @@ pub const RFC822_FMT: &str = "%e %h %Y %H:%M:%S \0";
 pub const DEFAULT_FMT: &str = "%a, %d %b %Y %R\0";
 //"Tue May 21 13:46:22 1991\n"
 pub const ASCTIME_FMT: &str = "%a %b %d %H:%M:%S %Y\n\0";
+pub const RFC5322_FMT: &str = "%a, %d %b %Y %T %z\0";
 
 extern "C" {
     fn strptime(
@@ -69,6 +70,10 @@ extern "C" {
 
     fn localtime_r(timep: *const libc::time_t, tm: *mut libc::tm) -> *mut libc::tm;
 
+    fn gmtime_r(timep: *const libc::time_t, tm: *mut libc::tm) -> *mut libc::tm;
+
+    fn tzset();
+
     fn gettimeofday(tv: *mut libc::timeval, tz: *mut libc::timezone) -> i32;
 }
 
@@ -115,6 +120,29 @@ pub fn timestamp_to_string(timestamp: UnixTimestamp, fmt: Option<&str>, posix: b
         let i: i64 = timestamp.try_into().unwrap_or(0);
         localtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
     }
+    format_tm(&new_tm, fmt, posix)
+}
+
+/// Like `timestamp_to_string`, but in the time zone that is `offset` seconds east of UTC instead
+/// of the local one.
+pub fn timestamp_to_string_with_offset(
+    timestamp: UnixTimestamp,
+    offset: i64,
+    fmt: Option<&str>,
+    posix: bool,
+) -> String {
+    let mut new_tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let i: i64 = (timestamp.try_into().unwrap_or(0i64)).saturating_add(offset);
+        gmtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
+    }
+    new_tm.tm_gmtoff = offset;
+    /* `gmtime_r` names the zone "GMT"; there is no name for an arbitrary offset. */
+    new_tm.tm_zone = b"\0".as_ptr() as *const _;
+    format_tm(&new_tm, fmt, posix)
+}
+
+fn format_tm(new_tm: &libc::tm, fmt: Option<&str>, posix: bool) -> String {
     let format: Cow<'_, CStr> = if let Some(cs) = fmt
         .map(str::as_bytes)
         .map(CStr::from_bytes_with_nul)
@@ -152,7 +180,7 @@ pub fn timestamp_to_string(timestamp: UnixTimestamp, fmt: Option<&str>, posix: b
                 vec.as_mut_ptr() as *mut _,
                 256,
                 format.as_ptr(),
-                &new_tm as *const _,
+                new_tm as *const _,
             )
         }
     };
@@ -272,61 +300,89 @@ pub fn rfc822_to_timestamp<T>(s: T) -> Result<UnixTimestamp>
 where
     T: Into<Vec<u8>>,
 {
-    let s = CString::new(s)?;
-    let mut new_tm: libc::tm = unsafe { std::mem::zeroed() };
-    for fmt in &[RFC822_FMT_WITH_TIME, RFC822_FMT] {
-        let fmt = unsafe { CStr::from_bytes_with_nul_unchecked(fmt.as_bytes()) };
-        let ret = {
-            let _with_locale = Locale::new(
-                libc::LC_TIME,
-                b"C\0".as_ptr() as *const i8,
-                std::ptr::null_mut(),
-            )
-            .chain_err_summary(|| "Could not set locale for datetime conversion")
-            .chain_err_kind(crate::error::ErrorKind::External)?;
-            unsafe { strptime(s.as_ptr(), fmt.as_ptr(), &mut new_tm as *mut _) }
-        };
+    Ok(crate::email::parser::dates::rfc5322_date(&s.into()).unwrap_or(0))
+}
 
-        if ret.is_null() {
-            continue;
-        }
-        let rest = unsafe { CStr::from_ptr(ret) };
-        let tm_gmtoff = if rest.to_bytes().len() > 4
-            && rest.to_bytes().is_ascii()
-            && rest.to_bytes()[1..5].iter().all(u8::is_ascii_digit)
-        {
-            // safe since rest.to_bytes().is_ascii()
-            let offset = unsafe { std::str::from_utf8_unchecked(&rest.to_bytes()[0..5]) };
-            if let (Ok(mut hr_offset), Ok(mut min_offset)) =
-                (offset[1..3].parse::<i64>(), offset[3..5].parse::<i64>())
-            {
-                if rest.to_bytes()[0] == b'-' {
-                    hr_offset = -hr_offset;
-                    min_offset = -min_offset;
-                }
-                hr_offset * 60 * 60 + min_offset * 60
-            } else {
-                0
-            }
-        } else {
-            let rest = if rest.to_bytes().starts_with(b"(") && rest.to_bytes().ends_with(b")") {
-                &rest.to_bytes()[1..rest.to_bytes().len() - 1]
-            } else {
-                rest.to_bytes()
-            };
+/// The timestamp of a date in the time zone that is `offset` seconds east of UTC. `month` counts
+/// from 0. Returns `None` if a field is out of range and 0 for dates before the Epoch.
+pub fn timestamp_from_date(
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    offset: i64,
+) -> Option<UnixTimestamp> {
+    let mut is_leap = false;
+    let year = year.checked_sub(1900)?;
+    let _ = year_to_secs(year, &mut is_leap);
+    let days_in_month = match month {
+        1 if is_leap => 29,
+        1 => 28,
+        3 | 5 | 8 | 10 => 30,
+        0..=11 => 31,
+        _ => return None,
+    };
+    /* 60 is a leap second. */
+    if day < 1 || day > days_in_month || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = year.try_into().ok()?;
+    tm.tm_mon = month as i32;
+    tm.tm_mday = day as i32;
+    tm.tm_hour = hour as i32;
+    tm.tm_min = minute as i32;
+    tm.tm_sec = second as i32;
+    Some(
+        tm_to_secs(tm)
+            .ok()
+            .and_then(|secs| secs.checked_sub(offset))
+            .and_then(|secs| secs.try_into().ok())
+            .unwrap_or(0),
+    )
+}
 
-            if let Ok(idx) = TIMEZONE_ABBR.binary_search_by(|probe| probe.0.cmp(rest)) {
-                let (hr_offset, min_offset) = TIMEZONE_ABBR[idx].1;
-                (hr_offset as i64) * 60 * 60 + (min_offset as i64) * 60
-            } else {
-                0
-            }
+/// The offset from UTC in seconds of a time zone abbreviation such as `EEST`.
+pub(crate) fn timezone_abbreviation_offset(name: &[u8]) -> Option<i64> {
+    let name = name.to_ascii_uppercase();
+    TIMEZONE_ABBR
+        .binary_search_by(|probe| probe.0.cmp(name.as_slice()))
+        .ok()
+        .map(|idx| {
+            let (hr_offset, min_offset) = TIMEZONE_ABBR[idx].1;
+            (hr_offset as i64) * 60 * 60 + (min_offset as i64) * 60
+        })
+}
+
+/// Show local times in the time zone `name`, which is either a name from the time zone database
+/// such as `Europe/Athens` or a POSIX `TZ` value such as `EST5EDT`.
+pub fn set_timezone(name: &str) -> Result<()> {
+    let known = name.eq_ignore_ascii_case("UTC")
+        || name.eq_ignore_ascii_case("GMT")
+        || {
+            let dir = std::env::var_os("TZDIR")
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|| std::path::PathBuf::from("/usr/share/zoneinfo"));
+            !name.starts_with('/') && !name.contains("..") && dir.join(name).is_file()
+        }
+        || {
+            /* std offset [dst [offset] [,rule]] */
+            let abbr_len = name.bytes().take_while(u8::is_ascii_alphabetic).count();
+            abbr_len >= 3
+                && name[abbr_len..]
+                    .bytes()
+                    .next()
+                    .map(|b| b.is_ascii_digit() || b == b'+' || b == b'-')
+                    .unwrap_or(false)
         };
-        return Ok(tm_to_secs(new_tm)
-            .map(|res| (res - tm_gmtoff) as u64)
-            .unwrap_or(0));
+    if !known {
+        return Err(MeliError::new(format!("Unknown time zone `{}`", name)));
     }
-    Ok(0)
+    std::env::set_var("TZ", name);
+    unsafe { tzset() };
+    Ok(())
 }
 
 pub fn rfc3339_to_timestamp<T>(s: T) -> Result<UnixTimestamp>
@@ -414,6 +470,56 @@ where
     }
 }
 
+/// The start and the end of the day, month or year `s`, written as `YYYY-MM-DD`, `YYYY-MM` or
+/// `YYYY`, in the local time zone.
+pub fn date_range(s: &str) -> Option<(UnixTimestamp, UnixTimestamp)> {
+    let fields = s
+        .trim()
+        .split('-')
+        .map(|f| {
+            if f.is_empty() || !f.bytes().all(|b| b.is_ascii_digit()) || f.len() > 4 {
+                None
+            } else {
+                f.parse::<i32>().ok()
+            }
+        })
+        .collect::<Option<Vec<i32>>>()?;
+    let (year, month, day) = match fields.as_slice() {
+        [year] => (*year, 1, 1),
+        [year, month] => (*year, *month, 1),
+        [year, month, day] => (*year, *month, *day),
+        _ => return None,
+    };
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let local = |year: i32, month: i32, day: i32| -> Option<UnixTimestamp> {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        /* Let mktime find out whether daylight saving time is in effect. */
+        tm.tm_isdst = -1;
+        let secs = unsafe { mktime(&tm as *const _) };
+        if secs < 0 {
+            None
+        } else {
+            Some(secs as UnixTimestamp)
+        }
+    };
+    let start = local(year, month, day)?;
+    /* mktime moves days past the end of the month to the next one. */
+    if start >= local(year, month + 1, 1)? {
+        return None;
+    }
+    let end = match fields.len() {
+        1 => local(year + 1, 1, 1)?,
+        2 => local(year, month + 1, 1)?,
+        _ => local(year, month, day + 1)?,
+    };
+    Some((start, end))
+}
+
 pub fn now() -> UnixTimestamp {
     use std::mem::MaybeUninit;
     let mut tv = MaybeUninit::<libc::timeval>::uninit();
@@ -432,6 +538,31 @@ fn test_datetime_timestamp() {
     timestamp_to_string(0, None, false);
 }
 
+#[test]
+fn test_datetime_date_range() {
+    let (start, end) = date_range("2020-02").unwrap();
+    assert_eq!(date_range("2020-02-01").unwrap().0, start);
+    assert_eq!(date_range("2020-03").unwrap().0, end);
+    /* Leap year. */
+    assert_eq!(date_range("2020-02-29").unwrap().1, end);
+    assert_eq!(date_range("2019-02-29"), None);
+    let (start, end) = date_range("2020").unwrap();
+    assert_eq!(date_range("2020-01").unwrap().0, start);
+    assert_eq!(date_range("2020-12-31").unwrap().1, end);
+    assert!(end - start >= 365 * 24 * 60 * 60);
+    for invalid in &[
+        "",
+        "2020-13",
+        "2020-00",
+        "2020-1-",
+        "20x0",
+        "1960",
+        "2020-01-32",
+    ] {
+        assert_eq!(date_range(invalid), None, "{}", invalid);
+    }
+}
+
 #[test]
 fn test_datetime_rfcs() {
     if unsafe { libc::setlocale(libc::LC_ALL, b"\0".as_ptr() as _) }.is_null() {
@@ -543,6 +674,43 @@ fn test_datetime_rfcs() {
         rfc822_to_timestamp("Mon, 16 Mar 2020 10:23:01 +0200").unwrap(),
         1584346981
     );
+
+    /* Obsolete and malformed dates seen in the wild. */
+    for date in &[
+        "Mon, 16 Mar 20 08:23:01 GMT",
+        "Mon, 16 Mar 20 08:23:01 Z",
+        "Mon, 16 Mar 2020 8:23:01 +0000",
+        "monday, 16 MARCH 2020 10:23:01 +02:00",
+        "Mon 16 Mar 2020 03:23:01 EST",
+        "Mon, 16 Mar 2020 10:23:01",
+        "  Mon ,16 Mar 2020 (comment) 10:23:01 +0200 (EET)",
+    ] {
+        assert_eq!(
+            rfc822_to_timestamp(*date).unwrap(),
+            1584346981 + if date.ends_with("01") { 2 * 3600 } else { 0 },
+            "{}",
+            date
+        );
+    }
+    assert_eq!(
+        rfc822_to_timestamp("Tue, 15 Jun 99 10:23 -0700").unwrap(),
+        rfc822_to_timestamp("Tue, 15 Jun 1999 10:23:00 -0700").unwrap(),
+    );
+    assert_eq!(
+        rfc822_to_timestamp("Tue, 15 Jun 101 10:23 -0700").unwrap(),
+        rfc822_to_timestamp("Tue, 15 Jun 2001 10:23:00 -0700").unwrap(),
+    );
+    assert_eq!(rfc822_to_timestamp("Mon, 31 Feb 2020 10:23:01").unwrap(), 0);
+    assert_eq!(rfc822_to_timestamp("Mon, 16 Mar 2020 25:23:01").unwrap(), 0);
+
+    assert_eq!(
+        timestamp_to_string_with_offset(1584346981, 2 * 3600, Some(RFC5322_FMT), true),
+        "Mon, 16 Mar 2020 10:23:01 +0200"
+    );
+    assert_eq!(
+        timestamp_to_string_with_offset(1584346981, -(5 * 3600 + 30 * 60), Some(RFC5322_FMT), true),
+        "Mon, 16 Mar 2020 02:53:01 -0530"
+    );
 }
 
 #[allow(clippy::zero_prefixed_literal)]
@@ -92,8 +92,10 @@
 pub mod address;
 pub mod attachment_types;
 pub mod attachments;
+pub mod autocrypt;
 pub mod compose;
 pub mod headers;
+pub mod icalendar;
 pub mod list_management;
 pub mod mailto;
 pub mod parser;
@@ -112,7 +114,6 @@ use crate::thread::ThreadNodeHash;
 
 use smallvec::SmallVec;
 use std::borrow::Cow;
-use std::collections::hash_map::DefaultHasher;
 use std::convert::TryInto;
 use std::hash::Hasher;
 use std::ops::Deref;
@@ -198,7 +199,49 @@ impl Mail {
     }
 }
 
-pub type EnvelopeHash = u64;
+/// Backends derive it from the identity of a message in their store with an [`EnvelopeHasher`].
+/// It is 128 bits wide so that collisions are unlikely even in very large stores; they are still
+/// detected when envelopes are added to a [`Collection`](crate::Collection).
+pub type EnvelopeHash = u128;
+
+/// Hashes the identity of a message into an [`EnvelopeHash`]: the first 128 bits of the SHA-256
+/// digest of what is written to it. Unlike `DefaultHasher`, its output doesn't change between
+/// Rust releases, so the hashes can be kept in caches.
+#[derive(Clone, Default)]
+pub struct EnvelopeHasher(sha2::Sha256);
+
+impl EnvelopeHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn envelope_hash(&self) -> EnvelopeHash {
+        use sha2::Digest;
+        let digest = self.0.clone().finalize();
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        u128::from_be_bytes(bytes)
+    }
+}
+
+impl Hasher for EnvelopeHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        sha2::Digest::update(&mut self.0, bytes);
+    }
+
+    /// The lower 64 bits of [`EnvelopeHasher::envelope_hash`].
+    fn finish(&self) -> u64 {
+        self.envelope_hash() as u64
+    }
+}
+
+/// 64-bit FNV-1a hash of `bytes`. Unlike `DefaultHasher`, its output is guaranteed not to change
+/// between Rust releases, so it can be used for values that are saved to disk.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
 
 /// `Envelope` represents all the header and structure data of an email we need to know.
 ///
@@ -224,6 +267,8 @@ pub struct Envelope {
     pub flags: Flag,
     pub has_attachments: bool,
     pub labels: SmallVec<[u64; 8]>,
+    /// Size of the raw message in bytes, `0` if the backend doesn't know it.
+    pub size: usize,
 }
 
 impl core::fmt::Debug for Envelope {
@@ -267,6 +312,7 @@ impl Envelope {
             has_attachments: false,
             flags: Flag::default(),
             labels: SmallVec::new(),
+            size: 0,
         }
     }
 
@@ -276,9 +322,10 @@ impl Envelope {
     }
 
     pub fn from_bytes(bytes: &[u8], flags: Option<Flag>) -> Result<Envelope> {
-        let mut h = DefaultHasher::new();
+        let mut h = EnvelopeHasher::new();
         h.write(bytes);
-        let mut e = Envelope::new(h.finish());
+        let mut e = Envelope::new(h.envelope_hash());
+        e.size = bytes.len();
         let res = e.populate_headers(bytes).ok();
         if res.is_some() {
             if let Some(f) = flags {
@@ -300,6 +347,11 @@ impl Envelope {
                 bytes = &bytes[offset + 1..];
             }
         }
+        let headers_bytes = bytes
+            .find(b"\r\n\r\n")
+            .or_else(|| bytes.find(b"\n\n"))
+            .map(|pos| &bytes[..pos])
+            .unwrap_or(bytes);
         let (headers, body) = match parser::mail(bytes) {
             Ok(v) => v,
             Err(e) => {
@@ -308,8 +360,10 @@ impl Envelope {
                 return Err(MeliError::new(error_msg));
             }
         };
-        for (name, value) in headers {
+        for (name, raw_value) in headers {
             let name: HeaderName = name.try_into()?;
+            let value = parser::encodings::decode_8bit_header(raw_value);
+            let value = value.as_ref();
             if name == "to" {
                 let parse_result = parser::address::rfc2822address_list(value);
                 if let Ok((_, value)) = parse_result {
@@ -336,11 +390,11 @@ impl Envelope {
                     self.set_subject(value);
                 };
             } else if name == "message-id" {
-                self.set_message_id(value);
+                self.set_message_id(raw_value);
             } else if name == "references" {
-                self.set_references(value);
+                self.set_references(raw_value);
             } else if name == "in-reply-to" {
-                self.set_in_reply_to(value);
+                self.set_in_reply_to(raw_value);
             } else if name == "date" {
                 let parse_result = parser::encodings::phrase(value, false);
                 if let Ok((_, value)) = parse_result {
@@ -349,13 +403,13 @@ impl Envelope {
                     self.set_date(value);
                 }
             } else if name == "content-type" {
-                match parser::attachments::content_type(value) {
+                match parser::attachments::content_type(raw_value) {
                     Ok((_, (ct, cst, ref params)))
                         if ct.eq_ignore_ascii_case(b"multipart")
                             && cst.eq_ignore_ascii_case(b"mixed") =>
                     {
                         let mut builder = AttachmentBuilder::default();
-                        builder.set_content_type_from_bytes(value);
+                        builder.set_content_type_from_bytes(raw_value);
                         let mut boundary = None;
                         for (n, v) in params {
                             if n.eq_ignore_ascii_case(b"boundary") {
@@ -395,8 +449,11 @@ impl Envelope {
             self.set_datetime(d);
         }
         if self.message_id.raw().is_empty() {
-            let hash = self.hash;
-            self.set_message_id(format!("<{:x}>", hash).as_bytes());
+            /* Derive the id from the headers instead of the envelope hash, which depends on the
+             * backend, so that copies of the message in other mailboxes get the same id. */
+            self.set_message_id(
+                format!("<{:016x}@meli.invalid>", stable_hash(headers_bytes)).as_bytes(),
+            );
         }
         if self.references.is_some() {
             if let Some(pos) = self
@@ -488,6 +545,14 @@ impl Envelope {
         self.to.as_slice()
     }
 
+    pub fn cc(&self) -> &[Address] {
+        self.cc.as_slice()
+    }
+
+    pub fn bcc(&self) -> &[Address] {
+        self.bcc.as_slice()
+    }
+
     pub fn field_to_to_string(&self) -> String {
         if self.to.is_empty() {
             self.other_headers
@@ -779,6 +844,35 @@ impl Envelope {
         self.has_attachments
     }
 
+    /// Whether the message is `multipart/signed`, going by its Content-Type header.
+    pub fn is_signed(&self) -> bool {
+        self.is_multipart(b"signed")
+    }
+
+    /// Whether the message is `multipart/encrypted`, going by its Content-Type header.
+    pub fn is_encrypted(&self) -> bool {
+        self.is_multipart(b"encrypted")
+    }
+
+    fn is_multipart(&self, subtype: &[u8]) -> bool {
+        self.other_headers
+            .get("Content-Type")
+            .and_then(|value| parser::attachments::content_type(value.as_bytes()).ok())
+            .map(|(_, (ct, cst, _))| {
+                ct.eq_ignore_ascii_case(b"multipart") && cst.eq_ignore_ascii_case(subtype)
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn set_size(&mut self, new_val: usize) -> &mut Self {
+        self.size = new_val;
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     pub fn labels(&self) -> &SmallVec<[u64; 8]> {
         &self.labels
     }
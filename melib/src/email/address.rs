@@ -236,6 +236,19 @@ impl Address {
         }
     }
 
+    /// Get the individual mailboxes this address refers to: the address itself if it is a
+    /// mailbox, or the members of a group (RFC 5322 section 3.4).
+    pub fn mailboxes(&self) -> Vec<&Address> {
+        match self {
+            Address::Mailbox(_) => vec![self],
+            Address::Group(g) => g
+                .mailbox_list
+                .iter()
+                .flat_map(|addr| addr.mailboxes())
+                .collect(),
+        }
+    }
+
     /// Get subaddress out of an address (e.g. `ken+subaddress@example.org`).
     ///
     /// Subaddresses are commonly text following a "+" character in an email address's local part
@@ -334,7 +347,7 @@ impl core::fmt::Display for Address {
                     g.mailbox_list.iter().map(|a| format!("{}", a)).collect();
                 write!(
                     f,
-                    "{}: {}",
+                    "{}: {};",
                     g.display_name.display(&g.raw),
                     attachment_strings.join(", ")
                 )
@@ -442,6 +455,30 @@ fn test_strbuilder() {
     );
 }
 
+#[test]
+fn test_group_mailboxes() {
+    let (_, group) =
+        parser::address::address(b"A Group:Ed Jones <c@a.test>,joe@where.test;").unwrap();
+    assert_eq!(
+        group
+            .mailboxes()
+            .into_iter()
+            .map(Address::get_email)
+            .collect::<Vec<String>>(),
+        vec!["c@a.test".to_string(), "joe@where.test".to_string()]
+    );
+    assert_eq!(
+        group.to_string(),
+        "A Group: Ed Jones <c@a.test>, joe@where.test;"
+    );
+    assert_eq!(
+        parser::address::address(group.to_string().as_bytes())
+            .unwrap()
+            .1,
+        group
+    );
+}
+
 impl core::fmt::Display for MessageID {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         if self.val().is_ascii() {
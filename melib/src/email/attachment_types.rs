@@ -488,7 +488,7 @@ impl Display for ContentTransferEncoding {
             ContentTransferEncoding::Base64 => write!(f, "base64"),
             ContentTransferEncoding::QuotedPrintable => write!(f, "quoted-printable"),
             ContentTransferEncoding::Other { tag: ref t } => {
-                panic!("unknown encoding {:?}", str::from_utf8(t))
+                write!(f, "{}", String::from_utf8_lossy(t))
             }
         }
     }
@@ -552,6 +552,16 @@ impl Display for ContentDispositionKind {
         }
     }
 }
+impl Display for ContentDisposition {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.kind)?;
+        if let Some(ref filename) = self.filename {
+            write!(f, "; filename=\"{}\"", filename)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<&[u8]> for ContentDisposition {
     fn from(val: &[u8]) -> ContentDisposition {
         crate::email::parser::attachments::content_disposition(val)
@@ -295,7 +295,7 @@ impl AttachmentBuilder {
                 debug!(
                     "error {:?}\n\traw: {:?}\n\tboundary: {:?}",
                     a,
-                    str::from_utf8(raw).unwrap(),
+                    String::from_utf8_lossy(raw),
                     boundary
                 );
                 Vec::new()
@@ -591,6 +591,17 @@ impl Attachment {
         String::from_utf8_lossy(text.as_slice()).into()
     }
 
+    /// The charset the text of this part was decoded as, if it wasn't valid in its declared
+    /// charset, or wasn't UTF-8 and didn't declare one.
+    pub fn charset_fallback(&self) -> Option<Charset> {
+        match self.content_type {
+            ContentType::Text { charset, .. } => {
+                parser::encodings::decode_charset_fallback(&transfer_decode(self), charset).1
+            }
+            _ => None,
+        }
+    }
+
     pub fn mime_type(&self) -> String {
         self.content_type.to_string()
     }
@@ -874,13 +885,9 @@ pub fn decode_rec<'a, 'b>(a: &'a Attachment, mut filter: Option<Filter<'b>>) ->
     decode_rec_helper(a, &mut filter)
 }
 
-fn decode_helper<'a, 'b>(a: &'a Attachment, filter: &mut Option<Filter<'b>>) -> Vec<u8> {
-    let charset = match a.content_type {
-        ContentType::Text { charset: c, .. } => c,
-        _ => Default::default(),
-    };
-
-    let bytes = match a.content_transfer_encoding {
+/// The body of `a` without its Content-Transfer-Encoding.
+fn transfer_decode(a: &Attachment) -> Vec<u8> {
+    match a.content_transfer_encoding {
         ContentTransferEncoding::Base64 => match BASE64_MIME.decode(a.body()) {
             Ok(v) => v,
             _ => a.body().to_vec(),
@@ -893,16 +900,18 @@ fn decode_helper<'a, 'b>(a: &'a Attachment, filter: &mut Option<Filter<'b>>) ->
         ContentTransferEncoding::_7Bit
         | ContentTransferEncoding::_8Bit
         | ContentTransferEncoding::Other { .. } => a.body().to_vec(),
-    };
+    }
+}
 
-    let mut ret = if a.content_type.is_text() {
-        if let Ok(v) = parser::encodings::decode_charset(&bytes, charset) {
-            v.into_bytes()
-        } else {
-            a.body().to_vec()
+fn decode_helper<'a, 'b>(a: &'a Attachment, filter: &mut Option<Filter<'b>>) -> Vec<u8> {
+    let bytes = transfer_decode(a);
+    let mut ret = match a.content_type {
+        ContentType::Text { charset, .. } => {
+            parser::encodings::decode_charset_fallback(&bytes, charset)
+                .0
+                .into_bytes()
         }
-    } else {
-        bytes.to_vec()
+        _ => bytes,
     };
     if let Some(filter) = filter {
         filter(a, &mut ret);
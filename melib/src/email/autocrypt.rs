@@ -0,0 +1,308 @@
+/*
+ * meli - email module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! [Autocrypt](https://autocrypt.org/level1.html) `Autocrypt` headers and Setup Messages */
+use super::attachment_types::{ContentDisposition, ContentDispositionKind, ContentType};
+use super::compose::{random, Draft};
+use super::{AttachmentBuilder, Envelope};
+use crate::{MeliError, Result};
+use data_encoding::BASE64;
+
+/// Header that marks Autocrypt Setup Messages.
+pub const SETUP_MESSAGE_HEADER: &str = "Autocrypt-Setup-Message";
+/// Content type of the attachment of Autocrypt Setup Messages with the encrypted secret key.
+pub const SETUP_MESSAGE_CONTENT_TYPE: &str = "application/autocrypt-setup";
+
+/// An `Autocrypt` header, announcing the OpenPGP key of its `addr`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AutocryptHeader {
+    pub addr: String,
+    /// `prefer-encrypt=mutual`: the sender wants encrypted replies if the recipient does too.
+    pub prefer_encrypt: bool,
+    /// The binary OpenPGP transferable public key.
+    pub keydata: Vec<u8>,
+}
+
+impl AutocryptHeader {
+    /// Parse the value of an `Autocrypt` header. Headers with unknown critical attributes (those
+    /// whose name doesn't start with `_`) are rejected, as the specification requires.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut addr = None;
+        let mut prefer_encrypt = false;
+        let mut keydata = None;
+        for attribute in value.split(';') {
+            let attribute = attribute.trim();
+            if attribute.is_empty() {
+                continue;
+            }
+            let (name, value) = match attribute.find('=') {
+                Some(pos) => (attribute[..pos].trim(), attribute[pos + 1..].trim()),
+                None => {
+                    return Err(MeliError::new(format!(
+                        "Invalid Autocrypt attribute `{}`.",
+                        attribute
+                    )))
+                }
+            };
+            match name {
+                "addr" => addr = Some(value.to_lowercase()),
+                "prefer-encrypt" => prefer_encrypt = value == "mutual",
+                "keydata" => {
+                    let value = value
+                        .bytes()
+                        .filter(|b| !b.is_ascii_whitespace())
+                        .collect::<Vec<u8>>();
+                    keydata = Some(BASE64.decode(&value).map_err(|err| {
+                        MeliError::new(format!("Invalid Autocrypt keydata: {}", err))
+                    })?);
+                }
+                _ if name.starts_with('_') => {}
+                other => {
+                    return Err(MeliError::new(format!(
+                        "Unknown critical Autocrypt attribute `{}`.",
+                        other
+                    )))
+                }
+            }
+        }
+        match (addr, keydata) {
+            (Some(addr), Some(keydata)) if !keydata.is_empty() => Ok(AutocryptHeader {
+                addr,
+                prefer_encrypt,
+                keydata,
+            }),
+            (None, _) => Err(MeliError::new("Autocrypt header has no `addr` attribute.")),
+            (_, _) => Err(MeliError::new(
+                "Autocrypt header has no `keydata` attribute.",
+            )),
+        }
+    }
+
+    /// The `Autocrypt` header of `envelope`, if it has exactly one and it belongs to its single
+    /// `From` address.
+    pub fn from_envelope(envelope: &Envelope) -> Option<Self> {
+        let value = envelope.other_headers().get("Autocrypt")?;
+        let from = match envelope.from() {
+            [from] => from.get_email().to_lowercase(),
+            _ => return None,
+        };
+        Self::parse(value).ok().filter(|header| header.addr == from)
+    }
+
+    /// The value of the header, folded to fit in header lines.
+    pub fn to_header_value(&self) -> String {
+        let mut ret = format!("addr={};", self.addr);
+        if self.prefer_encrypt {
+            ret.push_str(" prefer-encrypt=mutual;");
+        }
+        ret.push_str(" keydata=");
+        let keydata = BASE64.encode(&self.keydata);
+        for chunk in keydata.as_bytes().chunks(76) {
+            ret.push_str("\r\n ");
+            ret.push_str(std::str::from_utf8(chunk).unwrap());
+        }
+        ret
+    }
+}
+
+/// A new Setup Code: 36 random digits in nine groups of four, used as the passphrase of a Setup
+/// Message.
+pub fn gen_setup_code() -> String {
+    format_setup_code(&random::gen_digits(36)).unwrap()
+}
+
+/// The Setup Code typed by the user in `input`, with or without dashes and spaces, or `None` if
+/// it doesn't have 36 digits.
+pub fn format_setup_code(input: &str) -> Option<String> {
+    let digits = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>();
+    if digits.len() != 36 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(
+        digits
+            .as_bytes()
+            .chunks(4)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect::<Vec<&str>>()
+            .join("-"),
+    )
+}
+
+/// The attachment of a Setup Message: `armored`, the ASCII armored secret key encrypted with
+/// `setup_code`, with the armor headers that help the user enter the code.
+pub fn setup_message_payload(armored: &str, setup_code: &str) -> String {
+    let armor_headers = format!(
+        "Passphrase-Format: numeric9x4\r\nPassphrase-Begin: {}\r\n",
+        &setup_code[..2]
+    );
+    let armored = armored.replace("\r\n", "\n").replace('\n', "\r\n");
+    let armored = match armored.find("-----BEGIN PGP MESSAGE-----\r\n") {
+        Some(pos) => {
+            let pos = pos + "-----BEGIN PGP MESSAGE-----\r\n".len();
+            format!("{}{}{}", &armored[..pos], armor_headers, &armored[pos..])
+        }
+        None => armored,
+    };
+    format!(
+        "<html><body>\r\n<p>This is the Autocrypt Setup File used to transfer settings and keys \
+         between clients. You can decrypt it using the Setup Code presented on your old device, \
+         and then import the contained key into your keyring.</p>\r\n<pre>\r\n{}</pre>\r\n\
+         </body></html>\r\n",
+        armored
+    )
+}
+
+/// A Setup Message from and to `address`, with `payload` as its attachment.
+pub fn setup_message(address: &str, payload: String) -> Draft {
+    let mut draft = Draft::default();
+    draft
+        .set_header("From", address.to_string())
+        .set_header("To", address.to_string())
+        .set_header("Subject", "Autocrypt Setup Message".to_string())
+        .set_header(SETUP_MESSAGE_HEADER, "v1".to_string())
+        .set_body(
+            "This message contains all information to transfer your Autocrypt settings along \
+             with your secret key securely from your original device.\n\n\
+             To set up your new device for Autocrypt, please follow the instructions that should \
+             be presented by your new device.\n\n\
+             You can keep this message and use it as a backup for your secret key. If you want to \
+             do this, you should write down the Setup Code and store it securely.\n"
+                .to_string(),
+        );
+    let mut attachment = AttachmentBuilder::default();
+    attachment
+        .set_raw(payload.into_bytes())
+        .set_body_to_raw()
+        .set_content_type(ContentType::Other {
+            name: Some("autocrypt-setup-message.html".to_string()),
+            tag: SETUP_MESSAGE_CONTENT_TYPE.as_bytes().to_vec(),
+        })
+        .set_content_disposition(ContentDisposition {
+            kind: ContentDispositionKind::Attachment,
+            ..Default::default()
+        });
+    draft.attachments_mut().push(attachment);
+    draft
+}
+
+/// The encrypted secret key in the attachment of a Setup Message.
+pub fn setup_message_armored_key(payload: &str) -> Option<&str> {
+    const END: &str = "-----END PGP MESSAGE-----";
+    let start = payload.find("-----BEGIN PGP MESSAGE-----")?;
+    let end = start + payload[start..].find(END)? + END.len();
+    Some(&payload[start..end])
+}
+
+#[test]
+fn test_autocrypt_header() {
+    let header = AutocryptHeader::parse(
+        "addr=Alice@example.com; prefer-encrypt=mutual; keydata=\n mQGNBF5E\n  c2Vj",
+    )
+    .unwrap();
+    assert_eq!(header.addr, "alice@example.com");
+    assert!(header.prefer_encrypt);
+    assert_eq!(header.keydata, BASE64.decode(b"mQGNBF5Ec2Vj").unwrap());
+
+    let header =
+        AutocryptHeader::parse("addr=bob@example.com; _extra=ignored; keydata=c2Vj").unwrap();
+    assert!(!header.prefer_encrypt);
+
+    assert!(AutocryptHeader::parse("addr=bob@example.com; keydata=c2Vj; unknown=1").is_err());
+    assert!(AutocryptHeader::parse("addr=bob@example.com").is_err());
+    assert!(AutocryptHeader::parse("keydata=c2Vj").is_err());
+    assert!(AutocryptHeader::parse("addr=bob@example.com; keydata=!!").is_err());
+
+    let envelope = Envelope::from_bytes(
+        b"From: Bob <bob@example.com>\nTo: alice@example.com\nSubject: hi\nAutocrypt: addr=bob@example.com; keydata=c2Vj\n\nhello\n",
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        AutocryptHeader::from_envelope(&envelope).map(|h| h.addr),
+        Some("bob@example.com".to_string())
+    );
+    /* The header must belong to the sender. */
+    let envelope = Envelope::from_bytes(
+        b"From: Mallory <mallory@example.com>\nTo: alice@example.com\nSubject: hi\nAutocrypt: addr=bob@example.com; keydata=c2Vj\n\nhello\n",
+        None,
+    )
+    .unwrap();
+    assert!(AutocryptHeader::from_envelope(&envelope).is_none());
+
+    let header = AutocryptHeader {
+        addr: "bob@example.com".to_string(),
+        prefer_encrypt: true,
+        keydata: vec![7; 100],
+    };
+    let value = header.to_header_value();
+    assert!(value.lines().all(|l| l.len() <= 78), "{}", value);
+    assert_eq!(AutocryptHeader::parse(&value).unwrap(), header);
+}
+
+#[test]
+fn test_autocrypt_setup_code() {
+    let code = gen_setup_code();
+    assert_eq!(code.len(), 36 + 8);
+    assert_eq!(
+        format_setup_code(&code.replace('-', " ")),
+        Some(code.clone())
+    );
+    assert_eq!(format_setup_code("1234-5678"), None);
+    assert_eq!(format_setup_code(&"a".repeat(36)), None);
+
+    let payload = setup_message_payload(
+        "-----BEGIN PGP MESSAGE-----\n\nwy4ECQMI\n=AbCd\n-----END PGP MESSAGE-----\n",
+        &code,
+    );
+    assert!(payload.starts_with("<html>"));
+    let armored = setup_message_armored_key(&payload).unwrap();
+    assert_eq!(
+        armored,
+        format!(
+            "-----BEGIN PGP MESSAGE-----\r\nPassphrase-Format: numeric9x4\r\nPassphrase-Begin: \
+             {}\r\n\r\nwy4ECQMI\r\n=AbCd\r\n-----END PGP MESSAGE-----",
+            &code[..2]
+        )
+    );
+    assert_eq!(setup_message_armored_key("no key"), None);
+
+    let message = setup_message("alice@example.com", payload.clone())
+        .finalise()
+        .unwrap();
+    let envelope = Envelope::from_bytes(message.as_bytes(), None).unwrap();
+    assert_eq!(&envelope.other_headers()[SETUP_MESSAGE_HEADER], "v1");
+    let body = envelope.body_bytes(message.as_bytes());
+    let attachment = body
+        .attachments()
+        .into_iter()
+        .find(|a| a.mime_type() == SETUP_MESSAGE_CONTENT_TYPE)
+        .unwrap();
+    assert_eq!(
+        setup_message_armored_key(&String::from_utf8_lossy(&super::attachments::decode(
+            &attachment,
+            None
+        ))),
+        Some(armored)
+    );
+}
@@ -22,7 +22,8 @@
 /*! Compose a `Draft`, with MIME and attachment support */
 use super::*;
 use crate::email::attachment_types::{
-    Charset, ContentTransferEncoding, ContentType, MultipartType,
+    Charset, ContentDisposition, ContentDispositionKind, ContentTransferEncoding, ContentType,
+    MultipartType,
 };
 use crate::email::attachments::{decode, decode_rec, AttachmentBuilder};
 use crate::shellexpand::ShellExpandTrait;
@@ -255,7 +256,8 @@ impl Draft {
 
         if self.attachments.is_empty() {
             let content_type: ContentType = Default::default();
-            let content_transfer_encoding: ContentTransferEncoding = ContentTransferEncoding::_8Bit;
+            let content_transfer_encoding: ContentTransferEncoding =
+                mime::TransferEncoding::Auto.content_transfer_encoding(self.body.as_bytes());
             ret.push_str(&format!(
                 "Content-Type: {}; charset=\"utf-8\"\r\n",
                 content_type
@@ -265,7 +267,8 @@ impl Draft {
                 content_transfer_encoding
             ));
             ret.push_str("\r\n");
-            for line in self.body.lines() {
+            let body = mime::encode_body(self.body.as_bytes(), &content_transfer_encoding);
+            for line in String::from_utf8_lossy(&body).lines() {
                 ret.push_str(line);
                 ret.push_str("\r\n");
             }
@@ -276,8 +279,15 @@ impl Draft {
             let mut parts = Vec::with_capacity(self.attachments.len() + 1);
             let attachments = std::mem::replace(&mut self.attachments, Vec::new());
             if !self.body.is_empty() {
+                let content_transfer_encoding =
+                    mime::TransferEncoding::Auto.content_transfer_encoding(self.body.as_bytes());
                 let mut body_attachment = AttachmentBuilder::default();
-                body_attachment.set_raw(self.body.as_bytes().to_vec());
+                body_attachment
+                    .set_raw(mime::encode_body(
+                        self.body.as_bytes(),
+                        &content_transfer_encoding,
+                    ))
+                    .set_content_transfer_encoding(content_transfer_encoding);
                 parts.push(body_attachment);
             }
             parts.extend(attachments.into_iter());
@@ -318,7 +328,12 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
             kind: crate::email::attachment_types::Text::Plain,
             charset: Charset::UTF8,
             parameters: ref v,
-        } if v.is_empty() => {
+        } if v.is_empty() && a.content_disposition.kind.is_inline() => {
+            ret.push_str("Content-Type: text/plain; charset=\"utf-8\"\r\n");
+            ret.push_str(&format!(
+                "Content-Transfer-Encoding: {}\r\n",
+                a.content_transfer_encoding
+            ));
             ret.push_str("\r\n");
             for line in String::from_utf8_lossy(a.raw()).lines() {
                 ret.push_str(line);
@@ -326,6 +341,12 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
             }
         }
         Text { .. } => {
+            if a.content_disposition.kind.is_attachment() {
+                ret.push_str(&format!(
+                    "Content-Disposition: {}\r\n",
+                    a.content_disposition
+                ));
+            }
             for line in a.build().into_raw().lines() {
                 ret.push_str(line);
                 ret.push_str("\r\n");
@@ -371,8 +392,8 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
             }
         }
         _ => {
-            let content_transfer_encoding: ContentTransferEncoding = if a.raw().is_ascii() {
-                ContentTransferEncoding::_8Bit
+            let content_transfer_encoding: ContentTransferEncoding = if mime::is_7bit(a.raw()) {
+                ContentTransferEncoding::_7Bit
             } else {
                 ContentTransferEncoding::Base64
             };
@@ -388,7 +409,10 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
                     a.content_type()
                 ));
             }
-            ret.push_str("Content-Disposition: attachment\r\n");
+            ret.push_str(&format!(
+                "Content-Disposition: {}\r\n",
+                a.content_disposition
+            ));
             ret.push_str(&format!(
                 "Content-Transfer-Encoding: {}\r\n",
                 content_transfer_encoding
@@ -455,6 +479,77 @@ mod tests {
         println!("{}", default.finalise().unwrap());
         */
     }
+
+    #[test]
+    fn test_attachment_disposition() {
+        let mut default = Draft::default();
+        default.set_body("body".to_string());
+        let mut attachment = AttachmentBuilder::default();
+        attachment
+            .set_raw(b"notes".to_vec())
+            .set_body_to_raw()
+            .set_content_type(ContentType::Text {
+                kind: crate::email::attachment_types::Text::Plain,
+                parameters: Vec::new(),
+                charset: Charset::UTF8,
+            })
+            .set_content_disposition(ContentDisposition {
+                kind: ContentDispositionKind::Attachment,
+                filename: Some("notes.txt".to_string()),
+                ..Default::default()
+            });
+        default.attachments_mut().push(attachment);
+        let output = default.finalise().unwrap();
+        assert!(output.contains("Content-Disposition: attachment; filename=\"notes.txt\"\r\n"));
+    }
+
+    #[test]
+    fn test_finalise_transfer_encoding() {
+        let body = format!(
+            "{}\n\nΚαλημέρα, {}\nend \n",
+            "Long line with words. ".repeat(60),
+            "a long line with a greeting in it ".repeat(30)
+        );
+        for attachment in &[false, true] {
+            let mut draft = Draft::default();
+            draft.set_body(body.clone());
+            if *attachment {
+                let mut attachment = AttachmentBuilder::default();
+                attachment
+                    .set_raw(b"notes".to_vec())
+                    .set_body_to_raw()
+                    .set_content_type(ContentType::Other {
+                        name: Some("notes.bin".to_string()),
+                        tag: b"application/octet-stream".to_vec(),
+                    });
+                draft.attachments_mut().push(attachment);
+            }
+            let output = draft.finalise().unwrap();
+            assert!(output.contains("Content-Transfer-Encoding: quoted-printable\r\n"));
+            assert!(output.is_ascii());
+            /* The encoded body, after its headers and up to the next part. */
+            let encoded = output
+                .split("Content-Transfer-Encoding: quoted-printable\r\n")
+                .nth(1)
+                .unwrap()
+                .split("\r\n--")
+                .next()
+                .unwrap();
+            assert!(encoded.split("\r\n").all(|line| line.len() <= 76));
+            let envelope = Envelope::from_bytes(output.as_bytes(), None).unwrap();
+            let text = envelope.body_bytes(output.as_bytes()).text();
+            /* The line break before a boundary belongs to the boundary. */
+            assert!(text
+                .replace("\r\n", "\n")
+                .starts_with(body.trim_end_matches('\n')));
+        }
+        let mut draft = Draft::default();
+        draft.set_body("ascii only\n".to_string());
+        assert!(draft
+            .finalise()
+            .unwrap()
+            .contains("Content-Transfer-Encoding: 7bit\r\n"));
+    }
 }
 
 /// Reads file from given path, and returns an 'application/octet-stream' AttachmentBuilder object
@@ -482,6 +577,10 @@ where
             } else {
                 b"application/octet-stream".to_vec()
             },
+        })
+        .set_content_disposition(ContentDisposition {
+            kind: ContentDispositionKind::Attachment,
+            ..Default::default()
         });
 
     Ok(attachment)
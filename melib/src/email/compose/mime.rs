@@ -24,6 +24,109 @@ use super::*;
 #[cfg(feature = "unicode_algorithms")]
 use crate::text_processing::grapheme_clusters::TextProcessing;
 
+/// How the `Content-Transfer-Encoding` of a composed text part is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferEncoding {
+    /// `7bit` for short lines of ASCII, otherwise `base64` if most of the text is not ASCII and
+    /// `quoted-printable` if it is.
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "8bit")]
+    _8Bit,
+    #[serde(rename = "quoted-printable")]
+    QuotedPrintable,
+    #[serde(rename = "base64")]
+    Base64,
+}
+
+impl Default for TransferEncoding {
+    fn default() -> Self {
+        TransferEncoding::Auto
+    }
+}
+
+impl TransferEncoding {
+    pub fn content_transfer_encoding(self, body: &[u8]) -> ContentTransferEncoding {
+        match self {
+            TransferEncoding::_8Bit => ContentTransferEncoding::_8Bit,
+            TransferEncoding::QuotedPrintable => ContentTransferEncoding::QuotedPrintable,
+            TransferEncoding::Base64 => ContentTransferEncoding::Base64,
+            TransferEncoding::Auto if is_7bit(body) => ContentTransferEncoding::_7Bit,
+            TransferEncoding::Auto => {
+                if 3 * body.iter().filter(|b| !b.is_ascii()).count() > body.len() {
+                    ContentTransferEncoding::Base64
+                } else {
+                    ContentTransferEncoding::QuotedPrintable
+                }
+            }
+        }
+    }
+}
+
+/// Whether `body` can be sent as `7bit`: ASCII without NUL or lone CR and LF characters, in lines
+/// of at most 998 octets ([rfc5322#section-2.1.1]).
+pub fn is_7bit(body: &[u8]) -> bool {
+    body.iter().all(|&b| b.is_ascii() && b != 0)
+        && body.split(|&b| b == b'\n').all(|line| {
+            let content = if line.ends_with(b"\r") {
+                &line[..line.len() - 1]
+            } else {
+                line
+            };
+            line.len() <= 998 && !content.contains(&b'\r')
+        })
+}
+
+/// `body` in `encoding`, with CRLF line endings in the encoded forms.
+pub fn encode_body(body: &[u8], encoding: &ContentTransferEncoding) -> Vec<u8> {
+    match encoding {
+        ContentTransferEncoding::QuotedPrintable => encode_quoted_printable(body).into_bytes(),
+        ContentTransferEncoding::Base64 => BASE64_MIME.encode(body).into_bytes(),
+        _ => body.to_vec(),
+    }
+}
+
+/// Encode `input` as quoted-printable ([rfc2045#section-6.7]). Line breaks of `input` become
+/// CRLF and longer lines are broken with soft line breaks so that no encoded line is longer than
+/// 76 characters.
+pub fn encode_quoted_printable(input: &[u8]) -> String {
+    let mut ret = String::with_capacity(input.len() + input.len() / 8);
+    let mut line_len = 0;
+    let mut i = 0;
+    while i < input.len() {
+        let b = input[i];
+        if b == b'\n' || input[i..].starts_with(b"\r\n") {
+            ret.push_str("\r\n");
+            line_len = 0;
+            i += if b == b'\n' { 1 } else { 2 };
+            continue;
+        }
+        let at_line_end =
+            i + 1 == input.len() || input[i + 1] == b'\n' || input[i + 1..].starts_with(b"\r\n");
+        /* Whitespace at the end of a line is encoded, since transports may strip it. */
+        let literal = match b {
+            b' ' | b'\t' => !at_line_end,
+            b'=' => false,
+            33..=126 => true,
+            _ => false,
+        };
+        let len = if literal { 1 } else { 3 };
+        /* A soft line break takes a column, except after the last character of a line. */
+        if line_len + len > 75 && !(at_line_end && line_len + len <= 76) {
+            ret.push_str("=\r\n");
+            line_len = 0;
+        }
+        if literal {
+            ret.push(b as char);
+        } else {
+            ret.push_str(&format!("={:02X}", b));
+        }
+        line_len += len;
+        i += 1;
+    }
+    ret
+}
+
 pub fn encode_header(value: &str) -> String {
     let mut ret = String::with_capacity(value.len());
     let mut is_current_window_ascii = true;
@@ -191,3 +294,69 @@ fn test_encode_header() {
         .unwrap(),
     );
 }
+
+#[test]
+fn test_encode_quoted_printable() {
+    use crate::email::parser::encodings::quoted_printable_bytes;
+    let decode = |encoded: &str| {
+        String::from_utf8(quoted_printable_bytes(encoded.as_bytes()).unwrap().1).unwrap()
+    };
+    assert_eq!(encode_quoted_printable(b"a=b \nc\t"), "a=3Db=20\r\nc=09");
+    let inputs = [
+        "Ένα πολύ μακρύ κείμενο που γράφτηκε στα ελληνικά και πρέπει να σπάσει σε γραμμές "
+            .repeat(3),
+        format!("{}\n\n{} \nend", "x".repeat(200), "é".repeat(100)),
+        /* Exactly 76 and 77 characters. */
+        format!("{}\n{}", "a".repeat(76), "a".repeat(77)),
+        "=".repeat(30),
+    ];
+    for input in &inputs {
+        let encoded = encode_quoted_printable(input.as_bytes());
+        assert!(encoded.is_ascii());
+        for line in encoded.split("\r\n") {
+            assert!(line.len() <= 76, "{:?}", line);
+            assert!(!line.ends_with(' '), "{:?}", line);
+        }
+        assert_eq!(&decode(&encoded), input);
+    }
+    let encoded = encode_quoted_printable("a".repeat(76).as_bytes());
+    assert!(!encoded.contains('='));
+}
+
+#[test]
+fn test_transfer_encoding() {
+    use ContentTransferEncoding::*;
+    assert_eq!(
+        TransferEncoding::Auto.content_transfer_encoding(b"hello\r\n"),
+        _7Bit
+    );
+    let long_line = "a".repeat(1000);
+    assert_eq!(
+        TransferEncoding::Auto.content_transfer_encoding(long_line.as_bytes()),
+        QuotedPrintable
+    );
+    assert_eq!(
+        TransferEncoding::Auto.content_transfer_encoding("Viele Grüße aus Berlin".as_bytes()),
+        QuotedPrintable
+    );
+    let greek = "Καλημέρα";
+    assert_eq!(
+        TransferEncoding::Auto.content_transfer_encoding(greek.as_bytes()),
+        Base64
+    );
+    assert_eq!(
+        decode(
+            &Attachment::new(
+                ContentType::default(),
+                Base64,
+                encode_body(greek.as_bytes(), &Base64)
+            ),
+            None
+        ),
+        greek.as_bytes()
+    );
+    assert_eq!(
+        TransferEncoding::_8Bit.content_transfer_encoding(greek.as_bytes()),
+        _8Bit
+    );
+}
@@ -78,3 +78,10 @@ pub fn gen_boundary() -> String {
 
     format!("{}{}{}", rand, clock, rand2)
 }
+
+/// `len` random decimal digits.
+pub fn gen_digits(len: usize) -> String {
+    (0..len)
+        .map(|_| char::from_digit((random_u64() % 10) as u32, 10).unwrap())
+        .collect()
+}
@@ -0,0 +1,327 @@
+/*
+ * meli - melib
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Minimal parsing of rfc5545 `text/calendar` attachments.
+ *
+ * Only the parts needed to hand events over to external calendar tools are extracted: top level
+ * calendar properties, `VTIMEZONE` definitions and `VEVENT` components, which are kept verbatim.
+ */
+use crate::error::{MeliError, Result};
+
+/// Maximum length of a content line in octets, excluding the line break.
+const MAX_LINE_LENGTH: usize = 75;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Event {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub location: Option<String>,
+    pub dtstart: Option<String>,
+    pub dtend: Option<String>,
+    /// Unfolded content lines of the event, including `BEGIN:VEVENT` and `END:VEVENT`.
+    raw: Vec<String>,
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.summary
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or("(no summary)")
+        )?;
+        if let Some(ref dtstart) = self.dtstart {
+            write!(f, " ({})", dtstart)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Calendar {
+    /// Top level properties such as `VERSION`, `PRODID` and `METHOD`.
+    pub properties: Vec<String>,
+    /// Unfolded content lines of each `VTIMEZONE` component.
+    pub timezones: Vec<Vec<String>>,
+    pub events: Vec<Event>,
+}
+
+/// Join folded lines (continuation lines start with a space or horizontal tab).
+fn unfold(input: &str) -> Vec<String> {
+    let mut ret: Vec<String> = vec![];
+    for l in input.lines() {
+        let l = l.trim_end_matches('\r');
+        if (l.starts_with(' ') || l.starts_with('\t')) && !ret.is_empty() {
+            ret.last_mut().unwrap().push_str(&l[1..]);
+        } else if !l.is_empty() {
+            ret.push(l.to_string());
+        }
+    }
+    ret
+}
+
+/// Fold a content line to lines of at most `MAX_LINE_LENGTH` octets.
+fn fold(line: &str, acc: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_LENGTH {
+            acc.push_str("\r\n ");
+            width = 1;
+        }
+        width += c.len_utf8();
+        acc.push(c);
+    }
+    acc.push_str("\r\n");
+}
+
+/// Split a content line into its uppercased name and value, ignoring any parameters.
+fn split_property(line: &str) -> Option<(String, &str)> {
+    let mut in_quotes = false;
+    let mut name_end = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes && name_end.is_none() => name_end = Some(i),
+            ':' if !in_quotes => {
+                let name = &line[..name_end.unwrap_or(i)];
+                return Some((name.trim().to_ascii_uppercase(), &line[i + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Unescape a `TEXT` property value.
+fn unescape(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => ret.push('\n'),
+                Some(c) => ret.push(c),
+                None => {}
+            }
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+impl Event {
+    fn from_lines(raw: Vec<String>) -> Self {
+        let mut ret = Event::default();
+        let mut depth = 0;
+        for l in raw.iter() {
+            let (name, value) = match split_property(l) {
+                Some(v) => v,
+                None => continue,
+            };
+            match name.as_str() {
+                "BEGIN" => depth += 1,
+                "END" => depth -= 1,
+                /* Skip properties of nested components such as VALARM */
+                _ if depth != 1 => {}
+                "UID" => ret.uid = Some(unescape(value)),
+                "SUMMARY" => ret.summary = Some(unescape(value)),
+                "LOCATION" => ret.location = Some(unescape(value)),
+                "DTSTART" => ret.dtstart = Some(value.to_string()),
+                "DTEND" => ret.dtend = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        ret.raw = raw;
+        ret
+    }
+}
+
+impl Calendar {
+    pub fn new(input: &str) -> Result<Self> {
+        let lines = unfold(input);
+        let mut ret = Calendar::default();
+        let mut iter = lines.into_iter();
+        let mut found = false;
+        while let Some(l) = iter.next() {
+            if l.eq_ignore_ascii_case("BEGIN:VCALENDAR") {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            return Err(MeliError::new(
+                "Error while parsing calendar: no VCALENDAR component found.",
+            ));
+        }
+
+        let mut component: Option<(String, Vec<String>)> = None;
+        let mut depth = 0;
+        for l in iter {
+            let (name, value) = match split_property(&l) {
+                Some((name, value)) => (name, value.trim().to_ascii_uppercase()),
+                None => continue,
+            };
+            match (name.as_str(), component.as_mut()) {
+                ("BEGIN", None) => {
+                    depth = 1;
+                    component = Some((value, vec![l]));
+                }
+                ("BEGIN", Some((_, lines))) => {
+                    depth += 1;
+                    lines.push(l);
+                }
+                ("END", Some((_, lines))) => {
+                    depth -= 1;
+                    lines.push(l);
+                    if depth == 0 {
+                        let (kind, lines) = component.take().unwrap();
+                        match kind.as_str() {
+                            "VEVENT" => ret.events.push(Event::from_lines(lines)),
+                            "VTIMEZONE" => ret.timezones.push(lines),
+                            _ => {}
+                        }
+                    }
+                }
+                ("END", None) if value == "VCALENDAR" => {
+                    return Ok(ret);
+                }
+                (_, Some((_, lines))) => {
+                    lines.push(l);
+                }
+                (_, None) => {
+                    ret.properties.push(l);
+                }
+            }
+        }
+        Err(MeliError::new(
+            "Error while parsing calendar: VCALENDAR component is not terminated.",
+        ))
+    }
+
+    /// Append the events and timezones of `other` to `self`.
+    pub fn extend(&mut self, other: Calendar) {
+        if self.properties.is_empty() {
+            self.properties = other.properties;
+        }
+        for tz in other.timezones {
+            if !self.timezones.contains(&tz) {
+                self.timezones.push(tz);
+            }
+        }
+        self.events.extend(other.events.into_iter());
+    }
+
+    /// Serialize to a `VCALENDAR` object that contains only the timezones and events.
+    pub fn to_ics(&self) -> String {
+        let mut ret = String::new();
+        fold("BEGIN:VCALENDAR", &mut ret);
+        let mut has_version = false;
+        let mut has_prodid = false;
+        for p in self.properties.iter() {
+            match split_property(p).map(|(name, _)| name) {
+                Some(ref name) if name == "VERSION" => has_version = true,
+                Some(ref name) if name == "PRODID" => has_prodid = true,
+                _ => {}
+            }
+            fold(p, &mut ret);
+        }
+        if !has_version {
+            fold("VERSION:2.0", &mut ret);
+        }
+        if !has_prodid {
+            fold("PRODID:-//meli//meli//EN", &mut ret);
+        }
+        for l in self.timezones.iter().flatten() {
+            fold(l, &mut ret);
+        }
+        for l in self.events.iter().map(|ev| ev.raw.iter()).flatten() {
+            fold(l, &mut ret);
+        }
+        fold("END:VCALENDAR", &mut ret);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_parse() {
+        let input = "BEGIN:VCALENDAR\r\n\
+PRODID:-//Example Corp//EN\r\n\
+VERSION:2.0\r\n\
+METHOD:REQUEST\r\n\
+BEGIN:VTIMEZONE\r\n\
+TZID:Europe/Athens\r\n\
+BEGIN:STANDARD\r\n\
+DTSTART:19701025T040000\r\n\
+TZOFFSETFROM:+0300\r\n\
+TZOFFSETTO:+0200\r\n\
+END:STANDARD\r\n\
+END:VTIMEZONE\r\n\
+BEGIN:VEVENT\r\n\
+UID:1234@example.com\r\n\
+SUMMARY:Meeting about\r\n  the\\, project\r\n\
+LOCATION;LANGUAGE=en:Room 1\r\n\
+DTSTART;TZID=\"Europe/Athens\":20201010T100000\r\n\
+DTEND;TZID=\"Europe/Athens\":20201010T110000\r\n\
+BEGIN:VALARM\r\n\
+ACTION:DISPLAY\r\n\
+SUMMARY:Reminder\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+BEGIN:VTODO\r\n\
+UID:todo@example.com\r\n\
+END:VTODO\r\n\
+END:VCALENDAR\r\n";
+        let cal = Calendar::new(input).unwrap();
+        assert_eq!(cal.properties.len(), 3);
+        assert_eq!(cal.timezones.len(), 1);
+        assert_eq!(cal.events.len(), 1);
+        let ev = &cal.events[0];
+        assert_eq!(ev.uid.as_ref().unwrap(), "1234@example.com");
+        assert_eq!(ev.summary.as_ref().unwrap(), "Meeting about the, project");
+        assert_eq!(ev.location.as_ref().unwrap(), "Room 1");
+        assert_eq!(ev.dtstart.as_ref().unwrap(), "20201010T100000");
+        assert_eq!(ev.dtend.as_ref().unwrap(), "20201010T110000");
+
+        let ics = cal.to_ics();
+        assert!(!ics.contains("VTODO"));
+        assert!(ics.contains("BEGIN:VALARM\r\n"));
+        assert_eq!(Calendar::new(&ics).unwrap(), cal);
+
+        assert!(Calendar::new("BEGIN:VCARD\r\nEND:VCARD\r\n").is_err());
+        assert!(Calendar::new("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n").is_err());
+    }
+
+    #[test]
+    fn test_calendar_fold() {
+        let mut acc = String::new();
+        let line = format!("DESCRIPTION:{}", "α".repeat(60));
+        fold(&line, &mut acc);
+        assert!(acc.split("\r\n").all(|l| l.len() <= MAX_LINE_LENGTH));
+        assert_eq!(unfold(&acc), vec![line]);
+    }
+}
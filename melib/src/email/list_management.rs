@@ -79,6 +79,9 @@ pub struct ListActions<'a> {
     pub archive: Option<&'a str>,
     pub post: Option<SmallVec<[ListAction<'a>; 4]>>,
     pub unsubscribe: Option<SmallVec<[ListAction<'a>; 4]>>,
+    /// The `List-Unsubscribe-Post` header allows unsubscribing from the https `unsubscribe` URLs
+    /// with an rfc8058 one-click `POST` request, see `unsubscribe_one_click`.
+    pub unsubscribe_one_click: bool,
 }
 
 pub fn list_id_header(envelope: &'_ Envelope) -> Option<&'_ str> {
@@ -135,6 +138,10 @@ impl<'a> ListActions<'a> {
             ret.unsubscribe = ListAction::parse_options_list(unsubscribe.as_bytes());
         }
 
+        if let Some(post) = envelope.other_headers().get("List-Unsubscribe-Post") {
+            ret.unsubscribe_one_click = post.trim() == "List-Unsubscribe=One-Click";
+        }
+
         if ret.id.is_none()
             && ret.archive.is_none()
             && ret.post.is_none()
@@ -146,3 +153,37 @@ impl<'a> ListActions<'a> {
         }
     }
 }
+
+/// Unsubscribe with the rfc8058 one-click `POST` request to an https `List-Unsubscribe` URL of a
+/// message whose `ListActions::unsubscribe_one_click` is set.
+#[cfg(feature = "http")]
+pub async fn unsubscribe_one_click(url: String) -> crate::Result<()> {
+    use crate::error::MeliError;
+    use isahc::config::{Configurable, RedirectPolicy};
+    use isahc::prelude::HttpClient;
+
+    if !url.starts_with("https://") {
+        return Err(MeliError::new(format!(
+            "One-click unsubscribe URL {} is not https",
+            url
+        )));
+    }
+    /* rfc8058 forbids redirecting the request, and it carries no cookies or credentials. */
+    let client = HttpClient::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .redirect_policy(RedirectPolicy::None)
+        .build()?;
+    let req = isahc::http::Request::post(url.as_str())
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .map_err(|err| MeliError::new(err.to_string()))?;
+    let res = client.send_async(req).await?;
+    if !res.status().is_success() {
+        return Err(MeliError::new(format!(
+            "{} replied to the unsubscribe request with {}",
+            url,
+            res.status()
+        )));
+    }
+    Ok(())
+}
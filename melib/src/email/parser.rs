@@ -49,7 +49,7 @@ pub struct ParsingError<I> {
 impl core::fmt::Debug for ParsingError<&'_ [u8]> {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
         fmt.debug_struct("ParsingError")
-            .field("input", &to_str!(&self.input))
+            .field("input", &String::from_utf8_lossy(self.input))
             .field("error", &self.error)
             .finish()
     }
@@ -60,7 +60,12 @@ struct DebugOkWrapper<'r, I, R: AsRef<[u8]>>(&'r IResult<I, R>);
 impl<R: AsRef<[u8]> + core::fmt::Debug> core::fmt::Debug for DebugOkWrapper<'_, &'_ [u8], R> {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
         if let Ok((a, b)) = self.0 {
-            write!(fmt, "Ok({}, {})", &to_str!(a), &to_str!(b.as_ref()))
+            write!(
+                fmt,
+                "Ok({}, {})",
+                String::from_utf8_lossy(a),
+                String::from_utf8_lossy(b.as_ref())
+            )
         } else {
             write!(fmt, "{:?}", self.0)
         }
@@ -333,34 +338,47 @@ pub mod dates {
     ///have been used in Internet messages.  Any such time zone whose
     ///meaning is not known SHOULD be considered equivalent to "-0000"
     ///unless there is out-of-band information confirming their meaning.
-    fn obs_zone(input: &[u8]) -> IResult<&[u8], (&[u8], &[u8])> {
-        alt((
-            map(tag("UT"), |_| (&b"+"[..], &b"0000"[..])),
-            map(tag("GMT"), |_| (&b"+"[..], &b"0000"[..])),
-            map(tag("EDT"), |_| (&b"-"[..], &b"0400"[..])),
-            map(tag("EST"), |_| (&b"-"[..], &b"0500"[..])),
-            map(tag("CDT"), |_| (&b"-"[..], &b"0500"[..])),
-            map(tag("CST"), |_| (&b"-"[..], &b"0600"[..])),
-            map(tag("MDT"), |_| (&b"-"[..], &b"0600"[..])),
-            map(tag("MST"), |_| (&b"-"[..], &b"0700"[..])),
-            map(tag("PDT"), |_| (&b"-"[..], &b"0700"[..])),
-            map(tag("PST"), |_| (&b"-"[..], &b"0800"[..])),
-            map(take_while1(is_alphabetic), |_| (&b"-"[..], &b"0000"[..])),
-        ))(input)
+    ///
+    ///The offsets of the zones in `datetime::TIMEZONE_ABBR` are known.
+    fn obs_zone(input: &[u8]) -> IResult<&[u8], i64> {
+        let (input, name) = take_while1(is_alphabetic)(input)?;
+        let offset = match name.to_ascii_uppercase().as_slice() {
+            b"UT" | b"GMT" => 0,
+            b"EDT" => -4 * 3600,
+            b"EST" | b"CDT" => -5 * 3600,
+            b"CST" | b"MDT" => -6 * 3600,
+            b"MST" | b"PDT" => -7 * 3600,
+            b"PST" => -8 * 3600,
+            [_] => 0,
+            name => crate::datetime::timezone_abbreviation_offset(name).unwrap_or(0),
+        };
+        Ok((input, offset))
     }
 
     ///zone            =   (FWS ( "+" / "-" ) 4DIGIT) / obs-zone
-    fn zone(input: &[u8]) -> IResult<&[u8], (&[u8], &[u8])> {
+    ///
+    ///Returns the offset from UTC in seconds. A colon between the hours and the minutes is
+    ///tolerated.
+    fn zone(input: &[u8]) -> IResult<&[u8], i64> {
         alt((
             |input| {
                 let (input, sign) = alt((tag("+"), tag("-")))(input)?;
-                let (input, zone) = take_n_digits(4)(input)?;
-                Ok((input, (sign, zone)))
+                let (input, hours) = take_n_digits(2)(input)?;
+                let (input, _) = opt(tag(":"))(input)?;
+                let (input, minutes) = take_n_digits(2)(input)?;
+                let offset = (digits_value(hours) * 60 + digits_value(minutes)) * 60;
+                Ok((input, if sign == b"-" { -offset } else { offset }))
             },
             obs_zone,
         ))(input)
     }
 
+    fn digits_value(digits: &[u8]) -> i64 {
+        digits
+            .iter()
+            .fold(0, |acc, d| acc * 10 + i64::from(d - b'0'))
+    }
+
     ///date-time       =   [ day-of-week "," ] date time [CFWS]
     ///date            =   day month year
     ///time            =   time-of-day zone
@@ -368,48 +386,42 @@ pub mod dates {
     ///hour            =   2DIGIT / obs-hour
     ///minute          =   2DIGIT / obs-minute
     ///second          =   2DIGIT / obs-second
-    fn date_time(input: &[u8]) -> IResult<&[u8], UnixTimestamp> {
-        let orig_input = input;
-        let mut accum: SmallVec<[u8; 32]> = SmallVec::new();
-        let (input, day_of_week) = opt(terminated(day_of_week, tag(",")))(input)?;
+    ///
+    ///Names are matched case-insensitively, hours can have one digit and a missing zone is
+    ///taken as "-0000", unless a comment after the time names a known one, as in
+    ///`10:00:00 (EEST)`. Returns the timestamp and the zone's offset from UTC in seconds.
+    fn date_time(input: &[u8]) -> IResult<&[u8], (UnixTimestamp, i64)> {
+        let (input, _) = opt(cfws)(input)?;
+        let (input, _) = opt(terminated(day_of_week, pair(opt(cfws), opt(tag(",")))))(input)?;
         let (input, day) = day(input)?;
         let (input, month) = month(input)?;
         let (input, year) = year(input)?;
-        let (input, hour) = take_n_digits(2)(input)?;
+        let (input, hour) = alt((take_n_digits(2), take_n_digits(1)))(input)?;
         let (input, _) = tag(":")(input)?;
         let (input, minute) = take_n_digits(2)(input)?;
         let (input, second) = opt(preceded(tag(":"), take_n_digits(2)))(input)?;
-        let (input, _) = fws(input)?;
-        let (input, (sign, zone)) = zone(input)?;
+        let (input, _) = opt(fws)(input)?;
+        let (input, offset) = opt(zone)(input)?;
+        let offset = match offset {
+            Some(offset) => offset,
+            None => opt(delimited(tag("("), take_while1(is_alphabetic), tag(")")))(input)?
+                .1
+                .and_then(crate::datetime::timezone_abbreviation_offset)
+                .unwrap_or(0),
+        };
         let (input, _) = opt(cfws)(input)?;
-        if let Some(day_of_week) = day_of_week {
-            accum.extend_from_slice(&day_of_week);
-            accum.extend_from_slice(b", ");
-        }
-        accum.extend_from_slice(&day);
-        accum.extend_from_slice(b" ");
-        accum.extend_from_slice(&month);
-        accum.extend_from_slice(b" ");
-        accum.extend_from_slice(&year);
-        accum.extend_from_slice(b" ");
-        accum.extend_from_slice(&hour);
-        accum.extend_from_slice(b":");
-        accum.extend_from_slice(&minute);
-        if let Some(second) = second {
-            accum.extend_from_slice(b":");
-            accum.extend_from_slice(&second);
-        }
-        accum.extend_from_slice(b" ");
-        accum.extend_from_slice(&sign);
-        accum.extend_from_slice(&zone);
-        match crate::datetime::rfc822_to_timestamp(accum.to_vec()) {
-            Ok(t) => Ok((input, t)),
-            Err(_err) => Err(nom::Err::Error(
-                (
-                    orig_input,
-                    "date_time(): could not convert date from rfc822",
-                )
-                    .into(),
+        match crate::datetime::timestamp_from_date(
+            year,
+            month,
+            digits_value(day),
+            digits_value(hour),
+            digits_value(minute),
+            second.map(digits_value).unwrap_or(0),
+            offset,
+        ) {
+            Some(t) => Ok((input, (t, offset))),
+            None => Err(nom::Err::Error(
+                (input, "date_time(): invalid date or time").into(),
             )),
         }
     }
@@ -417,24 +429,28 @@ pub mod dates {
     ///`day-of-week     =   ([FWS] day-name) / obs-day-of-week`
     ///day-name        =   "Mon" / "Tue" / "Wed" / "Thu" /
     ///                    "Fri" / "Sat" / "Sun"
-    fn day_of_week(input: &[u8]) -> IResult<&[u8], Cow<'_, [u8]>> {
-        let (input, day_name) = alt((
-            tag("Mon"),
-            tag("Tue"),
-            tag("Wed"),
-            tag("Thu"),
-            tag("Fri"),
-            tag("Sat"),
-            tag("Sun"),
-        ))(input)?;
-        Ok((input, day_name.into()))
+    ///
+    ///Full names are accepted too.
+    fn day_of_week(input: &[u8]) -> IResult<&[u8], &[u8]> {
+        let (input, name) = take_while1(is_alphabetic)(input)?;
+        if name.len() >= 3
+            && [b"mon", b"tue", b"wed", b"thu", b"fri", b"sat", b"sun"]
+                .iter()
+                .any(|d| name[..3].eq_ignore_ascii_case(&d[..]))
+        {
+            Ok((input, name))
+        } else {
+            Err(nom::Err::Error(
+                (input, "day_of_week(): not a day name").into(),
+            ))
+        }
     }
 
     ///day             =   ([FWS] 1*2DIGIT FWS) / obs-day
     fn day(input: &[u8]) -> IResult<&[u8], &[u8]> {
-        let (input, _) = opt(fws)(input)?;
+        let (input, _) = opt(cfws)(input)?;
         let (input, ret) = alt((take_n_digits(2), take_n_digits(1)))(input)?;
-        let (input, _) = fws(input)?;
+        let (input, _) = opt(cfws)(input)?;
 
         Ok((input, ret))
     }
@@ -442,35 +458,55 @@ pub mod dates {
     ///month           =   "Jan" / "Feb" / "Mar" / "Apr" /
     ///                    "May" / "Jun" / "Jul" / "Aug" /
     ///                    "Sep" / "Oct" / "Nov" / "Dec"
-    fn month(input: &[u8]) -> IResult<&[u8], &[u8]> {
-        alt((
-            tag("Jan"),
-            tag("Feb"),
-            tag("Mar"),
-            tag("Apr"),
-            tag("May"),
-            tag("Jun"),
-            tag("Jul"),
-            tag("Aug"),
-            tag("Sep"),
-            tag("Oct"),
-            tag("Nov"),
-            tag("Dec"),
-        ))(input)
+    ///
+    ///Returns the month's index from 0. Full names are accepted too.
+    fn month(input: &[u8]) -> IResult<&[u8], i64> {
+        const MONTHS: [&[u8]; 12] = [
+            b"jan", b"feb", b"mar", b"apr", b"may", b"jun", b"jul", b"aug", b"sep", b"oct", b"nov",
+            b"dec",
+        ];
+        let (input, name) = take_while1(is_alphabetic)(input)?;
+        match MONTHS
+            .iter()
+            .position(|m| name.len() >= 3 && name[..3].eq_ignore_ascii_case(m))
+        {
+            Some(i) => Ok((input, i as i64)),
+            None => Err(nom::Err::Error((input, "month(): not a month name").into())),
+        }
     }
 
     ///year            =   (FWS 4*DIGIT FWS) / obs-year
-    fn year(input: &[u8]) -> IResult<&[u8], &[u8]> {
-        let (input, _) = fws(input)?;
-        let (input, ret) = take_n_digits(4)(input)?;
-        let (input, _) = fws(input)?;
-        Ok((input, ret))
+    ///obs-year        =   [CFWS] 2*DIGIT [CFWS]
+    ///
+    ///"Where a two or three digit year occurs in a date, the year is to be interpreted as
+    ///follows: If a two digit year is encountered whose value is between 00 and 49, the year is
+    ///interpreted by adding 2000, ending up with a value between 2000 and 2049. If a two digit
+    ///year is encountered with a value between 50 and 99, or any three digit year is
+    ///encountered, the year is interpreted by adding 1900."
+    fn year(input: &[u8]) -> IResult<&[u8], i64> {
+        let (input, _) = opt(cfws)(input)?;
+        let (input, digits) = take_while1(is_digit)(input)?;
+        let (input, _) = opt(cfws)(input)?;
+        let year = digits_value(digits);
+        let year = match digits.len() {
+            1 => {
+                return Err(nom::Err::Error((input, "year(): one digit").into()));
+            }
+            2 if year < 50 => year + 2000,
+            2 | 3 => year + 1900,
+            _ => year,
+        };
+        Ok((input, year))
     }
 
     pub fn rfc5322_date(input: &[u8]) -> Result<crate::datetime::UnixTimestamp> {
+        rfc5322_date_and_offset(input).map(|(t, _)| t)
+    }
+
+    /// The timestamp of a date and the offset from UTC in seconds of the zone it was written in.
+    pub fn rfc5322_date_and_offset(input: &[u8]) -> Result<(UnixTimestamp, i64)> {
         date_time(input)
             .or_else(|_| {
-                //let (_, mut parsed_result) = encodings::phrase(&eat_comments(input), false)?;
                 let (rest, parsed_result) = encodings::phrase(input, false)?;
                 let (_, ret) = match date_time(&parsed_result) {
                     Ok(v) => v,
@@ -484,14 +520,6 @@ pub mod dates {
             })
             .map(|(_, r)| r)
             .map_err(|err: nom::Err<ParsingError<_>>| err.into())
-        /*
-        }
-        if let Some(pos) = parsed_result.find(b"-0000") {
-            parsed_result[pos] = b'+';
-        }
-
-        crate::datetime::rfc822_to_timestamp(parsed_result.trim())
-            */
     }
 
     #[test]
@@ -503,6 +531,14 @@ pub mod dates {
         assert_eq!(rfc5322_date(_s).unwrap(), rfc5322_date(__s).unwrap());
         let val = b"Fri, 23 Dec 0001 21:20:36 -0800 (PST)";
         assert_eq!(rfc5322_date(val).unwrap(), 0);
+        assert_eq!(
+            rfc5322_date_and_offset(b"Thu, 31 Aug 2017 16:43:37 +0300").unwrap(),
+            (rfc5322_date(_s).unwrap(), 3 * 3600)
+        );
+        assert_eq!(
+            rfc5322_date_and_offset(b"Thu, 31 Aug 17 09:43 EDT").unwrap(),
+            (rfc5322_date(_s).unwrap() - 37, -4 * 3600)
+        );
     }
 }
 
@@ -686,8 +722,12 @@ pub mod generic {
             } else if input.starts_with(b"(") {
                 comment_level += 1;
                 input = &input[1..];
-            } else {
-                input = context("comment()", opt(fws))(input)?.0;
+            } else if let Ok((rest, _)) = context("comment()", fws)(input) {
+                input = rest;
+            } else if !input.is_empty() {
+                return Err(nom::Err::Error(
+                    (input, "comment(): invalid character").into(),
+                ));
             }
         }
         Ok((input, ()))
@@ -948,7 +988,7 @@ pub mod generic {
                 (input, "atom(): starts with whitespace or empty").into(),
             ));
         }
-        while i + 1 > 0 {
+        while i > 0 {
             if input[i - 1] == b' ' || input[i - 1] == b'\t' {
                 i -= 1;
             } else {
@@ -1507,13 +1547,14 @@ pub mod attachments {
             }
             offset += b_start - 2;
             input = &input[b_start - 2..];
-            if &input[0..2] == b"--" {
-                offset += 2 + boundary.len();
-                input = &input[2 + boundary.len()..];
-                if input[0] == b'\n' {
+            let is_delimiter = &input[0..2] == b"--";
+            offset += 2 + boundary.len();
+            input = &input[2 + boundary.len()..];
+            if is_delimiter {
+                if input.starts_with(b"\n") {
                     offset += 1;
                     input = &input[1..];
-                } else if input[0..].starts_with(b"\r\n") {
+                } else if input.starts_with(b"\r\n") {
                     offset += 2;
                     input = &input[2..];
                 } else {
@@ -1530,25 +1571,21 @@ pub mod attachments {
                 ));
             }
             if let Some(end) = input.find(boundary) {
-                if &input[end - 2..end] != b"--" {
+                if end < 2 || &input[end - 2..end] != b"--" {
                     return Err(nom::Err::Error(
                         (input, "multipart_parts(): malformed boundary").into(),
                     ));
                 }
-                if input[..end - 2].ends_with(b"\r\n") {
-                    ret.push(StrBuilder {
-                        offset,
-                        length: end - 4,
-                    });
-                } else {
-                    ret.push(StrBuilder {
-                        offset,
-                        length: end - 3,
-                    });
-                }
+                ret.push(StrBuilder {
+                    offset,
+                    length: part_length(&input[..end - 2]),
+                });
                 offset += end + boundary.len();
                 input = &input[end + boundary.len()..];
-                if input.len() < 2 || input[0] != b'\n' || &input[0..2] == b"--" {
+                if input.len() < 2
+                    || (input[0] != b'\n' && &input[0..2] != b"\r\n")
+                    || &input[0..2] == b"--"
+                {
                     break;
                 }
                 if input[0] == b'\n' {
@@ -1569,6 +1606,18 @@ pub mod attachments {
         Ok((input, ret))
     }
 
+    /// Length of a part that ends right before a `--boundary` delimiter, without the line break
+    /// that belongs to the delimiter.
+    fn part_length(part: &[u8]) -> usize {
+        if part.ends_with(b"\r\n") {
+            part.len() - 2
+        } else if part.ends_with(b"\n") {
+            part.len() - 1
+        } else {
+            part.len()
+        }
+    }
+
     fn parts_f(boundary: &[u8]) -> impl Fn(&[u8]) -> IResult<&[u8], Vec<&[u8]>> + '_ {
         move |input: &[u8]| -> IResult<&[u8], Vec<&[u8]>> {
             let mut ret: Vec<&[u8]> = Vec::new();
@@ -1588,11 +1637,12 @@ pub mod attachments {
                     ));
                 }
                 input = &input[b_start - 2..];
-                if &input[0..2] == b"--" {
-                    input = &input[2 + boundary.len()..];
-                    if input[0] == b'\n' {
+                let is_delimiter = &input[0..2] == b"--";
+                input = &input[2 + boundary.len()..];
+                if is_delimiter {
+                    if input.starts_with(b"\n") {
                         input = &input[1..];
-                    } else if input[0..].starts_with(b"\r\n") {
+                    } else if input.starts_with(b"\r\n") {
                         input = &input[2..];
                     } else {
                         continue;
@@ -1605,14 +1655,12 @@ pub mod attachments {
                     return Err(nom::Err::Error((input, "parts_f(): found EOF").into()));
                 }
                 if let Some(end) = input.find(boundary) {
-                    if &input[end - 2..end] != b"--" {
-                        return Err(nom::Err::Error((input, "parts_f(): found EOF").into()));
-                    }
-                    if input[..end - 2].ends_with(b"\r\n") {
-                        ret.push(&input[..end - 4]);
-                    } else {
-                        ret.push(&input[..end - 3]);
+                    if end < 2 || &input[end - 2..end] != b"--" {
+                        return Err(nom::Err::Error(
+                            (input, "parts_f(): malformed boundary").into(),
+                        ));
                     }
+                    ret.push(&input[..part_length(&input[..end - 2])]);
                     input = &input[end + boundary.len()..];
                     if input.len() < 2
                         || (input[0] != b'\n' && &input[0..2] != b"\r\n")
@@ -1907,6 +1955,42 @@ pub mod encodings {
         }
     }
 
+    /// Decode `s`, which is declared as `charset`. If it isn't valid in `charset`, or isn't valid
+    /// UTF-8 when no charset was declared, it's decoded as UTF-8 or else as latin1, which maps
+    /// every byte to a character so that nothing is lost. Returns the charset that was used
+    /// instead of `charset`, if any.
+    pub fn decode_charset_fallback(s: &[u8], charset: Charset) -> (String, Option<Charset>) {
+        let is_utf8 = match charset {
+            Charset::UTF8 | Charset::Ascii => true,
+            _ => false,
+        };
+        if !is_utf8 {
+            if let Ok(ret) = decode_charset(s, charset) {
+                return (ret, None);
+            }
+        }
+        match std::str::from_utf8(s) {
+            Ok(ret) => (
+                ret.to_string(),
+                if is_utf8 { None } else { Some(Charset::UTF8) },
+            ),
+            Err(_) => (
+                s.iter().map(|&b| char::from(b)).collect(),
+                Some(Charset::ISO8859_1),
+            ),
+        }
+    }
+
+    /// Header values should be ASCII with anything else in encoded words, but some clients send
+    /// raw 8-bit text. Values that aren't valid UTF-8 are decoded as latin1.
+    pub fn decode_8bit_header(value: &[u8]) -> Cow<'_, [u8]> {
+        if std::str::from_utf8(value).is_ok() {
+            Cow::Borrowed(value)
+        } else {
+            Cow::Owned(decode_charset_fallback(value, Charset::UTF8).0.into_bytes())
+        }
+    }
+
     fn quoted_printable_soft_break(input: &[u8]) -> IResult<&[u8], u8> {
         if input.starts_with(b"=\n") {
             Ok((&input[2..], input[1])) // `=\n` is an escaped space character.
@@ -2896,4 +2980,38 @@ border=3D=220=22>
         let c = msg_id(s).unwrap().1;
         assert_eq!(&msg_id_list(value).unwrap().1, &[a, c]);
     }
+
+    #[test]
+    fn test_decode_charset_fallback() {
+        use crate::email::attachment_types::Charset;
+        assert_eq!(
+            decode_charset_fallback("Jörg".as_bytes(), Charset::UTF8),
+            ("Jörg".to_string(), None)
+        );
+        /* Undeclared latin1 */
+        assert_eq!(
+            decode_charset_fallback(b"J\xf6rg", Charset::UTF8),
+            ("Jörg".to_string(), Some(Charset::ISO8859_1))
+        );
+        /* Declared as something it isn't */
+        assert_eq!(
+            decode_charset_fallback("Jörg".as_bytes(), Charset::ISO2022JP),
+            ("Jörg".to_string(), Some(Charset::UTF8))
+        );
+        assert_eq!(
+            decode_charset_fallback(b"J\xf6rg", Charset::ISO8859_1),
+            ("Jörg".to_string(), None)
+        );
+        assert_eq!(
+            &*decode_8bit_header(b"J\xf6rg <j@example.com>"),
+            "Jörg <j@example.com>".as_bytes()
+        );
+        let envelope = crate::Envelope::from_bytes(
+            b"From: J\xf6rg <j@example.com>\r\nSubject: Gr\xfc\xdfe =?utf-8?q?f=C3=BCr?= dich\r\nMessage-ID: <a@example.com>\r\n\r\nHallo\r\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(envelope.field_from_to_string(), "Jörg <j@example.com>");
+        assert_eq!(envelope.subject(), "Grüße für dich");
+    }
 }
@@ -19,13 +19,32 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-/*! Verification of OpenPGP signatures */
+/*! Verification of OpenPGP signatures and protected headers of encrypted mail */
 use crate::email::{
-    attachment_types::{ContentType, MultipartType},
-    attachments::Attachment,
+    attachment_types::{Charset, ContentType, MultipartType, Text},
+    attachments::{Attachment, AttachmentBuilder},
+    compose::mime,
+    headers::HeaderMap,
 };
 use crate::{MeliError, Result};
 
+/// Headers copied into the encrypted part of mail, as
+/// [protected headers](https://datatracker.ietf.org/doc/draft-autocrypt-lamps-protected-headers/).
+pub const PROTECTED_HEADERS: &[&str] = &[
+    "Subject",
+    "From",
+    "To",
+    "Cc",
+    "Reply-To",
+    "Date",
+    "Message-ID",
+    "References",
+    "In-Reply-To",
+];
+
+/// The `Subject` of encrypted mail with protected headers, the same as Thunderbird and Enigmail.
+pub const PROTECTED_SUBJECT: &str = "...";
+
 /// Convert raw attachment to the form needed for signature verification ([rfc3156](https://tools.ietf.org/html/rfc3156))
 ///
 /// ## rfc3156
@@ -136,6 +155,73 @@ pub fn verify_signature(a: &Attachment) -> Result<(Vec<u8>, &Attachment)> {
     }
 }
 
+/// The part to encrypt instead of `body`: a `multipart/mixed` part with the protected headers of
+/// `headers` and, if there is a `Subject`, a "legacy display" part that shows it in clients
+/// without support for protected headers, followed by `body`.
+pub fn protect_headers(body: Attachment, headers: &HeaderMap) -> String {
+    let mut parts = vec![];
+    if let Some(subject) = headers.get("Subject") {
+        let mut legacy_display = AttachmentBuilder::default();
+        legacy_display
+            .set_raw(format!("Subject: {}\r\n", subject).into_bytes())
+            .set_body_to_raw()
+            .set_content_type(ContentType::Text {
+                kind: Text::Other {
+                    tag: b"rfc822-headers".to_vec(),
+                },
+                parameters: vec![(b"protected-headers".to_vec(), b"v1".to_vec())],
+                charset: Charset::UTF8,
+            });
+        parts.push(legacy_display);
+    }
+    parts.push(body.into());
+    let boundary = ContentType::make_boundary(&parts);
+    let mut ret = format!(
+        "Content-Type: multipart/mixed; boundary=\"{}\"; protected-headers=\"v1\"\r\n",
+        boundary
+    );
+    for name in PROTECTED_HEADERS {
+        if let Some(value) = headers.get(name) {
+            if value.is_ascii() {
+                ret.push_str(&format!("{}: {}\r\n", name, value));
+            } else {
+                ret.push_str(&format!("{}: {}\r\n", name, mime::encode_header(value)));
+            }
+        }
+    }
+    for part in parts {
+        ret.push_str(&format!("\r\n--{}\r\n", boundary));
+        ret.push_str(&Attachment::from(part).into_raw());
+    }
+    ret.push_str(&format!("\r\n--{}--\r\n", boundary));
+    ret
+}
+
+/// Whether the decrypted part `a` has protected headers.
+pub fn has_protected_headers(a: &Attachment) -> bool {
+    a.parameters()
+        .iter()
+        .any(|(n, v)| n.eq_ignore_ascii_case(b"protected-headers") && v.eq_ignore_ascii_case(b"v1"))
+}
+
+/// Whether `a` is the "legacy display" part of protected headers, shown only by clients without
+/// support for them.
+pub fn is_legacy_display_part(a: &Attachment) -> bool {
+    match a.content_type {
+        ContentType::Text {
+            kind: Text::Other { ref tag },
+            ref parameters,
+            ..
+        } => {
+            tag.eq_ignore_ascii_case(b"rfc822-headers")
+                && parameters.iter().any(|(n, v)| {
+                    n.eq_ignore_ascii_case(b"protected-headers") && v.eq_ignore_ascii_case(b"v1")
+                })
+        }
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DecryptionMetadata {
     pub recipients: Vec<Recipient>,
@@ -156,3 +242,46 @@ pub struct SignatureMetadata {
     pub file_name: Option<String>,
     pub is_mime: bool,
 }
+
+#[test]
+fn test_protected_headers() {
+    use crate::email::{attachment_types::ContentDisposition, Envelope};
+    let mut headers = HeaderMap::default();
+    for (name, value) in [
+        ("Subject", "Secret plans"),
+        ("From", "alice@example.com"),
+        ("To", "bob@example.com"),
+        ("X-Mailer", "meli"),
+    ]
+    .iter()
+    {
+        headers.insert(
+            crate::email::headers::HeaderName::new_unchecked(name),
+            value.to_string(),
+        );
+    }
+    let mut body = AttachmentBuilder::new(b"Meet at noon.");
+    body.set_content_disposition(ContentDisposition::default());
+    let raw = protect_headers(body.build(), &headers);
+    let part = AttachmentBuilder::new(raw.as_bytes()).build();
+    assert!(has_protected_headers(&part));
+    let envelope = Envelope::from_bytes(raw.as_bytes(), None).unwrap();
+    assert_eq!(envelope.subject(), "Secret plans");
+    assert_eq!(envelope.field_to_to_string(), "bob@example.com");
+    assert!(!envelope.other_headers().contains_key("X-Mailer"));
+    match part.content_type {
+        ContentType::Multipart { ref parts, .. } => {
+            assert_eq!(parts.len(), 2);
+            assert!(is_legacy_display_part(&parts[0]));
+            assert_eq!(
+                String::from_utf8_lossy(parts[0].body()).trim(),
+                "Subject: Secret plans"
+            );
+            assert!(!is_legacy_display_part(&parts[1]));
+            assert_eq!(parts[1].text(), "Meet at noon.");
+        }
+        _ => panic!("{:?}", part.content_type),
+    }
+    let unprotected = AttachmentBuilder::new(b"Content-Type: text/plain\r\n\r\nhi").build();
+    assert!(!has_protected_headers(&unprotected));
+}
@@ -82,6 +82,11 @@ impl ErrorKind {
             _ => false,
         }
     }
+
+    /// Whether trying the same operation again later may succeed.
+    pub fn is_transient(&self) -> bool {
+        self.is_network() || self.is_timeout()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -271,7 +276,7 @@ impl From<std::num::ParseIntError> for MeliError {
     }
 }
 
-#[cfg(feature = "jmap_backend")]
+#[cfg(feature = "http")]
 impl From<isahc::Error> for MeliError {
     #[inline]
     fn from(kind: isahc::Error) -> MeliError {
@@ -279,7 +284,7 @@ impl From<isahc::Error> for MeliError {
     }
 }
 
-#[cfg(feature = "jmap_backend")]
+#[cfg(feature = "serde_json")]
 impl From<serde_json::error::Error> for MeliError {
     #[inline]
     fn from(kind: serde_json::error::Error) -> MeliError {
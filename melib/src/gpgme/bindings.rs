@@ -2057,19 +2057,16 @@ extern "C" {
 pub type gpgme_set_keylist_mode =
     unsafe extern "C" fn(ctx: gpgme_ctx_t, mode: gpgme_keylist_mode_t) -> gpgme_error_t;
 pub type gpgme_get_keylist_mode = unsafe extern "C" fn(ctx: gpgme_ctx_t) -> gpgme_keylist_mode_t;
-extern "C" {
-    pub fn gpgme_set_pinentry_mode(ctx: gpgme_ctx_t, mode: gpgme_pinentry_mode_t) -> gpgme_error_t;
-}
+pub type gpgme_set_pinentry_mode =
+    unsafe extern "C" fn(ctx: gpgme_ctx_t, mode: gpgme_pinentry_mode_t) -> gpgme_error_t;
 extern "C" {
     pub fn gpgme_get_pinentry_mode(ctx: gpgme_ctx_t) -> gpgme_pinentry_mode_t;
 }
-extern "C" {
-    pub fn gpgme_set_passphrase_cb(
-        ctx: gpgme_ctx_t,
-        cb: gpgme_passphrase_cb_t,
-        hook_value: *mut ::std::os::raw::c_void,
-    );
-}
+pub type gpgme_set_passphrase_cb = unsafe extern "C" fn(
+    ctx: gpgme_ctx_t,
+    cb: gpgme_passphrase_cb_t,
+    hook_value: *mut ::std::os::raw::c_void,
+);
 extern "C" {
     pub fn gpgme_get_passphrase_cb(
         ctx: gpgme_ctx_t,
@@ -3783,9 +3780,8 @@ pub type gpgme_import_result_t = *mut _gpgme_op_import_result;
 extern "C" {
     pub fn gpgme_op_import_result(ctx: gpgme_ctx_t) -> gpgme_import_result_t;
 }
-extern "C" {
-    pub fn gpgme_op_import_start(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
-}
+pub type gpgme_op_import_start =
+    unsafe extern "C" fn(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
 extern "C" {
     pub fn gpgme_op_import(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
 }
@@ -3827,14 +3823,12 @@ extern "C" {
         keydata: gpgme_data_t,
     ) -> gpgme_error_t;
 }
-extern "C" {
-    pub fn gpgme_op_export_keys_start(
-        ctx: gpgme_ctx_t,
-        keys: *mut gpgme_key_t,
-        mode: gpgme_export_mode_t,
-        keydata: gpgme_data_t,
-    ) -> gpgme_error_t;
-}
+pub type gpgme_op_export_keys_start = unsafe extern "C" fn(
+    ctx: gpgme_ctx_t,
+    keys: *mut gpgme_key_t,
+    mode: gpgme_export_mode_t,
+    keydata: gpgme_data_t,
+) -> gpgme_error_t;
 extern "C" {
     pub fn gpgme_op_export_keys(
         ctx: gpgme_ctx_t,
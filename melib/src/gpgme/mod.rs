@@ -191,6 +191,9 @@ unsafe impl Sync for IoState {}
 pub struct ContextInner {
     inner: core::ptr::NonNull<gpgme_context>,
     lib: Arc<libloading::Library>,
+    /// Passphrase given to the engine instead of asking the user with pinentry, see
+    /// `Context::set_passphrase`.
+    passphrase: Mutex<Option<CString>>,
 }
 
 unsafe impl Send for ContextInner {}
@@ -264,6 +267,7 @@ impl Context {
                     MeliError::new("Could not use libgpgme").set_kind(ErrorKind::Bug)
                 })?,
                 lib,
+                passphrase: Mutex::new(None),
             }),
             io_state,
         };
@@ -367,6 +371,45 @@ impl Context {
         }
     }
 
+    /// Make key listings look up keys that aren't in the keyring with the `auto-key-locate`
+    /// mechanisms, like `gpg --locate-keys`.
+    pub fn set_locate_keys(&self, value: bool) -> Result<()> {
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_set_keylist_mode)(
+                    self.inner.inner.as_ptr(),
+                    if value {
+                        GPGME_KEYLIST_MODE_LOCATE
+                    } else {
+                        GPGME_KEYLIST_MODE_LOCAL
+                    },
+                ),
+            )
+        }
+    }
+
+    /// Give `passphrase` to the engine whenever it needs one, instead of asking the user with
+    /// pinentry. Used for symmetric encryption with passphrases the user doesn't type.
+    pub fn set_passphrase(&self, passphrase: &str) -> Result<()> {
+        *self.inner.passphrase.lock().unwrap() = Some(CString::new(passphrase)?);
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_set_pinentry_mode)(
+                    self.inner.inner.as_ptr(),
+                    gpgme_pinentry_mode_t_GPGME_PINENTRY_MODE_LOOPBACK,
+                ),
+            )?;
+            call!(&self.inner.lib, gpgme_set_passphrase_cb)(
+                self.inner.inner.as_ptr(),
+                Some(passphrase_cb),
+                Arc::as_ptr(&self.inner) as *mut ::std::os::raw::c_void,
+            );
+        }
+        Ok(())
+    }
+
     pub fn get_auto_key_locate(&self) -> Result<LocateKey> {
         let auto_key_locate: *const ::std::os::raw::c_char = c_string_literal!("auto-key-locate");
         let raw_value =
@@ -1122,6 +1165,229 @@ impl Context {
             Ok(cipher.into_bytes()?)
         })
     }
+
+    /// Import the OpenPGP keys of `key_data` into the keyring.
+    pub fn import_key(
+        &mut self,
+        mut key_data: Data,
+    ) -> Result<impl Future<Output = Result<()>> + Send> {
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_import_start)(
+                    self.inner.inner.as_ptr(),
+                    key_data.inner.as_mut(),
+                ),
+            )?;
+        }
+        let op = self.run_op();
+        Ok(async move {
+            let _k = key_data;
+            op.await
+        })
+    }
+
+    /// Export `keys`, the secret keys along with the public ones if `secret` is set. Only the
+    /// parts of public keys needed to encrypt and verify signatures are exported.
+    pub fn export_keys(
+        &mut self,
+        keys: Vec<Key>,
+        secret: bool,
+    ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
+        let mut key_data: gpgme_data_t = std::ptr::null_mut();
+        let mut raw_keys: Vec<gpgme_key_t> = Vec::with_capacity(keys.len() + 1);
+        raw_keys.extend(keys.iter().map(|k| k.inner.inner.as_ptr()));
+        raw_keys.push(std::ptr::null_mut());
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_data_new)(&mut key_data),
+            )?;
+        }
+        let mut key_data = Data {
+            lib: self.inner.lib.clone(),
+            kind: DataKind::Memory,
+            inner: core::ptr::NonNull::new(key_data).ok_or_else(|| {
+                MeliError::new("internal libgpgme error").set_kind(ErrorKind::Bug)
+            })?,
+        };
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_export_keys_start)(
+                    self.inner.inner.as_ptr(),
+                    raw_keys.as_mut_ptr(),
+                    if secret {
+                        GPGME_EXPORT_MODE_SECRET
+                    } else {
+                        GPGME_EXPORT_MODE_MINIMAL
+                    },
+                    key_data.inner.as_mut(),
+                ),
+            )?;
+        }
+        let op = self.run_op();
+        Ok(async move {
+            let _k = keys;
+            op.await?;
+            key_data
+                .seek(std::io::SeekFrom::Start(0))
+                .chain_err_summary(|| "libgpgme error: could not perform seek on key data")?;
+            Ok(key_data.into_bytes()?)
+        })
+    }
+
+    /// Encrypt `plain` with the passphrase set with `set_passphrase`.
+    pub fn encrypt_symmetric(
+        &mut self,
+        mut plain: Data,
+    ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
+        let mut cipher: gpgme_data_t = std::ptr::null_mut();
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_data_new)(&mut cipher),
+            )?;
+        }
+        let mut cipher = Data {
+            lib: self.inner.lib.clone(),
+            kind: DataKind::Memory,
+            inner: core::ptr::NonNull::new(cipher).ok_or_else(|| {
+                MeliError::new("internal libgpgme error").set_kind(ErrorKind::Bug)
+            })?,
+        };
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_encrypt_start)(
+                    self.inner.inner.as_ptr(),
+                    std::ptr::null_mut(),
+                    gpgme_encrypt_flags_t_GPGME_ENCRYPT_SYMMETRIC,
+                    plain.inner.as_mut(),
+                    cipher.inner.as_mut(),
+                ),
+            )?;
+        }
+        let op = self.run_op();
+        Ok(async move {
+            let _p = plain;
+            op.await?;
+            cipher
+                .seek(std::io::SeekFrom::Start(0))
+                .chain_err_summary(|| "libgpgme error: could not perform seek on cipher text")?;
+            Ok(cipher.into_bytes()?)
+        })
+    }
+
+    /// Drive the file descriptors of the operation just started until it is done.
+    fn run_op(&self) -> impl Future<Output = Result<()>> + Send {
+        let io_state = self.io_state.clone();
+        let io_state_lck = self.io_state.lock().unwrap();
+        let done = io_state_lck.done.clone();
+        let fut = io_state_lck
+            .ops
+            .values()
+            .map(|a| Async::new(a.clone()).unwrap())
+            .collect::<Vec<Async<GpgmeFd>>>();
+        drop(io_state_lck);
+        async move {
+            futures::future::join_all(fut.iter().map(|fut| {
+                let done = done.clone();
+                if fut.get_ref().write {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.write_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                } else {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.read_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                }
+            }))
+            .await;
+            let rcv = {
+                let io_state_lck = io_state.lock().unwrap();
+                io_state_lck.receiver.clone()
+            };
+            let _ = rcv.recv().await;
+            let io_state_lck = io_state.lock().unwrap();
+            let ret = io_state_lck
+                .done
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| Err(MeliError::new("Unspecified libgpgme error")));
+            ret
+        }
+    }
+}
+
+/// Passphrase callback of contexts with a passphrase set with `Context::set_passphrase`. `hook`
+/// is the context's `ContextInner`.
+unsafe extern "C" fn passphrase_cb(
+    hook: *mut ::std::os::raw::c_void,
+    _uid_hint: *const ::std::os::raw::c_char,
+    _passphrase_info: *const ::std::os::raw::c_char,
+    prev_was_bad: ::std::os::raw::c_int,
+    fd: ::std::os::raw::c_int,
+) -> gpgme_error_t {
+    /* GPG_ERR_CANCELED */
+    const CANCELED: gpgme_error_t = 99;
+    let inner = &*(hook as *const ContextInner);
+    let passphrase = inner.passphrase.lock().unwrap();
+    let passphrase = match passphrase.as_ref() {
+        /* Asking again won't help. */
+        Some(passphrase) if prev_was_bad == 0 => passphrase,
+        _ => return CANCELED,
+    };
+    let mut bytes = passphrase.as_bytes().to_vec();
+    bytes.push(b'\n');
+    let mut written = 0;
+    while written < bytes.len() {
+        let n = libc::write(
+            fd,
+            bytes[written..].as_ptr() as *const ::std::os::raw::c_void,
+            bytes.len() - written,
+        );
+        if n <= 0 {
+            return CANCELED;
+        }
+        written += n as usize;
+    }
+    0
 }
 
 fn gpgme_error_try(lib: &libloading::Library, error_code: GpgmeError) -> Result<()> {
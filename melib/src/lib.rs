@@ -97,6 +97,8 @@ pub mod dbg {
 #[cfg(feature = "unicode_algorithms")]
 pub mod text_processing;
 
+pub mod collation;
+pub use collation::Collator;
 pub mod datetime;
 pub use datetime::UnixTimestamp;
 
@@ -121,7 +123,9 @@ pub mod thread;
 pub use thread::*;
 pub mod connections;
 pub mod parsec;
+pub mod paths;
 pub mod search;
+pub mod secrets;
 
 #[cfg(feature = "gpgme")]
 pub mod gpgme;
@@ -20,10 +20,12 @@
  */
 
 use crate::shellexpand::ShellExpandTrait;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
 use std::fs::OpenOptions;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 
 #[derive(Copy, Clone, PartialEq, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub enum LoggingLevel {
@@ -62,65 +64,192 @@ impl Default for LoggingLevel {
 
 use LoggingLevel::*;
 
+/// Target of the records logged with [`log`].
+pub const DEFAULT_TARGET: &str = "meli";
+
+/// How many of the most recent records are kept in memory for the log viewer.
+const RECENT_RECORDS: usize = 1000;
+
+/// A single logged message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub timestamp: crate::UnixTimestamp,
+    pub level: LoggingLevel,
+    /// The module the record came from, eg `imap` or `ui`.
+    pub target: String,
+    pub message: String,
+    /// Key-value pairs attached to the message.
+    pub fields: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}] {}: {}",
+            crate::datetime::timestamp_to_string(self.timestamp, None, false),
+            self.level,
+            self.target,
+            self.message
+        )?;
+        for (key, value) in &self.fields {
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                write!(f, " {}={:?}", key, value)?;
+            } else {
+                write!(f, " {}={}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 struct LoggingBackend {
     dest: BufWriter<std::fs::File>,
     level: LoggingLevel,
+    /// Levels of specific targets, overriding `level`.
+    targets: HashMap<String, LoggingLevel>,
+    recent: VecDeque<LogRecord>,
+}
+
+impl LoggingBackend {
+    fn target_level(&self, target: &str) -> LoggingLevel {
+        target_level(&self.targets, self.level, target)
+    }
+}
+
+/// The level of `target`, or of the closest parent target (`imap` for `imap::untagged`) that has
+/// one.
+fn target_level(
+    targets: &HashMap<String, LoggingLevel>,
+    default: LoggingLevel,
+    mut target: &str,
+) -> LoggingLevel {
+    loop {
+        if let Some(level) = targets.get(target) {
+            return *level;
+        }
+        match target.rfind("::") {
+            Some(pos) => target = &target[..pos],
+            None => return default,
+        }
+    }
 }
 
-thread_local!(static LOG: Arc<Mutex<LoggingBackend>> = Arc::new(Mutex::new({
-    let data_dir = xdg::BaseDirectories::with_prefix("meli").unwrap();
-    let log_file = OpenOptions::new().append(true) /* writes will append to a file instead of overwriting previous contents */
+static LOG: Lazy<Mutex<LoggingBackend>> = Lazy::new(|| {
+    let log_file = OpenOptions::new()
+        .append(true) /* writes will append to a file instead of overwriting previous contents */
         .create(true) /* a new file will be created if the file does not yet already exist.*/
         .read(true)
-        .open(data_dir.place_data_file("meli.log").unwrap()).unwrap();
-    LoggingBackend {
+        .open(crate::paths::state_file(None, "meli.log").unwrap())
+        .unwrap();
+    Mutex::new(LoggingBackend {
         dest: BufWriter::new(log_file),
         level: LoggingLevel::default(),
-    }}))
-);
+        targets: HashMap::default(),
+        recent: VecDeque::with_capacity(RECENT_RECORDS),
+    })
+});
 
 pub fn log<S: AsRef<str>>(val: S, level: LoggingLevel) {
-    LOG.with(|f| {
-        let mut b = f.lock().unwrap();
-        if level <= b.level {
-            b.dest
-                .write_all(
-                    crate::datetime::timestamp_to_string(crate::datetime::now(), None, false)
-                        .as_bytes(),
-                )
-                .unwrap();
-            b.dest.write_all(b" [").unwrap();
-            b.dest.write_all(level.to_string().as_bytes()).unwrap();
-            b.dest.write_all(b"]: ").unwrap();
-            b.dest.write_all(val.as_ref().as_bytes()).unwrap();
-            b.dest.write_all(b"\n").unwrap();
-            b.dest.flush().unwrap();
-        }
-    });
+    log_fields(DEFAULT_TARGET, level, val, &[]);
+}
+
+pub fn log_target<S: AsRef<str>>(target: &str, val: S, level: LoggingLevel) {
+    log_fields(target, level, val, &[]);
+}
+
+/// Log `message` with key-value `fields`, eg
+/// `log_fields("jobs", TRACE, "spawned", &[("id", &job_id)])`.
+pub fn log_fields<S: AsRef<str>>(
+    target: &str,
+    level: LoggingLevel,
+    message: S,
+    fields: &[(&str, &dyn std::fmt::Display)],
+) {
+    if level > LOG.lock().unwrap().target_level(target) {
+        return;
+    }
+    /* Format the fields before taking the lock again, in case they log something themselves */
+    let record = LogRecord {
+        timestamp: crate::datetime::now(),
+        level,
+        target: target.to_string(),
+        message: message.as_ref().to_string(),
+        fields: fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    };
+    let mut b = LOG.lock().unwrap();
+    writeln!(b.dest, "{}", record).unwrap();
+    b.dest.flush().unwrap();
+    if b.recent.len() == RECENT_RECORDS {
+        b.recent.pop_front();
+    }
+    b.recent.push_back(record);
+}
+
+/// The most recent records, oldest first.
+pub fn recent_log_records() -> Vec<LogRecord> {
+    LOG.lock().unwrap().recent.iter().cloned().collect()
 }
 
 pub fn get_log_level() -> LoggingLevel {
-    let mut level = INFO;
-    LOG.with(|f| {
-        level = f.lock().unwrap().level;
-    });
-    level
+    LOG.lock().unwrap().level
+}
+
+pub fn get_log_target_level(target: &str) -> LoggingLevel {
+    LOG.lock().unwrap().target_level(target)
 }
 
 pub fn change_log_dest(path: PathBuf) {
-    LOG.with(|f| {
-        let path = path.expand(); // expand shell stuff
-        let mut backend = f.lock().unwrap();
-        backend.dest = BufWriter::new(OpenOptions::new().append(true) /* writes will append to a file instead of overwriting previous contents */
-                         .create(true) /* a new file will be created if the file does not yet already exist.*/
-                         .read(true)
-                         .open(path).unwrap());
-    });
+    let path = path.expand(); // expand shell stuff
+    let mut backend = LOG.lock().unwrap();
+    backend.dest = BufWriter::new(OpenOptions::new().append(true) /* writes will append to a file instead of overwriting previous contents */
+                     .create(true) /* a new file will be created if the file does not yet already exist.*/
+                     .read(true)
+                     .open(path).unwrap());
 }
 
 pub fn change_log_level(new_val: LoggingLevel) {
-    LOG.with(|f| {
-        let mut backend = f.lock().unwrap();
-        backend.level = new_val;
-    });
+    LOG.lock().unwrap().level = new_val;
+}
+
+/// Set the level of `target` and its children, overriding the maximum level.
+pub fn change_log_target_level(target: &str, new_val: LoggingLevel) {
+    LOG.lock()
+        .unwrap()
+        .targets
+        .insert(target.to_string(), new_val);
+}
+
+/// Remove all per-target levels.
+pub fn reset_log_target_levels() {
+    LOG.lock().unwrap().targets.clear();
+}
+
+#[test]
+fn test_log_target_level() {
+    let mut targets = HashMap::default();
+    targets.insert("imap".to_string(), TRACE);
+    targets.insert("imap::untagged".to_string(), ERROR);
+    assert_eq!(target_level(&targets, WARN, "ui"), WARN);
+    assert_eq!(target_level(&targets, WARN, "imap"), TRACE);
+    assert_eq!(target_level(&targets, WARN, "imap::connection"), TRACE);
+    assert_eq!(target_level(&targets, WARN, "imap::untagged"), ERROR);
+    assert_eq!(target_level(&targets, WARN, "imaps"), WARN);
+
+    let record = LogRecord {
+        timestamp: 0,
+        level: DEBUG,
+        target: "jobs".to_string(),
+        message: "finished".to_string(),
+        fields: vec![
+            ("id".to_string(), "3".to_string()),
+            ("desc".to_string(), "refresh INBOX".to_string()),
+        ],
+    };
+    assert!(record
+        .to_string()
+        .ends_with(" [DEBUG] jobs: finished id=3 desc=\"refresh INBOX\""));
 }
@@ -0,0 +1,360 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Locations of the files kept between sessions.
+ *
+ * Indexes, caches, sessions, notes and the other state of meli are kept in
+ * `$XDG_STATE_HOME/meli` (`~/.local/state/meli` by default), with a directory per account for
+ * the state of accounts. Each profile selected with [`PROFILE_VAR`] keeps its state in
+ * `$XDG_STATE_HOME/meli/profiles/<name>` instead, and setting [`DATA_DIR_VAR`] moves it anywhere.
+ *
+ * Earlier versions kept these files in `$XDG_DATA_HOME/meli` and `$XDG_CACHE_HOME/meli`; they are
+ * moved to their new location the first time they are used.
+ */
+use crate::{MeliError, Result};
+use std::fs;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides the state directory, set by `meli --data-dir`.
+pub const DATA_DIR_VAR: &str = "MELI_DATA_DIR";
+/// Environment variable that selects a profile, with its own configuration file and state, set
+/// by `meli --profile`.
+pub const PROFILE_VAR: &str = "MELI_PROFILE";
+
+/// The name of the selected profile, if any.
+pub fn profile() -> Result<Option<String>> {
+    match std::env::var(PROFILE_VAR) {
+        Ok(name) if name.is_empty() => Ok(None),
+        Ok(name) => {
+            check_profile_name(&name)?;
+            Ok(Some(name))
+        }
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(MeliError::new(format!("Invalid profile name: {}", err))),
+    }
+}
+
+fn check_profile_name(name: &str) -> Result<()> {
+    if name.contains('/') || name.starts_with('.') {
+        return Err(MeliError::new(format!(
+            "Invalid profile name `{}`: it can't contain `/` or start with `.`.",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// The directories state paths are resolved against.
+#[derive(Debug, Clone)]
+struct Dirs {
+    /// The directory of all state.
+    state: PathBuf,
+    /// The `meli` data and cache directories of earlier versions, whose files are moved to
+    /// `state`. Empty for profiles and `--data-dir`, which start afresh.
+    legacy: Vec<PathBuf>,
+}
+
+impl Dirs {
+    fn from_env() -> Result<Self> {
+        let profile = profile()?;
+        if let Some(dir) = std::env::var_os(DATA_DIR_VAR).filter(|dir| !dir.is_empty()) {
+            return Ok(Dirs {
+                state: PathBuf::from(dir),
+                legacy: vec![],
+            });
+        }
+        let state_home = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let mut home = PathBuf::from(std::env::var_os("HOME").ok_or_else(|| {
+                    MeliError::new("$HOME is not set, can't locate the state directory.")
+                })?);
+                home.push(".local");
+                home.push("state");
+                home
+            }
+        };
+        let legacy = match (profile.as_ref(), xdg::BaseDirectories::with_prefix("meli")) {
+            (None, Ok(legacy_dirs)) => {
+                vec![legacy_dirs.get_data_home(), legacy_dirs.get_cache_home()]
+            }
+            _ => vec![],
+        };
+        Ok(Dirs {
+            state: profile_state_dir(&state_home, profile.as_deref()),
+            legacy,
+        })
+    }
+
+    fn account_dir(&self, account_name: &str) -> Result<PathBuf> {
+        Ok(self.state.join(account_dir_name(account_name)?))
+    }
+
+    fn state_file(&self, account: Option<&str>, name: &Path) -> Result<PathBuf> {
+        Ok(match account {
+            Some(account_name) => self.account_dir(account_name)?,
+            None => self.state.clone(),
+        }
+        .join(name))
+    }
+
+    /// Like [`Dirs::state_file`], but creates the file's directories and moves the file of an
+    /// earlier version to it.
+    fn moved_state_file(
+        &self,
+        account: Option<&str>,
+        name: &Path,
+        legacy_account: Option<&str>,
+        legacy_name: &Path,
+    ) -> Result<PathBuf> {
+        let path = self.state_file(account, name)?;
+        if let Some(parent) = path.parent() {
+            create_dir(parent)?;
+        }
+        if !path.exists() {
+            for legacy_dir in self.legacy.iter() {
+                let legacy = match legacy_account {
+                    Some(account_name) => legacy_dir.join(account_name).join(legacy_name),
+                    None => legacy_dir.join(legacy_name),
+                };
+                if legacy.is_file() {
+                    move_file(&legacy, &path)?;
+                    break;
+                }
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// The state directory of `profile` in `state_home`, `$XDG_STATE_HOME` or its default.
+fn profile_state_dir(state_home: &Path, profile: Option<&str>) -> PathBuf {
+    let mut dir = state_home.join("meli");
+    if let Some(profile) = profile {
+        dir.push("profiles");
+        dir.push(profile);
+    }
+    dir
+}
+
+/// The name of the state directory of account `account_name`, which must stay inside the state
+/// directory.
+fn account_dir_name(account_name: &str) -> Result<String> {
+    match account_name {
+        "" | "." | ".." => Err(MeliError::new(format!(
+            "Invalid account name `{}`: it can't be used as a directory name.",
+            account_name
+        ))),
+        _ => Ok(account_name.replace(|c| c == '/' || c == '\0', "_")),
+    }
+}
+
+/// The directory of all state, created if it doesn't exist.
+pub fn state_dir() -> Result<PathBuf> {
+    let dir = state_dir_path()?;
+    create_dir(&dir)?;
+    Ok(dir)
+}
+
+/// The directory of all state, without creating it.
+pub fn state_dir_path() -> Result<PathBuf> {
+    Ok(Dirs::from_env()?.state)
+}
+
+/// The state directory of account `account_name`, created if it doesn't exist.
+pub fn account_dir(account_name: &str) -> Result<PathBuf> {
+    let dir = account_dir_path(account_name)?;
+    create_dir(&dir)?;
+    Ok(dir)
+}
+
+/// The state directory of account `account_name`, without creating it.
+pub fn account_dir_path(account_name: &str) -> Result<PathBuf> {
+    Dirs::from_env()?.account_dir(account_name)
+}
+
+/// The path of state file `name`, of account `account` or global. `name` may have directories,
+/// which are created.
+pub fn state_file(account: Option<&str>, name: impl AsRef<Path>) -> Result<PathBuf> {
+    moved_state_file(account, name.as_ref(), account, name.as_ref())
+}
+
+/// The path of state file `name`, of account `account` or global, without creating its
+/// directories or moving the file of an earlier version.
+pub fn state_file_path(account: Option<&str>, name: impl AsRef<Path>) -> Result<PathBuf> {
+    Dirs::from_env()?.state_file(account, name.as_ref())
+}
+
+/// Like [`state_file`], for files that earlier versions named `legacy_name`, of account
+/// `legacy_account` or global.
+pub fn moved_state_file(
+    account: Option<&str>,
+    name: impl AsRef<Path>,
+    legacy_account: Option<&str>,
+    legacy_name: impl AsRef<Path>,
+) -> Result<PathBuf> {
+    Dirs::from_env()?.moved_state_file(account, name.as_ref(), legacy_account, legacy_name.as_ref())
+}
+
+/// Replace the contents of `path` with `contents`, readable and writable by the owner only.
+/// The contents are written to a temporary file next to `path` which is then renamed over it, so
+/// that a crash while writing leaves either the old or the new contents.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let ret = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600) // Read/write for owner only.
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if let Err(err) = ret {
+        let _ = fs::remove_file(&tmp);
+        return Err(
+            MeliError::new(format!("Could not write {}: {}", path.display(), err))
+                .set_source(Some(std::sync::Arc::new(err))),
+        );
+    }
+    Ok(())
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700) // Owner only.
+        .create(dir)
+        .map_err(|err| {
+            MeliError::new(format!("Could not create {}: {}", dir.display(), err))
+                .set_source(Some(std::sync::Arc::new(err)))
+        })
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    /* The directories can be on different file systems. */
+    fs::rename(from, to)
+        .or_else(|_| fs::copy(from, to).and_then(|_| fs::remove_file(from)))
+        .map_err(|err: io::Error| {
+            MeliError::new(format!(
+                "Could not move {} to {}: {}",
+                from.display(),
+                to.display(),
+                err
+            ))
+        })
+}
+
+#[test]
+fn test_state_file() {
+    let base = std::env::temp_dir().join(format!("meli-paths-{}", std::process::id()));
+    let dirs = Dirs {
+        state: base.join("state/meli"),
+        legacy: vec![base.join("data/meli"), base.join("cache/meli")],
+    };
+    fs::create_dir_all(base.join("data/meli/work")).unwrap();
+    fs::write(base.join("data/meli/work/notes"), "note").unwrap();
+    fs::write(base.join("data/meli/work_index.db"), "db").unwrap();
+
+    /* Resolving a path changes nothing. */
+    let notes = dirs.state_file(Some("work"), Path::new("notes")).unwrap();
+    assert_eq!(notes, base.join("state/meli/work/notes"));
+    assert!(!base.join("state").exists());
+
+    /* Files of earlier versions are moved. */
+    let notes = dirs
+        .moved_state_file(
+            Some("work"),
+            Path::new("notes"),
+            Some("work"),
+            Path::new("notes"),
+        )
+        .unwrap();
+    assert_eq!(notes, base.join("state/meli/work/notes"));
+    assert_eq!(fs::read_to_string(&notes).unwrap(), "note");
+    assert!(!base.join("data/meli/work/notes").exists());
+    let db = dirs
+        .moved_state_file(
+            Some("work"),
+            Path::new("index.db"),
+            None,
+            Path::new("work_index.db"),
+        )
+        .unwrap();
+    assert_eq!(fs::read_to_string(&db).unwrap(), "db");
+    assert_eq!(
+        dirs.moved_state_file(Some("a/b"), Path::new("threads/1"), None, Path::new("x"))
+            .unwrap(),
+        base.join("state/meli/a_b/threads/1")
+    );
+    assert!(base.join("state/meli/a_b/threads").is_dir());
+    assert_eq!(
+        dirs.state_file(None, Path::new("meli.log")).unwrap(),
+        base.join("state/meli/meli.log")
+    );
+
+    /* Account names can't leave the state directory. */
+    assert!(dirs.account_dir("..").is_err());
+    assert!(dirs.account_dir(".").is_err());
+    assert!(dirs.account_dir("").is_err());
+    assert_eq!(
+        dirs.account_dir("../work").unwrap(),
+        base.join("state/meli/.._work")
+    );
+    assert_eq!(
+        dirs.account_dir(".work").unwrap(),
+        base.join("state/meli/.work")
+    );
+
+    /* Profiles start afresh. */
+    assert_eq!(
+        profile_state_dir(&base.join("state"), Some("personal")),
+        base.join("state/meli/profiles/personal")
+    );
+    let dirs = Dirs {
+        state: base.join("profile"),
+        legacy: vec![],
+    };
+    fs::write(base.join("data/meli/work/watched_threads"), "").unwrap();
+    let watched = dirs
+        .moved_state_file(
+            Some("work"),
+            Path::new("watched_threads"),
+            Some("work"),
+            Path::new("watched_threads"),
+        )
+        .unwrap();
+    assert_eq!(watched, base.join("profile/work/watched_threads"));
+    assert!(!watched.exists());
+    assert!(check_profile_name("personal").is_ok());
+    assert!(check_profile_name("../work").is_err());
+    assert!(check_profile_name(".hidden").is_err());
+    fs::remove_dir_all(&base).unwrap();
+}
@@ -48,6 +48,13 @@ pub enum Query {
     /* * * * */
     Flags(Vec<String>),
     HasAttachment,
+    /// Messages larger than this many bytes.
+    Larger(usize),
+    /// Messages smaller than this many bytes.
+    Smaller(usize),
+    /// Text of the private note attached to the message. Notes are stored by the client, so this
+    /// term cannot be handled by backends.
+    Note(String),
     And(Box<Query>, Box<Query>),
     Or(Box<Query>, Box<Query>),
     Not(Box<Query>),
@@ -57,6 +64,28 @@ pub trait QueryTrait {
     fn is_match(&self, query: &Query) -> bool;
 }
 
+impl Query {
+    /// Whether this query contains a `note:` term anywhere.
+    pub fn has_note(&self) -> bool {
+        match self {
+            Note(_) => true,
+            And(q_a, q_b) | Or(q_a, q_b) => q_a.has_note() || q_b.has_note(),
+            Not(q) => q.has_note(),
+            _ => false,
+        }
+    }
+
+    /// Whether this query contains a `size:` term anywhere.
+    pub fn has_size(&self) -> bool {
+        match self {
+            Larger(_) | Smaller(_) => true,
+            And(q_a, q_b) | Or(q_a, q_b) => q_a.has_size() || q_b.has_size(),
+            Not(q) => q.has_size(),
+            _ => false,
+        }
+    }
+}
+
 impl QueryTrait for crate::Envelope {
     fn is_match(&self, query: &Query) -> bool {
         use Query::*;
@@ -83,6 +112,9 @@ impl QueryTrait for crate::Envelope {
             Flags(v) => v.iter().any(|s| self.flags() == s.as_str()),
             Subject(s) => self.other_headers()["Subject"].contains(s),
             HasAttachment => self.has_attachments(),
+            /* Messages of unknown size don't match either. */
+            Larger(n) => self.size() > *n,
+            Smaller(n) => self.size() != 0 && self.size() < *n,
             And(q_a, q_b) => self.is_match(q_a) && self.is_match(q_b),
             Or(q_a, q_b) => self.is_match(q_a) || self.is_match(q_b),
             Not(q) => !self.is_match(q),
@@ -144,6 +176,14 @@ pub mod query_parser {
         .map(Query::Bcc)
     }
 
+    fn note<'a>() -> impl Parser<'a, Query> {
+        prefix(
+            whitespace_wrap(match_literal("note:")),
+            whitespace_wrap(literal()),
+        )
+        .map(Query::Note)
+    }
+
     fn or<'a>() -> impl Parser<'a, Query> {
         move |input| {
             whitespace_wrap(match_literal_anycase("or"))
@@ -179,6 +219,45 @@ pub mod query_parser {
         }
     }
 
+    /// `size:>10M`, `size:<500k` or `size:1G`, which is the same as `size:>1G`. Sizes are in
+    /// bytes, `k`, `M` and `G` suffixes are powers of 1024.
+    fn size<'a>() -> impl Parser<'a, Query> {
+        move |input| {
+            whitespace_wrap(match_literal_anycase("size:"))
+                .parse(input)
+                .and_then(|(rest, _)| {
+                    map(one_or_more(pred(any_char, |c| *c != ' ')), |chars| {
+                        chars.into_iter().collect::<String>()
+                    })
+                    .parse(rest)
+                })
+                .and_then(|(rest, term)| {
+                    let constructor: fn(usize) -> Query = if term.starts_with('<') {
+                        Smaller
+                    } else {
+                        Larger
+                    };
+                    let number = if term.starts_with('<') || term.starts_with('>') {
+                        &term[1..]
+                    } else {
+                        &term
+                    };
+                    let (number, multiplier) = match number.chars().last() {
+                        Some('k') | Some('K') => (&number[..number.len() - 1], 1 << 10),
+                        Some('m') | Some('M') => (&number[..number.len() - 1], 1 << 20),
+                        Some('g') | Some('G') => (&number[..number.len() - 1], 1 << 30),
+                        _ => (number, 1),
+                    };
+                    number
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| n.checked_mul(multiplier))
+                        .map(|n| (rest, constructor(n)))
+                        .ok_or(rest)
+                })
+        }
+    }
+
     fn literal<'a>() -> impl Parser<'a, String> {
         move |input| either(quoted_string(), string()).parse(input)
     }
@@ -252,6 +331,8 @@ pub mod query_parser {
                 .or_else(|_| subject().parse(input))
                 .or_else(|_| flags().parse(input))
                 .or_else(|_| has_attachment().parse(input))
+                .or_else(|_| size().parse(input))
+                .or_else(|_| note().parse(input))
             {
                 Ok(q)
             } else if let Ok((rest, query_a)) = not().parse(input) {
@@ -380,6 +461,36 @@ pub mod query_parser {
             Ok(("", Flags(vec!["f".to_string()]))),
             query().parse_complete("tags:f")
         );
+        assert_eq!(
+            Ok((
+                "",
+                And(
+                    Box::new(Note("call back".to_string())),
+                    Box::new(Not(Box::new(From("Manos".to_string()))))
+                )
+            )),
+            query().parse_complete("note: \"call back\" and not from: Manos")
+        );
+        assert_eq!(
+            Ok(("", Larger(10 * 1024 * 1024))),
+            query().parse_complete("size:>10M")
+        );
+        assert_eq!(
+            query().parse_complete("size:>10M"),
+            query().parse_complete("size:10m")
+        );
+        assert_eq!(
+            Ok((
+                "",
+                And(
+                    Box::new(Smaller(500 * 1024)),
+                    Box::new(From("Manos".to_string()))
+                )
+            )),
+            query().parse_complete("size:<500k and from: Manos")
+        );
+        assert_eq!(Ok(("", Larger(4096))), query().parse_complete("size:4096"));
+        assert!(query().parse_complete("size:>lots").is_err());
     }
 }
 
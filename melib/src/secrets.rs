@@ -0,0 +1,228 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2020  Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Passwords read from the configuration, the output of a command or the system keyring, and
+//! kept in memory for a limited time.
+
+use crate::error::{MeliError, Result};
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, PartialEq)]
+pub enum SecretSource {
+    Raw(String),
+    /// Output of a shell command, eg `gpg2 --no-tty -q -d ~/.passwords/mail.gpg`.
+    Command(String),
+    /// A Secret Service item with attributes `service` and `username`, or on macOS a keychain
+    /// generic password with the same service and account.
+    Keyring {
+        service: String,
+        username: String,
+    },
+}
+
+impl fmt::Debug for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SecretSource::Raw(_) => write!(f, "Raw(..)"),
+            SecretSource::Command(command) => write!(f, "Command({:?})", command),
+            SecretSource::Keyring { service, username } => {
+                write!(f, "Keyring({:?}, {:?})", service, username)
+            }
+        }
+    }
+}
+
+impl SecretSource {
+    pub fn fetch(&self) -> Result<String> {
+        match self {
+            SecretSource::Raw(secret) => Ok(secret.clone()),
+            SecretSource::Command(command) => run_command(command),
+            SecretSource::Keyring { service, username } => keyring_lookup(service, username),
+        }
+    }
+}
+
+/// Run `command` with `sh -c` and return its output without the trailing newline.
+pub fn run_command(command: &str) -> Result<String> {
+    let output = Command::new("sh")
+        .args(&["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !output.status.success() {
+        return Err(MeliError::new(format!(
+            "Password command `{}` returned {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(std::str::from_utf8(&output.stdout)?.trim_end().to_string())
+}
+
+/// Look up a password in the macOS keychain.
+#[cfg(target_os = "macos")]
+pub fn keyring_lookup(service: &str, username: &str) -> Result<String> {
+    let mut command = Command::new("security");
+    command.args(&["find-generic-password", "-s", service, "-a", username, "-w"]);
+    keyring_output(command, service, username)
+}
+
+/// Look up a password with the Secret Service API (GNOME Keyring, KeePassXC, KWallet) through
+/// libsecret's `secret-tool`.
+#[cfg(not(target_os = "macos"))]
+pub fn keyring_lookup(service: &str, username: &str) -> Result<String> {
+    let mut command = Command::new("secret-tool");
+    command.args(&["lookup", "service", service, "username", username]);
+    keyring_output(command, service, username)
+}
+
+fn keyring_output(mut command: Command, service: &str, username: &str) -> Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| {
+            MeliError::new(format!("Could not run {:?}: {}", command, err))
+                .set_source(Some(Arc::new(err)))
+        })?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(MeliError::new(format!(
+            "No password found in the keyring for service `{}` and username `{}`. {}",
+            service,
+            username,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(std::str::from_utf8(&output.stdout)?
+        .trim_end_matches('\n')
+        .to_string())
+}
+
+/// A fetched secret, shared between clones.
+#[derive(Clone, Default)]
+pub struct SecretCache {
+    inner: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl fmt::Debug for SecretCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretCache")
+    }
+}
+
+/* The cache doesn't change what a configuration means, so it shouldn't affect equality. */
+impl PartialEq for SecretCache {
+    fn eq(&self, _other: &SecretCache) -> bool {
+        true
+    }
+}
+
+impl SecretCache {
+    /// Return the cached secret if it was fetched less than `timeout` ago, otherwise `fetch` it
+    /// again. A `None` timeout keeps the secret for as long as the cache exists, and a zero one
+    /// doesn't keep it at all.
+    pub fn get_or_fetch(
+        &self,
+        timeout: Option<Duration>,
+        fetch: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        /* Hold the lock while fetching, so that concurrent connections don't prompt twice. */
+        let mut cached = self.inner.lock().unwrap();
+        if let Some((ref secret, fetched)) = *cached {
+            if timeout.map(|t| fetched.elapsed() < t).unwrap_or(true) {
+                return Ok(secret.clone());
+            }
+        }
+        *cached = None;
+        let secret = fetch()?;
+        if timeout != Some(Duration::from_secs(0)) {
+            *cached = Some((secret.clone(), Instant::now()));
+        }
+        Ok(secret)
+    }
+
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = None;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Secret {
+    pub source: SecretSource,
+    /// How long to keep the secret in memory, see [`SecretCache::get_or_fetch`].
+    pub cache_timeout: Option<Duration>,
+    cache: SecretCache,
+}
+
+impl Secret {
+    pub fn new(source: SecretSource, cache_timeout: Option<Duration>) -> Self {
+        Secret {
+            source,
+            cache_timeout,
+            cache: SecretCache::default(),
+        }
+    }
+
+    pub fn get(&self) -> Result<String> {
+        if let SecretSource::Raw(ref secret) = self.source {
+            return Ok(secret.clone());
+        }
+        self.cache
+            .get_or_fetch(self.cache_timeout, || self.source.fetch())
+    }
+
+    /// Forget the cached secret, eg after the server rejected it.
+    pub fn clear(&self) {
+        self.cache.clear();
+    }
+}
+
+#[test]
+fn test_secret_cache() {
+    let cache = SecretCache::default();
+    let mut fetches = 0;
+    let mut fetch = |timeout| {
+        cache.get_or_fetch(timeout, || {
+            fetches += 1;
+            Ok(format!("hunter{}", fetches))
+        })
+    };
+    assert_eq!(fetch(None).unwrap(), "hunter1");
+    assert_eq!(fetch(None).unwrap(), "hunter1");
+    assert_eq!(fetch(Some(Duration::from_secs(0))).unwrap(), "hunter2");
+    assert_eq!(fetch(Some(Duration::from_secs(60))).unwrap(), "hunter3");
+    assert_eq!(fetch(Some(Duration::from_secs(60))).unwrap(), "hunter3");
+    cache.clear();
+    assert_eq!(fetch(None).unwrap(), "hunter4");
+
+    let secret = Secret::new(SecretSource::Command("echo hunter2".to_string()), None);
+    assert_eq!(secret.get().unwrap(), "hunter2");
+    assert_eq!(
+        format!("{:?}", SecretSource::Raw("hunter2".into())),
+        "Raw(..)"
+    );
+}
@@ -42,6 +42,8 @@
  *        danger_accept_invalid_certs: false,
  *    },
  *    extensions: SmtpExtensionSupport::default(),
+ *    tls: SmtpTlsConf::default(),
+ *    proxy: None,
  *    auth: SmtpAuth::Auto {
  *        username: "l15".into(),
  *        password: Password::CommandEval(
@@ -71,17 +73,24 @@
  *```
  */
 
-use crate::connections::{lookup_ipv4, Connection};
+use crate::connections::{
+    lookup_ipv4,
+    proxy::Proxy,
+    tls::{self, TlsSettings},
+    Connection,
+};
 use crate::email::{parser::BytesExt, Address, Envelope};
 use crate::error::{MeliError, Result, ResultIntoMeliError};
+use crate::secrets::{SecretCache, SecretSource};
+use crate::shellexpand::ShellExpandTrait;
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 use smallvec::SmallVec;
 use smol::unblock;
 use smol::Async as AsyncWrapper;
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::net::TcpStream;
+use std::path::Path;
 use std::process::Command;
 
 /// Kind of server security (StartTLS/TLS/None) the client should attempt
@@ -123,6 +132,22 @@ pub enum Password {
     Raw(String),
     #[serde(alias = "command_evaluation", alias = "command_eval")]
     CommandEval(String),
+    /// Secret Service or macOS keychain item, see `melib::secrets::keyring_lookup`.
+    #[serde(alias = "keyring")]
+    Keyring { service: String, username: String },
+}
+
+impl Password {
+    fn source(&self) -> SecretSource {
+        match self {
+            Password::Raw(p) => SecretSource::Raw(p.clone()),
+            Password::CommandEval(command) => SecretSource::Command(command.clone()),
+            Password::Keyring { service, username } => SecretSource::Keyring {
+                service: service.clone(),
+                username: username.clone(),
+            },
+        }
+    }
 }
 
 /// Kind of server authentication the client should attempt
@@ -139,6 +164,12 @@ pub enum SmtpAuth {
         require_auth: bool,
         #[serde(skip_serializing, skip_deserializing, default)]
         auth_type: SmtpAuthType,
+        /// Seconds to keep an evaluated password in memory. If unset, it's evaluated on every
+        /// connection.
+        #[serde(default)]
+        password_cache_timeout: Option<u64>,
+        #[serde(skip_serializing, skip_deserializing, default)]
+        password_cache: SecretCache,
     },
     #[serde(alias = "xoauth2")]
     XOAuth2 {
@@ -185,6 +216,62 @@ pub struct SmtpServerConf {
     pub security: SmtpSecurity,
     #[serde(default)]
     pub extensions: SmtpExtensionSupport,
+    #[serde(flatten)]
+    pub tls: SmtpTlsConf,
+    /// Proxy to connect through, in the same format as the `proxy` account setting.
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+/// Certificate pinning, client certificate and minimum TLS version of the connection. The
+/// values have the same format as the IMAP and NNTP account settings of the same name.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SmtpTlsConf {
+    /// Comma separated SHA-256 fingerprints of the certificates the server may present.
+    #[serde(default)]
+    pub tls_pinned_certificates: Option<String>,
+    /// PKCS#12 archive with the client certificate and key.
+    #[serde(default)]
+    pub tls_client_certificate: Option<String>,
+    #[serde(default)]
+    pub tls_client_certificate_password: Option<String>,
+    #[serde(default)]
+    pub tls_min_version: Option<String>,
+}
+
+impl SmtpTlsConf {
+    fn settings(&self, danger_accept_invalid_certs: bool) -> Result<TlsSettings> {
+        Ok(TlsSettings {
+            account_name: String::new(),
+            danger_accept_invalid_certs,
+            pinned_certificates: match self.tls_pinned_certificates {
+                Some(ref v) => tls::parse_pinned_certificates(v).map_err(|f| {
+                    MeliError::new(format!(
+                        "SMTP configuration error: invalid value for field `tls_pinned_certificates`: {}",
+                        f
+                    ))
+                })?,
+                None => vec![],
+            },
+            client_certificate: self
+                .tls_client_certificate
+                .as_ref()
+                .map(|p| Path::new(p).expand()),
+            client_certificate_password: self
+                .tls_client_certificate_password
+                .clone()
+                .unwrap_or_default(),
+            min_version: match self.tls_min_version {
+                Some(ref v) => Some(tls::parse_min_version(v).ok_or_else(|| {
+                    MeliError::new(format!(
+                        "SMTP configuration error: invalid value for field `tls_min_version`: {}",
+                        v
+                    ))
+                })?),
+                None => None,
+            },
+        })
+    }
 }
 
 //example: "SIZE 52428800", "8BITMIME", "PIPELINING", "CHUNKING", "PRDR",
@@ -232,6 +319,18 @@ impl Default for SmtpExtensionSupport {
     }
 }
 
+/// Open the TCP connection to `host`, directly or through `proxy`.
+async fn tcp_connect(proxy: Option<&Proxy>, host: &str, port: u16) -> Result<TcpStream> {
+    if let Some(proxy) = proxy {
+        /* The proxy's default handshake timeout leaves time for slow networks like Tor. */
+        return proxy.connect(host, port, None).await;
+    }
+    let addr = lookup_ipv4(host, port).await?;
+    smol::unblock(move || TcpStream::connect_timeout(&addr, std::time::Duration::new(4, 0)))
+        .await
+        .chain_err_kind(crate::error::ErrorKind::Network)
+}
+
 #[derive(Debug)]
 /// SMTP client session object.
 ///
@@ -247,6 +346,11 @@ impl SmtpConnection {
     pub async fn new_connection(mut server_conf: SmtpServerConf) -> Result<Self> {
         let path = &server_conf.hostname;
         let mut res = String::with_capacity(8 * 1024);
+        let proxy = server_conf
+            .proxy
+            .as_ref()
+            .map(|p| p.parse::<Proxy>())
+            .transpose()?;
         let stream = match server_conf.security {
             SmtpSecurity::Auto {
                 danger_accept_invalid_certs,
@@ -257,18 +361,16 @@ impl SmtpConnection {
             | SmtpSecurity::StartTLS {
                 danger_accept_invalid_certs,
             } => {
-                let mut connector = TlsConnector::builder();
-                if danger_accept_invalid_certs {
-                    connector.danger_accept_invalid_certs(true);
-                }
-                let connector = connector
-                    .build()
-                    .chain_err_kind(crate::error::ErrorKind::Network)?;
+                let tls = server_conf.tls.settings(danger_accept_invalid_certs)?;
+                let accepted = if danger_accept_invalid_certs {
+                    vec![]
+                } else {
+                    tls.accepted_fingerprints(path, server_conf.port)
+                };
+                let connector = tls.connector(!accepted.is_empty())?;
 
-                let addr = lookup_ipv4(path, server_conf.port)?;
                 let mut socket = AsyncWrapper::new(Connection::Tcp(
-                    TcpStream::connect_timeout(&addr, std::time::Duration::new(4, 0))
-                        .chain_err_kind(crate::error::ErrorKind::Network)?,
+                    tcp_connect(proxy.as_ref(), path, server_conf.port).await?,
                 ))
                 .chain_err_kind(crate::error::ErrorKind::Network)?;
                 let pre_ehlo_extensions_reply = read_lines(
@@ -331,6 +433,7 @@ impl SmtpConnection {
                     let conn = unblock(move || connector.connect(&_path, socket))
                         .await
                         .chain_err_kind(crate::error::ErrorKind::Network)?;
+                    tls::verify(path, server_conf.port, &conn, &accepted)?;
                     /*
                     if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
                         conn_result
@@ -361,10 +464,8 @@ impl SmtpConnection {
                 ret
             }
             SmtpSecurity::None => {
-                let addr = lookup_ipv4(path, server_conf.port)?;
                 let mut ret = AsyncWrapper::new(Connection::Tcp(
-                    TcpStream::connect_timeout(&addr, std::time::Duration::new(4, 0))
-                        .chain_err_kind(crate::error::ErrorKind::Network)?,
+                    tcp_connect(proxy.as_ref(), path, server_conf.port).await?,
                 ))
                 .chain_err_kind(crate::error::ErrorKind::Network)?;
                 res.clear();
@@ -444,35 +545,21 @@ impl SmtpConnection {
                     username,
                     password,
                     auth_type,
+                    password_cache_timeout,
+                    password_cache,
                     ..
                 } => {
-                    let password = match password {
-                        Password::Raw(p) => p.as_bytes().to_vec(),
-                        Password::CommandEval(command) => {
-                            let _command = command.clone();
-
-                            let mut output = unblock(move || {
-                                Command::new("sh")
-                                    .args(&["-c", &_command])
-                                    .stdin(std::process::Stdio::piped())
-                                    .stdout(std::process::Stdio::piped())
-                                    .stderr(std::process::Stdio::piped())
-                                    .output()
-                            })
-                            .await?;
-                            if !output.status.success() {
-                                return Err(MeliError::new(format!(
-                                    "SMTP password evaluation command `{}` returned {}: {}",
-                                    command,
-                                    output.status,
-                                    String::from_utf8_lossy(&output.stderr)
-                                )));
-                            }
-                            if output.stdout.ends_with(b"\n") {
-                                output.stdout.pop();
-                            }
-                            output.stdout
-                        }
+                    let password_cache = password_cache.clone();
+                    let password = {
+                        let source = password.source();
+                        let timeout = Some(std::time::Duration::from_secs(
+                            password_cache_timeout.unwrap_or(0),
+                        ));
+                        let password_cache = password_cache.clone();
+                        unblock(move || password_cache.get_or_fetch(timeout, || source.fetch()))
+                            .await
+                            .chain_err_summary(|| "Could not get SMTP password")?
+                            .into_bytes()
                     };
                     if auth_type.login {
                         let username = username.to_string();
@@ -506,9 +593,11 @@ impl SmtpConnection {
                         ret.send_command(&[b"AUTH PLAIN ", username_password.as_bytes()])
                             .await?;
                     }
-                    ret.read_lines(&mut res, Some((ReplyCode::_235, &[])))
-                        .await
-                        .chain_err_kind(crate::error::ErrorKind::Authentication)?;
+                    if let Err(err) = ret.read_lines(&mut res, Some((ReplyCode::_235, &[]))).await {
+                        /* Ask for the password again next time. */
+                        password_cache.clear();
+                        return Err(err.set_kind(crate::error::ErrorKind::Authentication));
+                    }
                     ret.send_command(&[b"EHLO meli.delivery"]).await?;
                 }
                 SmtpAuth::XOAuth2 { token_command, .. } => {
@@ -651,7 +740,8 @@ impl SmtpConnection {
         //return a reply indicating whether the failure is permanent (i.e., will occur again if
         //the client tries to send the same address again) or temporary (i.e., the address might
         //be accepted if the client tries again later).
-        for addr in tos {
+        //Address groups are not valid forward-paths, so their members are sent instead.
+        for addr in tos.iter().flat_map(|addr| addr.mailboxes()) {
             current_command.clear();
             current_command.push(b"RCPT TO:<");
             current_command.push(addr.address_spec_raw().trim());
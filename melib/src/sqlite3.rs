@@ -19,7 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use crate::{error::*, logging::log, Envelope};
+use crate::{error::*, logging::log_target, Envelope};
 use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput};
 pub use rusqlite::{self, params, Connection};
 use std::path::PathBuf;
@@ -31,12 +31,14 @@ pub struct DatabaseDescription {
     pub version: u32,
 }
 
-pub fn db_path(name: &str) -> Result<PathBuf> {
-    let data_dir =
-        xdg::BaseDirectories::with_prefix("meli").map_err(|e| MeliError::new(e.to_string()))?;
-    Ok(data_dir
-        .place_data_file(name)
-        .map_err(|e| MeliError::new(e.to_string()))?)
+/// The path of database `name`, of account `identifier` or global.
+pub fn db_path(name: &str, identifier: Option<&str>) -> Result<PathBuf> {
+    match identifier {
+        Some(id) => {
+            crate::paths::moved_state_file(Some(id), name, None, &format!("{}_{}", id, name))
+        }
+        None => crate::paths::state_file(None, name),
+    }
 }
 
 pub fn open_db(db_path: PathBuf) -> Result<Connection> {
@@ -52,14 +54,11 @@ pub fn open_or_create_db(
 ) -> Result<Connection> {
     let mut second_try: bool = false;
     loop {
-        let db_path = if let Some(id) = identifier {
-            db_path(&format!("{}_{}", id, description.name))
-        } else {
-            db_path(description.name)
-        }?;
+        let db_path = db_path(description.name, identifier)?;
         let mut set_mode = false;
         if !db_path.exists() {
-            log(
+            log_target(
+                "sqlite3",
                 format!(
                     "Creating {} database in {}",
                     description.name,
@@ -81,7 +80,8 @@ pub fn open_or_create_db(
         }
         let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
         if version != 0_i32 && version as u32 != description.version {
-            log(
+            log_target(
+                "sqlite3",
                 format!(
                     "Database version mismatch, is {} but expected {}",
                     version, description.version
@@ -113,15 +113,12 @@ pub fn open_or_create_db(
 
 /// Return database to a clean slate.
 pub fn reset_db(description: &DatabaseDescription, identifier: Option<&str>) -> Result<()> {
-    let db_path = if let Some(id) = identifier {
-        db_path(&format!("{}_{}", id, description.name))
-    } else {
-        db_path(description.name)
-    }?;
+    let db_path = db_path(description.name, identifier)?;
     if !db_path.exists() {
         return Ok(());
     }
-    log(
+    log_target(
+        "sqlite3",
         format!(
             "Resetting {} database in {}",
             description.name,
@@ -31,6 +31,7 @@
 
 use super::types::Reflow;
 use super::wcwidth::{wcwidth, CodePointsIter};
+use std::borrow::Cow;
 extern crate unicode_segmentation;
 use self::unicode_segmentation::UnicodeSegmentation;
 
@@ -67,6 +68,10 @@ pub trait TextProcessing: UnicodeSegmentation + CodePointsIter {
     fn split_lines(&self, width: usize) -> Vec<String>;
 
     fn split_lines_reflow(&self, reflow: Reflow, width: Option<usize>) -> Vec<String>;
+
+    /// Shorten to at most `width` columns without splitting grapheme clusters, ending with "…"
+    /// if anything was cut.
+    fn trim_with_ellipsis(&self, width: usize) -> Cow<'_, str>;
 }
 
 impl TextProcessing for str {
@@ -83,6 +88,32 @@ impl TextProcessing for str {
         }
         super::line_break::split_lines_reflow(self, reflow, width)
     }
+
+    fn trim_with_ellipsis(&self, width: usize) -> Cow<'_, str> {
+        use super::Truncate;
+        if self.grapheme_width() <= width {
+            return Cow::Borrowed(self);
+        }
+        if width == 0 {
+            return Cow::Borrowed("");
+        }
+        Cow::Owned(format!("{}…", self.trim_at_width(width - 1)))
+    }
+}
+
+#[test]
+fn test_trim_with_ellipsis() {
+    assert_eq!("abc".trim_with_ellipsis(3), "abc");
+    assert_eq!("abcd".trim_with_ellipsis(3), "ab…");
+    assert_eq!("abcd".trim_with_ellipsis(0), "");
+    assert_eq!("日本語".trim_with_ellipsis(4), "日…");
+    assert_eq!("日本語".trim_with_ellipsis(5), "日本…");
+    assert_eq!(
+        "e\u{301}e\u{301}e\u{301}".trim_with_ellipsis(2),
+        "e\u{301}…"
+    );
+    let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    assert_eq!(format!("{}{}", family, family).trim_with_ellipsis(4), "…");
 }
 
 pub struct WordBreakIter<'s> {
@@ -1435,6 +1435,39 @@ impl LineBreakText {
         self
     }
 
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The index in the text where the next line starts, if the lines so far end at a line of
+    /// the text, so that `seek` to it gives the same lines that follow.
+    pub fn position(&self) -> Option<usize> {
+        if !self.paragraph.is_empty() {
+            return None;
+        }
+        match self.state {
+            ReflowState::ReflowNo { cur_index }
+            | ReflowState::ReflowAll { cur_index }
+            | ReflowState::ReflowFormatFlowed { cur_index }
+            | ReflowState::ReflowAllWidth {
+                width: _,
+                state: LineBreakTextState::AtLine { cur_index },
+            } => Some(cur_index),
+            ReflowState::ReflowAllWidth {
+                width: _,
+                state: LineBreakTextState::WithinLine { .. },
+            } => None,
+        }
+    }
+
+    /// Continue breaking lines from `index`, a value `position` returned.
+    pub fn seek(&mut self, index: usize) -> &mut Self {
+        self.paragraph.clear();
+        self.state = ReflowState::new(self.reflow, self.width, index);
+        self.paragraph_start_index = index;
+        self
+    }
+
     pub fn is_finished(&self) -> bool {
         match self.state {
             ReflowState::ReflowNo { cur_index }
@@ -1744,8 +1777,8 @@ impl Iterator for LineBreakText {
             ReflowState::ReflowNo { ref mut cur_index }
             | ReflowState::ReflowAll { ref mut cur_index } => {
                 for line in self.text[*cur_index..].split('\n') {
-                    let ret = line.to_string();
-                    *cur_index += line.len() + 2;
+                    let ret = line.trim_end_matches('\r').to_string();
+                    *cur_index += line.len() + 1;
                     return Some(ret);
                 }
                 return None;
@@ -1796,3 +1829,39 @@ fn reflow_helper2(
         }
     }
 }
+
+#[test]
+fn test_line_break_text_seek() {
+    let text = "first line that is long enough to be broken\nsecond line\n> third line\nfourth";
+    for reflow in &[Reflow::All, Reflow::FormatFlowed] {
+        let lines =
+            LineBreakText::new(text.to_string(), *reflow, Some(20)).collect::<Vec<String>>();
+        let mut line_breaker = LineBreakText::new(text.to_string(), *reflow, Some(20));
+        let mut positions = vec![];
+        for i in 0..lines.len() {
+            if let Some(index) = line_breaker.position() {
+                positions.push((i, index));
+            }
+            line_breaker.next();
+        }
+        assert!(positions.len() > 1);
+        for (i, index) in positions {
+            assert_eq!(
+                line_breaker.seek(index).collect::<Vec<String>>(),
+                &lines[i..],
+                "{:?}",
+                reflow
+            );
+        }
+    }
+}
+
+#[test]
+fn test_line_break_text_no_reflow() {
+    let long_line = "word ".repeat(30);
+    let text = format!("first\r\n{}\n\nlast", long_line);
+    assert_eq!(
+        LineBreakText::new(text, Reflow::No, Some(20)).collect::<Vec<String>>(),
+        vec!["first", &long_line, "", "last"]
+    );
+}
@@ -30,11 +30,39 @@ pub use grapheme_clusters::*;
 pub use line_break::*;
 pub use wcwidth::*;
 
+/// Shorten strings without splitting grapheme clusters. The `*_boundary` methods count grapheme
+/// clusters, the `*_width` methods count terminal columns.
 pub trait Truncate {
     fn truncate_at_boundary(&mut self, new_len: usize);
     fn trim_at_boundary(&self, new_len: usize) -> &str;
     fn trim_left_at_boundary(&self, new_len: usize) -> &str;
     fn truncate_left_at_boundary(&mut self, new_len: usize);
+    fn truncate_at_width(&mut self, width: usize);
+    fn trim_at_width(&self, width: usize) -> &str;
+}
+
+/// Byte index of the `n`th grapheme cluster of `s`, or `None` if it has `n` or fewer.
+fn nth_grapheme_index(s: &str, n: usize) -> Option<usize> {
+    extern crate unicode_segmentation;
+    use unicode_segmentation::UnicodeSegmentation;
+    UnicodeSegmentation::grapheme_indices(s, true)
+        .nth(n)
+        .map(|(idx, _)| idx)
+}
+
+/// Byte length of the longest prefix of `s` that is made of whole grapheme clusters and is at
+/// most `width` columns wide.
+fn width_boundary(s: &str, width: usize) -> usize {
+    extern crate unicode_segmentation;
+    use unicode_segmentation::UnicodeSegmentation;
+    let mut total = 0;
+    for (idx, g) in UnicodeSegmentation::grapheme_indices(s, true) {
+        total += g.grapheme_width();
+        if total > width {
+            return idx;
+        }
+    }
+    s.len()
 }
 
 impl Truncate for &str {
@@ -42,14 +70,8 @@ impl Truncate for &str {
         if new_len >= self.len() {
             return;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((last, _)) = UnicodeSegmentation::grapheme_indices(*self, true)
-            .take(new_len)
-            .last()
-        {
-            *self = &self[..last];
+        if let Some(idx) = nth_grapheme_index(self, new_len) {
+            *self = &self[..idx];
         }
     }
 
@@ -57,16 +79,9 @@ impl Truncate for &str {
         if new_len >= self.len() {
             return self;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((last, _)) = UnicodeSegmentation::grapheme_indices(*self, true)
-            .take(new_len)
-            .last()
-        {
-            &self[..last]
-        } else {
-            self
+        match nth_grapheme_index(self, new_len) {
+            Some(idx) => &self[..idx],
+            None => self,
         }
     }
 
@@ -74,16 +89,9 @@ impl Truncate for &str {
         if skip_len >= self.len() {
             return "";
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((first, _)) = UnicodeSegmentation::grapheme_indices(*self, true)
-            .skip(skip_len)
-            .next()
-        {
-            &self[first..]
-        } else {
-            self
+        match nth_grapheme_index(self, skip_len) {
+            Some(idx) => &self[idx..],
+            None => "",
         }
     }
 
@@ -92,16 +100,19 @@ impl Truncate for &str {
             *self = "";
             return;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((first, _)) = UnicodeSegmentation::grapheme_indices(*self, true)
-            .skip(skip_len)
-            .next()
-        {
-            *self = &self[first..];
+        match nth_grapheme_index(self, skip_len) {
+            Some(idx) => *self = &self[idx..],
+            None => *self = "",
         }
     }
+
+    fn truncate_at_width(&mut self, width: usize) {
+        *self = &self[..width_boundary(self, width)];
+    }
+
+    fn trim_at_width(&self, width: usize) -> &str {
+        &self[..width_boundary(self, width)]
+    }
 }
 
 impl Truncate for String {
@@ -109,14 +120,8 @@ impl Truncate for String {
         if new_len >= self.len() {
             return;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((last, _)) = UnicodeSegmentation::grapheme_indices(self.as_str(), true)
-            .take(new_len)
-            .last()
-        {
-            String::truncate(self, last);
+        if let Some(idx) = nth_grapheme_index(self, new_len) {
+            String::truncate(self, idx);
         }
     }
 
@@ -124,16 +129,9 @@ impl Truncate for String {
         if new_len >= self.len() {
             return self;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((last, _)) = UnicodeSegmentation::grapheme_indices(self.as_str(), true)
-            .take(new_len)
-            .last()
-        {
-            &self[..last]
-        } else {
-            self.as_str()
+        match nth_grapheme_index(self, new_len) {
+            Some(idx) => &self[..idx],
+            None => self,
         }
     }
 
@@ -141,16 +139,9 @@ impl Truncate for String {
         if skip_len >= self.len() {
             return "";
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((first, _)) = UnicodeSegmentation::grapheme_indices(self.as_str(), true)
-            .skip(skip_len)
-            .next()
-        {
-            &self[first..]
-        } else {
-            self.as_str()
+        match nth_grapheme_index(self, skip_len) {
+            Some(idx) => &self[idx..],
+            None => "",
         }
     }
 
@@ -159,23 +150,172 @@ impl Truncate for String {
             self.clear();
             return;
         }
-
-        extern crate unicode_segmentation;
-        use unicode_segmentation::UnicodeSegmentation;
-        if let Some((first, _)) = UnicodeSegmentation::grapheme_indices(self.as_str(), true)
-            .skip(skip_len)
-            .next()
-        {
-            *self = self[first..].to_string();
+        match nth_grapheme_index(self, skip_len) {
+            Some(idx) => {
+                self.drain(..idx);
+            }
+            None => self.clear(),
         }
     }
+
+    fn truncate_at_width(&mut self, width: usize) {
+        let idx = width_boundary(self, width);
+        String::truncate(self, idx);
+    }
+
+    fn trim_at_width(&self, width: usize) -> &str {
+        &self[..width_boundary(self, width)]
+    }
+}
+
+#[test]
+fn test_truncate() {
+    let mut s = "abcdef".to_string();
+    s.truncate_at_boundary(3);
+    assert_eq!(s, "abc");
+    assert_eq!("abcdef".trim_at_boundary(10), "abcdef");
+    assert_eq!("abcdef".trim_left_at_boundary(4), "ef");
+    assert_eq!("ab".trim_left_at_boundary(1), "b");
+    assert_eq!("日本語".trim_left_at_boundary(3), "");
+    let mut s = "日本語のテキスト".to_string();
+    s.truncate_left_at_boundary(4);
+    assert_eq!(s, "テキスト");
+
+    /* CJK characters are two columns wide. */
+    assert_eq!("日本語".trim_at_boundary(2), "日本");
+    assert_eq!("日本語".trim_at_width(5), "日本");
+    assert_eq!("日本語".trim_at_width(6), "日本語");
+    let mut s = "日本語";
+    s.truncate_at_width(1);
+    assert_eq!(s, "");
+
+    /* Combining marks are kept with their base character. */
+    let combining = "e\u{301}e\u{301}e\u{301}";
+    assert_eq!(combining.trim_at_boundary(2), "e\u{301}e\u{301}");
+    assert_eq!(combining.trim_at_width(2), "e\u{301}e\u{301}");
+
+    /* Emoji ZWJ sequences are never split. */
+    let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    let mut s = format!("a{}b", family);
+    s.truncate_at_boundary(2);
+    assert_eq!(s, format!("a{}", family));
+    assert_eq!(format!("a{}b", family).trim_at_width(3), "a");
+    assert_eq!(
+        format!("a{}b", family).trim_at_width(family.grapheme_width() + 1),
+        format!("a{}", family)
+    );
 }
 
+/// Shell-style pattern matching on grapheme clusters. `*` matches any sequence, including `/`,
+/// `?` matches exactly one grapheme, `[abc]`, `[a-z]` and `[!abc]` (or `[^abc]`) match one of (or
+/// none of) a set, and `\` matches the next character literally. A trailing `/` on either side is
+/// ignored.
 pub trait GlobMatch {
     fn matches_glob(&self, s: &str) -> bool;
     fn is_glob(&self) -> bool;
 }
 
+#[derive(Debug, PartialEq)]
+enum GlobToken<'p> {
+    Literal(&'p str),
+    AnyOne,
+    AnySequence,
+    Class {
+        negated: bool,
+        members: Vec<ClassMember<'p>>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum ClassMember<'p> {
+    Single(&'p str),
+    Range(char, char),
+}
+
+impl GlobToken<'_> {
+    fn matches(&self, g: &str) -> bool {
+        match self {
+            GlobToken::Literal(l) => *l == g,
+            GlobToken::AnyOne => true,
+            GlobToken::AnySequence => false,
+            GlobToken::Class { negated, members } => {
+                let mut chars = g.chars();
+                let single = match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                };
+                let found = members.iter().any(|m| match m {
+                    ClassMember::Single(s) => *s == g,
+                    ClassMember::Range(from, to) => {
+                        single.map(|c| *from <= c && c <= *to).unwrap_or(false)
+                    }
+                });
+                found != *negated
+            }
+        }
+    }
+}
+
+fn first_char(g: &str) -> char {
+    g.chars().next().unwrap_or_default()
+}
+
+/// Split `pattern`'s graphemes into tokens. An unclosed `[` and a trailing `\` are literals.
+fn glob_tokens<'p>(pattern: &[&'p str]) -> Vec<GlobToken<'p>> {
+    let mut ret = Vec::with_capacity(pattern.len());
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            "*" => {
+                /* Consecutive stars are equivalent to one. */
+                if ret.last() != Some(&GlobToken::AnySequence) {
+                    ret.push(GlobToken::AnySequence);
+                }
+            }
+            "?" => ret.push(GlobToken::AnyOne),
+            "\\" if i + 1 < pattern.len() => {
+                i += 1;
+                ret.push(GlobToken::Literal(pattern[i]));
+            }
+            "[" => {
+                let mut j = i + 1;
+                let negated = matches!(pattern.get(j), Some(&"!") | Some(&"^"));
+                if negated {
+                    j += 1;
+                }
+                let mut members = vec![];
+                /* A `]` right after the opening bracket is a member. */
+                let mut first = true;
+                while j < pattern.len() && (first || pattern[j] != "]") {
+                    first = false;
+                    if pattern.get(j + 1) == Some(&"-")
+                        && j + 2 < pattern.len()
+                        && pattern[j + 2] != "]"
+                    {
+                        members.push(ClassMember::Range(
+                            first_char(pattern[j]),
+                            first_char(pattern[j + 2]),
+                        ));
+                        j += 3;
+                    } else {
+                        members.push(ClassMember::Single(pattern[j]));
+                        j += 1;
+                    }
+                }
+                if j < pattern.len() {
+                    ret.push(GlobToken::Class { negated, members });
+                    i = j;
+                } else {
+                    ret.push(GlobToken::Literal("["));
+                }
+            }
+            g => ret.push(GlobToken::Literal(g)),
+        }
+        i += 1;
+    }
+    ret
+}
+
 impl GlobMatch for str {
     fn matches_glob(&self, _pattern: &str) -> bool {
         macro_rules! strip_slash {
@@ -188,6 +328,7 @@ impl GlobMatch for str {
             };
         }
         let pattern: Vec<&str> = strip_slash!(_pattern).split_graphemes();
+        let pattern = glob_tokens(&pattern);
         let s: Vec<&str> = strip_slash!(self).split_graphemes();
 
         // Taken from https://research.swtch.com/glob
@@ -199,14 +340,7 @@ impl GlobMatch for str {
         while px < pattern.len() || sx < s.len() {
             if px < pattern.len() {
                 match pattern[px] {
-                    "?" => {
-                        if sx < s.len() {
-                            px += 1;
-                            sx += 1;
-                            continue;
-                        }
-                    }
-                    "*" => {
+                    GlobToken::AnySequence => {
                         // Try to match at sx.
                         // If that doesn't work out,
                         // restart at sx+1 next.
@@ -215,8 +349,8 @@ impl GlobMatch for str {
                         px += 1;
                         continue;
                     }
-                    p => {
-                        if sx < s.len() && s[sx] == p {
+                    ref p => {
+                        if sx < s.len() && p.matches(s[sx]) {
                             px += 1;
                             sx += 1;
                             continue;
@@ -236,7 +370,9 @@ impl GlobMatch for str {
     }
 
     fn is_glob(&self) -> bool {
-        self.contains('*')
+        glob_tokens(&self.split_graphemes())
+            .iter()
+            .any(|t| !matches!(t, GlobToken::Literal(_)))
     }
 }
 
@@ -257,6 +393,34 @@ fn test_globmatch() {
     assert!("INBOX/Archives/2047".matches_glob("INBOX/Archives/204?"));
 
     assert!(!"INBOX/Lists/".matches_glob("INBOX/Lists/*"));
+
+    assert!("INBOX".matches_glob("INB?X"));
+    assert!(!"INBOX".matches_glob("INBOX?"));
+    assert!("Lists/rust".matches_glob("Lists/[a-z]*"));
+    assert!(!"Lists/Rust".matches_glob("Lists/[a-z]*"));
+    assert!("Lists/Rust".matches_glob("Lists/[!a-z]*"));
+    assert!("Lists/Rust".matches_glob("Lists/[^a-z]*"));
+    assert!("2047".matches_glob("20[0-9][0-9]"));
+    assert!("a]".matches_glob("a[]]"));
+    assert!("a-".matches_glob("a[x-]"));
+    assert!("[a".matches_glob("[a"));
+    assert!("a*".matches_glob("a\\*"));
+    assert!(!"ab".matches_glob("a\\*"));
+    assert!("a**b".matches_glob("a**b"));
+
+    /* Multi-byte characters and grapheme clusters. */
+    assert!("Κοινόχρηστα".matches_glob("Κοιν?χρηστα"));
+    assert!("Κοινόχρηστα".matches_glob("Κ*α"));
+    assert!("Mailbox ñ".matches_glob("Mailbox [ñn]"));
+    assert!("Mailbox n\u{303}".matches_glob("Mailbox ?"));
+    assert!("日本語".matches_glob("日[本]*"));
+    assert!("文書".matches_glob("[一-龥][一-龥]"));
+
+    assert!("*".is_glob());
+    assert!("a?".is_glob());
+    assert!("[ab]".is_glob());
+    assert!(!"INBOX".is_glob());
+    assert!(!"a\\*".is_glob());
 }
 
 pub const _ALICE_CHAPTER_1: &str = r#"CHAPTER I. Down the Rabbit-Hole
@@ -40,8 +40,7 @@ use crate::email::*;
 mod iterators;
 pub use iterators::*;
 
-#[cfg(feature = "unicode_algorithms")]
-use crate::text_processing::grapheme_clusters::*;
+use crate::collation::Collator;
 use uuid::Uuid;
 
 use std::cmp::Ordering;
@@ -115,63 +114,6 @@ macro_rules! remove_from_parent {
     }};
 }
 
-macro_rules! make {
-    (($p:expr)parent of($c:expr), $threads:expr) => {{
-        let old_group_hash = $threads.find_group($threads.thread_nodes[&$c].group);
-        let parent_group_hash = $threads.find_group($threads.thread_nodes[&$p].group);
-        if old_group_hash != parent_group_hash {
-            let prev_parent = remove_from_parent!(&mut $threads.thread_nodes, $c);
-            if !($threads.thread_nodes[&$p]).children.contains(&$c) {
-                /* Pruned nodes keep their children in case they show up in a later merge, so do not panic
-                 * if children exists */
-                $threads.thread_nodes.entry($p).and_modify(|e| e.children.push($c));
-            }
-            $threads.thread_nodes.entry($c).and_modify(|e| {
-                e.parent = Some($p);
-            });
-            let old_group = std::mem::replace($threads.groups.entry(old_group_hash).or_default(), ThreadGroup::Node {
-                parent: Arc::new(RwLock::new(parent_group_hash)),
-            });
-            $threads.thread_nodes.entry($c).and_modify(|e| {
-                e.group = parent_group_hash;
-            });
-            $threads.thread_nodes.entry($p).and_modify(|e| {
-                e.group = parent_group_hash;
-            });
-            {
-                let parent_group = $threads.thread_ref_mut(parent_group_hash);
-                match (parent_group, old_group) {
-                    (Thread {
-                        ref mut date,
-                        ref mut len,
-                        ref mut unseen,
-                        ref mut snoozed,
-                        ref mut attachments,
-                        ..
-                    }, ThreadGroup::Root(Thread {
-                        date: old_date,
-                        len: old_len,
-                        unseen: old_unseen,
-                        snoozed: old_snoozed,
-                        attachments: old_attachments,
-                        ..
-                    })) => {
-                        *date = std::cmp::max(old_date, *date);
-                        *len += old_len;
-                        *unseen += old_unseen;
-                        *attachments += old_attachments;
-                        *snoozed |= old_snoozed;
-                    }
-                    _ => unreachable!(),
-                 }
-            }
-            prev_parent
-        } else {
-            None
-        }
-        }};
-}
-
 /* Strip common prefixes from subjects */
 trait SubjectPrefix {
     fn is_a_reply(&self) -> bool;
@@ -241,6 +183,8 @@ pub enum SortOrder {
 pub enum SortField {
     Subject,
     Date,
+    /// The name, or else the address, of the first sender.
+    From,
 }
 
 impl Default for SortField {
@@ -261,11 +205,31 @@ impl FromStr for SortField {
         match s.trim() {
             "subject" | "s" | "sub" | "sbj" | "subj" => Ok(SortField::Subject),
             "date" | "d" => Ok(SortField::Date),
+            "from" | "f" | "sender" => Ok(SortField::From),
             _ => Err(()),
         }
     }
 }
 
+impl SortField {
+    /// Compare two envelopes by this field, in ascending order.
+    pub fn compare(self, a: &Envelope, b: &Envelope, collator: &Collator) -> Ordering {
+        match self {
+            SortField::Date => a.date().cmp(&b.date()),
+            SortField::Subject => collator.compare(&a.subject(), &b.subject()),
+            SortField::From => {
+                let sender = |e: &Envelope| {
+                    e.from()
+                        .first()
+                        .map(|a| a.get_display_name().unwrap_or_else(|| a.get_email()))
+                        .unwrap_or_default()
+                };
+                collator.compare(&sender(a), &sender(b))
+            }
+        }
+    }
+}
+
 impl FromStr for SortOrder {
     type Err = ();
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
@@ -286,6 +250,7 @@ pub struct Thread {
     pub attachments: usize,
 
     pub snoozed: bool,
+    pub watched: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -323,6 +288,7 @@ impl Thread {
     property!(len: usize);
     property!(unseen: usize);
     property!(snoozed: bool);
+    property!(watched: bool);
     property!(date: UnixTimestamp);
 
     pub fn has_attachments(&self) -> bool {
@@ -332,6 +298,10 @@ impl Thread {
     pub fn set_snoozed(&mut self, val: bool) {
         self.snoozed = val;
     }
+
+    pub fn set_watched(&mut self, val: bool) {
+        self.watched = val;
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -436,6 +406,12 @@ impl Threads {
         self.thread_ref(self.thread_nodes[&h].group).snoozed()
     }
 
+    /// Find the node of the message with the raw `message_id`. The node might be a placeholder
+    /// for a message that is not in the mailbox.
+    pub fn find_message_id(&self, message_id: &[u8]) -> Option<ThreadNodeHash> {
+        self.message_ids.get(message_id).cloned()
+    }
+
     pub fn thread_ref(&self, h: ThreadHash) -> &Thread {
         match self.groups[&self.find_group(h)] {
             ThreadGroup::Root(ref root) => root,
@@ -451,19 +427,33 @@ impl Threads {
     }
 
     pub fn find_group(&self, h: ThreadHash) -> ThreadHash {
-        let p = match self.groups[&h] {
-            ThreadGroup::Root(_) => return h,
-            ThreadGroup::Node { ref parent } => *parent.read().unwrap(),
-        };
-
-        let parent_group = self.find_group(p);
-        match self.groups[&h] {
-            ThreadGroup::Node { ref parent } => {
-                *parent.write().unwrap() = parent_group;
-            }
-            _ => unreachable!(),
+        /* Walk up to the root group, then point every group on the way straight at it. Merges
+         * can chain as many groups as there are messages, so this must not recurse. */
+        let mut root = h;
+        while let ThreadGroup::Node { ref parent } = self.groups[&root] {
+            root = *parent.read().unwrap();
+        }
+        let mut g = h;
+        while let ThreadGroup::Node { ref parent } = self.groups[&g] {
+            g = std::mem::replace(&mut *parent.write().unwrap(), root);
         }
-        parent_group
+        root
+    }
+
+    /// The ancestors of `h`, closest first. The walk stops at a node it has already visited, so
+    /// that it ends even if the parent links ever form a loop.
+    fn ancestors(&self, h: ThreadNodeHash) -> impl Iterator<Item = ThreadNodeHash> + '_ {
+        let mut visited: HashSet<ThreadNodeHash> = HashSet::default();
+        visited.insert(h);
+        std::iter::successors(self.thread_nodes[&h].parent, move |p| {
+            self.thread_nodes[p].parent
+        })
+        .take_while(move |p| visited.insert(*p))
+    }
+
+    /// The topmost ancestor of `h`, or `h` itself.
+    fn top(&self, h: ThreadNodeHash) -> ThreadNodeHash {
+        self.ancestors(h).last().unwrap_or(h)
     }
 
     pub fn new(length: usize) -> Threads {
@@ -654,11 +644,35 @@ impl Threads {
         env_hash: EnvelopeHash,
         other_mailbox: bool,
     ) -> bool {
+        /* "If there's already a message with this Message-ID, treat this one as if it had a
+         * unique made-up ID": give it a node of its own that isn't in `message_ids`. */
+        let mut duplicate = false;
         {
             let envelopes_lck = envelopes.read().unwrap();
             let message_id = envelopes_lck[&env_hash].message_id().raw();
             if self.message_ids.contains_key(message_id)
                 && !self.missing_message_ids.contains(message_id)
+                && self.thread_nodes[&self.message_ids[message_id]]
+                    .message
+                    .map(|h| {
+                        h != env_hash
+                            && envelopes_lck.contains_key(&h)
+                            && !(self.thread_nodes[&self.message_ids[message_id]].other_mailbox
+                                && !other_mailbox)
+                    })
+                    .unwrap_or(false)
+            {
+                if self
+                    .thread_nodes
+                    .get(&Self::duplicate_node(message_id, env_hash))
+                    .map(|n| n.message == Some(env_hash))
+                    .unwrap_or(false)
+                {
+                    return false;
+                }
+                duplicate = true;
+            } else if self.message_ids.contains_key(message_id)
+                && !self.missing_message_ids.contains(message_id)
             {
                 let thread_hash = self.message_ids[message_id];
                 let node = self.thread_nodes.entry(thread_hash).or_default();
@@ -686,34 +700,35 @@ impl Threads {
         let message_id = envelopes_lck[&env_hash].message_id().raw();
 
         if other_mailbox
-            && reply_to_id.is_none()
-            && !self.message_ids.contains_key(message_id)
-            && !envelopes_lck[&env_hash]
-                .references()
-                .iter()
-                .any(|r| self.message_ids.contains_key(r.raw()))
+            && (duplicate
+                || reply_to_id.is_none()
+                    && !self.message_ids.contains_key(message_id)
+                    && !envelopes_lck[&env_hash]
+                        .references()
+                        .iter()
+                        .any(|r| self.message_ids.contains_key(r.raw())))
         {
             return false;
         }
 
-        let new_id = self
-            .message_ids
-            .get(message_id)
-            .cloned()
-            .or(
-                if envelopes_lck[&env_hash].thread() != ThreadNodeHash::null() {
-                    Some(envelopes_lck[&env_hash].thread())
-                } else {
-                    None
-                },
-            )
-            .unwrap_or_else(|| ThreadNodeHash::from(message_id));
+        let new_id = if duplicate {
+            Self::duplicate_node(message_id, env_hash)
+        } else {
+            self.message_ids
+                .get(message_id)
+                .cloned()
+                .or(
+                    if envelopes_lck[&env_hash].thread() != ThreadNodeHash::null() {
+                        Some(envelopes_lck[&env_hash].thread())
+                    } else {
+                        None
+                    },
+                )
+                .unwrap_or_else(|| ThreadNodeHash::from(message_id))
+        };
         {
             let mut node = self.thread_nodes.entry(new_id).or_default();
             node.message = Some(env_hash);
-            if node.parent.is_none() {
-                node.parent = reply_to_id;
-            }
             node.other_mailbox = other_mailbox;
             node.date = envelopes_lck[&env_hash].date();
             node.unseen = !envelopes_lck[&env_hash].is_seen();
@@ -737,6 +752,7 @@ impl Threads {
                         0
                     },
                     snoozed: false,
+                    watched: false,
                 }),
             );
         } else {
@@ -755,84 +771,51 @@ impl Threads {
             };
         }
 
-        self.message_ids.insert(message_id.to_vec(), new_id);
-        self.message_ids_set.insert(message_id.to_vec());
-        self.missing_message_ids.remove(message_id);
+        if !duplicate {
+            self.message_ids.insert(message_id.to_vec(), new_id);
+            self.message_ids_set.insert(message_id.to_vec());
+            self.missing_message_ids.remove(message_id);
+        }
         self.hash_set.insert(env_hash);
-        if let Some(reply_to_id) = reply_to_id {
-            make!((reply_to_id) parent of (new_id), self);
-        } else if let Some(r) = envelopes_lck[&env_hash].in_reply_to().map(StrBuild::raw) {
-            let reply_to_id = ThreadNodeHash::from(r);
-            self.thread_nodes.insert(
-                reply_to_id,
-                ThreadNode {
-                    date: envelopes_lck[&env_hash].date(),
-                    ..ThreadNode::new()
-                },
-            );
-
-            self.groups.insert(
-                self.thread_nodes[&reply_to_id].group,
-                ThreadGroup::Root(Thread {
-                    root: reply_to_id,
-                    date: envelopes_lck[&env_hash].date(),
-                    len: 0,
-                    unseen: 0,
-                    attachments: 0,
-                    snoozed: false,
-                }),
-            );
-            make!((reply_to_id) parent of (new_id), self);
-            self.message_ids.insert(r.to_vec(), reply_to_id);
-            self.message_ids_set.insert(r.to_vec());
-            self.missing_message_ids.insert(r.to_vec());
+        /* "If the message has a References header, take the message IDs from it, in order. If
+         * it has an In-Reply-To header that isn't the last of them, append it." Some clients list
+         * references newest first, so reverse them if they start with In-Reply-To. */
+        let envelope = &envelopes_lck[&env_hash];
+        let in_reply_to = envelope.in_reply_to().map(StrBuild::raw);
+        let mut references: SmallVec<[&[u8]; 8]> = envelope
+            .references()
+            .into_iter()
+            .map(StrBuild::raw)
+            .collect();
+        if references.len() > 1 && references.first() == in_reply_to.as_ref() {
+            references.reverse();
         }
-
-        if envelopes_lck[&env_hash].references.is_some() {
-            let mut current_descendant_id = new_id;
-            let mut references = envelopes_lck[&env_hash].references();
-            if references.first() == envelopes_lck[&env_hash].in_reply_to().as_ref() {
-                references.reverse();
+        if let Some(in_reply_to) = in_reply_to {
+            if references.last() != Some(&in_reply_to) {
+                references.retain(|r| *r != in_reply_to);
+                references.push(in_reply_to);
             }
-
-            for reference in references.into_iter().rev() {
-                if let Some(&id) = self.message_ids.get(reference.raw()) {
-                    if self.thread_nodes[&id].date > self.thread_nodes[&current_descendant_id].date
-                        || self.thread_nodes[&current_descendant_id].parent.is_some()
-                    {
-                        current_descendant_id = id;
-                        continue;
-                    }
-                    make!((id) parent of (current_descendant_id), self);
-                    current_descendant_id = id;
-                } else {
-                    let id = ThreadNodeHash::from(reference.raw());
-                    self.thread_nodes.insert(
-                        id,
-                        ThreadNode {
-                            date: envelopes_lck[&env_hash].date(),
-                            ..ThreadNode::new()
-                        },
-                    );
-                    self.groups.insert(
-                        self.thread_nodes[&id].group,
-                        ThreadGroup::Root(Thread {
-                            root: id,
-                            date: envelopes_lck[&env_hash].date(),
-                            len: 0,
-                            unseen: 0,
-                            attachments: 0,
-                            snoozed: false,
-                        }),
-                    );
-                    make!((id) parent of (current_descendant_id), self);
-                    self.missing_message_ids.insert(reference.raw().to_vec());
-                    self.message_ids.insert(reference.raw().to_vec(), id);
-                    self.message_ids_set.insert(reference.raw().to_vec());
-                    current_descendant_id = id;
-                }
+        }
+        references.retain(|r| *r != message_id);
+
+        /* "For each element in the message's References field, find the container for it,
+         * creating an empty one if needed. Link the References together in order, unless they
+         * already have parents or that would introduce a loop. Then set the parent of this
+         * message to be the last element of References." */
+        let date = envelope.date();
+        let mut prev: Option<ThreadNodeHash> = None;
+        for reference in references {
+            let id = self.find_or_create_container(reference, date);
+            if let Some(prev) = prev {
+                self.link(prev, id, false, &envelopes_lck);
             }
+            prev = Some(id);
         }
+        if let Some(parent) = prev {
+            self.link(parent, new_id, true, &envelopes_lck);
+            self.prune_root(self.thread_nodes[&parent].group);
+        }
+        self.prune_root(self.thread_nodes[&new_id].group);
         drop(envelopes_lck);
         self.update_show_subject(new_id, env_hash, envelopes);
         envelopes
@@ -857,6 +840,171 @@ impl Threads {
         true
     }
 
+    /// The node of a message whose `message_id` is already taken by another message. It
+    /// depends on the envelope hash too, so that it's the same in the threads of every mailbox.
+    fn duplicate_node(message_id: &[u8], env_hash: EnvelopeHash) -> ThreadNodeHash {
+        let mut key = message_id.to_vec();
+        key.extend_from_slice(&env_hash.to_be_bytes());
+        ThreadNodeHash::from(key.as_slice())
+    }
+
+    /// The node of the message with the raw `message_id`, or a new empty one.
+    fn find_or_create_container(
+        &mut self,
+        message_id: &[u8],
+        date: UnixTimestamp,
+    ) -> ThreadNodeHash {
+        if let Some(&id) = self.message_ids.get(message_id) {
+            return id;
+        }
+        let id = ThreadNodeHash::from(message_id);
+        let node = ThreadNode {
+            date,
+            ..ThreadNode::new()
+        };
+        self.groups.insert(
+            node.group,
+            ThreadGroup::Root(Thread {
+                root: id,
+                date,
+                len: 0,
+                unseen: 0,
+                attachments: 0,
+                snoozed: false,
+                watched: false,
+            }),
+        );
+        self.thread_nodes.insert(id, node);
+        self.message_ids.insert(message_id.to_vec(), id);
+        self.message_ids_set.insert(message_id.to_vec());
+        self.missing_message_ids.insert(message_id.to_vec());
+        id
+    }
+
+    /// Make `parent` the parent of `child`, merging their threads. A child that already has a
+    /// parent is only moved if `force` is set, which is the case for the parent a message names
+    /// itself. Links that would introduce a loop are ignored.
+    fn link(
+        &mut self,
+        parent: ThreadNodeHash,
+        child: ThreadNodeHash,
+        force: bool,
+        envelopes: &HashMap<EnvelopeHash, Envelope>,
+    ) {
+        if parent == child
+            || self.thread_nodes[&child].parent == Some(parent)
+            || (self.thread_nodes[&child].parent.is_some() && !force)
+        {
+            return;
+        }
+        if self.ancestors(parent).any(|a| a == child) {
+            return;
+        }
+        let old_parent = remove_from_parent!(&mut self.thread_nodes, child);
+        self.thread_nodes
+            .entry(parent)
+            .and_modify(|e| e.children.push(child));
+        self.thread_nodes
+            .entry(child)
+            .and_modify(|e| e.parent = Some(parent));
+        let parent_group = self.find_group(self.thread_nodes[&parent].group);
+        let child_group = self.find_group(self.thread_nodes[&child].group);
+        if parent_group != child_group {
+            self.merge_groups(parent_group, child_group);
+        }
+        if old_parent.is_some() {
+            /* The rest of the child's old thread is not connected to it anymore. */
+            self.split_group(parent_group, envelopes);
+        }
+    }
+
+    /// Merge the thread group `child_group` into `parent_group`.
+    fn merge_groups(&mut self, parent_group: ThreadHash, child_group: ThreadHash) {
+        let old_group = std::mem::replace(
+            self.groups.entry(child_group).or_default(),
+            ThreadGroup::Node {
+                parent: Arc::new(RwLock::new(parent_group)),
+            },
+        );
+        let old = match old_group {
+            ThreadGroup::Root(old) => old,
+            ThreadGroup::Node { .. } => unreachable!(),
+        };
+        let thread = self.thread_ref_mut(parent_group);
+        thread.date = std::cmp::max(old.date, thread.date);
+        thread.len += old.len;
+        thread.unseen += old.unseen;
+        thread.attachments += old.attachments;
+        thread.snoozed |= old.snoozed;
+        thread.watched |= old.watched;
+    }
+
+    /// Split the thread group `group` into one group per tree after a node was moved out of a
+    /// tree. The tree of the group's root keeps the group.
+    fn split_group(&mut self, group: ThreadHash, envelopes: &HashMap<EnvelopeHash, Envelope>) {
+        let members: Vec<ThreadNodeHash> = self
+            .thread_nodes
+            .iter()
+            .filter(|(_, n)| self.find_group(n.group) == group)
+            .map(|(h, _)| *h)
+            .collect();
+        let old = self.thread_ref(group).clone();
+        let kept_root = self.top(old.root);
+        let mut trees: HashMap<ThreadNodeHash, Vec<ThreadNodeHash>> = HashMap::default();
+        for h in members {
+            trees.entry(self.top(h)).or_default().push(h);
+        }
+        for (root, nodes) in trees {
+            let group = if root == kept_root {
+                group
+            } else {
+                ThreadHash::new()
+            };
+            let mut thread = Thread {
+                root,
+                date: self.thread_nodes[&root].date,
+                len: 0,
+                unseen: 0,
+                attachments: 0,
+                snoozed: old.snoozed,
+                watched: old.watched,
+            };
+            for h in nodes {
+                let node = self.thread_nodes.get_mut(&h).unwrap();
+                node.group = group;
+                if let Some(env_hash) = node.message {
+                    thread.len += 1;
+                    thread.date = std::cmp::max(thread.date, node.date);
+                    if node.unseen {
+                        thread.unseen += 1;
+                    }
+                    if envelopes
+                        .get(&env_hash)
+                        .map(Envelope::has_attachments)
+                        .unwrap_or(false)
+                    {
+                        thread.attachments += 1;
+                    }
+                }
+            }
+            self.groups.insert(group, ThreadGroup::Root(thread));
+            self.prune_root(group);
+        }
+    }
+
+    /// "Prune empty containers": the root of a thread is the topmost node, skipping empty nodes
+    /// with a single child.
+    fn prune_root(&mut self, group: ThreadHash) {
+        let group = self.find_group(group);
+        let mut root = self.top(self.thread_ref(group).root);
+        while self.thread_nodes[&root].message.is_none()
+            && self.thread_nodes[&root].children.len() == 1
+        {
+            root = self.thread_nodes[&root].children[0];
+        }
+        self.thread_ref_mut(group).root = root;
+    }
+
     /* Insert or update */
     pub fn insert_reply(&mut self, envelopes: &Envelopes, env_hash: EnvelopeHash) -> bool {
         self.insert_internal(envelopes, env_hash, true)
@@ -928,11 +1076,33 @@ impl Threads {
         */
     }
 
+    /// Compare the messages of two nodes by `field`, in ascending order. Nodes without a
+    /// message come first.
+    fn compare_nodes(
+        &self,
+        a: ThreadNodeHash,
+        b: ThreadNodeHash,
+        field: SortField,
+        envelopes: &HashMap<EnvelopeHash, Envelope>,
+        collator: &Collator,
+    ) -> Ordering {
+        match (
+            self.thread_nodes[&a].message(),
+            self.thread_nodes[&b].message(),
+        ) {
+            (Some(a), Some(b)) => field.compare(&envelopes[&a], &envelopes[&b], collator),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
     pub fn group_inner_sort_by(
         &self,
         vec: &mut [ThreadHash],
         sort: (SortField, SortOrder),
         envelopes: &Envelopes,
+        collator: &Collator,
     ) {
         let envelopes = envelopes.read().unwrap();
         vec.sort_by(|a, b| match sort {
@@ -946,64 +1116,18 @@ impl Threads {
                 let b = self.thread_ref(*b).date();
                 a.cmp(&b)
             }
-            (SortField::Subject, SortOrder::Desc) => {
-                let a = &self.thread_nodes[&self.thread_ref(*a).root()].message();
-                let b = &self.thread_nodes[&self.thread_ref(*b).root()].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Greater;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Less;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    ma.subject()
-                        .split_graphemes()
-                        .cmp(&mb.subject().split_graphemes())
-                }
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    ma.subject().cmp(&mb.subject())
-                }
-            }
-            (SortField::Subject, SortOrder::Asc) => {
-                let a = &self.thread_nodes[&self.thread_ref(*a).root()].message();
-                let b = &self.thread_nodes[&self.thread_ref(*b).root()].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Less;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Greater;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    mb.subject()
-                        .as_ref()
-                        .split_graphemes()
-                        .cmp(&ma.subject().split_graphemes())
-                }
-
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    mb.subject().as_ref().cmp(&ma.subject())
+            (field, order) => {
+                let ret = self.compare_nodes(
+                    self.thread_ref(*a).root(),
+                    self.thread_ref(*b).root(),
+                    field,
+                    &envelopes,
+                    collator,
+                );
+                if order == SortOrder::Asc {
+                    ret.reverse()
+                } else {
+                    ret
                 }
             }
         });
@@ -1013,85 +1137,31 @@ impl Threads {
         vec: &mut [ThreadNodeHash],
         sort: (SortField, SortOrder),
         envelopes: &Envelopes,
+        collator: &Collator,
     ) {
         let envelopes = envelopes.read().unwrap();
-        vec.sort_by(|a, b| match sort {
-            (SortField::Date, SortOrder::Desc) => {
-                let a = self.thread_ref(self.thread_nodes[&a].group).date();
-                let b = self.thread_ref(self.thread_nodes[&b].group).date();
-                b.cmp(&a)
-            }
-            (SortField::Date, SortOrder::Asc) => {
-                let a = self.thread_ref(self.thread_nodes[&a].group).date();
-                let b = self.thread_ref(self.thread_nodes[&b].group).date();
-                a.cmp(&b)
-            }
-            (SortField::Subject, SortOrder::Desc) => {
-                let a = &self.thread_nodes[&a].message();
-                let b = &self.thread_nodes[&b].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Greater;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Less;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    ma.subject()
-                        .split_graphemes()
-                        .cmp(&mb.subject().split_graphemes())
-                }
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    ma.subject().cmp(&mb.subject())
-                }
-            }
-            (SortField::Subject, SortOrder::Asc) => {
-                let a = &self.thread_nodes[&a].message();
-                let b = &self.thread_nodes[&b].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Less;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Greater;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    mb.subject()
-                        .as_ref()
-                        .split_graphemes()
-                        .cmp(&ma.subject().split_graphemes())
-                }
-
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    mb.subject().as_ref().cmp(&ma.subject())
-                }
-            }
-        });
+        vec.sort_by(|a, b| self.node_cmp(*a, *b, sort, &envelopes, collator));
     }
-    fn inner_sort_by(&self, sort: (SortField, SortOrder), envelopes: &Envelopes) {
+    fn inner_sort_by(
+        &self,
+        sort: (SortField, SortOrder),
+        envelopes: &Envelopes,
+        collator: &Collator,
+    ) {
         let tree = &mut self.tree_index.write().unwrap();
         let envelopes = envelopes.read().unwrap();
-        tree.sort_by(|a, b| match sort {
+        tree.sort_by(|a, b| self.node_cmp(*a, *b, sort, &envelopes, collator));
+    }
+
+    fn node_cmp(
+        &self,
+        a: ThreadNodeHash,
+        b: ThreadNodeHash,
+        sort: (SortField, SortOrder),
+        envelopes: &HashMap<EnvelopeHash, Envelope>,
+        collator: &Collator,
+    ) -> Ordering {
+        match sort {
             (SortField::Date, SortOrder::Desc) => {
                 let a = self.thread_ref(self.thread_nodes[&a].group).date();
                 let b = self.thread_ref(self.thread_nodes[&b].group).date();
@@ -1102,67 +1172,11 @@ impl Threads {
                 let b = self.thread_ref(self.thread_nodes[&b].group).date();
                 a.cmp(&b)
             }
-            (SortField::Subject, SortOrder::Desc) => {
-                let a = &self.thread_nodes[&a].message();
-                let b = &self.thread_nodes[&b].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Greater;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Less;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    ma.subject()
-                        .split_graphemes()
-                        .cmp(&mb.subject().split_graphemes())
-                }
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    ma.subject().cmp(&mb.subject())
-                }
-            }
-            (SortField::Subject, SortOrder::Asc) => {
-                let a = &self.thread_nodes[&a].message();
-                let b = &self.thread_nodes[&b].message();
-
-                match (a, b) {
-                    (Some(_), Some(_)) => {}
-                    (Some(_), None) => {
-                        return Ordering::Less;
-                    }
-                    (None, Some(_)) => {
-                        return Ordering::Greater;
-                    }
-                    (None, None) => {
-                        return Ordering::Equal;
-                    }
-                }
-                let ma = &envelopes[&a.unwrap()];
-                let mb = &envelopes[&b.unwrap()];
-                #[cfg(feature = "unicode_algorithms")]
-                {
-                    mb.subject()
-                        .as_ref()
-                        .split_graphemes()
-                        .cmp(&ma.subject().split_graphemes())
-                }
-
-                #[cfg(not(feature = "unicode_algorithms"))]
-                {
-                    mb.subject().as_ref().cmp(&ma.subject())
-                }
-            }
-        });
+            (field, SortOrder::Desc) => self.compare_nodes(a, b, field, envelopes, collator),
+            (field, SortOrder::Asc) => self
+                .compare_nodes(a, b, field, envelopes, collator)
+                .reverse(),
+        }
     }
 
     pub fn sort_by(
@@ -1170,9 +1184,10 @@ impl Threads {
         sort: (SortField, SortOrder),
         subsort: (SortField, SortOrder),
         envelopes: &Envelopes,
+        collator: &Collator,
     ) {
         if *self.sort.read().unwrap() != sort {
-            self.inner_sort_by(sort, envelopes);
+            self.inner_sort_by(sort, envelopes, collator);
             *self.sort.write().unwrap() = sort;
         }
         if *self.subsort.read().unwrap() != subsort {
@@ -1206,7 +1221,7 @@ impl Threads {
         //FIXME: refactor filter
         self.groups
             .iter()
-            .filter_map(|(h, g)| g.root().map(|_| *h))
+            .filter_map(|(h, g)| g.root().filter(|t| t.len > 0).map(|_| *h))
             .collect::<SmallVec<[ThreadHash; 1024]>>()
     }
 }
@@ -1390,3 +1405,298 @@ fn save_graph(
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn envelope(message_id: &str, references: &[&str], date: &str) -> Envelope {
+        let mut raw = format!(
+            "From: a@example.com\r\nTo: b@example.com\r\nSubject: test\r\nDate: {}\r\nMessage-ID: <{}>\r\n",
+            date, message_id
+        );
+        if let Some(parent) = references.last() {
+            raw.push_str(&format!(
+                "In-Reply-To: <{}>\r\nReferences: {}\r\n",
+                parent,
+                references
+                    .iter()
+                    .map(|r| format!("<{}>", r))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ));
+        }
+        raw.push_str("\r\nbody\r\n");
+        Envelope::from_bytes(raw.as_bytes(), None).unwrap()
+    }
+
+    /// Every permutation of `0..n`.
+    fn permutations(n: usize) -> Vec<Vec<usize>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+        let mut ret = vec![];
+        for p in permutations(n - 1) {
+            for i in 0..n {
+                let mut p = p.clone();
+                p.insert(i, n - 1);
+                ret.push(p);
+            }
+        }
+        ret
+    }
+
+    /// For each message, the Message-ID of its closest ancestor with a message and the
+    /// Message-IDs of the messages of its thread.
+    fn structure(
+        envelopes: &[Envelope],
+        order: &[usize],
+    ) -> BTreeMap<String, (Option<String>, BTreeSet<String>)> {
+        let envelopes: Envelopes = Arc::new(RwLock::new(
+            envelopes.iter().map(|e| (e.hash(), e.clone())).collect(),
+        ));
+        let sorted: Vec<EnvelopeHash> = {
+            let lck = envelopes.read().unwrap();
+            let mut by_id: Vec<(String, EnvelopeHash)> = lck
+                .values()
+                .map(|e| (e.message_id_display().to_string(), e.hash()))
+                .collect();
+            by_id.sort();
+            by_id.into_iter().map(|(_, h)| h).collect()
+        };
+        let mut threads = Threads::new(sorted.len());
+        for &i in order {
+            threads.insert(&envelopes, sorted[i]);
+        }
+        let lck = envelopes.read().unwrap();
+        let mut ret = BTreeMap::new();
+        for env in lck.values() {
+            let node = env.thread();
+            let mut ancestor = threads.thread_nodes[&node].parent;
+            while let Some(a) = ancestor {
+                if threads.thread_nodes[&a].message.is_some() {
+                    break;
+                }
+                ancestor = threads.thread_nodes[&a].parent;
+            }
+            let ancestor = ancestor.map(|a| {
+                lck[&threads.thread_nodes[&a].message.unwrap()]
+                    .message_id_display()
+                    .to_string()
+            });
+            let group = threads.find_group(threads.thread_nodes[&node].group);
+            let members: BTreeSet<String> = threads
+                .thread_group_iter(group)
+                .filter_map(|(_, h)| threads.thread_nodes[&h].message)
+                .map(|h| lck[&h].message_id_display().to_string())
+                .collect();
+            assert_eq!(members.len(), threads.thread_ref(group).len());
+            ret.insert(env.message_id_display().to_string(), (ancestor, members));
+        }
+        ret
+    }
+
+    #[test]
+    fn test_threading_is_independent_of_insertion_order() {
+        let envelopes = vec![
+            envelope("a@example.com", &[], "Mon, 1 Jun 2020 10:00:00 +0000"),
+            envelope(
+                "b@example.com",
+                &["a@example.com"],
+                "Mon, 1 Jun 2020 11:00:00 +0000",
+            ),
+            envelope(
+                "c@example.com",
+                &["a@example.com", "b@example.com"],
+                "Mon, 1 Jun 2020 12:00:00 +0000",
+            ),
+            /* Older than its ancestors. */
+            envelope(
+                "d@example.com",
+                &["a@example.com", "b@example.com", "c@example.com"],
+                "Sun, 31 May 2020 09:00:00 +0000",
+            ),
+            envelope(
+                "e@example.com",
+                &["x@example.com", "y@example.com"],
+                "Mon, 1 Jun 2020 13:00:00 +0000",
+            ),
+            envelope(
+                "y@example.com",
+                &["x@example.com"],
+                "Mon, 1 Jun 2020 14:00:00 +0000",
+            ),
+        ];
+        let expected = structure(&envelopes, &[0, 1, 2, 3, 4, 5]);
+        let thread = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| format!("<{}>", id))
+                .collect::<BTreeSet<String>>()
+        };
+        let abcd = thread(&[
+            "a@example.com",
+            "b@example.com",
+            "c@example.com",
+            "d@example.com",
+        ]);
+        assert_eq!(expected["<a@example.com>"], (None, abcd.clone()));
+        assert_eq!(
+            expected["<d@example.com>"],
+            (Some("<c@example.com>".to_string()), abcd)
+        );
+        assert_eq!(
+            expected["<e@example.com>"],
+            (
+                Some("<y@example.com>".to_string()),
+                thread(&["e@example.com", "y@example.com"])
+            )
+        );
+        for order in permutations(envelopes.len()) {
+            assert_eq!(structure(&envelopes, &order), expected, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn test_late_parent_merges_threads() {
+        /* g and h reply to different missing messages, until p turns out to link them. */
+        let envelopes = vec![
+            envelope(
+                "g@example.com",
+                &["p@example.com"],
+                "Mon, 1 Jun 2020 10:00:00 +0000",
+            ),
+            envelope(
+                "h@example.com",
+                &["q@example.com"],
+                "Mon, 1 Jun 2020 11:00:00 +0000",
+            ),
+            envelope(
+                "p@example.com",
+                &["q@example.com"],
+                "Mon, 1 Jun 2020 09:00:00 +0000",
+            ),
+        ];
+        for order in permutations(envelopes.len()) {
+            let structure = structure(&envelopes, &order);
+            let members = &structure["<g@example.com>"].1;
+            assert_eq!(members.len(), 3, "{:?}", order);
+            assert_eq!(
+                structure["<g@example.com>"].0,
+                Some("<p@example.com>".to_string())
+            );
+            assert_eq!(structure["<p@example.com>"].0, None);
+        }
+    }
+
+    #[test]
+    fn test_own_references_take_precedence() {
+        /* c claims b replies to a, but b says it replies to x. */
+        let envelopes = vec![
+            envelope("x@example.com", &[], "Mon, 1 Jun 2020 09:00:00 +0000"),
+            envelope(
+                "b@example.com",
+                &["x@example.com"],
+                "Mon, 1 Jun 2020 10:00:00 +0000",
+            ),
+            envelope(
+                "c@example.com",
+                &["a@example.com", "b@example.com"],
+                "Mon, 1 Jun 2020 11:00:00 +0000",
+            ),
+            envelope(
+                "d@example.com",
+                &["a@example.com"],
+                "Mon, 1 Jun 2020 12:00:00 +0000",
+            ),
+        ];
+        for order in permutations(envelopes.len()) {
+            let structure = structure(&envelopes, &order);
+            assert_eq!(
+                structure["<b@example.com>"].0,
+                Some("<x@example.com>".to_string()),
+                "{:?}",
+                order
+            );
+            assert_eq!(structure["<d@example.com>"].1.len(), 1, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn test_reference_cycles() {
+        let date = "Mon, 1 Jun 2020 10:00:00 +0000";
+        let cases: Vec<Vec<Envelope>> = vec![
+            /* A message that replies to itself. */
+            vec![envelope("a@example.com", &["a@example.com"], date)],
+            /* Two messages that reply to each other. */
+            vec![
+                envelope("a@example.com", &["b@example.com"], date),
+                envelope("b@example.com", &["a@example.com"], date),
+            ],
+            /* A longer loop, partly through References. */
+            vec![
+                envelope("a@example.com", &["c@example.com", "b@example.com"], date),
+                envelope("b@example.com", &["a@example.com", "c@example.com"], date),
+                envelope("c@example.com", &["b@example.com", "a@example.com"], date),
+                envelope("d@example.com", &["a@example.com", "d@example.com"], date),
+            ],
+        ];
+        for envelopes in cases {
+            for order in permutations(envelopes.len()) {
+                let structure = structure(&envelopes, &order);
+                /* Every message is in the one thread, and `structure` visited each once. */
+                for (_, members) in structure.values() {
+                    assert_eq!(members.len(), envelopes.len(), "{:?}", order);
+                }
+                assert_eq!(
+                    structure
+                        .values()
+                        .filter(|(parent, _)| parent.is_none())
+                        .count(),
+                    1,
+                    "{:?}",
+                    order
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_thread() {
+        /* Each message replies to the previous one. Inserting them newest first makes every
+         * insertion merge the thread so far into the new message's. */
+        const DEPTH: usize = 50_000;
+        /* Parsing whole messages would take most of the test's time. */
+        let chain: Vec<Envelope> = (0..DEPTH)
+            .map(|i| {
+                let mut env = Envelope::new(i as EnvelopeHash + 1);
+                env.set_message_id(format!("<{}@example.com>", i).as_bytes());
+                if i > 0 {
+                    let parent = format!("<{}@example.com>", i - 1);
+                    env.set_in_reply_to(parent.as_bytes());
+                    env.set_references(parent.as_bytes());
+                }
+                env
+            })
+            .collect();
+        let hashes: Vec<EnvelopeHash> = chain.iter().rev().map(Envelope::hash).collect();
+        let envelopes: Envelopes = Arc::new(RwLock::new(
+            chain.into_iter().map(|e| (e.hash(), e)).collect(),
+        ));
+        let mut threads = Threads::new(DEPTH);
+        for h in &hashes {
+            threads.insert(&envelopes, *h);
+        }
+        /* The group of the first message inserted is at the end of a chain of merged groups. */
+        let (first, last) = {
+            let lck = envelopes.read().unwrap();
+            (lck[&hashes[0]].thread(), lck[&hashes[DEPTH - 1]].thread())
+        };
+        let group = threads.find_group(threads.thread_nodes[&first].group);
+        assert_eq!(threads.thread_ref(group).len(), DEPTH);
+        assert_eq!(threads.thread_ref(group).root(), last);
+        let visited: Vec<(usize, ThreadNodeHash)> = threads.thread_group_iter(group).collect();
+        assert_eq!(visited.len(), DEPTH);
+        assert_eq!(visited[DEPTH - 1].0, DEPTH - 1);
+    }
+}
@@ -41,19 +41,20 @@ use std::collections::HashMap;
 pub struct ThreadsGroupIterator<'a> {
     pub(super) root_tree: SmallVec<[ThreadNodeHash; 1024]>,
     pub(super) pos: usize,
-    pub(super) stack: SmallVec<[usize; 16]>,
+    /// The position and hash of each node whose children are being visited.
+    pub(super) stack: SmallVec<[(usize, ThreadNodeHash); 16]>,
     pub(super) thread_nodes: &'a HashMap<ThreadNodeHash, ThreadNode>,
 }
 impl<'a> Iterator for ThreadsGroupIterator<'a> {
     type Item = (usize, ThreadNodeHash, bool);
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let mut tree = &(*self.root_tree);
-            for i in self.stack.iter() {
-                tree = &self.thread_nodes[&tree[*i]].children;
-            }
+            let tree = match self.stack.last() {
+                Some((_, h)) => self.thread_nodes[h].children.as_slice(),
+                None => &self.root_tree,
+            };
             if self.pos == tree.len() {
-                if let Some(p) = self.stack.pop() {
+                if let Some((p, _)) = self.stack.pop() {
                     self.pos = p + 1;
                 } else {
                     return None;
@@ -66,7 +67,7 @@ impl<'a> Iterator for ThreadsGroupIterator<'a> {
                     !self.stack.is_empty() && (self.pos < (tree.len() - 1)),
                 );
                 if !self.thread_nodes[&tree[self.pos]].children.is_empty() {
-                    self.stack.push(self.pos);
+                    self.stack.push((self.pos, ret.1));
                     self.pos = 0;
                     if self.thread_nodes[&ret.1].message.is_some() {
                         return Some(ret);
@@ -98,7 +99,8 @@ impl<'a> Iterator for ThreadsGroupIterator<'a> {
 pub struct ThreadGroupIterator<'a> {
     pub(super) group: ThreadNodeHash,
     pub(super) pos: usize,
-    pub(super) stack: SmallVec<[usize; 16]>,
+    /// The position and hash of each node whose children are being visited.
+    pub(super) stack: SmallVec<[(usize, ThreadNodeHash); 16]>,
     pub(super) thread_nodes: &'a HashMap<ThreadNodeHash, ThreadNode>,
 }
 
@@ -106,20 +108,21 @@ impl<'a> Iterator for ThreadGroupIterator<'a> {
     type Item = (usize, ThreadNodeHash);
     fn next(&mut self) -> Option<(usize, ThreadNodeHash)> {
         loop {
-            let mut tree = &[self.group][..];
-            for i in self.stack.iter() {
-                tree = self.thread_nodes[&tree[*i]].children.as_slice();
-            }
+            let root = [self.group];
+            let tree = match self.stack.last() {
+                Some((_, h)) => self.thread_nodes[h].children.as_slice(),
+                None => &root[..],
+            };
             if self.pos == tree.len() {
                 if self.stack.is_empty() {
                     return None;
                 }
-                self.pos = self.stack.pop().unwrap() + 1;
+                self.pos = self.stack.pop().unwrap().0 + 1;
             } else {
                 debug_assert!(self.pos < tree.len());
                 let ret = (self.stack.len(), tree[self.pos]);
                 if !self.thread_nodes[&tree[self.pos]].children.is_empty() {
-                    self.stack.push(self.pos);
+                    self.stack.push((self.pos, ret.1));
                     self.pos = 0;
                     if self.thread_nodes[&ret.1].message.is_some() {
                         return Some(ret);
@@ -0,0 +1,317 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Drives the `imap` backend end-to-end against the mock server in `tests/mock_imap`.
+//!
+//! Each test uses its own account name, because accounts share the message byte cache.
+
+#![cfg(feature = "imap_backend")]
+
+mod mock_imap;
+
+use futures::stream::TryStreamExt;
+use melib::backends::{
+    BackendEvent, BackendEventConsumer, EnvelopeHashBatch, ImapType, MailBackend, MailboxHash,
+    RefreshEventKind,
+};
+use melib::conf::AccountSettings;
+use melib::email::{Envelope, Flag};
+use mock_imap::MockServer;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn message(n: usize) -> Vec<u8> {
+    format!(
+        "From: Sender {n} <sender{n}@example.com>\r\nTo: user@example.com\r\n\
+         Subject: message {n}\r\nDate: Wed, 01 Jan 2020 00:{n:02}:00 +0000\r\n\
+         Message-ID: <{n}@mock.example.com>\r\n\r\nbody of message {n}\r\n",
+        n = n
+    )
+    .into_bytes()
+}
+
+type Events = Arc<Mutex<Vec<BackendEvent>>>;
+
+struct Client {
+    backend: Box<dyn MailBackend>,
+    events: Events,
+}
+
+impl Client {
+    fn new(name: &str, server: &MockServer) -> Client {
+        /* The backend opens its sqlite3 cache even when it isn't kept. */
+        static XDG: Once = Once::new();
+        XDG.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("meli-imap-test-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_var("XDG_DATA_HOME", &dir);
+        });
+        let mut settings = AccountSettings {
+            name: name.to_string(),
+            root_mailbox: "INBOX".to_string(),
+            format: "imap".to_string(),
+            ..AccountSettings::default()
+        };
+        for (key, value) in &[
+            ("server_hostname", "127.0.0.1".to_string()),
+            ("server_port", server.port.to_string()),
+            ("server_username", mock_imap::USERNAME.to_string()),
+            ("server_password", mock_imap::PASSWORD.to_string()),
+            ("use_tls", "false".to_string()),
+            ("offline_cache", "false".to_string()),
+            ("timeout", "5".to_string()),
+        ] {
+            settings.extra.insert(key.to_string(), value.clone());
+        }
+        let events: Events = Arc::new(Mutex::new(vec![]));
+        let events_ = events.clone();
+        let backend = ImapType::new(
+            &settings,
+            Box::new(|_| true),
+            BackendEventConsumer::new(Arc::new(move |_, event| {
+                events_.lock().unwrap().push(event);
+            })),
+        )
+        .unwrap();
+        Client { backend, events }
+    }
+
+    /// A client that has already connected, as accounts do before fetching anything.
+    fn connect(name: &str, server: &MockServer) -> Client {
+        let client = Client::new(name, server);
+        smol::block_on(client.backend.is_online().unwrap()).unwrap();
+        client
+    }
+
+    fn mailbox(&self, name: &str) -> MailboxHash {
+        smol::block_on(self.backend.mailboxes().unwrap())
+            .unwrap()
+            .into_iter()
+            .find(|(_, m)| m.path() == name)
+            .map(|(hash, _)| hash)
+            .unwrap()
+    }
+
+    fn fetch(&mut self, mailbox_hash: MailboxHash) -> Vec<Envelope> {
+        let mut ret: Vec<Envelope> =
+            smol::block_on(self.backend.fetch(mailbox_hash).unwrap().try_concat()).unwrap();
+        ret.sort_by_key(|e| e.subject().to_string());
+        ret
+    }
+
+    fn bytes(&self, envelope: &Envelope) -> Vec<u8> {
+        let mut op = self.backend.operation(envelope.hash()).unwrap();
+        smol::block_on(op.as_bytes().unwrap()).unwrap()
+    }
+
+    /// Run `watch()` in the background for the rest of the test.
+    fn watch(&self) {
+        let watch = self.backend.watch().unwrap();
+        thread::spawn(move || smol::block_on(watch));
+    }
+
+    /// Wait for an event for which `f` returns `Some`, or panic after ten seconds.
+    fn wait_for<T>(&self, what: &str, f: impl Fn(&RefreshEventKind) -> Option<T>) -> T {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            for event in self.events.lock().unwrap().iter() {
+                if let BackendEvent::Refresh(event) = event {
+                    if let Some(ret) = f(&event.kind) {
+                        return ret;
+                    }
+                }
+            }
+            if Instant::now() >= deadline {
+                panic!("timed out waiting for {}", what);
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+#[test]
+fn test_imap_fetch() {
+    let server = MockServer::start(&[
+        ("INBOX", (1..=3).map(message).collect()),
+        ("Sent", vec![message(4)]),
+    ]);
+    let mut client = Client::connect("test_imap_fetch", &server);
+    let inbox = client.mailbox("INBOX");
+    let sent = client.mailbox("Sent");
+    let envelopes = client.fetch(inbox);
+    assert_eq!(
+        envelopes
+            .iter()
+            .map(|e| e.subject().to_string())
+            .collect::<Vec<String>>(),
+        vec!["message 1", "message 2", "message 3"]
+    );
+    assert_eq!(envelopes[1].message_id_display(), "<2@mock.example.com>");
+    assert_eq!(envelopes[1].from()[0].get_email(), "sender2@example.com");
+    assert!(!envelopes[1].is_seen());
+    assert_eq!(client.bytes(&envelopes[1]), message(2));
+    assert_eq!(client.fetch(sent).len(), 1);
+}
+
+#[test]
+fn test_imap_set_flags() {
+    let server = MockServer::start(&[("INBOX", (1..=2).map(message).collect())]);
+    let mut client = Client::connect("test_imap_set_flags", &server);
+    let inbox = client.mailbox("INBOX");
+    let envelopes = client.fetch(inbox);
+    let flags = |set: bool| {
+        let mut ret = smallvec::SmallVec::new();
+        ret.push((Ok(Flag::SEEN), set));
+        ret.push((Ok(Flag::FLAGGED), set));
+        ret
+    };
+    smol::block_on(
+        client
+            .backend
+            .set_flags(
+                EnvelopeHashBatch::from(envelopes[0].hash()),
+                inbox,
+                flags(true),
+            )
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        server.mailbox("INBOX").messages[0].flags,
+        vec!["\\Seen", "\\Flagged"]
+    );
+    smol::block_on(
+        client
+            .backend
+            .set_flags(
+                EnvelopeHashBatch::from(envelopes[0].hash()),
+                inbox,
+                flags(false),
+            )
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(server.mailbox("INBOX").messages[0].flags.is_empty());
+    assert!(server.mailbox("INBOX").messages[1].flags.is_empty());
+}
+
+#[test]
+fn test_imap_idle_exists_and_expunge() {
+    let server = MockServer::start(&[("INBOX", (1..=2).map(message).collect())]);
+    let mut client = Client::connect("test_imap_idle_exists_and_expunge", &server);
+    let inbox = client.mailbox("INBOX");
+    let envelopes = client.fetch(inbox);
+    client.watch();
+    server.wait_for_idle("INBOX");
+
+    server.append("INBOX", message(3));
+    let new = client.wait_for("the new message", |kind| match kind {
+        RefreshEventKind::Create(env) => Some(env.clone()),
+        _ => None,
+    });
+    assert_eq!(new.subject(), "message 3");
+
+    /* The first message, so that the sequence numbers of the others change. */
+    server.wait_for_idle("INBOX");
+    server.expunge("INBOX", 1);
+    let removed = client.wait_for("the expunged message", |kind| match kind {
+        RefreshEventKind::Remove(hash) => Some(*hash),
+        _ => None,
+    });
+    assert_eq!(removed, envelopes[0].hash());
+
+    /* The server's sequence numbers and the client's must still agree. */
+    server.wait_for_idle("INBOX");
+    server.expunge("INBOX", 3);
+    client.wait_for("the second expunged message", |kind| match kind {
+        RefreshEventKind::Remove(hash) if *hash == new.hash() => Some(()),
+        _ => None,
+    });
+}
+
+#[test]
+fn test_imap_uidvalidity_change() {
+    let server = MockServer::start(&[("INBOX", (1..=3).map(message).collect())]);
+    let mut client = Client::connect("test_imap_uidvalidity_change", &server);
+    let inbox = client.mailbox("INBOX");
+    client.fetch(inbox);
+    server.expunge("INBOX", 1);
+    server.set_uidvalidity("INBOX", 5000);
+    smol::block_on(client.backend.refresh(inbox).unwrap()).unwrap();
+    client.wait_for("a rescan", |kind| match kind {
+        RefreshEventKind::Rescan => Some(()),
+        _ => None,
+    });
+    /* Fetching again picks up the new UIDs. */
+    let envelopes = client.fetch(inbox);
+    assert_eq!(envelopes.len(), 2);
+    assert_eq!(client.bytes(&envelopes[0]), message(2));
+}
+
+#[test]
+fn test_imap_reconnect() {
+    let server = MockServer::start(&[("INBOX", (1..=2).map(message).collect())]);
+    let mut client = Client::connect("test_imap_reconnect", &server);
+    let inbox = client.mailbox("INBOX");
+    let envelopes = client.fetch(inbox);
+    assert_eq!(server.logins(), 1);
+    server.drop_connections();
+    assert_eq!(client.bytes(&envelopes[1]), message(2));
+    assert_eq!(server.logins(), 2);
+}
+
+#[test]
+fn test_imap_reconnect_idle() {
+    let server = MockServer::start(&[("INBOX", vec![message(1)])]);
+    let mut client = Client::connect("test_imap_reconnect_idle", &server);
+    let inbox = client.mailbox("INBOX");
+    client.fetch(inbox);
+    client.watch();
+    server.wait_for_idle("INBOX");
+    server.drop_connections();
+    server.wait_for_idle("INBOX");
+    server.append("INBOX", message(2));
+    client.wait_for(
+        "the message delivered while idling again",
+        |kind| match kind {
+            RefreshEventKind::Create(env) if env.subject() == "message 2" => Some(()),
+            _ => None,
+        },
+    );
+}
+
+#[test]
+fn test_imap_authentication_failure() {
+    let server = MockServer::start(&[("INBOX", vec![])]);
+    /* `is_online` tries to connect twice. */
+    for _ in 0..2 {
+        server.script(
+            "LOGIN",
+            "{tag} NO [AUTHENTICATIONFAILED] Invalid credentials",
+        );
+    }
+    let client = Client::new("test_imap_authentication_failure", &server);
+    let err = smol::block_on(client.backend.is_online().unwrap()).unwrap_err();
+    assert!(err.kind.is_authentication(), "{:?}", err);
+    assert!(server.commands().iter().any(|c| c.starts_with("LOGIN")));
+    assert_eq!(server.logins(), 0);
+}
@@ -0,0 +1,868 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! An in-memory IMAP server for the integration tests.
+//!
+//! It serves plain TCP connections on `127.0.0.1`, one thread per connection, and implements
+//! enough of RFC 3501 for the `imap` backend: `CAPABILITY`, `LOGIN`, `LIST`, `LSUB`, `STATUS`,
+//! `SELECT`, `EXAMINE`, `UNSELECT`, `SEARCH`, `FETCH`, `STORE`, `EXPUNGE` (and their `UID`
+//! forms), `IDLE`, `NOOP` and `LOGOUT`. Messages are expected to be single part `text/plain`.
+//!
+//! Tests change the mailboxes while clients are connected with [`MockServer::append`],
+//! [`MockServer::expunge`] and [`MockServer::set_uidvalidity`]; connections that have the mailbox
+//! selected are told with `EXISTS` and `EXPUNGE` responses, right away while idling and otherwise
+//! before the completion of their next command. [`MockServer::script`] replaces the server's reply
+//! to a command and [`MockServer::drop_connections`] simulates a network failure.
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const USERNAME: &str = "user";
+pub const PASSWORD: &str = "password";
+const CAPABILITIES: &str = "IMAP4rev1 IDLE UIDPLUS UNSELECT";
+const SYSTEM_FLAGS: &str = "\\Answered \\Flagged \\Deleted \\Seen \\Draft";
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mailbox {
+    pub name: String,
+    pub uidvalidity: u32,
+    pub uidnext: u32,
+    pub subscribed: bool,
+    pub messages: Vec<Message>,
+}
+
+#[derive(Debug)]
+struct Session {
+    id: usize,
+    stream: TcpStream,
+    selected: Option<String>,
+    /// UIDs of the selected mailbox in message sequence number order, as this client knows them.
+    view: Vec<u32>,
+    /// Tag of the `IDLE` command in progress.
+    idling: Option<String>,
+    /// Untagged responses to send before the completion of the next command.
+    pending: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    mailboxes: Vec<Mailbox>,
+    sessions: Vec<Session>,
+    next_session: usize,
+    /// Every command received, without its tag.
+    commands: Vec<String>,
+    logins: usize,
+    /// Replies that replace the server's own for the next command starting with the prefix.
+    scripts: VecDeque<(String, String)>,
+}
+
+impl State {
+    fn mailbox(&self, name: &str) -> Option<&Mailbox> {
+        self.mailboxes
+            .iter()
+            .find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    fn mailbox_mut(&mut self, name: &str) -> Option<&mut Mailbox> {
+        self.mailboxes
+            .iter_mut()
+            .find(|m| m.name.eq_ignore_ascii_case(name))
+    }
+
+    fn session(&mut self, id: usize) -> Option<&mut Session> {
+        self.sessions.iter_mut().find(|s| s.id == id)
+    }
+}
+
+pub struct MockServer {
+    pub port: u16,
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl MockServer {
+    /// Start serving `mailboxes`, given as names and the raw messages in each.
+    pub fn start(mailboxes: &[(&str, Vec<Vec<u8>>)]) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let state = State {
+            mailboxes: mailboxes
+                .iter()
+                .enumerate()
+                .map(|(i, (name, messages))| Mailbox {
+                    name: name.to_string(),
+                    uidvalidity: 1000 + i as u32,
+                    uidnext: messages.len() as u32 + 1,
+                    subscribed: true,
+                    messages: messages
+                        .iter()
+                        .enumerate()
+                        .map(|(i, bytes)| Message {
+                            uid: i as u32 + 1,
+                            flags: vec![],
+                            bytes: bytes.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            ..State::default()
+        };
+        let state = Arc::new((Mutex::new(state), Condvar::new()));
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let state = server_state.clone();
+                thread::spawn(move || serve(stream, state));
+            }
+        });
+        MockServer { port, state }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Wait until `cond` holds for the server's state, or panic after ten seconds.
+    fn wait_until(&self, what: &str, cond: impl Fn(&State) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut state = self.lock();
+        while !cond(&state) {
+            let now = Instant::now();
+            if now >= deadline {
+                panic!("timed out waiting for {}", what);
+            }
+            state = self
+                .state
+                .1
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+    }
+
+    pub fn mailbox(&self, name: &str) -> Mailbox {
+        self.lock().mailbox(name).unwrap().clone()
+    }
+
+    pub fn commands(&self) -> Vec<String> {
+        self.lock().commands.clone()
+    }
+
+    pub fn logins(&self) -> usize {
+        self.lock().logins
+    }
+
+    /// Reply with `response` to the next command that starts with `prefix`. `{tag}` in the
+    /// response is replaced with the command's tag.
+    pub fn script(&self, prefix: &str, response: &str) {
+        self.lock()
+            .scripts
+            .push_back((prefix.to_string(), response.to_string()));
+    }
+
+    /// Wait until a client is idling on `mailbox`.
+    pub fn wait_for_idle(&self, mailbox: &str) {
+        self.wait_until("a client to IDLE", |state| {
+            state.sessions.iter().any(|s| {
+                s.idling.is_some()
+                    && s.selected
+                        .as_ref()
+                        .map(|m| m.eq_ignore_ascii_case(mailbox))
+                        .unwrap_or(false)
+            })
+        });
+    }
+
+    /// Deliver a new message to `mailbox` and return its UID.
+    pub fn append(&self, mailbox: &str, bytes: Vec<u8>) -> u32 {
+        let mut state = self.lock();
+        let state = &mut *state;
+        let mailbox_entry = state.mailbox_mut(mailbox).unwrap();
+        let uid = mailbox_entry.uidnext;
+        mailbox_entry.uidnext += 1;
+        mailbox_entry.messages.push(Message {
+            uid,
+            flags: vec!["\\Recent".to_string()],
+            bytes,
+        });
+        for session in state.sessions.iter_mut() {
+            if session
+                .selected
+                .as_ref()
+                .map(|m| m.eq_ignore_ascii_case(mailbox))
+                .unwrap_or(false)
+            {
+                session.view.push(uid);
+                let line = format!("* {} EXISTS\r\n", session.view.len());
+                notify(session, line);
+            }
+        }
+        uid
+    }
+
+    /// Remove the message with `uid` from `mailbox`.
+    pub fn expunge(&self, mailbox: &str, uid: u32) {
+        let mut state = self.lock();
+        let state = &mut *state;
+        state
+            .mailbox_mut(mailbox)
+            .unwrap()
+            .messages
+            .retain(|m| m.uid != uid);
+        for session in state.sessions.iter_mut() {
+            if !session
+                .selected
+                .as_ref()
+                .map(|m| m.eq_ignore_ascii_case(mailbox))
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            if let Some(pos) = session.view.iter().position(|u| *u == uid) {
+                session.view.remove(pos);
+                let line = format!("* {} EXPUNGE\r\n", pos + 1);
+                notify(session, line);
+            }
+        }
+    }
+
+    /// Renumber the messages of `mailbox` as a server does when it rebuilds its index.
+    /// Connected clients are not told until they select the mailbox again.
+    pub fn set_uidvalidity(&self, mailbox: &str, uidvalidity: u32) {
+        let mut state = self.lock();
+        let mailbox = state.mailbox_mut(mailbox).unwrap();
+        mailbox.uidvalidity = uidvalidity;
+        for (i, message) in mailbox.messages.iter_mut().enumerate() {
+            message.uid = i as u32 + 1;
+        }
+        mailbox.uidnext = mailbox.messages.len() as u32 + 1;
+    }
+
+    /// Close every open connection without a `BYE`, like a network failure would.
+    pub fn drop_connections(&self) {
+        let mut state = self.lock();
+        for session in state.sessions.drain(..) {
+            let _ = session.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.drop_connections();
+    }
+}
+
+/// Send `line` now if the client is idling, otherwise with its next command's response.
+fn notify(session: &mut Session, line: String) {
+    if session.idling.is_some() {
+        let _ = session.stream.write_all(line.as_bytes());
+    } else {
+        session.pending.push(line);
+    }
+}
+
+fn serve(stream: TcpStream, state: Arc<(Mutex<State>, Condvar)>) {
+    let id = {
+        let mut lck = state.0.lock().unwrap();
+        let id = lck.next_session;
+        lck.next_session += 1;
+        lck.sessions.push(Session {
+            id,
+            stream: stream.try_clone().unwrap(),
+            selected: None,
+            view: vec![],
+            idling: None,
+            pending: vec![],
+        });
+        id
+    };
+    let mut writer = stream.try_clone().unwrap();
+    let _ = writer.write_all(
+        format!(
+            "* OK [CAPABILITY {}] mock IMAP server ready\r\n",
+            CAPABILITIES
+        )
+        .as_bytes(),
+    );
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = line.trim_end_matches(&['\r', '\n'][..]);
+        let mut lck = state.0.lock().unwrap_or_else(|err| err.into_inner());
+        let response = match lck.session(id) {
+            /* Dropped with `drop_connections`. */
+            None => break,
+            Some(session) if session.idling.is_some() => {
+                if line.eq_ignore_ascii_case("DONE") {
+                    let tag = session.idling.take().unwrap();
+                    format!("{} OK IDLE terminated\r\n", tag)
+                } else {
+                    "* BAD expected DONE\r\n".to_string()
+                }
+            }
+            Some(_) => {
+                let (tag, command) = match line.find(' ') {
+                    Some(pos) => (&line[..pos], &line[pos + 1..]),
+                    None => (line, ""),
+                };
+                lck.commands.push(command.to_string());
+                let scripted = lck
+                    .scripts
+                    .iter()
+                    .position(|(prefix, _)| command.starts_with(prefix.as_str()));
+                match scripted {
+                    Some(pos) => {
+                        let (_, response) = lck.scripts.remove(pos).unwrap();
+                        format!("{}\r\n", response.replace("{tag}", tag))
+                    }
+                    None => handle(&mut lck, id, tag, command),
+                }
+            }
+        };
+        let logout = response.starts_with("* BYE");
+        if writer.write_all(response.as_bytes()).is_err() || logout {
+            break;
+        }
+        state.1.notify_all();
+    }
+    let mut lck = state.0.lock().unwrap_or_else(|err| err.into_inner());
+    lck.sessions.retain(|s| s.id != id);
+    state.1.notify_all();
+}
+
+/// Split command arguments on spaces outside of quotes, parentheses and brackets, unquoting
+/// quoted strings.
+fn arguments(input: &str) -> Vec<String> {
+    let mut ret = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '"' if depth == 0 => quoted = !quoted,
+            '(' | '[' if !quoted => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' | ']' if !quoted => {
+                depth -= 1;
+                current.push(c);
+            }
+            ' ' if !quoted && depth == 0 => {
+                ret.push(std::mem::take(&mut current));
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() || input.ends_with("\"\"") {
+        ret.push(current);
+    }
+    ret
+}
+
+fn quote(s: &str) -> String {
+    if s.contains(&['\r', '\n'][..]) || !s.is_ascii() {
+        format!("{{{}}}\r\n{}", s.len(), s)
+    } else {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+fn nstring(s: Option<&str>) -> String {
+    s.map(quote).unwrap_or_else(|| "NIL".to_string())
+}
+
+/// Parse a sequence set like `1,3:5,7:*` against `max`, the value of `*`.
+fn sequence_set(set: &str, max: u32) -> Vec<(u32, u32)> {
+    set.split(',')
+        .filter_map(|range| {
+            let number = |s: &str| {
+                if s == "*" {
+                    Some(max)
+                } else {
+                    s.parse::<u32>().ok()
+                }
+            };
+            let (from, to) = match range.find(':') {
+                Some(pos) => (number(&range[..pos])?, number(&range[pos + 1..])?),
+                None => (number(range)?, number(range)?),
+            };
+            Some((std::cmp::min(from, to), std::cmp::max(from, to)))
+        })
+        .collect()
+}
+
+fn in_set(set: &[(u32, u32)], n: u32) -> bool {
+    set.iter().any(|(from, to)| *from <= n && n <= *to)
+}
+
+/// The unfolded value of header `name` of `bytes`.
+fn header(bytes: &[u8], name: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    let headers = text.split("\r\n\r\n").next().unwrap_or("");
+    let mut ret: Option<String> = None;
+    for line in headers.split("\r\n") {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(ref mut value) = ret {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        if ret.is_some() {
+            break;
+        }
+        if let Some(pos) = line.find(':') {
+            if line[..pos].eq_ignore_ascii_case(name) {
+                ret = Some(line[pos + 1..].trim().to_string());
+            }
+        }
+    }
+    ret
+}
+
+/// An address list in the form of an ENVELOPE structure, for addresses like
+/// `Name <mailbox@host>` or `mailbox@host`.
+fn address_list(value: Option<String>) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return "NIL".to_string(),
+    };
+    let addresses = value
+        .split(',')
+        .map(|address| {
+            let address = address.trim();
+            let (name, spec) = match (address.find('<'), address.rfind('>')) {
+                (Some(start), Some(end)) => (
+                    Some(address[..start].trim().trim_matches('"')),
+                    &address[start + 1..end],
+                ),
+                _ => (None, address),
+            };
+            let (mailbox, host) = match spec.find('@') {
+                Some(pos) => (&spec[..pos], &spec[pos + 1..]),
+                None => (spec, ""),
+            };
+            format!(
+                "({} NIL {} {})",
+                nstring(name.filter(|n| !n.is_empty())),
+                quote(mailbox),
+                quote(host)
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("({})", addresses.join(""))
+}
+
+fn envelope(bytes: &[u8]) -> String {
+    let from = address_list(header(bytes, "From"));
+    format!(
+        "({} {} {} {} {} {} {} NIL {} {})",
+        nstring(header(bytes, "Date").as_deref()),
+        nstring(header(bytes, "Subject").as_deref()),
+        from,
+        from,
+        from,
+        address_list(header(bytes, "To")),
+        address_list(header(bytes, "Cc")),
+        nstring(header(bytes, "In-Reply-To").as_deref()),
+        nstring(header(bytes, "Message-ID").as_deref()),
+    )
+}
+
+fn body_structure(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let body = text
+        .find("\r\n\r\n")
+        .map(|pos| &text[pos + "\r\n\r\n".len()..])
+        .unwrap_or("");
+    format!(
+        "(\"TEXT\" \"PLAIN\" (\"CHARSET\" \"UTF-8\") NIL NIL \"7BIT\" {} {} NIL NIL NIL NIL)",
+        body.len(),
+        body.matches("\r\n").count()
+    )
+}
+
+fn fetch_items(message: &Message, items: &str, uid: bool) -> String {
+    let items = items.trim_start_matches('(').trim_end_matches(')');
+    let items = arguments(items)
+        .into_iter()
+        .map(|i| i.to_ascii_uppercase())
+        .collect::<Vec<String>>();
+    let mut ret = vec![];
+    if uid || items.iter().any(|i| i == "UID") {
+        ret.push(format!("UID {}", message.uid));
+    }
+    for item in items.iter() {
+        match item.as_str() {
+            "UID" => {}
+            "FLAGS" => ret.push(format!("FLAGS ({})", message.flags.join(" "))),
+            "RFC822.SIZE" => ret.push(format!("RFC822.SIZE {}", message.bytes.len())),
+            "ENVELOPE" => ret.push(format!("ENVELOPE {}", envelope(&message.bytes))),
+            "BODYSTRUCTURE" => {
+                ret.push(format!("BODYSTRUCTURE {}", body_structure(&message.bytes)))
+            }
+            "BODY.PEEK[HEADER.FIELDS (REFERENCES)]" | "BODY[HEADER.FIELDS (REFERENCES)]" => {
+                let value = header(&message.bytes, "References")
+                    .map(|v| format!("References: {}\r\n\r\n", v))
+                    .unwrap_or_else(|| "\r\n".to_string());
+                ret.push(format!(
+                    "BODY[HEADER.FIELDS (REFERENCES)] {{{}}}\r\n{}",
+                    value.len(),
+                    value
+                ));
+            }
+            "RFC822" | "BODY[]" | "BODY.PEEK[]" => ret.push(format!(
+                "{} {{{}}}\r\n{}",
+                if item == "RFC822" { "RFC822" } else { "BODY[]" },
+                message.bytes.len(),
+                String::from_utf8_lossy(&message.bytes)
+            )),
+            _ => {}
+        }
+    }
+    ret.join(" ")
+}
+
+/// Apply a STORE to `flags` and return whether anything changed.
+fn store(flags: &mut Vec<String>, action: &str, values: &str) -> bool {
+    let values = values
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    let before = flags.clone();
+    match action
+        .trim_end_matches(".SILENT")
+        .to_ascii_uppercase()
+        .as_str()
+    {
+        "+FLAGS" => {
+            for v in values {
+                if !flags.iter().any(|f| f.eq_ignore_ascii_case(&v)) {
+                    flags.push(v);
+                }
+            }
+        }
+        "-FLAGS" => flags.retain(|f| !values.iter().any(|v| v.eq_ignore_ascii_case(f))),
+        _ => *flags = values,
+    }
+    *flags != before
+}
+
+fn status_items(mailbox: &Mailbox, items: &str) -> String {
+    arguments(items.trim_start_matches('(').trim_end_matches(')'))
+        .into_iter()
+        .filter_map(|item| {
+            let value = match item.to_ascii_uppercase().as_str() {
+                "MESSAGES" => mailbox.messages.len() as u32,
+                "UNSEEN" => mailbox
+                    .messages
+                    .iter()
+                    .filter(|m| !m.flags.iter().any(|f| f == "\\Seen"))
+                    .count() as u32,
+                "RECENT" => 0,
+                "UIDNEXT" => mailbox.uidnext,
+                "UIDVALIDITY" => mailbox.uidvalidity,
+                _ => return None,
+            };
+            Some(format!("{} {}", item.to_ascii_uppercase(), value))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn handle(state: &mut State, id: usize, tag: &str, command: &str) -> String {
+    let args = arguments(command);
+    let mut name = args
+        .first()
+        .cloned()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    let mut args = &args[std::cmp::min(1, args.len())..];
+    let uid = name == "UID";
+    if uid {
+        name = args
+            .first()
+            .cloned()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        args = &args[std::cmp::min(1, args.len())..];
+    }
+    let mut out = String::new();
+    let ok = |out: &mut String, text: &str| format!("{}{} OK {}\r\n", out, tag, text);
+    let bad = |text: &str| format!("{} BAD {}\r\n", tag, text);
+    match name.as_str() {
+        "CAPABILITY" => {
+            out.push_str(&format!("* CAPABILITY {}\r\n", CAPABILITIES));
+            ok(&mut out, "CAPABILITY completed")
+        }
+        "LOGIN" => {
+            if args.len() == 2 && args[0] == USERNAME && args[1] == PASSWORD {
+                state.logins += 1;
+                out.push_str(&format!("* CAPABILITY {}\r\n", CAPABILITIES));
+                ok(&mut out, "Logged in")
+            } else {
+                format!("{} NO [AUTHENTICATIONFAILED] Invalid credentials\r\n", tag)
+            }
+        }
+        "LIST" | "LSUB" => {
+            for mailbox in state.mailboxes.iter() {
+                if name == "LSUB" && !mailbox.subscribed {
+                    continue;
+                }
+                out.push_str(&format!(
+                    "* {} (\\HasNoChildren) \"/\" \"{}\"\r\n",
+                    name, mailbox.name
+                ));
+            }
+            ok(&mut out, &format!("{} completed", name))
+        }
+        "STATUS" if args.len() == 2 => match state.mailbox(&args[0]) {
+            Some(mailbox) => {
+                out.push_str(&format!(
+                    "* STATUS \"{}\" ({})\r\n",
+                    mailbox.name,
+                    status_items(mailbox, &args[1])
+                ));
+                ok(&mut out, "STATUS completed")
+            }
+            None => format!("{} NO Mailbox doesn't exist\r\n", tag),
+        },
+        "SELECT" | "EXAMINE" if args.len() == 1 => {
+            let mailbox = match state.mailbox(&args[0]) {
+                Some(mailbox) => mailbox.clone(),
+                None => return format!("{} NO Mailbox doesn't exist\r\n", tag),
+            };
+            let session = state.session(id).unwrap();
+            session.selected = Some(mailbox.name.clone());
+            session.view = mailbox.messages.iter().map(|m| m.uid).collect();
+            session.pending.clear();
+            out.push_str(&format!(
+                "* FLAGS ({})\r\n* OK [PERMANENTFLAGS ({} \\*)] Flags permitted\r\n\
+                 * {} EXISTS\r\n* 0 RECENT\r\n* OK [UIDVALIDITY {}] UIDs valid\r\n\
+                 * OK [UIDNEXT {}] Predicted next UID\r\n",
+                SYSTEM_FLAGS,
+                SYSTEM_FLAGS,
+                mailbox.messages.len(),
+                mailbox.uidvalidity,
+                mailbox.uidnext
+            ));
+            let text = if name == "SELECT" {
+                "[READ-WRITE] SELECT completed"
+            } else {
+                "[READ-ONLY] EXAMINE completed"
+            };
+            ok(&mut out, text)
+        }
+        "UNSELECT" | "CLOSE" => {
+            let session = state.session(id).unwrap();
+            session.selected = None;
+            session.view.clear();
+            session.pending.clear();
+            ok(&mut out, &format!("{} completed", name))
+        }
+        "NOOP" | "CHECK" => {
+            let session = state.session(id).unwrap();
+            for line in session.pending.drain(..) {
+                out.push_str(&line);
+            }
+            ok(&mut out, &format!("{} completed", name))
+        }
+        "IDLE" => {
+            let session = state.session(id).unwrap();
+            session.idling = Some(tag.to_string());
+            out.push_str("+ idling\r\n");
+            for line in session.pending.drain(..) {
+                out.push_str(&line);
+            }
+            out
+        }
+        "SEARCH" | "FETCH" | "STORE" | "EXPUNGE" => {
+            let session = state.session(id).unwrap();
+            let (selected, view) = match session.selected {
+                Some(ref selected) => (selected.clone(), session.view.clone()),
+                None => return bad("No mailbox selected"),
+            };
+            /* EXPUNGE responses aren't allowed during FETCH, STORE and SEARCH, RFC 3501 7.4.1 */
+            if uid || name == "EXPUNGE" {
+                for line in session.pending.drain(..) {
+                    out.push_str(&line);
+                }
+            }
+            let max_uid = view.iter().max().cloned().unwrap_or(0);
+            let mailbox = state.mailbox_mut(&selected).unwrap();
+            let msn = |uid: u32| view.iter().position(|u| *u == uid).map(|p| p as u32 + 1);
+            /* Messages the command applies to, with their sequence numbers. */
+            let matching = |set: &str, messages: &[Message]| -> Vec<usize> {
+                let set = if uid {
+                    sequence_set(set, max_uid)
+                } else {
+                    sequence_set(set, view.len() as u32)
+                };
+                messages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, m)| match msn(m.uid) {
+                        Some(n) => in_set(&set, if uid { m.uid } else { n }),
+                        None => false,
+                    })
+                    .map(|(i, _)| i)
+                    .collect()
+            };
+            match name.as_str() {
+                "SEARCH" => {
+                    let mut results = vec![];
+                    let criteria = args.join(" ").to_ascii_uppercase();
+                    for m in mailbox.messages.iter() {
+                        let n = match msn(m.uid) {
+                            Some(n) => n,
+                            None => continue,
+                        };
+                        let seen = m.flags.iter().any(|f| f == "\\Seen");
+                        let hit = match criteria.as_str() {
+                            "" | "ALL" => true,
+                            "UNSEEN" => !seen,
+                            "SEEN" => seen,
+                            "RECENT" | "NEW" => m.flags.iter().any(|f| f == "\\Recent"),
+                            c if c.starts_with("UID ") => {
+                                in_set(&sequence_set(&c["UID ".len()..], max_uid), m.uid)
+                            }
+                            c => in_set(&sequence_set(c, view.len() as u32), n),
+                        };
+                        if hit {
+                            results.push(if uid { m.uid } else { n }.to_string());
+                        }
+                    }
+                    out.push_str(
+                        &format!("* SEARCH {}\r\n", results.join(" "))
+                            .replace("SEARCH \r\n", "SEARCH\r\n"),
+                    );
+                    ok(&mut out, "SEARCH completed")
+                }
+                "FETCH" if args.len() >= 2 => {
+                    let items = args[1..].join(" ");
+                    /* Fetching the body without PEEK sets \Seen. */
+                    let sets_seen = arguments(items.trim_start_matches('(').trim_end_matches(')'))
+                        .iter()
+                        .any(|i| {
+                            i.eq_ignore_ascii_case("RFC822") || i.eq_ignore_ascii_case("BODY[]")
+                        });
+                    for i in matching(&args[0], &mailbox.messages) {
+                        let message = &mut mailbox.messages[i];
+                        let n = msn(message.uid).unwrap();
+                        if sets_seen && !message.flags.iter().any(|f| f == "\\Seen") {
+                            message.flags.push("\\Seen".to_string());
+                        }
+                        let items = fetch_items(message, &items, uid);
+                        out.push_str(&format!("* {} FETCH ({})\r\n", n, items));
+                    }
+                    ok(&mut out, "FETCH completed")
+                }
+                "STORE" if args.len() == 3 => {
+                    let silent = args[1].to_ascii_uppercase().ends_with(".SILENT");
+                    for i in matching(&args[0], &mailbox.messages) {
+                        let message = &mut mailbox.messages[i];
+                        store(&mut message.flags, &args[1], &args[2]);
+                        if !silent {
+                            let n = msn(message.uid).unwrap();
+                            out.push_str(&format!(
+                                "* {} FETCH ({}FLAGS ({}))\r\n",
+                                n,
+                                if uid {
+                                    format!("UID {} ", message.uid)
+                                } else {
+                                    String::new()
+                                },
+                                message.flags.join(" ")
+                            ));
+                        }
+                    }
+                    ok(&mut out, "STORE completed")
+                }
+                "EXPUNGE" => {
+                    let set = args.first().map(|s| sequence_set(s, max_uid));
+                    let deleted = mailbox
+                        .messages
+                        .iter()
+                        .filter(|m| m.flags.iter().any(|f| f == "\\Deleted"))
+                        .filter(|m| set.as_ref().map(|s| in_set(s, m.uid)).unwrap_or(true))
+                        .map(|m| m.uid)
+                        .collect::<Vec<u32>>();
+                    mailbox.messages.retain(|m| !deleted.contains(&m.uid));
+                    let selected = selected.clone();
+                    for session in state.sessions.iter_mut() {
+                        if session.selected.as_ref() != Some(&selected) {
+                            continue;
+                        }
+                        for uid in deleted.iter() {
+                            if let Some(pos) = session.view.iter().position(|u| u == uid) {
+                                session.view.remove(pos);
+                                let line = format!("* {} EXPUNGE\r\n", pos + 1);
+                                if session.id == id {
+                                    out.push_str(&line);
+                                } else {
+                                    notify(session, line);
+                                }
+                            }
+                        }
+                    }
+                    ok(&mut out, "EXPUNGE completed")
+                }
+                _ => bad("Invalid arguments"),
+            }
+        }
+        "LOGOUT" => {
+            out.push_str("* BYE Logging out\r\n");
+            ok(&mut out, "LOGOUT completed")
+        }
+        _ => bad("Unknown command"),
+    }
+}
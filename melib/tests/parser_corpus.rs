@@ -0,0 +1,198 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Runs the e-mail parsers over the messages in `tests/corpus` and over random mutations of
+//! them. Parsing may fail, but must never panic. Inputs that made the fuzz targets in
+//! `fuzz/` crash belong in the corpus.
+
+use melib::email::attachment_types::ContentType;
+use melib::email::parser;
+use melib::email::{Attachment, AttachmentBuilder, Envelope};
+use std::fs;
+use std::path::PathBuf;
+
+fn corpus() -> Vec<(PathBuf, Vec<u8>)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut ret = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let bytes = fs::read(&path).unwrap();
+            (path, bytes)
+        })
+        .collect::<Vec<_>>();
+    ret.sort();
+    assert!(!ret.is_empty(), "{} is empty", dir.display());
+    ret
+}
+
+fn walk(attachment: &Attachment) {
+    let _ = attachment.text();
+    let _ = attachment.mime_type();
+    let _ = attachment.filename();
+    let _ = attachment.into_raw();
+    let _ = attachment.part_boundaries();
+    let _ = melib::email::attachments::decode_rec(attachment, None);
+    let _ = attachment.attachments();
+    if let ContentType::Multipart { ref parts, .. } = attachment.content_type() {
+        for part in parts {
+            walk(part);
+        }
+    }
+}
+
+/// Everything the UI does with a message it opens.
+fn parse(bytes: &[u8]) {
+    if let Ok(envelope) = Envelope::from_bytes(bytes, None) {
+        let _ = envelope.subject();
+        let _ = envelope.field_from_to_string();
+        let _ = envelope.headers(bytes);
+        walk(&envelope.body_bytes(bytes));
+    }
+    walk(&AttachmentBuilder::new(bytes).build());
+    let _ = parser::headers::headers(bytes);
+    let _ = parser::address::rfc2822address_list(bytes);
+    let _ = parser::address::msg_id_list(bytes);
+    let _ = parser::dates::rfc5322_date(bytes);
+    let _ = parser::encodings::phrase(bytes, false);
+    let _ = parser::encodings::phrase(bytes, true);
+    let _ = parser::attachments::content_type(bytes);
+}
+
+/// xorshift64, so that failures can be reproduced from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+/// Bytes that are likely to confuse the parsers.
+const INTERESTING: &[&[u8]] = &[
+    b"\r\n",
+    b"\n",
+    b"\r",
+    b"\r\n\r\n",
+    b"--",
+    b"=",
+    b"=?",
+    b"?=",
+    b"=?utf-8?B?",
+    b"=?iso-8859-1?Q?",
+    b"\"",
+    b"<",
+    b">",
+    b";",
+    b":",
+    b" ",
+    b"\t",
+    b"\xff",
+    b"\x00",
+    b"\xc3",
+];
+
+fn mutate(rng: &mut Rng, input: &[u8]) -> Vec<u8> {
+    let mut ret = input.to_vec();
+    for _ in 0..=rng.below(4) {
+        let pos = rng.below(ret.len() + 1);
+        match rng.below(5) {
+            0 => ret.truncate(pos),
+            1 if pos < ret.len() => {
+                let end = pos + rng.below(ret.len() - pos);
+                ret.drain(pos..end);
+            }
+            2 if pos < ret.len() => ret[pos] = rng.next() as u8,
+            3 => {
+                let end = pos + rng.below(ret.len() - pos);
+                let chunk = ret[pos..end].to_vec();
+                ret.splice(pos..pos, chunk);
+            }
+            _ => {
+                let interesting = INTERESTING[rng.below(INTERESTING.len())];
+                ret.splice(pos..pos, interesting.iter().cloned());
+            }
+        }
+    }
+    ret
+}
+
+#[test]
+fn test_corpus() {
+    for (path, bytes) in corpus() {
+        eprintln!("{}", path.display());
+        parse(&bytes);
+    }
+}
+
+#[test]
+fn test_corpus_truncations() {
+    for (path, bytes) in corpus() {
+        eprintln!("{}", path.display());
+        for len in 0..bytes.len() {
+            parse(&bytes[..len]);
+        }
+    }
+}
+
+#[test]
+fn test_corpus_mutations() {
+    let corpus = corpus();
+    let mut rng = Rng(0x6d65_6c69);
+    for _ in 0..5000 {
+        let (path, bytes) = &corpus[rng.below(corpus.len())];
+        let input = mutate(&mut rng, bytes);
+        eprintln!(
+            "{} mutated: {:?}",
+            path.display(),
+            String::from_utf8_lossy(&input)
+        );
+        parse(&input);
+    }
+}
+
+#[test]
+fn test_corpus_expectations() {
+    let broken_boundary = fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/broken-boundary.eml"),
+    )
+    .unwrap();
+    let envelope = Envelope::from_bytes(&broken_boundary, None).unwrap();
+    assert_eq!(
+        envelope.message_id_display().to_string(),
+        "<broken-boundary@example.com>"
+    );
+    /* The last part is kept even though the closing boundary is missing. */
+    let body = envelope.body_bytes(&broken_boundary);
+    assert_eq!(body.attachments().len(), 3);
+
+    let only_separator =
+        fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/only-separator.eml"))
+            .unwrap();
+    assert!(Envelope::from_bytes(&only_separator, None).is_err());
+}
@@ -0,0 +1,327 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Journal of what happened to the messages of an account.
+ *
+ * Actions of the user (marking, tagging, deleting, moving, saving) are recorded when they are
+ * asked for, and the changes the backend reports (messages added, removed, flags changed) when
+ * they arrive. A change to a message an action was taken on shortly before is taken for a
+ * consequence of it and recorded as local, anything else as remote. The journal is appended to a
+ * file of JSON lines in the account's data directory so that it outlives the session.
+ */
+use melib::{Envelope, Flag, UnixTimestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How many entries are kept, in memory and in the file.
+const MAX_ENTRIES: usize = 5000;
+/// How long after an action changes to its messages are attributed to it.
+const LOCAL_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The user did it, or it follows from something the user did.
+    Local,
+    /// Another client or the server did it.
+    Remote,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Local => f.pad("local"),
+            Source::Remote => f.pad("remote"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    /// The user asked for this, for example "delete" or "move to Archive".
+    Action(String),
+    /// The message appeared in the mailbox.
+    Added,
+    /// The message left the mailbox.
+    Removed,
+    /// The flags and tags of the message are now these.
+    Flags(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Action(action) => write!(f, "{}", action),
+            Change::Added => write!(f, "added"),
+            Change::Removed => write!(f, "removed"),
+            Change::Flags(flags) if flags.is_empty() => write!(f, "flags: none"),
+            Change::Flags(flags) => write!(f, "flags: {}", flags),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: UnixTimestamp,
+    pub source: Source,
+    pub mailbox: String,
+    pub change: Change,
+    pub message_id: String,
+    pub subject: String,
+    pub from: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:6} {}: {} \"{}\" from {} {}",
+            melib::datetime::timestamp_to_string(self.timestamp, Some("%Y-%m-%d %H:%M:%S"), true),
+            self.source,
+            self.mailbox,
+            self.change,
+            self.subject,
+            self.from,
+            self.message_id
+        )
+    }
+}
+
+/// The flags and tags of a message as a list of names.
+pub fn describe_flags(flags: Flag, tags: &[String]) -> String {
+    [
+        (Flag::SEEN, "seen"),
+        (Flag::REPLIED, "replied"),
+        (Flag::PASSED, "passed"),
+        (Flag::FLAGGED, "flagged"),
+        (Flag::DRAFT, "draft"),
+        (Flag::TRASHED, "trashed"),
+    ]
+    .iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, name)| name.to_string())
+    .chain(tags.iter().map(|tag| format!("#{}", tag)))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+#[derive(Debug, Default)]
+pub struct Journal {
+    path: Option<PathBuf>,
+    entries: VecDeque<Entry>,
+    /// Message-IDs of messages the user acted on, with when.
+    acted_on: HashMap<String, Instant>,
+}
+
+impl Journal {
+    /// Load the journal of account `account_name`. A missing store is treated as empty and corrupt
+    /// lines are skipped.
+    pub fn new(account_name: &str) -> Self {
+        Self::load(melib::paths::state_file(Some(account_name), "activity").ok())
+    }
+
+    fn load(path: Option<PathBuf>) -> Self {
+        let mut ret = Journal {
+            path,
+            ..Journal::default()
+        };
+        if let Some(f) = ret
+            .path
+            .as_ref()
+            .filter(|p| p.exists())
+            .and_then(|p| fs::File::open(p).ok())
+        {
+            for line in io::BufReader::new(f).lines().map_while(|l| l.ok()) {
+                if let Ok(entry) = serde_json::from_str(&line) {
+                    ret.entries.push_back(entry);
+                }
+            }
+            if ret.entries.len() > MAX_ENTRIES {
+                ret.entries.drain(..ret.entries.len() - MAX_ENTRIES);
+                /* Rewrite the file with what is kept so that it doesn't grow without bound. */
+                if let Err(err) = ret.rewrite() {
+                    debug!("Could not trim activity journal: {}", err);
+                }
+            }
+        }
+        ret
+    }
+
+    /// The entries, oldest first.
+    pub fn entries(&self) -> &VecDeque<Entry> {
+        &self.entries
+    }
+
+    /// Record that the user asked for `action` on `envelope` in `mailbox`. Asking again right
+    /// after is not recorded twice.
+    pub fn record_action(&mut self, envelope: &Envelope, mailbox: &str, action: String) {
+        let message_id = envelope.message_id_display().into_owned();
+        let change = Change::Action(action);
+        let repeated = self.acted_on.contains_key(&message_id)
+            && matches!(self.entries.back(), Some(last) if last.message_id == message_id
+                && last.mailbox == mailbox
+                && last.change == change);
+        self.acted_on.insert(message_id, Instant::now());
+        if !repeated {
+            self.push(envelope, mailbox, Source::Local, change);
+        }
+    }
+
+    /// Record a change the backend reported.
+    pub fn record_change(&mut self, envelope: &Envelope, mailbox: &str, change: Change) {
+        let now = Instant::now();
+        self.acted_on
+            .retain(|_, when| now.duration_since(*when) < LOCAL_WINDOW);
+        let source = if self
+            .acted_on
+            .contains_key(envelope.message_id_display().as_ref())
+        {
+            Source::Local
+        } else {
+            Source::Remote
+        };
+        self.push(envelope, mailbox, source, change);
+    }
+
+    fn push(&mut self, envelope: &Envelope, mailbox: &str, source: Source, change: Change) {
+        let entry = Entry {
+            timestamp: melib::datetime::now(),
+            source,
+            mailbox: mailbox.to_string(),
+            change,
+            message_id: envelope.message_id_display().into_owned(),
+            subject: envelope.subject().into_owned(),
+            from: envelope.field_from_to_string(),
+        };
+        if let Err(err) = self.append(&entry) {
+            debug!("Could not write to activity journal: {}", err);
+        }
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    fn open(&self, append: bool) -> io::Result<Option<fs::File>> {
+        let path = match self.path.as_ref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .mode(0o600) // Read/write for owner only.
+            .open(path)
+            .map(Some)
+    }
+
+    fn append(&self, entry: &Entry) -> io::Result<()> {
+        if let Some(mut f) = self.open(true)? {
+            writeln!(f, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(())
+    }
+
+    fn rewrite(&self) -> io::Result<()> {
+        if let Some(f) = self.open(false)? {
+            let mut f = io::BufWriter::new(f);
+            for entry in &self.entries {
+                writeln!(f, "{}", serde_json::to_string(entry)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_activity_journal() {
+    let dir = std::env::temp_dir().join(format!("meli-activity-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("activity");
+    let _ = fs::remove_file(&path);
+    let envelope = |id: &str| {
+        Envelope::from_bytes(
+            format!(
+                "From: Alice <alice@example.com>\r\nSubject: {0}\r\nMessage-ID: <{0}@example.com>\r\n\r\nbody\r\n",
+                id
+            )
+            .as_bytes(),
+            None,
+        )
+        .unwrap()
+    };
+    let mut journal = Journal::load(Some(path.clone()));
+    journal.record_action(&envelope("a"), "INBOX", "move to Archive".to_string());
+    journal.record_action(&envelope("a"), "INBOX", "move to Archive".to_string());
+    journal.record_change(&envelope("a"), "INBOX", Change::Removed);
+    journal.record_change(&envelope("a"), "Archive", Change::Added);
+    journal.record_change(
+        &envelope("b"),
+        "INBOX",
+        Change::Flags(describe_flags(
+            Flag::SEEN | Flag::FLAGGED,
+            &["work".to_string()],
+        )),
+    );
+    assert_eq!(
+        journal
+            .entries()
+            .iter()
+            .map(|e| (e.source, e.mailbox.as_str(), e.change.to_string()))
+            .collect::<Vec<_>>(),
+        vec![
+            (Source::Local, "INBOX", "move to Archive".to_string()),
+            (Source::Local, "INBOX", "removed".to_string()),
+            (Source::Local, "Archive", "added".to_string()),
+            (
+                Source::Remote,
+                "INBOX",
+                "flags: seen, flagged, #work".to_string()
+            ),
+        ]
+    );
+
+    /* The file has the same entries, and a corrupt line doesn't lose the rest. */
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"{not json\n")
+        .unwrap();
+    let reloaded = Journal::load(Some(path));
+    assert_eq!(reloaded.entries(), journal.entries());
+    let entry = &reloaded.entries()[3];
+    assert_eq!(entry.message_id, "<b@example.com>");
+    assert_eq!(entry.subject, "b");
+    assert!(entry.to_string().ends_with(
+        "remote INBOX: flags: seen, flagged, #work \"b\" from Alice <alice@example.com> <b@example.com>"
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
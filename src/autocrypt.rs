@@ -0,0 +1,231 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Autocrypt peer state, kept from received mail.
+ *
+ * When a message is opened, the state of its sender is updated as described in [Autocrypt Level
+ * 1](https://autocrypt.org/level1.html): the key of the most recent message with an `Autocrypt`
+ * header is kept along with the date of the most recent message, with or without the header. The
+ * state is kept in a JSON file in the account's data directory, and tells whether mail to the
+ * sender can and should be encrypted.
+ */
+use crate::json_store::JsonStore;
+use melib::email::autocrypt::AutocryptHeader;
+use melib::{Result, UnixTimestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Keys older than this compared to the most recent message of their peer are stale.
+const STALE_KEY_AGE: UnixTimestamp = 35 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutocryptPeer {
+    /// Date of the most recent message from the peer.
+    pub last_seen: UnixTimestamp,
+    /// Date of the most recent message from the peer with an `Autocrypt` header.
+    pub autocrypt_timestamp: UnixTimestamp,
+    pub prefer_encrypt: bool,
+    /// The binary OpenPGP key.
+    pub keydata: Vec<u8>,
+}
+
+impl AutocryptPeer {
+    /// Whether mail to this peer can be encrypted, before taking other recipients into account.
+    pub fn recommendation(&self) -> Recommendation {
+        /* The peer has been sending mail without the header for a while: they may have switched
+         * to a client without Autocrypt and be unable to decrypt. */
+        if self.autocrypt_timestamp + STALE_KEY_AGE < self.last_seen {
+            Recommendation::Discourage
+        } else {
+            Recommendation::Available
+        }
+    }
+}
+
+/// Whether a message should be encrypted, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Recommendation {
+    /// Some recipients have no key.
+    Disable,
+    /// All recipients have a key but some might not be able to read encrypted mail.
+    Discourage,
+    Available,
+    /// Everyone prefers encrypted mail.
+    Encrypt,
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recommendation::Disable => write!(f, "disable"),
+            Recommendation::Discourage => write!(f, "discourage"),
+            Recommendation::Available => write!(f, "available"),
+            Recommendation::Encrypt => write!(f, "encrypt"),
+        }
+    }
+}
+
+/// The recommendation for a message, given the recommendation of each recipient and whether they
+/// prefer encrypted mail. `prefer_encrypt` is the preference of the sender.
+pub fn recommendation(
+    recipients: impl IntoIterator<Item = (Recommendation, bool)>,
+    prefer_encrypt: bool,
+) -> Recommendation {
+    let mut all_prefer_encrypt = prefer_encrypt;
+    let mut ret = Recommendation::Available;
+    let mut empty = true;
+    for (recommendation, prefer_encrypt) in recipients {
+        empty = false;
+        ret = std::cmp::min(ret, recommendation);
+        all_prefer_encrypt &= prefer_encrypt;
+    }
+    if empty {
+        Recommendation::Disable
+    } else if ret == Recommendation::Available && all_prefer_encrypt {
+        Recommendation::Encrypt
+    } else {
+        ret
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AutocryptPeers {
+    store: JsonStore,
+    /// Lowercase address to its announced key.
+    peers: HashMap<String, AutocryptPeer>,
+}
+
+impl AutocryptPeers {
+    /// Load the keys harvested by account `account_name`. A corrupt store is treated as empty and
+    /// isn't saved over.
+    pub fn new(account_name: &str) -> Self {
+        let (store, peers) = JsonStore::load(account_name, "autocrypt_peers");
+        AutocryptPeers { store, peers }
+    }
+
+    pub fn get(&self, address: &str) -> Option<&AutocryptPeer> {
+        self.peers.get(&address.to_lowercase())
+    }
+
+    /// Update the state of `address` with a message dated `date`, with `header` if it had one.
+    /// Returns whether the state changed.
+    pub fn update(
+        &mut self,
+        address: &str,
+        header: Option<AutocryptHeader>,
+        date: UnixTimestamp,
+    ) -> Result<bool> {
+        /* Dates in the future would keep newer messages from updating the state. */
+        let date = std::cmp::min(date, melib::datetime::now());
+        let address = address.to_lowercase();
+        let peer = match (self.peers.get_mut(&address), header) {
+            (Some(peer), Some(header)) if date > peer.autocrypt_timestamp => {
+                peer.autocrypt_timestamp = date;
+                peer.prefer_encrypt = header.prefer_encrypt;
+                peer.keydata = header.keydata;
+                peer.last_seen = std::cmp::max(peer.last_seen, date);
+                return self.save().map(|()| true);
+            }
+            (Some(peer), _) if date > peer.last_seen => {
+                peer.last_seen = date;
+                return self.save().map(|()| true);
+            }
+            (Some(_), _) | (None, None) => return Ok(false),
+            (None, Some(header)) => AutocryptPeer {
+                last_seen: date,
+                autocrypt_timestamp: date,
+                prefer_encrypt: header.prefer_encrypt,
+                keydata: header.keydata,
+            },
+        };
+        self.peers.insert(address, peer);
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.peers)
+    }
+}
+
+#[test]
+fn test_autocrypt_peers_update() {
+    let header = |keydata: &[u8]| {
+        Some(AutocryptHeader {
+            addr: "bob@example.com".to_string(),
+            prefer_encrypt: true,
+            keydata: keydata.to_vec(),
+        })
+    };
+    let mut peers = AutocryptPeers::default();
+    /* Without a data directory the key is kept but can't be saved. */
+    assert!(peers.update("bob@example.com", header(b"old"), 10).is_err());
+    assert_eq!(peers.get("Bob@example.com").unwrap().keydata, b"old");
+    let dir = std::env::temp_dir().join(format!("meli-autocrypt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    peers.store =
+        JsonStore::load_path::<HashMap<String, AutocryptPeer>>(dir.join("autocrypt_peers")).0;
+    assert!(peers.update("bob@example.com", header(b"new"), 20).unwrap());
+    assert!(!peers.update("bob@example.com", header(b"new"), 20).unwrap());
+    /* Older messages don't replace the key of newer ones. */
+    assert!(!peers
+        .update("bob@example.com", header(b"older"), 5)
+        .unwrap());
+    assert_eq!(peers.get("bob@example.com").unwrap().keydata, b"new");
+    /* Messages without the header only count as seen. */
+    assert!(peers.update("bob@example.com", None, 30).unwrap());
+    assert!(!peers.update("carol@example.com", None, 30).unwrap());
+    let peer = peers.get("bob@example.com").unwrap();
+    assert_eq!((peer.last_seen, peer.autocrypt_timestamp), (30, 20));
+    assert_eq!(peer.recommendation(), Recommendation::Available);
+    assert!(peers
+        .update("bob@example.com", None, 20 + STALE_KEY_AGE + 1)
+        .unwrap());
+    assert_eq!(
+        peers.get("bob@example.com").unwrap().recommendation(),
+        Recommendation::Discourage
+    );
+    let stored: HashMap<String, AutocryptPeer> =
+        serde_json::from_reader(std::fs::File::open(peers.store.path().unwrap()).unwrap()).unwrap();
+    assert_eq!(stored["bob@example.com"].autocrypt_timestamp, 20);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_autocrypt_recommendation() {
+    use Recommendation::*;
+    assert_eq!(recommendation(vec![], true), Disable);
+    assert_eq!(recommendation(vec![(Available, true)], true), Encrypt);
+    assert_eq!(recommendation(vec![(Available, true)], false), Available);
+    assert_eq!(
+        recommendation(vec![(Available, true), (Available, false)], true),
+        Available
+    );
+    assert_eq!(
+        recommendation(vec![(Available, true), (Discourage, true)], true),
+        Discourage
+    );
+    assert_eq!(
+        recommendation(vec![(Discourage, true), (Disable, true)], true),
+        Disable
+    );
+}
@@ -64,6 +64,9 @@ use crate::command::*;
 pub mod state;
 use crate::state::*;
 
+pub mod session;
+use crate::session::*;
+
 pub mod components;
 use crate::components::*;
 
@@ -74,8 +77,25 @@ use crate::conf::*;
 #[cfg(feature = "sqlite3")]
 pub mod sqlite3;
 
+pub mod activity;
+#[cfg(feature = "gpgme")]
+pub mod autocrypt;
+pub mod crash;
 pub mod jobs;
+pub mod json_store;
+pub mod mailbox_properties;
 pub mod mailcap;
+pub mod manpages;
+use crate::manpages::{parse_manpage, ManPages};
+pub mod notes;
+pub mod phishing;
+pub mod power;
+pub mod profiling;
+pub mod recipients;
+pub mod recorder;
+#[cfg(test)]
+pub mod testing;
+pub mod thread_marks;
 
 use std::os::raw::c_int;
 
@@ -103,8 +123,9 @@ fn notify(
     std::thread::spawn(move || {
         let mut ctr = 0;
         loop {
-            ctr %= 3;
-            if ctr == 0 {
+            /* Pulse every 300ms, or every 1.5s while on battery. */
+            ctr %= 15;
+            if ctr % (if crate::power::is_throttled() { 15 } else { 3 }) == 0 {
                 let _ = sender
                     .send_timeout(ThreadEvent::Pulse, Duration::from_millis(500))
                     .ok();
@@ -121,31 +142,8 @@ fn notify(
     Ok(r)
 }
 
-fn parse_manpage(src: &str) -> Result<ManPages> {
-    match src {
-        "" | "meli" | "main" => Ok(ManPages::Main),
-        "meli.conf" | "conf" | "config" | "configuration" => Ok(ManPages::Conf),
-        "meli-themes" | "themes" | "theming" | "theme" => Ok(ManPages::Themes),
-        _ => Err(MeliError::new(format!(
-            "Invalid documentation page: {}",
-            src
-        ))),
-    }
-}
-
 use structopt::StructOpt;
 
-#[derive(Copy, Clone, Debug)]
-/// Choose manpage
-enum ManPages {
-    /// meli(1)
-    Main = 0,
-    /// meli.conf(5)
-    Conf = 1,
-    /// meli-themes(5)
-    Themes = 2,
-}
-
 #[derive(Debug, StructOpt)]
 #[structopt(name = "meli", about = "terminal mail client", version_short = "v")]
 struct Opt {
@@ -153,6 +151,26 @@ struct Opt {
     #[structopt(short, long, parse(from_os_str))]
     config: Option<PathBuf>,
 
+    /// keep indexes, caches, sessions and other state in DIR instead of $XDG_STATE_HOME/meli
+    #[structopt(long, value_name = "DIR", parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+
+    /// use the configuration file and state of profile NAME, to keep separate environments
+    #[structopt(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// print the time each start-up phase took to stderr on exit
+    #[structopt(long)]
+    profile_startup: bool,
+
+    /// record user input and events to FILE, to reproduce a problem with --replay
+    #[structopt(long, value_name = "FILE", parse(from_os_str))]
+    record_events: Option<PathBuf>,
+
+    /// type the input recorded in FILE with --record-events again, with the same timing
+    #[structopt(long, value_name = "FILE", parse(from_os_str))]
+    replay: Option<PathBuf>,
+
     #[structopt(subcommand)]
     subcommand: Option<SubCommand>,
 }
@@ -185,6 +203,8 @@ enum SubCommand {
         #[structopt(value_name = "INPUT", parse(from_os_str))]
         path: PathBuf,
     },
+    /// print the locations of the configuration file and of the state of meli and its accounts.
+    Paths,
 }
 
 #[derive(Debug, StructOpt)]
@@ -198,7 +218,13 @@ struct ManOpt {
 
 fn main() {
     let opt = Opt::from_args();
-    ::std::process::exit(match run_app(opt) {
+    remove_stale_temp_dirs();
+    let ret = run_app(opt);
+    remove_temp_dir();
+    if let Some(report) = profiling::report() {
+        eprint!("{}", report);
+    }
+    ::std::process::exit(match ret {
         Ok(()) => 0,
         Err(err) => {
             eprintln!("{}", err);
@@ -208,9 +234,28 @@ fn main() {
 }
 
 fn run_app(opt: Opt) -> Result<()> {
+    if opt.profile_startup {
+        profiling::enable();
+    }
     if let Some(config_location) = opt.config.as_ref() {
         std::env::set_var("MELI_CONFIG", config_location);
     }
+    if let Some(data_dir) = opt.data_dir.as_ref() {
+        std::env::set_var(melib::paths::DATA_DIR_VAR, data_dir);
+    }
+    if let Some(profile) = opt.profile.as_ref() {
+        std::env::set_var(melib::paths::PROFILE_VAR, profile);
+    }
+    melib::paths::profile()?;
+
+    let replay = if let Some(path) = opt.replay.as_ref() {
+        Some(recorder::Replay::load(path)?)
+    } else {
+        None
+    };
+    if let Some(path) = opt.record_events.as_ref() {
+        recorder::enable(path)?;
+    }
 
     match opt.subcommand {
         Some(SubCommand::TestConfig { path }) => {
@@ -237,27 +282,9 @@ fn run_app(opt: Opt) -> Result<()> {
             conf::create_config_file(&config_path)?;
             return Ok(());
         }
-        #[cfg(feature = "cli-docs")]
         Some(SubCommand::Man(manopt)) => {
             let ManOpt { page, no_raw } = manopt;
-            const MANPAGES: [&'static [u8]; 3] = [
-                include_bytes!(concat!(env!("OUT_DIR"), "/meli.txt.gz")),
-                include_bytes!(concat!(env!("OUT_DIR"), "/meli.conf.txt.gz")),
-                include_bytes!(concat!(env!("OUT_DIR"), "/meli-themes.txt.gz")),
-            ];
-            use flate2::bufread::GzDecoder;
-            use std::io::prelude::*;
-            let mut gz = GzDecoder::new(MANPAGES[page as usize]);
-            let mut v = String::with_capacity(
-                str::parse::<usize>(unsafe {
-                    std::str::from_utf8_unchecked(gz.header().unwrap().comment().unwrap())
-                })
-                .expect(&format!(
-                    "{:?} was not compressed with size comment header",
-                    page
-                )),
-            );
-            gz.read_to_string(&mut v)?;
+            let v = manpages::read(page)?;
 
             if let Some(no_raw) = no_raw {
                 match no_raw {
@@ -275,6 +302,7 @@ fn run_app(opt: Opt) -> Result<()> {
                 }
             }
 
+            use std::io::Write;
             use std::process::{Command, Stdio};
             let mut handle = Command::new(std::env::var("PAGER").unwrap_or("more".to_string()))
                 .stdin(Stdio::piped())
@@ -286,10 +314,6 @@ fn run_app(opt: Opt) -> Result<()> {
 
             return Ok(());
         }
-        #[cfg(not(feature = "cli-docs"))]
-        Some(SubCommand::Man(_manopt)) => {
-            return Err(MeliError::new("error: this version of meli was not build with embedded documentation. You might have it installed as manpages (eg `man meli`), otherwise check https://meli.delivery"));
-        }
         Some(SubCommand::PrintLoadedThemes) => {
             let s = conf::FileSettings::new()?;
             print!("{}", s.terminal.themes.to_string());
@@ -299,6 +323,29 @@ fn run_app(opt: Opt) -> Result<()> {
             print!("{}", conf::Themes::default().key_to_string("dark", false));
             return Ok(());
         }
+        Some(SubCommand::Paths) => {
+            if let Some(profile) = melib::paths::profile()? {
+                println!("profile: {}", profile);
+            }
+            /* Only resolve the paths, without creating directories or moving files. */
+            let config_path = crate::conf::config_file_path()?;
+            println!("configuration: {}", config_path.display());
+            println!("state: {}", melib::paths::state_dir_path()?.display());
+            println!(
+                "log: {}",
+                melib::paths::state_file_path(None, "meli.log")?.display()
+            );
+            if config_path.exists() {
+                for name in conf::FileSettings::validate(config_path)?.accounts.keys() {
+                    println!(
+                        "account {}: {}",
+                        name,
+                        melib::paths::account_dir_path(name)?.display()
+                    );
+                }
+            }
+            return Ok(());
+        }
         Some(SubCommand::View { ref path }) => {
             if !path.exists() {
                 return Err(MeliError::new(format!(
@@ -324,10 +371,17 @@ fn run_app(opt: Opt) -> Result<()> {
         signal_hook::SIGWINCH,
         /* Catch SIGCHLD to handle embed applications status change */
         signal_hook::SIGCHLD,
+        /* Catch SIGUSR1 to refresh all accounts and select the dark or light theme again */
+        signal_hook::SIGUSR1,
+        /* Catch SIGUSR2 to reload the configuration */
+        signal_hook::SIGUSR2,
     ];
 
     let signal_recvr = notify(signals, sender.clone())?;
 
+    /* Restore the terminal and write a crash report on panic. */
+    crash::install_panic_hook();
+
     /* Create the application State. */
     let mut state;
 
@@ -346,13 +400,23 @@ fn run_app(opt: Opt) -> Result<()> {
         state = State::new(None, sender, receiver.clone())?;
         #[cfg(feature = "svgscreenshot")]
         state.register_component(Box::new(components::svg::SVGScreenshotFilter::new()));
-        let window = Box::new(Tabbed::new(
-            vec![
-                Box::new(listing::Listing::new(&mut state.context)),
-                Box::new(ContactList::new(&state.context)),
-            ],
-            &state.context,
-        ));
+        let mut listing = listing::Listing::new(&mut state.context);
+        let session = if state.context.settings.terminal.restore_session {
+            Session::load()
+        } else {
+            None
+        };
+        if let Some(listing_session) = session.as_ref().and_then(|s| s.listing.clone()) {
+            listing.restore_session(listing_session);
+        }
+        let mut children: Vec<Box<dyn Component>> = vec![
+            Box::new(listing),
+            Box::new(ContactList::new(&state.context)),
+        ];
+        if let Some(session) = session {
+            children.extend(session.open_tabs(&mut state.context));
+        }
+        let window = Box::new(Tabbed::new(children, &state.context));
 
         let status_bar = Box::new(StatusBar::new(&state.context, window));
         state.register_component(status_bar);
@@ -367,24 +431,25 @@ fn run_app(opt: Opt) -> Result<()> {
         state.register_component(Box::new(
             components::notifications::NotificationCommand::new(),
         ));
+        if let Err(err) = conf::watch_config_files(state.sender()) {
+            debug!("Could not watch configuration files: {}", err);
+        }
+    }
+    if let Some(replay) = replay {
+        replay.start(state.sender());
     }
-    let enter_command_mode: Key = state
-        .context
-        .settings
-        .shortcuts
-        .general
-        .enter_command_mode
-        .clone();
     let quit_key: Key = state.context.settings.shortcuts.general.quit.clone();
 
     /* Keep track of the input mode. See UIMode for details */
     'main: loop {
         state.render();
+        profiling::mark_once("first draw");
 
         'inner: loop {
             /* Check if any components have sent reply events to State. */
             let events: smallvec::SmallVec<[UIEvent; 8]> = state.context.replies();
             for e in events {
+                recorder::record_reply(&e);
                 state.rcv_event(e);
             }
             state.redraw();
@@ -396,7 +461,12 @@ fn run_app(opt: Opt) -> Result<()> {
                          Ok(ThreadEvent::Pulse) | Ok(ThreadEvent::UIEvent(UIEvent::Timer(_))) => {},
                         _ => {debug!(&r);}
                     }
-                    match r.unwrap() {
+                    let r = r.unwrap();
+                    match r {
+                        ThreadEvent::Pulse | ThreadEvent::UIEvent(UIEvent::Timer(_)) => {},
+                        ref event => recorder::record_thread_event(event),
+                    }
+                    match r {
                         ThreadEvent::Input((Key::Ctrl('z'), _)) if state.mode != UIMode::Embed => {
                             state.switch_to_main_screen();
                             //_thread_handler.join().expect("Couldn't join on the associated thread");
@@ -420,59 +490,22 @@ fn run_app(opt: Opt) -> Result<()> {
                         },
                         ThreadEvent::Input((k, r)) => {
                             match state.mode {
-                                UIMode::Normal => {
-                                    match k {
-                                        _ if k == quit_key => {
-                                            if state.can_quit_cleanly() {
-                                                drop(state);
-                                                break 'main;
-                                            } else {
-                                                state.redraw();
-                                            }
-                                        },
-                                        _ if k == enter_command_mode => {
-                                            state.mode = UIMode::Command;
-                                            state.rcv_event(UIEvent::ChangeMode(UIMode::Command));
-                                            state.redraw();
+                                UIMode::Normal if k == quit_key => {
+                                    if state.can_quit_cleanly() {
+                                        if state.context.settings.terminal.restore_session {
+                                            state.save_session();
                                         }
-                                        key  => {
-                                            state.rcv_event(UIEvent::Input(key));
-                                            state.redraw();
-                                        },
+                                        state.save_read_positions();
+                                        drop(state);
+                                        break 'main;
+                                    } else {
+                                        state.redraw();
                                     }
                                 },
-                                UIMode::Insert => {
-                                    match k {
-                                        Key::Esc => {
-                                            state.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
-                                            state.redraw();
-                                        },
-                                        k => {
-                                            state.rcv_event(UIEvent::InsertInput(k));
-                                            state.redraw();
-                                        },
-                                    }
-                                }
-                                UIMode::Command => {
-                                    match k {
-                                        Key::Char('\n') => {
-                                            state.mode = UIMode::Normal;
-                                            state.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
-                                            state.redraw();
-                                        },
-                                        k => {
-                                            state.rcv_event(UIEvent::CmdInput(k));
-                                            state.redraw();
-                                        },
-                                    }
-                                },
-                                UIMode::Embed => {
-                                    state.rcv_event(UIEvent::EmbedInput((k,r)));
-                                    state.redraw();
-                                },
                                 UIMode::Fork => {
                                     break 'inner; // `goto` 'reap loop, and wait on child.
                                 },
+                                _ => state.process_input(k, r),
                             }
                         },
                         ThreadEvent::RefreshMailbox(event) => {
@@ -505,7 +538,9 @@ fn run_app(opt: Opt) -> Result<()> {
                     }
                 },
                 recv(signal_recvr) -> sig => {
-                    match sig.unwrap() {
+                    let sig = sig.unwrap();
+                    recorder::record_signal(sig);
+                    match sig {
                         signal_hook::SIGWINCH => {
                             if state.mode != UIMode::Fork  {
                                 state.update_size();
@@ -518,6 +553,21 @@ fn run_app(opt: Opt) -> Result<()> {
                             state.redraw();
 
                         }
+                        signal_hook::SIGUSR1 => {
+                            for account in state.context.accounts.values_mut() {
+                                account.refresh_all();
+                            }
+                            if state.mode != UIMode::Fork && state.mode != UIMode::Embed {
+                                state.update_auto_theme();
+                                state.redraw();
+                            }
+                        }
+                        signal_hook::SIGUSR2 => {
+                            if state.mode != UIMode::Fork {
+                                state.reload_configuration();
+                                state.redraw();
+                            }
+                        }
                         other => {
                             debug!("got other signal: {:?}", other);
                         }
@@ -27,8 +27,8 @@ use melib::nom::{
     branch::alt,
     bytes::complete::{is_a, is_not, tag, take_until},
     character::complete::{digit1, not_line_ending},
-    combinator::{map, map_res},
-    multi::separated_list,
+    combinator::{map, map_res, opt},
+    multi::{many0, many1, separated_list},
     sequence::{pair, preceded, separated_pair},
     IResult,
 };
@@ -41,11 +41,16 @@ pub mod history;
 pub use crate::actions::AccountAction::{self, *};
 pub use crate::actions::Action::{self, *};
 pub use crate::actions::ComposeAction::{self, *};
+pub use crate::actions::ConversationsGrouping;
 pub use crate::actions::ListingAction::{self, *};
 pub use crate::actions::MailingListAction::{self, *};
+pub use crate::actions::SelectionChange;
 pub use crate::actions::TabAction::{self, *};
 pub use crate::actions::TagAction::{self, *};
+pub use crate::actions::UnreadScope;
 pub use crate::actions::ViewAction::{self, *};
+pub use crate::actions::YankTarget;
+use crate::manpages::ManPages;
 use std::str::FromStr;
 
 /// Helper macro to convert an array of tokens into a TokenStream
@@ -343,8 +348,8 @@ define_commands!([
                    )
                  },
                  { tags: ["subsort"],
-                   desc: "subsort [date/subject] [asc/desc], sorts first level replies in threads.",
-                   tokens: &[One(Literal("subsort")), One(Alternatives(&[to_stream!(One(Literal("date"))), to_stream!(One(Literal("subject")))])), One(Alternatives(&[to_stream!(One(Literal("asc"))), to_stream!(One(Literal("desc")))])) ],
+                   desc: "subsort [date/subject/from] [asc/desc], sorts first level replies in threads.",
+                   tokens: &[One(Literal("subsort")), One(Alternatives(&[to_stream!(One(Literal("date"))), to_stream!(One(Literal("subject"))), to_stream!(One(Literal("from")))])), One(Alternatives(&[to_stream!(One(Literal("asc"))), to_stream!(One(Literal("desc")))])) ],
                    parser: (
                        fn subsort(input: &[u8]) -> IResult<&[u8], Action> {
                            let (input, _) = tag("subsort")(input)?;
@@ -356,8 +361,8 @@ define_commands!([
                    )
                  },
                 { tags: ["sort"],
-                  desc: "sort [date/subject] [asc/desc], sorts threads.",
-                   tokens: &[One(Literal("sort")), One(Alternatives(&[to_stream!(One(Literal("date"))), to_stream!(One(Literal("subject")))])), One(Alternatives(&[to_stream!(One(Literal("asc"))), to_stream!(One(Literal("desc")))])) ],
+                  desc: "sort [date/subject/from] [asc/desc], sorts threads.",
+                   tokens: &[One(Literal("sort")), One(Alternatives(&[to_stream!(One(Literal("date"))), to_stream!(One(Literal("subject"))), to_stream!(One(Literal("from")))])), One(Alternatives(&[to_stream!(One(Literal("asc"))), to_stream!(One(Literal("desc")))])) ],
                   parser: (
                       fn sort(input: &[u8]) -> IResult<&[u8], Action> {
                           let (input, _) = tag("sort")(input)?;
@@ -380,19 +385,64 @@ define_commands!([
                       }
                   )
                 },
-                { tags: ["toggle thread_snooze"],
-                  desc: "turn off new notifications for this thread",
-                  tokens: &[One(Literal("toggle thread_snooze"))],
+                { tags: ["toggle thread_snooze", "toggle thread_mute"],
+                  desc: "mute this thread: turn off new notifications and unseen counting for it",
+                  tokens: &[One(Literal("toggle")), One(Alternatives(&[to_stream!(One(Literal("thread_snooze"))), to_stream!(One(Literal("thread_mute")))]))],
                   parser: (
                       fn toggle_thread_snooze(input: &[u8]) -> IResult<&[u8], Action> {
                           let (input, _) = tag("toggle")(input)?;
                           let (input, _) = is_a(" ")(input)?;
-                          let (input, _) = tag("thread_snooze")(input)?;
+                          let (input, _) = alt((tag("thread_snooze"), tag("thread_mute")))(input)?;
                           let (input, _) = eof(input)?;
                           Ok((input, Listing(ToggleThreadSnooze)))
                       }
                   )
                 },
+                { tags: ["toggle thread_watch"],
+                  desc: "watch this thread: always issue notifications for new messages in it",
+                  tokens: &[One(Literal("toggle")), One(Literal("thread_watch"))],
+                  parser: (
+                      fn toggle_thread_watch(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("thread_watch")(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(ToggleThreadWatch)))
+                      }
+                  )
+                },
+                { tags: ["group-by "],
+                  desc: "group-by thread | correspondent, group the conversations listing by thread or by the address of the other party",
+                  tokens: &[One(Literal("group-by")), One(Alternatives(&[to_stream!(One(Literal("thread"))), to_stream!(One(Literal("correspondent")))]))],
+                  parser: (
+                      fn group_by(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("group-by")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, grouping) = alt((
+                              map(tag("thread"), |_| ConversationsGrouping::Threads),
+                              map(tag("correspondent"), |_| ConversationsGrouping::Correspondents),
+                          ))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(SetGrouping(grouping))))
+                      }
+                  )
+                },
+                { tags: ["toggle preview_pane", "toggle preview_pane_orientation"],
+                  desc: "toggle preview_pane [orientation], show opened entries next to the listing or in its place, or switch between a vertical and horizontal split",
+                  tokens: &[One(Literal("toggle")), One(Alternatives(&[to_stream!(One(Literal("preview_pane"))), to_stream!(One(Literal("preview_pane_orientation")))]))],
+                  parser: (
+                      fn toggle_preview_pane(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, ret) = alt((
+                              map(tag("preview_pane_orientation"), |_| Listing(TogglePreviewPaneOrientation)),
+                              map(tag("preview_pane"), |_| Listing(TogglePreviewPane)),
+                          ))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, ret))
+                      }
+                  )
+                },
                 { tags: ["search"],
                   desc: "search <TERM>, searches list with given term",
                   tokens: &[One(Literal("search")), One(RestOfStringValue)],
@@ -406,16 +456,62 @@ define_commands!([
                       }
                   )
                 },
+                { tags: ["filter-preset "],
+                  desc: "filter-preset <NAME>, searches list with the query of the given preset of listing.filter_presets",
+                  tokens: &[One(Literal("filter-preset")), One(RestOfStringValue)],
+                  parser:(
+                      fn filter_preset(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("filter-preset")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(FilterPreset(string.trim().to_string()))))
+                      }
+                  )
+                },
+                { tags: ["goto date "],
+                  desc: "goto date <DATE>, moves to the first entry dated DATE (YYYY, YYYY-MM or YYYY-MM-DD), or since:DATE or before:DATE where DATE can also be a duration ago like 2w",
+                  tokens: &[One(Literal("goto")), One(Literal("date")), One(RestOfStringValue)],
+                  parser:(
+                      fn goto_date(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("goto")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("date")(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(GotoDate(string.trim().to_string()))))
+                      }
+                  )
+                },
+                { tags: ["limit "],
+                  desc: "limit <DATE>, limits list to the entries dated DATE, see goto date",
+                  tokens: &[One(Literal("limit")), One(RestOfStringValue)],
+                  parser:(
+                      fn limit(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("limit")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(Limit(string.trim().to_string()))))
+                      }
+                  )
+                },
                 { tags: ["select"],
-                  desc: "select <TERM>, selects envelopes matching with given term",
-                  tokens: &[One(Literal("select")), One(RestOfStringValue)],
+                  desc: "select <TERM>|all|none|invert, selects envelopes matching with given term, all entries, no entries or the entries that are not selected",
+                  tokens: &[One(Literal("select")), One(Alternatives(&[to_stream!(One(Literal("all"))), to_stream!(One(Literal("none"))), to_stream!(One(Literal("invert"))), to_stream!(One(RestOfStringValue))]))],
                   parser:(
                       fn select(input: &[u8]) -> IResult<&[u8], Action> {
                           let (input, _) = tag("select")(input.trim())?;
                           let (input, _) = is_a(" ")(input)?;
                           let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
                           let (input, _) = eof(input)?;
-                          Ok((input, Listing(Select(String::from(string)))))
+                          Ok((input, Listing(match string.trim() {
+                              "all" => ChangeSelection(SelectionChange::All),
+                              "none" => ChangeSelection(SelectionChange::None),
+                              "invert" => ChangeSelection(SelectionChange::Invert),
+                              _ => Select(String::from(string)),
+                          })))
                       }
                   )
                 },
@@ -432,6 +528,83 @@ define_commands!([
                       }
                   )
                 },
+                { tags: ["refresh"],
+                  desc: "refresh [MAILBOX], refresh the current mailbox or MAILBOX of the current account",
+                  tokens: &[One(Literal("refresh")), ZeroOrOne(MailboxPath)],
+                  parser:(
+                      fn refresh<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                          alt((
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("refresh")(input.trim())?;
+                                      let (input, _) = is_a(" ")(input)?;
+                                      let (input, path) = quoted_argument(input)?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, Listing(Refresh(Some(path.to_string())))))
+                                  },
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("refresh")(input.trim())?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, Listing(Refresh(None))))
+                                  }
+                          ))(input)
+                      }
+                  )
+                },
+                { tags: ["refresh-all"],
+                  desc: "refresh-all, refresh all loaded mailboxes of the current account",
+                  tokens: &[One(Literal("refresh-all"))],
+                  parser:(
+                      fn refresh_all(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("refresh-all")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(RefreshAll)))
+                      }
+                  )
+                },
+                { tags: ["next-unread"],
+                  desc: "next-unread [mailbox|all], go to the next unread entry in the current mailbox, the next mailbox with unread messages, or the next unread entry in any account",
+                  tokens: &[One(Literal("next-unread")), ZeroOrOne(Alternatives(&[to_stream!(One(Literal("mailbox"))), to_stream!(One(Literal("all")))]))],
+                  parser:(
+                      fn next_unread(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("next-unread")(input.trim())?;
+                          let (input, scope) = opt(preceded(
+                              is_a(" "),
+                              alt((
+                                  map(tag("mailbox"), |_| UnreadScope::NextMailbox),
+                                  map(tag("all"), |_| UnreadScope::All),
+                              )),
+                          ))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(NextUnread(scope.unwrap_or(UnreadScope::Mailbox)))))
+                      }
+                  )
+                },
+                { tags: ["bulk-reply-template "],
+                  desc: "bulk-reply-template NAME [send], reply to selected envelopes with template NAME from `composing.reply_templates`, opening each reply in a new tab or sending them all",
+                  tokens: &[One(Literal("bulk-reply-template")), One(AlphanumericStringValue), ZeroOrOne(Literal("send"))],
+                  parser:(
+                      fn bulk_reply_template<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                          alt((
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("bulk-reply-template")(input.trim())?;
+                                      let (input, _) = is_a(" ")(input)?;
+                                      let (input, name) = quoted_argument(input)?;
+                                      let (input, _) = is_a(" ")(input)?;
+                                      let (input, _) = tag("send")(input)?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, Listing(BulkReplyTemplate(name.to_string(), true))))
+                                  },
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("bulk-reply-template")(input.trim())?;
+                                      let (input, _) = is_a(" ")(input)?;
+                                      let (input, name) = quoted_argument(input)?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, Listing(BulkReplyTemplate(name.to_string(), false))))
+                                  }
+                          ))(input)
+                      }
+                  )
+                },
                 { tags: ["list-archive", "list-post", "list-unsubscribe", "list-"],
                   desc: "list-[unsubscribe/post/archive]",
                   tokens: &[One(Alternatives(&[to_stream!(One(Literal("list-archive"))), to_stream!(One(Literal("list-post"))), to_stream!(One(Literal("list-unsubscribe")))]))],
@@ -598,6 +771,19 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["toggle markdown"],
+                  desc: "toggle writing this draft in Markdown, sent with an HTML rendering",
+                  tokens: &[One(Literal("toggle")), One(Literal("markdown"))],
+                  parser:(
+                      fn toggle_markdown(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("markdown")(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(ToggleMarkdown)))
+                      }
+                  )
+                },
                 { tags: ["create-mailbox "],
                   desc: "create-mailbox ACCOUNT MAILBOX_PATH",
                   tokens: &[One(Literal("create-mailbox")), One(AccountName), One(MailboxPath)],
@@ -675,6 +861,21 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["mailbox-properties "],
+                  desc: "mailbox-properties ACCOUNT MAILBOX_PATH, view and edit the ignore, notify, autoload and usage properties of a mailbox",
+                  tokens: &[One(Literal("mailbox-properties")), One(AccountName), One(MailboxPath)],
+                  parser:(
+                      fn mailbox_properties(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("mailbox-properties")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, path) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Mailbox(account.to_string(), MailboxOperation::Properties(path.to_string()))))
+                      }
+                  )
+                },
                 { tags: ["reindex "],
                   desc: "reindex ACCOUNT, rebuild account cache in the background",
                   tokens: &[One(Literal("reindex")), One(AccountName)],
@@ -688,6 +889,124 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["stats "],
+                  desc: "stats ACCOUNT, show per-mailbox message counts and storage usage of account",
+                  tokens: &[One(Literal("stats")), One(AccountName)],
+                  parser:(
+                      fn stats(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("stats")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), Stats)))
+                      }
+                  )
+                },
+                { tags: ["activity "],
+                  desc: "activity ACCOUNT, show what happened to the account's messages: local actions and remote changes",
+                  tokens: &[One(Literal("activity")), One(AccountName)],
+                  parser:(
+                      fn activity(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("activity")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), Activity)))
+                      }
+                  )
+                },
+                { tags: ["doctor "],
+                  desc: "doctor ACCOUNT, check the connection to the account's server step by step and show a report",
+                  tokens: &[One(Literal("doctor")), One(AccountName)],
+                  parser:(
+                      fn doctor(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("doctor")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), Doctor)))
+                      }
+                  )
+                },
+                { tags: ["empty-trash "],
+                  desc: "empty-trash ACCOUNT, delete all messages in the account's trash mailbox",
+                  tokens: &[One(Literal("empty-trash")), One(AccountName)],
+                  parser:(
+                      fn empty_trash(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("empty-trash")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), EmptyTrash)))
+                      }
+                  )
+                },
+                { tags: ["sync "],
+                  desc: "sync ACCOUNT, run the account's sync_command and refresh its mailboxes",
+                  tokens: &[One(Literal("sync")), One(AccountName)],
+                  parser:(
+                      fn sync(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("sync")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), Synchronize)))
+                      }
+                  )
+                },
+                { tags: ["empty-junk "],
+                  desc: "empty-junk ACCOUNT, delete all messages in the account's junk mailbox",
+                  tokens: &[One(Literal("empty-junk")), One(AccountName)],
+                  parser:(
+                      fn empty_junk(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("empty-junk")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), EmptyJunk)))
+                      }
+                  )
+                },
+                { tags: ["backend "],
+                  desc: "backend ACCOUNT [ACTION [ARGS]], run an action specific to the account's backend, or list them",
+                  tokens: &[One(Literal("backend")), One(AccountName), ZeroOrOne(AlphanumericStringValue), ZeroOrMore(QuotedStringValue)],
+                  parser:(
+                      fn backend(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("backend")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, action) = opt(preceded(is_a(" "), quoted_argument))(input)?;
+                          let (input, args) = many0(preceded(is_a(" "), quoted_argument))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), Backend(action.map(String::from), args.into_iter().map(String::from).collect()))))
+                      }
+                  )
+                },
+                { tags: ["autocrypt-setup-message "],
+                  desc: "autocrypt-setup-message ACCOUNT, compose an Autocrypt Setup Message with the account's secret key to set up other clients",
+                  tokens: &[One(Literal("autocrypt-setup-message")), One(AccountName)],
+                  parser:(
+                      fn autocrypt_setup_message(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("autocrypt-setup-message")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), AutocryptSetupMessage)))
+                      }
+                  )
+                },
+                { tags: ["autocrypt-import "],
+                  desc: "autocrypt-import SETUP-CODE, import the secret key of the viewed Autocrypt Setup Message",
+                  tokens: &[One(Literal("autocrypt-import")), OneOrMore(QuotedStringValue)],
+                  parser:(
+                      fn autocrypt_import(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("autocrypt-import")(input.trim())?;
+                          let (input, code) = many1(preceded(is_a(" "), quoted_argument))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(ImportAutocryptSetup(code.concat()))))
+                      }
+                  )
+                },
                 { tags: ["open-in-tab"],
                   desc: "opens envelope view in new tab",
                   tokens: &[One(Literal("open-in-tab"))],
@@ -727,6 +1046,95 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["export-ics"],
+                  desc: "export-ics [INDEX], pipe calendar events of (all or INDEX) text/calendar attachments to `pager.ics_export_command`",
+                  tokens: &[One(Literal("export-ics")), ZeroOrOne(AttachmentIndexValue)],
+                  parser:(
+                      fn export_ics<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                          alt((
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("export-ics")(input.trim())?;
+                                      let (input, _) = is_a(" ")(input)?;
+                                      let (input, idx) = map_res(quoted_argument, usize::from_str)(input)?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, View(ExportIcs(Some(idx)))))
+                                  },
+                                  |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                      let (input, _) = tag("export-ics")(input.trim())?;
+                                      let (input, _) = eof(input)?;
+                                      Ok((input, View(ExportIcs(None))))
+                                  }
+                          ))(input)
+                      }
+                  )
+                },
+                { tags: ["note "],
+                  desc: "note TEXT, attaches a private note to the viewed message, replacing any previous one",
+                  tokens: &[One(Literal("note")), One(RestOfStringValue)],
+                  parser:(
+                      fn set_note(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("note")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(SetNote(string.trim().to_string()))))
+                      }
+                  )
+                },
+                { tags: ["remove-note"],
+                  desc: "remove-note, removes the private note of the viewed message",
+                  tokens: &[One(Literal("remove-note"))],
+                  parser:(
+                      fn remove_note(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("remove-note")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(RemoveNote)))
+                      }
+                  )
+                },
+                { tags: ["dismiss-warnings"],
+                  desc: "dismiss-warnings, stops warning that the sender of the viewed message may be impersonating someone",
+                  tokens: &[One(Literal("dismiss-warnings"))],
+                  parser:(
+                      fn dismiss_warnings(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("dismiss-warnings")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(DismissWarnings)))
+                      }
+                  )
+                },
+                { tags: ["thread-search "],
+                  desc: "thread-search <TERM>, finds the messages of the viewed thread that contain the given term and highlights it",
+                  tokens: &[One(Literal("thread-search")), One(RestOfStringValue)],
+                  parser:(
+                      fn thread_search(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("thread-search")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, string) = map_res(not_line_ending, std::str::from_utf8)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(SearchThread(String::from(string)))))
+                      }
+                  )
+                },
+                { tags: ["yank "],
+                  desc: "yank message-id|sender|subject|body|link INDEX, copies part of the viewed message to the clipboard",
+                  tokens: &[One(Literal("yank")), One(Alternatives(&[to_stream!(One(Literal("message-id"))), to_stream!(One(Literal("sender"))), to_stream!(One(Literal("subject"))), to_stream!(One(Literal("body"))), to_stream!(One(Literal("link")), One(IndexValue))]))],
+                  parser:(
+                      fn yank(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("yank")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, target) = alt((
+                              map(tag("message-id"), |_| YankTarget::MessageId),
+                              map(tag("sender"), |_| YankTarget::Sender),
+                              map(tag("subject"), |_| YankTarget::Subject),
+                              map(tag("body"), |_| YankTarget::Body),
+                              map(preceded(pair(tag("link"), is_a(" ")), usize_c), YankTarget::Link),
+                          ))(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, View(Yank(target))))
+                      }
+                  )
+                },
                 { tags: ["tag", "tag add", "tag remove"],
                    desc: "tag [add/remove], edits message's tags.",
                    tokens: &[One(Literal("tag")), One(Alternatives(&[to_stream!(One(Literal("add"))), to_stream!(One(Literal("remove")))]))],
@@ -814,6 +1222,78 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                           Ok((input, ReloadConfiguration))
                       }
                   )
+                },
+                { tags: ["view-log"],
+                  desc: "view the most recent log messages",
+                  tokens: &[One(Literal("view-log"))],
+                  parser:(
+                      fn view_log(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("view-log")(input.trim())?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, ViewLog))
+                      }
+                  )
+                },
+                { tags: ["messages"],
+                  desc: "view the status messages and notifications shown so far",
+                  tokens: &[One(Literal("messages"))],
+                  parser:(
+                      fn view_messages(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("messages")(input.trim())?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, ViewMessages))
+                      }
+                  )
+                },
+                { tags: ["power-saving "],
+                  desc: "power-saving on|off|auto, throttle background activity regardless of the power source, or follow it",
+                  tokens: &[One(Literal("power-saving")), One(Alternatives(&[to_stream!(One(Literal("on"))), to_stream!(One(Literal("off"))), to_stream!(One(Literal("auto")))]))],
+                  parser:(
+                      fn power_saving(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("power-saving")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, value) = alt((
+                              map(tag("on"), |_| Some(true)),
+                              map(tag("off"), |_| Some(false)),
+                              map(tag("auto"), |_| None),
+                          ))(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, PowerSaving(value)))
+                      }
+                  )
+                },
+                { tags: ["cache stats"],
+                  desc: "show the memory used by messages of remote accounts kept in memory",
+                  tokens: &[One(Literal("cache")), One(Literal("stats"))],
+                  parser:(
+                      fn cache_stats(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("cache")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("stats")(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, CacheStats))
+                      }
+                  )
+                },
+                { tags: ["help"],
+                  desc: "help [meli|conf|themes|commands], opens a manual page, or the list of commands, in a new tab",
+                  tokens: &[One(Literal("help")), ZeroOrOne(Alternatives(&[to_stream!(One(Literal("meli"))), to_stream!(One(Literal("conf"))), to_stream!(One(Literal("themes"))), to_stream!(One(Literal("commands")))]))],
+                  parser:(
+                      fn help(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("help")(input.trim())?;
+                          let (input, page) = opt(preceded(
+                              is_a(" "),
+                              alt((
+                                  map(tag("commands"), |_| None),
+                                  map_res(map_res(not_line_ending, std::str::from_utf8), |s: &str| {
+                                      crate::manpages::parse_manpage(s.trim()).map(Some)
+                                  }),
+                              )),
+                          ))(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, Help(page.unwrap_or(Some(ManPages::Main)))))
+                      }
+                  )
                 }
 ]);
 
@@ -862,10 +1342,20 @@ fn listing_action(input: &[u8]) -> IResult<&[u8], Action> {
         copymove,
         import,
         search,
+        filter_preset,
+        goto_date,
+        limit,
         select,
         toggle_thread_snooze,
+        toggle_thread_watch,
+        group_by,
+        toggle_preview_pane,
         open_in_new_tab,
         export_mbox,
+        bulk_reply_template,
+        refresh_all,
+        refresh,
+        next_unread,
         _tag,
     ))(input)
 }
@@ -876,16 +1366,39 @@ fn compose_action(input: &[u8]) -> IResult<&[u8], Action> {
         remove_attachment,
         toggle_sign,
         toggle_encrypt,
+        toggle_markdown,
         save_draft,
     ))(input)
 }
 
 fn account_action(input: &[u8]) -> IResult<&[u8], Action> {
-    alt((reindex, print_account_setting))(input)
+    alt((
+        reindex,
+        stats,
+        activity,
+        doctor,
+        empty_trash,
+        empty_junk,
+        sync,
+        backend,
+        autocrypt_setup_message,
+        print_account_setting,
+    ))(input)
 }
 
 fn view(input: &[u8]) -> IResult<&[u8], Action> {
-    alt((pipe, save_attachment, export_mail))(input)
+    alt((
+        pipe,
+        save_attachment,
+        export_mail,
+        export_ics,
+        set_note,
+        remove_note,
+        yank,
+        dismiss_warnings,
+        autocrypt_import,
+        thread_search,
+    ))(input)
 }
 
 pub fn parse_command(input: &[u8]) -> Result<Action, MeliError> {
@@ -903,18 +1416,54 @@ pub fn parse_command(input: &[u8]) -> Result<Action, MeliError> {
         create_mailbox,
         sub_mailbox,
         unsub_mailbox,
-        delete_mailbox,
-        rename_mailbox,
+        alt((delete_mailbox, rename_mailbox, mailbox_properties)),
         account_action,
         print_setting,
         toggle_mouse,
         reload_config,
+        alt((view_log, view_messages, power_saving, cache_stats, help)),
         quit,
     ))(input)
     .map(|(_, v)| v)
     .map_err(|err| err.into())
 }
 
+/// The shortcut section of the components that the command with tag `tag` applies to, or `None` if
+/// it applies anywhere. The shortcuts screen lists the commands of the sections it shows.
+pub fn command_section(tag: &str) -> Option<&'static str> {
+    match tag.trim() {
+        "set"
+        | "delete"
+        | "copyto"
+        | "import"
+        | "go"
+        | "sort"
+        | "subsort"
+        | "group-by"
+        | "toggle thread_snooze"
+        | "toggle thread_watch"
+        | "toggle preview_pane"
+        | "filter-preset"
+        | "goto date"
+        | "limit"
+        | "select"
+        | "export-mbox"
+        | "refresh"
+        | "refresh-all"
+        | "next-unread"
+        | "bulk-reply-template"
+        | "open-in-tab"
+        | "tag" => Some("listing"),
+        "list-archive" | "save-attachment" | "export-mail" | "export-ics" | "note"
+        | "remove-note" | "dismiss-warnings" | "yank" | "autocrypt-import" => Some("view mail"),
+        "thread-search" => Some("thread view"),
+        "pipe" => Some("pager"),
+        "add-attachment" | "remove-attachment" | "save-draft" | "toggle sign"
+        | "toggle encrypt" | "toggle markdown" => Some("composing"),
+        _ => None,
+    }
+}
+
 #[test]
 #[ignore]
 fn test_parser() {
@@ -24,6 +24,7 @@
  */
 
 use crate::components::Component;
+use crate::manpages::ManPages;
 pub use melib::thread::{SortField, SortOrder};
 use std::path::PathBuf;
 
@@ -42,7 +43,15 @@ pub enum ListingAction {
     SetThreaded,
     SetCompact,
     SetConversations,
+    /// Switch to the conversations style with this grouping.
+    SetGrouping(ConversationsGrouping),
     Search(String),
+    /// Search with the query of the named `listing.filter_presets` entry.
+    FilterPreset(String),
+    /// Move to the first entry in a date range, see `melib::datetime::date_range`.
+    GotoDate(String),
+    /// Show only the entries in a date range.
+    Limit(String),
     Select(String),
     SetSeen,
     SetUnseen,
@@ -56,6 +65,68 @@ pub enum ListingAction {
     OpenInNewTab,
     Tag(TagAction),
     ToggleThreadSnooze,
+    ToggleThreadWatch,
+    TogglePreviewPane,
+    TogglePreviewPaneOrientation,
+    /// Reply to the selection with a template; `true` sends the replies instead of opening them.
+    BulkReplyTemplate(String, bool),
+    /// Refresh the given mailbox, or the current one.
+    Refresh(Option<MailboxPath>),
+    RefreshAll,
+    NextUnread(UnreadScope),
+    ChangeSelection(SelectionChange),
+}
+
+impl ListingAction {
+    /// Whether the action changes messages of the account, which read-only accounts refuse.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            ListingAction::SetSeen
+                | ListingAction::SetUnseen
+                | ListingAction::CopyTo(_)
+                | ListingAction::CopyToOtherAccount(_, _)
+                | ListingAction::MoveTo(_)
+                | ListingAction::MoveToOtherAccount(_, _)
+                | ListingAction::Import(_, _)
+                | ListingAction::Delete
+                | ListingAction::Tag(_)
+        )
+    }
+}
+
+/// How the conversations listing groups messages into entries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConversationsGrouping {
+    /// One entry per thread.
+    Threads,
+    /// One entry per correspondent, with the threads with that address.
+    Correspondents,
+}
+
+impl Default for ConversationsGrouping {
+    fn default() -> Self {
+        ConversationsGrouping::Threads
+    }
+}
+
+/// Where to look for the next unread entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnreadScope {
+    /// The current mailbox, wrapping around.
+    Mailbox,
+    /// The next mailbox of the current account with unread messages.
+    NextMailbox,
+    /// The current mailbox, then the mailboxes of all accounts.
+    All,
+}
+
+/// How `select all`, `select none` and `select invert` change the selection of every entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionChange {
+    All,
+    None,
+    Invert,
 }
 
 #[derive(Debug)]
@@ -77,6 +148,27 @@ pub enum ViewAction {
     Pipe(String, Vec<String>),
     SaveAttachment(usize, String),
     ExportMail(String),
+    ExportIcs(Option<usize>),
+    SetNote(String),
+    RemoveNote,
+    Yank(YankTarget),
+    /// Stop warning about the sender of the viewed message.
+    DismissWarnings,
+    /// Import the secret key of the viewed Autocrypt Setup Message with its Setup Code.
+    ImportAutocryptSetup(String),
+    /// Find the messages of the viewed thread that contain the pattern.
+    SearchThread(String),
+}
+
+/// What the `yank` command copies to the clipboard from the viewed message.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum YankTarget {
+    MessageId,
+    Sender,
+    Subject,
+    Body,
+    /// The link with this number in URL mode.
+    Link(usize),
 }
 
 #[derive(Debug)]
@@ -88,12 +180,25 @@ pub enum ComposeAction {
     SaveDraft,
     ToggleSign,
     ToggleEncrypt,
+    ToggleMarkdown,
 }
 
 #[derive(Debug)]
 pub enum AccountAction {
     ReIndex,
     PrintAccountSetting(String),
+    EmptyTrash,
+    EmptyJunk,
+    Stats,
+    /// Show the journal of what happened to the account's messages.
+    Activity,
+    Synchronize,
+    Doctor,
+    /// An action of the account's backend and its arguments. Without an action, the available
+    /// ones are listed.
+    Backend(Option<String>, Vec<String>),
+    /// Compose an Autocrypt Setup Message with the account's secret key.
+    AutocryptSetupMessage,
 }
 
 #[derive(Debug)]
@@ -103,6 +208,8 @@ pub enum MailboxOperation {
     Subscribe(MailboxPath),
     Unsubscribe(MailboxPath),
     Rename(MailboxPath, NewMailboxPath),
+    /// Open a dialog to edit the mailbox's properties.
+    Properties(MailboxPath),
     // Placeholder
     SetPermissions(MailboxPath),
 }
@@ -124,28 +231,46 @@ pub enum Action {
     PrintSetting(String),
     ReloadConfiguration,
     ToggleMouse,
+    ViewLog,
+    ViewMessages,
+    /// Throttle background activity regardless of the power source, or `None` to follow it.
+    PowerSaving(Option<bool>),
+    /// Show the memory used by message bytes kept in memory.
+    CacheStats,
+    /// Open a manual page, or the list of commands if `None`, in a new tab.
+    Help(Option<ManPages>),
     Quit,
 }
 
 impl Action {
     pub fn needs_confirmation(&self) -> bool {
         match self {
+            Action::Listing(ListingAction::BulkReplyTemplate(_, send)) => *send,
             Action::Listing(_) => false,
             Action::ViewMailbox(_) => false,
             Action::Sort(_, _) => false,
             Action::SubSort(_, _) => false,
             Action::Tab(_) => false,
+            /* The view asks which option of the header to carry out. */
+            Action::MailingListAction(MailingListAction::ListUnsubscribe) => false,
             Action::MailingListAction(_) => true,
             Action::View(_) => false,
             Action::SetEnv(_, _) => false,
             Action::PrintEnv(_) => false,
             Action::Compose(_) => false,
             Action::Mailbox(_, _) => true,
+            Action::AccountAction(_, AccountAction::EmptyTrash)
+            | Action::AccountAction(_, AccountAction::EmptyJunk) => true,
             Action::AccountAction(_, _) => false,
             Action::PrintSetting(_) => false,
             Action::ToggleMouse => false,
             Action::Quit => true,
             Action::ReloadConfiguration => false,
+            Action::ViewLog => false,
+            Action::ViewMessages => false,
+            Action::PowerSaving(_) => false,
+            Action::CacheStats => false,
+            Action::Help(_) => false,
         }
     }
 }
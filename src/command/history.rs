@@ -24,11 +24,10 @@ use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
 
 thread_local!(static CMD_HISTORY_FILE: Arc<Mutex<std::fs::File>> = Arc::new(Mutex::new({
-    let data_dir = xdg::BaseDirectories::with_prefix("meli").unwrap();
 OpenOptions::new().append(true) /* writes will append to a file instead of overwriting previous contents */
                          .create(true) /* a new file will be created if the file does not yet already exist.*/
                          .read(true)
-                         .open(data_dir.place_data_file("cmd_history").unwrap()).unwrap()
+                         .open(melib::paths::state_file(None, "cmd_history").unwrap()).unwrap()
 })));
 
 pub fn log_cmd(mut cmd: String) {
@@ -71,6 +71,8 @@ pub struct ScrollContext {
     shown_lines: usize,
     total_lines: usize,
     has_more_lines: bool,
+    /// The last shown and the total columns, if the content is wider than the view.
+    columns: Option<(usize, usize)>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -107,4 +109,18 @@ pub trait Component: Display + Debug + Send + Sync {
     fn get_status(&self, _context: &Context) -> String {
         String::new()
     }
+
+    /// Add the state of the component to `session`, see `terminal.restore_session`.
+    fn save_session(&self, _session: &mut Session, _context: &Context) {}
+
+    /// What commands entered now would apply to, if not the entry under the cursor. It's shown
+    /// before the command prompt.
+    fn command_scope(&self, _context: &Context) -> Option<String> {
+        None
+    }
+
+    /// The account the component acts in, whose `accent_color` marks its tab and the status bar.
+    fn account_hash(&self) -> Option<AccountHash> {
+        None
+    }
 }
@@ -74,6 +74,28 @@ impl ContactManager {
         }
     }
 
+    /// Edit `card` in a tab of its own, which is closed when the editor is.
+    pub fn edit(card: Card, account_pos: usize, context: &Context) -> Self {
+        let id = ComponentId::new_v4();
+        ContactManager {
+            id,
+            parent_id: id,
+            card,
+            account_pos,
+            ..ContactManager::new(context)
+        }
+    }
+
+    /// Close the editor: the tab when it has one of its own, otherwise the parent contact list
+    /// handles `ComponentKill`.
+    fn close(&self, context: &mut Context) {
+        context.replies.push_back(if self.parent_id == self.id {
+            UIEvent::Action(Tab(Kill(self.id)))
+        } else {
+            UIEvent::ComponentKill(self.id)
+        });
+    }
+
     fn initialize(&mut self) {
         let (width, _) = self.content.size();
 
@@ -232,10 +254,10 @@ impl Component for ContactManager {
                             context.replies.push_back(UIEvent::StatusEvent(
                                 StatusEvent::DisplayMessage("Saved.".into()),
                             ));
-                            context.replies.push_back(UIEvent::ComponentKill(self.id));
+                            self.close(context);
                         }
                         Some(false) => {
-                            context.replies.push_back(UIEvent::ComponentKill(self.id));
+                            self.close(context);
                         }
                     }
                     self.set_dirty(true);
@@ -248,7 +270,7 @@ impl Component for ContactManager {
             ViewMode::ReadOnly => {
                 if let &mut UIEvent::Input(Key::Esc) = event {
                     if self.can_quit_cleanly(context) {
-                        context.replies.push_back(UIEvent::ComponentKill(self.id));
+                        self.close(context);
                     }
                     return true;
                 }
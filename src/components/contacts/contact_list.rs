@@ -60,7 +60,6 @@ pub struct ContactList {
 
     menu_visibility: bool,
     movement: Option<PageMovement>,
-    cmd_buf: String,
     view: Option<ContactManager>,
     ratio: usize, // right/(container width) * 100
     id: ComponentId,
@@ -98,7 +97,6 @@ impl ContactList {
             initialized: false,
             dirty: true,
             movement: None,
-            cmd_buf: String::with_capacity(8),
             view: None,
             ratio: 90,
             sidebar_divider: context.settings.listing.sidebar_divider,
@@ -424,6 +422,7 @@ impl ContactList {
                             shown_lines: top_idx + rows,
                             total_lines: self.length,
                             has_more_lines: false,
+                            columns: None,
                         },
                     },
                 )));
@@ -695,21 +694,7 @@ impl Component for ContactList {
                 UIEvent::Input(ref key)
                     if shortcut!(key == shortcuts[Self::DESCRIPTION]["next_account"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     if self.accounts.is_empty() {
                         return true;
                     }
@@ -732,21 +717,7 @@ impl Component for ContactList {
                 UIEvent::Input(ref key)
                     if shortcut!(key == shortcuts[Self::DESCRIPTION]["prev_account"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     if self.accounts.is_empty() {
                         return true;
                     }
@@ -771,42 +742,10 @@ impl Component for ContactList {
                     self.menu_visibility = !self.menu_visibility;
                     self.set_dirty(true);
                 }
-                UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt(''))
-                    if !self.cmd_buf.is_empty() =>
-                {
-                    self.cmd_buf.clear();
-                    context
-                        .replies
-                        .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                    return true;
-                }
-                UIEvent::Input(Key::Char(c)) if c >= '0' && c <= '9' => {
-                    self.cmd_buf.push(c);
-                    context
-                        .replies
-                        .push_back(UIEvent::StatusEvent(StatusEvent::BufSet(
-                            self.cmd_buf.clone(),
-                        )));
-                    return true;
-                }
                 UIEvent::Input(ref key)
                     if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_up"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     self.movement = Some(PageMovement::Up(amount));
                     self.set_dirty(true);
                     return true;
@@ -815,61 +754,19 @@ impl Component for ContactList {
                     if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_down"])
                         && self.cursor_pos < self.length.saturating_sub(1) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     self.set_dirty(true);
                     self.movement = Some(PageMovement::Down(amount));
                     return true;
                 }
                 UIEvent::Input(Key::PageUp) => {
-                    let mult = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(mult) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        mult
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let mult = context.count.unwrap_or(1);
                     self.set_dirty(true);
                     self.movement = Some(PageMovement::PageUp(mult));
                     return true;
                 }
                 UIEvent::Input(Key::PageDown) => {
-                    let mult = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(mult) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        mult
-                    } else {
-                        self.cmd_buf.clear();
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let mult = context.count.unwrap_or(1);
                     self.set_dirty(true);
                     self.movement = Some(PageMovement::PageDown(mult));
                     return true;
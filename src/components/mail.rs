@@ -39,6 +39,43 @@ pub mod pgp;
 mod status;
 pub use self::status::*;
 
+mod stats;
+pub use self::stats::*;
+
+mod doctor;
+pub use self::doctor::*;
+
+mod activity;
+pub use self::activity::*;
+
+mod mailbox_properties;
+pub use self::mailbox_properties::*;
+
+/// The date of `envelope` as shown in the thread view and its Date header: in the local time
+/// zone, or in the sender's if `show_sender_timezone` is set and the header names one.
+fn display_date(envelope: &Envelope, show_sender_timezone: bool) -> String {
+    use melib::datetime::{timestamp_to_string, timestamp_to_string_with_offset, RFC5322_FMT};
+    if show_sender_timezone {
+        if let Ok((timestamp, offset)) =
+            melib::email::parser::dates::rfc5322_date_and_offset(envelope.date_as_str().as_bytes())
+        {
+            if timestamp != 0 {
+                return timestamp_to_string_with_offset(
+                    timestamp,
+                    offset,
+                    Some(RFC5322_FMT),
+                    false,
+                );
+            }
+        }
+    }
+    if envelope.datetime() == 0 {
+        /* Keep whatever the header says if it couldn't be parsed. */
+        return envelope.date_as_str().to_string();
+    }
+    timestamp_to_string(envelope.datetime(), Some(RFC5322_FMT), false)
+}
+
 fn get_display_name(context: &Context, account_hash: AccountHash) -> String {
     let settings = context.accounts[&account_hash].settings.account();
     if let Some(d) = settings.display_name.as_ref() {
@@ -0,0 +1,363 @@
+/*
+ * meli - activity journal tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::activity::{Entry, Source};
+
+/// Tab with the activity journal of an account, opened with the `activity` command.
+#[derive(Debug)]
+pub struct ActivityViewer {
+    cursor: (usize, usize),
+    account_pos: usize,
+    entries: Vec<Entry>,
+    /// Show only entries from this source.
+    source: Option<Source>,
+    /// Show only entries containing this pattern.
+    search: String,
+    /// The search pattern is being typed.
+    searching: bool,
+    content: CellBuffer,
+    dirty: bool,
+    theme_default: ThemeAttribute,
+    id: ComponentId,
+}
+
+impl fmt::Display for ActivityViewer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ActivityViewer::DESCRIPTION)
+    }
+}
+
+impl ActivityViewer {
+    pub const DESCRIPTION: &'static str = "activity";
+    pub fn new(account_pos: usize, context: &Context) -> ActivityViewer {
+        let theme_default = crate::conf::value(context, "theme_default");
+        ActivityViewer {
+            /* Start at the newest entries, like `tail` */
+            cursor: (0, usize::MAX),
+            account_pos,
+            entries: context.accounts[account_pos]
+                .activity
+                .entries()
+                .iter()
+                .cloned()
+                .collect(),
+            source: None,
+            search: String::new(),
+            searching: false,
+            content: CellBuffer::new_with_context(120, 5, None, context),
+            dirty: true,
+            theme_default,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    fn next_source(source: Option<Source>) -> Option<Source> {
+        match source {
+            None => Some(Source::Local),
+            Some(Source::Local) => Some(Source::Remote),
+            Some(Source::Remote) => None,
+        }
+    }
+
+    fn matches(&self, entry: &Entry) -> bool {
+        self.source.map(|s| s == entry.source).unwrap_or(true)
+            && (self.search.is_empty()
+                || entry
+                    .to_string()
+                    .to_lowercase()
+                    .contains(&self.search.to_lowercase()))
+    }
+
+    fn draw_content(&mut self, context: &Context) {
+        let mut default_cell = Cell::with_char(' ');
+        default_cell
+            .set_fg(self.theme_default.fg)
+            .set_bg(self.theme_default.bg)
+            .set_attrs(self.theme_default.attrs);
+        self.content = CellBuffer::new(120, 5, default_cell);
+        self.content.set_growable(true);
+
+        let entries = self
+            .entries
+            .iter()
+            .filter(|e| self.matches(e))
+            .collect::<Vec<&Entry>>();
+        let header = format!(
+            "Activity of {}{}{}{}{} ({} of {})",
+            context.accounts[self.account_pos].name(),
+            match self.source {
+                None => "",
+                Some(Source::Local) => ", local only",
+                Some(Source::Remote) => ", remote only",
+            },
+            if self.search.is_empty() && !self.searching {
+                ""
+            } else {
+                ", matching: "
+            },
+            self.search,
+            if self.searching { "▁" } else { "" },
+            entries.len(),
+            self.entries.len()
+        );
+        let mut lines = vec![(header, self.theme_default.fg, Attr::BOLD)];
+        lines.extend(entries.iter().map(|e| {
+            let fg = match e.source {
+                Source::Remote => Color::Yellow,
+                Source::Local => self.theme_default.fg,
+            };
+            (e.to_string(), fg, self.theme_default.attrs)
+        }));
+        for (i, (text, fg, attrs)) in lines.into_iter().enumerate() {
+            let width = self.content.size().0;
+            write_string_to_grid(
+                &text,
+                &mut self.content,
+                fg,
+                self.theme_default.bg,
+                attrs,
+                ((0, i), (width - 1, i)),
+                None,
+            );
+        }
+    }
+}
+
+impl Component for ActivityViewer {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.draw_content(context);
+
+        /* self.content may have been resized with write_string_to_grid() calls above since it has
+         * growable set */
+        let (width, height) = self.content.size();
+        let (cols, rows) = (width!(area), height!(area));
+        self.cursor = (
+            std::cmp::min(width.saturating_sub(cols), self.cursor.0),
+            std::cmp::min(height.saturating_sub(rows), self.cursor.1),
+        );
+        clear_area(grid, area, self.theme_default);
+        copy_area(
+            grid,
+            &self.content,
+            area,
+            (
+                (
+                    std::cmp::min((width - 1).saturating_sub(cols), self.cursor.0),
+                    std::cmp::min((height - 1).saturating_sub(rows), self.cursor.1),
+                ),
+                (
+                    std::cmp::min(self.cursor.0 + cols, width - 1),
+                    std::cmp::min(self.cursor.1 + rows, height - 1),
+                ),
+            ),
+        );
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.dirty = true;
+            }
+            UIEvent::InsertInput(Key::Char('\n')) if self.searching => {
+                self.searching = false;
+                self.cursor.1 = usize::MAX;
+                context
+                    .replies
+                    .push_back(UIEvent::ChangeMode(UIMode::Normal));
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Backspace) if self.searching => {
+                self.search.pop();
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Char(c)) if self.searching => {
+                self.search.push(c);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Paste(ref s)) if self.searching => {
+                self.search.push_str(s);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::ChangeMode(UIMode::Normal) if self.searching => {
+                /* Escape cancels the search */
+                self.searching = false;
+                self.search.clear();
+                self.dirty = true;
+                return false;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ActivityViewer::DESCRIPTION]["search"]) =>
+            {
+                self.searching = true;
+                self.search.clear();
+                context
+                    .replies
+                    .push_back(UIEvent::ChangeMode(UIMode::Insert));
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ActivityViewer::DESCRIPTION]["cycle_source"]) =>
+            {
+                self.source = Self::next_source(self.source);
+                self.cursor.1 = usize::MAX;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ActivityViewer::DESCRIPTION]["refresh"]) =>
+            {
+                self.entries = context.accounts[self.account_pos]
+                    .activity
+                    .entries()
+                    .iter()
+                    .cloned()
+                    .collect();
+                self.cursor.1 = usize::MAX;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts["general"]["scroll_left"]) && self.cursor.0 != 0 =>
+            {
+                self.cursor.0 -= 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_right"]) => {
+                self.cursor.0 += 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_up"]) => {
+                self.cursor.1 = self.cursor.1.saturating_sub(1);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_down"]) => {
+                self.cursor.1 = self.cursor.1.saturating_add(1);
+                self.dirty = true;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert(
+            ActivityViewer::DESCRIPTION,
+            context.settings.shortcuts.activity.key_values(),
+        );
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        session.tabs.push(SessionTab::Activity {
+            account: context.accounts[self.account_pos].name().to_string(),
+        });
+    }
+}
+
+#[test]
+fn test_activity_viewer() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[
+            message(
+                "kept",
+                "Alice <alice@example.com>",
+                "Thu, 02 Jan 2020 10:00:00 +0000",
+            ),
+            message(
+                "gone",
+                "Bob <bob@example.com>",
+                "Wed, 01 Jan 2020 10:00:00 +0000",
+            ),
+        ],
+        "[listing]\nindex_style = \"plain\"\n",
+    );
+    h.wait_for("kept");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Subject: kept");
+    /* Another client removes a message. */
+    for entry in std::fs::read_dir(h.maildir().join("cur")).unwrap() {
+        let path = entry.unwrap().path();
+        if std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("Subject: gone")
+        {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+    h.wait_until("the removal to be recorded", |h| {
+        h.state.context.accounts[0]
+            .activity
+            .entries()
+            .iter()
+            .any(|e| e.subject == "gone")
+    });
+    h.command("activity test");
+    h.wait_for("Activity of test");
+    h.wait_for(": mark seen on opening \"kept\" from Alice <alice@example.com>");
+    let removed = h
+        .find(": removed \"gone\" from Bob")
+        .unwrap_or_else(|| panic!("{}", h.text()));
+    assert!(h.row(removed).contains(" remote "), "{}", h.text());
+    /* Only local entries. */
+    h.keys(&[Key::Char('l')]);
+    h.wait_for("Activity of test, local only");
+    assert!(h.find("removed \"gone\"").is_none(), "{}", h.text());
+    assert!(h.find("mark seen on opening").is_some(), "{}", h.text());
+}
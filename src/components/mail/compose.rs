@@ -21,6 +21,7 @@
 
 use super::*;
 use melib::email::attachment_types::{ContentType, MultipartType};
+use melib::email::compose::mime;
 use melib::list_management;
 use melib::Draft;
 
@@ -31,6 +32,7 @@ use indexmap::IndexSet;
 use nix::sys::wait::WaitStatus;
 use std::convert::TryInto;
 use std::future::Future;
+use std::io::Write;
 use std::pin::Pin;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
@@ -91,11 +93,26 @@ pub struct Composer {
 
     embed_area: Area,
     embed: Option<EmbedStatus>,
+    /// Attachments of the replied to or forwarded message the user hasn't chosen to include or
+    /// leave out yet.
+    original_attachments: Vec<Attachment>,
     #[cfg(feature = "gpgme")]
     gpg_state: gpg::GpgComposeState,
+    /// Whether each recipient's key was found, see `pgp.opportunistic_encryption`.
+    #[cfg(feature = "gpgme")]
+    recipient_keys: indexmap::IndexMap<String, gpg::RecipientKey>,
+    /// The Autocrypt recommendation once all recipient keys are looked up, see `pgp.autocrypt`.
+    #[cfg(feature = "gpgme")]
+    autocrypt_recommendation: Option<crate::autocrypt::Recommendation>,
     dirty: bool,
     has_changes: bool,
     initialized: bool,
+    /// Use the whole width instead of centering the draft.
+    zoomed: bool,
+    /// The body is Markdown, sent along with its rendering to HTML.
+    markdown: bool,
+    /// Show the rendered HTML of a Markdown body instead of its text.
+    markdown_preview: bool,
     id: ComponentId,
 }
 
@@ -108,6 +125,7 @@ enum ViewMode {
     Edit,
     Embed,
     SelectRecipients(UIDialog<Address>),
+    SelectAttachments(UIDialog<Attachment>),
     #[cfg(feature = "gpgme")]
     SelectEncryptKey(bool, gpg::KeySelection),
     Send(UIConfirmationDialog),
@@ -161,11 +179,19 @@ impl Composer {
             mode: ViewMode::Edit,
             #[cfg(feature = "gpgme")]
             gpg_state: gpg::GpgComposeState::new(),
+            #[cfg(feature = "gpgme")]
+            recipient_keys: indexmap::IndexMap::default(),
+            #[cfg(feature = "gpgme")]
+            autocrypt_recommendation: None,
             dirty: true,
             has_changes: false,
             embed_area: ((0, 0), (0, 0)),
             embed: None,
+            original_attachments: Vec::new(),
             initialized: false,
+            zoomed: false,
+            markdown: false,
+            markdown_preview: false,
             id: ComponentId::new_v4(),
         }
     }
@@ -193,6 +219,24 @@ impl Composer {
             ret.pager
                 .set_reflow(melib::text_processing::Reflow::FormatFlowed);
         }
+        ret.markdown = cfg!(feature = "markdown")
+            && *account_settings!(context[account_hash].composing.markdown);
+        ret
+    }
+
+    /// A composer for an Autocrypt Setup Message. It is sent as it is, since its attachment is
+    /// already encrypted.
+    #[cfg(feature = "gpgme")]
+    pub fn with_autocrypt_setup_message(
+        account_hash: AccountHash,
+        draft: Draft,
+        context: &Context,
+    ) -> Self {
+        let mut ret = Composer::with_account(account_hash, context);
+        ret.set_draft(draft);
+        ret.markdown = false;
+        ret.gpg_state.sign_mail = ToggleFlag::False;
+        ret.gpg_state.encrypt_mail = ToggleFlag::False;
         ret
     }
 
@@ -408,11 +452,244 @@ impl Composer {
         Composer::reply_to(coordinates, reply_body, context, true)
     }
 
+    /// Reply to the sender of the envelope with `template`, expanded with
+    /// `reply_template_string`.
+    pub fn reply_with_template(
+        coordinates: (AccountHash, MailboxHash, EnvelopeHash),
+        template: &str,
+        context: &mut Context,
+    ) -> Self {
+        let mut ret = Composer::reply_to(coordinates, String::new(), context, false);
+        let envelope = context.accounts[&coordinates.0]
+            .collection
+            .get_env(coordinates.2);
+        ret.draft.body = reply_template_string(template, &envelope);
+        ret.draft.set_header(
+            "From",
+            crate::components::mail::get_display_name(context, coordinates.0),
+        );
+        ret
+    }
+
+    pub fn forward(
+        coordinates: (AccountHash, MailboxHash, EnvelopeHash),
+        forward_body: String,
+        context: &Context,
+    ) -> Self {
+        let mut ret = Composer::with_account(coordinates.0, context);
+        let account = &context.accounts[&coordinates.0];
+        let envelope = account.collection.get_env(coordinates.2);
+        let subject = envelope.subject();
+        ret.draft.set_header(
+            "Subject",
+            if !subject.starts_with("Fwd: ") {
+                format!("Fwd: {}", subject)
+            } else {
+                subject.into()
+            },
+        );
+        ret.draft.body = {
+            let mut ret = format!(
+                "---------- Forwarded message ----------\nFrom: {}\nDate: {}\nSubject: {}\nTo: {}\n",
+                envelope.field_from_to_string(),
+                envelope.date_as_str(),
+                envelope.subject(),
+                envelope.field_to_to_string()
+            );
+            let cc = envelope.field_cc_to_string();
+            if !cc.is_empty() {
+                ret.push_str(&format!("Cc: {}\n", cc));
+            }
+            ret.push('\n');
+            ret.push_str(&forward_body);
+            ret
+        };
+        ret
+    }
+
+    /// Let the user choose which attachments of the replied to or forwarded message `body` to
+    /// include in the draft.
+    pub fn set_original_attachments(&mut self, body: &Attachment, context: &Context) {
+        self.original_attachments = original_attachments(body);
+        if self.mode.is_edit() {
+            self.select_original_attachments(context);
+        }
+    }
+
+    fn select_original_attachments(&mut self, context: &Context) {
+        if self.original_attachments.is_empty() {
+            return;
+        }
+        self.mode = ViewMode::SelectAttachments(UIDialog::new(
+            "include attachments",
+            self.original_attachments
+                .iter()
+                .map(|a| (a.clone(), a.to_string()))
+                .collect::<Vec<(Attachment, String)>>(),
+            false,
+            Some(Box::new(move |id: ComponentId, results: &[Attachment]| {
+                Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
+            })),
+            context,
+        ));
+    }
+
+    pub fn draft(&self) -> &Draft {
+        &self.draft
+    }
+
     pub fn set_draft(&mut self, draft: Draft) {
         self.draft = draft;
         self.update_form();
     }
 
+    /// The text shown in the body pager: the draft's body, or when previewing a Markdown draft
+    /// its HTML rendering piped through the account's `html_filter`.
+    fn pager_text(&self, context: &Context) -> String {
+        if !(self.markdown && self.markdown_preview) {
+            return self.draft.body().to_string();
+        }
+        let html = match markdown_to_html(self.draft.body()) {
+            Ok(html) => html,
+            Err(err) => return err.to_string(),
+        };
+        let filter_invocation = account_settings!(context[self.account_hash].pager.html_filter)
+            .as_ref()
+            .map(|s| s.as_str())
+            .unwrap_or("w3m -I utf-8 -T text/html");
+        let output = Command::new("sh")
+            .args(&["-c", filter_invocation])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                child.stdin.as_mut().unwrap().write_all(html.as_bytes())?;
+                child.wait_with_output()
+            });
+        match output {
+            Ok(output) if output.status.success() => {
+                String::from_utf8_lossy(&output.stdout).into_owned()
+            }
+            /* Show the HTML source if it can't be rendered as text. */
+            _ => html,
+        }
+    }
+
+    /// The message as it will be sent, before it is signed or encrypted.
+    fn outgoing_mail(&self, context: &Context) -> Result<Mail> {
+        let mut draft = self.draft.clone();
+        let body = draft_body(context, self.account_hash, &mut draft, self.markdown)?;
+        draft.attachments.insert(0, body);
+        Mail::new(draft.finalise()?.into_bytes(), Some(Flag::SEEN))
+    }
+
+    /// Look up the keys of recipients added since the last lookup.
+    #[cfg(feature = "gpgme")]
+    fn update_recipient_keys(&mut self, context: &mut Context) {
+        if !(*account_settings!(context[self.account_hash].pgp.opportunistic_encryption)
+            || *account_settings!(context[self.account_hash].pgp.autocrypt))
+        {
+            return;
+        }
+        let values = self.form.values();
+        let recipients = ["To", "Cc", "Bcc"]
+            .iter()
+            .filter_map(|h| values.get(*h))
+            .filter_map(|v| {
+                melib::email::parser::address::rfc2822address_list(v.as_str().as_bytes()).ok()
+            })
+            .flat_map(|(_, list)| list.into_iter())
+            .map(|addr| addr.get_email().to_lowercase())
+            .collect::<IndexSet<String>>();
+        self.recipient_keys
+            .retain(|address, _| recipients.contains(address));
+        let remote_lookup =
+            if account_settings!(context[self.account_hash].pgp.allow_remote_lookup).is_true() {
+                Some(*account_settings!(
+                    context[self.account_hash].pgp.remote_lookup_mechanisms
+                ))
+            } else {
+                None
+            };
+        for address in recipients {
+            if self.recipient_keys.contains_key(&address) {
+                continue;
+            }
+            let autocrypt_key = context.accounts[&self.account_hash]
+                .autocrypt_peers
+                .get(&address)
+                .map(|peer| peer.keydata.clone());
+            let handle = context
+                .job_executor
+                .spawn_specialized(gpg::lookup_recipient_key(
+                    address.clone(),
+                    remote_lookup,
+                    autocrypt_key,
+                ));
+            self.recipient_keys
+                .insert(address, gpg::RecipientKey::Pending(handle));
+        }
+        self.update_encryption(context);
+    }
+
+    /// Encrypt when all recipients have a key and the Autocrypt recommendation, if enabled,
+    /// allows it, unless the user turned encryption on or off.
+    #[cfg(feature = "gpgme")]
+    fn update_encryption(&mut self, context: &Context) {
+        use crate::autocrypt::{recommendation, Recommendation};
+        let pending = self
+            .recipient_keys
+            .values()
+            .any(|k| matches!(k, gpg::RecipientKey::Pending(_)));
+        self.autocrypt_recommendation =
+            if *account_settings!(context[self.account_hash].pgp.autocrypt) && !pending {
+                let peers = &context.accounts[&self.account_hash].autocrypt_peers;
+                Some(recommendation(
+                    self.recipient_keys.iter().map(|(address, key)| {
+                        match (key, peers.get(address)) {
+                            (gpg::RecipientKey::Found(_), Some(peer)) => {
+                                (peer.recommendation(), peer.prefer_encrypt)
+                            }
+                            /* Keys from the keyring or remote lookups. */
+                            (gpg::RecipientKey::Found(_), None) => {
+                                (Recommendation::Available, false)
+                            }
+                            _ => (Recommendation::Disable, false),
+                        }
+                    }),
+                    *account_settings!(context[self.account_hash].pgp.autocrypt_prefer_encrypt),
+                ))
+            } else {
+                None
+            };
+        if !(self.gpg_state.encrypt_mail.is_unset() || self.gpg_state.encrypt_mail.is_internal()) {
+            return;
+        }
+        let encrypt = match self.autocrypt_recommendation {
+            Some(Recommendation::Encrypt) => true,
+            Some(Recommendation::Available) | None => {
+                *account_settings!(context[self.account_hash].pgp.opportunistic_encryption)
+            }
+            Some(Recommendation::Discourage) | Some(Recommendation::Disable) => false,
+        };
+        let keys = self
+            .recipient_keys
+            .values()
+            .map(|k| match k {
+                gpg::RecipientKey::Found(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<melib::gpgme::Key>>>()
+            .filter(|keys| encrypt && !keys.is_empty());
+        if let Some(keys) = keys {
+            self.gpg_state.encrypt_mail = ToggleFlag::InternalVal(true);
+            self.gpg_state.encrypt_keys = keys;
+        } else if self.gpg_state.encrypt_mail.is_true() {
+            self.gpg_state.encrypt_mail = ToggleFlag::InternalVal(false);
+            self.gpg_state.encrypt_keys.clear();
+        }
+    }
+
     fn update_draft(&mut self) {
         let header_values = self.form.values_mut();
         let draft_header_map = self.draft.headers_mut();
@@ -423,6 +700,102 @@ impl Composer {
         }
     }
 
+    /// Expand personal aliases and contact nicknames from the account's address book in the
+    /// `To`, `Cc` and `Bcc` fields, and remove addresses (including group members) that already appear in a previous
+    /// recipient field or earlier in the same field.
+    fn expand_recipients(&mut self, context: &Context) {
+        let book = &context.accounts[&self.account_hash].address_book;
+        let mut seen: std::collections::HashSet<String> = Default::default();
+        for &k in &["To", "Cc", "Bcc"] {
+            let value = self.draft.headers()[k].to_string();
+            let mut entries: Vec<String> = vec![];
+            for entry in split_address_list(&value) {
+                let expanded: Vec<String> = match (book.alias(entry), book.nickname(entry)) {
+                    (Some(addresses), _) => addresses.to_vec(),
+                    (None, Some(address)) => vec![address],
+                    (None, None) => vec![entry.to_string()],
+                };
+                for entry in expanded {
+                    match melib::email::parser::address::address(entry.as_bytes()) {
+                        Ok((rest, addr @ Address::Mailbox(_)))
+                            if rest.iter().all(u8::is_ascii_whitespace) =>
+                        {
+                            if seen.insert(addr.get_email().to_lowercase()) {
+                                entries.push(entry);
+                            }
+                        }
+                        Ok((rest, addr @ Address::Group(_)))
+                            if rest.iter().all(u8::is_ascii_whitespace) =>
+                        {
+                            let members = addr
+                                .mailboxes()
+                                .into_iter()
+                                .filter(|m| seen.insert(m.get_email().to_lowercase()))
+                                .cloned()
+                                .collect::<Vec<Address>>();
+                            entries.push(
+                                Address::new_group(
+                                    addr.get_display_name().unwrap_or_default(),
+                                    members,
+                                )
+                                .to_string(),
+                            );
+                        }
+                        _ => entries.push(entry),
+                    }
+                }
+            }
+            self.draft.set_header(k, entries.join(", "));
+        }
+    }
+
+    /// Expand the alias or contact nickname that was typed last in the recipient field under the
+    /// cursor. Returns `false` if there is none.
+    fn expand_typed_nickname(&mut self, context: &Context) -> bool {
+        let book = &context.accounts[&self.account_hash].address_book;
+        let (name, field) = match self.form.focused_field_mut() {
+            Some((name, field)) if !field.has_selected_suggestion() => (name, field),
+            _ => return false,
+        };
+        if !["To", "Cc", "Bcc"].contains(&name.as_ref()) {
+            return false;
+        }
+        let mut entries: Vec<String> = split_address_list(field.as_str())
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let expanded = match entries.last().map(|e| (book.alias(e), book.nickname(e))) {
+            Some((Some(addresses), _)) => addresses.join(", "),
+            Some((None, Some(address))) => address,
+            _ => return false,
+        };
+        *entries.last_mut().unwrap() = expanded;
+        field.set_text(entries.join(", "));
+        true
+    }
+
+    /// The recipients that are neither contacts nor were ever sent mail, see
+    /// `RecipientHistory`.
+    fn new_recipients(&self, context: &Context) -> Vec<String> {
+        let account = &context.accounts[&self.account_hash];
+        let identity = account.settings.account().identity.to_lowercase();
+        ["To", "Cc", "Bcc"]
+            .iter()
+            .flat_map(|k| Address::list_try_from(&self.draft.headers()[*k]).unwrap_or_default())
+            .flat_map(|a| {
+                a.mailboxes()
+                    .into_iter()
+                    .map(|m| m.get_email())
+                    .collect::<Vec<String>>()
+            })
+            .filter(|email| {
+                email.to_lowercase() != identity
+                    && !account.address_book.contains_address(email)
+                    && account.recipient_history.get(email).is_none()
+            })
+            .collect()
+    }
+
     fn update_form(&mut self) {
         let old_cursor = self.form.cursor();
         self.form = FormWidget::new(("Save".into(), true));
@@ -437,7 +810,10 @@ impl Composer {
                     headers[k].to_string().into(),
                     Box::new(move |c, term| {
                         let book: &AddressBook = &c.accounts[&account_hash].address_book;
-                        let results: Vec<String> = book.search(term);
+                        let mut results: Vec<String> = book.search(term);
+                        c.accounts[&account_hash]
+                            .recipient_history
+                            .rank(book, &mut results);
                         results
                             .into_iter()
                             .map(|r| AutoCompleteEntry::from(r))
@@ -477,6 +853,9 @@ impl Composer {
                                             hostname.truncate_at_boundary(10);
                                             format!("{} [smtp: {}]", acc.name(), hostname)
                                         }
+                                        crate::conf::composing::SendMail::ServerSubmission => {
+                                            format!("{} [server submission]", acc.name())
+                                        }
                                     };
 
                                 (addr, desc)
@@ -543,52 +922,84 @@ impl Composer {
             );
         }
         #[cfg(feature = "gpgme")]
-        if self.gpg_state.encrypt_mail.is_true() {
-            let key_list = self
-                .gpg_state
-                .encrypt_keys
-                .iter()
-                .map(|k| k.fingerprint())
-                .collect::<Vec<_>>()
-                .join(", ");
-
-            write_string_to_grid(
-                &format!(
-                    "{}{}",
-                    if self.gpg_state.encrypt_keys.is_empty() {
-                        "☐ no keys to encrypt with!"
+        {
+            let (x, y) = if self.gpg_state.encrypt_mail.is_true() {
+                let key_list = self
+                    .gpg_state
+                    .encrypt_keys
+                    .iter()
+                    .map(|k| k.fingerprint())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                write_string_to_grid(
+                    &format!(
+                        "{}{}",
+                        if self.gpg_state.encrypt_keys.is_empty() {
+                            "☐ no keys to encrypt with!"
+                        } else {
+                            "☑ encrypt with "
+                        },
+                        if self.gpg_state.encrypt_keys.is_empty() {
+                            ""
+                        } else {
+                            key_list.as_str()
+                        }
+                    ),
+                    grid,
+                    theme_default.fg,
+                    if self.cursor == Cursor::Encrypt {
+                        Color::Byte(237)
                     } else {
-                        "☑ encrypt with "
+                        theme_default.bg
                     },
-                    if self.gpg_state.encrypt_keys.is_empty() {
-                        ""
+                    theme_default.attrs,
+                    (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area)),
+                    None,
+                )
+            } else {
+                write_string_to_grid(
+                    "☐ don't encrypt",
+                    grid,
+                    theme_default.fg,
+                    if self.cursor == Cursor::Encrypt {
+                        Color::Byte(237)
                     } else {
-                        key_list.as_str()
-                    }
-                ),
-                grid,
-                theme_default.fg,
-                if self.cursor == Cursor::Encrypt {
-                    Color::Byte(237)
-                } else {
-                    theme_default.bg
-                },
-                theme_default.attrs,
-                (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area)),
-                None,
-            );
-        } else {
+                        theme_default.bg
+                    },
+                    theme_default.attrs,
+                    (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area)),
+                    None,
+                )
+            };
+            /* Whether each recipient's key was found. */
+            let recipients = self
+                .recipient_keys
+                .iter()
+                .map(|(address, key)| {
+                    format!(
+                        "{} {}",
+                        match key {
+                            gpg::RecipientKey::Pending(_) => "…",
+                            gpg::RecipientKey::Found(_) => "🔒",
+                            gpg::RecipientKey::Missing => "🔓",
+                        },
+                        address
+                    )
+                })
+                .chain(
+                    self.autocrypt_recommendation
+                        .map(|r| format!("autocrypt: {}", r)),
+                )
+                .collect::<Vec<String>>()
+                .join("  ");
             write_string_to_grid(
-                "☐ don't encrypt",
+                &recipients,
                 grid,
                 theme_default.fg,
-                if self.cursor == Cursor::Encrypt {
-                    Color::Byte(237)
-                } else {
-                    theme_default.bg
-                },
+                theme_default.bg,
                 theme_default.attrs,
-                (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area)),
+                ((x + 2, y), bottom_right!(area)),
                 None,
             );
         }
@@ -680,18 +1091,34 @@ impl Component for Composer {
                     crate::components::mail::get_display_name(context, self.account_hash),
                 );
             }
-            self.pager.update_from_str(self.draft.body(), Some(77));
+            let text = self.pager_text(context);
+            self.pager.update_from_str(&text, Some(77));
             self.update_form();
+            #[cfg(feature = "gpgme")]
+            self.update_recipient_keys(context);
             self.initialized = true;
         }
         let header_height = self.form.len();
         let theme_default = crate::conf::value(context, "theme_default");
 
-        let mid = if width > 80 {
+        let mid = if width > 80 && !self.zoomed {
             let width = width - 80;
             let mid = width / 2;
 
             if self.dirty {
+                clear_area(
+                    grid,
+                    (upper_left, set_x(bottom_right, get_x(upper_left) + mid)),
+                    theme_default,
+                );
+                clear_area(
+                    grid,
+                    (
+                        set_x(upper_left, get_x(upper_left) + mid + 80),
+                        bottom_right,
+                    ),
+                    theme_default,
+                );
                 for i in get_y(upper_left)..=get_y(bottom_right) {
                     //set_and_join_box(grid, (mid, i), VERT_BOUNDARY);
                     grid[(mid, i)]
@@ -736,11 +1163,19 @@ impl Component for Composer {
         );
 
         let (x, y) = write_string_to_grid(
-            if self.reply_context.is_some() {
-                "COMPOSING REPLY"
-            } else {
-                "COMPOSING MESSAGE"
-            },
+            &format!(
+                "{}{}",
+                if self.reply_context.is_some() {
+                    "COMPOSING REPLY"
+                } else {
+                    "COMPOSING MESSAGE"
+                },
+                match (self.markdown, self.markdown_preview) {
+                    (false, _) => "",
+                    (true, false) => " IN MARKDOWN",
+                    (true, true) => " IN MARKDOWN (PREVIEW)",
+                }
+            ),
             grid,
             Color::Byte(189),
             Color::Byte(167),
@@ -920,6 +1355,9 @@ impl Component for Composer {
             ViewMode::SelectRecipients(ref mut s) => {
                 s.draw(grid, area, context);
             }
+            ViewMode::SelectAttachments(ref mut s) => {
+                s.draw(grid, area, context);
+            }
             ViewMode::Discard(_, ref mut s) => {
                 /* Let user choose whether to quit with/without saving or cancel */
                 s.draw(grid, area, context);
@@ -940,6 +1378,11 @@ impl Component for Composer {
         if let UIEvent::VisibilityChange(_) = event {
             self.pager.process_event(event, context);
         }
+        if let UIEvent::ZoomChange(zoomed) = event {
+            self.zoomed = *zoomed;
+            self.set_dirty(true);
+            return true;
+        }
         let shortcuts = self.get_shortcuts(context);
         match (&mut self.mode, &mut event) {
             (ViewMode::Edit, _) => {
@@ -972,6 +1415,7 @@ impl Component for Composer {
                         context,
                         self.account_hash,
                         self.draft.clone(),
+                        self.markdown,
                         SpecialUsageMailbox::Sent,
                         Flag::SEEN,
                     ) {
@@ -1026,6 +1470,14 @@ impl Component for Composer {
             (ViewMode::SelectRecipients(ref dialog), UIEvent::ComponentKill(ref id))
                 if *id == dialog.id() =>
             {
+                self.mode = ViewMode::Edit;
+                self.select_original_attachments(context);
+                self.set_dirty(true);
+            }
+            (ViewMode::SelectAttachments(ref dialog), UIEvent::ComponentKill(ref id))
+                if *id == dialog.id() =>
+            {
+                self.original_attachments.clear();
                 self.mode = ViewMode::Edit;
                 self.set_dirty(true);
             }
@@ -1058,6 +1510,7 @@ impl Component for Composer {
                     self.update_form();
                 }
                 self.mode = ViewMode::Edit;
+                self.select_original_attachments(context);
                 return true;
             }
             (ViewMode::SelectRecipients(ref mut selector), _) => {
@@ -1065,6 +1518,26 @@ impl Component for Composer {
                     return true;
                 }
             }
+            (
+                ViewMode::SelectAttachments(ref selector),
+                UIEvent::FinishedUIDialog(id, ref mut result),
+            ) if selector.id() == *id => {
+                if let Some(attachments) = result.downcast_mut::<Vec<Attachment>>() {
+                    for a in attachments.drain(..) {
+                        self.draft.attachments_mut().push(carry_over_attachment(a));
+                        self.has_changes = true;
+                    }
+                }
+                self.original_attachments.clear();
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::SelectAttachments(ref mut selector), _) => {
+                if selector.process_event(event, context) {
+                    return true;
+                }
+            }
             (ViewMode::Discard(u, ref selector), UIEvent::FinishedUIDialog(id, ref mut result))
                 if selector.id() == *id =>
             {
@@ -1188,12 +1661,24 @@ impl Component for Composer {
             }
             _ => {}
         }
+        if self.cursor == Cursor::Headers
+            && self.mode.is_edit()
+            && matches!(event, UIEvent::InsertInput(Key::Char('\t')))
+            && self.expand_typed_nickname(context)
+        {
+            self.has_changes = true;
+            self.set_dirty(true);
+            return true;
+        }
         if self.cursor == Cursor::Headers
             && self.mode.is_edit()
             && self.form.process_event(event, context)
         {
             if let UIEvent::InsertInput(_) = event {
                 self.has_changes = true;
+            } else {
+                #[cfg(feature = "gpgme")]
+                self.update_recipient_keys(context);
             }
             return true;
         }
@@ -1202,6 +1687,32 @@ impl Component for Composer {
             UIEvent::ConfigReload { old_settings: _ } => {
                 self.set_dirty(true);
             }
+            #[cfg(feature = "gpgme")]
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self.recipient_keys.values().any(
+                    |k| matches!(k, gpg::RecipientKey::Pending(handle) if handle.job_id == *job_id),
+                ) =>
+            {
+                for k in self.recipient_keys.values_mut() {
+                    let result = match k {
+                        gpg::RecipientKey::Pending(ref mut handle) if handle.job_id == *job_id => {
+                            handle.chan.try_recv()
+                        }
+                        _ => continue,
+                    };
+                    *k = match result {
+                        Ok(Some(Ok(Some(key)))) => gpg::RecipientKey::Found(key),
+                        Ok(Some(Err(err))) => {
+                            debug!("Could not look up recipient key: {}", err);
+                            gpg::RecipientKey::Missing
+                        }
+                        _ => gpg::RecipientKey::Missing,
+                    };
+                }
+                self.update_encryption(context);
+                self.set_dirty(true);
+                return true;
+            }
             UIEvent::Resize => {
                 self.set_dirty(true);
             }
@@ -1274,13 +1785,67 @@ impl Component for Composer {
                 };
                 self.dirty = true;
             }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["toggle_markdown_preview"])
+                    && self.mode.is_edit() =>
+            {
+                if self.markdown {
+                    self.markdown_preview = !self.markdown_preview;
+                    self.initialized = false;
+                    self.set_dirty(true);
+                } else {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        "This draft isn't written in Markdown, see the `toggle markdown` command."
+                            .to_string(),
+                    )));
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["preview_mail"])
+                    && self.mode.is_edit() =>
+            {
+                self.update_draft();
+                self.expand_recipients(context);
+                self.update_form();
+                match self.outgoing_mail(context) {
+                    Ok(mail) => {
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(
+                                EnvelopeView::new(mail, None, None, self.account_hash),
+                            ))))));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not preview message".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Self::DESCRIPTION]["send_mail"])
                     && self.mode.is_edit() =>
             {
                 self.update_draft();
+                self.expand_recipients(context);
+                self.update_form();
+                let new_recipients = self.new_recipients(context);
+                let title = if new_recipients.is_empty() {
+                    "send mail?".to_string()
+                } else {
+                    format!(
+                        "send mail? New recipients, never written to and not in contacts: {}",
+                        new_recipients.join(", ")
+                    )
+                };
                 self.mode = ViewMode::Send(UIConfirmationDialog::new(
-                    "send mail?",
+                    &title,
                     vec![(true, "yes".to_string()), (false, "no".to_string())],
                     /* only one choice */
                     true,
@@ -1567,12 +2132,22 @@ impl Component for Composer {
                 };
                 /* update Draft's headers based on form values */
                 self.update_draft();
-                let f = create_temp_file(
+                let f = match create_temp_file(
                     self.draft.to_string().unwrap().as_str().as_bytes(),
                     None,
                     None,
                     true,
-                );
+                ) {
+                    Ok(f) => f,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not create temporary file".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                        return true;
+                    }
+                };
 
                 if *account_settings!(context[self.account_hash].composing.embed) {
                     self.embed = Some(EmbedStatus::Running(
@@ -1600,7 +2175,8 @@ impl Component for Composer {
                 }
 
                 let editor_command = format!("{} {}", editor, f.path().display());
-                log(
+                log_target(
+                    "ui",
                     format!(
                         "Executing: sh -c \"{}\"",
                         editor_command.replace("\"", "\\\"")
@@ -1664,7 +2240,17 @@ impl Component for Composer {
                         ));
                         return false;
                     }
-                    let f = create_temp_file(&[], None, None, true);
+                    let f = match create_temp_file(&[], None, None, true) {
+                        Ok(f) => f,
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not create temporary file".to_string()),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                            return false;
+                        }
+                    };
                     match Command::new("sh")
                         .args(&["-c", command])
                         .stdin(Stdio::null())
@@ -1748,7 +2334,8 @@ impl Component for Composer {
                         context.input_kill();
                     }
 
-                    log(
+                    log_target(
+                        "ui",
                         format!("Executing: sh -c \"{}\"", command.replace("\"", "\\\"")),
                         DEBUG,
                     );
@@ -1843,6 +2430,21 @@ impl Component for Composer {
                     self.set_dirty(true);
                     return true;
                 }
+                Action::Compose(ComposeAction::ToggleMarkdown) => {
+                    if let Err(err) = markdown_to_html("") {
+                        context.replies.push_back(UIEvent::Notification(
+                            None,
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    } else {
+                        self.markdown = !self.markdown;
+                        self.markdown_preview = false;
+                        self.initialized = false;
+                        self.set_dirty(true);
+                    }
+                    return true;
+                }
                 _ => {}
             },
             _ => {}
@@ -1861,6 +2463,9 @@ impl Component for Composer {
             ViewMode::SelectRecipients(ref widget) => {
                 widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
             }
+            ViewMode::SelectAttachments(ref widget) => {
+                widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
+            }
             #[cfg(feature = "gpgme")]
             ViewMode::SelectEncryptKey(_, ref widget) => {
                 widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
@@ -1939,6 +2544,10 @@ impl Component for Composer {
         self.id = id;
     }
 
+    fn account_hash(&self) -> Option<AccountHash> {
+        Some(self.account_hash)
+    }
+
     fn can_quit_cleanly(&mut self, context: &Context) -> bool {
         if !self.has_changes {
             return true;
@@ -2025,7 +2634,8 @@ pub fn send_draft(
     match output {
         Err(err) => {
             debug!("{:?} could not sign draft msg", err);
-            log(
+            log_target(
+                "ui",
                 format!(
                     "Could not sign draft in account `{}`: {}.",
                     context.accounts[&account_hash].name(),
@@ -2058,15 +2668,14 @@ pub fn send_draft(
             {
                 parameters.push((b"format".to_vec(), b"flowed".to_vec()));
             }
-
-            let body: AttachmentBuilder = Attachment::new(
-                content_type,
-                Default::default(),
-                std::mem::replace(&mut draft.body, String::new()).into_bytes(),
-            )
-            .into();
-            draft.attachments.insert(0, body);
         }
+        let body = body_part(
+            context,
+            account_hash,
+            content_type,
+            std::mem::replace(&mut draft.body, String::new()),
+        );
+        draft.attachments.insert(0, body);
     }
     let bytes = draft.finalise().unwrap();
     let send_mail = account_settings!(context[account_hash].composing.send_mail).clone();
@@ -2109,16 +2718,130 @@ pub fn save_draft(
     }
 }
 
+/// The text of a draft as a part in the transfer encoding of the account's settings.
+fn body_part(
+    context: &Context,
+    account_hash: AccountHash,
+    content_type: ContentType,
+    body: String,
+) -> AttachmentBuilder {
+    let content_transfer_encoding =
+        account_settings!(context[account_hash].composing.transfer_encoding)
+            .content_transfer_encoding(body.as_bytes());
+    let raw = mime::encode_body(body.as_bytes(), &content_transfer_encoding);
+    Attachment::new(content_type, content_transfer_encoding, raw).into()
+}
+
+/// The text of a draft as a part, or with `markdown` a multipart/alternative of the text and its
+/// rendering to HTML.
+fn text_part(
+    context: &Context,
+    account_hash: AccountHash,
+    content_type: ContentType,
+    body: String,
+    markdown: bool,
+) -> Result<AttachmentBuilder> {
+    if !markdown {
+        return Ok(body_part(context, account_hash, content_type, body));
+    }
+    let html = markdown_to_html(&body)?;
+    let parts = vec![
+        body_part(context, account_hash, content_type, body),
+        body_part(
+            context,
+            account_hash,
+            ContentType::Text {
+                kind: Text::Html,
+                charset: Charset::UTF8,
+                parameters: vec![],
+            },
+            html,
+        ),
+    ];
+    let boundary = ContentType::make_boundary(&parts);
+    Ok(Attachment::new(
+        ContentType::Multipart {
+            boundary: boundary.into_bytes(),
+            kind: MultipartType::Alternative,
+            parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
+        },
+        Default::default(),
+        Vec::new(),
+    )
+    .into())
+}
+
+#[cfg(feature = "markdown")]
+fn markdown_to_html(text: &str) -> Result<String> {
+    use pulldown_cmark::{html, Options, Parser};
+    let mut ret = String::with_capacity(text.len() * 3 / 2);
+    html::push_html(
+        &mut ret,
+        Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH),
+    );
+    Ok(ret)
+}
+
+#[cfg(not(feature = "markdown"))]
+fn markdown_to_html(_text: &str) -> Result<String> {
+    Err(MeliError::new(
+        "Markdown drafts require meli to be built with the `markdown` feature.",
+    ))
+}
+
+/// The body of a draft as it is sent: its text, along with its attachments if it has any. The
+/// text and attachments are taken out of `draft`.
+fn draft_body(
+    context: &Context,
+    account_hash: AccountHash,
+    draft: &mut Draft,
+    markdown: bool,
+) -> Result<AttachmentBuilder> {
+    let format_flowed = *account_settings!(context[account_hash].composing.format_flowed);
+    let mut content_type = ContentType::default();
+    if format_flowed {
+        if let ContentType::Text {
+            ref mut parameters, ..
+        } = content_type
+        {
+            parameters.push((b"format".to_vec(), b"flowed".to_vec()));
+        }
+    }
+    let mut body: AttachmentBuilder = text_part(
+        context,
+        account_hash,
+        content_type,
+        std::mem::replace(&mut draft.body, String::new()),
+        markdown,
+    )?;
+    if !draft.attachments.is_empty() {
+        let mut parts = std::mem::replace(&mut draft.attachments, Vec::new());
+        parts.insert(0, body);
+        let boundary = ContentType::make_boundary(&parts);
+        body = Attachment::new(
+            ContentType::Multipart {
+                boundary: boundary.into_bytes(),
+                kind: MultipartType::Mixed,
+                parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
+            },
+            Default::default(),
+            Vec::new(),
+        )
+        .into();
+    }
+    Ok(body)
+}
+
 pub fn send_draft_async(
     #[cfg(feature = "gpgme")] gpg_state: gpg::GpgComposeState,
     context: &mut Context,
     account_hash: AccountHash,
     mut draft: Draft,
+    markdown: bool,
     mailbox_type: SpecialUsageMailbox,
     flags: Flag,
 ) -> Result<Pin<Box<dyn Future<Output = Result<()>> + Send>>> {
     let store_sent_mail = *account_settings!(context[account_hash].composing.store_sent_mail);
-    let format_flowed = *account_settings!(context[account_hash].composing.format_flowed);
     let event_sender = context.sender.clone();
     #[cfg(feature = "gpgme")]
     let mut filters_stack: Vec<
@@ -2136,6 +2859,15 @@ pub fn send_draft_async(
             gpg_state.sign_keys.clone(),
         )?));
     } else if gpg_state.encrypt_mail.is_true() {
+        let protected_headers = if *account_settings!(context[account_hash].pgp.protected_headers) {
+            let headers = draft.headers().clone();
+            if draft.headers().contains_key("Subject") {
+                draft.set_header("Subject", melib::email::pgp::PROTECTED_SUBJECT.to_string());
+            }
+            Some(headers)
+        } else {
+            None
+        };
         filters_stack.push(Box::new(crate::components::mail::pgp::encrypt_filter(
             if gpg_state.sign_mail.is_true() {
                 Some(gpg_state.sign_keys.clone())
@@ -2143,46 +2875,50 @@ pub fn send_draft_async(
                 None
             },
             gpg_state.encrypt_keys.clone(),
+            protected_headers,
         )?));
     }
+    #[cfg(feature = "gpgme")]
+    let autocrypt_header = if *account_settings!(context[account_hash].pgp.autocrypt) {
+        draft
+            .headers()
+            .get("From")
+            .and_then(|from| melib::email::parser::address::mailbox(from.as_bytes()).ok())
+            .map(|(_, from)| {
+                crate::components::mail::pgp::autocrypt_header(
+                    from.get_email(),
+                    account_settings!(context[account_hash].pgp.sign_key).clone(),
+                    *account_settings!(context[account_hash].pgp.autocrypt_prefer_encrypt),
+                )
+            })
+    } else {
+        None
+    };
     let send_mail = account_settings!(context[account_hash].composing.send_mail).clone();
     let send_cb = context.accounts[&account_hash].send_async(send_mail);
-    let mut content_type = ContentType::default();
-    if format_flowed {
-        if let ContentType::Text {
-            ref mut parameters, ..
-        } = content_type
-        {
-            parameters.push((b"format".to_vec(), b"flowed".to_vec()));
-        }
-    }
-    let mut body: AttachmentBuilder = Attachment::new(
-        content_type,
-        Default::default(),
-        std::mem::replace(&mut draft.body, String::new()).into_bytes(),
-    )
-    .into();
-    if !draft.attachments.is_empty() {
-        let mut parts = std::mem::replace(&mut draft.attachments, Vec::new());
-        parts.insert(0, body);
-        let boundary = ContentType::make_boundary(&parts);
-        body = Attachment::new(
-            ContentType::Multipart {
-                boundary: boundary.into_bytes(),
-                kind: MultipartType::Mixed,
-                parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
-            },
-            Default::default(),
-            Vec::new(),
-        )
-        .into();
-    }
+    let mut body = draft_body(context, account_hash, &mut draft, markdown)?;
     Ok(Box::pin(async move {
         #[cfg(feature = "gpgme")]
         for f in filters_stack {
             body = f(body).await?;
         }
 
+        #[cfg(feature = "gpgme")]
+        if let Some(header) = autocrypt_header {
+            /* Mail is sent without the header if the account has no key. */
+            match header.await {
+                Ok(header) => {
+                    draft.set_header("Autocrypt", header.to_header_value());
+                }
+                Err(err) => {
+                    log_target(
+                        "ui",
+                        format!("Could not add the Autocrypt header: {}", err),
+                        WARN,
+                    );
+                }
+            }
+        }
         draft.attachments.insert(0, body);
         let message = Arc::new(draft.finalise()?);
         let ret = send_cb(message.clone()).await;
@@ -2210,14 +2946,24 @@ pub fn send_draft_async(
                 ))))
                 .unwrap();
         } else if !store_sent_mail && is_ok {
-            let f = create_temp_file(message.as_bytes(), None, None, false);
-            log(
-                format!(
-                    "store_sent_mail is false; stored sent mail to {}",
-                    f.path().display()
+            match create_temp_file(message.as_bytes(), None, None, false) {
+                Ok(f) => log_target(
+                    "ui",
+                    format!(
+                        "store_sent_mail is false; stored sent mail to {}",
+                        f.path().display()
+                    ),
+                    INFO,
                 ),
-                INFO,
-            );
+                Err(err) => log_target(
+                    "ui",
+                    format!(
+                        "store_sent_mail is false; could not store sent mail: {}",
+                        err
+                    ),
+                    ERROR,
+                ),
+            }
         }
         ret
     }))
@@ -2255,3 +3001,274 @@ fn attribution_string(
     );
     melib::datetime::timestamp_to_string(date, Some(fmt.as_str()), posix)
 }
+
+/* Replied envelope details
+ * %+f — the sender's name and email address.
+ * %+n — the sender's name (or email address, if no name is included).
+ * %+a — the sender's email address.
+ * %+s — the subject.
+ * %+d — the date.
+ */
+/// Split a recipient header value at top-level commas, keeping quoted strings, angle-bracketed
+/// addresses and address groups intact.
+fn split_address_list(value: &str) -> Vec<&str> {
+    let mut ret = vec![];
+    let mut start = 0;
+    let (mut in_quotes, mut in_angle, mut in_group, mut escaped) = (false, false, false, false);
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            _ if in_quotes => {}
+            '<' => in_angle = true,
+            '>' => in_angle = false,
+            ':' if !in_angle => in_group = true,
+            ';' if in_group => in_group = false,
+            ',' if !in_angle && !in_group => {
+                ret.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ret.push(value[start..].trim());
+    ret.retain(|s| !s.is_empty());
+    ret
+}
+
+fn reply_template_string(template: &str, envelope: &Envelope) -> String {
+    let sender = envelope.from().get(0);
+    template
+        .replace(
+            "%+f",
+            &sender
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|| "\"\"".to_string()),
+        )
+        .replace(
+            "%+n",
+            &sender
+                .map(|addr| addr.get_display_name().unwrap_or_else(|| addr.get_email()))
+                .unwrap_or_else(|| "\"\"".to_string()),
+        )
+        .replace(
+            "%+a",
+            &sender
+                .map(|addr| addr.get_email())
+                .unwrap_or_else(|| "\"\"".to_string()),
+        )
+        .replace("%+s", &envelope.subject())
+        .replace("%+d", envelope.date_as_str())
+}
+
+/// Parts of `body` that aren't shown as its text, i.e. what a reply or forward can carry over.
+fn original_attachments(body: &Attachment) -> Vec<Attachment> {
+    fn original_attachments_rec(a: &Attachment, ret: &mut Vec<Attachment>) {
+        match a.content_type {
+            ContentType::Multipart {
+                kind: MultipartType::Alternative,
+                ..
+            }
+            | ContentType::Multipart {
+                kind: MultipartType::Encrypted,
+                ..
+            } => {}
+            ContentType::Multipart {
+                kind: MultipartType::Signed,
+                ref parts,
+                ..
+            } => {
+                /* Only the signed content, not the signature */
+                if let Some(p) = parts.first() {
+                    original_attachments_rec(p, ret);
+                }
+            }
+            ContentType::Multipart { ref parts, .. } => {
+                for p in parts {
+                    original_attachments_rec(p, ret);
+                }
+            }
+            ContentType::PGPSignature | ContentType::CMSSignature => {}
+            ContentType::Text { .. } if a.content_disposition.kind.is_inline() => {}
+            _ => ret.push(a.clone()),
+        }
+    }
+
+    let mut ret = Vec::new();
+    if let ContentType::Multipart { .. } = body.content_type {
+        original_attachments_rec(body, &mut ret);
+    }
+    ret
+}
+
+/// Turn a part of an existing message into a draft attachment, keeping its content type and
+/// disposition.
+fn carry_over_attachment(a: Attachment) -> AttachmentBuilder {
+    let filename = a.filename();
+    let mut ret = AttachmentBuilder::default();
+    /* Draft attachments hold decoded content; text is decoded to UTF-8 */
+    ret.set_raw(decode(&a, None)).set_body_to_raw();
+    ret.set_content_type(match a.content_type {
+        ContentType::Text {
+            kind, parameters, ..
+        } => ContentType::Text {
+            kind,
+            parameters,
+            charset: Charset::UTF8,
+        },
+        ContentType::Other { name: None, tag } => ContentType::Other {
+            name: filename.clone(),
+            tag,
+        },
+        ContentType::OctetStream { name: None } => ContentType::OctetStream {
+            name: filename.clone(),
+        },
+        content_type => content_type,
+    });
+    let mut disposition = a.content_disposition;
+    if disposition.filename.is_none() {
+        disposition.filename = filename;
+    }
+    ret.set_content_disposition(disposition);
+    ret
+}
+
+#[cfg(feature = "markdown")]
+#[test]
+fn test_composer_markdown() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account(
+        &[message(
+            "hello",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "composing = { markdown = true }\n",
+        "[pager]\nhtml_filter = \"echo rendered preview\"\n",
+    );
+    h.wait_for("hello");
+    h.keys(&[Key::Char('m')]);
+    h.wait_for("COMPOSING MESSAGE IN MARKDOWN");
+    h.keys(&[Key::Char('p')]);
+    h.wait_for("COMPOSING MESSAGE IN MARKDOWN (PREVIEW)");
+    h.wait_for("rendered preview");
+    h.keys(&[Key::Char('p')]);
+    h.wait_until("preview off", |h| h.find("rendered preview").is_none());
+    h.command("toggle markdown");
+    h.wait_until("markdown off", |h| h.find("IN MARKDOWN").is_none());
+    assert!(h.find("COMPOSING MESSAGE").is_some(), "{}", h.text());
+
+    /* The body is sent as its text and its rendering to HTML. */
+    let account_hash = h.state.context.accounts[0].hash();
+    let part: Attachment = text_part(
+        &h.state.context,
+        account_hash,
+        ContentType::default(),
+        "Hello *world*\n".to_string(),
+        true,
+    )
+    .unwrap()
+    .into();
+    match part.content_type() {
+        ContentType::Multipart {
+            kind: MultipartType::Alternative,
+            parts,
+            ..
+        } => {
+            assert_eq!(parts.len(), 2);
+            assert!(parts[0].content_type().is_text());
+            assert_eq!(parts[0].text(), "Hello *world*\n");
+            assert!(parts[1].content_type().is_text_html());
+            assert!(parts[1].text().contains("<em>world</em>"));
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_composer_preview_mail() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account(
+        &[message(
+            "hello",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "composing = { default_header_values = { Subject = \"preview me\" } }\n",
+        "",
+    );
+    h.wait_for("hello");
+    h.keys(&[Key::Char('m')]);
+    h.wait_for("COMPOSING MESSAGE");
+    let attachment = h.maildir().join("notes.txt");
+    std::fs::write(&attachment, "some notes\n").unwrap();
+    h.command(&format!("add-attachment {}", attachment.display()));
+    h.wait_for("1 attachments");
+    h.keys(&[Key::Char('v')]);
+    h.wait_for("Subject: preview me");
+    assert!(h.find("view mail").is_some(), "{}", h.text());
+    assert!(h.find("\"notes.txt\"").is_some(), "{}", h.text());
+    assert!(h.find("composing").is_some(), "{}", h.text());
+}
+
+#[test]
+fn test_composer_nicknames() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "hello",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("hello");
+    let mut card = melib::addressbook::Card::new();
+    card.set_name("Bob".to_string());
+    card.set_additionalname("bobby".to_string());
+    card.set_email("bob@example.com".to_string());
+    h.state.context.accounts[0].address_book.add_card(card);
+    h.keys(&[Key::Char('m')]);
+    h.wait_for("COMPOSING MESSAGE");
+    /* Date, From, To. */
+    h.keys(&[Key::Down, Key::Down, Key::Char('\n')]);
+    h.wait_until("insert mode", |h| h.state.mode == UIMode::Insert);
+    h.type_str("bobby");
+    h.keys(&[Key::Char('\t')]);
+    h.wait_for("Bob <bob@example.com>");
+    h.type_str(", carol@example.com");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_until("normal mode", |h| h.state.mode == UIMode::Normal);
+    /* Bob is a contact, Carol was never written to. */
+    h.keys(&[Key::Char('s')]);
+    h.wait_for("New recipients");
+    assert!(h.find("carol@example.com").is_some(), "{}", h.text());
+    let row = h.find("New recipients").unwrap();
+    assert!(!h.row(row).contains("bob@"), "{}", h.text());
+}
+
+#[cfg(feature = "gpgme")]
+#[test]
+fn test_composer_autocrypt_recommendation() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account(
+        &[message(
+            "hello",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "composing = { default_header_values = { To = \"bob@example.com\" } }\n\
+         pgp = { autocrypt = true }\n",
+        "",
+    );
+    h.wait_for("hello");
+    h.keys(&[Key::Char('m')]);
+    h.wait_for("COMPOSING MESSAGE");
+    /* Bob has no key, so nothing is encrypted. */
+    h.wait_for("autocrypt: disable");
+    assert!(h.find("don't encrypt").is_some(), "{}", h.text());
+}
@@ -266,6 +266,52 @@ impl Component for KeySelection {
     }
 }
 
+/// Whether mail can be encrypted to a recipient.
+#[derive(Debug)]
+pub enum RecipientKey {
+    Pending(JoinHandle<Result<Option<melib::gpgme::Key>>>),
+    Found(melib::gpgme::Key),
+    Missing,
+}
+
+/// Look up a usable encryption key for `address`: in the keyring, then with the
+/// `remote_lookup` mechanisms if given, then by importing the key of its `Autocrypt` header.
+pub fn lookup_recipient_key(
+    address: String,
+    remote_lookup: Option<melib::gpgme::LocateKey>,
+    autocrypt_key: Option<Vec<u8>>,
+) -> impl Future<Output = Result<Option<melib::gpgme::Key>>> + Send {
+    use melib::gpgme::*;
+    fn usable(keys: Vec<Key>) -> Option<Key> {
+        keys.into_iter().find(|k| {
+            k.can_encrypt() && !(k.revoked() || k.expired() || k.disabled() || k.invalid())
+        })
+    }
+    async move {
+        let mut ctx = Context::new()?;
+        if let Some(key) = usable(ctx.keylist(false, Some(address.clone()))?.await?) {
+            return Ok(Some(key));
+        }
+        if let Some(mechanisms) = remote_lookup {
+            let mut ctx = Context::new()?;
+            ctx.set_flag(GpgmeFlag::OfflineMode, false)?;
+            ctx.set_auto_key_locate(mechanisms)?;
+            ctx.set_locate_keys(true)?;
+            if let Some(key) = usable(ctx.keylist(false, Some(address.clone()))?.await?) {
+                return Ok(Some(key));
+            }
+        }
+        if let Some(keydata) = autocrypt_key {
+            let mut ctx = Context::new()?;
+            let data = ctx.new_data_mem(&keydata)?;
+            ctx.import_key(data)?.await?;
+            let mut ctx = Context::new()?;
+            return Ok(usable(ctx.keylist(false, Some(address))?.await?));
+        }
+        Ok(None)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GpgComposeState {
     pub sign_mail: ToggleFlag,
@@ -0,0 +1,276 @@
+/*
+ * meli - connection diagnostics tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::jobs::{JobId, JoinHandle};
+use melib::backends::{Diagnostic, DiagnosticStatus};
+
+#[derive(Debug)]
+enum DoctorState {
+    Running(JoinHandle<Result<Vec<Diagnostic>>>),
+    Finished(Vec<Diagnostic>),
+    Failed(MeliError),
+}
+
+/// Tab with the outcome of the connection checks of an account's backend.
+#[derive(Debug)]
+pub struct AccountDoctor {
+    cursor: (usize, usize),
+    account_pos: usize,
+    content: CellBuffer,
+    state: DoctorState,
+    dirty: bool,
+    theme_default: ThemeAttribute,
+    id: ComponentId,
+}
+
+impl fmt::Display for AccountDoctor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", AccountDoctor::DESCRIPTION)
+    }
+}
+
+impl AccountDoctor {
+    pub const DESCRIPTION: &'static str = "doctor";
+    pub fn new(account_pos: usize, context: &Context) -> Result<AccountDoctor> {
+        let theme_default = crate::conf::value(context, "theme_default");
+        let account = &context.accounts[account_pos];
+        let job = account.backend.read().unwrap().diagnose().map_err(|_| {
+            MeliError::new(format!(
+                "Connection checks are not supported by the backend of account {}.",
+                account.name()
+            ))
+        })?;
+        let handle = if account.backend_capabilities.is_async {
+            account.job_executor.spawn_specialized(job)
+        } else {
+            account.job_executor.spawn_blocking(job)
+        };
+        Ok(AccountDoctor {
+            cursor: (0, 0),
+            account_pos,
+            content: Self::new_content(theme_default),
+            state: DoctorState::Running(handle),
+            dirty: true,
+            theme_default,
+            id: ComponentId::new_v4(),
+        })
+    }
+
+    fn new_content(theme_default: ThemeAttribute) -> CellBuffer {
+        let mut default_cell = Cell::with_char(' ');
+        default_cell
+            .set_fg(theme_default.fg)
+            .set_bg(theme_default.bg)
+            .set_attrs(theme_default.attrs);
+        let mut content = CellBuffer::new(120, 5, default_cell);
+        content.set_growable(true);
+        content
+    }
+
+    fn write_line(&mut self, text: &str, (col, line): (usize, usize), fg: Color, attrs: Attr) {
+        let width = self.content.size().0;
+        write_string_to_grid(
+            text,
+            &mut self.content,
+            fg,
+            self.theme_default.bg,
+            attrs,
+            ((col, line), (width - 1, line)),
+            None,
+        );
+    }
+
+    fn draw_diagnostics(&mut self, diagnostics: &[Diagnostic], mut line: usize) {
+        for d in diagnostics {
+            let (marker, fg) = match d.status {
+                DiagnosticStatus::Ok => ("[ OK ]", Color::Green),
+                DiagnosticStatus::Warning => ("[WARN]", Color::Yellow),
+                DiagnosticStatus::Failed => ("[FAIL]", Color::Red),
+                DiagnosticStatus::Skipped => ("[SKIP]", self.theme_default.fg),
+            };
+            self.write_line(marker, (1, line), fg, Attr::BOLD);
+            self.write_line(d.name, (8, line), self.theme_default.fg, Attr::BOLD);
+            line += 1;
+            for details in d.details.lines() {
+                self.write_line(
+                    details,
+                    (8, line),
+                    self.theme_default.fg,
+                    self.theme_default.attrs,
+                );
+                line += 1;
+            }
+        }
+    }
+}
+
+impl Component for AccountDoctor {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.content = Self::new_content(self.theme_default);
+        let account_name = context.accounts[self.account_pos].name().to_string();
+        self.write_line(
+            &format!("Connection checks of {}", account_name),
+            (1, 0),
+            self.theme_default.fg,
+            Attr::BOLD | Attr::UNDERLINE,
+        );
+        match std::mem::replace(
+            &mut self.state,
+            DoctorState::Failed(MeliError::new("Checks are being redrawn.")),
+        ) {
+            DoctorState::Running(handle) => {
+                self.write_line(
+                    "Running checks…",
+                    (1, 2),
+                    self.theme_default.fg,
+                    self.theme_default.attrs,
+                );
+                self.state = DoctorState::Running(handle);
+            }
+            DoctorState::Failed(err) => {
+                self.write_line(
+                    &format!("Could not run checks: {}", err),
+                    (1, 2),
+                    self.theme_default.fg,
+                    self.theme_default.attrs,
+                );
+                self.state = DoctorState::Failed(err);
+            }
+            DoctorState::Finished(diagnostics) => {
+                self.draw_diagnostics(&diagnostics, 2);
+                self.state = DoctorState::Finished(diagnostics);
+            }
+        }
+
+        /* self.content may have been resized with write_string_to_grid() calls above since it has
+         * growable set */
+        let (width, height) = self.content.size();
+        let (cols, rows) = (width!(area), height!(area));
+        self.cursor = (
+            std::cmp::min(width.saturating_sub(cols), self.cursor.0),
+            std::cmp::min(height.saturating_sub(rows), self.cursor.1),
+        );
+        clear_area(grid, area, self.theme_default);
+        copy_area(
+            grid,
+            &self.content,
+            area,
+            (
+                (
+                    std::cmp::min((width - 1).saturating_sub(cols), self.cursor.0),
+                    std::cmp::min((height - 1).saturating_sub(rows), self.cursor.1),
+                ),
+                (
+                    std::cmp::min(self.cursor.0 + cols, width - 1),
+                    std::cmp::min(self.cursor.1 + rows, height - 1),
+                ),
+            ),
+        );
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.dirty = true;
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if matches_job(&self.state, job_id) =>
+            {
+                if let DoctorState::Running(ref mut handle) = self.state {
+                    self.state = match handle.chan.try_recv() {
+                        Ok(Some(Ok(diagnostics))) => DoctorState::Finished(diagnostics),
+                        Ok(Some(Err(err))) => DoctorState::Failed(err),
+                        Err(_) | Ok(None) => {
+                            DoctorState::Failed(MeliError::new("Job was canceled."))
+                        }
+                    };
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts["general"]["scroll_left"]) && self.cursor.0 != 0 =>
+            {
+                self.cursor.0 -= 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_right"]) => {
+                self.cursor.0 += 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_up"]) => {
+                self.cursor.1 = self.cursor.1.saturating_sub(1);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_down"]) => {
+                self.cursor.1 += 1;
+                self.dirty = true;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+fn matches_job(state: &DoctorState, job_id: &JobId) -> bool {
+    if let DoctorState::Running(ref handle) = state {
+        handle.job_id == *job_id
+    } else {
+        false
+    }
+}
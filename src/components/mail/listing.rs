@@ -21,6 +21,8 @@
 
 use super::*;
 use crate::conf::accounts::JobRequest;
+use crate::conf::PreviewPaneOrientation;
+use crate::jobs::JoinHandle;
 use crate::types::segment_tree::SegmentTree;
 use melib::backends::EnvelopeHashBatch;
 use smallvec::SmallVec;
@@ -83,6 +85,7 @@ struct ColorCache {
     attachment_flag: ThemeAttribute,
     thread_snooze_flag: ThemeAttribute,
     tag_default: ThemeAttribute,
+    date_separator: ThemeAttribute,
 
     /* Conversations */
     subject: ThemeAttribute,
@@ -99,6 +102,7 @@ pub(super) struct EntryStrings {
     pub(super) flag: FlagString,
     pub(super) from: FromString,
     pub(super) tags: TagString,
+    pub(super) size: SizeString,
 }
 
 #[macro_export]
@@ -144,6 +148,18 @@ column_str!(struct FromString(String));
 column_str!(struct SubjectString(String));
 column_str!(struct FlagString(String));
 column_str!(struct TagString(String, SmallVec<[Option<Color>; 8]>));
+column_str!(struct SizeString(String));
+
+impl SizeString {
+    /// Empty if the size is unknown.
+    fn new(bytes: usize) -> Self {
+        if bytes == 0 {
+            SizeString(String::new())
+        } else {
+            SizeString(melib::Bytes(bytes).to_string())
+        }
+    }
+}
 
 #[derive(Debug)]
 struct AccountMenuEntry {
@@ -151,6 +167,21 @@ struct AccountMenuEntry {
     hash: AccountHash,
     index: usize,
     entries: SmallVec<[(usize, u32, bool, MailboxHash); 16]>,
+    /// Hash, name and unread count of each tag shown below the mailboxes.
+    tags: SmallVec<[(u64, String, usize); 16]>,
+    /// The account's `group` setting.
+    group: Option<String>,
+}
+
+impl AccountMenuEntry {
+    /// Number of sidebar lines occupied by the tags section.
+    fn tags_height(&self) -> usize {
+        if self.tags.is_empty() {
+            0
+        } else {
+            self.tags.len() + 1
+        }
+    }
 }
 
 pub trait MailListingTrait: ListingTrait {
@@ -177,6 +208,29 @@ pub trait MailListingTrait: ListingTrait {
             return;
         }
         let env_hashes = EnvelopeHashBatch::try_from(envs_to_set.as_slice()).unwrap();
+        if a.is_mutating() {
+            if let Err(err) = account.check_writable() {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        err.to_string(),
+                    )));
+                return;
+            }
+        }
+        let action = match a {
+            ListingAction::SetSeen => Some("mark seen".to_string()),
+            ListingAction::SetUnseen => Some("mark unseen".to_string()),
+            ListingAction::Tag(Add(ref tag_str)) => Some(format!("tag +{}", tag_str)),
+            ListingAction::Tag(Remove(ref tag_str)) => Some(format!("tag -{}", tag_str)),
+            ListingAction::Delete => Some("delete".to_string()),
+            ListingAction::CopyTo(ref mailbox_path) => Some(format!("copy to {}", mailbox_path)),
+            ListingAction::MoveTo(ref mailbox_path) => Some(format!("move to {}", mailbox_path)),
+            _ => None,
+        };
+        if let Some(action) = action {
+            account.record_action(envs_to_set.iter().copied(), mailbox_hash, &action);
+        }
         match a {
             ListingAction::SetSeen => {
                 let job = account.backend.write().unwrap().set_flags(
@@ -446,6 +500,49 @@ pub trait MailListingTrait: ListingTrait {
                         "Unimplemented.".into(),
                     )));
             }
+            ListingAction::BulkReplyTemplate(ref name, send) => {
+                let template = if let Some(t) =
+                    account_settings!(context[account_hash].composing.reply_templates).get(name)
+                {
+                    t.clone()
+                } else {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                            "No reply template named `{}` in composing.reply_templates.",
+                            name
+                        ))));
+                    return;
+                };
+                for env_hash in envs_to_set {
+                    let composer = Composer::reply_with_template(
+                        (account_hash, mailbox_hash, env_hash),
+                        &template,
+                        context,
+                    );
+                    if !send {
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+                        continue;
+                    }
+                    if let Err(err) = super::compose::send_draft(
+                        ToggleFlag::False,
+                        context,
+                        account_hash,
+                        composer.draft().clone(),
+                        SpecialUsageMailbox::Sent,
+                        Flag::SEEN,
+                        true,
+                    ) {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not send templated reply".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+            }
             _ => unreachable!(),
         }
         self.set_dirty(true);
@@ -453,6 +550,24 @@ pub trait MailListingTrait: ListingTrait {
 
     fn row_updates(&mut self) -> &mut SmallVec<[ThreadHash; 8]>;
     fn selection(&mut self) -> &mut HashMap<ThreadHash, bool>;
+    /// Select or deselect every entry, or invert the selection. Returns how many entries are
+    /// selected.
+    fn change_selection(&mut self, change: SelectionChange) -> usize {
+        let mut row_updates: SmallVec<[ThreadHash; 8]> = SmallVec::new();
+        for (k, v) in self.selection().iter_mut() {
+            let new_val = match change {
+                SelectionChange::All => true,
+                SelectionChange::None => false,
+                SelectionChange::Invert => !*v,
+            };
+            if *v != new_val {
+                *v = new_val;
+                row_updates.push(*k);
+            }
+        }
+        self.row_updates().extend(row_updates);
+        self.selection().values().filter(|v| **v).count()
+    }
     fn get_focused_items(&self, _context: &Context) -> SmallVec<[ThreadHash; 8]>;
     fn redraw_threads_list(
         &mut self,
@@ -484,12 +599,695 @@ pub trait ListingTrait: Component {
     ) {
     }
     fn unfocused(&self) -> bool;
-    fn set_modifier_active(&mut self, _new_val: bool) {}
+    /// Close the open entry and return to the list.
+    fn exit_entry(&mut self, _context: &mut Context) {}
     fn set_modifier_command(&mut self, _new_val: Option<Modifier>) {}
     fn modifier_command(&self) -> Option<Modifier> {
         None
     }
     fn set_movement(&mut self, mvm: PageMovement);
+    /// Save the cursor position, sort order and filter, see `terminal.restore_session`.
+    fn save_listing_session(&self, _session: &mut ListingSession) {}
+    /// An envelope of the entry under the cursor, or `None` if the listing hasn't been populated
+    /// yet.
+    fn envelope_under_cursor(&self, _context: &Context) -> Option<EnvelopeHash> {
+        None
+    }
+    /// A message of each entry dated from the start of `range` until before its end, in the
+    /// listing's order, or `None` if the listing style can't tell.
+    fn dated_entries(&self, _range: (UnixTimestamp, UnixTimestamp)) -> Option<Vec<EnvelopeHash>> {
+        None
+    }
+    /// Move the cursor to the entry of the message in `thread_node`. Returns `false` if it's not
+    /// in the listing.
+    fn select_thread_node(&mut self, _thread_node: ThreadNodeHash, _context: &Context) -> bool {
+        false
+    }
+    /// Move the cursor to the next entry with unseen messages after the cursor, or the first one
+    /// if `from_start` is set. With `wrap`, search from the start when there's none after the
+    /// cursor. An entry that is open is replaced with the new one. Returns `false` if there is
+    /// none.
+    fn select_unseen(&mut self, _from_start: bool, _wrap: bool, _context: &mut Context) -> bool {
+        false
+    }
+    /// Whether the entries between the one under the cursor when visual mode started and the
+    /// cursor are being selected.
+    fn visual_mode(&self) -> bool {
+        false
+    }
+    fn start_visual_mode(&mut self) {}
+    /// Stop extending the selection, and deselect the range if `deselect` is true.
+    fn end_visual_mode(&mut self, _deselect: bool) {}
+    /// How many entries are selected.
+    fn selected_count(&self) -> usize {
+        0
+    }
+    /// The messages of the selected entries.
+    fn selected_envelopes(&self, _context: &Context) -> Vec<EnvelopeHash> {
+        Vec::new()
+    }
+    fn preview_pane_mut(&mut self) -> Option<&mut PreviewPane> {
+        None
+    }
+}
+
+/// The dates of `goto date` and `limit`: a date of `melib::datetime::date_range`, or `since:` or
+/// `before:` a date or a number of days, weeks, months or years ago such as `2w`.
+fn date_spec_range(spec: &str) -> Option<(UnixTimestamp, UnixTimestamp)> {
+    const DAY: UnixTimestamp = 24 * 60 * 60;
+    let start = |date: &str| {
+        if let Some((start, _)) = melib::datetime::date_range(date) {
+            return Some(start);
+        }
+        let unit = date.chars().last()?;
+        let count: UnixTimestamp = date[..date.len() - unit.len_utf8()].parse().ok()?;
+        let unit = match unit {
+            'd' => DAY,
+            'w' => 7 * DAY,
+            'm' => 30 * DAY,
+            'y' => 365 * DAY,
+            _ => return None,
+        };
+        Some(melib::datetime::now().saturating_sub(count.checked_mul(unit)?))
+    };
+    if spec.starts_with("since:") {
+        Some((start(spec["since:".len()..].trim())?, UnixTimestamp::MAX))
+    } else if spec.starts_with("before:") {
+        Some((0, start(spec["before:".len()..].trim())?))
+    } else {
+        melib::datetime::date_range(spec)
+    }
+}
+
+/// Pick the entry to move to out of the `unseen` entries, see `ListingTrait::select_unseen`.
+fn next_unseen_idx(
+    unseen: impl Iterator<Item = usize>,
+    cursor: Option<usize>,
+    wrap: bool,
+) -> Option<usize> {
+    let mut first: Option<usize> = None;
+    let mut next: Option<usize> = None;
+    for idx in unseen {
+        first = Some(first.map_or(idx, |f| std::cmp::min(f, idx)));
+        if cursor.map(|c| idx > c).unwrap_or(true) {
+            next = Some(next.map_or(idx, |n| std::cmp::min(n, idx)));
+        }
+    }
+    next.or(if wrap { first } else { None })
+}
+
+/// The line above the results of a search of a listing, naming the filter preset whose query it
+/// was, if any.
+fn search_results_header(
+    context: &Context,
+    account_hash: AccountHash,
+    filter_term: &str,
+    results: usize,
+) -> String {
+    match account_settings!(context[account_hash].listing.filter_presets)
+        .iter()
+        .find(|(_, preset)| preset.query == filter_term)
+    {
+        Some((name, _)) => format!(
+            "{} results for preset `{}`: `{}` (Press ESC to exit)",
+            results, name, filter_term
+        ),
+        None => format!(
+            "{} results for `{}` (Press ESC to exit)",
+            results, filter_term
+        ),
+    }
+}
+
+/// What to show in place of the messages of a mailbox that can't be listed yet.
+fn mailbox_status(
+    context: &Context,
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+) -> String {
+    let entry = &context.accounts[&account_hash][&mailbox_hash];
+    if let crate::conf::accounts::MailboxStatus::Deferred = entry.status {
+        format!(
+            "Mailbox not loaded, press {} to load it.",
+            context.settings.shortcuts.listing.open_mailbox
+        )
+    } else {
+        entry.status()
+    }
+}
+
+/// The collation of the `listing.collation_locale` setting of a mailbox.
+fn collator(context: &Context, account_hash: AccountHash, mailbox_hash: MailboxHash) -> Collator {
+    mailbox_settings!(
+        context[account_hash][&mailbox_hash]
+            .listing
+            .collation_locale
+    )
+    .as_ref()
+    .map(|locale| Collator::new(locale))
+    .unwrap_or_else(Collator::from_env)
+}
+
+/// The strings of the flag column of a mailbox's listing: its `listing.*_flag` settings, or emoji,
+/// or ASCII letters with `terminal.ascii_drawing` so that columns stay aligned without an emoji
+/// font.
+#[derive(Debug, Default)]
+pub(super) struct FlagIndicators {
+    attachment: String,
+    snoozed: String,
+    watched: String,
+    note: String,
+    replied: String,
+    flagged: String,
+    signed: String,
+    encrypted: String,
+}
+
+impl FlagIndicators {
+    pub(super) fn new(
+        context: &Context,
+        account_hash: AccountHash,
+        mailbox_hash: MailboxHash,
+    ) -> Self {
+        let ascii = context.settings.terminal.ascii_drawing;
+        macro_rules! indicator {
+            ($field:ident, $unicode:literal, $ascii:literal) => {
+                mailbox_settings!(context[account_hash][&mailbox_hash].listing.$field)
+                    .clone()
+                    .unwrap_or_else(|| if ascii { $ascii } else { $unicode }.to_string())
+            };
+        }
+        FlagIndicators {
+            attachment: indicator!(attachment_flag, "📎", "@"),
+            snoozed: indicator!(snoozed_flag, "💤", "z"),
+            watched: indicator!(watched_flag, "🔔", "w"),
+            note: indicator!(note_flag, "📝", "n"),
+            replied: indicator!(replied_flag, "↩", "r"),
+            flagged: indicator!(flagged_flag, "⚑", "!"),
+            signed: indicator!(signed_flag, "🔏", "s"),
+            encrypted: indicator!(encrypted_flag, "🔒", "e"),
+        }
+    }
+
+    fn join(indicators: &[(bool, &str)]) -> FlagString {
+        FlagString(
+            indicators
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, indicator)| *indicator)
+                .collect(),
+        )
+    }
+
+    /// The flag column of message `e`.
+    pub(super) fn envelope(&self, e: &Envelope, has_note: bool) -> FlagString {
+        Self::join(&[
+            (e.has_attachments(), &self.attachment),
+            (has_note, &self.note),
+            (e.flags().is_replied(), &self.replied),
+            (e.flags().is_flagged(), &self.flagged),
+            (e.is_signed(), &self.signed),
+            (e.is_encrypted(), &self.encrypted),
+        ])
+    }
+
+    /// The flag column of `thread`, with the flags of its message `e`.
+    pub(super) fn thread(&self, thread: &Thread, e: &Envelope, has_note: bool) -> FlagString {
+        Self::join(&[
+            (thread.has_attachments(), &self.attachment),
+            (thread.snoozed(), &self.snoozed),
+            (thread.watched(), &self.watched),
+            (has_note, &self.note),
+            (e.flags().is_replied(), &self.replied),
+            (e.flags().is_flagged(), &self.flagged),
+            (e.is_signed(), &self.signed),
+            (e.is_encrypted(), &self.encrypted),
+        ])
+    }
+
+    /// The column of the snoozed indicator in the flag column of a thread with attachments.
+    pub(super) fn snoozed_column(&self) -> usize {
+        self.attachment.grapheme_width()
+    }
+}
+
+/// The rows of a listing with `listing.date_separators`, in slots that each hold an entry or a
+/// separator before the first entry of a group of dates.
+#[derive(Debug, Default)]
+pub struct DateSeparators {
+    enabled: bool,
+    /// The entry each separator is before, its slot and its label.
+    separators: Vec<(usize, usize, String)>,
+    entries: usize,
+    now: UnixTimestamp,
+    today: String,
+    yesterday: String,
+}
+
+/// What a slot of `DateSeparators` holds.
+#[derive(Debug, PartialEq)]
+pub enum Slot<'a> {
+    Entry(usize),
+    Separator(&'a str),
+}
+
+impl DateSeparators {
+    const DAY: UnixTimestamp = 24 * 60 * 60;
+
+    /// Start over for a mailbox sorted by `sort`.
+    pub fn reset(
+        &mut self,
+        context: &Context,
+        (account_hash, mailbox_hash): (AccountHash, MailboxHash),
+        sort: SortField,
+    ) {
+        let day = |t| melib::datetime::timestamp_to_string(t, Some("%Y-%m-%d"), true);
+        self.enabled = sort == SortField::Date
+            && *mailbox_settings!(context[account_hash][&mailbox_hash].listing.date_separators);
+        self.separators.clear();
+        self.entries = 0;
+        self.now = melib::datetime::now();
+        self.today = day(self.now);
+        self.yesterday = day(self.now.saturating_sub(Self::DAY));
+    }
+
+    /// Count the next entry, dated `timestamp`.
+    pub fn push(&mut self, timestamp: UnixTimestamp) {
+        if self.enabled {
+            let label = self.label(timestamp);
+            if self.separators.last().map(|s| &s.2) != Some(&label) {
+                let slot = self.entries + self.separators.len();
+                self.separators.push((self.entries, slot, label));
+            }
+        }
+        self.entries += 1;
+    }
+
+    fn label(&self, timestamp: UnixTimestamp) -> String {
+        let date = melib::datetime::timestamp_to_string(timestamp, Some("%Y-%m-%d"), true);
+        if date >= self.today {
+            "Today".to_string()
+        } else if date == self.yesterday {
+            "Yesterday".to_string()
+        } else if timestamp + 7 * Self::DAY > self.now {
+            "Last week".to_string()
+        } else {
+            melib::datetime::timestamp_to_string(timestamp, Some("%B %Y"), false)
+        }
+    }
+
+    /// The number of slots of `length` entries.
+    pub fn len(&self, length: usize) -> usize {
+        length + self.separators.len()
+    }
+
+    /// The slot of an entry.
+    pub fn slot(&self, entry: usize) -> usize {
+        entry
+            + self
+                .separators
+                .binary_search_by(|s| {
+                    if s.0 <= entry {
+                        std::cmp::Ordering::Less
+                    } else {
+                        std::cmp::Ordering::Greater
+                    }
+                })
+                .unwrap_err()
+    }
+
+    pub fn get(&self, slot: usize) -> Slot<'_> {
+        let before = match self.separators.binary_search_by(|s| s.1.cmp(&slot)) {
+            Ok(i) => return Slot::Separator(&self.separators[i].2),
+            Err(i) => i,
+        };
+        Slot::Entry(slot - before)
+    }
+
+    /// The first entry at or after `slot`.
+    pub fn entry_from(&self, slot: usize) -> usize {
+        match self.get(slot) {
+            Slot::Entry(entry) => entry,
+            Slot::Separator(_) => self.get(slot + 1).entry().unwrap_or(0),
+        }
+    }
+
+    /// What the `rows` slots from `top` show, as runs of consecutive entries with the row of
+    /// their first one, and the separators with their row.
+    pub fn page(
+        &self,
+        top: usize,
+        rows: usize,
+        length: usize,
+    ) -> (
+        SmallVec<[(usize, std::ops::Range<usize>); 8]>,
+        SmallVec<[(usize, String); 8]>,
+    ) {
+        let mut runs: SmallVec<[(usize, std::ops::Range<usize>); 8]> = SmallVec::new();
+        let mut separators = SmallVec::new();
+        for row in 0..rows {
+            match self.get(top + row) {
+                Slot::Separator(label) => separators.push((row, label.to_string())),
+                Slot::Entry(entry) if entry < length => match runs.last_mut() {
+                    Some((first_row, entries)) if *first_row + entries.len() == row => {
+                        entries.end = entry + 1;
+                    }
+                    _ => runs.push((row, entry..entry + 1)),
+                },
+                Slot::Entry(_) => break,
+            }
+        }
+        (runs, separators)
+    }
+}
+
+impl Slot<'_> {
+    fn entry(&self) -> Option<usize> {
+        match self {
+            Slot::Entry(entry) => Some(*entry),
+            Slot::Separator(_) => None,
+        }
+    }
+}
+
+/// Draw the separator `label` on row `y` of `area`.
+fn draw_date_separator(
+    grid: &mut CellBuffer,
+    area: Area,
+    y: usize,
+    label: &str,
+    attr: ThemeAttribute,
+) {
+    let row = (set_y(upper_left!(area), y), set_y(bottom_right!(area), y));
+    clear_area(grid, row, attr);
+    write_string_to_grid(
+        label,
+        grid,
+        attr.fg,
+        attr.bg,
+        attr.attrs,
+        (pos_inc(row.0, (1, 0)), row.1),
+        None,
+    );
+}
+
+/// The date of each entry of a listing in its order, to find the entries of a date range without
+/// searching the mailbox.
+#[derive(Debug, Default)]
+pub struct EntryDates {
+    /// The date of each entry and a message of it.
+    entries: Vec<(UnixTimestamp, EnvelopeHash)>,
+    /// Whether a date is newer than the one before it, or older.
+    increases: bool,
+    decreases: bool,
+}
+
+impl EntryDates {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.increases = false;
+        self.decreases = false;
+    }
+
+    /// Count the next entry, dated `timestamp`.
+    pub fn push(&mut self, timestamp: UnixTimestamp, env_hash: EnvelopeHash) {
+        if let Some(&(last, _)) = self.entries.last() {
+            self.increases |= timestamp > last;
+            self.decreases |= timestamp < last;
+        }
+        self.entries.push((timestamp, env_hash));
+    }
+
+    /// A message of each entry dated from `start` until before `end`, in the listing's order.
+    /// When the listing is sorted by date, the range is found with a binary search.
+    pub fn range(&self, (start, end): (UnixTimestamp, UnixTimestamp)) -> Vec<EnvelopeHash> {
+        if self.increases && self.decreases {
+            return self
+                .entries
+                .iter()
+                .filter(|&&(date, _)| date >= start && date < end)
+                .map(|&(_, env_hash)| env_hash)
+                .collect();
+        }
+        /* The index of the first entry on the later side of `timestamp`. */
+        let bisect = |timestamp: UnixTimestamp| {
+            self.entries
+                .binary_search_by(|&(date, _)| {
+                    if (date < timestamp) == self.increases {
+                        std::cmp::Ordering::Less
+                    } else {
+                        std::cmp::Ordering::Greater
+                    }
+                })
+                .unwrap_err()
+        };
+        let (first, last) = if self.increases {
+            (bisect(start), bisect(end))
+        } else {
+            (bisect(end), bisect(start))
+        };
+        self.entries[first..std::cmp::max(first, last)]
+            .iter()
+            .map(|&(_, env_hash)| env_hash)
+            .collect()
+    }
+}
+
+/// Runtime overrides of the `listing.preview_pane*` settings. They are kept by `Listing`, so that
+/// they survive changes of the listing style.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PreviewPaneOverrides {
+    pub enabled: Option<bool>,
+    pub orientation: Option<PreviewPaneOrientation>,
+    pub ratio: Option<usize>,
+}
+
+/// Where an opened entry is shown: next to the listing or in its place. The `listing.preview_pane*`
+/// settings can be overriden at runtime, otherwise the listing style's defaults apply.
+#[derive(Debug, Clone, Copy)]
+pub struct PreviewPane {
+    overrides: PreviewPaneOverrides,
+    default_enabled: bool,
+    default_orientation: PreviewPaneOrientation,
+    /// The area last split and the column, or row if horizontal, of the boundary.
+    boundary: Option<(Area, usize)>,
+    /// Whether the boundary is being dragged with the mouse.
+    dragging: bool,
+    /// Show only the opened entry, see `UIEvent::ZoomChange`.
+    maximized: bool,
+}
+
+impl PreviewPane {
+    pub fn new(default_enabled: bool, default_orientation: PreviewPaneOrientation) -> Self {
+        PreviewPane {
+            overrides: PreviewPaneOverrides::default(),
+            default_enabled,
+            default_orientation,
+            boundary: None,
+            dragging: false,
+            maximized: false,
+        }
+    }
+
+    pub fn overrides(&self) -> PreviewPaneOverrides {
+        self.overrides
+    }
+
+    pub fn set_overrides(&mut self, overrides: PreviewPaneOverrides) {
+        self.overrides = overrides;
+    }
+
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.maximized = maximized;
+    }
+
+    pub fn is_enabled(&self, context: &Context, coordinates: (AccountHash, MailboxHash)) -> bool {
+        self.overrides.enabled.unwrap_or_else(|| {
+            mailbox_settings!(context[coordinates.0][&coordinates.1].listing.preview_pane)
+                .unwrap_or(self.default_enabled)
+        })
+    }
+
+    pub fn orientation(
+        &self,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+    ) -> PreviewPaneOrientation {
+        self.overrides.orientation.unwrap_or_else(|| {
+            mailbox_settings!(
+                context[coordinates.0][&coordinates.1]
+                    .listing
+                    .preview_pane_orientation
+            )
+            .unwrap_or(self.default_orientation)
+        })
+    }
+
+    /// Percentage of the area given to the preview pane.
+    pub fn ratio(&self, context: &Context, coordinates: (AccountHash, MailboxHash)) -> usize {
+        let ratio = self.overrides.ratio.unwrap_or_else(|| {
+            mailbox_settings!(
+                context[coordinates.0][&coordinates.1]
+                    .listing
+                    .preview_pane_ratio
+            )
+            .unwrap_or(match self.orientation(context, coordinates) {
+                PreviewPaneOrientation::Vertical => 66,
+                PreviewPaneOrientation::Horizontal => {
+                    *mailbox_settings!(context[coordinates.0][&coordinates.1].pager.pager_ratio)
+                }
+            })
+        });
+        std::cmp::min(100, ratio)
+    }
+
+    pub fn toggle(&mut self, context: &Context, coordinates: (AccountHash, MailboxHash)) {
+        self.overrides.enabled = Some(!self.is_enabled(context, coordinates));
+    }
+
+    pub fn toggle_orientation(
+        &mut self,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+    ) {
+        self.overrides.orientation = Some(match self.orientation(context, coordinates) {
+            PreviewPaneOrientation::Vertical => PreviewPaneOrientation::Horizontal,
+            PreviewPaneOrientation::Horizontal => PreviewPaneOrientation::Vertical,
+        });
+    }
+
+    /// Grow the preview pane by `amount` percent, or shrink it if `amount` is negative.
+    pub fn resize(
+        &mut self,
+        amount: isize,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+    ) {
+        let ratio = self.ratio(context, coordinates) as isize + amount;
+        self.overrides.ratio = Some(std::cmp::max(5, std::cmp::min(95, ratio)) as usize);
+    }
+
+    /// Drag the boundary with the mouse. Returns `true` if `event` was handled.
+    pub fn process_mouse(
+        &mut self,
+        event: &UIEvent,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+    ) -> bool {
+        let (area, mid) = match self.boundary {
+            Some(boundary) => boundary,
+            None => return false,
+        };
+        let (upper_left, bottom_right) = area;
+        let vertical = self.orientation(context, coordinates) == PreviewPaneOrientation::Vertical;
+        /* Mouse coordinates start from 1 */
+        let pos = |x: u16, y: u16| {
+            (
+                usize::from(x).saturating_sub(1),
+                usize::from(y).saturating_sub(1),
+            )
+        };
+        match *event {
+            UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, y))) => {
+                let (x, y) = pos(x, y);
+                self.dragging = if vertical {
+                    x == mid && y >= get_y(upper_left) && y <= get_y(bottom_right)
+                } else {
+                    y == mid && x >= get_x(upper_left) && x <= get_x(bottom_right)
+                };
+                self.dragging
+            }
+            UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, y)))
+            | UIEvent::Input(Key::Mouse(MouseEvent::Release(x, y)))
+                if self.dragging =>
+            {
+                let (x, y) = pos(x, y);
+                let (list_size, size) = if vertical {
+                    (x.saturating_sub(get_x(upper_left)), width!(area))
+                } else {
+                    (y.saturating_sub(get_y(upper_left)), height!(area))
+                };
+                let ratio = 100 - std::cmp::min(100, (list_size * 100) / std::cmp::max(1, size));
+                self.overrides.ratio = Some(std::cmp::max(5, std::cmp::min(95, ratio)));
+                if let UIEvent::Input(Key::Mouse(MouseEvent::Release(_, _))) = event {
+                    self.dragging = false;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Split `area` into the listing's and the preview pane's areas and draw the boundary
+    /// between them. Returns `None` if the preview pane is disabled or doesn't fit, in which case
+    /// the opened entry takes up the whole area.
+    pub fn split(
+        &mut self,
+        grid: &mut CellBuffer,
+        area: Area,
+        context: &mut Context,
+        coordinates: (AccountHash, MailboxHash),
+    ) -> Option<(Area, Area)> {
+        self.boundary = None;
+        if self.maximized || !self.is_enabled(context, coordinates) {
+            return None;
+        }
+        let (upper_left, bottom_right) = area;
+        let ratio = self.ratio(context, coordinates);
+        let theme_default = crate::conf::value(context, "theme_default");
+        match self.orientation(context, coordinates) {
+            PreviewPaneOrientation::Vertical => {
+                let width = width!(area);
+                let list_width = width - (ratio * width) / 100;
+                if list_width == 0 || list_width + 1 >= width {
+                    return None;
+                }
+                let mid = get_x(upper_left) + list_width;
+                self.boundary = Some((area, mid));
+                for y in get_y(upper_left)..=get_y(bottom_right) {
+                    grid[(mid, y)]
+                        .set_ch(VERT_BOUNDARY)
+                        .set_fg(theme_default.fg)
+                        .set_bg(theme_default.bg)
+                        .set_attrs(theme_default.attrs);
+                }
+                context
+                    .dirty_areas
+                    .push_back(((mid, get_y(upper_left)), (mid, get_y(bottom_right))));
+                Some((
+                    (upper_left, set_x(bottom_right, mid - 1)),
+                    (set_x(upper_left, mid + 1), bottom_right),
+                ))
+            }
+            PreviewPaneOrientation::Horizontal => {
+                let height = height!(area);
+                let list_height = height - (ratio * height) / 100;
+                if list_height == 0 || list_height + 1 >= height {
+                    return None;
+                }
+                let mid = get_y(upper_left) + list_height;
+                self.boundary = Some((area, mid));
+                for x in get_x(upper_left)..=get_x(bottom_right) {
+                    grid[(x, mid)]
+                        .set_ch(HORZ_BOUNDARY)
+                        .set_fg(theme_default.fg)
+                        .set_bg(theme_default.bg)
+                        .set_attrs(theme_default.attrs);
+                }
+                if get_x(upper_left) > 0 && grid[(get_x(upper_left) - 1, mid)].ch() == VERT_BOUNDARY
+                {
+                    grid[(get_x(upper_left) - 1, mid)].set_ch(LIGHT_VERTICAL_AND_RIGHT);
+                }
+                context
+                    .dirty_areas
+                    .push_back(((get_x(upper_left), mid), (get_x(bottom_right), mid)));
+                Some((
+                    (upper_left, set_y(bottom_right, mid - 1)),
+                    (set_y(upper_left, mid + 1), bottom_right),
+                ))
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -569,6 +1367,8 @@ enum ListingFocus {
 enum MenuEntryCursor {
     Status,
     Mailbox(usize),
+    /// Tags are only selectable in the menu, selecting one filters the current mailbox.
+    Tag(usize),
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -598,11 +1398,31 @@ pub struct Listing {
     sidebar_divider_theme: ThemeAttribute,
 
     menu_visibility: bool,
-    cmd_buf: String,
     /// This is the width of the right container to the entire width.
     ratio: usize, // right/(container width) * 100
     menu_width: WidgetWidth,
     focus: ListingFocus,
+    /// Saved session to restore once the account's mailboxes are available.
+    pending_session: Option<ListingSession>,
+    /// Saved cursor position to restore once the mailbox is loaded.
+    pending_cursor: Option<((AccountHash, MailboxHash), usize)>,
+    /// Mailbox of the read position, and whether the cursor has been moved to the remembered
+    /// position yet.
+    read_position: ((AccountHash, MailboxHash), bool),
+    /// Mailbox whose first unread entry should be selected instead of the read position.
+    pending_unseen: Option<(AccountHash, MailboxHash)>,
+    /// Sizes of selected messages in the mailbox they were requested for, for the selection
+    /// summary.
+    message_sizes: ((AccountHash, MailboxHash), HashMap<EnvelopeHash, usize>),
+    message_sizes_requested: HashSet<EnvelopeHash>,
+    message_sizes_job: Option<JoinHandle<Result<HashMap<EnvelopeHash, usize>>>>,
+    /// "N selected (M unread, total S)", shown in the status bar.
+    selection_summary: Option<String>,
+    preview_pane: PreviewPaneOverrides,
+    /// Hide the menu, and the list if an entry is open, see `UIEvent::ZoomChange`.
+    zoomed: bool,
+    /// Account groups whose accounts are shown without their mailboxes.
+    collapsed_groups: HashSet<String>,
 }
 
 impl fmt::Display for Listing {
@@ -629,7 +1449,14 @@ impl Component for Listing {
         let bottom_right = bottom_right!(area);
         let total_cols = get_x(bottom_right) - get_x(upper_left);
 
-        let right_component_width = if self.menu_visibility {
+        let right_component_width = if self.menu_visible() && total_cols < Self::MENU_MIN_WIDTH {
+            /* Not enough room for both, show only the focused one */
+            if self.focus == ListingFocus::Menu {
+                0
+            } else {
+                total_cols
+            }
+        } else if self.menu_visible() {
             if self.focus == ListingFocus::Menu {
                 (self.ratio * total_cols) / 100
             } else {
@@ -666,6 +1493,11 @@ impl Component for Listing {
                 .push_back(((mid, get_y(upper_left)), (mid, get_y(bottom_right))));
         }
 
+        let (preview_pane, zoomed) = (self.preview_pane, self.zoomed);
+        if let Some(p) = self.component.preview_pane_mut() {
+            p.set_overrides(preview_pane);
+            p.set_maximized(zoomed);
+        }
         let account_hash = self.accounts[self.cursor_pos.0].hash;
         if right_component_width == total_cols {
             if context.is_online(account_hash).is_err() {
@@ -705,10 +1537,20 @@ impl Component for Listing {
                     .draw(grid, (set_x(upper_left, mid + 1), bottom_right), context);
             }
         }
+        if self.status.is_none() {
+            self.update_read_position(context);
+        }
+        self.update_selection_summary(context);
         self.dirty = false;
     }
 
     fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if self.pending_session.is_some() || self.pending_cursor.is_some() {
+            self.restore_pending_session(context);
+        }
+        if !self.read_position.1 {
+            self.update_read_position(context);
+        }
         match event {
             UIEvent::ConfigReload { old_settings: _ } => {
                 self.theme_default = crate::conf::value(context, "theme_default");
@@ -719,6 +1561,15 @@ impl Component for Listing {
                 self.menu_content = CellBuffer::new_with_context(0, 0, None, context);
                 self.set_dirty(true);
             }
+            UIEvent::ZoomChange(zoomed) => {
+                self.zoomed = *zoomed;
+                if self.zoomed {
+                    self.focus = ListingFocus::Mailbox;
+                    self.ratio = 90;
+                }
+                self.set_dirty(true);
+                return true;
+            }
             UIEvent::Timer(n) if *n == self.menu_scrollbar_show_timer.id() => {
                 if self.show_menu_scrollbar == ShowMenuScrollbar::True {
                     self.show_menu_scrollbar = ShowMenuScrollbar::False;
@@ -734,6 +1585,20 @@ impl Component for Listing {
                     }
                 }
             }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .message_sizes_job
+                    .as_ref()
+                    .map(|j| j == job_id)
+                    .unwrap_or(false) =>
+            {
+                let mut handle = self.message_sizes_job.take().unwrap();
+                if let Ok(Some(Ok(sizes))) = handle.chan.try_recv() {
+                    self.message_sizes.1.extend(sizes);
+                }
+                self.update_selection_summary(context);
+                return true;
+            }
             UIEvent::Timer(n) if *n == self.startup_checks_rate.id() => {
                 if self.startup_checks_rate.active {
                     self.startup_checks_rate.reset();
@@ -848,18 +1713,79 @@ impl Component for Listing {
                 }
             }
         }
-        if self.focus == ListingFocus::Mailbox
-            && self.status.is_none()
-            && self.component.process_event(event, context)
-        {
-            return true;
+        /* Handle this before the listing, which clears the whole selection on Esc. */
+        if let UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) = event {
+            if self.focus == ListingFocus::Mailbox
+                && self.status.is_none()
+                && self.component.visual_mode()
+            {
+                self.component.end_visual_mode(true);
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                        self.get_status(context),
+                    )));
+                return true;
+            }
+        }
+        if let UIEvent::Input(Key::Mouse(_)) = event {
+            let coordinates = self.component.coordinates();
+            let preview_pane = self.preview_pane;
+            if self.focus == ListingFocus::Mailbox
+                && self.status.is_none()
+                && self.component.unfocused()
+            {
+                if let Some(p) = self.component.preview_pane_mut() {
+                    p.set_overrides(preview_pane);
+                    if p.process_mouse(event, context, coordinates) {
+                        self.preview_pane = p.overrides();
+                        self.set_dirty(true);
+                        return true;
+                    }
+                }
+            }
+        }
+        if let UIEvent::Action(Action::Listing(Search(_))) = event {
+            /* Searches from an open entry, eg for the mail of an address in the envelope view,
+             * show their results in the list. */
+            if self.focus == ListingFocus::Mailbox
+                && self.status.is_none()
+                && self.component.unfocused()
+            {
+                self.component.exit_entry(context);
+            }
+        }
+        if let UIEvent::Input(ref k) = event {
+            let (account_hash, mailbox_hash) = self.component.coordinates();
+            if self.focus == ListingFocus::Mailbox
+                && self.status.is_none()
+                && *k == context.settings.shortcuts.listing.open_mailbox
+                && context.accounts[&account_hash]
+                    .mailbox_entries
+                    .get(&mailbox_hash)
+                    .map(|entry| {
+                        matches!(entry.status, crate::conf::accounts::MailboxStatus::Deferred)
+                    })
+                    .unwrap_or(false)
+            {
+                let _ = context.accounts[&account_hash].load_deferred(mailbox_hash);
+                self.component.refresh_mailbox(context, true);
+                self.set_dirty(true);
+                return true;
+            }
+        }
+        if self.focus == ListingFocus::Mailbox
+            && self.status.is_none()
+            && self.component.process_event(event, context)
+        {
+            return true;
         }
 
         let shortcuts = self.get_shortcuts(context);
         if self.focus == ListingFocus::Mailbox {
             match *event {
                 UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, _y)))
-                    if self.menu_visibility =>
+                    if self.menu_visible() =>
                 {
                     match self.menu_width {
                         WidgetWidth::Hold(wx) | WidgetWidth::Set(wx)
@@ -876,7 +1802,7 @@ impl Component for Listing {
                     self.set_dirty(true);
                     return true;
                 }
-                UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, _y))) if self.menu_visibility => {
+                UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, _y))) if self.menu_visible() => {
                     match self.menu_width {
                         WidgetWidth::Hold(ref mut hx) => {
                             *hx = usize::from(x).saturating_sub(1);
@@ -886,7 +1812,7 @@ impl Component for Listing {
                     self.set_dirty(true);
                     return true;
                 }
-                UIEvent::Input(Key::Mouse(MouseEvent::Release(x, _y))) if self.menu_visibility => {
+                UIEvent::Input(Key::Mouse(MouseEvent::Release(x, _y))) if self.menu_visible() => {
                     match self.menu_width {
                         WidgetWidth::Hold(_) => {
                             self.menu_width = WidgetWidth::Set(usize::from(x).saturating_sub(1));
@@ -896,7 +1822,7 @@ impl Component for Listing {
                     self.set_dirty(true);
                     return true;
                 }
-                UIEvent::Input(Key::Left) if self.menu_visibility => {
+                UIEvent::Input(Key::Left) if self.menu_visible() => {
                     self.focus = ListingFocus::Menu;
                     if self.show_menu_scrollbar != ShowMenuScrollbar::Never {
                         self.menu_scrollbar_show_timer.rearm();
@@ -909,33 +1835,18 @@ impl Component for Listing {
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_mailbox"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_mailbox"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     let target = match k {
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_mailbox"]) => {
                             match self.cursor_pos.1 {
                                 MenuEntryCursor::Status => amount.saturating_sub(1),
                                 MenuEntryCursor::Mailbox(idx) => idx + amount,
+                                MenuEntryCursor::Tag(_) => return true,
                             }
                         }
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_mailbox"]) => {
                             match self.cursor_pos.1 {
-                                MenuEntryCursor::Status => {
+                                MenuEntryCursor::Status | MenuEntryCursor::Tag(_) => {
                                     return true;
                                 }
                                 MenuEntryCursor::Mailbox(idx) => {
@@ -965,23 +1876,7 @@ impl Component for Listing {
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_account"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     match k {
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account"]) => {
                             if self.cursor_pos.0 + amount < self.accounts.len() {
@@ -1005,6 +1900,22 @@ impl Component for Listing {
 
                     return true;
                 }
+                UIEvent::Input(ref k)
+                    if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account_in_group"]) =>
+                {
+                    let next = self.next_account_in_group(self.cursor_pos.0);
+                    if next != self.cursor_pos.0 {
+                        self.cursor_pos = (next, MenuEntryCursor::Mailbox(0));
+                        self.change_account(context);
+                    }
+                    return true;
+                }
+                UIEvent::Input(ref k)
+                    if shortcut!(k == shortcuts[Listing::DESCRIPTION]["toggle_account_group"]) =>
+                {
+                    self.toggle_group(self.cursor_pos.0);
+                    return true;
+                }
                 UIEvent::Input(ref k)
                     if shortcut!(
                         k == shortcuts[Listing::DESCRIPTION]["toggle_menu_visibility"]
@@ -1035,6 +1946,81 @@ impl Component for Listing {
                             self.component.set_style(IndexStyle::Conversations);
                             return true;
                         }
+                        Action::Listing(ListingAction::SetGrouping(grouping)) => {
+                            self.component.set_style(IndexStyle::Conversations);
+                            if let ListingComponent::Conversations(ref mut listing) = self.component
+                            {
+                                listing.set_grouping(*grouping, context);
+                            }
+                            return true;
+                        }
+                        Action::Listing(ListingAction::TogglePreviewPane) => {
+                            self.change_preview_pane(PreviewPane::toggle, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::TogglePreviewPaneOrientation) => {
+                            self.change_preview_pane(PreviewPane::toggle_orientation, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::FilterPreset(name)) => {
+                            let account_hash = self.accounts[self.cursor_pos.0].hash;
+                            match account_settings!(context[account_hash].listing.filter_presets)
+                                .get(name)
+                            {
+                                Some(preset) => {
+                                    let mut event = UIEvent::Action(Action::Listing(Search(
+                                        preset.query.clone(),
+                                    )));
+                                    self.process_event(&mut event, context);
+                                }
+                                None => context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(format!(
+                                        "No filter preset named `{}`.",
+                                        name
+                                    )),
+                                )),
+                            }
+                            return true;
+                        }
+                        Action::Listing(ListingAction::GotoDate(spec)) => {
+                            let entries = match self.dated_entries(spec, context) {
+                                Some(entries) => entries,
+                                None => return true,
+                            };
+                            let account_hash = self.component.coordinates().0;
+                            match entries.first() {
+                                Some(env_hash) => {
+                                    let thread_node = context.accounts[&account_hash]
+                                        .collection
+                                        .get_env(*env_hash)
+                                        .thread();
+                                    self.component.select_thread_node(thread_node, context);
+                                }
+                                None => context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(format!(
+                                        "No messages dated `{}`.",
+                                        spec
+                                    )),
+                                )),
+                            }
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Limit(spec)) => {
+                            let entries = match self.dated_entries(spec, context) {
+                                Some(entries) => entries,
+                                None => return true,
+                            };
+                            if self.component.unfocused() {
+                                self.component.exit_entry(context);
+                            }
+                            self.component.filter(
+                                format!("limit {}", spec),
+                                Ok(entries.into_iter().collect()),
+                                context,
+                            );
+                            self.set_dirty(true);
+                            return true;
+                        }
                         Action::Listing(ListingAction::Import(file_path, mailbox_path)) => {
                             let account = &mut context.accounts[self.cursor_pos.0];
                             if let Err(err) = account
@@ -1057,6 +2043,45 @@ impl Component for Listing {
                             }
                             return true;
                         }
+                        Action::Listing(ListingAction::Refresh(mailbox_path)) => {
+                            let account = &mut context.accounts[self.cursor_pos.0];
+                            let mailbox_hash = match (mailbox_path, self.cursor_pos.1) {
+                                (Some(mailbox_path), _) => account.mailbox_by_path(mailbox_path),
+                                (None, MenuEntryCursor::Mailbox(idx)) => account
+                                    .mailboxes_order
+                                    .get(idx)
+                                    .cloned()
+                                    .ok_or_else(|| MeliError::new("No mailbox selected.")),
+                                (None, _) => Err(MeliError::new("No mailbox selected.")),
+                            };
+                            if let Err(err) =
+                                mailbox_hash.and_then(|mailbox_hash| account.refresh(mailbox_hash))
+                            {
+                                context.replies.push_back(UIEvent::Notification(
+                                    Some("Could not refresh.".to_string()),
+                                    err.to_string(),
+                                    Some(NotificationType::Error(err.kind)),
+                                ));
+                            }
+                            return true;
+                        }
+                        Action::Listing(ListingAction::RefreshAll) => {
+                            context.accounts[self.cursor_pos.0].refresh_all();
+                            return true;
+                        }
+                        Action::Listing(ListingAction::NextUnread(scope)) => {
+                            self.next_unread(*scope, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::ChangeSelection(change)) => {
+                            self.component.end_visual_mode(false);
+                            let selected = self.component.change_selection(*change);
+                            self.component.set_dirty(true);
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(format!("{} selected.", selected)),
+                            ));
+                            return true;
+                        }
                         Action::Listing(a @ ListingAction::SetSeen)
                         | Action::Listing(a @ ListingAction::SetUnseen)
                         | Action::Listing(a @ ListingAction::Delete)
@@ -1065,9 +2090,11 @@ impl Component for Listing {
                         | Action::Listing(a @ ListingAction::CopyToOtherAccount(_, _))
                         | Action::Listing(a @ ListingAction::MoveToOtherAccount(_, _))
                         | Action::Listing(a @ ListingAction::ExportMbox(_, _))
+                        | Action::Listing(a @ ListingAction::BulkReplyTemplate(_, _))
                         | Action::Listing(a @ ListingAction::Tag(_)) => {
                             let focused = self.component.get_focused_items(context);
                             self.component.perform_action(context, focused, a);
+                            self.component.end_visual_mode(false);
                             let mut row_updates: SmallVec<[ThreadHash; 8]> = SmallVec::new();
                             for (k, v) in self.component.selection().iter_mut() {
                                 if *v {
@@ -1078,95 +2105,36 @@ impl Component for Listing {
                         }
                         _ => {}
                     },
+                    UIEvent::Input(ref key) if self.filter_preset_query(key, context).is_some() => {
+                        let query = self.filter_preset_query(key, context).unwrap();
+                        let mut event = UIEvent::Action(Action::Listing(Search(query)));
+                        return self.process_event(&mut event, context);
+                    }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["scroll_up"]) =>
                     {
-                        let amount = if self.cmd_buf.is_empty() {
-                            1
-                        } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            amount
-                        } else {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            return true;
-                        };
+                        let amount = context.count.unwrap_or(1);
                         self.component.set_movement(PageMovement::Up(amount));
                         return true;
                     }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["scroll_down"]) =>
                     {
-                        let amount = if self.cmd_buf.is_empty() {
-                            1
-                        } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            amount
-                        } else {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            return true;
-                        };
+                        let amount = context.count.unwrap_or(1);
                         self.component.set_movement(PageMovement::Down(amount));
                         return true;
                     }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["prev_page"]) =>
                     {
-                        let mult = if self.cmd_buf.is_empty() {
-                            1
-                        } else if let Ok(mult) = self.cmd_buf.parse::<usize>() {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            mult
-                        } else {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            return true;
-                        };
+                        let mult = context.count.unwrap_or(1);
                         self.component.set_movement(PageMovement::PageUp(mult));
                         return true;
                     }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["next_page"]) =>
                     {
-                        let mult = if self.cmd_buf.is_empty() {
-                            1
-                        } else if let Ok(mult) = self.cmd_buf.parse::<usize>() {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            mult
-                        } else {
-                            self.cmd_buf.clear();
-                            self.component.set_modifier_active(false);
-                            context
-                                .replies
-                                .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                            return true;
-                        };
+                        let mult = context.count.unwrap_or(1);
                         self.component.set_movement(PageMovement::PageDown(mult));
                         return true;
                     }
@@ -1197,6 +2165,101 @@ impl Component for Listing {
                             return true;
                         }
                     }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["first_unread"]) =>
+                    {
+                        if !self.component.select_unseen(true, false, context) {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage("No unread messages.".to_string()),
+                            ));
+                        }
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["next_unread"]) =>
+                    {
+                        self.next_unread(UnreadScope::Mailbox, context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["next_unread_mailbox"]
+                        ) =>
+                    {
+                        self.next_unread(UnreadScope::NextMailbox, context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["next_unread_all"]) =>
+                    {
+                        self.next_unread(UnreadScope::All, context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["toggle_preview_pane"]
+                        ) =>
+                    {
+                        self.change_preview_pane(PreviewPane::toggle, context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["increase_preview_pane"]
+                        ) || shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["decrease_preview_pane"]
+                        ) =>
+                    {
+                        let mut amount = context.count.unwrap_or(5) as isize;
+                        if shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["decrease_preview_pane"]
+                        ) {
+                            amount = -amount;
+                        }
+                        self.change_preview_pane(
+                            |preview_pane, context, coordinates| {
+                                preview_pane.resize(amount, context, coordinates)
+                            },
+                            context,
+                        );
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if self.menu_visible()
+                            && (shortcut!(
+                                key == shortcuts[Listing::DESCRIPTION]["increase_sidebar"]
+                            ) || shortcut!(
+                                key == shortcuts[Listing::DESCRIPTION]["decrease_sidebar"]
+                            )) =>
+                    {
+                        let amount = context.count.unwrap_or(5);
+                        if let WidgetWidth::Set(ref mut width) = self.menu_width {
+                            if shortcut!(key == shortcuts[Listing::DESCRIPTION]["increase_sidebar"])
+                            {
+                                *width += amount;
+                            } else {
+                                *width = std::cmp::max(1, width.saturating_sub(amount));
+                            }
+                            self.set_dirty(true);
+                        }
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && shortcut!(key == shortcuts[Listing::DESCRIPTION]["visual_mode"]) =>
+                    {
+                        if self.component.visual_mode() {
+                            self.component.end_visual_mode(false);
+                        } else {
+                            self.component.start_visual_mode();
+                        }
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                                self.get_status(context),
+                            )));
+                        return true;
+                    }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["refresh"]) =>
                     {
@@ -1214,6 +2277,26 @@ impl Component for Listing {
                         }
                         return true;
                     }
+                    UIEvent::Input(ref key)
+                        if shortcut!(
+                            key == shortcuts[Listing::DESCRIPTION]["mailbox_properties"]
+                        ) =>
+                    {
+                        let account = &context.accounts[self.cursor_pos.0];
+                        if let MenuEntryCursor::Mailbox(idx) = self.cursor_pos.1 {
+                            if let Some(&mailbox_hash) = account.mailboxes_order.get(idx) {
+                                let dialog = MailboxPropertiesDialog::new(
+                                    account.hash(),
+                                    mailbox_hash,
+                                    context,
+                                );
+                                context
+                                    .replies
+                                    .push_back(UIEvent::GlobalUIDialog(Box::new(dialog)));
+                            }
+                        }
+                        return true;
+                    }
                     UIEvent::Input(ref key)
                         if !self.component.unfocused()
                             && shortcut!(
@@ -1222,6 +2305,7 @@ impl Component for Listing {
                             && self.component.modifier_command().is_some() =>
                     {
                         self.component.set_modifier_command(Some(Modifier::Union));
+                        context.keep_count();
                     }
                     UIEvent::Input(ref key)
                         if !self.component.unfocused()
@@ -1232,6 +2316,7 @@ impl Component for Listing {
                     {
                         self.component
                             .set_modifier_command(Some(Modifier::Difference));
+                        context.keep_count();
                     }
                     UIEvent::Input(ref key)
                         if !self.component.unfocused()
@@ -1242,6 +2327,7 @@ impl Component for Listing {
                     {
                         self.component
                             .set_modifier_command(Some(Modifier::Intersection));
+                        context.keep_count();
                     }
                     _ => {}
                 }
@@ -1278,6 +2364,46 @@ impl Component for Listing {
                 UIEvent::Input(ref k)
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["open_mailbox"]) =>
                 {
+                    if let (account_idx, MenuEntryCursor::Tag(tag_idx)) = self.menu_cursor_pos {
+                        let tag = if let Some((_, tag, _)) =
+                            self.accounts[account_idx].tags.get(tag_idx)
+                        {
+                            tag.clone()
+                        } else {
+                            return true;
+                        };
+                        /* Filter the current mailbox if it belongs to the tag's account, otherwise
+                         * its first mailbox. */
+                        if self.cursor_pos.0 != account_idx
+                            || self.cursor_pos.1 == MenuEntryCursor::Status
+                        {
+                            self.cursor_pos = (account_idx, MenuEntryCursor::Mailbox(0));
+                            self.change_account(context);
+                        }
+                        self.component.process_event(
+                            &mut UIEvent::Action(Action::Listing(ListingAction::Search(format!(
+                                "tags:{}",
+                                tag
+                            )))),
+                            context,
+                        );
+                        self.focus = ListingFocus::Mailbox;
+                        self.ratio = 90;
+                        self.set_dirty(true);
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::ScrollUpdate(
+                                ScrollUpdate::End(self.id),
+                            )));
+                        return true;
+                    }
+                    if let (account_idx, MenuEntryCursor::Mailbox(idx)) = self.menu_cursor_pos {
+                        if let Some(&(_, _, _, mailbox_hash)) =
+                            self.accounts[account_idx].entries.get(idx)
+                        {
+                            let _ = context.accounts[account_idx].load_deferred(mailbox_hash);
+                        }
+                    }
                     self.cursor_pos = self.menu_cursor_pos;
                     self.change_account(context);
                     self.focus = ListingFocus::Mailbox;
@@ -1299,23 +2425,10 @@ impl Component for Listing {
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["scroll_up"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["scroll_down"]) =>
                 {
-                    let mut amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let mut amount = context.count.unwrap_or(1);
+                    let collapsed: Vec<bool> = (0..self.accounts.len())
+                        .map(|a| self.is_collapsed(a))
+                        .collect();
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["scroll_up"]) {
                         while amount > 0 {
                             match self.menu_cursor_pos {
@@ -1325,12 +2438,20 @@ impl Component for Listing {
                                 ) => {
                                     if *account_cursor > 0 {
                                         *account_cursor -= 1;
-                                        *entry_cursor = MenuEntryCursor::Mailbox(
-                                            self.accounts[*account_cursor]
-                                                .entries
-                                                .len()
-                                                .saturating_sub(1),
-                                        );
+                                        *entry_cursor = if collapsed[*account_cursor] {
+                                            MenuEntryCursor::Status
+                                        } else if self.accounts[*account_cursor].tags.is_empty() {
+                                            MenuEntryCursor::Mailbox(
+                                                self.accounts[*account_cursor]
+                                                    .entries
+                                                    .len()
+                                                    .saturating_sub(1),
+                                            )
+                                        } else {
+                                            MenuEntryCursor::Tag(
+                                                self.accounts[*account_cursor].tags.len() - 1,
+                                            )
+                                        };
                                     } else {
                                         return true;
                                     }
@@ -1342,6 +2463,18 @@ impl Component for Listing {
                                         self.menu_cursor_pos.1 = MenuEntryCursor::Status;
                                     }
                                 }
+                                (account_cursor, MenuEntryCursor::Tag(ref mut tag_idx)) => {
+                                    if *tag_idx > 0 {
+                                        *tag_idx -= 1;
+                                    } else {
+                                        self.menu_cursor_pos.1 = MenuEntryCursor::Mailbox(
+                                            self.accounts[account_cursor]
+                                                .entries
+                                                .len()
+                                                .saturating_sub(1),
+                                        );
+                                    }
+                                }
                             }
 
                             amount -= 1;
@@ -1353,7 +2486,9 @@ impl Component for Listing {
                                 (
                                     ref account_cursor,
                                     ref mut entry_cursor @ MenuEntryCursor::Status,
-                                ) if !self.accounts[*account_cursor].entries.is_empty() => {
+                                ) if !self.accounts[*account_cursor].entries.is_empty()
+                                    && !collapsed[*account_cursor] =>
+                                {
                                     *entry_cursor = MenuEntryCursor::Mailbox(0);
                                 }
                                 /* If current account has no mailboxes, go to next account */
@@ -1376,6 +2511,18 @@ impl Component for Listing {
                                         < self.accounts[*account_cursor].entries.len()
                                     {
                                         *mailbox_idx += 1;
+                                    } else if !self.accounts[*account_cursor].tags.is_empty() {
+                                        self.menu_cursor_pos.1 = MenuEntryCursor::Tag(0);
+                                    } else if *account_cursor + 1 < self.accounts.len() {
+                                        *account_cursor += 1;
+                                        self.menu_cursor_pos.1 = MenuEntryCursor::Status;
+                                    } else {
+                                        return true;
+                                    }
+                                }
+                                (ref mut account_cursor, MenuEntryCursor::Tag(ref mut tag_idx)) => {
+                                    if (*tag_idx + 1) < self.accounts[*account_cursor].tags.len() {
+                                        *tag_idx += 1;
                                     } else if *account_cursor + 1 < self.accounts.len() {
                                         *account_cursor += 1;
                                         self.menu_cursor_pos.1 = MenuEntryCursor::Status;
@@ -1400,33 +2547,18 @@ impl Component for Listing {
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_mailbox"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_mailbox"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     let target = match k {
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_mailbox"]) => {
                             match self.menu_cursor_pos.1 {
                                 MenuEntryCursor::Status => amount.saturating_sub(1),
                                 MenuEntryCursor::Mailbox(idx) => idx + amount,
+                                MenuEntryCursor::Tag(_) => return true,
                             }
                         }
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_mailbox"]) => {
                             match self.menu_cursor_pos.1 {
-                                MenuEntryCursor::Status => {
+                                MenuEntryCursor::Status | MenuEntryCursor::Tag(_) => {
                                     return true;
                                 }
                                 MenuEntryCursor::Mailbox(idx) => {
@@ -1440,6 +2572,9 @@ impl Component for Listing {
                         }
                         _ => return true,
                     };
+                    if self.is_collapsed(self.menu_cursor_pos.0) {
+                        return true;
+                    }
                     if self.accounts[self.menu_cursor_pos.0]
                         .entries
                         .get(target)
@@ -1456,29 +2591,32 @@ impl Component for Listing {
                     self.menu_content.empty();
                     return true;
                 }
+                UIEvent::Input(ref k)
+                    if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account_in_group"]) =>
+                {
+                    let next = self.next_account_in_group(self.menu_cursor_pos.0);
+                    self.menu_cursor_pos = if self.is_collapsed(next) {
+                        (next, MenuEntryCursor::Status)
+                    } else {
+                        (next, MenuEntryCursor::Mailbox(0))
+                    };
+                    self.menu_content.empty();
+                    self.set_dirty(true);
+                    return true;
+                }
+                UIEvent::Input(ref k)
+                    if shortcut!(k == shortcuts[Listing::DESCRIPTION]["toggle_account_group"]) =>
+                {
+                    self.toggle_group(self.menu_cursor_pos.0);
+                    return true;
+                }
                 UIEvent::Input(ref k)
                     if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_account"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_page"])
                         || shortcut!(k == shortcuts[Listing::DESCRIPTION]["prev_page"]) =>
                 {
-                    let amount = if self.cmd_buf.is_empty() {
-                        1
-                    } else if let Ok(amount) = self.cmd_buf.parse::<usize>() {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        amount
-                    } else {
-                        self.cmd_buf.clear();
-                        self.component.set_modifier_active(false);
-                        context
-                            .replies
-                            .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                        return true;
-                    };
+                    let amount = context.count.unwrap_or(1);
                     match k {
                         k if shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_account"])
                             || shortcut!(k == shortcuts[Listing::DESCRIPTION]["next_page"]) =>
@@ -1539,33 +2677,6 @@ impl Component for Listing {
                         self.get_status(context),
                     )));
             }
-            UIEvent::Input(Key::Backspace) if !self.cmd_buf.is_empty() => {
-                self.cmd_buf.pop();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufSet(
-                        self.cmd_buf.clone(),
-                    )));
-                return true;
-            }
-            UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) if !self.cmd_buf.is_empty() => {
-                self.cmd_buf.clear();
-                self.component.set_modifier_active(false);
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                return true;
-            }
-            UIEvent::Input(Key::Char(c)) if c >= '0' && c <= '9' => {
-                self.cmd_buf.push(c);
-                self.component.set_modifier_active(true);
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufSet(
-                        self.cmd_buf.clone(),
-                    )));
-                return true;
-            }
             _ => {}
         }
         false
@@ -1609,6 +2720,41 @@ impl Component for Listing {
         self.component.set_id(id);
     }
 
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        if let MenuEntryCursor::Mailbox(idx) = self.cursor_pos.1 {
+            if let Some((_, _, _, mailbox_hash)) = self.accounts[self.cursor_pos.0].entries.get(idx)
+            {
+                let account = &context.accounts[self.cursor_pos.0];
+                let mut listing_session = ListingSession {
+                    account: account.name().to_string(),
+                    mailbox: account[mailbox_hash].ref_mailbox.path().to_string(),
+                    sidebar_width: match self.menu_width {
+                        WidgetWidth::Set(width) | WidgetWidth::Hold(width) => Some(width),
+                        WidgetWidth::Unset => None,
+                    },
+                    preview_pane_ratio: self.preview_pane.ratio,
+                    ..ListingSession::default()
+                };
+                self.component.save_listing_session(&mut listing_session);
+                session.listing = Some(listing_session);
+            }
+        }
+    }
+
+    fn account_hash(&self) -> Option<AccountHash> {
+        Some(self.accounts[self.cursor_pos.0].hash)
+    }
+
+    fn command_scope(&self, _context: &Context) -> Option<String> {
+        if self.focus != ListingFocus::Mailbox || self.status.is_some() {
+            return None;
+        }
+        match self.component.selected_count() {
+            0 => None,
+            n => Some(format!("{} selected", n)),
+        }
+    }
+
     fn get_status(&self, context: &Context) -> String {
         let mailbox_hash = match self.cursor_pos.1 {
             MenuEntryCursor::Mailbox(idx) => {
@@ -1623,19 +2769,22 @@ impl Component for Listing {
             MenuEntryCursor::Status => {
                 return format!("{} status", &self.accounts[self.cursor_pos.0].name)
             }
+            MenuEntryCursor::Tag(_) => return String::new(),
         };
 
         let account = &context.accounts[self.cursor_pos.0];
         use crate::conf::accounts::MailboxStatus;
         match account[&mailbox_hash].status {
             MailboxStatus::Available | MailboxStatus::Parsing(_, _) => {
-                let (unseen, total) = account[&mailbox_hash]
+                let total = account[&mailbox_hash]
                     .ref_mailbox
                     .count()
                     .ok()
-                    .unwrap_or((0, 0));
+                    .map(|(_, total)| total)
+                    .unwrap_or(0);
+                let unseen = account.unseen_count(mailbox_hash).unwrap_or(0);
                 format!(
-                    "Mailbox: {}, Messages: {}, New: {}{}",
+                    "Mailbox: {}, Messages: {}, New: {}{}{}{}{}{}",
                     account[&mailbox_hash].name(),
                     total,
                     unseen,
@@ -1643,16 +2792,40 @@ impl Component for Listing {
                         "(Loading...)"
                     } else {
                         ""
+                    },
+                    if account.is_read_only() {
+                        ", read-only"
+                    } else {
+                        ""
+                    },
+                    if let Some(quota) = account.quota {
+                        format!(", Quota: {}", quota)
+                    } else {
+                        String::new()
+                    },
+                    if self.component.visual_mode() {
+                        ", VISUAL"
+                    } else {
+                        ""
+                    },
+                    if let Some(ref summary) = self.selection_summary {
+                        format!(", {}", summary)
+                    } else {
+                        String::new()
                     }
                 )
             }
-            MailboxStatus::Failed(_) | MailboxStatus::None => account[&mailbox_hash].status(),
+            MailboxStatus::Failed(_) | MailboxStatus::Deferred | MailboxStatus::None => {
+                mailbox_status(context, account.hash(), mailbox_hash)
+            }
         }
     }
 }
 
 impl Listing {
     pub const DESCRIPTION: &'static str = "listing";
+    /// Below this many columns the menu and the mailbox aren't shown side by side.
+    const MENU_MIN_WIDTH: usize = 60;
     pub fn new(context: &mut Context) -> Self {
         let account_entries: Vec<AccountMenuEntry> = context
             .accounts
@@ -1671,6 +2844,8 @@ impl Listing {
                     hash: *h,
                     index: i,
                     entries,
+                    tags: SmallVec::new(),
+                    group: a.settings.conf.group.clone(),
                 }
             })
             .collect();
@@ -1700,20 +2875,393 @@ impl Listing {
             ratio: 90,
             menu_width: WidgetWidth::Unset,
             focus: ListingFocus::Mailbox,
-            cmd_buf: String::with_capacity(4),
+            pending_session: None,
+            pending_cursor: None,
+            read_position: ((first_account_hash, 0), false),
+            pending_unseen: None,
+            message_sizes: ((first_account_hash, 0), HashMap::default()),
+            message_sizes_requested: HashSet::default(),
+            message_sizes_job: None,
+            selection_summary: None,
+            preview_pane: PreviewPaneOverrides::default(),
+            zoomed: false,
+            collapsed_groups: HashSet::default(),
         };
         ret.change_account(context);
         ret
     }
 
-    fn draw_menu(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
-        clear_area(grid, area, self.theme_default);
-        let total_height: usize = 3 * (self.accounts.len())
-            + self
-                .accounts
+    /// The group whose header is shown above account `aidx`, if it is the first of its group.
+    fn group_header(&self, aidx: usize) -> Option<&str> {
+        let group = self.accounts[aidx].group.as_deref()?;
+        if aidx > 0 && self.accounts[aidx - 1].group.as_deref() == Some(group) {
+            None
+        } else {
+            Some(group)
+        }
+    }
+
+    fn is_collapsed(&self, aidx: usize) -> bool {
+        self.accounts[aidx]
+            .group
+            .as_ref()
+            .map(|g| self.collapsed_groups.contains(g))
+            .unwrap_or(false)
+    }
+
+    /// Collapse or expand the group of account `aidx`.
+    fn toggle_group(&mut self, aidx: usize) {
+        let group = if let Some(ref group) = self.accounts[aidx].group {
+            group.clone()
+        } else {
+            return;
+        };
+        if !self.collapsed_groups.remove(&group) {
+            self.collapsed_groups.insert(group);
+            /* The mailboxes under the cursor are hidden. */
+            if self.is_collapsed(self.menu_cursor_pos.0) {
+                self.menu_cursor_pos.1 = MenuEntryCursor::Status;
+            }
+        }
+        self.menu_content.empty();
+        self.set_dirty(true);
+    }
+
+    /// The query of the filter preset of the current account whose key is `key`.
+    fn filter_preset_query(&self, key: &Key, context: &Context) -> Option<String> {
+        let account_hash = self.accounts[self.cursor_pos.0].hash;
+        account_settings!(context[account_hash].listing.filter_presets)
+            .values()
+            .find(|preset| preset.key.as_ref() == Some(key))
+            .map(|preset| preset.query.clone())
+    }
+
+    /// The entries of the listing in the dates of `spec`, see `date_spec_range`. Shows an error and
+    /// returns `None` if they can't be found.
+    fn dated_entries(&self, spec: &str, context: &mut Context) -> Option<Vec<EnvelopeHash>> {
+        let message = match date_spec_range(spec) {
+            Some(range) => match self.component.dated_entries(range) {
+                Some(entries) => return Some(entries),
+                None => "Dates can't be looked up in this listing style.".to_string(),
+            },
+            None => format!(
+                "Invalid date `{}`: expected YYYY, YYYY-MM or YYYY-MM-DD, optionally after \
+                 since: or before:, which also accept a duration ago like 2w.",
+                spec
+            ),
+        };
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+        None
+    }
+
+    /// The account after `aidx` that is in the same group, wrapping around. Accounts without a
+    /// group form a group of their own.
+    fn next_account_in_group(&self, aidx: usize) -> usize {
+        ((aidx + 1)..self.accounts.len())
+            .chain(0..aidx)
+            .find(|i| self.accounts[*i].group == self.accounts[aidx].group)
+            .unwrap_or(aidx)
+    }
+
+    /// Number of sidebar lines of account `aidx`, without the spacing after it.
+    fn account_menu_height(&self, aidx: usize) -> usize {
+        if self.is_collapsed(aidx) {
+            1
+        } else {
+            self.accounts[aidx].entries.len() + 1 + self.accounts[aidx].tags_height()
+        }
+    }
+
+    fn menu_visible(&self) -> bool {
+        self.menu_visibility && !self.zoomed
+    }
+
+    /// Apply `change` to the current listing's preview pane, and keep its overrides for other
+    /// listings.
+    fn change_preview_pane<F>(&mut self, change: F, context: &mut Context)
+    where
+        F: FnOnce(&mut PreviewPane, &Context, (AccountHash, MailboxHash)),
+    {
+        let coordinates = self.component.coordinates();
+        if let Some(preview_pane) = self.component.preview_pane_mut() {
+            preview_pane.set_overrides(self.preview_pane);
+            change(preview_pane, context, coordinates);
+            self.preview_pane = preview_pane.overrides();
+            self.set_dirty(true);
+        } else {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "This listing has no preview pane.".to_string(),
+                )));
+        }
+    }
+
+    /// Update the summary of the selected entries shown in the status bar, requesting the sizes
+    /// of newly selected messages from the backend.
+    fn update_selection_summary(&mut self, context: &mut Context) {
+        let summary = if self.status.is_some() || self.component.selected_count() == 0 {
+            None
+        } else {
+            let coordinates = self.component.coordinates();
+            if self.message_sizes.0 != coordinates {
+                self.message_sizes = (coordinates, HashMap::default());
+                self.message_sizes_requested.clear();
+                self.message_sizes_job = None;
+            }
+            let env_hashes = self.component.selected_envelopes(context);
+            let account = &context.accounts[&coordinates.0];
+            let unseen = {
+                let envelopes = account.collection.envelopes.read().unwrap();
+                env_hashes
+                    .iter()
+                    .filter(|h| envelopes.get(h).map(|e| !e.is_seen()).unwrap_or(false))
+                    .count()
+            };
+            let missing = env_hashes
+                .iter()
+                .filter(|h| {
+                    !self.message_sizes.1.contains_key(h)
+                        && !self.message_sizes_requested.contains(h)
+                })
+                .cloned()
+                .collect::<Vec<EnvelopeHash>>();
+            if !missing.is_empty() && self.message_sizes_job.is_none() {
+                self.message_sizes_requested.extend(missing.iter().cloned());
+                if let Ok(fut) =
+                    crate::components::mail::message_sizes(account, coordinates.1, &missing)
+                {
+                    self.message_sizes_job = Some(context.job_executor.spawn_specialized(fut));
+                }
+            }
+            let total = env_hashes
                 .iter()
-                .map(|entry| entry.entries.len() + 1)
-                .sum::<usize>();
+                .map(|h| self.message_sizes.1.get(h))
+                .sum::<Option<usize>>();
+            Some(format!(
+                "{} selected ({} unread{})",
+                env_hashes.len(),
+                unseen,
+                if let Some(total) = total {
+                    format!(", total {}", Bytes(total))
+                } else {
+                    String::new()
+                }
+            ))
+        };
+        if summary != self.selection_summary {
+            self.selection_summary = summary;
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                    self.get_status(context),
+                )));
+        }
+    }
+
+    /// Select the mailbox, sort order, filter and cursor position saved in `session`. This is
+    /// done lazily since accounts might not have fetched their mailboxes yet.
+    pub fn restore_session(&mut self, session: ListingSession) {
+        self.pending_session = Some(session);
+    }
+
+    fn restore_pending_session(&mut self, context: &mut Context) {
+        if let Some(session) = self.pending_session.take() {
+            let account_pos = match context
+                .accounts
+                .values()
+                .position(|a| a.name() == session.account)
+            {
+                Some(pos) => pos,
+                None => return,
+            };
+            let mailbox_hash = match context.accounts[account_pos].mailbox_by_path(&session.mailbox)
+            {
+                Ok(hash) => hash,
+                Err(_) if context.accounts[account_pos].mailbox_entries.is_empty() => {
+                    /* Mailboxes have not been fetched yet */
+                    self.pending_session = Some(session);
+                    return;
+                }
+                Err(_) => return,
+            };
+            if let Some(width) = session.sidebar_width {
+                self.menu_width = WidgetWidth::Set(width);
+            }
+            self.preview_pane.ratio = session.preview_pane_ratio;
+            self.cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
+            self.change_account(context);
+            if let Some(idx) = self.accounts[account_pos]
+                .entries
+                .iter()
+                .position(|e| e.3 == mailbox_hash)
+            {
+                self.cursor_pos.1 = MenuEntryCursor::Mailbox(idx);
+                self.change_account(context);
+            }
+            if let Some((field, order)) = session.sort {
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Action::Sort(field, order)));
+            }
+            /* Date limits can only be applied to the loaded mailbox. */
+            if let Some(filter) = session.filter.filter(|f| !f.starts_with("limit ")) {
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Action::Listing(ListingAction::Search(
+                        filter,
+                    ))));
+            } else if session.cursor > 0 {
+                self.pending_cursor = Some((self.component.coordinates(), session.cursor));
+            }
+            /* The saved session takes precedence over the remembered read position */
+            self.read_position = (self.component.coordinates(), true);
+        }
+        if let Some((coordinates, cursor)) = self.pending_cursor {
+            if coordinates != self.component.coordinates() {
+                self.pending_cursor = None;
+            } else if let crate::conf::accounts::MailboxStatus::Available =
+                context.accounts[&coordinates.0][&coordinates.1].status
+            {
+                self.pending_cursor = None;
+                self.component.set_movement(PageMovement::Down(cursor));
+                self.set_dirty(true);
+            }
+        }
+    }
+
+    fn next_unread(&mut self, scope: UnreadScope, context: &mut Context) {
+        let found = match scope {
+            UnreadScope::Mailbox => self.component.select_unseen(false, true, context),
+            UnreadScope::NextMailbox => self.next_unread_mailbox(false, context),
+            UnreadScope::All => {
+                self.component.select_unseen(false, false, context)
+                    || self.next_unread_mailbox(true, context)
+                    || self.component.select_unseen(true, false, context)
+            }
+        };
+        if !found {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "No unread messages.".to_string(),
+                )));
+        }
+    }
+
+    /// Select the next mailbox with unread messages after the current one, wrapping around. With
+    /// `all_accounts`, the mailboxes of the other accounts are searched before wrapping around
+    /// and the first unread entry of the mailbox is selected once it's loaded.
+    fn next_unread_mailbox(&mut self, all_accounts: bool, context: &mut Context) -> bool {
+        let start = match self.cursor_pos.1 {
+            MenuEntryCursor::Mailbox(idx) => idx + 1,
+            MenuEntryCursor::Status | MenuEntryCursor::Tag(_) => 0,
+        };
+        let current = self.cursor_pos.0;
+        let mut candidates: Vec<(usize, usize)> = (start..self.accounts[current].entries.len())
+            .map(|idx| (current, idx))
+            .collect();
+        if all_accounts {
+            for account_pos in (1..self.accounts.len()).map(|i| (current + i) % self.accounts.len())
+            {
+                candidates.extend(
+                    (0..self.accounts[account_pos].entries.len()).map(|idx| (account_pos, idx)),
+                );
+            }
+        }
+        candidates.extend((0..start.saturating_sub(1)).map(|idx| (current, idx)));
+        let target = candidates.into_iter().find(|&(account_pos, idx)| {
+            let mailbox_hash = self.accounts[account_pos].entries[idx].3;
+            context.accounts[account_pos]
+                .unseen_count(mailbox_hash)
+                .map(|unseen| unseen > 0)
+                .unwrap_or(false)
+        });
+        let (account_pos, idx) = match target {
+            Some(target) => target,
+            None => return false,
+        };
+        self.cursor_pos = (account_pos, MenuEntryCursor::Mailbox(idx));
+        self.change_account(context);
+        if all_accounts {
+            self.pending_unseen = Some(self.component.coordinates());
+        }
+        true
+    }
+
+    /// Move the cursor to the remembered read position when a mailbox has been selected and
+    /// loaded, and remember the current position afterwards.
+    fn update_read_position(&mut self, context: &mut Context) {
+        let coordinates = self.component.coordinates();
+        if self.read_position.0 != coordinates {
+            self.read_position = (coordinates, false);
+        }
+        let account = match context.accounts.get(&coordinates.0) {
+            Some(account) if account.mailbox_entries.contains_key(&coordinates.1) => account,
+            _ => return,
+        };
+        let env_hash = match self.component.envelope_under_cursor(context) {
+            Some(env_hash) if account.contains_key(env_hash) => env_hash,
+            /* Not loaded yet, or removed by another client before the listing caught up */
+            _ => return,
+        };
+        let mailbox_path = account[&coordinates.1].ref_mailbox.path();
+        if !self.read_position.1 {
+            if !matches!(
+                account[&coordinates.1].status,
+                crate::conf::accounts::MailboxStatus::Available
+            ) {
+                return;
+            }
+            self.read_position.1 = true;
+            if self.pending_unseen.take() == Some(coordinates) {
+                self.component.select_unseen(true, false, context);
+                self.set_dirty(true);
+                return;
+            }
+            if let Some(thread_node) = context
+                .read_positions
+                .mailbox(account.name(), mailbox_path)
+                .and_then(|message_id| {
+                    account
+                        .collection
+                        .get_threads(coordinates.1)
+                        .find_message_id(message_id.as_bytes())
+                })
+            {
+                if self.component.select_thread_node(thread_node, context) {
+                    self.set_dirty(true);
+                }
+            }
+            return;
+        }
+        let message_id = ReadPositions::message_id(account, env_hash);
+        if context.read_positions.mailbox(account.name(), mailbox_path) != Some(message_id.as_str())
+        {
+            let (account_name, mailbox_path) =
+                (account.name().to_string(), mailbox_path.to_string());
+            context
+                .read_positions
+                .set_mailbox(&account_name, &mailbox_path, message_id);
+        }
+    }
+
+    fn draw_menu(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        clear_area(grid, area, self.theme_default);
+        if self.menu_content.size() == (0, 0) {
+            self.refresh_tags(context);
+        }
+        let total_height: usize = 3 * (self.accounts.len())
+            + self
+                .accounts
+                .iter()
+                .map(|entry| entry.entries.len() + 1 + entry.tags_height())
+                .sum::<usize>()
+            + (0..self.accounts.len())
+                .filter(|a| self.group_header(*a).is_some())
+                .count();
         let min_width: usize = 2 * width!(area);
         let (width, height) = self.menu_content.size();
         let cursor = match self.focus {
@@ -1728,8 +3276,29 @@ impl Listing {
                 if y > get_y(bottom_right) {
                     break;
                 }
+                if let Some(group) = self.group_header(a) {
+                    let header_att = crate::conf::value(context, "mail.sidebar_index");
+                    write_string_to_grid(
+                        &format!("{} {}", if self.is_collapsed(a) { "▸" } else { "▾" }, group),
+                        &mut self.menu_content,
+                        header_att.fg,
+                        header_att.bg,
+                        header_att.attrs | Attr::BOLD,
+                        ((0, y), bottom_right),
+                        None,
+                    );
+                    y += 1;
+                }
                 y += self.print_account(((0, y), bottom_right), a, context);
-                y += 3;
+                /* The accounts of a collapsed group are listed one per line. */
+                if !self.is_collapsed(a)
+                    || a + 1 == self.accounts.len()
+                    || self.accounts[a + 1].group != self.accounts[a].group
+                {
+                    y += 3;
+                } else {
+                    y += 1;
+                }
             }
         }
 
@@ -1737,15 +3306,16 @@ impl Listing {
         let (width, height) = self.menu_content.size();
         const SCROLLING_CONTEXT: usize = 3;
         let y_offset = (cursor.0)
-            + self
-                .accounts
-                .iter()
-                .take(cursor.0)
-                .map(|entry| entry.entries.len() + 1)
+            + (0..cursor.0)
+                .map(|a| self.account_menu_height(a))
                 .sum::<usize>()
+            + (0..=cursor.0)
+                .filter(|a| self.group_header(*a).is_some())
+                .count()
             + match cursor.1 {
                 MenuEntryCursor::Status => 0,
                 MenuEntryCursor::Mailbox(idx) => idx + 1,
+                MenuEntryCursor::Tag(idx) => self.accounts[cursor.0].entries.len() + idx + 2,
             }
             + SCROLLING_CONTEXT;
         let skip_offset = if y_offset <= rows {
@@ -1777,6 +3347,7 @@ impl Listing {
                                 shown_lines: skip_offset + rows,
                                 total_lines: total_height,
                                 has_more_lines: false,
+                                columns: None,
                             },
                         },
                     )));
@@ -1845,7 +3416,7 @@ impl Listing {
                             indentation,
                             has_sibling,
                             mailbox_hash,
-                            mailboxes[&mailbox_hash].count().ok().map(|(v, _)| v),
+                            context.accounts[self.accounts[aidx].index].unseen_count(mailbox_hash),
                         ));
                     }
                 }
@@ -1867,19 +3438,50 @@ impl Listing {
         };
 
         /* Print account name first */
-        write_string_to_grid(
+        let (x, _) = write_string_to_grid(
             &self.accounts[aidx].name,
             &mut self.menu_content,
-            account_attrs.fg,
+            match context.accounts[self.accounts[aidx].index].accent_color() {
+                Some(color) if !(must_highlight_account && cursor.1 == MenuEntryCursor::Status) => {
+                    color
+                }
+                _ => account_attrs.fg,
+            },
             account_attrs.bg,
             account_attrs.attrs,
             area,
             None,
         );
+        let account = &context.accounts[self.accounts[aidx].index];
+        let mut notes = String::new();
+        if account.is_read_only() {
+            notes.push_str(" (read-only)");
+        }
+        if let Some((loaded, total)) = account.loading_progress() {
+            notes.push_str(&format!(" loading {}/{}", loaded, total));
+        }
+        if !notes.is_empty() {
+            write_string_to_grid(
+                &notes,
+                &mut self.menu_content,
+                Color::Byte(243),
+                account_attrs.bg,
+                account_attrs.attrs,
+                ((x, get_y(upper_left)), bottom_right),
+                None,
+            );
+        }
+        if self.is_collapsed(aidx) {
+            return 0;
+        }
 
         if lines.is_empty() {
             write_string_to_grid(
-                "offline",
+                if context.accounts[self.accounts[aidx].index].is_connecting() {
+                    "connecting…"
+                } else {
+                    "offline"
+                },
                 &mut self.menu_content,
                 Color::Byte(243),
                 account_attrs.bg,
@@ -2026,10 +3628,16 @@ impl Listing {
                 ((x, y), bottom_right),
                 None,
             );
+            let entry = &context.accounts[self.accounts[aidx].index].mailbox_entries[&mailbox_idx];
             let (x, _) = write_string_to_grid(
-                context.accounts[self.accounts[aidx].index].mailbox_entries[&mailbox_idx].name(),
+                entry.name(),
                 &mut self.menu_content,
-                att.fg,
+                /* Mailboxes that aren't loaded until they're opened are dimmed. */
+                if let crate::conf::accounts::MailboxStatus::Deferred = entry.status {
+                    Color::Byte(243)
+                } else {
+                    att.fg
+                },
                 att.bg,
                 att.attrs,
                 ((x, y), bottom_right),
@@ -2076,10 +3684,143 @@ impl Listing {
             }
             idx += 1;
         }
+
+        /* Print tags section */
+        let tags_height = self.accounts[aidx].tags_height();
+        if tags_height > 0 && get_y(upper_left) + 1 + idx <= get_y(bottom_right) {
+            let y = get_y(upper_left) + 1 + idx;
+            let header_att = crate::conf::value(context, "mail.sidebar_index");
+            write_string_to_grid(
+                "Tags",
+                &mut self.menu_content,
+                header_att.fg,
+                header_att.bg,
+                header_att.attrs,
+                (set_y(upper_left, y), bottom_right),
+                None,
+            );
+            let tag_default = crate::conf::value(context, "mail.listing.tag_default");
+            for (i, (tag_hash, name, unseen)) in self.accounts[aidx].tags.iter().enumerate() {
+                let y = y + 1 + i;
+                if y > get_y(bottom_right) {
+                    break;
+                }
+                let (att, unread_count_att) = if must_highlight_account {
+                    if cursor.1 == MenuEntryCursor::Tag(i) {
+                        let mut ret = (
+                            crate::conf::value(context, "mail.sidebar_highlighted"),
+                            crate::conf::value(context, "mail.sidebar_highlighted_unread_count"),
+                        );
+                        if !context.settings.terminal.use_color() {
+                            ret.0.attrs |= Attr::REVERSE;
+                            ret.1.attrs |= Attr::REVERSE;
+                        }
+                        ret
+                    } else {
+                        (
+                            crate::conf::value(context, "mail.sidebar_highlighted_account"),
+                            crate::conf::value(
+                                context,
+                                "mail.sidebar_highlighted_account_unread_count",
+                            ),
+                        )
+                    }
+                } else {
+                    (
+                        crate::conf::value(context, "mail.sidebar"),
+                        crate::conf::value(context, "mail.sidebar_unread_count"),
+                    )
+                };
+                let color = account_settings!(context[self.accounts[aidx].hash].tags.colors)
+                    .get(tag_hash)
+                    .or_else(|| context.settings.tags.colors.get(tag_hash))
+                    .cloned()
+                    .unwrap_or(tag_default.bg);
+                let (x, _) = write_string_to_grid(
+                    "  ",
+                    &mut self.menu_content,
+                    att.fg,
+                    att.bg,
+                    att.attrs,
+                    (set_y(upper_left, y), bottom_right),
+                    None,
+                );
+                let (x, _) = write_string_to_grid(
+                    &format!(" {} ", name),
+                    &mut self.menu_content,
+                    tag_default.fg,
+                    color,
+                    tag_default.attrs,
+                    ((x, y), bottom_right),
+                    None,
+                );
+                let count_string = if *unseen > 0 {
+                    format!(" {}", unseen)
+                } else {
+                    String::new()
+                };
+                let (x, _) = write_string_to_grid(
+                    &count_string,
+                    &mut self.menu_content,
+                    unread_count_att.fg,
+                    unread_count_att.bg,
+                    unread_count_att.attrs | Attr::BOLD,
+                    (
+                        (
+                            std::cmp::min(
+                                x,
+                                get_x(bottom_right).saturating_sub(count_string.len()),
+                            ),
+                            y,
+                        ),
+                        bottom_right,
+                    ),
+                    None,
+                );
+                for c in self.menu_content.row_iter(x..(get_x(bottom_right) + 1), y) {
+                    self.menu_content[c]
+                        .set_fg(att.fg)
+                        .set_bg(att.bg)
+                        .set_attrs(att.attrs);
+                }
+            }
+        }
         if idx == 0 {
-            0
+            tags_height
         } else {
-            idx - 1
+            idx - 1 + tags_height
+        }
+    }
+
+    /// Collect the tags of each account shown in the sidebar along with their unread counts.
+    fn refresh_tags(&mut self, context: &Context) {
+        for entry in self.accounts.iter_mut() {
+            entry.tags.clear();
+            let account = &context.accounts[entry.index];
+            if !account.backend_capabilities.supports_tags
+                || !*account_settings!(context[entry.hash].listing.sidebar_show_tags)
+            {
+                continue;
+            }
+            let mut unseen: HashMap<u64, usize> = HashMap::default();
+            for envelope in account.collection.envelopes.read().unwrap().values() {
+                if !envelope.is_seen() {
+                    for t in envelope.labels().iter() {
+                        *unseen.entry(*t).or_default() += 1;
+                    }
+                }
+            }
+            let ignore_tags = account_settings!(context[entry.hash].tags.ignore_tags);
+            let tag_lck = account.collection.tag_index.read().unwrap();
+            entry.tags.extend(
+                tag_lck
+                    .iter()
+                    .filter(|(h, _)| {
+                        !ignore_tags.contains(h) && !context.settings.tags.ignore_tags.contains(h)
+                    })
+                    .map(|(h, name)| (*h, name.clone(), unseen.get(h).cloned().unwrap_or(0))),
+            );
+            entry.tags.sort_by(|a, b| a.1.cmp(&b.1));
         }
     }
 
@@ -2124,6 +3865,7 @@ impl Listing {
             MenuEntryCursor::Status => {
                 self.open_status(self.cursor_pos.0, context);
             }
+            MenuEntryCursor::Tag(_) => {}
         }
         self.sidebar_divider = *account_settings!(context[account_hash].listing.sidebar_divider);
         self.set_dirty(true);
@@ -2148,3 +3890,512 @@ impl Listing {
             )));
     }
 }
+
+#[cfg(test)]
+fn headless_listing(config: &str) -> crate::testing::Headless {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[
+            message(
+                "first",
+                "Alice <alice@example.com>",
+                "Wed, 01 Jan 2020 10:00:00 +0000",
+            ),
+            message(
+                "second",
+                "Bob <bob@example.com>",
+                "Wed, 01 Jan 2020 11:00:00 +0000",
+            ),
+            message(
+                "third",
+                "Carol <carol@example.com>",
+                "Wed, 01 Jan 2020 12:00:00 +0000",
+            ),
+        ],
+        config,
+    );
+    h.wait_until("all messages", |h| {
+        ["first", "second", "third"]
+            .iter()
+            .all(|s| h.find(s).is_some())
+    });
+    h
+}
+
+#[test]
+fn test_listing_navigation() {
+    let mut h = headless_listing("");
+    /* Newest first. */
+    let top = h.find("third").unwrap();
+    assert_eq!(h.find("second"), Some(top + 1));
+    assert_eq!(h.find("first"), Some(top + 2));
+    let highlighted = h.cell(15, top).bg();
+    assert_ne!(highlighted, h.cell(15, top + 1).bg());
+    h.keys(&[Key::Down]);
+    assert_eq!(h.cell(15, top + 1).bg(), highlighted);
+    assert_ne!(h.cell(15, top).bg(), highlighted);
+    h.keys(&[Key::Down, Key::Down]);
+    assert_eq!(h.cell(15, top + 2).bg(), highlighted);
+    h.keys(&[Key::Up]);
+    assert_eq!(h.cell(15, top + 1).bg(), highlighted);
+    assert_ne!(h.cell(15, top + 2).bg(), highlighted);
+}
+
+#[test]
+fn test_listing_selection() {
+    for config in &["", "[listing]\nindex_style = \"Plain\"\n"] {
+        let mut h = headless_listing(config);
+        let top = h.find("third").unwrap();
+        let plain = (h.cell(15, top + 2).fg(), h.cell(15, top + 2).bg());
+        let select = h
+            .state
+            .context
+            .settings
+            .shortcuts
+            .listing
+            .select_entry
+            .clone();
+        h.keys(&[select.clone(), Key::Down]);
+        let selected = (h.cell(15, top).fg(), h.cell(15, top).bg());
+        assert_ne!(selected, plain);
+        assert_ne!(selected.1, h.cell(15, top + 1).bg());
+        /* Selecting again clears it. */
+        h.keys(&[Key::Up, select, Key::Down]);
+        assert_eq!((h.cell(15, top).fg(), h.cell(15, top).bg()), plain);
+    }
+}
+
+#[test]
+fn test_listing_filter_esc() {
+    let mut h = headless_listing("");
+    let top = h.find("third").unwrap();
+    let rows =
+        |h: &crate::testing::Headless| (top..top + 3).map(|y| h.row(y)).collect::<Vec<String>>();
+    let unfiltered = rows(&h);
+
+    h.command("search subject:ir");
+    h.wait_for("2 results");
+    /* Leave the cursor away from the top: ESC moves it back there. */
+    h.keys(&[Key::Down]);
+    h.keys(&[Key::Esc]);
+    assert!(h.find("results").is_none(), "{}", h.text());
+    assert_eq!(rows(&h), unfiltered);
+
+    h.command("search subject:nothing");
+    h.wait_for("0 results");
+    h.keys(&[Key::Esc]);
+    assert!(h.find("results").is_none(), "{}", h.text());
+    assert_eq!(rows(&h), unfiltered);
+}
+
+#[test]
+fn test_listing_filter_presets() {
+    let mut h = headless_listing(
+        "[listing.filter_presets.bob]\nquery = \"from:bob\"\nkey = \"B\"\n\
+         [listing.filter_presets.carol]\nquery = \"from:carol\"\n",
+    );
+    h.command("filter-preset carol");
+    h.wait_for("1 results for preset `carol`: `from:carol`");
+    assert!(h.find("second").is_none(), "{}", h.text());
+    h.keys(&[Key::Esc]);
+    h.keys(&[Key::Char('B')]);
+    h.wait_for("1 results for preset `bob`: `from:bob`");
+    assert!(h.find("third").is_none(), "{}", h.text());
+    h.keys(&[Key::Esc]);
+    /* Typed searches with the query of a preset are indicated too. */
+    h.command("search from:bob");
+    h.wait_for("1 results for preset `bob`");
+    h.keys(&[Key::Esc]);
+    h.command("filter-preset nobody");
+    h.wait_for("No filter preset named `nobody`.");
+}
+
+#[test]
+fn test_listing_entry_dates() {
+    let mut dates = EntryDates::default();
+    for order in &[
+        vec![40, 30, 30, 20, 10],
+        vec![10, 20, 30, 30, 40],
+        vec![30, 10, 40, 30, 20],
+    ] {
+        dates.clear();
+        for &date in order {
+            dates.push(
+                date,
+                EnvelopeHash::from(date) + 1000 * dates.entries.len() as EnvelopeHash,
+            );
+        }
+        let range = |range| {
+            dates
+                .range(range)
+                .into_iter()
+                .map(|h| (h % 1000) as UnixTimestamp)
+                .collect::<Vec<_>>()
+        };
+        let mut expected = order
+            .iter()
+            .cloned()
+            .filter(|&d| (20..40).contains(&d))
+            .collect::<Vec<_>>();
+        assert_eq!(range((20, 40)), expected);
+        expected.retain(|&d| d == 30);
+        assert_eq!(range((25, 31)), expected);
+        assert_eq!(range((0, 10)), Vec::<u64>::new());
+        assert_eq!(range((41, 50)), Vec::<u64>::new());
+        assert_eq!(range((0, UnixTimestamp::MAX)).len(), 5);
+    }
+}
+
+#[test]
+fn test_listing_dates() {
+    use crate::testing::{message, Headless};
+    for config in &["", "[listing]\nindex_style = \"Plain\"\n"] {
+        let mut h = Headless::new(
+            &[
+                message(
+                    "old",
+                    "Alice <alice@example.com>",
+                    "Sat, 15 Jun 2019 12:00:00 +0000",
+                ),
+                message(
+                    "middle",
+                    "Bob <bob@example.com>",
+                    "Wed, 15 Jan 2020 12:00:00 +0000",
+                ),
+                message(
+                    "new",
+                    "Carol <carol@example.com>",
+                    "Sat, 15 Feb 2020 12:00:00 +0000",
+                ),
+            ],
+            config,
+        );
+        h.wait_until("all messages", |h| {
+            ["old", "middle", "new"].iter().all(|s| h.find(s).is_some())
+        });
+        let top = h.find("new").unwrap();
+        let highlighted = h.cell(15, top).bg();
+        h.command("goto date 2020-01");
+        h.wait_until("cursor on middle", |h| {
+            h.cell(15, top + 1).bg() == highlighted
+        });
+        h.command("goto date before:2020");
+        h.wait_until("cursor on old", |h| h.cell(15, top + 2).bg() == highlighted);
+        h.command("goto date 2018");
+        h.wait_for("No messages dated `2018`.");
+
+        h.command("limit 2020");
+        h.wait_for("2 results for `limit 2020`");
+        h.wait_until("old hidden", |h| h.find("Alice").is_none());
+        h.keys(&[Key::Esc]);
+        h.command("limit since:2020-02-01");
+        h.wait_for("1 results for `limit since:2020-02-01`");
+        h.wait_until("middle hidden", |h| h.find("Bob").is_none());
+        h.keys(&[Key::Esc]);
+        /* Everything is older than a week ago. */
+        h.command("limit before:1w");
+        h.wait_for("3 results");
+        h.keys(&[Key::Esc]);
+        h.command("limit 2020-13");
+        h.wait_for("Invalid date `2020-13`");
+    }
+}
+
+#[test]
+fn test_listing_sort_collation() {
+    use crate::testing::{message, Headless};
+    let date = "Wed, 01 Jan 2020 10:00:00 +0000";
+    let from = "Alice <alice@example.com>";
+    let mut h = Headless::new(
+        &[
+            message("Zebra", from, date),
+            message("éclair", from, date),
+            message("apple", from, date),
+        ],
+        "[listing]\ncollation_locale = \"en_GB.UTF-8\"\n",
+    );
+    h.wait_until("all messages", |h| {
+        ["Zebra", "éclair", "apple"]
+            .iter()
+            .all(|s| h.find(s).is_some())
+    });
+    h.command("sort subject desc");
+    h.wait_until("sorted messages", |h| {
+        let apple = h.find("apple").unwrap();
+        h.find("éclair") == Some(apple + 1) && h.find("Zebra") == Some(apple + 2)
+    });
+}
+
+#[test]
+fn test_listing_date_separators() {
+    use crate::testing::{message, Headless};
+    let now = melib::datetime::timestamp_to_string(
+        melib::datetime::now(),
+        Some(melib::datetime::RFC5322_FMT),
+        true,
+    );
+    let from = "Alice <alice@example.com>";
+    for style in &["compact", "plain", "conversations"] {
+        let mut h = Headless::new(
+            &[
+                message("recent", from, &now),
+                message("new year", from, "Wed, 01 Jan 2020 10:00:00 +0000"),
+                message("mid january", from, "Wed, 15 Jan 2020 10:00:00 +0000"),
+                message("christmas", from, "Wed, 25 Dec 2019 10:00:00 +0000"),
+            ],
+            &format!(
+                "[listing]\ndate_separators = true\nindex_style = \"{}\"\n",
+                style
+            ),
+        );
+        h.wait_for("christmas");
+        /* Conversations entries take three rows and their separators have the label in the
+         * middle one. */
+        let (first, step) = if *style == "conversations" {
+            (2, 3)
+        } else {
+            (1, 1)
+        };
+        let today = h.find("Today").unwrap();
+        let january = h.find("January 2020").unwrap();
+        assert!(today < h.find("recent").unwrap(), "{}", h.text());
+        assert!(h.find("recent").unwrap() < january, "{}", h.text());
+        assert_eq!(h.find("mid january"), Some(january + first), "{}", h.text());
+        assert_eq!(
+            h.find("new year"),
+            Some(january + first + step),
+            "{}",
+            h.text()
+        );
+        assert_eq!(
+            h.find("December 2019").map(|y| y + first),
+            h.find("christmas"),
+            "{}",
+            h.text()
+        );
+        /* The cursor skips separators. */
+        let highlighted = h.cell(15, h.find("recent").unwrap()).bg();
+        h.keys(&[Key::Down]);
+        let mid_january = h.find("mid january").unwrap();
+        assert_eq!(h.cell(15, mid_january).bg(), highlighted, "{}", h.text());
+
+        h.command("sort subject asc");
+        h.wait_until("separators to go away", |h| h.find("Today").is_none());
+    }
+}
+
+#[test]
+fn test_listing_flag_indicators() {
+    use crate::testing::{message, Headless};
+    for (terminal, listing, indicator) in &[
+        ("", "", "🔏"),
+        ("[terminal]\nascii_drawing = true\n", "", "s"),
+        ("", "signed_flag = \"SIG\"\n", "SIG"),
+    ] {
+        for style in &["compact", "plain", "conversations", "threaded"] {
+            let signed = message(
+                "hello",
+                "Alice <alice@example.com>",
+                "Wed, 01 Jan 2020 10:00:00 +0000",
+            )
+            .replacen(
+                "\r\n\r\n",
+                "\r\nContent-Type: multipart/signed; boundary=\"b\"\r\n\r\n",
+                1,
+            );
+            let mut h = Headless::new(
+                &[signed],
+                &format!(
+                    "{}[listing]\nindex_style = \"{}\"\n{}",
+                    terminal, style, listing
+                ),
+            );
+            /* Only the flag column has an "s" in the row of "hello". */
+            h.wait_until(indicator, |h| {
+                h.find("hello")
+                    .map(|y| h.row(y).contains(indicator))
+                    .unwrap_or(false)
+            });
+        }
+    }
+}
+
+#[test]
+fn test_listing_read_only() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account(
+        &[message(
+            "untouched",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "read_only = true\n",
+        "[listing]\nindex_style = \"plain\"\n",
+    );
+    h.wait_for("untouched");
+    assert!(h.find("test (read").is_some(), "{}", h.text());
+    h.command("set seen");
+    h.wait_for("Account test is read-only.");
+    /* Opening the message doesn't mark it seen either. */
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Body of untouched.");
+    let account = &h.state.context.accounts[0];
+    let env_hash = *account
+        .collection
+        .envelopes
+        .read()
+        .unwrap()
+        .keys()
+        .next()
+        .unwrap();
+    assert!(!account.collection.get_env(env_hash).is_seen());
+    /* The backend refuses changes that get past the UI. */
+    let mailbox_hash = *account.mailbox_entries.keys().next().unwrap();
+    assert!(account
+        .backend
+        .write()
+        .unwrap()
+        .set_flags(
+            env_hash.into(),
+            mailbox_hash,
+            smallvec::smallvec![(Ok(Flag::SEEN), true)]
+        )
+        .is_err());
+    assert!(!account
+        .activity
+        .entries()
+        .iter()
+        .any(|entry| entry.subject == "untouched"));
+}
+
+#[test]
+fn test_listing_deferred_load() {
+    use crate::conf::accounts::MailboxStatus;
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account(
+        &[message(
+            "deferred",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "[accounts.test.mailboxes.mail]\nautoload = false\n",
+        "",
+    );
+    /* Nothing is fetched until the mailbox is opened. */
+    h.wait_for("Mailbox not loaded, press Enter to load it.");
+    let account = &h.state.context.accounts[0];
+    let mailbox_hash = *account.mailbox_entries.keys().next().unwrap();
+    assert!(matches!(
+        account[&mailbox_hash].status,
+        MailboxStatus::Deferred
+    ));
+    assert!(account.collection.envelopes.read().unwrap().is_empty());
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("deferred");
+    assert!(h.find("Mailbox not loaded").is_none(), "{}", h.text());
+}
+
+#[test]
+fn test_listing_loading_progress() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "progress",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("progress");
+    h.wait_until("loading to end", |h| {
+        h.state.context.accounts[0].loading_progress().is_none()
+    });
+    assert!(h.find(" loading ").is_none(), "{}", h.text());
+}
+
+#[test]
+fn test_listing_account_groups() {
+    use crate::testing::Headless;
+    let work = std::env::temp_dir()
+        .join(format!("meli-account-groups-{}", std::process::id()))
+        .join("work");
+    for sub in &["cur", "new", "tmp"] {
+        std::fs::create_dir_all(work.join(sub)).unwrap();
+    }
+    let mut h = Headless::with_account(
+        &[],
+        "group = \"Work\"\n",
+        &format!(
+            "[accounts.other]\nroot_mailbox = \"{}\"\nformat = \"Maildir\"\n\
+             identity = \"other@example.com\"\nsearch_backend = \"none\"\n\
+             group = \"Work\"\ndisplay_order = 0\n",
+            work.display()
+        ),
+    );
+    /* `display_order` puts the second account first. */
+    assert_eq!(h.state.context.accounts[0].name(), "other");
+    h.wait_for("▾ Work");
+    h.wait_for("work [0 messages]");
+    h.keys(&[Key::Alt('h')]);
+    h.wait_for("mail [0 messages]");
+    h.keys(&[Key::Alt('h')]);
+    h.wait_for("work [0 messages]");
+    h.keys(&[Key::Char('z')]);
+    h.wait_for("▸ Work");
+    assert!(h.find("▾ Work").is_none(), "{}", h.text());
+}
+
+#[test]
+fn test_listing_accent_color() {
+    use crate::testing::{message, Headless, ROWS};
+    let mut h = Headless::with_account(
+        &[message(
+            "accent",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "accent_color = \"#ff0000\"\n",
+        "",
+    );
+    h.wait_for("accent");
+    let red = Color::Rgb(255, 0, 0);
+    /* The sidebar entry, the focused tab and the status bar segment. */
+    let sidebar = h.find("test").unwrap();
+    assert_eq!(h.cell(0, sidebar).fg(), red);
+    assert_eq!(h.cell(1, 0).bg(), red);
+    let status_row = |h: &Headless| (0..ROWS).rev().find(|y| h.row(*y).starts_with(" test "));
+    h.wait_until("status bar segment", |h| status_row(h).is_some());
+    assert_eq!(h.cell(1, status_row(&h).unwrap()).bg(), red);
+}
+
+#[test]
+fn test_listing_large_count() {
+    use crate::testing::{message, Headless};
+    for style in &["compact", "conversations", "plain", "threaded"] {
+        let mut h = Headless::new(
+            &[
+                message(
+                    "first",
+                    "Alice <alice@example.com>",
+                    "Wed, 01 Jan 2020 10:00:00 +0000",
+                ),
+                message(
+                    "second",
+                    "Bob <bob@example.com>",
+                    "Thu, 02 Jan 2020 10:00:00 +0000",
+                ),
+            ],
+            &format!("[listing]\nindex_style = \"{}\"\n", style),
+        );
+        h.wait_for("second");
+        /* Counts too large to move by are capped instead of overflowing. */
+        for key in &[Key::Down, Key::PageDown, Key::Up, Key::PageUp] {
+            h.type_str(&"9".repeat(40));
+            assert_eq!(h.state.context.count, Some(99_999));
+            h.keys(&[key.clone()]);
+        }
+        h.type_str(&"9".repeat(40));
+        h.command("set seen");
+        h.wait_for("second");
+    }
+}
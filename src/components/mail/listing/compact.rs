@@ -134,6 +134,8 @@ pub struct CompactListing {
     data_columns: DataColumns,
     rows_drawn: SegmentTree,
     rows: Vec<((usize, (ThreadHash, EnvelopeHash)), EntryStrings)>,
+    separators: DateSeparators,
+    dates: EntryDates,
 
     search_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
     select_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
@@ -147,12 +149,16 @@ pub struct CompactListing {
     /// If `self.view` exists or not.
     unfocused: bool,
     view: ThreadView,
+    preview_pane: PreviewPane,
     row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    flags: FlagIndicators,
 
     movement: Option<PageMovement>,
-    modifier_active: bool,
     modifier_command: Option<Modifier>,
+    /// Row where visual mode started and the row of the cursor when the selection was last
+    /// extended.
+    visual: Option<(usize, usize)>,
     id: ComponentId,
 }
 
@@ -165,9 +171,18 @@ impl MailListingTrait for CompactListing {
         &mut self.selection
     }
 
-    fn get_focused_items(&self, _context: &Context) -> SmallVec<[ThreadHash; 8]> {
+    fn get_focused_items(&self, context: &Context) -> SmallVec<[ThreadHash; 8]> {
         let is_selection_empty = self.selection.values().cloned().any(std::convert::identity);
-        let i = [self.get_thread_under_cursor(self.cursor_pos.2)];
+        /* With a count, focus that many entries starting from the cursor. */
+        let rows = if self.filter_term.is_empty() {
+            self.length
+        } else {
+            self.filtered_selection.len()
+        };
+        let i: SmallVec<[ThreadHash; 8]> = (self.cursor_pos.2
+            ..std::cmp::min(self.cursor_pos.2 + context.count.unwrap_or(1), rows))
+            .map(|row| self.get_thread_under_cursor(row))
+            .collect();
         let cursor_iter;
         let sel_iter = if is_selection_empty {
             cursor_iter = None;
@@ -188,6 +203,7 @@ impl MailListingTrait for CompactListing {
     /// chosen.
     fn refresh_mailbox(&mut self, context: &mut Context, force: bool) {
         self.dirty = true;
+        self.visual = None;
         self.all_threads.clear();
         self.selection.clear();
         let old_cursor_pos = self.cursor_pos;
@@ -212,9 +228,11 @@ impl MailListingTrait for CompactListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            date_separator: crate::conf::value(context, "mail.listing.date_separator"),
             theme_default: crate::conf::value(context, "theme_default"),
             ..self.color_cache
         };
+        self.flags = FlagIndicators::new(context, self.cursor_pos.0, self.cursor_pos.1);
         if !context.settings.terminal.use_color() {
             self.color_cache.highlighted.attrs |= Attr::REVERSE;
             self.color_cache.tag_default.attrs |= Attr::REVERSE;
@@ -228,7 +246,7 @@ impl MailListingTrait for CompactListing {
             Ok(()) => {}
             Err(_) => {
                 let message: String =
-                    context.accounts[&self.cursor_pos.0][&self.cursor_pos.1].status();
+                    super::mailbox_status(context, self.cursor_pos.0, self.cursor_pos.1);
                 self.data_columns.columns[0] =
                     CellBuffer::new_with_context(message.len(), 1, None, context);
                 self.length = 0;
@@ -253,6 +271,7 @@ impl MailListingTrait for CompactListing {
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
+            &collator(context, self.cursor_pos.0, self.cursor_pos.1),
         );
 
         self.redraw_threads_list(
@@ -279,8 +298,11 @@ impl MailListingTrait for CompactListing {
         let threads = account.collection.get_threads(self.cursor_pos.1);
         self.order.clear();
         self.length = 0;
+        self.separators
+            .reset(context, (self.cursor_pos.0, self.cursor_pos.1), self.sort.0);
+        self.dates.clear();
         let mut rows = Vec::with_capacity(1024);
-        let mut min_width = (0, 0, 0, 0, 0);
+        let mut min_width = (0, 0, 0, 0, 0, 0);
         let mut row_widths: (
             SmallVec<[u8; 1024]>,
             SmallVec<[u8; 1024]>,
@@ -341,7 +363,10 @@ impl MailListingTrait for CompactListing {
                 }
             }
 
-            let entry_strings = self.make_entry_string(&root_envelope, context, &threads, thread);
+            let mut entry_strings =
+                self.make_entry_string(&root_envelope, context, &threads, thread);
+            drop(root_envelope);
+            entry_strings.size = self.size_string(context, &threads, thread);
             row_widths.1.push(
                 entry_strings
                     .date
@@ -375,7 +400,11 @@ impl MailListingTrait for CompactListing {
                 min_width.4,
                 entry_strings.subject.grapheme_width() + 1 + entry_strings.tags.grapheme_width(),
             ); /* subject */
+            min_width.5 = cmp::max(min_width.5, entry_strings.size.grapheme_width()); /* size */
             rows.push(((self.length, (thread, root_env_hash)), entry_strings));
+            self.separators.push(threads.thread_ref(thread).date());
+            self.dates
+                .push(threads.thread_ref(thread).date(), root_env_hash);
             self.all_threads.insert(thread);
 
             self.order.insert(thread, self.length);
@@ -403,6 +432,9 @@ impl MailListingTrait for CompactListing {
         self.data_columns.columns[4] =
             CellBuffer::new_with_context(min_width.4, rows.len(), None, context);
         self.data_columns.segment_tree[4] = row_widths.4.into();
+        /* size column */
+        self.data_columns.columns[5] =
+            CellBuffer::new_with_context(min_width.5, rows.len(), None, context);
 
         self.rows = rows;
         self.rows_drawn = SegmentTree::from(
@@ -438,6 +470,10 @@ impl ListingTrait for CompactListing {
         (self.new_cursor_pos.0, self.new_cursor_pos.1)
     }
 
+    fn preview_pane_mut(&mut self) -> Option<&mut PreviewPane> {
+        Some(&mut self.preview_pane)
+    }
+
     fn set_coordinates(&mut self, coordinates: (AccountHash, MailboxHash)) {
         self.new_cursor_pos = (coordinates.0, coordinates.1, 0);
         self.unfocused = false;
@@ -554,14 +590,21 @@ impl ListingTrait for CompactListing {
             }
         }
 
-        let prev_page_no = (self.cursor_pos.2).wrapping_div(rows);
-        let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
+        let visual_changed = self.update_visual_selection();
 
-        let top_idx = page_no * rows;
+        let prev_page_no = self.separators.slot(self.cursor_pos.2).wrapping_div(rows);
+        let page_no = self
+            .separators
+            .slot(self.new_cursor_pos.2)
+            .wrapping_div(rows);
+
+        let top_slot = page_no * rows;
+        let top_idx = self.separators.entry_from(top_slot);
+        let (runs, separators) = self.separators.page(top_slot, rows, self.length);
         self.draw_rows(
             context,
             top_idx,
-            cmp::min(self.length.saturating_sub(1), top_idx + rows - 1),
+            runs.last().map(|(_, r)| r.end - 1).unwrap_or(top_idx),
         );
 
         /* If cursor position has changed, remove the highlight from the previous position and
@@ -573,14 +616,12 @@ impl ListingTrait for CompactListing {
                 if *idx >= self.length {
                     continue; //bounds check
                 }
-                let new_area = (
-                    set_y(upper_left, get_y(upper_left) + (*idx % rows)),
-                    set_y(bottom_right, get_y(upper_left) + (*idx % rows)),
-                );
+                let y = get_y(upper_left) + self.separators.slot(*idx) % rows;
+                let new_area = (set_y(upper_left, y), set_y(bottom_right, y));
                 self.highlight_line(grid, new_area, *idx, context);
                 context.dirty_areas.push_back(new_area);
             }
-            if !self.force_draw {
+            if !self.force_draw && !visual_changed {
                 return;
             }
         } else if self.cursor_pos != self.new_cursor_pos {
@@ -598,7 +639,14 @@ impl ListingTrait for CompactListing {
         self.data_columns.widths[2] = self.data_columns.columns[2].size().0; /* from */
         self.data_columns.widths[3] = self.data_columns.columns[3].size().0; /* flags */
         self.data_columns.widths[4] = self.data_columns.columns[4].size().0; /* subject */
+        self.data_columns.widths[5] = self.data_columns.columns[5].size().0; /* size */
 
+        /* The size column is kept whole at the end of the line */
+        let width = if self.data_columns.widths[5] > 0 {
+            width.saturating_sub(self.data_columns.widths[5] + 2)
+        } else {
+            width
+        };
         let min_col_width = std::cmp::min(
             15,
             std::cmp::min(self.data_columns.widths[4], self.data_columns.widths[2]),
@@ -631,8 +679,8 @@ impl ListingTrait for CompactListing {
             self.data_columns.widths[i] =
                 self.data_columns.segment_tree[i].get_max(top_idx, top_idx + rows) as usize;
         }
-        if self.data_columns.widths.iter().sum::<usize>() > width {
-            let diff = self.data_columns.widths.iter().sum::<usize>() - width;
+        if self.data_columns.widths[..5].iter().sum::<usize>() > width {
+            let diff = self.data_columns.widths[..5].iter().sum::<usize>() - width;
             if self.data_columns.widths[2] > 2 * diff {
                 self.data_columns.widths[2] -= diff;
             } else {
@@ -658,21 +706,23 @@ impl ListingTrait for CompactListing {
             if self.data_columns.widths[i] == 0 {
                 continue;
             }
-            copy_area(
-                grid,
-                &self.data_columns.columns[i],
-                (
-                    set_x(upper_left, x),
-                    set_x(
-                        bottom_right,
-                        std::cmp::min(get_x(bottom_right), x + (self.data_columns.widths[i])),
+            for (row, entries) in &runs {
+                copy_area(
+                    grid,
+                    &self.data_columns.columns[i],
+                    (
+                        (x, get_y(upper_left) + row),
+                        set_x(
+                            bottom_right,
+                            std::cmp::min(get_x(bottom_right), x + (self.data_columns.widths[i])),
+                        ),
                     ),
-                ),
-                (
-                    (0, top_idx),
-                    (column_width.saturating_sub(1), self.length - 1),
-                ),
-            );
+                    (
+                        (0, entries.start),
+                        (column_width.saturating_sub(1), entries.end - 1),
+                    ),
+                );
+            }
             x += self.data_columns.widths[i] + 2; // + SEPARATOR
             if x > get_x(bottom_right) {
                 break;
@@ -681,13 +731,16 @@ impl ListingTrait for CompactListing {
 
         let account = &context.accounts[&self.cursor_pos.0];
         let threads = account.collection.get_threads(self.cursor_pos.1);
-        for r in 0..cmp::min(self.length - top_idx, rows) {
-            let thread_hash = self.get_thread_under_cursor(r + top_idx);
+        for (r, idx) in runs
+            .iter()
+            .flat_map(|(row, entries)| (*row..).zip(entries.clone()))
+        {
+            let thread_hash = self.get_thread_under_cursor(idx);
             let row_attr = row_attr!(
                 self.color_cache,
-                (r + top_idx) % 2 == 0,
+                idx % 2 == 0,
                 threads.thread_ref(thread_hash).unseen() > 0,
-                self.cursor_pos.2 == (r + top_idx),
+                self.cursor_pos.2 == idx,
                 self.selection[&thread_hash]
             );
             change_colors(
@@ -721,23 +774,29 @@ impl ListingTrait for CompactListing {
             );
         }
 
+        for (r, label) in separators {
+            draw_date_separator(
+                grid,
+                area,
+                get_y(upper_left) + r,
+                &label,
+                self.color_cache.date_separator,
+            );
+        }
+
+        let y = get_y(upper_left) + self.separators.slot(self.cursor_pos.2) % rows;
         self.highlight_line(
             grid,
-            (
-                set_y(upper_left, get_y(upper_left) + (self.cursor_pos.2 % rows)),
-                set_y(bottom_right, get_y(upper_left) + (self.cursor_pos.2 % rows)),
-            ),
+            (set_y(upper_left, y), set_y(bottom_right, y)),
             self.cursor_pos.2,
             context,
         );
 
-        if top_idx + rows > self.length {
+        let slots = self.separators.len(self.length);
+        if top_slot + rows > slots {
             clear_area(
                 grid,
-                (
-                    pos_inc(upper_left, (0, self.length - top_idx)),
-                    bottom_right,
-                ),
+                (pos_inc(upper_left, (0, slots - top_slot)), bottom_right),
                 self.color_cache.theme_default,
             );
         }
@@ -786,6 +845,7 @@ impl ListingTrait for CompactListing {
                         &mut self.filtered_selection,
                         self.sort,
                         &context.accounts[&self.cursor_pos.0].collection.envelopes,
+                        &collator(context, self.cursor_pos.0, self.cursor_pos.1),
                     );
                     self.new_cursor_pos.2 =
                         std::cmp::min(self.filtered_selection.len() - 1, self.cursor_pos.2);
@@ -806,7 +866,8 @@ impl ListingTrait for CompactListing {
                     "Encountered an error while searching for `{}`: {}.",
                     &self.filter_term, e
                 );
-                log(
+                log_target(
+                    "ui",
                     format!("Failed to search for term {}: {}", &self.filter_term, e),
                     ERROR,
                 );
@@ -829,10 +890,6 @@ impl ListingTrait for CompactListing {
         self.unfocused
     }
 
-    fn set_modifier_active(&mut self, new_val: bool) {
-        self.modifier_active = new_val;
-    }
-
     fn set_modifier_command(&mut self, new_val: Option<Modifier>) {
         self.modifier_command = new_val;
     }
@@ -841,10 +898,182 @@ impl ListingTrait for CompactListing {
         self.modifier_command
     }
 
+    fn visual_mode(&self) -> bool {
+        self.visual.is_some()
+    }
+
+    fn start_visual_mode(&mut self) {
+        if self.length == 0 {
+            return;
+        }
+        let cursor = self.new_cursor_pos.2;
+        let thread = self.get_thread_under_cursor(cursor);
+        self.selection.entry(thread).and_modify(|e| *e = true);
+        self.row_updates.push(thread);
+        self.visual = Some((cursor, cursor));
+        self.dirty = true;
+    }
+
+    fn end_visual_mode(&mut self, deselect: bool) {
+        if let Some((anchor, cursor)) = self.visual.take() {
+            if deselect {
+                for row in cmp::min(anchor, cursor)..=cmp::max(anchor, cursor) {
+                    let thread = self.get_thread_under_cursor(row);
+                    self.selection.entry(thread).and_modify(|e| *e = false);
+                    self.row_updates.push(thread);
+                }
+            }
+            self.dirty = true;
+        }
+    }
+
+    fn selected_count(&self) -> usize {
+        self.selection.values().filter(|v| **v).count()
+    }
+
+    fn selected_envelopes(&self, context: &Context) -> Vec<EnvelopeHash> {
+        let account = &context.accounts[&self.cursor_pos.0];
+        let threads = account.collection.get_threads(self.cursor_pos.1);
+        self.selection
+            .iter()
+            .filter(|(_, v)| **v)
+            .flat_map(|(thread, _)| threads.thread_group_iter(*thread))
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .collect()
+    }
+
+    fn exit_entry(&mut self, context: &mut Context) {
+        self.unfocused = false;
+        self.view
+            .process_event(&mut UIEvent::VisibilityChange(false), context);
+        self.dirty = true;
+        /* If self.row_updates is not empty and we exit a thread, the row_update events
+         * will be performed but the list will not be drawn. So force a draw in any case.
+         * */
+        self.force_draw = true;
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn save_listing_session(&self, session: &mut ListingSession) {
+        session.cursor = self.cursor_pos.2;
+        session.sort = Some(self.sort);
+        if !self.filter_term.is_empty() {
+            session.filter = Some(self.filter_term.clone());
+        }
+    }
+
+    fn dated_entries(&self, range: (UnixTimestamp, UnixTimestamp)) -> Option<Vec<EnvelopeHash>> {
+        Some(self.dates.range(range))
+    }
+
+    fn envelope_under_cursor(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0
+            || self.cursor_pos.1 != self.new_cursor_pos.1
+            || self.cursor_pos.2 >= self.length
+        {
+            return None;
+        }
+        let thread_hash = self.get_thread_under_cursor(self.cursor_pos.2);
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        if !threads.groups.contains_key(&thread_hash) {
+            return None;
+        }
+        threads
+            .thread_group_iter(thread_hash)
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .next()
+    }
+
+    fn select_thread_node(&mut self, thread_node: ThreadNodeHash, context: &Context) -> bool {
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        let thread_hash = match threads.thread_nodes().get(&thread_node) {
+            Some(node) => threads.find_group(node.group),
+            None => return false,
+        };
+        let idx = if self.filter_term.is_empty() {
+            self.order.get(&thread_hash)
+        } else {
+            self.filtered_order.get(&thread_hash)
+        };
+        if let Some(&idx) = idx {
+            self.new_cursor_pos.2 = idx;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn select_unseen(&mut self, from_start: bool, wrap: bool, context: &mut Context) -> bool {
+        let cursor = if from_start {
+            None
+        } else {
+            Some(self.new_cursor_pos.2)
+        };
+        let (idx, expanded_hash) = {
+            let threads = context.accounts[&self.cursor_pos.0]
+                .collection
+                .get_threads(self.cursor_pos.1);
+            let is_unseen = |thread_hash: &ThreadHash| {
+                threads.groups.contains_key(thread_hash)
+                    && threads.thread_ref(*thread_hash).unseen() > 0
+            };
+            let idx = if self.filter_term.is_empty() {
+                next_unseen_idx(
+                    self.order
+                        .iter()
+                        .filter(|(thread_hash, _)| is_unseen(thread_hash))
+                        .map(|(_, idx)| *idx),
+                    cursor,
+                    wrap,
+                )
+            } else {
+                next_unseen_idx(
+                    self.filtered_selection
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, thread_hash)| is_unseen(thread_hash))
+                        .map(|(idx, _)| idx),
+                    cursor,
+                    wrap,
+                )
+            };
+            let idx = match idx {
+                Some(idx) => idx,
+                None => return false,
+            };
+            /* Expand the first unseen message of the thread */
+            let envelopes = &context.accounts[&self.cursor_pos.0].collection;
+            let expanded_hash = threads
+                .thread_group_iter(self.get_thread_under_cursor(idx))
+                .map(|(_, h)| h)
+                .find(|h| {
+                    threads.thread_nodes()[h]
+                        .message()
+                        .map(|env_hash| {
+                            envelopes.contains_key(&env_hash)
+                                && !envelopes.get_env(env_hash).is_seen()
+                        })
+                        .unwrap_or(false)
+                });
+            (idx, expanded_hash)
+        };
+        self.new_cursor_pos.2 = idx;
+        if self.unfocused {
+            let thread = self.get_thread_under_cursor(idx);
+            self.view = ThreadView::new(self.new_cursor_pos, thread, expanded_hash, context);
+        }
+        self.set_dirty(true);
+        true
+    }
 }
 
 impl fmt::Display for CompactListing {
@@ -874,14 +1103,18 @@ impl CompactListing {
             data_columns: DataColumns::default(),
             rows_drawn: SegmentTree::default(),
             rows: vec![],
+            separators: DateSeparators::default(),
+            dates: EntryDates::default(),
             dirty: true,
             force_draw: true,
             unfocused: false,
             view: ThreadView::default(),
+            preview_pane: PreviewPane::new(false, PreviewPaneOrientation::Vertical),
             color_cache: ColorCache::default(),
+            flags: FlagIndicators::default(),
             movement: None,
-            modifier_active: false,
             modifier_command: None,
+            visual: None,
             id: ComponentId::new_v4(),
         }
     }
@@ -930,33 +1163,73 @@ impl CompactListing {
                 tags.pop();
             }
         }
-        let mut subject = e.subject().to_string();
-        subject.truncate_at_boundary(150);
+        let subject = e.subject().trim_with_ellipsis(150).into_owned();
         if thread.len() > 1 {
             EntryStrings {
                 date: DateString(ConversationsListing::format_date(context, thread.date())),
                 subject: SubjectString(format!("{} ({})", subject, thread.len(),)),
-                flag: FlagString(format!(
-                    "{}{}",
-                    if thread.has_attachments() { "📎" } else { "" },
-                    if thread.snoozed() { "💤" } else { "" }
-                )),
+                flag: self.flags.thread(thread, e, account.notes.contains(e)),
                 from: FromString(address_list!((e.from()) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                size: SizeString(String::new()),
             }
         } else {
             EntryStrings {
                 date: DateString(ConversationsListing::format_date(context, thread.date())),
                 subject: SubjectString(subject),
-                flag: FlagString(format!(
-                    "{}{}",
-                    if thread.has_attachments() { "📎" } else { "" },
-                    if thread.snoozed() { "💤" } else { "" }
-                )),
+                flag: self.flags.thread(thread, e, account.notes.contains(e)),
                 from: FromString(address_list!((e.from()) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                size: SizeString(String::new()),
+            }
+        }
+    }
+
+    /// Total size of the messages in thread `hash`, if `listing.show_size` is set. Call it without
+    /// holding an `EnvelopeRef`, it locks the envelopes.
+    fn size_string(&self, context: &Context, threads: &Threads, hash: ThreadHash) -> SizeString {
+        if !*mailbox_settings!(
+            context[self.cursor_pos.0][&self.cursor_pos.1]
+                .listing
+                .show_size
+        ) {
+            return SizeString(String::new());
+        }
+        let account = &context.accounts[&self.cursor_pos.0];
+        SizeString::new(
+            threads
+                .thread_group_iter(hash)
+                .filter_map(|(_, node_hash)| threads.thread_nodes()[&node_hash].message())
+                .filter(|&env_hash| account.contains_key(env_hash))
+                .map(|env_hash| account.collection.get_env(env_hash).size())
+                .sum(),
+        )
+    }
+
+    /// Select the entries between the visual mode anchor and the cursor, and deselect the ones the
+    /// cursor moved away from. Returns `true` if the selection changed.
+    fn update_visual_selection(&mut self) -> bool {
+        let (anchor, prev_cursor) = match self.visual {
+            Some(visual) => visual,
+            None => return false,
+        };
+        let cursor = self.new_cursor_pos.2;
+        if cursor == prev_cursor || cursor >= self.length {
+            return false;
+        }
+        let range = |end: usize| cmp::min(anchor, end)..=cmp::max(anchor, end);
+        for row in range(prev_cursor) {
+            if !range(cursor).contains(&row) {
+                let thread = self.get_thread_under_cursor(row);
+                self.selection.entry(thread).and_modify(|e| *e = false);
             }
         }
+        for row in range(cursor) {
+            let thread = self.get_thread_under_cursor(row);
+            self.selection.entry(thread).and_modify(|e| *e = true);
+        }
+        self.visual = Some((anchor, cursor));
+        true
     }
 
     fn get_thread_under_cursor(&self, cursor: usize) -> ThreadHash {
@@ -995,8 +1268,9 @@ impl CompactListing {
                 false,
             );
             let envelope: EnvelopeRef = account.collection.get_env(env_hash);
-            let strings = self.make_entry_string(&envelope, context, &threads, thread_hash);
+            let mut strings = self.make_entry_string(&envelope, context, &threads, thread_hash);
             drop(envelope);
+            strings.size = self.size_string(context, &threads, thread_hash);
             let columns = &mut self.data_columns.columns;
             let min_width = (
                 columns[0].size().0,
@@ -1101,7 +1375,8 @@ impl CompactListing {
             match (thread.snoozed(), thread.has_attachments()) {
                 (true, true) => {
                     columns[3][(0, idx)].set_fg(self.color_cache.attachment_flag.fg);
-                    columns[3][(2, idx)].set_fg(self.color_cache.thread_snooze_flag.fg);
+                    columns[3][(self.flags.snoozed_column(), idx)]
+                        .set_fg(self.color_cache.thread_snooze_flag.fg);
                 }
                 (true, false) => {
                     columns[3][(0, idx)].set_fg(self.color_cache.thread_snooze_flag.fg);
@@ -1135,6 +1410,7 @@ impl CompactListing {
             self.data_columns.columns[2].size().0,
             self.data_columns.columns[3].size().0,
             self.data_columns.columns[4].size().0,
+            self.data_columns.columns[5].size().0,
         );
         let account = &context.accounts[&self.cursor_pos.0];
 
@@ -1282,11 +1558,20 @@ impl CompactListing {
                     .set_bg(row_attr.bg)
                     .set_attrs(row_attr.attrs);
             }
+            write_string_to_grid(
+                &format!("{:>1$}", strings.size.as_str(), min_width.5),
+                &mut self.data_columns.columns[5],
+                row_attr.fg,
+                row_attr.bg,
+                row_attr.attrs,
+                ((0, idx), (min_width.5, idx)),
+                None,
+            );
             match (thread.snoozed(), thread.has_attachments()) {
                 (true, true) => {
                     self.data_columns.columns[3][(0, idx)]
                         .set_fg(self.color_cache.attachment_flag.fg);
-                    self.data_columns.columns[3][(2, idx)]
+                    self.data_columns.columns[3][(self.flags.snoozed_column(), idx)]
                         .set_fg(self.color_cache.thread_snooze_flag.fg);
                 }
                 (true, false) => {
@@ -1337,266 +1622,288 @@ impl CompactListing {
                     "Encountered an error while searching for `{}`: {}.",
                     search_term, &err
                 );
-                log(message.clone(), ERROR);
+                log_target("ui", message.clone(), ERROR);
                 context.replies.push_back(UIEvent::Notification(
                     Some("Could not perform search".to_string()),
                     message,
                     Some(crate::types::NotificationType::Error(err.kind)),
                 ));
+                return;
             }
         }
+        let selected = self.selection.values().filter(|v| **v).count();
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                "{} selected.",
+                selected
+            ))));
     }
 }
 
 impl Component for CompactListing {
     fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
-        if !self.unfocused {
-            if !self.is_dirty() {
+        let mut area = area;
+        if self.unfocused {
+            if self.length == 0 && self.dirty {
+                clear_area(grid, area, self.color_cache.theme_default);
+                context.dirty_areas.push_back(area);
                 return;
             }
-            let mut area = area;
-            if !self.filter_term.is_empty() {
-                let (upper_left, bottom_right) = area;
-                let (x, y) = write_string_to_grid(
-                    &format!(
-                        "{} results for `{}` (Press ESC to exit)",
-                        self.filtered_selection.len(),
-                        self.filter_term
-                    ),
-                    grid,
-                    self.color_cache.theme_default.fg,
-                    self.color_cache.theme_default.bg,
-                    self.color_cache.theme_default.attrs,
-                    area,
-                    Some(get_x(upper_left)),
-                );
-                let default_cell = {
-                    let mut ret = Cell::with_char(' ');
-                    ret.set_fg(self.color_cache.theme_default.fg)
-                        .set_bg(self.color_cache.theme_default.bg)
-                        .set_attrs(self.color_cache.theme_default.attrs);
-                    ret
-                };
-                for row in grid.bounds_iter(((x, y), set_y(bottom_right, y))) {
-                    for c in row {
-                        grid[c] = default_cell;
-                    }
+            let coordinates = (self.cursor_pos.0, self.cursor_pos.1);
+            if let Some((list_area, preview_area)) =
+                self.preview_pane.split(grid, area, context, coordinates)
+            {
+                self.view.draw(grid, preview_area, context);
+                if !self.dirty {
+                    return;
                 }
-                context
-                    .dirty_areas
-                    .push_back((upper_left, set_y(bottom_right, y + 1)));
-
-                area = (set_y(upper_left, y + 1), bottom_right);
+                area = list_area;
+            } else {
+                self.view.draw(grid, area, context);
+                self.dirty = false;
+                return;
             }
+        } else if !self.is_dirty() {
+            return;
+        }
+        if !self.filter_term.is_empty() {
             let (upper_left, bottom_right) = area;
-            let rows = get_y(bottom_right) - get_y(upper_left) + 1;
-
-            if let Some(modifier) = self.modifier_command.take() {
-                if let Some(mvm) = self.movement.as_ref() {
-                    match mvm {
-                        PageMovement::Up(amount) => {
-                            for c in self.new_cursor_pos.2.saturating_sub(*amount)
-                                ..=self.new_cursor_pos.2
+            let (x, y) = write_string_to_grid(
+                &search_results_header(
+                    context,
+                    self.new_cursor_pos.0,
+                    &self.filter_term,
+                    self.filtered_selection.len(),
+                ),
+                grid,
+                self.color_cache.theme_default.fg,
+                self.color_cache.theme_default.bg,
+                self.color_cache.theme_default.attrs,
+                area,
+                Some(get_x(upper_left)),
+            );
+            let default_cell = {
+                let mut ret = Cell::with_char(' ');
+                ret.set_fg(self.color_cache.theme_default.fg)
+                    .set_bg(self.color_cache.theme_default.bg)
+                    .set_attrs(self.color_cache.theme_default.attrs);
+                ret
+            };
+            for row in grid.bounds_iter(((x, y), set_y(bottom_right, y))) {
+                for c in row {
+                    grid[c] = default_cell;
+                }
+            }
+            context
+                .dirty_areas
+                .push_back((upper_left, set_y(bottom_right, y + 1)));
+
+            area = (set_y(upper_left, y + 1), bottom_right);
+        }
+        let (upper_left, bottom_right) = area;
+        let rows = get_y(bottom_right) - get_y(upper_left) + 1;
+
+        if let Some(modifier) = self.modifier_command.take() {
+            if let Some(mvm) = self.movement.as_ref() {
+                match mvm {
+                    PageMovement::Up(amount) => {
+                        for c in
+                            self.new_cursor_pos.2.saturating_sub(*amount)..=self.new_cursor_pos.2
+                        {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
+                                }
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = false);
+                                }
+                                Modifier::Intersection => {}
+                            }
+                            self.row_updates.push(thread);
+                        }
+                        if modifier == Modifier::Intersection {
+                            for c in (0..self.new_cursor_pos.2.saturating_sub(*amount))
+                                .chain((self.new_cursor_pos.2 + 2)..self.length)
                             {
                                 let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
-                                }
+                                self.selection.entry(thread).and_modify(|e| *e = false);
                                 self.row_updates.push(thread);
                             }
-                            if modifier == Modifier::Intersection {
-                                for c in (0..self.new_cursor_pos.2.saturating_sub(*amount))
-                                    .chain((self.new_cursor_pos.2 + 2)..self.length)
-                                {
-                                    let thread = self.get_thread_under_cursor(c);
+                        }
+                    }
+                    PageMovement::PageUp(multiplier) => {
+                        for c in self.new_cursor_pos.2.saturating_sub(rows * multiplier)
+                            ..=self.new_cursor_pos.2
+                        {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
+                                }
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
                                     self.selection.entry(thread).and_modify(|e| *e = false);
-                                    self.row_updates.push(thread);
                                 }
+                                Modifier::Intersection => {}
                             }
+                            self.row_updates.push(thread);
                         }
-                        PageMovement::PageUp(multiplier) => {
-                            for c in self.new_cursor_pos.2.saturating_sub(rows * multiplier)
-                                ..=self.new_cursor_pos.2
-                            {
-                                let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
+                    }
+                    PageMovement::Down(amount) => {
+                        for c in self.new_cursor_pos.2
+                            ..std::cmp::min(self.length, self.new_cursor_pos.2 + amount + 1)
+                        {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
                                 }
-                                self.row_updates.push(thread);
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = false);
+                                }
+                                Modifier::Intersection => {}
                             }
+                            self.row_updates.push(thread);
                         }
-                        PageMovement::Down(amount) => {
-                            for c in self.new_cursor_pos.2
-                                ..std::cmp::min(self.length, self.new_cursor_pos.2 + amount + 1)
-                            {
+                        if modifier == Modifier::Intersection {
+                            for c in (0..self.new_cursor_pos.2).chain(
+                                (std::cmp::min(self.length, self.new_cursor_pos.2 + amount + 1) + 1)
+                                    ..self.length,
+                            ) {
                                 let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
-                                }
+                                self.selection.entry(thread).and_modify(|e| *e = false);
                                 self.row_updates.push(thread);
                             }
-                            if modifier == Modifier::Intersection {
-                                for c in (0..self.new_cursor_pos.2).chain(
-                                    (std::cmp::min(self.length, self.new_cursor_pos.2 + amount + 1)
-                                        + 1)..self.length,
-                                ) {
-                                    let thread = self.get_thread_under_cursor(c);
+                        }
+                    }
+                    PageMovement::PageDown(multiplier) => {
+                        for c in self.new_cursor_pos.2
+                            ..std::cmp::min(
+                                self.new_cursor_pos.2 + rows * multiplier + 1,
+                                self.length,
+                            )
+                        {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
+                                }
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
                                     self.selection.entry(thread).and_modify(|e| *e = false);
-                                    self.row_updates.push(thread);
                                 }
+                                Modifier::Intersection => {}
                             }
+                            self.row_updates.push(thread);
                         }
-                        PageMovement::PageDown(multiplier) => {
-                            for c in self.new_cursor_pos.2
-                                ..std::cmp::min(
+                        if modifier == Modifier::Intersection {
+                            for c in (0..self.new_cursor_pos.2).chain(
+                                (std::cmp::min(
                                     self.new_cursor_pos.2 + rows * multiplier + 1,
                                     self.length,
-                                )
-                            {
+                                ) + 1)..self.length,
+                            ) {
                                 let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
-                                }
+                                self.selection.entry(thread).and_modify(|e| *e = false);
                                 self.row_updates.push(thread);
                             }
-                            if modifier == Modifier::Intersection {
-                                for c in (0..self.new_cursor_pos.2).chain(
-                                    (std::cmp::min(
-                                        self.new_cursor_pos.2 + rows * multiplier + 1,
-                                        self.length,
-                                    ) + 1)..self.length,
-                                ) {
-                                    let thread = self.get_thread_under_cursor(c);
+                        }
+                    }
+                    PageMovement::Right(_) | PageMovement::Left(_) => {}
+                    PageMovement::Home => {
+                        for c in 0..=self.new_cursor_pos.2 {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
+                                }
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
                                     self.selection.entry(thread).and_modify(|e| *e = false);
-                                    self.row_updates.push(thread);
                                 }
+                                Modifier::Intersection => {}
                             }
+                            self.row_updates.push(thread);
                         }
-                        PageMovement::Right(_) | PageMovement::Left(_) => {}
-                        PageMovement::Home => {
-                            for c in 0..=self.new_cursor_pos.2 {
+                        if modifier == Modifier::Intersection {
+                            for c in (self.new_cursor_pos.2 + 1)..self.length {
                                 let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
-                                }
+                                self.selection.entry(thread).and_modify(|e| *e = false);
                                 self.row_updates.push(thread);
                             }
-                            if modifier == Modifier::Intersection {
-                                for c in (self.new_cursor_pos.2 + 1)..self.length {
-                                    let thread = self.get_thread_under_cursor(c);
+                        }
+                    }
+                    PageMovement::End => {
+                        for c in self.new_cursor_pos.2..self.length {
+                            let thread = self.get_thread_under_cursor(c);
+                            match modifier {
+                                Modifier::SymmetricDifference => {
+                                    self.selection.entry(thread).and_modify(|e| *e = !*e);
+                                }
+                                Modifier::Union => {
+                                    self.selection.entry(thread).and_modify(|e| *e = true);
+                                }
+                                Modifier::Difference => {
                                     self.selection.entry(thread).and_modify(|e| *e = false);
-                                    self.row_updates.push(thread);
                                 }
+                                Modifier::Intersection => {}
                             }
+                            self.row_updates.push(thread);
                         }
-                        PageMovement::End => {
-                            for c in self.new_cursor_pos.2..self.length {
+                        if modifier == Modifier::Intersection {
+                            for c in 0..self.new_cursor_pos.2 {
                                 let thread = self.get_thread_under_cursor(c);
-                                match modifier {
-                                    Modifier::SymmetricDifference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = !*e);
-                                    }
-                                    Modifier::Union => {
-                                        self.selection.entry(thread).and_modify(|e| *e = true);
-                                    }
-                                    Modifier::Difference => {
-                                        self.selection.entry(thread).and_modify(|e| *e = false);
-                                    }
-                                    Modifier::Intersection => {}
-                                }
+                                self.selection.entry(thread).and_modify(|e| *e = false);
                                 self.row_updates.push(thread);
                             }
-                            if modifier == Modifier::Intersection {
-                                for c in 0..self.new_cursor_pos.2 {
-                                    let thread = self.get_thread_under_cursor(c);
-                                    self.selection.entry(thread).and_modify(|e| *e = false);
-                                    self.row_updates.push(thread);
-                                }
-                            }
                         }
                     }
                 }
-                self.force_draw = true;
             }
+            self.force_draw = true;
+        }
 
-            if !self.row_updates.is_empty() {
-                while let Some(row) = self.row_updates.pop() {
-                    self.update_line(context, row);
-                    let row: usize = self.order[&row];
-                    let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
-
-                    let top_idx = page_no * rows;
-                    if row >= top_idx && row < top_idx + rows {
-                        let area = (
-                            set_y(upper_left, get_y(upper_left) + (row % rows)),
-                            set_y(bottom_right, get_y(upper_left) + (row % rows)),
-                        );
-                        self.highlight_line(grid, area, row, context);
-                        context.dirty_areas.push_back(area);
-                    }
+        if !self.row_updates.is_empty() {
+            while let Some(row) = self.row_updates.pop() {
+                self.update_line(context, row);
+                let row: usize = self.order[&row];
+                let slot = self.separators.slot(row);
+                let page_no = self
+                    .separators
+                    .slot(self.new_cursor_pos.2)
+                    .wrapping_div(rows);
+
+                let top_slot = page_no * rows;
+                if slot >= top_slot && slot < top_slot + rows {
+                    let y = get_y(upper_left) + (slot % rows);
+                    let area = (set_y(upper_left, y), set_y(bottom_right, y));
+                    self.highlight_line(grid, area, row, context);
+                    context.dirty_areas.push_back(area);
                 }
-                if self.force_draw {
-                    /* Draw the entire list */
-                    self.draw_list(grid, area, context);
-                    self.force_draw = false;
-                }
-            } else {
-                /* Draw the entire list */
+            }
+            /* Also draw the list if there is a pending movement, otherwise the highlight stays
+             * on the previous entry. */
+            if self.force_draw || self.movement.is_some() {
                 self.draw_list(grid, area, context);
+                self.force_draw = false;
             }
         } else {
-            if self.length == 0 && self.dirty {
-                clear_area(grid, area, self.color_cache.theme_default);
-                context.dirty_areas.push_back(area);
-                return;
-            }
-
-            self.view.draw(grid, area, context);
+            /* Draw the entire list */
+            self.draw_list(grid, area, context);
         }
         self.dirty = false;
     }
@@ -1626,22 +1933,17 @@ impl Component for CompactListing {
                             k == shortcuts[CompactListing::DESCRIPTION]["exit_thread"]
                         ) =>
                 {
-                    self.unfocused = false;
-                    self.view
-                        .process_event(&mut UIEvent::VisibilityChange(false), context);
-                    self.dirty = true;
-                    /* If self.row_updates is not empty and we exit a thread, the row_update events
-                     * will be performed but the list will not be drawn. So force a draw in any case.
-                     * */
-                    self.force_draw = true;
+                    self.exit_entry(context);
                     return true;
                 }
                 UIEvent::Input(ref key)
                     if !self.unfocused
                         && shortcut!(key == shortcuts[Listing::DESCRIPTION]["select_entry"]) =>
                 {
-                    if self.modifier_active && self.modifier_command.is_none() {
+                    if context.count.is_some() && self.modifier_command.is_none() {
+                        /* Keep the count for the movement that selects the range. */
                         self.modifier_command = Some(Modifier::default());
+                        context.keep_count();
                     } else {
                         let thread_hash = self.get_thread_under_cursor(self.cursor_pos.2);
                         self.selection.entry(thread_hash).and_modify(|e| *e = !*e);
@@ -1670,17 +1972,30 @@ impl Component for CompactListing {
                         }
                         Action::Listing(ToggleThreadSnooze) if !self.unfocused => {
                             let thread = self.get_thread_under_cursor(self.cursor_pos.2);
-                            let account = &mut context.accounts[&self.cursor_pos.0];
-                            account
-                                .collection
-                                .threads
-                                .write()
-                                .unwrap()
-                                .entry(self.cursor_pos.1)
-                                .and_modify(|threads| {
-                                    let is_snoozed = threads.thread_ref(thread).snoozed();
-                                    threads.thread_ref_mut(thread).set_snoozed(!is_snoozed);
-                                });
+                            if let Err(err) = context.accounts[&self.cursor_pos.0]
+                                .toggle_thread_mute(self.cursor_pos.1, thread)
+                            {
+                                context.replies.push_back(UIEvent::Notification(
+                                    Some("Could not save muted thread.".to_string()),
+                                    err.to_string(),
+                                    Some(crate::types::NotificationType::Error(err.kind)),
+                                ));
+                            }
+                            self.row_updates.push(thread);
+                            self.refresh_mailbox(context, false);
+                            return true;
+                        }
+                        Action::Listing(ToggleThreadWatch) if !self.unfocused => {
+                            let thread = self.get_thread_under_cursor(self.cursor_pos.2);
+                            if let Err(err) = context.accounts[&self.cursor_pos.0]
+                                .toggle_thread_watch(self.cursor_pos.1, thread)
+                            {
+                                context.replies.push_back(UIEvent::Notification(
+                                    Some("Could not save watched thread.".to_string()),
+                                    err.to_string(),
+                                    Some(crate::types::NotificationType::Error(err.kind)),
+                                ));
+                            }
                             self.row_updates.push(thread);
                             self.refresh_mailbox(context, false);
                             return true;
@@ -1718,6 +2033,7 @@ impl Component for CompactListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    date_separator: crate::conf::value(context, "mail.listing.date_separator"),
                     theme_default: crate::conf::value(context, "theme_default"),
                     ..self.color_cache
                 };
@@ -22,6 +22,7 @@
 use super::*;
 use crate::components::PageMovement;
 use crate::jobs::JoinHandle;
+use std::convert::TryFrom;
 use std::iter::FromIterator;
 
 macro_rules! row_attr {
@@ -89,6 +90,14 @@ macro_rules! row_attr {
     }};
 }
 
+/// The threads of an address, in `ConversationsGrouping::Correspondents`. The entry of a
+/// correspondent is keyed by its first thread in the current sort order.
+#[derive(Debug)]
+struct Correspondent {
+    address: Address,
+    threads: SmallVec<[ThreadHash; 8]>,
+}
+
 /// A list of all mail (`Envelope`s) in a `Mailbox`. On `\n` it opens the `Envelope` content in a
 /// `ThreadView`.
 #[derive(Debug)]
@@ -101,10 +110,16 @@ pub struct ConversationsListing {
     subsort: (SortField, SortOrder),
     all_threads: HashSet<ThreadHash>,
     order: HashMap<ThreadHash, usize>,
+    grouping: ConversationsGrouping,
+    /// Entry thread to correspondent, empty unless grouping by correspondent.
+    correspondents: HashMap<ThreadHash, Correspondent>,
     /// Cache current view.
     content: CellBuffer,
+    separators: DateSeparators,
+    dates: EntryDates,
 
     search_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
+    select_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
     filter_term: String,
     filtered_selection: Vec<ThreadHash>,
     filtered_order: HashMap<ThreadHash, usize>,
@@ -115,12 +130,16 @@ pub struct ConversationsListing {
     /// If `self.view` exists or not.
     unfocused: bool,
     view: ThreadView,
+    preview_pane: PreviewPane,
     row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    flags: FlagIndicators,
 
     movement: Option<PageMovement>,
-    modifier_active: bool,
     modifier_command: Option<Modifier>,
+    /// Row where visual mode started and the row of the cursor when the selection was last
+    /// extended.
+    visual: Option<(usize, usize)>,
     id: ComponentId,
 }
 
@@ -133,9 +152,18 @@ impl MailListingTrait for ConversationsListing {
         &mut self.selection
     }
 
-    fn get_focused_items(&self, _context: &Context) -> SmallVec<[ThreadHash; 8]> {
+    fn get_focused_items(&self, context: &Context) -> SmallVec<[ThreadHash; 8]> {
         let is_selection_empty = self.selection.values().cloned().any(std::convert::identity);
-        let i = [self.get_thread_under_cursor(self.cursor_pos.2)];
+        /* With a count, focus that many entries starting from the cursor. */
+        let rows = if self.filter_term.is_empty() {
+            self.length
+        } else {
+            self.filtered_selection.len()
+        };
+        let i: SmallVec<[ThreadHash; 8]> = (self.cursor_pos.2
+            ..std::cmp::min(self.cursor_pos.2 + context.count.unwrap_or(1), rows))
+            .map(|row| self.get_thread_under_cursor(row))
+            .collect();
         let cursor_iter;
         let sel_iter = if is_selection_empty {
             cursor_iter = None;
@@ -148,7 +176,7 @@ impl MailListingTrait for ConversationsListing {
             .into_iter()
             .flatten()
             .chain(cursor_iter.into_iter().flatten())
-            .cloned();
+            .flat_map(|thread| self.entry_threads(*thread));
         SmallVec::from_iter(iter.into_iter())
     }
 
@@ -156,6 +184,7 @@ impl MailListingTrait for ConversationsListing {
     /// chosen.
     fn refresh_mailbox(&mut self, context: &mut Context, force: bool) {
         self.dirty = true;
+        self.visual = None;
         let old_mailbox_hash = self.cursor_pos.1;
         let old_cursor_pos = self.cursor_pos;
         if !(self.cursor_pos.0 == self.new_cursor_pos.0
@@ -183,9 +212,11 @@ impl MailListingTrait for ConversationsListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            date_separator: crate::conf::value(context, "mail.listing.date_separator"),
             ..self.color_cache
         };
 
+        self.flags = FlagIndicators::new(context, self.cursor_pos.0, self.cursor_pos.1);
         if !context.settings.terminal.use_color() {
             self.color_cache.highlighted.attrs |= Attr::REVERSE;
             self.color_cache.tag_default.attrs |= Attr::REVERSE;
@@ -196,7 +227,7 @@ impl MailListingTrait for ConversationsListing {
             Ok(()) => {}
             Err(_) => {
                 let message: String =
-                    context.accounts[&self.cursor_pos.0][&self.cursor_pos.1].status();
+                    super::mailbox_status(context, self.cursor_pos.0, self.cursor_pos.1);
                 self.content = CellBuffer::new_with_context(message.len(), 1, None, context);
                 self.length = 0;
                 write_string_to_grid(
@@ -221,8 +252,15 @@ impl MailListingTrait for ConversationsListing {
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
+            &collator(context, self.cursor_pos.0, self.cursor_pos.1),
         );
 
+        drop(threads);
+        self.correspondents.clear();
+        if self.grouping == ConversationsGrouping::Correspondents && self.filter_term.is_empty() {
+            roots = self.group_by_correspondent(context, roots);
+        }
+
         self.redraw_threads_list(
             context,
             Box::new(roots.into_iter()) as Box<dyn Iterator<Item = ThreadHash>>,
@@ -249,6 +287,9 @@ impl MailListingTrait for ConversationsListing {
         self.order.clear();
         self.selection.clear();
         self.length = 0;
+        self.separators
+            .reset(context, (self.cursor_pos.0, self.cursor_pos.1), self.sort.0);
+        self.dates.clear();
         let mut rows = Vec::with_capacity(1024);
         let mut max_entry_columns = 0;
 
@@ -340,6 +381,9 @@ impl MailListingTrait for ConversationsListing {
                 strings.date.len() + 1 + strings.from.grapheme_width(),
             );
             rows.push(((self.length, (thread, root_env_hash)), strings));
+            self.separators.push(threads.thread_ref(thread).date());
+            self.dates
+                .push(threads.thread_ref(thread).date(), root_env_hash);
             self.all_threads.insert(thread);
 
             self.order.insert(thread, self.length);
@@ -356,11 +400,11 @@ impl MailListingTrait for ConversationsListing {
             if !context.accounts[&self.cursor_pos.0].contains_key(root_env_hash) {
                 panic!();
             }
-            let thread = threads.thread_ref(thread_hash);
+            let unseen = self.is_unseen(&threads, thread_hash);
 
             let row_attr = row_attr!(
                 self.color_cache,
-                thread.unseen() > 0,
+                unseen,
                 false,
                 self.selection[&thread_hash]
             );
@@ -380,7 +424,7 @@ impl MailListingTrait for ConversationsListing {
             let subject_attr = row_attr!(
                 subject,
                 self.color_cache,
-                thread.unseen() > 0,
+                unseen,
                 false,
                 self.selection[&thread_hash]
             );
@@ -426,7 +470,7 @@ impl MailListingTrait for ConversationsListing {
             let date_attr = row_attr!(
                 date,
                 self.color_cache,
-                thread.unseen() > 0,
+                unseen,
                 false,
                 self.selection[&thread_hash]
             );
@@ -449,7 +493,7 @@ impl MailListingTrait for ConversationsListing {
             let from_attr = row_attr!(
                 from,
                 self.color_cache,
-                thread.unseen() > 0,
+                unseen,
                 false,
                 self.selection[&thread_hash]
             );
@@ -498,6 +542,10 @@ impl ListingTrait for ConversationsListing {
         (self.new_cursor_pos.0, self.new_cursor_pos.1)
     }
 
+    fn preview_pane_mut(&mut self) -> Option<&mut PreviewPane> {
+        Some(&mut self.preview_pane)
+    }
+
     fn set_coordinates(&mut self, coordinates: (AccountHash, MailboxHash)) {
         self.new_cursor_pos = (coordinates.0, coordinates.1, 0);
         self.unfocused = false;
@@ -516,16 +564,16 @@ impl ListingTrait for ConversationsListing {
 
         let account = &context.accounts[&self.cursor_pos.0];
         let threads = account.collection.get_threads(self.cursor_pos.1);
-        let thread = threads.thread_ref(thread_hash);
+        let unseen = self.is_unseen(&threads, thread_hash);
 
         let row_attr = row_attr!(
             self.color_cache,
-            thread.unseen() > 0,
+            unseen,
             self.cursor_pos.2 == idx,
             self.selection[&thread_hash]
         );
 
-        let padding_fg = if thread.unseen() > 0 {
+        let padding_fg = if unseen {
             self.color_cache.unseen_padding.fg
         } else {
             self.color_cache.padding.fg
@@ -638,10 +686,15 @@ impl ListingTrait for ConversationsListing {
             }
         }
 
-        let prev_page_no = (self.cursor_pos.2).wrapping_div(rows);
-        let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
+        let visual_changed = self.update_visual_selection(context);
 
-        let top_idx = page_no * rows;
+        let prev_page_no = self.separators.slot(self.cursor_pos.2).wrapping_div(rows);
+        let page_no = self
+            .separators
+            .slot(self.new_cursor_pos.2)
+            .wrapping_div(rows);
+
+        let top_slot = page_no * rows;
 
         /* If cursor position has changed, remove the highlight from the previous position and
          * apply it in the new one. */
@@ -652,14 +705,14 @@ impl ListingTrait for ConversationsListing {
                 if *idx >= self.length {
                     continue; //bounds check
                 }
-                let new_area = (
-                    set_y(upper_left, get_y(upper_left) + 3 * (*idx % rows)),
-                    set_y(bottom_right, get_y(upper_left) + 3 * (*idx % rows) + 2),
-                );
+                let y = get_y(upper_left) + 3 * (self.separators.slot(*idx) % rows);
+                let new_area = (set_y(upper_left, y), set_y(bottom_right, y + 2));
                 self.highlight_line(grid, new_area, *idx, context);
                 context.dirty_areas.push_back(new_area);
             }
-            return;
+            if !visual_changed {
+                return;
+            }
         } else if self.cursor_pos != self.new_cursor_pos {
             self.cursor_pos = self.new_cursor_pos;
         }
@@ -669,47 +722,59 @@ impl ListingTrait for ConversationsListing {
         }
 
         clear_area(grid, area, self.color_cache.theme_default);
+        let (runs, separators) = self.separators.page(top_slot, rows, self.length);
         /* Page_no has changed, so draw new page */
-        copy_area(
-            grid,
-            &self.content,
-            (
-                upper_left,
-                set_x(
-                    bottom_right,
-                    std::cmp::min(
-                        get_x(bottom_right),
-                        get_x(upper_left) + self.content.size().0,
+        for (row, entries) in &runs {
+            copy_area(
+                grid,
+                &self.content,
+                (
+                    pos_inc(upper_left, (0, 3 * row)),
+                    set_x(
+                        bottom_right,
+                        std::cmp::min(
+                            get_x(bottom_right),
+                            get_x(upper_left) + self.content.size().0,
+                        ),
                     ),
                 ),
-            ),
-            ((0, 3 * top_idx), pos_dec(self.content.size(), (1, 1))),
-        );
+                (
+                    (0, 3 * entries.start),
+                    (self.content.size().0.saturating_sub(1), 3 * entries.end - 1),
+                ),
+            );
+        }
+        for (r, label) in &separators {
+            /* Separators take up a whole entry, with the label in the middle. */
+            draw_date_separator(
+                grid,
+                area,
+                get_y(upper_left) + 3 * r + 1,
+                label,
+                self.color_cache.date_separator,
+            );
+        }
 
+        let y = get_y(upper_left) + 3 * (self.separators.slot(self.cursor_pos.2) % rows);
         self.highlight_line(
             grid,
-            (
-                pos_inc(upper_left, (0, 3 * (self.cursor_pos.2 % rows))),
-                set_y(
-                    bottom_right,
-                    get_y(upper_left) + 3 * (self.cursor_pos.2 % rows) + 2,
-                ),
-            ),
+            (set_y(upper_left, y), set_y(bottom_right, y + 2)),
             self.cursor_pos.2,
             context,
         );
 
         /* calculate how many entries are visible in this page */
-        let (pad, rows) = if top_idx + rows > self.length {
+        let slots = self.separators.len(self.length);
+        let (pad, rows) = if top_slot + rows > slots {
             clear_area(
                 grid,
                 (
-                    pos_inc(upper_left, (0, 3 * (self.length - top_idx))),
+                    pos_inc(upper_left, (0, 3 * (slots - top_slot))),
                     bottom_right,
                 ),
                 self.color_cache.theme_default,
             );
-            (0, self.length - top_idx)
+            (0, slots - top_slot)
         } else {
             (pad, rows)
         };
@@ -720,7 +785,7 @@ impl ListingTrait for ConversationsListing {
 
         if width < width!(area) {
             let y_offset = get_y(upper_left);
-            for y in 0..rows {
+            for y in (0..rows).filter(|r| !separators.iter().any(|(s, _)| s == r)) {
                 let bg_color = grid[(get_x(upper_left) + width - 1, y_offset + 3 * y)].bg();
                 for x in (get_x(upper_left) + width)..=get_x(bottom_right) {
                     grid[(x, y_offset + 3 * y)].set_bg(bg_color);
@@ -770,6 +835,7 @@ impl ListingTrait for ConversationsListing {
         self.filtered_selection.clear();
         self.filtered_order.clear();
         self.filter_term = filter_term;
+        self.correspondents.clear();
         self.row_updates.clear();
         for v in self.selection.values_mut() {
             *v = false;
@@ -803,6 +869,7 @@ impl ListingTrait for ConversationsListing {
                         &mut self.filtered_selection,
                         self.sort,
                         &context.accounts[&self.cursor_pos.0].collection.envelopes,
+                        &collator(context, self.cursor_pos.0, self.cursor_pos.1),
                     );
                     self.new_cursor_pos.2 =
                         std::cmp::min(self.filtered_selection.len() - 1, self.cursor_pos.2);
@@ -822,7 +889,8 @@ impl ListingTrait for ConversationsListing {
                     "Encountered an error while searching for `{}`: {}.",
                     self.filter_term, e
                 );
-                log(
+                log_target(
+                    "ui",
                     format!("Failed to search for term {}: {}", self.filter_term, e),
                     ERROR,
                 );
@@ -844,10 +912,6 @@ impl ListingTrait for ConversationsListing {
         self.unfocused
     }
 
-    fn set_modifier_active(&mut self, new_val: bool) {
-        self.modifier_active = new_val;
-    }
-
     fn set_modifier_command(&mut self, new_val: Option<Modifier>) {
         self.modifier_command = new_val;
     }
@@ -856,10 +920,183 @@ impl ListingTrait for ConversationsListing {
         self.modifier_command
     }
 
+    fn visual_mode(&self) -> bool {
+        self.visual.is_some()
+    }
+
+    fn start_visual_mode(&mut self) {
+        if self.length == 0 {
+            return;
+        }
+        let cursor = self.new_cursor_pos.2;
+        let thread = self.get_thread_under_cursor(cursor);
+        self.selection.entry(thread).and_modify(|e| *e = true);
+        self.row_updates.push(thread);
+        self.visual = Some((cursor, cursor));
+        self.dirty = true;
+    }
+
+    fn end_visual_mode(&mut self, deselect: bool) {
+        if let Some((anchor, cursor)) = self.visual.take() {
+            if deselect {
+                for row in std::cmp::min(anchor, cursor)..=std::cmp::max(anchor, cursor) {
+                    let thread = self.get_thread_under_cursor(row);
+                    self.selection.entry(thread).and_modify(|e| *e = false);
+                    self.row_updates.push(thread);
+                }
+            }
+            self.dirty = true;
+        }
+    }
+
+    fn selected_count(&self) -> usize {
+        self.selection.values().filter(|v| **v).count()
+    }
+
+    fn selected_envelopes(&self, context: &Context) -> Vec<EnvelopeHash> {
+        let account = &context.accounts[&self.cursor_pos.0];
+        let threads = account.collection.get_threads(self.cursor_pos.1);
+        self.selection
+            .iter()
+            .filter(|(_, v)| **v)
+            .flat_map(|(thread, _)| self.entry_threads(*thread))
+            .flat_map(|thread| threads.thread_group_iter(thread))
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .collect()
+    }
+
+    fn exit_entry(&mut self, context: &mut Context) {
+        self.unfocused = false;
+        self.view
+            .process_event(&mut UIEvent::VisibilityChange(false), context);
+        self.dirty = true;
+        /* If self.row_updates is not empty and we exit a thread, the row_update events
+         * will be performed but the list will not be drawn. So force a draw in any case.
+         * */
+        self.force_draw = true;
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn save_listing_session(&self, session: &mut ListingSession) {
+        session.cursor = self.cursor_pos.2;
+        session.sort = Some(self.sort);
+        if !self.filter_term.is_empty() {
+            session.filter = Some(self.filter_term.clone());
+        }
+    }
+
+    fn dated_entries(&self, range: (UnixTimestamp, UnixTimestamp)) -> Option<Vec<EnvelopeHash>> {
+        Some(self.dates.range(range))
+    }
+
+    fn envelope_under_cursor(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0
+            || self.cursor_pos.1 != self.new_cursor_pos.1
+            || self.cursor_pos.2 >= self.length
+        {
+            return None;
+        }
+        let thread_hash = self.get_thread_under_cursor(self.cursor_pos.2);
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        if !threads.groups.contains_key(&thread_hash) {
+            return None;
+        }
+        threads
+            .thread_group_iter(thread_hash)
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .next()
+    }
+
+    fn select_thread_node(&mut self, thread_node: ThreadNodeHash, context: &Context) -> bool {
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        let thread_hash = match threads.thread_nodes().get(&thread_node) {
+            Some(node) => threads.find_group(node.group),
+            None => return false,
+        };
+        let idx = if self.filter_term.is_empty() {
+            self.order.get(&thread_hash)
+        } else {
+            self.filtered_order.get(&thread_hash)
+        };
+        if let Some(&idx) = idx {
+            self.new_cursor_pos.2 = idx;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn select_unseen(&mut self, from_start: bool, wrap: bool, context: &mut Context) -> bool {
+        let cursor = if from_start {
+            None
+        } else {
+            Some(self.new_cursor_pos.2)
+        };
+        let (idx, expanded_hash) = {
+            let threads = context.accounts[&self.cursor_pos.0]
+                .collection
+                .get_threads(self.cursor_pos.1);
+            let is_unseen = |thread_hash: &ThreadHash| {
+                threads.groups.contains_key(thread_hash)
+                    && threads.thread_ref(*thread_hash).unseen() > 0
+            };
+            let idx = if self.filter_term.is_empty() {
+                next_unseen_idx(
+                    self.order
+                        .iter()
+                        .filter(|(thread_hash, _)| is_unseen(thread_hash))
+                        .map(|(_, idx)| *idx),
+                    cursor,
+                    wrap,
+                )
+            } else {
+                next_unseen_idx(
+                    self.filtered_selection
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, thread_hash)| is_unseen(thread_hash))
+                        .map(|(idx, _)| idx),
+                    cursor,
+                    wrap,
+                )
+            };
+            let idx = match idx {
+                Some(idx) => idx,
+                None => return false,
+            };
+            /* Expand the first unseen message of the thread */
+            let envelopes = &context.accounts[&self.cursor_pos.0].collection;
+            let expanded_hash = threads
+                .thread_group_iter(self.get_thread_under_cursor(idx))
+                .map(|(_, h)| h)
+                .find(|h| {
+                    threads.thread_nodes()[h]
+                        .message()
+                        .map(|env_hash| {
+                            envelopes.contains_key(&env_hash)
+                                && !envelopes.get_env(env_hash).is_seen()
+                        })
+                        .unwrap_or(false)
+                });
+            (idx, expanded_hash)
+        };
+        self.new_cursor_pos.2 = idx;
+        if self.unfocused {
+            let thread = self.get_thread_under_cursor(idx);
+            self.view = ThreadView::new(self.new_cursor_pos, thread, expanded_hash, context);
+        }
+        self.set_dirty(true);
+        true
+    }
 }
 
 impl fmt::Display for ConversationsListing {
@@ -879,21 +1116,28 @@ impl ConversationsListing {
             subsort: (SortField::Date, SortOrder::Desc),
             order: HashMap::default(),
             all_threads: HashSet::default(),
+            grouping: ConversationsGrouping::default(),
+            correspondents: HashMap::default(),
             search_job: None,
+            select_job: None,
             filter_term: String::new(),
             filtered_selection: Vec::new(),
             filtered_order: HashMap::default(),
             selection: HashMap::default(),
             row_updates: SmallVec::new(),
             content: Default::default(),
+            separators: DateSeparators::default(),
+            dates: EntryDates::default(),
             dirty: true,
             force_draw: true,
             unfocused: false,
             view: ThreadView::default(),
+            preview_pane: PreviewPane::new(true, PreviewPaneOrientation::Vertical),
             color_cache: ColorCache::default(),
+            flags: FlagIndicators::default(),
             movement: None,
-            modifier_active: false,
             modifier_command: None,
+            visual: None,
             id: ComponentId::new_v4(),
         }
     }
@@ -906,6 +1150,21 @@ impl ConversationsListing {
         hash: ThreadHash,
     ) -> EntryStrings {
         let thread = threads.thread_ref(hash);
+        if let Some(correspondent) = self.correspondents.get(&hash) {
+            let subject = e.subject().trim_with_ellipsis(150).into_owned();
+            return EntryStrings {
+                date: DateString(ConversationsListing::format_date(context, thread.date())),
+                subject: SubjectString(format!(
+                    "{} ({})",
+                    correspondent.address,
+                    correspondent.threads.len()
+                )),
+                flag: FlagString(String::new()),
+                from: FromString(subject),
+                tags: TagString(String::new(), SmallVec::new()),
+                size: SizeString(String::new()),
+            };
+        }
         let mut tags = String::new();
         let mut colors = SmallVec::new();
         let account = &context.accounts[&self.cursor_pos.0];
@@ -943,31 +1202,24 @@ impl ConversationsListing {
                 tags.pop();
             }
         }
-        let mut subject = e.subject().to_string();
-        subject.truncate_at_boundary(150);
+        let subject = e.subject().trim_with_ellipsis(150).into_owned();
         if thread.len() > 1 {
             EntryStrings {
                 date: DateString(ConversationsListing::format_date(context, thread.date())),
                 subject: SubjectString(format!("{} ({})", subject, thread.len())),
-                flag: FlagString(format!(
-                    "{}{}",
-                    if thread.has_attachments() { "📎" } else { "" },
-                    if thread.snoozed() { "💤" } else { "" }
-                )),
+                flag: self.flags.thread(thread, e, account.notes.contains(e)),
                 from: FromString(address_list!((from) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                size: SizeString(String::new()),
             }
         } else {
             EntryStrings {
                 date: DateString(ConversationsListing::format_date(context, thread.date())),
                 subject: SubjectString(subject),
-                flag: FlagString(format!(
-                    "{}{}",
-                    if thread.has_attachments() { "📎" } else { "" },
-                    if thread.snoozed() { "💤" } else { "" }
-                )),
+                flag: self.flags.thread(thread, e, account.notes.contains(e)),
                 from: FromString(address_list!((from) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                size: SizeString(String::new()),
             }
         }
     }
@@ -1007,6 +1259,130 @@ impl ConversationsListing {
         }
     }
 
+    pub fn set_grouping(&mut self, grouping: ConversationsGrouping, context: &mut Context) {
+        if self.grouping == grouping {
+            return;
+        }
+        self.grouping = grouping;
+        self.set_coordinates((self.new_cursor_pos.0, self.new_cursor_pos.1));
+        self.refresh_mailbox(context, false);
+        self.set_dirty(true);
+    }
+
+    /// Group `roots` by the address of the other party of each thread: the sender of its first
+    /// message or, for threads started from the account's identity, the first recipient. Returns
+    /// the first thread of each correspondent, in the order of `roots`.
+    fn group_by_correspondent(
+        &mut self,
+        context: &Context,
+        roots: SmallVec<[ThreadHash; 1024]>,
+    ) -> SmallVec<[ThreadHash; 1024]> {
+        let account = &context.accounts[&self.cursor_pos.0];
+        let threads = account.collection.get_threads(self.cursor_pos.1);
+        let identity = Address::try_from(account.settings.account.identity())
+            .ok()
+            .map(|a| a.get_email().to_lowercase());
+        let mut entries: HashMap<String, ThreadHash> = HashMap::default();
+        let mut ret = SmallVec::new();
+        for thread in roots {
+            let env_hash = match threads
+                .thread_group_iter(thread)
+                .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+                .next()
+            {
+                Some(env_hash) => env_hash,
+                None => continue,
+            };
+            let envelope = account.collection.get_env(env_hash);
+            let address = match envelope.from().first() {
+                Some(from) if Some(from.get_email().to_lowercase()) != identity => from.clone(),
+                _ => match envelope.to().first() {
+                    Some(to) => to.clone(),
+                    None => continue,
+                },
+            };
+            let key = address.get_email().to_lowercase();
+            let entry = *entries.entry(key).or_insert_with(|| {
+                ret.push(thread);
+                thread
+            });
+            self.correspondents
+                .entry(entry)
+                .or_insert_with(|| Correspondent {
+                    address,
+                    threads: SmallVec::new(),
+                })
+                .threads
+                .push(thread);
+        }
+        ret
+    }
+
+    /// The threads of the entry keyed by `thread`.
+    fn entry_threads(&self, thread: ThreadHash) -> SmallVec<[ThreadHash; 8]> {
+        match self.correspondents.get(&thread) {
+            Some(correspondent) => correspondent.threads.clone(),
+            None => smallvec::smallvec![thread],
+        }
+    }
+
+    fn is_unseen(&self, threads: &Threads, thread: ThreadHash) -> bool {
+        self.entry_threads(thread)
+            .into_iter()
+            .any(|t| threads.thread_ref(t).unseen() > 0)
+    }
+
+    /// Show the threads of the correspondent under the cursor, until the filter is cleared.
+    fn open_correspondent(&mut self, context: &Context) -> bool {
+        let thread = self.get_thread_under_cursor(self.cursor_pos.2);
+        let correspondent = match self.correspondents.get(&thread) {
+            Some(correspondent) => correspondent,
+            None => return false,
+        };
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        let results = correspondent
+            .threads
+            .iter()
+            .flat_map(|thread| threads.thread_group_iter(*thread))
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .collect::<SmallVec<[EnvelopeHash; 512]>>();
+        let filter_term = format!("correspondent: {}", correspondent.address.get_email());
+        drop(threads);
+        self.filter(filter_term, Ok(results), context);
+        self.set_dirty(true);
+        true
+    }
+
+    /// Select the entries between the visual mode anchor and the cursor, and deselect the ones the
+    /// cursor moved away from. Returns `true` if the selection changed.
+    fn update_visual_selection(&mut self, context: &Context) -> bool {
+        let (anchor, prev_cursor) = match self.visual {
+            Some(visual) => visual,
+            None => return false,
+        };
+        let cursor = self.new_cursor_pos.2;
+        if cursor == prev_cursor || cursor >= self.length {
+            return false;
+        }
+        let range = |end: usize| std::cmp::min(anchor, end)..=std::cmp::max(anchor, end);
+        for row in range(prev_cursor) {
+            if !range(cursor).contains(&row) {
+                let thread = self.get_thread_under_cursor(row);
+                self.selection.entry(thread).and_modify(|e| *e = false);
+                self.update_line(context, thread);
+            }
+        }
+        for row in range(cursor) {
+            let thread = self.get_thread_under_cursor(row);
+            self.selection.entry(thread).and_modify(|e| *e = true);
+            self.update_line(context, thread);
+        }
+        self.visual = Some((anchor, cursor));
+        true
+    }
+
     fn get_thread_under_cursor(&self, cursor: usize) -> ThreadHash {
         if self.filter_term.is_empty() {
             *self
@@ -1024,9 +1400,18 @@ impl ConversationsListing {
     }
 
     fn update_line(&mut self, context: &Context, thread_hash: ThreadHash) {
+        let thread_hash = self
+            .correspondents
+            .iter()
+            .find(|(_, c)| c.threads.contains(&thread_hash))
+            .map(|(entry, _)| *entry)
+            .unwrap_or(thread_hash);
+        if !self.order.contains_key(&thread_hash) {
+            return;
+        }
         let account = &context.accounts[&self.cursor_pos.0];
         let threads = account.collection.get_threads(self.cursor_pos.1);
-        let thread = threads.thread_ref(thread_hash);
+        let unseen = self.is_unseen(&threads, thread_hash);
         let thread_node_hash = threads.thread_group_iter(thread_hash).next().unwrap().1;
         let idx: usize = self.order[&thread_hash];
         let width = self.content.size().0;
@@ -1035,12 +1420,12 @@ impl ConversationsListing {
 
         let row_attr = row_attr!(
             self.color_cache,
-            thread.unseen() > 0,
+            unseen,
             false,
             self.selection[&thread_hash]
         );
 
-        let padding_fg = if thread.unseen() > 0 {
+        let padding_fg = if unseen {
             self.color_cache.unseen_padding.fg
         } else {
             self.color_cache.padding.fg
@@ -1091,7 +1476,7 @@ impl ConversationsListing {
         let subject_attr = row_attr!(
             subject,
             self.color_cache,
-            thread.unseen() > 0,
+            unseen,
             false,
             self.selection[&thread_hash]
         );
@@ -1137,7 +1522,7 @@ impl ConversationsListing {
         let date_attr = row_attr!(
             date,
             self.color_cache,
-            thread.unseen() > 0,
+            unseen,
             false,
             self.selection[&thread_hash]
         );
@@ -1160,7 +1545,7 @@ impl ConversationsListing {
         let from_attr = row_attr!(
             from,
             self.color_cache,
-            thread.unseen() > 0,
+            unseen,
             false,
             self.selection[&thread_hash]
         );
@@ -1188,6 +1573,55 @@ impl ConversationsListing {
                 .set_bg(row_attr.bg);
         }
     }
+
+    fn select(
+        &mut self,
+        search_term: &str,
+        results: Result<SmallVec<[EnvelopeHash; 512]>>,
+        context: &mut Context,
+    ) {
+        let account = &context.accounts[&self.cursor_pos.0];
+        match results {
+            Ok(results) => {
+                let threads = account.collection.get_threads(self.cursor_pos.1);
+                for env_hash in results {
+                    if !account.collection.contains_key(&env_hash) {
+                        continue;
+                    }
+                    let env_thread_node_hash = account.collection.get_env(env_hash).thread();
+                    if !threads.thread_nodes.contains_key(&env_thread_node_hash) {
+                        continue;
+                    }
+                    let thread =
+                        threads.find_group(threads.thread_nodes[&env_thread_node_hash].group);
+                    if let Some(entry) = self.selection.get_mut(&thread) {
+                        *entry = true;
+                        self.row_updates.push(thread);
+                    }
+                }
+            }
+            Err(err) => {
+                let message = format!(
+                    "Encountered an error while searching for `{}`: {}.",
+                    search_term, &err
+                );
+                log_target("ui", message.clone(), ERROR);
+                context.replies.push_back(UIEvent::Notification(
+                    Some("Could not perform search".to_string()),
+                    message,
+                    Some(crate::types::NotificationType::Error(err.kind)),
+                ));
+                return;
+            }
+        }
+        let selected = self.selection.values().filter(|v| **v).count();
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                "{} selected.",
+                selected
+            ))));
+    }
 }
 
 impl Component for ConversationsListing {
@@ -1195,35 +1629,36 @@ impl Component for ConversationsListing {
         if !self.is_dirty() {
             return;
         }
+        let mut area = area;
+        if self.unfocused {
+            if self.length == 0 && self.dirty {
+                clear_area(grid, area, self.color_cache.theme_default);
+                context.dirty_areas.push_back(area);
+                return;
+            }
+            let coordinates = (self.cursor_pos.0, self.cursor_pos.1);
+            if let Some((list_area, preview_area)) =
+                self.preview_pane.split(grid, area, context, coordinates)
+            {
+                self.view.draw(grid, preview_area, context);
+                area = list_area;
+            } else {
+                self.view.draw(grid, area, context);
+                self.dirty = false;
+                return;
+            }
+        }
         let (upper_left, bottom_right) = area;
         {
-            let mut area = if self.unfocused {
-                clear_area(
-                    grid,
-                    (
-                        pos_inc(upper_left, (width!(area) / 3, 0)),
-                        set_x(bottom_right, get_x(upper_left) + width!(area) / 3 + 1),
-                    ),
-                    self.color_cache.theme_default,
-                );
-                context.dirty_areas.push_back((
-                    pos_inc(upper_left, (width!(area) / 3, 0)),
-                    set_x(bottom_right, get_x(upper_left) + width!(area) / 3 + 1),
-                ));
-                (
-                    upper_left,
-                    set_x(bottom_right, get_x(upper_left) + width!(area) / 3 - 1),
-                )
-            } else {
-                area
-            };
+            let mut area = area;
 
             if !self.filter_term.is_empty() {
                 let (x, y) = write_string_to_grid(
-                    &format!(
-                        "{} results for `{}` (Press ESC to exit)",
+                    &search_results_header(
+                        context,
+                        self.new_cursor_pos.0,
+                        &self.filter_term,
                         self.filtered_selection.len(),
-                        self.filter_term
                     ),
                     grid,
                     self.color_cache.theme_default.fg,
@@ -1425,22 +1860,22 @@ impl Component for ConversationsListing {
                 while let Some(row) = self.row_updates.pop() {
                     self.update_line(context, row);
                     let row: usize = self.order[&row];
+                    let slot = self.separators.slot(row);
 
-                    let page_no = (self.cursor_pos.2).wrapping_div(rows);
+                    let page_no = self.separators.slot(self.cursor_pos.2).wrapping_div(rows);
 
-                    let top_idx = page_no * rows;
+                    let top_slot = page_no * rows;
                     /* Update row only if it's currently visible */
-                    if row >= top_idx && row < top_idx + rows {
-                        let area = (
-                            set_y(upper_left, get_y(upper_left) + (3 * (row % rows))),
-                            set_y(bottom_right, get_y(upper_left) + (3 * (row % rows) + 2)),
-                        );
+                    if slot >= top_slot && slot < top_slot + rows {
+                        let y = get_y(upper_left) + 3 * (slot % rows);
+                        let area = (set_y(upper_left, y), set_y(bottom_right, y + 2));
                         self.highlight_line(grid, area, row, context);
                         context.dirty_areas.push_back(area);
                     }
                 }
-                if self.force_draw {
-                    /* Draw the entire list */
+                /* Also draw the list if there is a pending movement, otherwise the highlight stays
+                 * on the previous entry. */
+                if self.force_draw || self.movement.is_some() {
                     self.draw_list(grid, area, context);
                     self.force_draw = false;
                 }
@@ -1449,19 +1884,6 @@ impl Component for ConversationsListing {
                 self.draw_list(grid, area, context);
             }
         }
-        if self.unfocused {
-            if self.length == 0 && self.dirty {
-                clear_area(grid, area, self.color_cache.theme_default);
-                context.dirty_areas.push_back(area);
-                return;
-            }
-
-            let area = (
-                set_x(upper_left, get_x(upper_left) + width!(area) / 3 + 2),
-                bottom_right,
-            );
-            self.view.draw(grid, area, context);
-        }
         self.dirty = false;
     }
     fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
@@ -1478,6 +1900,9 @@ impl Component for ConversationsListing {
                             k == shortcuts[ConversationsListing::DESCRIPTION]["open_thread"]
                         ) =>
                 {
+                    if self.open_correspondent(context) {
+                        return true;
+                    }
                     let thread = self.get_thread_under_cursor(self.cursor_pos.2);
                     self.view = ThreadView::new(self.cursor_pos, thread, None, context);
                     self.unfocused = true;
@@ -1490,22 +1915,17 @@ impl Component for ConversationsListing {
                             k == shortcuts[ConversationsListing::DESCRIPTION]["exit_thread"]
                         ) =>
                 {
-                    self.unfocused = false;
-                    self.view
-                        .process_event(&mut UIEvent::VisibilityChange(false), context);
-                    self.dirty = true;
-                    /* If self.row_updates is not empty and we exit a thread, the row_update events
-                     * will be performed but the list will not be drawn. So force a draw in any case.
-                     * */
-                    self.force_draw = true;
+                    self.exit_entry(context);
                     return true;
                 }
                 UIEvent::Input(ref key)
                     if !self.unfocused
                         && shortcut!(key == shortcuts[Listing::DESCRIPTION]["select_entry"]) =>
                 {
-                    if self.modifier_active && self.modifier_command.is_none() {
+                    if context.count.is_some() && self.modifier_command.is_none() {
+                        /* Keep the count for the movement that selects the range. */
                         self.modifier_command = Some(Modifier::default());
+                        context.keep_count();
                     } else {
                         let thread_hash = self.get_thread_under_cursor(self.cursor_pos.2);
                         self.selection.entry(thread_hash).and_modify(|e| *e = !*e);
@@ -1584,37 +2004,41 @@ impl Component for ConversationsListing {
                     }
                     Action::Sort(field, order) if !self.unfocused => {
                         debug!("Sort {:?} , {:?}", field, order);
-                        // FIXME sort
-                        /*
                         self.sort = (*field, *order);
                         if !self.filtered_selection.is_empty() {
-                            let threads = &context.accounts[&self.cursor_pos.0].collection.threads
-                                [&self.cursor_pos.1];
-                            threads.vec_inner_sort_by(
-                                &mut self.filtered_selection,
-                                self.sort,
-                                &context.accounts[&self.cursor_pos.0].collection.envelopes,
-                            );
+                            // FIXME: perform sort
                             self.dirty = true;
                         } else {
                             self.refresh_mailbox(context, false);
                         }
-                            */
                         return true;
                     }
                     Action::Listing(ToggleThreadSnooze) if !self.unfocused => {
                         let thread = self.get_thread_under_cursor(self.cursor_pos.2);
-                        let account = &mut context.accounts[&self.cursor_pos.0];
-                        account
-                            .collection
-                            .threads
-                            .write()
-                            .unwrap()
-                            .entry(self.cursor_pos.1)
-                            .and_modify(|threads| {
-                                let is_snoozed = threads.thread_ref(thread).snoozed();
-                                threads.thread_ref_mut(thread).set_snoozed(!is_snoozed);
-                            });
+                        if let Err(err) = context.accounts[&self.cursor_pos.0]
+                            .toggle_thread_mute(self.cursor_pos.1, thread)
+                        {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not save muted thread.".to_string()),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            ));
+                        }
+                        self.row_updates.push(thread);
+                        self.refresh_mailbox(context, false);
+                        return true;
+                    }
+                    Action::Listing(ToggleThreadWatch) if !self.unfocused => {
+                        let thread = self.get_thread_under_cursor(self.cursor_pos.2);
+                        if let Err(err) = context.accounts[&self.cursor_pos.0]
+                            .toggle_thread_watch(self.cursor_pos.1, thread)
+                        {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not save watched thread.".to_string()),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            ));
+                        }
                         self.row_updates.push(thread);
                         self.refresh_mailbox(context, false);
                         return true;
@@ -1643,6 +2067,7 @@ impl Component for ConversationsListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    date_separator: crate::conf::value(context, "mail.listing.date_separator"),
                     ..self.color_cache
                 };
 
@@ -1693,6 +2118,33 @@ impl Component for ConversationsListing {
                     self.set_dirty(true);
                     return true;
                 }
+                Action::Listing(Select(ref search_term)) if !self.unfocused => {
+                    match context.accounts[&self.cursor_pos.0].search(
+                        search_term,
+                        self.sort,
+                        self.cursor_pos.1,
+                    ) {
+                        Ok(job) => {
+                            let mut handle = context.accounts[&self.cursor_pos.0]
+                                .job_executor
+                                .spawn_specialized(job);
+                            if let Ok(Some(search_result)) = try_recv_timeout!(&mut handle.chan) {
+                                self.select(search_term, search_result, context);
+                            } else {
+                                self.select_job = Some((search_term.to_string(), handle));
+                            }
+                        }
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not perform search".to_string()),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            ));
+                        }
+                    };
+                    self.set_dirty(true);
+                    return true;
+                }
                 _ => {}
             },
             UIEvent::Input(Key::Esc)
@@ -1731,6 +2183,21 @@ impl Component for ConversationsListing {
                 }
                 self.set_dirty(true);
             }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .select_job
+                    .as_ref()
+                    .map(|(_, j)| j == job_id)
+                    .unwrap_or(false) =>
+            {
+                let (search_term, mut handle) = self.select_job.take().unwrap();
+                match handle.chan.try_recv() {
+                    Err(_) => { /* search was canceled */ }
+                    Ok(None) => { /* something happened, perhaps a worker thread panicked */ }
+                    Ok(Some(results)) => self.select(&search_term, results, context),
+                }
+                self.set_dirty(true);
+            }
             _ => {}
         }
 
@@ -112,7 +112,17 @@ impl Component for OfflineListing {
         self.dirty = false;
         let theme_default = conf::value(context, "theme_default");
         clear_area(grid, area, theme_default);
-        if let Err(err) = context.is_online(self.cursor_pos.0) {
+        if context.accounts[&self.cursor_pos.0].is_connecting() {
+            write_string_to_grid(
+                "connecting…",
+                grid,
+                Color::Byte(243),
+                theme_default.bg,
+                theme_default.attrs,
+                area,
+                None,
+            );
+        } else if let Err(err) = context.is_online(self.cursor_pos.0) {
             let (x, _) = write_string_to_grid(
                 "offline: ",
                 grid,
@@ -132,8 +132,11 @@ pub struct PlainListing {
     order: HashMap<EnvelopeHash, usize>,
     /// Cache current view.
     data_columns: DataColumns,
+    separators: DateSeparators,
+    dates: EntryDates,
 
     search_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
+    select_job: Option<(String, JoinHandle<Result<SmallVec<[EnvelopeHash; 512]>>>)>,
     filter_term: String,
     filtered_selection: Vec<EnvelopeHash>,
     filtered_order: HashMap<EnvelopeHash, usize>,
@@ -147,9 +150,11 @@ pub struct PlainListing {
     /// If `self.view` exists or not.
     unfocused: bool,
     view: MailView,
+    preview_pane: PreviewPane,
     row_updates: SmallVec<[EnvelopeHash; 8]>,
     _row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    flags: FlagIndicators,
 
     active_jobs: HashMap<JobId, JoinHandle<Result<()>>>,
     movement: Option<PageMovement>,
@@ -165,6 +170,21 @@ impl MailListingTrait for PlainListing {
         &mut self._selection
     }
 
+    fn change_selection(&mut self, change: SelectionChange) -> usize {
+        for (k, v) in self.selection.iter_mut() {
+            let new_val = match change {
+                SelectionChange::All => true,
+                SelectionChange::None => false,
+                SelectionChange::Invert => !*v,
+            };
+            if *v != new_val {
+                *v = new_val;
+                self.row_updates.push(*k);
+            }
+        }
+        self.selection.values().filter(|v| **v).count()
+    }
+
     fn get_focused_items(&self, _context: &Context) -> SmallVec<[ThreadHash; 8]> {
         SmallVec::new()
         /*
@@ -209,9 +229,11 @@ impl MailListingTrait for PlainListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            date_separator: crate::conf::value(context, "mail.listing.date_separator"),
             theme_default: crate::conf::value(context, "theme_default"),
             ..self.color_cache
         };
+        self.flags = FlagIndicators::new(context, self.cursor_pos.0, self.cursor_pos.1);
         if !context.settings.terminal.use_color() {
             self.color_cache.highlighted.attrs |= Attr::REVERSE;
             self.color_cache.tag_default.attrs |= Attr::REVERSE;
@@ -225,7 +247,7 @@ impl MailListingTrait for PlainListing {
             Ok(()) => {}
             Err(_) => {
                 let message: String =
-                    context.accounts[&self.cursor_pos.0][&self.cursor_pos.1].status();
+                    super::mailbox_status(context, self.cursor_pos.0, self.cursor_pos.1);
                 self.data_columns.columns[0] =
                     CellBuffer::new_with_context(message.len(), 1, None, context);
                 self.length = 0;
@@ -259,26 +281,14 @@ impl MailListingTrait for PlainListing {
             .map(|h| (*h, env_lck[h].thread()))
             .collect();
         let sort = self.sort;
-        self.local_collection.sort_by(|a, b| match sort {
-            (SortField::Date, SortOrder::Desc) => {
-                let ma = &env_lck[a];
-                let mb = &env_lck[b];
-                mb.date().cmp(&ma.date())
-            }
-            (SortField::Date, SortOrder::Asc) => {
-                let ma = &env_lck[a];
-                let mb = &env_lck[b];
-                ma.date().cmp(&mb.date())
-            }
-            (SortField::Subject, SortOrder::Desc) => {
-                let ma = &env_lck[a];
-                let mb = &env_lck[b];
-                ma.subject().cmp(&mb.subject())
-            }
-            (SortField::Subject, SortOrder::Asc) => {
-                let ma = &env_lck[a];
-                let mb = &env_lck[b];
-                mb.subject().cmp(&ma.subject())
+        let collator = collator(context, self.cursor_pos.0, self.cursor_pos.1);
+        self.local_collection.sort_by(|a, b| {
+            let ret = sort.0.compare(&env_lck[a], &env_lck[b], &collator);
+            /* Dates are newest first in descending order, subjects and senders are A to Z. */
+            match sort {
+                (SortField::Date, SortOrder::Desc) => ret.reverse(),
+                (SortField::Date, SortOrder::Asc) | (_, SortOrder::Desc) => ret,
+                (_, SortOrder::Asc) => ret.reverse(),
             }
         });
         for &env_hash in &self.local_collection {
@@ -332,6 +342,22 @@ impl ListingTrait for PlainListing {
         (self.new_cursor_pos.0, self.new_cursor_pos.1)
     }
 
+    fn preview_pane_mut(&mut self) -> Option<&mut PreviewPane> {
+        Some(&mut self.preview_pane)
+    }
+
+    fn selected_count(&self) -> usize {
+        self.selection.values().filter(|v| **v).count()
+    }
+
+    fn selected_envelopes(&self, _context: &Context) -> Vec<EnvelopeHash> {
+        self.selection
+            .iter()
+            .filter(|(_, v)| **v)
+            .map(|(env_hash, _)| *env_hash)
+            .collect()
+    }
+
     fn set_coordinates(&mut self, coordinates: (AccountHash, MailboxHash)) {
         self.new_cursor_pos = (coordinates.0, coordinates.1, 0);
         self.unfocused = false;
@@ -447,10 +473,13 @@ impl ListingTrait for PlainListing {
             }
         }
 
-        let prev_page_no = (self.cursor_pos.2).wrapping_div(rows);
-        let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
+        let prev_page_no = self.separators.slot(self.cursor_pos.2).wrapping_div(rows);
+        let page_no = self
+            .separators
+            .slot(self.new_cursor_pos.2)
+            .wrapping_div(rows);
 
-        let top_idx = page_no * rows;
+        let top_slot = page_no * rows;
 
         /* If cursor position has changed, remove the highlight from the previous position and
          * apply it in the new one. */
@@ -461,10 +490,8 @@ impl ListingTrait for PlainListing {
                 if *idx >= self.length {
                     continue; //bounds check
                 }
-                let new_area = (
-                    set_y(upper_left, get_y(upper_left) + (*idx % rows)),
-                    set_y(bottom_right, get_y(upper_left) + (*idx % rows)),
-                );
+                let y = get_y(upper_left) + self.separators.slot(*idx) % rows;
+                let new_area = (set_y(upper_left, y), set_y(bottom_right, y));
                 self.highlight_line(grid, new_area, *idx, context);
                 context.dirty_areas.push_back(new_area);
             }
@@ -484,7 +511,14 @@ impl ListingTrait for PlainListing {
         self.data_columns.widths[2] = self.data_columns.columns[2].size().0; /* from */
         self.data_columns.widths[3] = self.data_columns.columns[3].size().0; /* flags */
         self.data_columns.widths[4] = self.data_columns.columns[4].size().0; /* subject */
+        self.data_columns.widths[5] = self.data_columns.columns[5].size().0; /* size */
 
+        /* The size column is kept whole at the end of the line */
+        let width = if self.data_columns.widths[5] > 0 {
+            width.saturating_sub(self.data_columns.widths[5] + 2)
+        } else {
+            width
+        };
         let min_col_width = std::cmp::min(
             15,
             std::cmp::min(self.data_columns.widths[4], self.data_columns.widths[2]),
@@ -511,6 +545,7 @@ impl ListingTrait for PlainListing {
             }
         }
         clear_area(grid, area, self.color_cache.theme_default);
+        let (runs, separators) = self.separators.page(top_slot, rows, self.length);
         /* Page_no has changed, so draw new page */
         let mut x = get_x(upper_left);
         let mut flag_x = 0;
@@ -522,29 +557,34 @@ impl ListingTrait for PlainListing {
             if self.data_columns.widths[i] == 0 {
                 continue;
             }
-            copy_area(
-                grid,
-                &self.data_columns.columns[i],
-                (
-                    set_x(upper_left, x),
-                    set_x(
-                        bottom_right,
-                        std::cmp::min(get_x(bottom_right), x + (self.data_columns.widths[i])),
+            for (row, entries) in &runs {
+                copy_area(
+                    grid,
+                    &self.data_columns.columns[i],
+                    (
+                        (x, get_y(upper_left) + row),
+                        set_x(
+                            bottom_right,
+                            std::cmp::min(get_x(bottom_right), x + (self.data_columns.widths[i])),
+                        ),
                     ),
-                ),
-                (
-                    (0, top_idx),
-                    (column_width.saturating_sub(1), self.length - 1),
-                ),
-            );
+                    (
+                        (0, entries.start),
+                        (column_width.saturating_sub(1), entries.end - 1),
+                    ),
+                );
+            }
             x += self.data_columns.widths[i] + 2; // + SEPARATOR
             if x > get_x(bottom_right) {
                 break;
             }
         }
-        for r in 0..cmp::min(self.length - top_idx, rows) {
+        for (r, idx) in runs
+            .iter()
+            .flat_map(|(row, entries)| (*row..).zip(entries.clone()))
+        {
             let (fg_color, bg_color) = {
-                let c = &self.data_columns.columns[0][(0, r + top_idx)];
+                let c = &self.data_columns.columns[0][(0, idx)];
                 (c.fg(), c.bg())
             };
             change_colors(
@@ -580,23 +620,29 @@ impl ListingTrait for PlainListing {
             );
         }
 
+        for (r, label) in separators {
+            draw_date_separator(
+                grid,
+                area,
+                get_y(upper_left) + r,
+                &label,
+                self.color_cache.date_separator,
+            );
+        }
+
+        let y = get_y(upper_left) + self.separators.slot(self.cursor_pos.2) % rows;
         self.highlight_line(
             grid,
-            (
-                set_y(upper_left, get_y(upper_left) + (self.cursor_pos.2 % rows)),
-                set_y(bottom_right, get_y(upper_left) + (self.cursor_pos.2 % rows)),
-            ),
+            (set_y(upper_left, y), set_y(bottom_right, y)),
             self.cursor_pos.2,
             context,
         );
 
-        if top_idx + rows > self.length {
+        let slots = self.separators.len(self.length);
+        if top_slot + rows > slots {
             clear_area(
                 grid,
-                (
-                    pos_inc(upper_left, (0, self.length - top_idx)),
-                    bottom_right,
-                ),
+                (pos_inc(upper_left, (0, slots - top_slot)), bottom_right),
                 self.color_cache.theme_default,
             );
         }
@@ -660,7 +706,8 @@ impl ListingTrait for PlainListing {
                     "Encountered an error while searching for `{}`: {}.",
                     &self.filter_term, e
                 );
-                log(
+                log_target(
+                    "ui",
                     format!("Failed to search for term {}: {}", &self.filter_term, e),
                     ERROR,
                 );
@@ -683,10 +730,118 @@ impl ListingTrait for PlainListing {
         self.unfocused
     }
 
+    fn exit_entry(&mut self, context: &mut Context) {
+        self.unfocused = false;
+        self.view
+            .process_event(&mut UIEvent::VisibilityChange(false), context);
+        self.dirty = true;
+        /* If self.row_updates is not empty and we exit a thread, the row_update events
+         * will be performed but the list will not be drawn. So force a draw in any case.
+         * */
+        self.force_draw = true;
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn save_listing_session(&self, session: &mut ListingSession) {
+        session.cursor = self.cursor_pos.2;
+        session.sort = Some(self.sort);
+        if !self.filter_term.is_empty() {
+            session.filter = Some(self.filter_term.clone());
+        }
+    }
+
+    fn dated_entries(&self, range: (UnixTimestamp, UnixTimestamp)) -> Option<Vec<EnvelopeHash>> {
+        Some(self.dates.range(range))
+    }
+
+    fn envelope_under_cursor(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0
+            || self.cursor_pos.1 != self.new_cursor_pos.1
+            || self.cursor_pos.2 >= self.length
+        {
+            return None;
+        }
+        Some(self.get_env_under_cursor(self.cursor_pos.2, context))
+    }
+
+    fn select_thread_node(&mut self, thread_node: ThreadNodeHash, context: &Context) -> bool {
+        let env_hash = match context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1)
+            .thread_nodes()
+            .get(&thread_node)
+            .and_then(|node| node.message())
+        {
+            Some(env_hash) => env_hash,
+            None => return false,
+        };
+        let idx = if self.filter_term.is_empty() {
+            self.order.get(&env_hash)
+        } else {
+            self.filtered_order.get(&env_hash)
+        };
+        if let Some(&idx) = idx {
+            self.new_cursor_pos.2 = idx;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn select_unseen(&mut self, from_start: bool, wrap: bool, context: &mut Context) -> bool {
+        let cursor = if from_start {
+            None
+        } else {
+            Some(self.new_cursor_pos.2)
+        };
+        let idx = {
+            let account = &context.accounts[&self.cursor_pos.0];
+            let is_unseen = |env_hash: &EnvelopeHash| {
+                account.contains_key(*env_hash) && !account.collection.get_env(*env_hash).is_seen()
+            };
+            if self.filter_term.is_empty() {
+                next_unseen_idx(
+                    self.order
+                        .iter()
+                        .filter(|(env_hash, _)| is_unseen(env_hash))
+                        .map(|(_, idx)| *idx),
+                    cursor,
+                    wrap,
+                )
+            } else {
+                next_unseen_idx(
+                    self.filtered_selection
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, env_hash)| is_unseen(env_hash))
+                        .map(|(idx, _)| idx),
+                    cursor,
+                    wrap,
+                )
+            }
+        };
+        let idx = match idx {
+            Some(idx) => idx,
+            None => return false,
+        };
+        self.new_cursor_pos.2 = idx;
+        if self.unfocused {
+            let env_hash = self.get_env_under_cursor(idx, context);
+            self.view = MailView::new(
+                (self.cursor_pos.0, self.cursor_pos.1, env_hash),
+                None,
+                None,
+                context,
+            );
+        }
+        self.set_dirty(true);
+        true
+    }
 }
 
 impl fmt::Display for PlainListing {
@@ -710,6 +865,7 @@ impl PlainListing {
             order: HashMap::default(),
             filter_term: String::new(),
             search_job: None,
+            select_job: None,
             filtered_selection: Vec::new(),
             filtered_order: HashMap::default(),
             selection: HashMap::default(),
@@ -717,11 +873,15 @@ impl PlainListing {
             row_updates: SmallVec::new(),
             _row_updates: SmallVec::new(),
             data_columns: DataColumns::default(),
+            separators: DateSeparators::default(),
+            dates: EntryDates::default(),
             dirty: true,
             force_draw: true,
             unfocused: false,
             view: MailView::default(),
+            preview_pane: PreviewPane::new(false, PreviewPaneOrientation::Vertical),
             color_cache: ColorCache::default(),
+            flags: FlagIndicators::default(),
             active_jobs: HashMap::default(),
 
             movement: None,
@@ -767,14 +927,22 @@ impl PlainListing {
                 tags.pop();
             }
         }
-        let mut subject = e.subject().to_string();
-        subject.truncate_at_boundary(150);
+        let subject = e.subject().trim_with_ellipsis(150).into_owned();
         EntryStrings {
             date: DateString(PlainListing::format_date(&e)),
             subject: SubjectString(subject),
-            flag: FlagString(format!("{}", if e.has_attachments() { "📎" } else { "" },)),
+            flag: self.flags.envelope(&e, account.notes.contains(&e)),
             from: FromString(address_list!((e.from()) as comma_sep_list)),
             tags: TagString(tags, colors),
+            size: if *mailbox_settings!(
+                context[self.cursor_pos.0][&self.cursor_pos.1]
+                    .listing
+                    .show_size
+            ) {
+                SizeString::new(e.size())
+            } else {
+                SizeString(String::new())
+            },
         }
     }
 
@@ -785,8 +953,11 @@ impl PlainListing {
         self.order.clear();
         self.selection.clear();
         self.length = 0;
+        self.separators
+            .reset(context, (self.cursor_pos.0, self.cursor_pos.1), self.sort.0);
+        self.dates.clear();
         let mut rows = Vec::with_capacity(1024);
-        let mut min_width = (0, 0, 0, 0, 0);
+        let mut min_width = (0, 0, 0, 0, 0, 0);
 
         for i in iter {
             if !context.accounts[&self.cursor_pos.0].contains_key(i) {
@@ -814,6 +985,8 @@ impl PlainListing {
                 }
             }
 
+            self.separators.push(envelope.datetime());
+            self.dates.push(envelope.datetime(), i);
             let entry_strings = self.make_entry_string(envelope, context);
             min_width.1 = cmp::max(min_width.1, entry_strings.date.grapheme_width()); /* date */
             min_width.2 = cmp::max(min_width.2, entry_strings.from.grapheme_width()); /* from */
@@ -822,6 +995,7 @@ impl PlainListing {
                 min_width.4,
                 entry_strings.subject.grapheme_width() + 1 + entry_strings.tags.grapheme_width(),
             ); /* tags + subject */
+            min_width.5 = cmp::max(min_width.5, entry_strings.size.grapheme_width()); /* size */
             rows.push(entry_strings);
 
             self.order.insert(i, self.length);
@@ -846,6 +1020,9 @@ impl PlainListing {
         /* subject column */
         self.data_columns.columns[4] =
             CellBuffer::new_with_context(min_width.4, rows.len(), None, context);
+        /* size column */
+        self.data_columns.columns[5] =
+            CellBuffer::new_with_context(min_width.5, rows.len(), None, context);
 
         let iter = if self.filter_term.is_empty() {
             Box::new(self.local_collection.iter().cloned())
@@ -967,6 +1144,15 @@ impl PlainListing {
             for c in columns[4].row_iter(x..min_width.4, idx) {
                 columns[4][c].set_bg(row_attr.bg).set_attrs(row_attr.attrs);
             }
+            write_string_to_grid(
+                &format!("{:>1$}", strings.size.as_str(), min_width.5),
+                &mut columns[5],
+                row_attr.fg,
+                row_attr.bg,
+                row_attr.attrs,
+                ((0, idx), (min_width.5, idx)),
+                None,
+            );
             if context.accounts[&self.cursor_pos.0]
                 .collection
                 .get_env(i)
@@ -1016,6 +1202,23 @@ impl PlainListing {
 
     fn perform_action(&mut self, context: &mut Context, env_hash: EnvelopeHash, a: &ListingAction) {
         let account = &mut context.accounts[&self.cursor_pos.0];
+        if let Err(err) = account.check_writable() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    err.to_string(),
+                )));
+            return;
+        }
+        match a {
+            ListingAction::SetSeen => {
+                account.record_action(Some(env_hash), self.cursor_pos.1, "mark seen")
+            }
+            ListingAction::SetUnseen => {
+                account.record_action(Some(env_hash), self.cursor_pos.1, "mark unseen")
+            }
+            _ => {}
+        }
         match {
             match a {
                 ListingAction::SetSeen => account.backend.write().unwrap().set_flags(
@@ -1049,76 +1252,127 @@ impl PlainListing {
         }
         self.row_updates.push(env_hash);
     }
+
+    fn select(
+        &mut self,
+        search_term: &str,
+        results: Result<SmallVec<[EnvelopeHash; 512]>>,
+        context: &mut Context,
+    ) {
+        match results {
+            Ok(results) => {
+                for env_hash in results {
+                    if let Some(entry) = self.selection.get_mut(&env_hash) {
+                        *entry = true;
+                        self.row_updates.push(env_hash);
+                    }
+                }
+            }
+            Err(err) => {
+                let message = format!(
+                    "Encountered an error while searching for `{}`: {}.",
+                    search_term, &err
+                );
+                log_target("ui", message.clone(), ERROR);
+                context.replies.push_back(UIEvent::Notification(
+                    Some("Could not perform search".to_string()),
+                    message,
+                    Some(crate::types::NotificationType::Error(err.kind)),
+                ));
+                return;
+            }
+        }
+        let selected = self.selection.values().filter(|v| **v).count();
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                "{} selected.",
+                selected
+            ))));
+    }
 }
 
 impl Component for PlainListing {
     fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
-        if !self.unfocused {
-            if !self.is_dirty() {
+        let mut area = area;
+        if self.unfocused {
+            if self.length == 0 && self.dirty {
+                clear_area(grid, area, self.color_cache.theme_default);
+                context.dirty_areas.push_back(area);
                 return;
             }
-            let mut area = area;
-            if !self.filter_term.is_empty() {
-                let (upper_left, bottom_right) = area;
-                let (x, y) = write_string_to_grid(
-                    &format!(
-                        "{} results for `{}` (Press ESC to exit)",
-                        self.filtered_selection.len(),
-                        self.filter_term
-                    ),
-                    grid,
-                    self.color_cache.theme_default.fg,
-                    self.color_cache.theme_default.bg,
-                    self.color_cache.theme_default.attrs,
-                    area,
-                    Some(get_x(upper_left)),
-                );
-                clear_area(
-                    grid,
-                    ((x, y), set_y(bottom_right, y)),
-                    self.color_cache.theme_default,
-                );
-                context
-                    .dirty_areas
-                    .push_back((upper_left, set_y(bottom_right, y + 1)));
-
-                area = (set_y(upper_left, y + 1), bottom_right);
+            let coordinates = (self.cursor_pos.0, self.cursor_pos.1);
+            if let Some((list_area, preview_area)) =
+                self.preview_pane.split(grid, area, context, coordinates)
+            {
+                self.view.draw(grid, preview_area, context);
+                if !self.dirty {
+                    return;
+                }
+                area = list_area;
+            } else {
+                self.view.draw(grid, area, context);
+                self.dirty = false;
+                return;
             }
+        } else if !self.is_dirty() {
+            return;
+        }
+        if !self.filter_term.is_empty() {
+            let (upper_left, bottom_right) = area;
+            let (x, y) = write_string_to_grid(
+                &search_results_header(
+                    context,
+                    self.new_cursor_pos.0,
+                    &self.filter_term,
+                    self.filtered_selection.len(),
+                ),
+                grid,
+                self.color_cache.theme_default.fg,
+                self.color_cache.theme_default.bg,
+                self.color_cache.theme_default.attrs,
+                area,
+                Some(get_x(upper_left)),
+            );
+            clear_area(
+                grid,
+                ((x, y), set_y(bottom_right, y)),
+                self.color_cache.theme_default,
+            );
+            context
+                .dirty_areas
+                .push_back((upper_left, set_y(bottom_right, y + 1)));
 
-            if !self.row_updates.is_empty() {
-                let (upper_left, bottom_right) = area;
-                while let Some(row) = self.row_updates.pop() {
-                    let row: usize = self.order[&row];
-                    let rows = get_y(bottom_right) - get_y(upper_left) + 1;
-                    let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
-
-                    let top_idx = page_no * rows;
-                    if row >= top_idx && row <= top_idx + rows {
-                        let area = (
-                            set_y(upper_left, get_y(upper_left) + (row % rows)),
-                            set_y(bottom_right, get_y(upper_left) + (row % rows)),
-                        );
-                        self.highlight_line(grid, area, row, context);
-                        context.dirty_areas.push_back(area);
-                    }
-                }
-                if self.force_draw {
-                    /* Draw the entire list */
-                    self.draw_list(grid, area, context);
-                    self.force_draw = false;
+            area = (set_y(upper_left, y + 1), bottom_right);
+        }
+
+        if !self.row_updates.is_empty() {
+            let (upper_left, bottom_right) = area;
+            while let Some(row) = self.row_updates.pop() {
+                let row: usize = self.order[&row];
+                let slot = self.separators.slot(row);
+                let rows = get_y(bottom_right) - get_y(upper_left) + 1;
+                let page_no = self
+                    .separators
+                    .slot(self.new_cursor_pos.2)
+                    .wrapping_div(rows);
+
+                let top_slot = page_no * rows;
+                if slot >= top_slot && slot < top_slot + rows {
+                    let y = get_y(upper_left) + (slot % rows);
+                    let area = (set_y(upper_left, y), set_y(bottom_right, y));
+                    self.highlight_line(grid, area, row, context);
+                    context.dirty_areas.push_back(area);
                 }
-            } else {
+            }
+            if self.force_draw {
                 /* Draw the entire list */
                 self.draw_list(grid, area, context);
+                self.force_draw = false;
             }
         } else {
-            if self.length == 0 && self.dirty {
-                clear_area(grid, area, self.color_cache.theme_default);
-                context.dirty_areas.push_back(area);
-                return;
-            }
-
-            self.view.draw(grid, area, context);
+            /* Draw the entire list */
+            self.draw_list(grid, area, context);
         }
         self.dirty = false;
     }
@@ -1145,14 +1399,7 @@ impl Component for PlainListing {
                     if self.unfocused
                         && shortcut!(k == shortcuts[PlainListing::DESCRIPTION]["exit_thread"]) =>
                 {
-                    self.unfocused = false;
-                    self.view
-                        .process_event(&mut UIEvent::VisibilityChange(false), context);
-                    self.dirty = true;
-                    /* If self.row_updates is not empty and we exit a thread, the row_update events
-                     * will be performed but the list will not be drawn. So force a draw in any case.
-                     * */
-                    self.force_draw = true;
+                    self.exit_entry(context);
                     return true;
                 }
                 UIEvent::Input(ref key)
@@ -1177,6 +1424,10 @@ impl Component for PlainListing {
                     Action::Sort(field, order) if !self.unfocused => {
                         debug!("Sort {:?} , {:?}", field, order);
                         self.sort = (*field, *order);
+                        if self.filter_term.is_empty() {
+                            self.refresh_mailbox(context, false);
+                        }
+                        self.set_dirty(true);
                         return true;
                     }
                     Action::Listing(a @ ListingAction::SetSeen)
@@ -1239,6 +1490,7 @@ impl Component for PlainListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    date_separator: crate::conf::value(context, "mail.listing.date_separator"),
                     theme_default: crate::conf::value(context, "theme_default"),
                     ..self.color_cache
                 };
@@ -1278,12 +1530,18 @@ impl Component for PlainListing {
                     self.order.insert(*new_hash, row);
                     let selection_status = self.selection.remove(old_hash).unwrap();
                     self.selection.insert(*new_hash, selection_status);
-                    for h in self.filtered_selection.iter_mut() {
+                    for h in self
+                        .filtered_selection
+                        .iter_mut()
+                        .chain(self.local_collection.iter_mut())
+                    {
                         if *h == *old_hash {
                             *h = *new_hash;
-                            break;
                         }
                     }
+                    if self.all_envelopes.remove(old_hash) {
+                        self.all_envelopes.insert(*new_hash);
+                    }
                 }
 
                 self.dirty = true;
@@ -1371,6 +1629,48 @@ impl Component for PlainListing {
                 }
                 self.set_dirty(true);
             }
+            UIEvent::Action(Action::Listing(Select(ref search_term))) if !self.unfocused => {
+                match context.accounts[&self.cursor_pos.0].search(
+                    search_term,
+                    self.sort,
+                    self.cursor_pos.1,
+                ) {
+                    Ok(job) => {
+                        let mut handle = context.accounts[&self.cursor_pos.0]
+                            .job_executor
+                            .spawn_specialized(job);
+                        if let Ok(Some(search_result)) = try_recv_timeout!(&mut handle.chan) {
+                            self.select(search_term, search_result, context);
+                        } else {
+                            self.select_job = Some((search_term.to_string(), handle));
+                        }
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not perform search".to_string()),
+                            err.to_string(),
+                            Some(crate::types::NotificationType::Error(err.kind)),
+                        ));
+                    }
+                };
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .select_job
+                    .as_ref()
+                    .map(|(_, j)| j == job_id)
+                    .unwrap_or(false) =>
+            {
+                let (search_term, mut handle) = self.select_job.take().unwrap();
+                match handle.chan.try_recv() {
+                    Err(_) => { /* search was canceled */ }
+                    Ok(None) => { /* something happened, perhaps a worker thread panicked */ }
+                    Ok(Some(results)) => self.select(&search_term, results, context),
+                }
+                self.set_dirty(true);
+            }
             _ => {}
         }
         false
@@ -112,6 +112,7 @@ pub struct ThreadListing {
     subsort: (SortField, SortOrder),
     /// Cache current view.
     color_cache: ColorCache,
+    flags: FlagIndicators,
 
     data_columns: DataColumns,
     rows_drawn: SegmentTree,
@@ -125,6 +126,7 @@ pub struct ThreadListing {
     unfocused: bool,
     initialised: bool,
     view: Option<MailView>,
+    preview_pane: PreviewPane,
     movement: Option<PageMovement>,
     id: ComponentId,
 }
@@ -170,6 +172,7 @@ impl MailListingTrait for ThreadListing {
             theme_default: crate::conf::value(context, "theme_default"),
             ..self.color_cache
         };
+        self.flags = FlagIndicators::new(context, self.cursor_pos.0, self.cursor_pos.1);
         if !context.settings.terminal.use_color() {
             self.color_cache.highlighted.attrs |= Attr::REVERSE;
             self.color_cache.tag_default.attrs |= Attr::REVERSE;
@@ -183,7 +186,7 @@ impl MailListingTrait for ThreadListing {
             Ok(_) => {}
             Err(_) => {
                 let message: String =
-                    context.accounts[&self.cursor_pos.0][&self.cursor_pos.1].status();
+                    super::mailbox_status(context, self.cursor_pos.0, self.cursor_pos.1);
                 self.data_columns.columns[0] =
                     CellBuffer::new_with_context(message.len(), 1, None, context);
                 self.length = 0;
@@ -207,6 +210,7 @@ impl MailListingTrait for ThreadListing {
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
+            &collator(context, self.cursor_pos.0, self.cursor_pos.1),
         );
 
         self.redraw_threads_list(
@@ -403,6 +407,10 @@ impl ListingTrait for ThreadListing {
     fn coordinates(&self) -> (AccountHash, MailboxHash) {
         (self.new_cursor_pos.0, self.new_cursor_pos.1)
     }
+
+    fn preview_pane_mut(&mut self) -> Option<&mut PreviewPane> {
+        Some(&mut self.preview_pane)
+    }
     fn set_coordinates(&mut self, coordinates: (AccountHash, MailboxHash)) {
         self.new_cursor_pos = (coordinates.0, coordinates.1, 0);
         self.unfocused = false;
@@ -728,6 +736,71 @@ impl ListingTrait for ThreadListing {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn save_listing_session(&self, session: &mut ListingSession) {
+        session.cursor = self.cursor_pos.2;
+        session.sort = Some(self.sort);
+    }
+
+    fn envelope_under_cursor(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0
+            || self.cursor_pos.1 != self.new_cursor_pos.1
+            || self.cursor_pos.2 >= self.length
+        {
+            return None;
+        }
+        Some(self.get_env_under_cursor(self.cursor_pos.2, context))
+    }
+
+    fn select_thread_node(&mut self, thread_node: ThreadNodeHash, context: &Context) -> bool {
+        let env_hash = match context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1)
+            .thread_nodes()
+            .get(&thread_node)
+            .and_then(|node| node.message())
+        {
+            Some(env_hash) => env_hash,
+            None => return false,
+        };
+        let idx = self.order.get(&env_hash);
+        if let Some(&idx) = idx {
+            self.new_cursor_pos.2 = idx;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn select_unseen(&mut self, from_start: bool, wrap: bool, context: &mut Context) -> bool {
+        let cursor = if from_start {
+            None
+        } else {
+            Some(self.new_cursor_pos.2)
+        };
+        let idx = {
+            let account = &context.accounts[&self.cursor_pos.0];
+            let is_unseen = |env_hash: &EnvelopeHash| {
+                account.contains_key(*env_hash) && !account.collection.get_env(*env_hash).is_seen()
+            };
+            next_unseen_idx(
+                self.order
+                    .iter()
+                    .filter(|(env_hash, _)| is_unseen(env_hash))
+                    .map(|(_, idx)| *idx),
+                cursor,
+                wrap,
+            )
+        };
+        let idx = match idx {
+            Some(idx) => idx,
+            None => return false,
+        };
+        self.new_cursor_pos.2 = idx;
+        self.set_dirty(true);
+        true
+    }
 }
 
 impl fmt::Display for ThreadListing {
@@ -745,6 +818,7 @@ impl ThreadListing {
             sort: (Default::default(), Default::default()),
             subsort: (Default::default(), Default::default()),
             color_cache: ColorCache::default(),
+            flags: FlagIndicators::default(),
             data_columns: DataColumns::default(),
             rows_drawn: SegmentTree::default(),
             rows: vec![],
@@ -754,6 +828,7 @@ impl ThreadListing {
             dirty: true,
             unfocused: false,
             view: None,
+            preview_pane: PreviewPane::new(true, PreviewPaneOrientation::Horizontal),
             initialised: false,
             movement: None,
             id: ComponentId::new_v4(),
@@ -874,14 +949,14 @@ impl ThreadListing {
                 tags.pop();
             }
         }
-        let mut subject = e.subject().to_string();
-        subject.truncate_at_boundary(150);
+        let subject = e.subject().trim_with_ellipsis(150).into_owned();
         EntryStrings {
             date: DateString(ConversationsListing::format_date(context, e.date())),
             subject: SubjectString(subject),
-            flag: FlagString(format!("{}", if e.has_attachments() { "📎" } else { "" },)),
+            flag: self.flags.envelope(e, account.notes.contains(e)),
             from: FromString(address_list!((e.from()) as comma_sep_list)),
             tags: TagString(tags, colors),
+            size: SizeString(String::new()),
         }
     }
 
@@ -1080,30 +1155,12 @@ impl Component for ThreadListing {
             self.draw_list(grid, area, context);
         } else {
             self.cursor_pos = self.new_cursor_pos;
-            let upper_left = upper_left!(area);
-            let bottom_right = bottom_right!(area);
             if self.length == 0 && self.dirty {
                 clear_area(grid, area, self.color_cache.theme_default);
                 context.dirty_areas.push_back(area);
                 return;
             }
 
-            /* Render the mail body in a pager, basically copy what HSplit does */
-            let total_rows = get_y(bottom_right) - get_y(upper_left);
-            let pager_ratio = *mailbox_settings!(
-                context[self.cursor_pos.0][&self.cursor_pos.1]
-                    .pager
-                    .pager_ratio
-            );
-
-            let bottom_entity_rows = (pager_ratio * total_rows) / 100;
-
-            if bottom_entity_rows > total_rows {
-                clear_area(grid, area, self.color_cache.theme_default);
-                context.dirty_areas.push_back(area);
-                return;
-            }
-
             let idx = self.cursor_pos.2;
 
             /* Mark message as read */
@@ -1123,37 +1180,24 @@ impl Component for ThreadListing {
                 self.highlight_line_self(idx, context);
             }
 
-            let mid = get_y(upper_left) + total_rows - bottom_entity_rows;
-            self.draw_list(
-                grid,
-                (
-                    upper_left,
-                    (get_x(bottom_right), get_y(upper_left) + mid - 1),
-                ),
-                context,
-            );
+            let preview_area = if let Some((list_area, preview_area)) =
+                self.preview_pane
+                    .split(grid, area, context, (self.cursor_pos.0, self.cursor_pos.1))
+            {
+                self.draw_list(grid, list_area, context);
+                preview_area
+            } else {
+                area
+            };
             if self.length == 0 {
                 self.dirty = false;
                 return;
             }
-            {
-                if get_x(upper_left) > 0 && grid[(get_x(upper_left) - 1, mid)].ch() == VERT_BOUNDARY
-                {
-                    grid[(get_x(upper_left) - 1, mid)].set_ch(LIGHT_VERTICAL_AND_RIGHT);
-                }
-
-                for i in get_x(upper_left)..=get_x(bottom_right) {
-                    grid[(i, mid)].set_ch(HORZ_BOUNDARY);
-                }
-                context
-                    .dirty_areas
-                    .push_back((set_y(upper_left, mid), set_y(bottom_right, mid)));
-            }
             // TODO: Make headers view configurable
 
             if !self.dirty {
                 if let Some(v) = self.view.as_mut() {
-                    v.draw(grid, (set_y(upper_left, mid + 1), bottom_right), context);
+                    v.draw(grid, preview_area, context);
                 }
                 return;
             }
@@ -1171,7 +1215,7 @@ impl Component for ThreadListing {
             }
 
             if let Some(v) = self.view.as_mut() {
-                v.draw(grid, (set_y(upper_left, mid + 1), bottom_right), context);
+                v.draw(grid, preview_area, context);
             }
 
             self.dirty = false;
@@ -0,0 +1,255 @@
+/*
+ * meli - mailbox properties dialog module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::mailbox_properties::MailboxProperties;
+use melib::backends::SpecialUsageMailbox;
+use std::borrow::Cow;
+
+const USAGES: [SpecialUsageMailbox; 8] = [
+    SpecialUsageMailbox::Normal,
+    SpecialUsageMailbox::Inbox,
+    SpecialUsageMailbox::Archive,
+    SpecialUsageMailbox::Drafts,
+    SpecialUsageMailbox::Flagged,
+    SpecialUsageMailbox::Junk,
+    SpecialUsageMailbox::Sent,
+    SpecialUsageMailbox::Trash,
+];
+
+/// Dialog to view and edit the properties of a mailbox, opened with the `mailbox-properties`
+/// command.
+#[derive(Debug)]
+pub struct MailboxPropertiesDialog {
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+    title: String,
+    form: FormWidget<FormButtonActions>,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for MailboxPropertiesDialog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MailboxPropertiesDialog::DESCRIPTION)
+    }
+}
+
+impl MailboxPropertiesDialog {
+    pub const DESCRIPTION: &'static str = "mailbox properties";
+    pub fn new(account_hash: AccountHash, mailbox_hash: MailboxHash, context: &Context) -> Self {
+        let account = &context.accounts[&account_hash];
+        let properties = account.mailbox_properties(mailbox_hash);
+        let mut form = FormWidget::new(("Save".into(), FormButtonActions::Accept));
+        form.add_button(("Cancel".into(), FormButtonActions::Cancel));
+        let fields: [(&'static str, Vec<Cow<'static, str>>, usize); 4] = [
+            ("ignore", yes_no(), if properties.ignore { 0 } else { 1 }),
+            ("notify", yes_no(), if properties.notify { 0 } else { 1 }),
+            (
+                "autoload",
+                yes_no(),
+                if properties.autoload { 0 } else { 1 },
+            ),
+            (
+                "usage",
+                USAGES.iter().map(|u| u.to_string().into()).collect(),
+                USAGES
+                    .iter()
+                    .position(|u| *u == properties.usage)
+                    .unwrap_or(0),
+            ),
+        ];
+        for (name, choices, selected) in fields.iter().cloned() {
+            form.push_choices((name.into(), choices));
+            if let Some(Field::Choice(_, cursor)) = form.values_mut().get_mut(name) {
+                *cursor = selected;
+            }
+        }
+        MailboxPropertiesDialog {
+            account_hash,
+            mailbox_hash,
+            title: format!(
+                "Properties of {} {}",
+                account.name(),
+                account[&mailbox_hash].name()
+            ),
+            form,
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    /// The properties chosen in the form.
+    fn properties(&self) -> MailboxProperties {
+        let values = self.form.values();
+        let yes = |name: &str| values.get(name).map(|f| f.cursor() == 0).unwrap_or(false);
+        MailboxProperties {
+            ignore: yes("ignore"),
+            notify: yes("notify"),
+            autoload: yes("autoload"),
+            usage: values
+                .get("usage")
+                .and_then(|f| USAGES.get(f.cursor()))
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn close(&self, context: &mut Context) {
+        context.replies.push_back(UIEvent::ComponentKill(self.id));
+    }
+}
+
+fn yes_no() -> Vec<Cow<'static, str>> {
+    vec!["yes".into(), "no".into()]
+}
+
+impl Component for MailboxPropertiesDialog {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        let theme_default = crate::conf::value(context, "theme_default");
+        let dialog_area = align_area(
+            area,
+            /* The fields, the buttons and the box perimeter. */
+            (std::cmp::max(40, self.title.len() + 6), self.form.len() + 6),
+            Alignment::Center,
+            Alignment::Center,
+        );
+        clear_area(grid, dialog_area, theme_default);
+        let inner_area = create_box(grid, dialog_area);
+        write_string_to_grid(
+            &self.title,
+            grid,
+            theme_default.fg,
+            theme_default.bg,
+            theme_default.attrs | Attr::BOLD,
+            (
+                pos_inc(upper_left!(dialog_area), (2, 0)),
+                bottom_right!(dialog_area),
+            ),
+            None,
+        );
+        self.form.set_dirty(true);
+        self.form.draw(grid, inner_area, context);
+        self.dirty = false;
+        context.dirty_areas.push_back(dialog_area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if self.form.process_event(event, context) {
+            match self.form.buttons_result() {
+                Some(FormButtonActions::Accept) => {
+                    let properties = self.properties();
+                    let account = &mut context.accounts[&self.account_hash];
+                    let message =
+                        match account.set_mailbox_properties(self.mailbox_hash, properties) {
+                            Ok(()) => "Mailbox properties saved.".to_string(),
+                            Err(err) => format!("Could not save mailbox properties: {}", err),
+                        };
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+                    self.close(context);
+                }
+                Some(_) => self.close(context),
+                None => {}
+            }
+            self.set_dirty(true);
+            return true;
+        }
+        match event {
+            UIEvent::Input(Key::Esc) => {
+                self.close(context);
+                true
+            }
+            /* Keys aren't for the components below the dialog. */
+            UIEvent::Input(_) | UIEvent::InsertInput(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.form.is_dirty()
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[test]
+fn test_mailbox_properties_dialog() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "hello",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("hello");
+    let mailbox_hash = *h.state.context.accounts[0]
+        .mailbox_entries
+        .keys()
+        .next()
+        .unwrap();
+    let autoload = h.state.context.accounts[0]
+        .mailbox_properties(mailbox_hash)
+        .autoload;
+    h.keys(&[Key::Alt('p')]);
+    h.wait_for("Properties of test");
+    let row = h.find("autoload").unwrap();
+    assert!(
+        h.row(row).contains(if autoload { "yes" } else { "no" }),
+        "{}",
+        h.text()
+    );
+    /* Change autoload and save. */
+    h.keys(&[Key::Down, Key::Down, Key::Char('\n')]);
+    h.wait_until("insert mode", |h| h.state.mode == UIMode::Insert);
+    h.keys(&[Key::Right, Key::Char('\n')]);
+    h.wait_until("normal mode", |h| h.state.mode == UIMode::Normal);
+    h.keys(&[Key::Down, Key::Down, Key::Char('\n')]);
+    h.wait_for("Mailbox properties saved.");
+    assert!(h.find("Properties of test").is_none(), "{}", h.text());
+    let account = &h.state.context.accounts[0];
+    assert_eq!(account.mailbox_properties(mailbox_hash).autoload, !autoload);
+    let path = account[&mailbox_hash].ref_mailbox.path().to_string();
+    let stored = crate::mailbox_properties::PropertyOverrides::new("test");
+    assert_eq!(stored.get(&path).map(|p| p.autoload), Some(!autoload));
+    /* Other tests use an account with the same name. */
+    std::fs::remove_file(melib::paths::state_file(Some("test"), "mailbox_properties").unwrap())
+        .unwrap();
+
+    /* Escape closes the dialog without saving. */
+    h.keys(&[Key::Alt('p')]);
+    h.wait_for("Properties of test");
+    h.keys(&[Key::Esc]);
+    assert!(h.find("Properties of test").is_none(), "{}", h.text());
+}
@@ -21,6 +21,8 @@
 
 use melib::email::{
     attachment_types::{ContentDisposition, ContentType, MultipartType},
+    autocrypt::{self, AutocryptHeader},
+    headers::HeaderMap,
     pgp as melib_pgp, Attachment, AttachmentBuilder,
 };
 use melib::error::*;
@@ -44,6 +46,71 @@ pub async fn verify(a: Attachment) -> Result<()> {
     ctx.verify(sig, data)?.await
 }
 
+/// The secret key of `address`, or `key` if it is set.
+async fn own_key(address: String, key: Option<String>) -> Result<Key> {
+    let mut ctx = Context::new()?;
+    ctx.keylist(true, Some(key.unwrap_or_else(|| address.clone())))?
+        .await?
+        .into_iter()
+        .find(|k| {
+            k.secret()
+                && k.can_encrypt()
+                && !(k.revoked() || k.expired() || k.disabled() || k.invalid())
+        })
+        .ok_or_else(|| MeliError::new(format!("No usable secret key for {}.", address)))
+}
+
+/// The `Autocrypt` header of mail sent from `address`, with the public part of its key.
+pub async fn autocrypt_header(
+    address: String,
+    key: Option<String>,
+    prefer_encrypt: bool,
+) -> Result<AutocryptHeader> {
+    let key = own_key(address.clone(), key).await?;
+    let mut ctx = Context::new()?;
+    ctx.set_flag(GpgmeFlag::AsciiArmor, false)?;
+    let keydata = ctx.export_keys(vec![key], false)?.await?;
+    Ok(AutocryptHeader {
+        addr: address.to_lowercase(),
+        prefer_encrypt,
+        keydata,
+    })
+}
+
+/// The attachment of an Autocrypt Setup Message with the secret key of `address`, encrypted with
+/// `setup_code`.
+pub async fn autocrypt_setup_payload(
+    address: String,
+    key: Option<String>,
+    setup_code: String,
+) -> Result<String> {
+    let key = own_key(address, key).await?;
+    let mut ctx = Context::new()?;
+    let secret = ctx.export_keys(vec![key], true)?.await?;
+    let mut ctx = Context::new()?;
+    ctx.set_passphrase(&setup_code)?;
+    let data = ctx.new_data_mem(&secret)?;
+    let armored = ctx.encrypt_symmetric(data)?.await?;
+    Ok(autocrypt::setup_message_payload(
+        &String::from_utf8_lossy(&armored),
+        &setup_code,
+    ))
+}
+
+/// Import the secret key of an Autocrypt Setup Message, `armored` with `setup_code`.
+pub async fn import_autocrypt_setup(armored: String, setup_code: String) -> Result<()> {
+    let mut ctx = Context::new()?;
+    ctx.set_passphrase(&setup_code)?;
+    let cipher = ctx.new_data_mem(armored.as_bytes())?;
+    let (_, secret) = ctx
+        .decrypt(cipher)?
+        .await
+        .chain_err_summary(|| "Could not decrypt the Setup Message, is the Setup Code right?")?;
+    let mut ctx = Context::new()?;
+    let data = ctx.new_data_mem(&secret)?;
+    ctx.import_key(data)?.await
+}
+
 pub fn sign_filter(
     sign_keys: Vec<Key>,
 ) -> Result<
@@ -81,9 +148,11 @@ pub fn sign_filter(
     )
 }
 
+/// Encrypt the body of mail. With `protected_headers`, their values are encrypted along with it.
 pub fn encrypt_filter(
     sign_keys: Option<Vec<Key>>,
     encrypt_keys: Vec<Key>,
+    protected_headers: Option<HeaderMap>,
 ) -> Result<
     impl FnOnce(AttachmentBuilder) -> Pin<Box<dyn Future<Output = Result<AttachmentBuilder>> + Send>>
         + Send,
@@ -94,9 +163,11 @@ pub fn encrypt_filter(
                 let a: Attachment = a.into();
                 debug!("main attachment is {:?}", &a);
                 let mut ctx = Context::new()?;
-                let data = ctx.new_data_mem(
-                                a.into_raw().as_bytes()
-                )?;
+                let raw = match protected_headers {
+                    Some(ref headers) => melib_pgp::protect_headers(a, headers),
+                    None => a.into_raw(),
+                };
+                let data = ctx.new_data_mem(raw.as_bytes())?;
 
                 let sig_attachment = {
                     let mut a = Attachment::new(
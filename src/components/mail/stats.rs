@@ -0,0 +1,634 @@
+/*
+ * meli - storage statistics tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::conf::accounts::Account;
+use crate::jobs::{JobId, JoinHandle};
+use melib::backends::{BackendOp, ResultFuture};
+use melib::thread::SortOrder;
+use melib::Bytes;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+/// How many entries to show in the largest messages and senders tables.
+const TOP_ENTRIES: usize = 10;
+
+#[derive(Debug, Default, Clone)]
+pub struct MailboxStatistics {
+    pub path: String,
+    pub total: usize,
+    pub unseen: usize,
+    /// Total size in bytes, if the backend could provide it.
+    pub size: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageStatistics {
+    pub mailbox: String,
+    pub from: String,
+    pub subject: String,
+    pub size: usize,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SenderStatistics {
+    pub address: String,
+    pub count: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AccountStatistics {
+    pub mailboxes: Vec<MailboxStatistics>,
+    pub largest: Vec<MessageStatistics>,
+    pub senders: Vec<SenderStatistics>,
+}
+
+enum MessageSizes {
+    Backend(Pin<Box<dyn Future<Output = Result<HashMap<EnvelopeHash, usize>>> + Send>>),
+    Operations(Vec<(EnvelopeHash, Box<dyn BackendOp>)>),
+    Unknown,
+}
+
+impl MessageSizes {
+    /// Ask the backend for the sizes of the messages in `mailbox_hash` or, for local backends,
+    /// read the messages in `env_hashes`.
+    fn new<'a>(
+        account: &Account,
+        mailbox_hash: MailboxHash,
+        env_hashes: Option<impl Iterator<Item = &'a EnvelopeHash>>,
+    ) -> MessageSizes {
+        match (
+            env_hashes,
+            account.backend.read().unwrap().message_sizes(mailbox_hash),
+        ) {
+            (_, Ok(fut)) => MessageSizes::Backend(fut),
+            (Some(env_hashes), Err(_)) if !account.backend_capabilities.is_remote => {
+                let backend_lck = account.backend.read().unwrap();
+                MessageSizes::Operations(
+                    env_hashes
+                        .filter_map(|&env_hash| {
+                            backend_lck
+                                .operation(env_hash)
+                                .ok()
+                                .map(|op| (env_hash, op))
+                        })
+                        .collect(),
+                )
+            }
+            _ => MessageSizes::Unknown,
+        }
+    }
+
+    async fn get(self) -> Option<HashMap<EnvelopeHash, usize>> {
+        match self {
+            MessageSizes::Backend(fut) => fut.await.ok(),
+            MessageSizes::Operations(ops) => {
+                let mut ret = HashMap::default();
+                for (env_hash, mut op) in ops {
+                    if let Ok(fut) = op.as_bytes() {
+                        if let Ok(bytes) = fut.await {
+                            ret.insert(env_hash, bytes.len());
+                        }
+                    }
+                }
+                Some(ret)
+            }
+            MessageSizes::Unknown => None,
+        }
+    }
+}
+
+/// Compute the sizes of `env_hashes`, which are in `mailbox_hash`. Backends that report sizes
+/// might return the sizes of the other messages in the mailbox too. Fails if the sizes can't be
+/// known without fetching the messages.
+pub fn message_sizes(
+    account: &Account,
+    mailbox_hash: MailboxHash,
+    env_hashes: &[EnvelopeHash],
+) -> ResultFuture<HashMap<EnvelopeHash, usize>> {
+    match MessageSizes::new(account, mailbox_hash, Some(env_hashes.iter())) {
+        MessageSizes::Unknown => Err(MeliError::new("Message sizes are not available.")),
+        sizes => Ok(Box::pin(async move {
+            Ok(sizes.get().await.unwrap_or_default())
+        })),
+    }
+}
+
+impl AccountStatistics {
+    /// Create a job that computes the statistics of `account` over its collection. Message sizes
+    /// are requested from the backend (eg `RFC822.SIZE` on IMAP) or, for local backends, computed
+    /// from the messages themselves.
+    pub fn job(account: &Account) -> ResultFuture<AccountStatistics> {
+        let collection = account.collection.clone();
+        let mut mailboxes = Vec::with_capacity(account.mailbox_entries.len());
+        for node in account.list_mailboxes() {
+            let entry = &account.mailbox_entries[&node.hash];
+            let env_hashes = if entry.status.is_available() {
+                collection
+                    .mailboxes
+                    .read()
+                    .unwrap()
+                    .get(&node.hash)
+                    .cloned()
+            } else {
+                None
+            };
+            let sizes =
+                MessageSizes::new(account, node.hash, env_hashes.as_ref().map(|e| e.iter()));
+            mailboxes.push((
+                entry.ref_mailbox.path().to_string(),
+                entry.ref_mailbox.count().ok(),
+                env_hashes,
+                sizes,
+            ));
+        }
+
+        Ok(Box::pin(async move {
+            let mut ret = AccountStatistics::default();
+            let mut senders: HashMap<String, SenderStatistics> = HashMap::default();
+            for (path, counts, env_hashes, sizes) in mailboxes {
+                let sizes = sizes.get().await;
+                let envelopes_lck = collection.envelopes.read().unwrap();
+                let (unseen, total) = if let Some(ref env_hashes) = env_hashes {
+                    (
+                        env_hashes
+                            .iter()
+                            .filter_map(|h| envelopes_lck.get(h))
+                            .filter(|env| !env.is_seen())
+                            .count(),
+                        env_hashes.len(),
+                    )
+                } else {
+                    counts.unwrap_or((0, 0))
+                };
+                if let Some(ref sizes) = sizes {
+                    for (env_hash, &size) in sizes.iter() {
+                        let env = match envelopes_lck.get(env_hash) {
+                            Some(env) => env,
+                            None => continue,
+                        };
+                        let from = env
+                            .from()
+                            .get(0)
+                            .map(|addr| addr.get_email())
+                            .unwrap_or_default();
+                        let entry = senders.entry(from.clone()).or_default();
+                        entry.address = from.clone();
+                        entry.count += 1;
+                        entry.size += size;
+                        ret.largest.push(MessageStatistics {
+                            mailbox: path.clone(),
+                            from,
+                            subject: env.subject().to_string(),
+                            size,
+                        });
+                    }
+                }
+                ret.mailboxes.push(MailboxStatistics {
+                    path,
+                    total,
+                    unseen,
+                    size: sizes.map(|sizes| sizes.values().sum()),
+                });
+            }
+            ret.largest.sort_by(|a, b| b.size.cmp(&a.size));
+            ret.largest.truncate(TOP_ENTRIES);
+            ret.senders = senders.into_iter().map(|(_, v)| v).collect();
+            ret.senders
+                .sort_by(|a, b| b.size.cmp(&a.size).then(b.count.cmp(&a.count)));
+            ret.senders.truncate(TOP_ENTRIES);
+            Ok(ret)
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StatsColumn {
+    Mailbox,
+    Messages,
+    Unread,
+    Size,
+}
+
+impl StatsColumn {
+    fn next(self) -> Self {
+        match self {
+            StatsColumn::Mailbox => StatsColumn::Messages,
+            StatsColumn::Messages => StatsColumn::Unread,
+            StatsColumn::Unread => StatsColumn::Size,
+            StatsColumn::Size => StatsColumn::Mailbox,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum StatsState {
+    Loading(JoinHandle<Result<AccountStatistics>>),
+    Loaded(AccountStatistics),
+    Failed(MeliError),
+}
+
+/// Tab showing per-mailbox message counts and sizes, the largest messages and the senders that
+/// take up the most space in an account.
+#[derive(Debug)]
+pub struct AccountStats {
+    cursor: (usize, usize),
+    account_pos: usize,
+    content: CellBuffer,
+    state: StatsState,
+    sort: (StatsColumn, SortOrder),
+    dirty: bool,
+    theme_default: ThemeAttribute,
+    id: ComponentId,
+}
+
+impl fmt::Display for AccountStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", AccountStats::DESCRIPTION)
+    }
+}
+
+impl AccountStats {
+    pub const DESCRIPTION: &'static str = "stats";
+    pub fn new(account_pos: usize, context: &Context) -> Result<AccountStats> {
+        let theme_default = crate::conf::value(context, "theme_default");
+        Ok(AccountStats {
+            cursor: (0, 0),
+            account_pos,
+            content: Self::new_content(theme_default),
+            state: StatsState::Loading(Self::spawn_job(account_pos, context)?),
+            sort: (StatsColumn::Size, SortOrder::Desc),
+            dirty: true,
+            theme_default,
+            id: ComponentId::new_v4(),
+        })
+    }
+
+    fn new_content(theme_default: ThemeAttribute) -> CellBuffer {
+        let mut default_cell = Cell::with_char(' ');
+        default_cell
+            .set_fg(theme_default.fg)
+            .set_bg(theme_default.bg)
+            .set_attrs(theme_default.attrs);
+        let mut content = CellBuffer::new(120, 5, default_cell);
+        content.set_growable(true);
+        content
+    }
+
+    fn spawn_job(
+        account_pos: usize,
+        context: &Context,
+    ) -> Result<JoinHandle<Result<AccountStatistics>>> {
+        let account = &context.accounts[account_pos];
+        let job = AccountStatistics::job(account)?;
+        Ok(if account.backend_capabilities.is_async {
+            account.job_executor.spawn_specialized(job)
+        } else {
+            account.job_executor.spawn_blocking(job)
+        })
+    }
+
+    fn write_line(&mut self, text: &str, line: usize, attrs: Attr) {
+        let width = self.content.size().0;
+        write_string_to_grid(
+            text,
+            &mut self.content,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            attrs,
+            ((1, line), (width - 1, line)),
+            None,
+        );
+    }
+
+    fn draw_statistics(&mut self, stats: &AccountStatistics, mut line: usize) {
+        let mut mailboxes = stats.mailboxes.iter().collect::<Vec<&MailboxStatistics>>();
+        mailboxes.sort_by(|a, b| {
+            let ord = match self.sort.0 {
+                StatsColumn::Mailbox => a.path.cmp(&b.path),
+                StatsColumn::Messages => a.total.cmp(&b.total),
+                StatsColumn::Unread => a.unseen.cmp(&b.unseen),
+                StatsColumn::Size => a.size.cmp(&b.size),
+            };
+            if self.sort.1 == SortOrder::Desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+        let name_width = std::cmp::min(
+            40,
+            mailboxes
+                .iter()
+                .map(|m| m.path.grapheme_width())
+                .max()
+                .unwrap_or(0)
+                .max("Mailbox".len()),
+        );
+        let sort = self.sort;
+        let header = |column: StatsColumn, title: &str| -> String {
+            if column != sort.0 {
+                title.to_string()
+            } else if sort.1 == SortOrder::Desc {
+                format!("{}▼", title)
+            } else {
+                format!("{}▲", title)
+            }
+        };
+        let size = |size: Option<usize>| -> String {
+            size.map(|s| Bytes(s).to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        self.write_line(
+            &format!(
+                "{:<name_width$} {:>10} {:>10} {:>14}",
+                header(StatsColumn::Mailbox, "Mailbox"),
+                header(StatsColumn::Messages, "Messages"),
+                header(StatsColumn::Unread, "Unread"),
+                header(StatsColumn::Size, "Size"),
+                name_width = name_width
+            ),
+            line,
+            Attr::BOLD,
+        );
+        line += 1;
+        for m in mailboxes.iter() {
+            self.write_line(
+                &format!(
+                    "{:<name_width$} {:>10} {:>10} {:>14}",
+                    m.path.trim_at_boundary(name_width),
+                    m.total,
+                    m.unseen,
+                    size(m.size),
+                    name_width = name_width
+                ),
+                line,
+                self.theme_default.attrs,
+            );
+            line += 1;
+        }
+        let total_size = if mailboxes.iter().any(|m| m.size.is_some()) {
+            Some(mailboxes.iter().filter_map(|m| m.size).sum())
+        } else {
+            None
+        };
+        self.write_line(
+            &format!(
+                "{:<name_width$} {:>10} {:>10} {:>14}",
+                "Total",
+                mailboxes.iter().map(|m| m.total).sum::<usize>(),
+                mailboxes.iter().map(|m| m.unseen).sum::<usize>(),
+                size(total_size),
+                name_width = name_width
+            ),
+            line,
+            Attr::BOLD,
+        );
+        if mailboxes.iter().any(|m| m.size.is_none()) {
+            line += 1;
+            self.write_line(
+                "Sizes of mailboxes that have not been loaded are not available.",
+                line,
+                self.theme_default.attrs,
+            );
+        }
+
+        line += 2;
+        self.write_line("Largest messages:", line, Attr::BOLD);
+        line += 1;
+        for m in stats.largest.iter() {
+            self.write_line(
+                &format!(
+                    "{:>14}  {}  {}  ({})",
+                    Bytes(m.size).to_string(),
+                    m.from.trim_at_boundary(30),
+                    m.subject.trim_at_boundary(60),
+                    m.mailbox
+                ),
+                line,
+                self.theme_default.attrs,
+            );
+            line += 1;
+        }
+
+        line += 1;
+        self.write_line("Senders by volume:", line, Attr::BOLD);
+        line += 1;
+        for s in stats.senders.iter() {
+            self.write_line(
+                &format!(
+                    "{:>14}  {:>6} message{}  {}",
+                    Bytes(s.size).to_string(),
+                    s.count,
+                    if s.count == 1 { " " } else { "s" },
+                    s.address
+                ),
+                line,
+                self.theme_default.attrs,
+            );
+            line += 1;
+        }
+    }
+}
+
+impl Component for AccountStats {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.content = Self::new_content(self.theme_default);
+        let account_name = context.accounts[self.account_pos].name().to_string();
+        self.write_line(
+            &format!("Storage statistics of {}", account_name),
+            0,
+            Attr::BOLD | Attr::UNDERLINE,
+        );
+        match std::mem::replace(
+            &mut self.state,
+            StatsState::Failed(MeliError::new("Statistics are being redrawn.")),
+        ) {
+            StatsState::Loading(handle) => {
+                self.write_line("Computing statistics…", 2, self.theme_default.attrs);
+                self.state = StatsState::Loading(handle);
+            }
+            StatsState::Failed(err) => {
+                self.write_line(
+                    &format!("Could not compute statistics: {}", err),
+                    2,
+                    self.theme_default.attrs,
+                );
+                self.state = StatsState::Failed(err);
+            }
+            StatsState::Loaded(stats) => {
+                self.draw_statistics(&stats, 2);
+                self.state = StatsState::Loaded(stats);
+            }
+        }
+
+        /* self.content may have been resized with write_string_to_grid() calls above since it has
+         * growable set */
+        let (width, height) = self.content.size();
+        let (cols, rows) = (width!(area), height!(area));
+        self.cursor = (
+            std::cmp::min(width.saturating_sub(cols), self.cursor.0),
+            std::cmp::min(height.saturating_sub(rows), self.cursor.1),
+        );
+        clear_area(grid, area, self.theme_default);
+        copy_area(
+            grid,
+            &self.content,
+            area,
+            (
+                (
+                    std::cmp::min((width - 1).saturating_sub(cols), self.cursor.0),
+                    std::cmp::min((height - 1).saturating_sub(rows), self.cursor.1),
+                ),
+                (
+                    std::cmp::min(self.cursor.0 + cols, width - 1),
+                    std::cmp::min(self.cursor.1 + rows, height - 1),
+                ),
+            ),
+        );
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.dirty = true;
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if matches_job(&self.state, job_id) =>
+            {
+                if let StatsState::Loading(ref mut handle) = self.state {
+                    self.state = match handle.chan.try_recv() {
+                        Ok(Some(Ok(stats))) => StatsState::Loaded(stats),
+                        Ok(Some(Err(err))) => StatsState::Failed(err),
+                        Err(_) | Ok(None) => {
+                            StatsState::Failed(MeliError::new("Job was canceled."))
+                        }
+                    };
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["next_sort_column"]) =>
+            {
+                self.sort.0 = self.sort.0.next();
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["toggle_sort_order"]) =>
+            {
+                self.sort.1 = match self.sort.1 {
+                    SortOrder::Asc => SortOrder::Desc,
+                    SortOrder::Desc => SortOrder::Asc,
+                };
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["refresh"]) =>
+            {
+                self.state = match Self::spawn_job(self.account_pos, context) {
+                    Ok(handle) => StatsState::Loading(handle),
+                    Err(err) => StatsState::Failed(err),
+                };
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts["general"]["scroll_left"]) && self.cursor.0 != 0 =>
+            {
+                self.cursor.0 -= 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_right"]) => {
+                self.cursor.0 += 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_up"]) => {
+                self.cursor.1 = self.cursor.1.saturating_sub(1);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_down"]) => {
+                self.cursor.1 += 1;
+                self.dirty = true;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret.insert(
+            Self::DESCRIPTION,
+            context.settings.shortcuts.stats.key_values(),
+        );
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        session.tabs.push(SessionTab::AccountStats {
+            account: context.accounts[self.account_pos].name().to_string(),
+        });
+    }
+}
+
+fn matches_job(state: &StatsState, job_id: &JobId) -> bool {
+    if let StatsState::Loading(ref handle) = state {
+        handle.job_id == *job_id
+    } else {
+        false
+    }
+}
@@ -204,6 +204,50 @@ impl Component for AccountStatus {
         );
         width = self.content.size().0;
         line += 1;
+        if let Some(quota) = a.quota {
+            let (_x, _y) = write_string_to_grid(
+                "Storage quota: ",
+                &mut self.content,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                Attr::BOLD,
+                ((1, line), (width - 1, line)),
+                None,
+            );
+            width = self.content.size().0;
+            write_string_to_grid(
+                &quota.to_string(),
+                &mut self.content,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                ((_x, _y), (width - 1, _y)),
+                None,
+            );
+            width = self.content.size().0;
+            line += 1;
+        }
+        let (_x, _y) = write_string_to_grid(
+            "Power: ",
+            &mut self.content,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            Attr::BOLD,
+            ((1, line), (width - 1, line)),
+            None,
+        );
+        width = self.content.size().0;
+        write_string_to_grid(
+            &context.power.to_string(),
+            &mut self.content,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            ((_x, _y), (width - 1, _y)),
+            None,
+        );
+        width = self.content.size().0;
+        line += 1;
 
         write_string_to_grid(
             "Special Mailboxes:",
@@ -373,6 +417,42 @@ impl Component for AccountStatus {
             }
         }
 
+        let actions = a.backend_capabilities.actions;
+        if !actions.is_empty() {
+            line += 1;
+            width = self.content.size().0;
+            write_string_to_grid(
+                "Backend actions:",
+                &mut self.content,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                Attr::BOLD,
+                ((1, line), (width - 1, line)),
+                None,
+            );
+            line += 1;
+            for action in actions {
+                width = self.content.size().0;
+                write_string_to_grid(
+                    &format!(
+                        "backend {} {}{}{}: {}",
+                        a.name(),
+                        action.name,
+                        if action.args.is_empty() { "" } else { " " },
+                        action.args.join(" "),
+                        action.description
+                    ),
+                    &mut self.content,
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs,
+                    ((1, line), (width - 1, line)),
+                    None,
+                );
+                line += 1;
+            }
+        }
+
         /* self.content may have been resized with write_string_to_grid() calls above since it has
          * growable set */
         let (width, height) = self.content.size();
@@ -21,10 +21,12 @@
 
 use super::*;
 use crate::conf::accounts::JobRequest;
-use crate::jobs::{JobId, JoinHandle};
+use crate::conf::pager::MultipartAlternativePreference;
+use crate::jobs::{JobId, JoinHandle, Timer};
 use melib::email::attachment_types::ContentType;
 use melib::list_management;
 use melib::parser::BytesExt;
+use melib::text_processing::{GlobMatch, Reflow};
 use smallvec::SmallVec;
 use std::collections::HashSet;
 use std::io::Write;
@@ -41,8 +43,11 @@ pub use self::thread::*;
 mod envelope;
 pub use self::envelope::*;
 
+mod mime_tree;
+pub use self::mime_tree::*;
+
 use linkify::LinkFinder;
-use xdg_utils::query_default_app;
+use xdg_utils::{query_default_app, query_mime_info};
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 enum Source {
@@ -59,6 +64,73 @@ enum ViewMode {
     //Ansi(RawBuffer),
     Subview,
     ContactSelector(UIDialog<Card>),
+    /// Picking an address from the headers, see the `address_actions` shortcut.
+    AddressSelector(UIDialog<Address>),
+    /// Picking what to do with the address.
+    AddressActions(UIDialog<(Address, AddressAction)>),
+    /// Confirming one of the options of the List-Unsubscribe header.
+    Unsubscribe(UIDialog<UnsubscribeAction>),
+    /// Picking one of the known replies to the envelope, see the `list_replies` shortcut.
+    Replies(UIDialog<(MailboxHash, EnvelopeHash)>),
+}
+
+/// What to do with an address picked from the headers.
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum AddressAction {
+    Compose,
+    AddToContacts,
+    EditContact,
+    SearchFrom,
+    Copy,
+}
+
+/// An option of the List-Unsubscribe header, described in the unsubscribe dialog before it is
+/// carried out.
+#[derive(PartialEq, Clone, Debug)]
+enum UnsubscribeAction {
+    /// Send the e-mail of a `mailto:` URI.
+    Email(String),
+    /// Make the rfc8058 one-click `POST` request to an https URL.
+    #[cfg(feature = "http")]
+    OneClick(String),
+    /// Open a URL in a browser.
+    Url(String),
+}
+
+impl UnsubscribeAction {
+    /// What carrying out the action does, sending e-mail `from` this address.
+    fn description(&self, from: &str) -> String {
+        match self {
+            UnsubscribeAction::Email(mailto) => match Mailto::try_from(mailto.as_bytes()) {
+                Ok(Mailto {
+                    address,
+                    subject,
+                    cc,
+                    bcc,
+                    body,
+                }) => {
+                    let mut ret = format!("send an e-mail from {} to {}", from, address);
+                    for (name, value) in &[("cc", cc), ("bcc", bcc)] {
+                        if let Some(value) = value.as_ref().filter(|v| !v.is_empty()) {
+                            ret.push_str(&format!(", {} {}", name, value));
+                        }
+                    }
+                    ret.push_str(&format!(
+                        " with subject {:?} and body {:?}",
+                        subject.unwrap_or_default(),
+                        body.unwrap_or_default()
+                    ));
+                    ret
+                }
+                Err(err) => format!("invalid mailto URI {}: {}", mailto, err),
+            },
+            #[cfg(feature = "http")]
+            UnsubscribeAction::OneClick(url) => {
+                format!("send a one-click unsubscribe POST request to {}", url)
+            }
+            UnsubscribeAction::Url(url) => format!("open {} in a browser", url),
+        }
+    }
 }
 
 impl Default for ViewMode {
@@ -82,12 +154,30 @@ impl ViewMode {
             _ => false,
         }
     }
-    fn is_contact_selector(&self) -> bool {
+    /// The dialog drawn over the envelope, if any.
+    fn selector(&self) -> Option<&dyn Component> {
         match self {
-            ViewMode::ContactSelector(_) => true,
-            _ => false,
+            ViewMode::ContactSelector(ref s) => Some(s),
+            ViewMode::AddressSelector(ref s) => Some(s),
+            ViewMode::AddressActions(ref s) => Some(s),
+            ViewMode::Unsubscribe(ref s) => Some(s),
+            ViewMode::Replies(ref s) => Some(s),
+            _ => None,
+        }
+    }
+    fn selector_mut(&mut self) -> Option<&mut dyn Component> {
+        match self {
+            ViewMode::ContactSelector(ref mut s) => Some(s),
+            ViewMode::AddressSelector(ref mut s) => Some(s),
+            ViewMode::AddressActions(ref mut s) => Some(s),
+            ViewMode::Unsubscribe(ref mut s) => Some(s),
+            ViewMode::Replies(ref mut s) => Some(s),
+            _ => None,
         }
     }
+    fn is_selector(&self) -> bool {
+        self.selector().is_some()
+    }
 }
 
 #[derive(Debug)]
@@ -144,6 +234,101 @@ pub enum AttachmentDisplay {
     },
 }
 
+impl AttachmentDisplay {
+    fn attachment(&self) -> &Attachment {
+        use AttachmentDisplay::*;
+        match self {
+            Alternative { inner, .. }
+            | InlineText { inner, .. }
+            | InlineOther { inner }
+            | Attachment { inner }
+            | SignedPending { inner, .. }
+            | SignedFailed { inner, .. }
+            | SignedUnverified { inner, .. }
+            | SignedVerified { inner, .. }
+            | EncryptedPending { inner, .. }
+            | EncryptedFailed { inner, .. }
+            | EncryptedSuccess { inner, .. } => inner,
+        }
+    }
+
+    /// Find the first `multipart/alternative` in `displays`, looking into signed and decrypted
+    /// parts, and return the index of its shown part along with its parts.
+    fn find_alternative(displays: &[AttachmentDisplay]) -> Option<(usize, &[AttachmentDisplay])> {
+        use AttachmentDisplay::*;
+        for d in displays {
+            match d {
+                Alternative {
+                    shown_display,
+                    display,
+                    ..
+                } => return Some((*shown_display, display.as_slice())),
+                SignedPending { display, .. }
+                | SignedFailed { display, .. }
+                | SignedUnverified { display, .. }
+                | SignedVerified { display, .. }
+                | EncryptedSuccess {
+                    plaintext_display: display,
+                    ..
+                } => {
+                    if let Some(ret) = Self::find_alternative(display) {
+                        return Some(ret);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The first decrypted part in `displays` with protected headers.
+    fn find_protected_headers(displays: &[AttachmentDisplay]) -> Option<&Attachment> {
+        displays.iter().find_map(|d| match d {
+            AttachmentDisplay::EncryptedSuccess { plaintext, .. }
+                if melib::pgp::has_protected_headers(plaintext) =>
+            {
+                Some(plaintext)
+            }
+            _ => None,
+        })
+    }
+
+    /// Show the next part of the first `multipart/alternative` in `displays`. Returns `false` if
+    /// there is none.
+    fn cycle_alternative(displays: &mut [AttachmentDisplay]) -> bool {
+        use AttachmentDisplay::*;
+        for d in displays {
+            match d {
+                Alternative {
+                    shown_display,
+                    display,
+                    ..
+                } => {
+                    if display.is_empty() {
+                        return false;
+                    }
+                    *shown_display = (*shown_display + 1) % display.len();
+                    return true;
+                }
+                SignedPending { display, .. }
+                | SignedFailed { display, .. }
+                | SignedUnverified { display, .. }
+                | SignedVerified { display, .. }
+                | EncryptedSuccess {
+                    plaintext_display: display,
+                    ..
+                } => {
+                    if Self::cycle_alternative(display) {
+                        return true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+}
+
 /// Contains an Envelope view, with sticky headers, a pager for the body, and subviews for more
 /// menus
 #[derive(Debug, Default)]
@@ -155,6 +340,11 @@ pub struct MailView {
     initialised: bool,
     mode: ViewMode,
     expand_headers: bool,
+    expand_note: bool,
+    /// Show the full error when the envelope failed to load.
+    expand_error: bool,
+    /// Automatic retries so far of loading the current envelope.
+    load_retries: u8,
     attachment_tree: String,
     attachment_paths: Vec<Vec<usize>>,
     headers_no: usize,
@@ -163,8 +353,11 @@ pub struct MailView {
     theme_default: ThemeAttribute,
     active_jobs: HashSet<JobId>,
     state: MailViewState,
+    /// Text highlighted in the body, set by the search of the thread view.
+    highlight: Option<String>,
+    /// Attachments opened in external viewers, deleted when the view is closed.
+    temp_files: Vec<File>,
 
-    cmd_buf: String,
     id: ComponentId,
 }
 
@@ -173,6 +366,7 @@ pub enum PendingReplyAction {
     Reply,
     ReplyToAuthor,
     ReplyToAll,
+    Forward,
 }
 
 #[derive(Debug)]
@@ -186,6 +380,8 @@ enum MailViewState {
     },
     Error {
         err: MeliError,
+        /// Fires when it's time to try loading again after a transient error.
+        retry: Option<Timer>,
     },
     Loaded {
         bytes: Vec<u8>,
@@ -221,13 +417,14 @@ impl Clone for MailView {
     fn clone(&self) -> Self {
         MailView {
             subview: None,
-            cmd_buf: String::with_capacity(4),
             pager: self.pager.clone(),
             mode: ViewMode::Normal,
             attachment_tree: self.attachment_tree.clone(),
             attachment_paths: self.attachment_paths.clone(),
             state: MailViewState::default(),
             active_jobs: self.active_jobs.clone(),
+            highlight: self.highlight.clone(),
+            temp_files: vec![],
             ..*self
         }
     }
@@ -239,6 +436,9 @@ impl fmt::Display for MailView {
     }
 }
 
+/// How many times loading an envelope is retried after network errors and timeouts.
+const LOAD_RETRIES: u8 = 3;
+
 impl MailView {
     const DESCRIPTION: &'static str = "view mail";
     pub fn new(
@@ -255,6 +455,9 @@ impl MailView {
             initialised: false,
             mode: ViewMode::Normal,
             expand_headers: false,
+            expand_note: false,
+            expand_error: false,
+            load_retries: 0,
             attachment_tree: String::new(),
             attachment_paths: vec![],
 
@@ -265,8 +468,9 @@ impl MailView {
             theme_default: crate::conf::value(context, "mail.view.body"),
             active_jobs: Default::default(),
             state: MailViewState::default(),
+            highlight: None,
+            temp_files: vec![],
 
-            cmd_buf: String::with_capacity(4),
             id: ComponentId::new_v4(),
         };
 
@@ -310,6 +514,8 @@ impl MailView {
                                             .get_env_mut(self.coordinates.2)
                                             .populate_headers(&bytes);
                                     }
+                                    #[cfg(feature = "gpgme")]
+                                    account.harvest_autocrypt(self.coordinates.2);
                                     let body = AttachmentBuilder::new(&bytes).build();
                                     let display = Self::attachment_to(
                                         &body,
@@ -332,7 +538,7 @@ impl MailView {
                                     };
                                 }
                                 Err(err) => {
-                                    self.state = MailViewState::Error { err };
+                                    self.set_load_error(err, context);
                                 }
                             }
                         } else {
@@ -347,14 +553,19 @@ impl MailView {
                         }
                     }
                     Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(format!("Could not get message: {}", err)),
-                        ));
+                        self.set_load_error(err, context);
                     }
                 }
             }
             let account = &mut context.accounts[&self.coordinates.0];
-            if !account.collection.get_env(self.coordinates.2).is_seen() {
+            /* Read-only accounts are read without marking anything. */
+            if !account.is_read_only() && !account.collection.get_env(self.coordinates.2).is_seen()
+            {
+                account.record_action(
+                    Some(self.coordinates.2),
+                    self.coordinates.1,
+                    "mark seen on opening",
+                );
                 let job = account.backend.write().unwrap().set_flags(
                     self.coordinates.2.into(),
                     self.coordinates.1,
@@ -387,8 +598,120 @@ impl MailView {
         }
     }
 
+    /// Show `err` instead of the envelope, and try again later if it might go away by itself.
+    fn set_load_error(&mut self, err: MeliError, context: &mut Context) {
+        let retry = if err.kind.is_transient() && self.load_retries < LOAD_RETRIES {
+            /* 1, 2 and 4 seconds. */
+            let delay = std::time::Duration::from_secs(1 << self.load_retries);
+            self.load_retries += 1;
+            Some(
+                context
+                    .job_executor
+                    .clone()
+                    .create_timer(std::time::Duration::from_secs(0), delay),
+            )
+        } else {
+            context.replies.push_back(UIEvent::Notification(
+                Some("Failed to open e-mail".to_string()),
+                err.summary
+                    .as_ref()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| err.details.to_string()),
+                Some(NotificationType::Error(err.kind)),
+            ));
+            None
+        };
+        log_target("ui", format!("Failed to open envelope: {}", err), ERROR);
+        self.state = MailViewState::Error { err, retry };
+        self.initialised = false;
+        self.set_dirty(true);
+    }
+
+    /// Highlight `pattern` in the body, or stop highlighting.
+    pub fn set_highlight(&mut self, pattern: Option<String>) {
+        if self.highlight != pattern {
+            self.highlight = pattern;
+            self.initialised = false;
+            self.set_dirty(true);
+        }
+    }
+
+    /// Load the envelope again after it failed to.
+    fn retry_load(&mut self, context: &mut Context) {
+        self.state = MailViewState::default();
+        self.initialised = false;
+        self.init_futures(context);
+        self.set_dirty(true);
+    }
+
+    /// What went wrong, where and what to do about it, shown in place of an envelope that failed
+    /// to load.
+    fn error_report(&self, err: &MeliError, retrying: bool, context: &Context) -> String {
+        let account = &context.accounts[&self.coordinates.0];
+        let mut ret = format!(
+            "Could not open this e-mail: {}\n\n",
+            err.summary.as_ref().unwrap_or(&err.details)
+        );
+        ret.push_str(&format!(
+            "Account: {} ({})\n",
+            account.name(),
+            account.settings.account.format()
+        ));
+        if let Some(entry) = account.mailbox_entries.get(&self.coordinates.1) {
+            ret.push_str(&format!("Mailbox: {}\n", entry.ref_mailbox.path()));
+        }
+        ret.push_str(&format!("Kind: {}\n\n", err.kind));
+        ret.push_str(match err.kind {
+            melib::ErrorKind::Network => {
+                "Check your network connection, and the server address and port in the \
+                 account's configuration."
+            }
+            melib::ErrorKind::Timeout => {
+                "The server took too long to answer. Check your network connection, or raise the \
+                 account's `timeout` setting if the server is slow."
+            }
+            melib::ErrorKind::Authentication => {
+                "The server rejected the account's credentials. Check the username and password \
+                 or the command that provides it."
+            }
+            melib::ErrorKind::Bug => {
+                "This is a bug in meli, please report it with the details below."
+            }
+            melib::ErrorKind::External => {
+                "An external program or library failed, see the details below and check its \
+                 configuration."
+            }
+            melib::ErrorKind::None => {
+                "The message may have been moved or deleted by another client. Refreshing the \
+                 mailbox will show its current contents."
+            }
+        });
+        ret.push_str("\n\n");
+        let shortcuts = &context.settings.shortcuts.envelope_view;
+        if retrying {
+            ret.push_str(&format!(
+                "Trying again automatically (attempt {} of {}).\n",
+                self.load_retries, LOAD_RETRIES
+            ));
+        } else {
+            ret.push_str(&format!("Press {} to try again.\n", shortcuts.retry));
+        }
+        if self.expand_error {
+            ret.push_str(&format!(
+                "Press {} to hide the details.\n\n{}\n",
+                shortcuts.toggle_expand_headers, err
+            ));
+        } else {
+            ret.push_str(&format!(
+                "Press {} to show the details.\n",
+                shortcuts.toggle_expand_headers
+            ));
+        }
+        ret
+    }
+
     fn perform_action(&mut self, action: PendingReplyAction, context: &mut Context) {
-        let reply_body = match self.state {
+        let (reply_body, body) = match self.state {
             MailViewState::Init {
                 ref mut pending_action,
                 ..
@@ -402,14 +725,19 @@ impl MailView {
                 }
                 return;
             }
-            MailViewState::Loaded { ref display, .. } => {
-                self.attachment_displays_to_text(&display, context, false)
-            }
+            MailViewState::Loaded {
+                ref display,
+                ref body,
+                ..
+            } => (
+                self.attachment_displays_to_text(&display, context, false),
+                body.clone(),
+            ),
             MailViewState::Error { .. } => {
                 return;
             }
         };
-        let composer = match action {
+        let mut composer = match action {
             PendingReplyAction::Reply => Box::new(Composer::reply_to_select(
                 self.coordinates,
                 reply_body,
@@ -425,7 +753,11 @@ impl MailView {
                 reply_body,
                 context,
             )),
+            PendingReplyAction::Forward => {
+                Box::new(Composer::forward(self.coordinates, reply_body, context))
+            }
         };
+        composer.set_original_attachments(&body, context);
 
         context
             .replies
@@ -711,6 +1043,10 @@ impl MailView {
                 acc.push(AttachmentDisplay::Attachment { inner: a.clone() });
             } else if a.content_type().is_text_html() {
                 let bytes = decode(a, None);
+                let charset_comment = a
+                    .charset_fallback()
+                    .map(|charset| format!("⚠ decoded as {}\n\n", charset))
+                    .unwrap_or_default();
                 let filter_invocation =
                     mailbox_settings!(context[coordinates.0][&coordinates.1].pager.html_filter)
                         .as_ref()
@@ -732,7 +1068,8 @@ impl MailView {
                             Some(NotificationType::Error(melib::ErrorKind::External)),
                         ));
                         let comment = Some(format!(
-                                "Failed to start html filter process: `{}`. Press `v` to open in web browser. \n\n",
+                                "{}Failed to start html filter process: `{}`. Press `v` to open in web browser. \n\n",
+                                charset_comment,
                                 filter_invocation
                             ));
                         let text = String::from_utf8_lossy(&bytes).to_string();
@@ -750,8 +1087,8 @@ impl MailView {
                             .write_all(&bytes)
                             .expect("Failed to write to stdin");
                         let comment = Some(format!(
-                            "Text piped through `{}`. Press `v` to open in web browser. \n\n",
-                            filter_invocation
+                            "{}Text piped through `{}`. Press `v` to open in web browser. \n\n",
+                            charset_comment, filter_invocation
                         ));
                         let text = String::from_utf8_lossy(
                             &html_filter.wait_with_output().unwrap().stdout,
@@ -768,7 +1105,9 @@ impl MailView {
                 let bytes = decode(a, None);
                 acc.push(AttachmentDisplay::InlineText {
                     inner: a.clone(),
-                    comment: None,
+                    comment: a
+                        .charset_fallback()
+                        .map(|charset| format!("⚠ decoded as {}", charset)),
                     text: String::from_utf8_lossy(&bytes).to_string(),
                 });
             } else if let ContentType::Multipart {
@@ -784,7 +1123,19 @@ impl MailView {
                         }
                         let mut display = vec![];
                         let mut chosen_attachment_idx = 0;
-                        if let Some(text_attachment_pos) =
+                        let preference = *mailbox_settings!(
+                            context[coordinates.0][&coordinates.1]
+                                .pager
+                                .multipart_alternative_preference
+                        );
+                        if let Some(html_attachment_pos) = parts
+                            .iter()
+                            .position(|a| a.content_type == "text/html")
+                            .filter(|_| preference == MultipartAlternativePreference::Html)
+                        {
+                            /* Select html alternative as configured */
+                            chosen_attachment_idx = html_attachment_pos;
+                        } else if let Some(text_attachment_pos) =
                             parts.iter().position(|a| a.content_type == "text/plain")
                         {
                             let bytes = decode(&parts[text_attachment_pos], None);
@@ -921,127 +1272,553 @@ impl MailView {
             self.coordinates = new_coordinates;
             self.mode = ViewMode::Normal;
             self.initialised = false;
+            self.load_retries = 0;
             self.init_futures(context);
             self.set_dirty(true);
         }
     }
 
-    fn open_attachment(
-        &'_ self,
-        lidx: usize,
-        context: &mut Context,
-    ) -> Option<&'_ melib::Attachment> {
-        let display = if let MailViewState::Loaded { ref display, .. } = self.state {
-            display
-        } else {
-            return None;
-        };
-        if let Some(path) =
-            self.attachment_paths.get(lidx).and_then(
-                |path| {
-                    if path.len() > 0 {
-                        Some(path)
-                    } else {
-                        None
-                    }
+    fn address_actions_dialog(
+        &self,
+        address: Address,
+        context: &Context,
+    ) -> UIDialog<(Address, AddressAction)> {
+        let email = address.get_email();
+        let in_contacts = context.accounts[&self.coordinates.0]
+            .address_book
+            .values()
+            .any(|card| card.email().eq_ignore_ascii_case(&email));
+        let entries = vec![
+            (AddressAction::Compose, "compose to".to_string()),
+            if in_contacts {
+                (AddressAction::EditContact, "edit contact".to_string())
+            } else {
+                (AddressAction::AddToContacts, "add to contacts".to_string())
+            },
+            (
+                AddressAction::SearchFrom,
+                "show all mail from this address".to_string(),
+            ),
+            (AddressAction::Copy, "copy address".to_string()),
+        ];
+        Selector::new(
+            &address.to_string(),
+            entries
+                .into_iter()
+                .map(|(action, desc)| ((address.clone(), action), desc))
+                .collect(),
+            true,
+            Some(Box::new(
+                move |id: ComponentId, results: &[(Address, AddressAction)]| {
+                    Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
                 },
-            )
-        {
-            let first = path[0];
-            use AttachmentDisplay::*;
-            let root_attachment = match &display[first] {
-                Alternative {
-                    inner,
-                    shown_display: _,
-                    display: _,
-                }
-                | InlineText {
-                    inner,
-                    text: _,
-                    comment: _,
-                }
-                | InlineOther { inner }
-                | Attachment { inner }
-                | SignedPending {
-                    inner,
-                    display: _,
-                    handle: _,
-                    job_id: _,
-                }
-                | SignedFailed {
-                    inner,
-                    display: _,
-                    error: _,
-                }
-                | SignedVerified {
-                    inner,
-                    display: _,
-                    description: _,
-                }
-                | SignedUnverified { inner, display: _ }
-                | EncryptedPending { inner, handle: _ }
-                | EncryptedFailed { inner, error: _ }
-                | EncryptedSuccess {
-                    inner: _,
-                    plaintext: inner,
-                    plaintext_display: _,
-                    description: _,
-                } => inner,
-            };
-            fn find_attachment<'a>(
-                a: &'a melib::Attachment,
-                path: &[usize],
-            ) -> Option<&'a melib::Attachment> {
-                if path.is_empty() {
-                    return Some(a);
+            )),
+            context,
+        )
+    }
+
+    fn perform_address_action(
+        &mut self,
+        address: &Address,
+        action: AddressAction,
+        context: &mut Context,
+    ) {
+        let email = address.get_email();
+        match action {
+            AddressAction::Compose => {
+                let mut draft: Draft = Draft::default();
+                draft.set_header("To", address.to_string());
+                let mut composer = Composer::with_account(self.coordinates.0, context);
+                composer.set_draft(draft);
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+            }
+            AddressAction::AddToContacts => {
+                let mut new_card: Card = Card::new();
+                new_card.set_email(email);
+                if let Some(display_name) = address.get_display_name() {
+                    new_card.set_name(display_name);
                 }
-                match a.content_type {
-                    ContentType::Multipart { ref parts, .. } => {
-                        let first = path[0];
-                        if first < parts.len() {
-                            return find_attachment(&parts[first], &path[1..]);
-                        }
-                    }
-                    _ => {}
+                context.accounts[&self.coordinates.0]
+                    .address_book
+                    .add_card(new_card);
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Added {} to contacts.",
+                        address
+                    ))));
+            }
+            AddressAction::EditContact => {
+                let account_pos = context
+                    .accounts
+                    .get_index_of(&self.coordinates.0)
+                    .unwrap_or(0);
+                if let Some(card) = context.accounts[&self.coordinates.0]
+                    .address_book
+                    .values()
+                    .find(|card| card.email().eq_ignore_ascii_case(&email))
+                {
+                    let manager = ContactManager::edit(card.clone(), account_pos, context);
+                    context
+                        .replies
+                        .push_back(UIEvent::Action(Tab(New(Some(Box::new(manager))))));
                 }
-                None
             }
-
-            let ret = find_attachment(root_attachment, &path[1..]);
-            if lidx == 0 {
-                return ret.and_then(|a| {
-                    if a.content_disposition.kind.is_attachment()
-                        || a.content_type == "message/rfc822"
-                    {
-                        Some(a)
-                    } else {
-                        None
-                    }
-                });
-            } else {
-                return ret;
+            AddressAction::SearchFrom => {
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Listing(Search(format!(
+                        "from:\"{}\"",
+                        email
+                    )))));
+            }
+            AddressAction::Copy => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::CopyToClipboard(email)));
             }
         }
+    }
+
+    /// Open the envelope at `(mailbox_hash, env_hash)` of this account in a new tab.
+    fn open_in_new_tab(
+        &self,
+        (mailbox_hash, env_hash): (MailboxHash, EnvelopeHash),
+        context: &mut Context,
+    ) {
+        let view = MailView::new(
+            (self.coordinates.0, mailbox_hash, env_hash),
+            None,
+            None,
+            context,
+        );
         context
             .replies
-            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
-                "Attachment `{}` not found.",
-                lidx
-            ))));
-        None
+            .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
     }
-}
 
-impl Component for MailView {
-    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
-        if !self.is_dirty() && !self.force_draw_headers {
-            return;
+    /// A dialog with the options of the List-Unsubscribe header described, so that nothing is sent
+    /// or opened before the user picks one.
+    fn unsubscribe_dialog(
+        &self,
+        actions: &list_management::ListActions,
+        context: &Context,
+    ) -> Option<UIDialog<UnsubscribeAction>> {
+        let from = crate::components::mail::get_display_name(context, self.coordinates.0);
+        let mut entries = vec![];
+        for option in actions.unsubscribe.iter().flatten() {
+            match option {
+                list_management::ListAction::Email(mailto) => {
+                    entries.push(UnsubscribeAction::Email(
+                        String::from_utf8_lossy(mailto).into_owned(),
+                    ));
+                }
+                list_management::ListAction::Url(url) => {
+                    let url = String::from_utf8_lossy(url).into_owned();
+                    #[cfg(feature = "http")]
+                    {
+                        if actions.unsubscribe_one_click && url.starts_with("https://") {
+                            entries.push(UnsubscribeAction::OneClick(url.clone()));
+                        }
+                    }
+                    entries.push(UnsubscribeAction::Url(url));
+                }
+                list_management::ListAction::No => {}
+            }
         }
-        self.dirty = false;
-        let upper_left = upper_left!(area);
-        let bottom_right = bottom_right!(area);
+        if entries.is_empty() {
+            return None;
+        }
+        Some(Selector::new(
+            &format!("unsubscribe from {}", actions.id.unwrap_or("mailing list")),
+            entries
+                .into_iter()
+                .map(|action| {
+                    let desc = action.description(&from);
+                    (action, desc)
+                })
+                .collect(),
+            true,
+            Some(Box::new(
+                move |id: ComponentId, results: &[UnsubscribeAction]| {
+                    Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
+                },
+            )),
+            context,
+        ))
+    }
 
-        let y: usize = {
+    fn unsubscribe(&mut self, action: &UnsubscribeAction, context: &mut Context) {
+        let from = crate::components::mail::get_display_name(context, self.coordinates.0);
+        melib::log_target(
+            "ui",
+            format!("Unsubscribing: {}", action.description(&from)),
+            melib::INFO,
+        );
+        match action {
+            UnsubscribeAction::Email(mailto) => {
+                let mut draft: Draft = match Mailto::try_from(mailto.as_bytes()) {
+                    Ok(mailto) => mailto.into(),
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Couldn't parse unsubscribe address {}: {}",
+                                mailto, err
+                            )),
+                        ));
+                        return;
+                    }
+                };
+                draft.set_header("From", from);
+                if let Err(err) = super::compose::send_draft(
+                    ToggleFlag::False,
+                    context,
+                    self.coordinates.0,
+                    draft,
+                    SpecialUsageMailbox::Sent,
+                    Flag::SEEN,
+                    true,
+                ) {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                            "Couldn't send unsubscribe e-mail: {}",
+                            err
+                        ))));
+                }
+            }
+            #[cfg(feature = "http")]
+            UnsubscribeAction::OneClick(url) => {
+                let account = &mut context.accounts[&self.coordinates.0];
+                let handle = account
+                    .job_executor
+                    .spawn_specialized(list_management::unsubscribe_one_click(url.clone()));
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: format!("one-click unsubscribe at {}", url).into(),
+                        handle,
+                        logging_level: melib::LoggingLevel::INFO,
+                        on_finish: None,
+                    },
+                );
+            }
+            UnsubscribeAction::Url(url) => {
+                match Command::new("xdg-open")
+                    .arg(url)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                {
+                    Ok(child) => {
+                        context.children.push(child);
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Couldn't launch xdg-open: {}",
+                                err
+                            )),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy `target` to the clipboard. Links are numbered as in URL mode.
+    fn yank(&mut self, target: YankTarget, context: &mut Context) {
+        let text = if let YankTarget::Body | YankTarget::Link(_) = target {
+            let (body_text, links) =
+                match self.state {
+                    MailViewState::Init { .. } => {
+                        self.init_futures(context);
+                        return;
+                    }
+                    MailViewState::Error { .. } | MailViewState::LoadingBody { .. } => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage("Message body is not loaded.".to_string()),
+                        ));
+                        return;
+                    }
+                    MailViewState::Loaded {
+                        ref body_text,
+                        ref links,
+                        ..
+                    } => (body_text, links),
+                };
+            match target {
+                YankTarget::Link(lidx) => {
+                    if links.is_empty() {
+                        /* Links are found when URL mode is first drawn. */
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(
+                                "Toggle URL mode to number the links first.".to_string(),
+                            ),
+                        ));
+                        return;
+                    }
+                    match links.get(lidx).and_then(|l| body_text.get(l.start..l.end)) {
+                        Some(url) => url.to_string(),
+                        None => {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(format!("Link `{}` not found.", lidx)),
+                            ));
+                            return;
+                        }
+                    }
+                }
+                _ => body_text.clone(),
+            }
+        } else {
+            let account = &context.accounts[&self.coordinates.0];
+            if !account.contains_key(self.coordinates.2) {
+                /* The envelope has been renamed or removed, so wait for the appropriate event to
+                 * arrive */
+                return;
+            }
+            let envelope: EnvelopeRef = account.collection.get_env(self.coordinates.2);
+            match target {
+                YankTarget::MessageId => envelope.message_id_display().to_string(),
+                YankTarget::Sender => match envelope.from().first() {
+                    Some(address) => address.get_email(),
+                    None => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage("Message has no sender.".to_string()),
+                        ));
+                        return;
+                    }
+                },
+                _ => envelope.subject().to_string(),
+            }
+        };
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::CopyToClipboard(text)));
+    }
+
+    /// Import the secret key of the viewed Autocrypt Setup Message, encrypted with `setup_code`.
+    #[cfg(feature = "gpgme")]
+    fn import_autocrypt_setup(&mut self, setup_code: &str, context: &mut Context) {
+        use melib::email::autocrypt;
+        let setup_code = if let Some(setup_code) = autocrypt::format_setup_code(setup_code) {
+            setup_code
+        } else {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "The Setup Code must have 36 digits.".to_string(),
+                )));
+            return;
+        };
+        let body = if let MailViewState::Loaded { ref body, .. } = self.state {
+            body
+        } else {
+            return;
+        };
+        let armored = body
+            .attachments()
+            .into_iter()
+            .find(|a| {
+                a.mime_type()
+                    .eq_ignore_ascii_case(autocrypt::SETUP_MESSAGE_CONTENT_TYPE)
+            })
+            .and_then(|a| {
+                autocrypt::setup_message_armored_key(&String::from_utf8_lossy(&decode(&a, None)))
+                    .map(String::from)
+            });
+        let armored = if let Some(armored) = armored {
+            armored
+        } else {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "This message is not an Autocrypt Setup Message.".to_string(),
+                )));
+            return;
+        };
+        let account = &mut context.accounts[&self.coordinates.0];
+        let handle = account.job_executor.spawn_specialized(
+            crate::components::mail::pgp::import_autocrypt_setup(armored, setup_code),
+        );
+        account.insert_job(
+            handle.job_id,
+            JobRequest::Generic {
+                name: "Autocrypt Setup Message import".into(),
+                handle,
+                logging_level: melib::LoggingLevel::INFO,
+                on_finish: None,
+            },
+        );
+    }
+
+    fn open_attachment(
+        &'_ self,
+        lidx: usize,
+        context: &mut Context,
+    ) -> Option<&'_ melib::Attachment> {
+        let display = if let MailViewState::Loaded { ref display, .. } = self.state {
+            display
+        } else {
+            return None;
+        };
+        if let Some(path) =
+            self.attachment_paths.get(lidx).and_then(
+                |path| {
+                    if path.len() > 0 {
+                        Some(path)
+                    } else {
+                        None
+                    }
+                },
+            )
+        {
+            let first = path[0];
+            use AttachmentDisplay::*;
+            let root_attachment = match &display[first] {
+                Alternative {
+                    inner,
+                    shown_display: _,
+                    display: _,
+                }
+                | InlineText {
+                    inner,
+                    text: _,
+                    comment: _,
+                }
+                | InlineOther { inner }
+                | Attachment { inner }
+                | SignedPending {
+                    inner,
+                    display: _,
+                    handle: _,
+                    job_id: _,
+                }
+                | SignedFailed {
+                    inner,
+                    display: _,
+                    error: _,
+                }
+                | SignedVerified {
+                    inner,
+                    display: _,
+                    description: _,
+                }
+                | SignedUnverified { inner, display: _ }
+                | EncryptedPending { inner, handle: _ }
+                | EncryptedFailed { inner, error: _ }
+                | EncryptedSuccess {
+                    inner: _,
+                    plaintext: inner,
+                    plaintext_display: _,
+                    description: _,
+                } => inner,
+            };
+            fn find_attachment<'a>(
+                a: &'a melib::Attachment,
+                path: &[usize],
+            ) -> Option<&'a melib::Attachment> {
+                if path.is_empty() {
+                    return Some(a);
+                }
+                match a.content_type {
+                    ContentType::Multipart { ref parts, .. } => {
+                        let first = path[0];
+                        if first < parts.len() {
+                            return find_attachment(&parts[first], &path[1..]);
+                        }
+                    }
+                    _ => {}
+                }
+                None
+            }
+
+            let ret = find_attachment(root_attachment, &path[1..]);
+            if lidx == 0 {
+                return ret.and_then(|a| {
+                    if a.content_disposition.kind.is_attachment()
+                        || a.content_type == "message/rfc822"
+                    {
+                        Some(a)
+                    } else {
+                        None
+                    }
+                });
+            } else {
+                return ret;
+            }
+        }
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                "Attachment `{}` not found.",
+                lidx
+            ))));
+        None
+    }
+
+    /// Whether attachment `lidx`, or any part of the message if `None`, was decrypted.
+    #[cfg(feature = "gpgme")]
+    fn is_decrypted(&self, lidx: Option<usize>) -> bool {
+        let display = match self.state {
+            MailViewState::Loaded { ref display, .. } => display,
+            _ => return false,
+        };
+        let is_decrypted =
+            |d: &AttachmentDisplay| matches!(d, AttachmentDisplay::EncryptedSuccess { .. });
+        match lidx {
+            Some(lidx) => self
+                .attachment_paths
+                .get(lidx)
+                .and_then(|path| path.first())
+                .and_then(|first| display.get(*first))
+                .map(is_decrypted)
+                .unwrap_or(false),
+            None => display.iter().any(is_decrypted),
+        }
+    }
+
+    /// Whether the temporary files of attachment `lidx`, or of any part of the message if
+    /// `None`, are shredded when deleted.
+    #[cfg(feature = "gpgme")]
+    fn shred_temp_files(&self, lidx: Option<usize>, context: &Context) -> bool {
+        self.is_decrypted(lidx)
+            && *mailbox_settings!(
+                context[self.coordinates.0][&self.coordinates.1]
+                    .pgp
+                    .shred_temp_files
+            )
+    }
+
+    #[cfg(not(feature = "gpgme"))]
+    fn shred_temp_files(&self, _lidx: Option<usize>, _context: &Context) -> bool {
+        false
+    }
+}
+
+impl Component for MailView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.is_dirty() && !self.force_draw_headers {
+            return;
+        }
+        self.dirty = false;
+        let upper_left = upper_left!(area);
+        let bottom_right = bottom_right!(area);
+
+        if let MailViewState::Init { .. } = self.state {
+            /* Views restored from a saved session are created before their mailbox is loaded */
+            let account = &mut context.accounts[&self.coordinates.0];
+            if account.contains_key(self.coordinates.2) {
+                self.init_futures(context);
+            } else if account.mailbox_entries.contains_key(&self.coordinates.1) {
+                let _ = account.load(self.coordinates.1);
+                return;
+            }
+        }
+
+        let y: usize = {
             let account = &context.accounts[&self.coordinates.0];
             if !account.contains_key(self.coordinates.2) {
                 /* The envelope has been renamed or removed, so wait for the appropriate event to
@@ -1049,6 +1826,17 @@ impl Component for MailView {
                 return;
             }
             let envelope: EnvelopeRef = account.collection.get_env(self.coordinates.2);
+            /* Encrypted mail shows the values of its protected headers. */
+            let protected_envelope = if let MailViewState::Loaded { ref display, .. } = self.state {
+                AttachmentDisplay::find_protected_headers(display).map(|plaintext| {
+                    let mut protected_envelope = (*envelope).clone();
+                    let _ = protected_envelope.populate_headers(plaintext.raw());
+                    protected_envelope
+                })
+            } else {
+                None
+            };
+            let envelope: &Envelope = protected_envelope.as_ref().unwrap_or(&envelope);
 
             let headers = crate::conf::value(context, "mail.view.headers");
             let headers_names = crate::conf::value(context, "mail.view.headers_names");
@@ -1111,11 +1899,24 @@ impl Component for MailView {
                         })+
                     };
                 }
+                let show_sender_timezone = *mailbox_settings!(
+                    context[self.coordinates.0][&self.coordinates.1]
+                        .pager
+                        .show_sender_timezone
+                );
                 print_header!(
-                    ("Date:", envelope.date_as_str()),
+                    ("Date:", display_date(&envelope, show_sender_timezone)),
                     ("From:", envelope.field_from_to_string()),
-                    ("To:", envelope.field_to_to_string()),
                 );
+                if !account.dismissed_warnings.contains(&envelope) {
+                    for warning in crate::phishing::check(
+                        &envelope,
+                        account.address_book.values().map(|card| card.email()),
+                    ) {
+                        print_header!(("Warning:", format!("⚠ {}", warning)));
+                    }
+                }
+                print_header!(("To:", envelope.field_to_to_string()));
                 if envelope.other_headers().contains_key("Cc")
                     && !envelope.other_headers()["Cc"].is_empty()
                 {
@@ -1125,6 +1926,44 @@ impl Component for MailView {
                     ("Subject:", envelope.subject()),
                     ("Message-ID:", format!("<{}>", envelope.message_id_raw()))
                 );
+                if let Some(note) = account.notes.get(&envelope) {
+                    if self.expand_note {
+                        print_header!(("Note:", note));
+                    } else {
+                        let mut first_line = note.lines().next().unwrap_or_default().to_string();
+                        if first_line.len() > 60 || first_line.len() < note.trim_end().len() {
+                            first_line.truncate_at_boundary(60);
+                            first_line.push('…');
+                        }
+                        print_header!(("Note:", first_line));
+                    }
+                }
+                let alternative = if let MailViewState::Loaded { ref display, .. } = self.state {
+                    AttachmentDisplay::find_alternative(display).map(|(shown_display, parts)| {
+                        format!(
+                            "{} ({}/{})",
+                            parts[shown_display].attachment().content_type(),
+                            shown_display + 1,
+                            parts.len()
+                        )
+                    })
+                } else {
+                    None
+                };
+                if let Some(alternative) = alternative {
+                    print_header!(("Alternative:", alternative));
+                }
+                let headers_decoded_as_latin1 =
+                    if let MailViewState::Loaded { ref bytes, .. } = self.state {
+                        melib::email::parser::headers::headers_raw(bytes)
+                            .map(|(_, raw)| std::str::from_utf8(raw).is_err())
+                            .unwrap_or(false)
+                    } else {
+                        false
+                    };
+                if headers_decoded_as_latin1 {
+                    print_header!(("Charset:", "⚠ headers decoded as iso-8859-1"));
+                }
                 if self.expand_headers {
                     if let Some(val) = envelope.in_reply_to_display() {
                         print_header!(
@@ -1146,6 +1985,7 @@ impl Component for MailView {
                     ref archive,
                     ref post,
                     ref unsubscribe,
+                    ..
                 }) = list_management::ListActions::detect(&envelope)
                 {
                     let mut x = get_x(upper_left);
@@ -1300,25 +2140,38 @@ impl Component for MailView {
             } = self.state
             {
                 (body, body_text, bytes, links)
-            } else if let MailViewState::Error { ref err } = self.state {
+            } else if let MailViewState::Error { ref err, ref retry } = self.state {
                 clear_area(
                     grid,
                     (set_y(upper_left, y), bottom_right),
                     self.theme_default,
                 );
+                let report = self.error_report(err, retry.is_some(), context);
+                let width = width!(area).saturating_sub(2);
+                let lines = report.lines().flat_map(|line| {
+                    if line.is_empty() {
+                        vec![String::new()]
+                    } else {
+                        line.split_lines(width)
+                    }
+                });
+                for (i, line) in lines.enumerate() {
+                    if y + 1 + i > get_y(bottom_right) {
+                        break;
+                    }
+                    write_string_to_grid(
+                        &line,
+                        grid,
+                        self.theme_default.fg,
+                        self.theme_default.bg,
+                        self.theme_default.attrs,
+                        ((get_x(upper_left) + 1, y + 1 + i), bottom_right),
+                        None,
+                    );
+                }
                 context
                     .dirty_areas
                     .push_back((set_y(upper_left, y), bottom_right));
-                context.replies.push_back(UIEvent::Notification(
-                    Some("Failed to open e-mail".to_string()),
-                    err.to_string(),
-                    Some(NotificationType::Error(err.kind)),
-                ));
-                log(
-                    format!("Failed to open envelope: {}", err.to_string()),
-                    ERROR,
-                );
-                self.init_futures(context);
                 return;
             } else {
                 clear_area(
@@ -1338,7 +2191,14 @@ impl Component for MailView {
                     let mut text = "Viewing attachment. Press `r` to return \n".to_string();
                     if let Some(attachment) = self.open_attachment(aidx, context) {
                         if attachment.is_html() {
-                            self.subview = Some(Box::new(HtmlView::new(&attachment, context)));
+                            let mut view = HtmlView::new(
+                                &attachment,
+                                self.coordinates.0,
+                                Some(self.coordinates.1),
+                                context,
+                            );
+                            view.set_shred_temp_files(self.shred_temp_files(Some(aidx), context));
+                            self.subview = Some(Box::new(view));
                             self.mode = ViewMode::Subview;
                         } else {
                             text.push_str(&attachment.text());
@@ -1355,7 +2215,10 @@ impl Component for MailView {
                     }
                 }
                 ViewMode::Normal if body.is_html() => {
-                    self.subview = Some(Box::new(HtmlView::new(&body, context)));
+                    let mut view =
+                        HtmlView::new(&body, self.coordinates.0, Some(self.coordinates.1), context);
+                    view.set_shred_temp_files(self.shred_temp_files(None, context));
+                    self.subview = Some(Box::new(view));
                     self.mode = ViewMode::Subview;
                 }
                 ViewMode::Normal
@@ -1376,7 +2239,7 @@ impl Component for MailView {
                             _ => false,
                         } =>
                 {
-                    self.subview = Some(Box::new(HtmlView::new(
+                    let mut view = HtmlView::new(
                         &body
                             .content_type
                             .parts()
@@ -1384,12 +2247,21 @@ impl Component for MailView {
                             .into_iter()
                             .find(|a| a.is_html())
                             .unwrap_or(&body),
+                        self.coordinates.0,
+                        Some(self.coordinates.1),
                         context,
-                    )));
+                    );
+                    view.set_shred_temp_files(self.shred_temp_files(None, context));
+                    self.subview = Some(Box::new(view));
                     self.mode = ViewMode::Subview;
                     self.initialised = false;
                 }
-                ViewMode::Subview | ViewMode::ContactSelector(_) => {}
+                ViewMode::Subview
+                | ViewMode::ContactSelector(_)
+                | ViewMode::AddressSelector(_)
+                | ViewMode::AddressActions(_)
+                | ViewMode::Unsubscribe(_)
+                | ViewMode::Replies(_) => {}
                 ViewMode::Source(source) => {
                     let text = {
                         if source == Source::Raw {
@@ -1431,6 +2303,12 @@ impl Component for MailView {
                     };
                     let colors = crate::conf::value(context, "mail.view.body");
                     self.pager = Pager::from_string(text, Some(context), None, None, colors);
+                    if let Some(ref pattern) = self.highlight {
+                        self.pager.process_event(
+                            &mut UIEvent::Action(Listing(Search(pattern.to_string()))),
+                            context,
+                        );
+                    }
                 }
                 /*
                 ViewMode::Ansi(ref buf) => {
@@ -1446,11 +2324,10 @@ impl Component for MailView {
                 }
                 */
                 ViewMode::Url => {
-                    let mut text = body_text.clone();
                     if links.is_empty() {
                         let finder = LinkFinder::new();
                         *links = finder
-                            .links(&text)
+                            .links(body_text)
                             .filter_map(|l| {
                                 if *l.kind() == linkify::LinkKind::Url {
                                     Some(Link {
@@ -1470,18 +2347,27 @@ impl Component for MailView {
                             })
                             .collect::<Vec<Link>>();
                     }
-                    for (lidx, l) in links.iter().enumerate().rev() {
-                        text.insert_str(l.start, &format!("[{}]", lidx));
+                    let mut text = String::with_capacity(body_text.len() + 4 * links.len());
+                    let mut prev = 0;
+                    for (lidx, l) in links.iter().enumerate() {
+                        text.push_str(&body_text[prev..l.start]);
+                        text.push_str(&format!("[{}]", lidx));
+                        prev = l.start;
                     }
+                    text.push_str(&body_text[prev..]);
                     if !text.ends_with("\n\n") {
                         text.push_str("\n\n");
                     }
                     text.push_str(&self.attachment_tree);
 
                     let cursor_pos = self.pager.cursor_pos();
+                    let no_wrap = self.pager.reflow() == Reflow::No;
                     let colors = crate::conf::value(context, "mail.view.body");
                     self.pager =
                         Pager::from_string(text, Some(context), Some(cursor_pos), None, colors);
+                    if no_wrap {
+                        self.pager.set_reflow(Reflow::No);
+                    }
                     self.subview = None;
                 }
                 _ => {
@@ -1495,9 +2381,13 @@ impl Component for MailView {
                     } else {
                         self.pager.cursor_pos()
                     };
+                    let no_wrap = self.pager.reflow() == Reflow::No;
                     let colors = crate::conf::value(context, "mail.view.body");
                     self.pager =
                         Pager::from_string(text, Some(context), Some(cursor_pos), None, colors);
+                    if no_wrap {
+                        self.pager.set_reflow(Reflow::No);
+                    }
                     self.subview = None;
                 }
             };
@@ -1517,7 +2407,7 @@ impl Component for MailView {
                     .draw(grid, (set_y(upper_left, y), bottom_right), context);
             }
         }
-        if let ViewMode::ContactSelector(ref mut s) = self.mode {
+        if let Some(s) = self.mode.selector_mut() {
             s.draw(grid, area, context);
         }
     }
@@ -1556,6 +2446,36 @@ impl Component for MailView {
                 self.set_dirty(true);
                 return true;
             }
+            (ViewMode::AddressSelector(ref s), UIEvent::FinishedUIDialog(id, results))
+                if *id == s.id() =>
+            {
+                self.mode = ViewMode::Normal;
+                if let Some(address) = results
+                    .downcast_ref::<Vec<Address>>()
+                    .and_then(|results| results.first())
+                {
+                    self.mode = ViewMode::AddressActions(
+                        self.address_actions_dialog(address.clone(), context),
+                    );
+                }
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::AddressActions(ref s), UIEvent::FinishedUIDialog(id, results))
+                if *id == s.id() =>
+            {
+                self.mode = ViewMode::Normal;
+                if let Some((address, action)) = results
+                    .downcast_ref::<Vec<(Address, AddressAction)>>()
+                    .and_then(|results| results.first())
+                {
+                    self.perform_address_action(address, *action, context);
+                }
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
             (ViewMode::ContactSelector(ref mut s), _) => {
                 if s.process_event(event, context) {
                     return true;
@@ -1564,6 +2484,64 @@ impl Component for MailView {
                     return true;
                 }
             }
+            (ViewMode::AddressSelector(ref mut s), _) => {
+                if s.process_event(event, context) {
+                    return true;
+                }
+                if self.pager.process_event(event, context) {
+                    return true;
+                }
+            }
+            (ViewMode::AddressActions(ref mut s), _) => {
+                if s.process_event(event, context) {
+                    return true;
+                }
+                if self.pager.process_event(event, context) {
+                    return true;
+                }
+            }
+            (ViewMode::Unsubscribe(ref s), UIEvent::FinishedUIDialog(id, results))
+                if *id == s.id() =>
+            {
+                self.mode = ViewMode::Normal;
+                if let Some(action) = results
+                    .downcast_ref::<Vec<UnsubscribeAction>>()
+                    .and_then(|results| results.first())
+                {
+                    self.unsubscribe(action, context);
+                }
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::Unsubscribe(ref mut s), _) => {
+                if s.process_event(event, context) {
+                    return true;
+                }
+                if self.pager.process_event(event, context) {
+                    return true;
+                }
+            }
+            (ViewMode::Replies(ref s), UIEvent::FinishedUIDialog(id, results)) if *id == s.id() => {
+                self.mode = ViewMode::Normal;
+                if let Some(coordinates) = results
+                    .downcast_ref::<Vec<(MailboxHash, EnvelopeHash)>>()
+                    .and_then(|results| results.first())
+                {
+                    self.open_in_new_tab(*coordinates, context);
+                }
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::Replies(ref mut s), _) => {
+                if s.process_event(event, context) {
+                    return true;
+                }
+                if self.pager.process_event(event, context) {
+                    return true;
+                }
+            }
             _ => match event {
                 UIEvent::Input(ref key)
                     if shortcut!(key == shortcuts[Pager::DESCRIPTION]["scroll_up"])
@@ -1623,6 +2601,9 @@ impl Component for MailView {
                                             .get_env_mut(self.coordinates.2)
                                             .populate_headers(&bytes);
                                     }
+                                    #[cfg(feature = "gpgme")]
+                                    context.accounts[&self.coordinates.0]
+                                        .harvest_autocrypt(self.coordinates.2);
                                     let body = AttachmentBuilder::new(&bytes).build();
                                     let display = Self::attachment_to(
                                         &body,
@@ -1645,7 +2626,7 @@ impl Component for MailView {
                                     };
                                 }
                                 Ok(Some(Err(err))) => {
-                                    self.state = MailViewState::Error { err };
+                                    self.set_load_error(err, context);
                                 }
                             }
                         }
@@ -1705,12 +2686,20 @@ impl Component for MailView {
                                                 let plaintext =
                                                     AttachmentBuilder::new(&decrypted_bytes)
                                                         .build();
-                                                let plaintext_display = Self::attachment_to(
+                                                let mut plaintext_display = Self::attachment_to(
                                                     &plaintext,
                                                     context,
                                                     self.coordinates,
                                                     &mut self.active_jobs,
                                                 );
+                                                if melib::pgp::has_protected_headers(&plaintext) {
+                                                    /* Its headers are shown instead. */
+                                                    plaintext_display.retain(|d| {
+                                                        !melib::pgp::is_legacy_display_part(
+                                                            d.attachment(),
+                                                        )
+                                                    });
+                                                }
                                                 *d = AttachmentDisplay::EncryptedSuccess {
                                                     inner: std::mem::replace(
                                                         inner,
@@ -1793,6 +2782,12 @@ impl Component for MailView {
                 self.perform_action(PendingReplyAction::ReplyToAuthor, context);
                 return true;
             }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[MailView::DESCRIPTION]["forward"]) =>
+            {
+                self.perform_action(PendingReplyAction::Forward, context);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[MailView::DESCRIPTION]["edit"]) =>
             {
@@ -1848,7 +2843,7 @@ impl Component for MailView {
                                                 .unwrap_or_else(|| "Not found".into()),
                                                 err.to_string()
                                             );
-                                            log(&err_string, ERROR);
+                                            log_target("ui", &err_string, ERROR);
                                             context.replies.push_back(UIEvent::Notification(
                                                     Some("Failed to open e-mail".to_string()),
                                                     err_string,
@@ -1865,7 +2860,7 @@ impl Component for MailView {
                 return true;
             }
             UIEvent::Input(ref key)
-                if !self.mode.is_contact_selector()
+                if !self.mode.is_selector()
                     && shortcut!(
                         key == shortcuts[MailView::DESCRIPTION]["add_addresses_to_contacts"]
                     ) =>
@@ -1896,28 +2891,146 @@ impl Component for MailView {
                 self.initialised = false;
                 return true;
             }
-            UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt(''))
-                if self.mode.is_contact_selector() =>
+            UIEvent::Input(ref key)
+                if !self.mode.is_selector()
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["address_actions"]) =>
             {
-                self.mode = ViewMode::Normal;
-                self.set_dirty(true);
+                let account = &context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    return true;
+                }
+                let envelope: EnvelopeRef = account.collection.get_env(self.coordinates.2);
+                let mut entries: Vec<(Address, String)> = Vec::new();
+                for addr in envelope
+                    .from()
+                    .iter()
+                    .chain(envelope.to().iter())
+                    .chain(envelope.cc.iter())
+                {
+                    if !entries
+                        .iter()
+                        .any(|(a, _)| a.get_email().eq_ignore_ascii_case(&addr.get_email()))
+                    {
+                        entries.push((addr.clone(), addr.to_string()));
+                    }
+                }
+                drop(envelope);
+                if entries.len() == 1 {
+                    let address = entries.remove(0).0;
+                    self.mode =
+                        ViewMode::AddressActions(self.address_actions_dialog(address, context));
+                } else if !entries.is_empty() {
+                    self.mode = ViewMode::AddressSelector(Selector::new(
+                        "select address",
+                        entries,
+                        true,
+                        Some(Box::new(move |id: ComponentId, results: &[Address]| {
+                            Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
+                        })),
+                        context,
+                    ));
+                }
+                self.dirty = true;
                 self.initialised = false;
                 return true;
             }
-            UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) if !self.cmd_buf.is_empty() => {
-                self.cmd_buf.clear();
+            UIEvent::Input(ref key)
+                if !self.mode.is_selector()
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["go_to_parent"]) =>
+            {
+                let account = &context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    return true;
+                }
+                if let Some(coordinates) = account
+                    .collection
+                    .parent_of(self.coordinates.2, self.coordinates.1)
+                {
+                    self.open_in_new_tab(coordinates, context);
+                    return true;
+                }
+                let envelope: EnvelopeRef = account.collection.get_env(self.coordinates.2);
+                let message = match envelope
+                    .in_reply_to()
+                    .or_else(|| envelope.references().last().copied())
+                {
+                    None => "This message is not a reply.".to_string(),
+                    #[cfg(feature = "sqlite3")]
+                    Some(parent)
+                        if *account.settings.conf.search_backend()
+                            == crate::conf::SearchBackend::Sqlite3
+                            && crate::sqlite3::contains_message_id(&parent.to_string())
+                                .unwrap_or(false) =>
+                    {
+                        format!(
+                            "{} is in the search index but not in a loaded mailbox.",
+                            parent
+                        )
+                    }
+                    Some(parent) => format!("{} is not in any loaded mailbox.", parent),
+                };
+                drop(envelope);
                 context
                     .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
                 return true;
             }
-            UIEvent::Input(Key::Char(c)) if c >= '0' && c <= '9' => {
-                self.cmd_buf.push(c);
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufSet(
-                        self.cmd_buf.clone(),
-                    )));
+            UIEvent::Input(ref key)
+                if !self.mode.is_selector()
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["list_replies"]) =>
+            {
+                let account = &context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    return true;
+                }
+                let replies = account
+                    .collection
+                    .replies_to(self.coordinates.2, self.coordinates.1);
+                if replies.is_empty() {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No known replies to this message.".to_string(),
+                        )));
+                    return true;
+                }
+                let entries = replies
+                    .into_iter()
+                    .map(|(mailbox_hash, env_hash)| {
+                        let envelope: EnvelopeRef = account.collection.get_env(env_hash);
+                        let desc = format!(
+                            "{} {} {} ({})",
+                            melib::datetime::timestamp_to_string(
+                                envelope.date(),
+                                Some("%Y-%m-%d %H:%M"),
+                                false
+                            ),
+                            envelope.field_from_to_string(),
+                            envelope.subject(),
+                            account.mailbox_entries[&mailbox_hash].name()
+                        );
+                        ((mailbox_hash, env_hash), desc)
+                    })
+                    .collect();
+                self.mode = ViewMode::Replies(Selector::new(
+                    "replies",
+                    entries,
+                    true,
+                    Some(Box::new(
+                        move |id: ComponentId, results: &[(MailboxHash, EnvelopeHash)]| {
+                            Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
+                        },
+                    )),
+                    context,
+                ));
+                self.dirty = true;
+                self.initialised = false;
+                return true;
+            }
+            UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) if self.mode.is_selector() => {
+                self.mode = ViewMode::Normal;
+                self.set_dirty(true);
+                self.initialised = false;
                 return true;
             }
             UIEvent::Input(ref key)
@@ -1953,14 +3066,10 @@ impl Component for MailView {
             }
             UIEvent::Input(ref key)
                 if (self.mode == ViewMode::Normal || self.mode == ViewMode::Subview)
-                    && !self.cmd_buf.is_empty()
+                    && context.count.is_some()
                     && shortcut!(key == shortcuts[MailView::DESCRIPTION]["open_mailcap"]) =>
             {
-                let lidx = self.cmd_buf.parse::<usize>().unwrap();
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                let lidx = context.count.unwrap();
                 match self.state {
                     MailViewState::Error { .. } | MailViewState::LoadingBody { .. } => {}
                     MailViewState::Loaded { .. } => {
@@ -1987,14 +3096,10 @@ impl Component for MailView {
             }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[MailView::DESCRIPTION]["open_attachment"])
-                    && !self.cmd_buf.is_empty()
+                    && context.count.is_some()
                     && (self.mode == ViewMode::Normal || self.mode == ViewMode::Subview) =>
             {
-                let lidx = self.cmd_buf.parse::<usize>().unwrap();
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                let lidx = context.count.unwrap();
                 match self.state {
                     MailViewState::Error { .. } | MailViewState::LoadingBody { .. } => {}
                     MailViewState::Loaded { .. } => {
@@ -2003,13 +3108,17 @@ impl Component for MailView {
                                 ContentType::MessageRfc822 => {
                                     match Mail::new(attachment.body().to_vec(), Some(Flag::SEEN)) {
                                         Ok(wrapper) => {
+                                            let mut view = EnvelopeView::new(
+                                                wrapper,
+                                                None,
+                                                None,
+                                                self.coordinates.0,
+                                            );
+                                            view.set_shred_temp_files(
+                                                self.shred_temp_files(Some(lidx), context),
+                                            );
                                             context.replies.push_back(UIEvent::Action(Tab(New(
-                                                Some(Box::new(EnvelopeView::new(
-                                                    wrapper,
-                                                    None,
-                                                    None,
-                                                    self.coordinates.0,
-                                                ))),
+                                                Some(Box::new(view)),
                                             ))));
                                         }
                                         Err(e) => {
@@ -2035,36 +3144,64 @@ impl Component for MailView {
                                         ),
                                     ));
                                 }
-                                ContentType::Other { .. } => {
-                                    let attachment_type = attachment.mime_type();
+                                ContentType::Other { .. } | ContentType::OctetStream { .. } => {
                                     let filename = attachment.filename();
-                                    if let Ok(command) = query_default_app(&attachment_type) {
-                                        let p = create_temp_file(
-                                            &decode(attachment, None),
-                                            filename.as_ref().map(|s| s.as_str()),
-                                            None,
-                                            true,
-                                        );
-                                        let (exec_cmd, argument) = desktop_exec_to_command(
-                                            &command,
-                                            p.path.display().to_string(),
-                                            false,
-                                        );
-                                        match Command::new(&exec_cmd)
-                                            .arg(&argument)
+                                    let mut p = match create_temp_file(
+                                        &decode(attachment, None),
+                                        filename.as_ref().map(|s| s.as_str()),
+                                        None,
+                                        true,
+                                    ) {
+                                        Ok(p) => p,
+                                        Err(err) => {
+                                            context.replies.push_back(UIEvent::StatusEvent(
+                                                StatusEvent::DisplayMessage(format!(
+                                                    "Could not create temporary file: {}",
+                                                    err
+                                                )),
+                                            ));
+                                            return true;
+                                        }
+                                    };
+                                    p.set_shred(self.shred_temp_files(Some(lidx), context));
+                                    let path = p.path.display().to_string();
+                                    let mut attachment_type = attachment.mime_type();
+                                    if matches!(
+                                        attachment.content_type(),
+                                        ContentType::OctetStream { .. }
+                                    ) {
+                                        /* Look up the application of the actual content. */
+                                        if let Ok(guess) = query_mime_info(&p.path) {
+                                            let guess = String::from_utf8_lossy(&guess);
+                                            if guess.contains('/') {
+                                                attachment_type = guess.into_owned();
+                                            }
+                                        }
+                                    }
+                                    let command = attachment_opener(
+                                        context,
+                                        self.coordinates.0,
+                                        Some(self.coordinates.1),
+                                        &attachment_type,
+                                        &path,
+                                    );
+                                    if let Some(command) = command {
+                                        match Command::new(&command[0])
+                                            .args(&command[1..])
                                             .stdin(Stdio::piped())
                                             .stdout(Stdio::piped())
                                             .spawn()
                                         {
                                             Ok(child) => {
-                                                context.temp_files.push(p);
+                                                self.temp_files.push(p);
                                                 context.children.push(child);
                                             }
                                             Err(err) => {
                                                 context.replies.push_back(UIEvent::StatusEvent(
                                                     StatusEvent::DisplayMessage(format!(
-                                                        "Failed to start `{} {}`: {}",
-                                                        &exec_cmd, &argument, err
+                                                        "Failed to start `{}`: {}",
+                                                        command.join(" "),
+                                                        err
                                                     )),
                                                 ));
                                             }
@@ -2086,14 +3223,6 @@ impl Component for MailView {
                                 ));
                                     }
                                 }
-                                ContentType::OctetStream { ref name } => {
-                                    context.replies.push_back(UIEvent::StatusEvent(
-                                        StatusEvent::DisplayMessage(format!(
-                                "Failed to open {}. application/octet-stream isn't supported yet",
-                                name.as_ref().map(|n| n.as_str()).unwrap_or("file")
-                            )),
-                                    ));
-                                }
                             }
                         }
                     }
@@ -2103,6 +3232,29 @@ impl Component for MailView {
                 }
                 return true;
             }
+            UIEvent::Timer(id) if matches!(self.state, MailViewState::Error { retry: Some(ref timer), .. } if timer.id() == id) =>
+            {
+                self.retry_load(context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if matches!(self.state, MailViewState::Error { .. })
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["retry"]) =>
+            {
+                self.load_retries = 0;
+                self.retry_load(context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if matches!(self.state, MailViewState::Error { .. })
+                    && shortcut!(
+                        key == shortcuts[MailView::DESCRIPTION]["toggle_expand_headers"]
+                    ) =>
+            {
+                self.expand_error = !self.expand_error;
+                self.set_dirty(true);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
                     && shortcut!(
@@ -2114,15 +3266,73 @@ impl Component for MailView {
                 return true;
             }
             UIEvent::Input(ref key)
-                if !self.cmd_buf.is_empty()
+                if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["toggle_note"]) =>
+            {
+                self.expand_note = !self.expand_note;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["view_mime_tree"]) =>
+            {
+                if let MailViewState::Loaded { ref body, .. } = self.state {
+                    let mut view =
+                        MimeTree::new(body, (self.coordinates.0, self.coordinates.1), context);
+                    view.set_shred_temp_files(self.shred_temp_files(None, context));
+                    self.subview = Some(Box::new(view));
+                    self.mode = ViewMode::Subview;
+                    self.set_dirty(true);
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["cycle_alternative"]) =>
+            {
+                let cycled = if let MailViewState::Loaded {
+                    ref mut display, ..
+                } = self.state
+                {
+                    AttachmentDisplay::cycle_alternative(display)
+                } else {
+                    false
+                };
+                if !cycled {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "Message has no alternative parts.".to_string(),
+                        )));
+                    return true;
+                }
+                let mut new_body_text = String::new();
+                if let MailViewState::Loaded { ref display, .. } = self.state {
+                    new_body_text = self.attachment_displays_to_text(&display, context, true);
+                    let (paths, attachment_tree_s) = self.attachment_displays_to_tree(&display);
+                    self.attachment_tree = attachment_tree_s;
+                    self.attachment_paths = paths;
+                }
+                if let MailViewState::Loaded {
+                    ref mut body_text,
+                    ref mut links,
+                    ..
+                } = self.state
+                {
+                    links.clear();
+                    *body_text = new_body_text;
+                }
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if context.count.is_some()
                     && self.mode == ViewMode::Url
                     && shortcut!(key == shortcuts[MailView::DESCRIPTION]["go_to_url"]) =>
             {
-                let lidx = self.cmd_buf.parse::<usize>().unwrap();
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                let lidx = context.count.unwrap();
                 match self.state {
                     MailViewState::Init { .. } => {
                         self.init_futures(context);
@@ -2173,6 +3383,15 @@ impl Component for MailView {
                 }
                 return true;
             }
+            UIEvent::Input(ref key)
+                if context.count.is_some()
+                    && self.mode == ViewMode::Url
+                    && shortcut!(key == shortcuts[MailView::DESCRIPTION]["yank_url"]) =>
+            {
+                let lidx = context.count.unwrap();
+                self.yank(YankTarget::Link(lidx), context);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
                     && shortcut!(key == shortcuts[MailView::DESCRIPTION]["toggle_url_mode"]) =>
@@ -2189,6 +3408,13 @@ impl Component for MailView {
             UIEvent::EnvelopeRename(old_hash, new_hash) if self.coordinates.2 == old_hash => {
                 self.coordinates.2 = new_hash;
             }
+            UIEvent::MailboxUpdate((account_hash, mailbox_hash))
+                if (account_hash, mailbox_hash) == (self.coordinates.0, self.coordinates.1)
+                    && matches!(self.state, MailViewState::Init { .. }) =>
+            {
+                self.set_dirty(true);
+                return false;
+            }
             UIEvent::Action(View(ViewAction::ExportMail(ref path))) => {
                 // Save entire message as eml
                 let account = &context.accounts[&self.coordinates.0];
@@ -2199,13 +3425,14 @@ impl Component for MailView {
                 }
                 let bytes = if let MailViewState::Loaded { ref bytes, .. } = self.state {
                     bytes
-                } else if let MailViewState::Error { ref err } = self.state {
+                } else if let MailViewState::Error { ref err, .. } = self.state {
                     context.replies.push_back(UIEvent::Notification(
                         Some("Failed to open e-mail".to_string()),
                         err.to_string(),
                         Some(NotificationType::Error(err.kind)),
                     ));
-                    log(
+                    log_target(
+                        "ui",
                         format!("Failed to open envelope: {}", err.to_string()),
                         ERROR,
                     );
@@ -2228,7 +3455,8 @@ impl Component for MailView {
                             err.to_string(),
                             Some(NotificationType::Error(melib::ErrorKind::External)),
                         ));
-                        log(
+                        log_target(
+                            "ui",
                             format!(
                                 "Failed to create file at {}: {}",
                                 path.display(),
@@ -2260,13 +3488,14 @@ impl Component for MailView {
                 }
                 let bytes = if let MailViewState::Loaded { ref bytes, .. } = self.state {
                     bytes
-                } else if let MailViewState::Error { ref err } = self.state {
+                } else if let MailViewState::Error { ref err, .. } = self.state {
                     context.replies.push_back(UIEvent::Notification(
                         Some("Failed to open e-mail".to_string()),
                         err.to_string(),
                         Some(NotificationType::Error(err.kind)),
                     ));
-                    log(
+                    log_target(
+                        "ui",
                         format!("Failed to open envelope: {}", err.to_string()),
                         ERROR,
                     );
@@ -2294,7 +3523,8 @@ impl Component for MailView {
                                 err.to_string(),
                                 Some(NotificationType::Error(melib::ErrorKind::External)),
                             ));
-                            log(
+                            log_target(
+                                "ui",
                                 format!(
                                     "Failed to create file at {}: {}",
                                     path.display(),
@@ -2325,7 +3555,8 @@ impl Component for MailView {
                                 err.to_string(),
                                 Some(NotificationType::Error(melib::ErrorKind::External)),
                             ));
-                            log(
+                            log_target(
+                                "ui",
                                 format!(
                                     "Failed to create file at {}: {}",
                                     path.display(),
@@ -2355,6 +3586,187 @@ impl Component for MailView {
                 }
                 return true;
             }
+            UIEvent::Action(View(ViewAction::ExportIcs(a_i))) => {
+                let command = if let Some(command) = mailbox_settings!(
+                    context[self.coordinates.0][&self.coordinates.1]
+                        .pager
+                        .ics_export_command
+                )
+                .as_ref()
+                {
+                    command.to_string()
+                } else {
+                    context.replies.push_back(UIEvent::StatusEvent(
+                        StatusEvent::DisplayMessage(
+                            "Set pager.ics_export_command in your configuration to export calendar events."
+                                .to_string(),
+                        ),
+                    ));
+                    return true;
+                };
+                let body = if let MailViewState::Loaded { ref body, .. } = self.state {
+                    body
+                } else {
+                    return true;
+                };
+                let is_calendar = |a: &Attachment| {
+                    let mime_type = a.mime_type();
+                    mime_type.eq_ignore_ascii_case("text/calendar")
+                        || mime_type.eq_ignore_ascii_case("application/ics")
+                };
+                let attachments: Vec<Attachment> = if let Some(a_i) = a_i {
+                    match self.open_attachment(a_i, context) {
+                        Some(a) if is_calendar(a) => vec![a.clone()],
+                        Some(a) => {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(format!(
+                                    "Attachment `{}` is of type {}, not text/calendar.",
+                                    a_i,
+                                    a.content_type()
+                                )),
+                            ));
+                            return true;
+                        }
+                        None => return true,
+                    }
+                } else {
+                    body.attachments()
+                        .into_iter()
+                        .filter(|a| is_calendar(a))
+                        .collect()
+                };
+                let mut calendar = melib::email::icalendar::Calendar::default();
+                for a in attachments.iter() {
+                    match melib::email::icalendar::Calendar::new(&String::from_utf8_lossy(&decode(
+                        a, None,
+                    ))) {
+                        Ok(c) => calendar.extend(c),
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not parse calendar attachment".to_string()),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                            return true;
+                        }
+                    }
+                }
+                if calendar.events.is_empty() {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No calendar events found in this e-mail.".to_string(),
+                        )));
+                    return true;
+                }
+                /* The command may take long or never exit, so don't wait for it on the UI
+                 * thread. */
+                let ics = calendar.to_ics();
+                let account = &mut context.accounts[&self.coordinates.0];
+                let handle = account
+                    .job_executor
+                    .spawn_blocking(async move { export_ics(&command, &ics) });
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: format!("export of {} calendar event(s)", calendar.events.len())
+                            .into(),
+                        handle,
+                        logging_level: melib::LoggingLevel::INFO,
+                        on_finish: None,
+                    },
+                );
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::Yank(target))) => {
+                self.yank(target, context);
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::SetNote(ref note))) => {
+                let account = &mut context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    /* The envelope has been renamed or removed, so wait for the appropriate event to
+                     * arrive */
+                    return true;
+                }
+                let envelope = account.collection.get_env(self.coordinates.2).clone();
+                if let Err(err) = account.notes.set(&envelope, note.to_string()) {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some("Could not save note".to_string()),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    ));
+                    return true;
+                }
+                context
+                    .replies
+                    .push_back(UIEvent::EnvelopeUpdate(self.coordinates.2));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::RemoveNote)) => {
+                let account = &mut context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    /* The envelope has been renamed or removed, so wait for the appropriate event to
+                     * arrive */
+                    return true;
+                }
+                let envelope = account.collection.get_env(self.coordinates.2).clone();
+                match account.notes.remove(&envelope) {
+                    Ok(true) => {
+                        context
+                            .replies
+                            .push_back(UIEvent::EnvelopeUpdate(self.coordinates.2));
+                        self.set_dirty(true);
+                    }
+                    Ok(false) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage("This message has no note.".to_string()),
+                        ));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not remove note".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::DismissWarnings)) => {
+                let account = &mut context.accounts[&self.coordinates.0];
+                if !account.contains_key(self.coordinates.2) {
+                    /* The envelope has been renamed or removed, so wait for the appropriate event to
+                     * arrive */
+                    return true;
+                }
+                let envelope = account.collection.get_env(self.coordinates.2).clone();
+                if let Err(err) = account.dismissed_warnings.add(&envelope) {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some("Could not dismiss warnings".to_string()),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    ));
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            UIEvent::Action(View(ViewAction::ImportAutocryptSetup(ref setup_code))) => {
+                self.import_autocrypt_setup(setup_code, context);
+                return true;
+            }
+            #[cfg(not(feature = "gpgme"))]
+            UIEvent::Action(View(ViewAction::ImportAutocryptSetup(_))) => {
+                context.replies.push_back(UIEvent::Notification(
+                    None,
+                    "Autocrypt Setup Message import failed: meli is not built with gpgme support."
+                        .to_string(),
+                    Some(NotificationType::Error(ErrorKind::None)),
+                ));
+                return true;
+            }
             UIEvent::Action(MailingListAction(ref e)) => {
                 let account = &context.accounts[&self.coordinates.0];
                 if !account.contains_key(self.coordinates.2) {
@@ -2393,68 +3805,17 @@ impl Component for MailView {
                             return true;
                         }
                         MailingListAction::ListUnsubscribe if actions.unsubscribe.is_some() => {
-                            /* autosend or open unsubscribe option*/
-                            let unsubscribe = actions.unsubscribe.as_ref().unwrap();
-                            for option in unsubscribe.iter() {
-                                /* TODO: Ask for confirmation before proceding with an action */
-                                match option {
-                                    list_management::ListAction::Email(email) => {
-                                        if let Ok(mailto) = Mailto::try_from(*email) {
-                                            let mut draft: Draft = mailto.into();
-                                            draft.set_header(
-                                                "From",
-                                                crate::components::mail::get_display_name(
-                                                    context,
-                                                    self.coordinates.0,
-                                                ),
-                                            );
-                                            /* Manually drop stuff because borrowck doesn't do it
-                                             * on its own */
-                                            drop(detect);
-                                            drop(envelope);
-                                            if let Err(err) = super::compose::send_draft(
-                                                ToggleFlag::False,
-                                                context,
-                                                self.coordinates.0,
-                                                draft,
-                                                SpecialUsageMailbox::Sent,
-                                                Flag::SEEN,
-                                                true,
-                                            ) {
-                                                context.replies.push_back(UIEvent::StatusEvent(
-                                                    StatusEvent::DisplayMessage(format!(
-                                                        "Couldn't send unsubscribe e-mail: {}",
-                                                        err
-                                                    )),
-                                                ));
-                                            }
-                                            return true;
-                                        }
-                                    }
-                                    list_management::ListAction::Url(url) => {
-                                        match Command::new("xdg-open")
-                                            .arg(String::from_utf8_lossy(url).into_owned())
-                                            .stdin(Stdio::piped())
-                                            .stdout(Stdio::piped())
-                                            .spawn()
-                                        {
-                                            Ok(child) => {
-                                                context.children.push(child);
-                                            }
-                                            Err(err) => {
-                                                context.replies.push_back(UIEvent::StatusEvent(
-                                                    StatusEvent::DisplayMessage(format!(
-                                                        "Couldn't launch xdg-open: {}",
-                                                        err
-                                                    )),
-                                                ));
-                                            }
-                                        }
-                                        return true;
-                                    }
-                                    list_management::ListAction::No => {}
-                                }
+                            if let Some(dialog) = self.unsubscribe_dialog(actions, context) {
+                                self.mode = ViewMode::Unsubscribe(dialog);
+                                self.set_dirty(true);
+                            } else {
+                                context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(String::from(
+                                        "Couldn't parse List-Unsubscribe header value",
+                                    )),
+                                ));
                             }
+                            return true;
                         }
                         MailingListAction::ListArchive if actions.archive.is_some() => {
                             /* open archive url with xdg-open */
@@ -2495,7 +3856,7 @@ impl Component for MailView {
         self.dirty
             || self.pager.is_dirty()
             || self.subview.as_ref().map(|p| p.is_dirty()).unwrap_or(false)
-            || if let ViewMode::ContactSelector(ref s) = self.mode {
+            || if let Some(s) = self.mode.selector() {
                 s.is_dirty()
             /*} else if let ViewMode::Ansi(ref r) = self.mode {
             r.is_dirty()*/
@@ -2510,9 +3871,15 @@ impl Component for MailView {
             ViewMode::Normal | ViewMode::Url | ViewMode::Source(_) | ViewMode::Attachment(_) => {
                 self.pager.set_dirty(value);
             }
-            ViewMode::ContactSelector(ref mut s) => {
+            ViewMode::ContactSelector(_)
+            | ViewMode::AddressSelector(_)
+            | ViewMode::AddressActions(_)
+            | ViewMode::Unsubscribe(_)
+            | ViewMode::Replies(_) => {
                 self.pager.set_dirty(value);
-                s.set_dirty(value);
+                if let Some(s) = self.mode.selector_mut() {
+                    s.set_dirty(value);
+                }
             }
             ViewMode::Subview => {
                 if let Some(s) = self.subview.as_mut() {
@@ -2540,8 +3907,12 @@ impl Component for MailView {
         {
             our_map.remove("return_to_normal_view");
         }
+        if !matches!(self.state, MailViewState::Error { .. }) {
+            our_map.remove("retry");
+        }
         if self.mode != ViewMode::Url {
             our_map.remove("go_to_url");
+            our_map.remove("yank_url");
         }
         if !(self.mode == ViewMode::Normal || self.mode == ViewMode::Url) {
             our_map.remove("toggle_url_mode");
@@ -2559,6 +3930,14 @@ impl Component for MailView {
         self.id = id;
     }
 
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        session.tabs.push(SessionTab::Envelope {
+            account: context.accounts[&self.coordinates.0].name().to_string(),
+            mailbox: self.coordinates.1,
+            envelope: self.coordinates.2,
+        });
+    }
+
     fn kill(&mut self, id: ComponentId, context: &mut Context) {
         if self.id == id {
             context
@@ -2566,6 +3945,10 @@ impl Component for MailView {
                 .push_back(UIEvent::Action(Tab(Kill(self.id))));
         }
     }
+
+    fn account_hash(&self) -> Option<AccountHash> {
+        Some(self.coordinates.0)
+    }
 }
 
 fn save_attachment(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
@@ -2578,6 +3961,141 @@ fn save_attachment(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
     Ok(())
 }
 
+/// Pass `ics` to `command`, either through a temporary file if `command` contains the `%s`
+/// placeholder or through its standard input.
+fn export_ics(command: &str, ics: &str) -> Result<()> {
+    let (command, temp_file) = if command.contains("%s") {
+        let f = create_temp_file(
+            ics.as_bytes(),
+            Some(&format!("{}.ics", Uuid::new_v4().to_hyphenated())),
+            None,
+            true,
+        )?;
+        (
+            command.replace("%s", &f.path().display().to_string()),
+            Some(f),
+        )
+    } else {
+        (command.to_string(), None)
+    };
+    log_target(
+        "ui",
+        format!("Executing: sh -c \"{}\"", command.replace("\"", "\\\"")),
+        DEBUG,
+    );
+    let mut child = Command::new("sh")
+        .args(&["-c", &command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    /* Write the input from another thread, so that a command that fills its output pipe before
+     * reading all of its input doesn't deadlock. */
+    let writer = if temp_file.is_none() {
+        let mut stdin = child.stdin.take().unwrap();
+        let ics = ics.to_string();
+        Some(std::thread::spawn(move || stdin.write_all(ics.as_bytes())))
+    } else {
+        None
+    };
+    let output = child.wait_with_output()?;
+    drop(temp_file);
+    let written = writer.map(|writer| writer.join().unwrap_or(Ok(())));
+    if output.status.success() {
+        written.unwrap_or(Ok(()))?;
+        Ok(())
+    } else {
+        Err(MeliError::new(format!(
+            "`{}` exited with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// The command line that opens the attachment at `path`, of content type `mime_type`: the first
+/// entry of `openers` that matches the type, or else the default application of the type
+/// according to the XDG MIME database. It is run in `sandbox`, if set.
+fn attachment_opener_command(
+    openers: &IndexMap<String, String>,
+    sandbox: Option<&str>,
+    mime_type: &str,
+    path: &str,
+) -> Option<Vec<String>> {
+    let mut command = if let Some((_, opener)) = openers.iter().find(|(pattern, _)| {
+        mime_type.eq_ignore_ascii_case(pattern) || mime_type.matches_glob(pattern)
+    }) {
+        let mut command = split_command!(opener)
+            .into_iter()
+            .map(|arg| arg.replace("%s", path))
+            .collect::<Vec<String>>();
+        if !opener.contains("%s") {
+            command.push(path.to_string());
+        }
+        command
+    } else {
+        let exec = query_default_app(mime_type).ok()?;
+        let (exec_cmd, argument) = desktop_exec_to_command(&exec, path.to_string(), false);
+        let mut command = split_command!(exec_cmd)
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        command.push(argument);
+        command
+    };
+    if command.is_empty() {
+        return None;
+    }
+    if let Some(sandbox) = sandbox {
+        command.splice(
+            0..0,
+            split_command!(sandbox).into_iter().map(str::to_string),
+        );
+    }
+    Some(command)
+}
+
+/// [`attachment_opener_command`] with the `attachment_openers` and `attachment_sandbox` pager
+/// settings of mailbox `mailbox_hash` of account `account_hash`, or of the account if there's no
+/// mailbox. E-mails that aren't in any account use the global settings.
+pub(super) fn attachment_opener(
+    context: &Context,
+    account_hash: AccountHash,
+    mailbox_hash: Option<MailboxHash>,
+    mime_type: &str,
+    path: &str,
+) -> Option<Vec<String>> {
+    let (openers, sandbox) = match context.accounts.get(&account_hash) {
+        Some(account)
+            if mailbox_hash.map_or(false, |h| account.mailbox_entries.contains_key(&h)) =>
+        {
+            let mailbox_hash = mailbox_hash.unwrap();
+            (
+                mailbox_settings!(
+                    context[account_hash][&mailbox_hash]
+                        .pager
+                        .attachment_openers
+                ),
+                mailbox_settings!(
+                    context[account_hash][&mailbox_hash]
+                        .pager
+                        .attachment_sandbox
+                ),
+            )
+        }
+        Some(_) => (
+            account_settings!(context[account_hash].pager.attachment_openers),
+            account_settings!(context[account_hash].pager.attachment_sandbox),
+        ),
+        None => (
+            &context.settings.pager.attachment_openers,
+            &context.settings.pager.attachment_sandbox,
+        ),
+    };
+    attachment_opener_command(openers, sandbox.as_deref(), mime_type, path)
+}
+
 fn desktop_exec_to_command(command: &str, path: String, is_url: bool) -> (String, String) {
     /* Purge unused field codes */
     let command = command
@@ -2624,3 +4142,275 @@ fn test_desktop_exec() {
     }
 }
 */
+
+#[test]
+fn test_open_error_report() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "vanished",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("vanished");
+    /* Another client removes the message after it was listed. The envelope is only gone from the
+     * listing once the backend's event is processed, and `keys` doesn't process it. */
+    for entry in std::fs::read_dir(h.maildir().join("cur")).unwrap() {
+        std::fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+    h.keys(&[Key::Char('\n')]);
+    assert!(
+        h.find("Could not open this e-mail").is_some(),
+        "{}",
+        h.text()
+    );
+    assert!(h.find("Account: test (maildir)").is_some(), "{}", h.text());
+    assert!(h.find("Press F5 to try again.").is_some(), "{}", h.text());
+    assert!(h.find("Summary:").is_none(), "{}", h.text());
+    h.keys(&[Key::Char('h')]);
+    assert!(
+        h.find("Press h to hide the details.").is_some(),
+        "{}",
+        h.text()
+    );
+    /* Still missing. */
+    h.keys(&[Key::F(5)]);
+    assert!(
+        h.find("Could not open this e-mail").is_some(),
+        "{}",
+        h.text()
+    );
+}
+
+#[test]
+fn test_view_sender_timezone() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "obsolete date",
+            "Alice <alice@example.com>",
+            "Mon, 16 Mar 20 10:23 +0200",
+        )],
+        "[pager]\nshow_sender_timezone = true\n",
+    );
+    h.wait_for("obsolete date");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Date: Mon, 16 Mar 2020 10:23:00 +0200");
+}
+
+#[test]
+fn test_view_unsubscribe_preview() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "newsletter",
+            "News <news@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )
+        .replacen(
+            "\r\n\r\n",
+            "\r\nList-Id: News <news.example.com>\r\n\
+             List-Unsubscribe: <mailto:leave@example.com?subject=bye>, \
+             <https://example.com/u?id=1>\r\n\
+             List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\r\n",
+            1,
+        )],
+        "",
+    );
+    h.wait_for("newsletter");
+    h.keys(&[Key::Char('\n')]);
+    /* The actions need the loaded message. */
+    h.wait_for("Body of newsletter.");
+    h.command("list-unsubscribe");
+    h.wait_for("unsubscribe from News <news.example.com>");
+    h.wait_for("send an e-mail from test@example.com to leave@example.com with subject \"bye\"");
+    #[cfg(feature = "http")]
+    h.wait_for("send a one-click unsubscribe POST request to https://example.com/u?id=1");
+    h.wait_for("open https://example.com/u?id=1 in a browser");
+    /* Nothing happens without picking an option. */
+    h.keys(&[Key::Esc]);
+    h.wait_until("the dialog to close", |h| {
+        h.find("unsubscribe from News <news.example.com>").is_none()
+    });
+}
+
+#[test]
+fn test_view_phishing_warning() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "account suspended",
+            "\"support@bank.example.com\" <crook@evil.example>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("account suspended");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for(
+        "Warning: ⚠ the sender's name shows support@bank.example.com but the message is from",
+    );
+    h.command("dismiss-warnings");
+    h.wait_until("the warning to be dismissed", |h| {
+        h.find("Warning:").is_none() && h.find("Subject: account suspended").is_some()
+    });
+}
+
+#[test]
+fn test_view_parent_and_replies() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[
+            message(
+                "question",
+                "Alice <alice@example.com>",
+                "Wed, 01 Jan 2020 10:00:00 +0000",
+            ),
+            message(
+                "answer",
+                "Bob <bob@example.com>",
+                "Thu, 02 Jan 2020 10:00:00 +0000",
+            )
+            .replacen(
+                "\r\n\r\n",
+                "\r\nIn-Reply-To: <question@headless.example.com>\r\n\r\n",
+                1,
+            ),
+        ],
+        "[listing]\nindex_style = \"plain\"\n",
+    );
+    h.wait_for("answer");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Subject: answer");
+    h.keys(&[Key::Char('<')]);
+    h.wait_for("Subject: question");
+    h.wait_for("Body of question.");
+    h.keys(&[Key::Char('>')]);
+    h.wait_for("Bob <bob@example.com> answer (");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Subject: answer");
+    h.keys(&[Key::Char('>')]);
+    h.wait_for("No known replies to this message.");
+}
+
+#[cfg(feature = "gpgme")]
+#[test]
+fn test_view_harvests_autocrypt_key() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::with_account_name(
+        "autocrypt-harvest",
+        &[message(
+            "my key",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )
+        .replacen(
+            "\r\n\r\n",
+            "\r\nAutocrypt: addr=alice@example.com; prefer-encrypt=mutual; keydata=c2Vj\r\n\r\n",
+            1,
+        )],
+        "",
+        "",
+    );
+    h.wait_for("my key");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_until("the key to be harvested", |h| {
+        h.state.context.accounts[0]
+            .autocrypt_peers
+            .get("alice@example.com")
+            .is_some()
+    });
+    let peer = h.state.context.accounts[0]
+        .autocrypt_peers
+        .get("Alice@example.com")
+        .unwrap();
+    assert!(peer.prefer_encrypt);
+    assert_eq!(peer.keydata, b"sec");
+}
+
+#[cfg(feature = "gpgme")]
+#[test]
+fn test_view_autocrypt_import_checks() {
+    use crate::testing::{message, Headless};
+    let mut h = Headless::new(
+        &[message(
+            "not a setup message",
+            "Alice <alice@example.com>",
+            "Wed, 01 Jan 2020 10:00:00 +0000",
+        )],
+        "",
+    );
+    h.wait_for("not a setup message");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Body of not a setup message.");
+    h.command("autocrypt-import 1234-5678");
+    h.wait_for("The Setup Code must have 36 digits.");
+    h.command(&format!(
+        "autocrypt-import {}",
+        "1234-".repeat(9).trim_end_matches('-')
+    ));
+    h.wait_for("not an Autocrypt");
+}
+
+#[test]
+fn test_view_protected_headers() {
+    let mut headers = melib::email::headers::HeaderMap::default();
+    headers.insert(
+        melib::HeaderName::new_unchecked("Subject"),
+        "Secret plans".to_string(),
+    );
+    let plaintext = AttachmentBuilder::new(
+        melib::pgp::protect_headers(AttachmentBuilder::new(b"Meet at noon.").build(), &headers)
+            .as_bytes(),
+    )
+    .build();
+    let displays = vec![AttachmentDisplay::EncryptedSuccess {
+        inner: AttachmentBuilder::new(&[]).build(),
+        plaintext,
+        plaintext_display: vec![],
+        description: String::new(),
+    }];
+    let plaintext = AttachmentDisplay::find_protected_headers(&displays).unwrap();
+    let mut envelope = Envelope::from_bytes(
+        b"From: alice@example.com\r\nSubject: ...\r\n\r\nencrypted\r\n",
+        None,
+    )
+    .unwrap();
+    envelope.populate_headers(plaintext.raw()).unwrap();
+    assert_eq!(envelope.subject(), "Secret plans");
+    assert_eq!(envelope.field_from_to_string(), "alice@example.com");
+    assert!(
+        AttachmentDisplay::find_protected_headers(&[AttachmentDisplay::InlineOther {
+            inner: AttachmentBuilder::new(b"Content-Type: text/plain\r\n\r\nhi").build(),
+        }])
+        .is_none()
+    );
+}
+
+#[test]
+fn test_attachment_opener_command() {
+    let mut openers = IndexMap::default();
+    openers.insert("image/*".to_string(), "feh --scale-down".to_string());
+    openers.insert(
+        "application/pdf".to_string(),
+        "zathura --mode=%s".to_string(),
+    );
+    openers.insert("application/*".to_string(), "libreoffice".to_string());
+    let command =
+        |sandbox, mime_type| attachment_opener_command(&openers, sandbox, mime_type, "/tmp/a");
+    assert_eq!(
+        command(None, "image/png").unwrap(),
+        ["feh", "--scale-down", "/tmp/a"]
+    );
+    /* The first matching entry is used. */
+    assert_eq!(
+        command(None, "Application/PDF").unwrap(),
+        ["zathura", "--mode=/tmp/a"]
+    );
+    assert_eq!(
+        command(Some("firejail --net=none"), "application/msword").unwrap(),
+        ["firejail", "--net=none", "libreoffice", "/tmp/a"]
+    );
+}
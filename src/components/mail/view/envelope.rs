@@ -23,8 +23,6 @@ use super::*;
 use linkify::{Link, LinkFinder};
 use std::process::{Command, Stdio};
 
-use xdg_utils::query_default_app;
-
 #[derive(PartialEq, Debug)]
 enum ViewMode {
     Normal,
@@ -54,7 +52,9 @@ pub struct EnvelopeView {
     mail: Mail,
 
     account_hash: AccountHash,
-    cmd_buf: String,
+    /// Attachments opened in external viewers, deleted when the view is closed.
+    temp_files: Vec<File>,
+    shred_temp_files: bool,
     id: ComponentId,
 }
 
@@ -78,11 +78,18 @@ impl EnvelopeView {
             mode: ViewMode::Normal,
             mail,
             account_hash,
-            cmd_buf: String::with_capacity(4),
+            temp_files: vec![],
+            shred_temp_files: false,
             id: ComponentId::new_v4(),
         }
     }
 
+    /// Shred the temporary files of parts opened in external viewers, for decrypted messages.
+    pub fn set_shred_temp_files(&mut self, shred: bool) -> &mut Self {
+        self.shred_temp_files = shred;
+        self
+    }
+
     /// Returns the string to be displayed in the Viewer
     fn attachment_to_text(&self, body: &Attachment, context: &mut Context) -> String {
         let finder = LinkFinder::new();
@@ -229,7 +236,10 @@ impl Component for EnvelopeView {
                 get_y(upper_left).saturating_sub(1)
             } else {
                 let (x, y) = write_string_to_grid(
-                    &format!("Date: {}", self.mail.date_as_str()),
+                    &format!(
+                        "Date: {}",
+                        display_date(&self.mail, context.settings.pager.show_sender_timezone)
+                    ),
                     grid,
                     Color::Byte(33),
                     Color::Default,
@@ -315,10 +325,14 @@ impl Component for EnvelopeView {
             match self.mode {
                 ViewMode::Attachment(aidx) if body.attachments()[aidx].is_html() => {
                     let attachment = &body.attachments()[aidx];
-                    self.subview = Some(Box::new(HtmlView::new(&attachment, context)));
+                    let mut view = HtmlView::new(&attachment, self.account_hash, None, context);
+                    view.set_shred_temp_files(self.shred_temp_files);
+                    self.subview = Some(Box::new(view));
                 }
                 ViewMode::Normal if body.is_html() => {
-                    self.subview = Some(Box::new(HtmlView::new(&body, context)));
+                    let mut view = HtmlView::new(&body, self.account_hash, None, context);
+                    view.set_shred_temp_files(self.shred_temp_files);
+                    self.subview = Some(Box::new(view));
                     self.mode = ViewMode::Subview;
                 }
                 _ => {
@@ -365,17 +379,6 @@ impl Component for EnvelopeView {
             }
         }
         match *event {
-            UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) if !self.cmd_buf.is_empty() => {
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
-                return true;
-            }
-            UIEvent::Input(Key::Char(c)) if c >= '0' && c <= '9' => {
-                self.cmd_buf.push(c);
-                return true;
-            }
             UIEvent::Input(Key::Char('r'))
                 if self.mode == ViewMode::Normal || self.mode == ViewMode::Raw =>
             {
@@ -396,13 +399,9 @@ impl Component for EnvelopeView {
                 return true;
             }
             UIEvent::Input(Key::Char('a'))
-                if !self.cmd_buf.is_empty() && self.mode == ViewMode::Normal =>
+                if context.count.is_some() && self.mode == ViewMode::Normal =>
             {
-                let lidx = self.cmd_buf.parse::<usize>().unwrap();
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                let lidx = context.count.unwrap();
 
                 {
                     if let Some(u) = self.mail.body().attachments().get(lidx) {
@@ -436,33 +435,47 @@ impl Component for EnvelopeView {
                             ContentType::Other { .. } => {
                                 let attachment_type = u.mime_type();
                                 let filename = u.filename();
-                                if let Ok(command) = query_default_app(&attachment_type) {
-                                    let p = create_temp_file(
-                                        &decode(u, None),
-                                        filename.as_ref().map(|s| s.as_str()),
-                                        None,
-                                        true,
-                                    );
-                                    let (exec_cmd, argument) = super::desktop_exec_to_command(
-                                        &command,
-                                        p.path.display().to_string(),
-                                        false,
-                                    );
-                                    match Command::new(&exec_cmd)
-                                        .arg(&argument)
+                                let mut p = match create_temp_file(
+                                    &decode(u, None),
+                                    filename.as_ref().map(|s| s.as_str()),
+                                    None,
+                                    true,
+                                ) {
+                                    Ok(p) => p,
+                                    Err(err) => {
+                                        context.replies.push_back(UIEvent::StatusEvent(
+                                            StatusEvent::DisplayMessage(format!(
+                                                "Could not create temporary file: {}",
+                                                err
+                                            )),
+                                        ));
+                                        return true;
+                                    }
+                                };
+                                p.set_shred(self.shred_temp_files);
+                                if let Some(command) = super::attachment_opener(
+                                    context,
+                                    self.account_hash,
+                                    None,
+                                    &attachment_type,
+                                    &p.path.display().to_string(),
+                                ) {
+                                    match Command::new(&command[0])
+                                        .args(&command[1..])
                                         .stdin(Stdio::piped())
                                         .stdout(Stdio::piped())
                                         .spawn()
                                     {
                                         Ok(child) => {
-                                            context.temp_files.push(p);
+                                            self.temp_files.push(p);
                                             context.children.push(child);
                                         }
                                         Err(err) => {
                                             context.replies.push_back(UIEvent::StatusEvent(
                                                 StatusEvent::DisplayMessage(format!(
-                                                    "Failed to start `{} {}`: {}",
-                                                    &exec_cmd, &argument, err
+                                                    "Failed to start `{}`: {}",
+                                                    command.join(" "),
+                                                    err
                                                 )),
                                             ));
                                         }
@@ -507,13 +520,9 @@ impl Component for EnvelopeView {
                 return true;
             }
             UIEvent::Input(Key::Char('g'))
-                if !self.cmd_buf.is_empty() && self.mode == ViewMode::Url =>
+                if context.count.is_some() && self.mode == ViewMode::Url =>
             {
-                let lidx = self.cmd_buf.parse::<usize>().unwrap();
-                self.cmd_buf.clear();
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                let lidx = context.count.unwrap();
                 let url = {
                     let finder = LinkFinder::new();
                     let t = self.mail.body().text();
@@ -27,11 +27,22 @@ use std::process::{Command, Stdio};
 pub struct HtmlView {
     pager: Pager,
     bytes: Vec<u8>,
+    /// The HTML opened in an external viewer, deleted when the view is closed.
+    temp_files: Vec<File>,
+    /// Where the e-mail is, for the settings of the external viewer.
+    account_hash: AccountHash,
+    mailbox_hash: Option<MailboxHash>,
+    shred_temp_files: bool,
     id: ComponentId,
 }
 
 impl HtmlView {
-    pub fn new(body: &Attachment, context: &mut Context) -> Self {
+    pub fn new(
+        body: &Attachment,
+        account_hash: AccountHash,
+        mailbox_hash: Option<MailboxHash>,
+        context: &mut Context,
+    ) -> Self {
         let id = ComponentId::new_v4();
         let bytes: Vec<u8> = decode_rec(body, None);
 
@@ -111,7 +122,21 @@ impl HtmlView {
         }
         let colors = crate::conf::value(context, "mail.view.body");
         let pager = Pager::from_string(display_text, None, None, None, colors);
-        HtmlView { pager, bytes, id }
+        HtmlView {
+            pager,
+            bytes,
+            temp_files: vec![],
+            account_hash,
+            mailbox_hash,
+            shred_temp_files: false,
+            id,
+        }
+    }
+
+    /// Shred the temporary files of parts opened in external viewers, for decrypted messages.
+    pub fn set_shred_temp_files(&mut self, shred: bool) -> &mut Self {
+        self.shred_temp_files = shred;
+        self
     }
 }
 
@@ -131,25 +156,42 @@ impl Component for HtmlView {
         }
 
         if let UIEvent::Input(Key::Char('v')) = event {
-            if let Ok(command) = query_default_app("text/html") {
-                let p = create_temp_file(&self.bytes, None, None, true);
-                let (exec_cmd, argument) =
-                    super::desktop_exec_to_command(&command, p.path.display().to_string(), false);
-                match Command::new(&exec_cmd)
-                    .arg(&argument)
+            let mut p = match create_temp_file(&self.bytes, None, None, true) {
+                Ok(p) => p,
+                Err(err) => {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                            "Could not create temporary file: {}",
+                            err
+                        ))));
+                    return true;
+                }
+            };
+            p.set_shred(self.shred_temp_files);
+            if let Some(command) = super::attachment_opener(
+                context,
+                self.account_hash,
+                self.mailbox_hash,
+                "text/html",
+                &p.path.display().to_string(),
+            ) {
+                match Command::new(&command[0])
+                    .args(&command[1..])
                     .stdin(Stdio::piped())
                     .stdout(Stdio::piped())
                     .spawn()
                 {
                     Ok(child) => {
-                        context.temp_files.push(p);
+                        self.temp_files.push(p);
                         context.children.push(child);
                     }
                     Err(err) => {
                         context.replies.push_back(UIEvent::StatusEvent(
                             StatusEvent::DisplayMessage(format!(
-                                "Failed to start `{} {}`: {}",
-                                &exec_cmd, &argument, err
+                                "Failed to start `{}`: {}",
+                                command.join(" "),
+                                err
                             )),
                         ));
                     }
@@ -0,0 +1,505 @@
+/*
+ * meli
+ *
+ * Copyright 2017-2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use melib::Bytes;
+
+/// A node of the MIME hierarchy, stored in pre-order along with its depth.
+#[derive(Debug)]
+struct MimeTreeEntry {
+    attachment: Attachment,
+    depth: usize,
+    has_children: bool,
+    collapsed: bool,
+}
+
+/// Collapsible tree of every MIME part of an envelope, including the parts of attached
+/// `message/rfc822` e-mails.
+#[derive(Debug)]
+pub struct MimeTree {
+    entries: Vec<MimeTreeEntry>,
+    /// Index of the selected row among the visible rows.
+    cursor: usize,
+    /// A part opened from the tree; drawn instead of the tree until dismissed.
+    viewing: Option<Box<dyn Component>>,
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+    shred_temp_files: bool,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+    dirty: bool,
+    /// Attachments opened in external viewers, deleted when the view is closed.
+    temp_files: Vec<File>,
+    id: ComponentId,
+}
+
+impl fmt::Display for MimeTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MimeTree::DESCRIPTION)
+    }
+}
+
+impl MimeTree {
+    pub const DESCRIPTION: &'static str = "mime-tree";
+
+    pub fn new(
+        body: &Attachment,
+        (account_hash, mailbox_hash): (AccountHash, MailboxHash),
+        context: &Context,
+    ) -> Self {
+        let mut entries = Vec::new();
+        Self::push_entries(body.clone(), 0, &mut entries);
+        MimeTree {
+            entries,
+            cursor: 0,
+            viewing: None,
+            account_hash,
+            mailbox_hash,
+            shred_temp_files: false,
+            theme_default: crate::conf::value(context, "theme_default"),
+            highlight_theme: crate::conf::value(context, "widgets.options.highlighted"),
+            dirty: true,
+            temp_files: vec![],
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    /// Shred the temporary files of parts opened in external viewers, for decrypted messages.
+    pub fn set_shred_temp_files(&mut self, shred: bool) -> &mut Self {
+        self.shred_temp_files = shred;
+        self
+    }
+
+    fn push_entries(attachment: Attachment, depth: usize, entries: &mut Vec<MimeTreeEntry>) {
+        let children: Vec<Attachment> = match attachment.content_type {
+            ContentType::Multipart { ref parts, .. } => parts.clone(),
+            ContentType::MessageRfc822 => {
+                vec![AttachmentBuilder::new(&decode(&attachment, None)).build()]
+            }
+            _ => Vec::new(),
+        };
+        entries.push(MimeTreeEntry {
+            attachment,
+            depth,
+            has_children: !children.is_empty(),
+            collapsed: false,
+        });
+        for c in children {
+            Self::push_entries(c, depth + 1, entries);
+        }
+    }
+
+    /// Indices of the entries that are not hidden by a collapsed ancestor.
+    fn visible_entries(&self) -> Vec<usize> {
+        let mut ret = Vec::with_capacity(self.entries.len());
+        let mut collapsed_depth: Option<usize> = None;
+        for (idx, e) in self.entries.iter().enumerate() {
+            match collapsed_depth {
+                Some(d) if e.depth > d => continue,
+                _ => collapsed_depth = None,
+            }
+            if e.collapsed {
+                collapsed_depth = Some(e.depth);
+            }
+            ret.push(idx);
+        }
+        ret
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.visible_entries().get(self.cursor).cloned()
+    }
+
+    fn entry_line(&self, idx: usize) -> String {
+        let e = &self.entries[idx];
+        let marker = if !e.has_children {
+            "-"
+        } else if e.collapsed {
+            "▸"
+        } else {
+            "▾"
+        };
+        format!(
+            "{}{} {} [{}, {}]{}",
+            "  ".repeat(e.depth),
+            marker,
+            e.attachment.content_type(),
+            Bytes(e.attachment.raw().len()),
+            e.attachment.content_transfer_encoding(),
+            e.attachment
+                .filename()
+                .map(|f| format!(" \"{}\"", f))
+                .unwrap_or_default()
+        )
+    }
+
+    fn open_part(&mut self, idx: usize, context: &mut Context) {
+        /* Multiparts are only containers; attached e-mails are opened in a new tab instead. */
+        if let ContentType::Multipart { .. } = self.entries[idx].attachment.content_type {
+            self.entries[idx].collapsed = !self.entries[idx].collapsed;
+            return;
+        }
+        let attachment = &self.entries[idx].attachment;
+        match attachment.content_type() {
+            ContentType::MessageRfc822 => {
+                match Mail::new(decode(attachment, None), Some(Flag::SEEN)) {
+                    Ok(wrapper) => {
+                        let mut view = EnvelopeView::new(wrapper, None, None, self.account_hash);
+                        view.set_shred_temp_files(self.shred_temp_files);
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                    }
+                }
+            }
+            _ if attachment.is_html() => {
+                let mut view = HtmlView::new(
+                    attachment,
+                    self.account_hash,
+                    Some(self.mailbox_hash),
+                    context,
+                );
+                view.set_shred_temp_files(self.shred_temp_files);
+                self.viewing = Some(Box::new(view));
+            }
+            ContentType::Text { .. } | ContentType::PGPSignature | ContentType::CMSSignature => {
+                let mut text = "Viewing part. Press `r` to return \n".to_string();
+                text.push_str(&attachment.text());
+                let colors = crate::conf::value(context, "mail.view.body");
+                self.viewing = Some(Box::new(Pager::from_string(
+                    text,
+                    Some(context),
+                    Some(0),
+                    None,
+                    colors,
+                )));
+            }
+            ContentType::Multipart { .. } => {}
+            ContentType::Other { .. } => {
+                let attachment_type = attachment.mime_type();
+                let filename = attachment.filename();
+                let mut p = match create_temp_file(
+                    &decode(attachment, None),
+                    filename.as_ref().map(|s| s.as_str()),
+                    None,
+                    true,
+                ) {
+                    Ok(p) => p,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Could not create temporary file: {}",
+                                err
+                            )),
+                        ));
+                        return;
+                    }
+                };
+                p.set_shred(self.shred_temp_files);
+                if let Some(command) = attachment_opener(
+                    context,
+                    self.account_hash,
+                    Some(self.mailbox_hash),
+                    &attachment_type,
+                    &p.path.display().to_string(),
+                ) {
+                    match Command::new(&command[0])
+                        .args(&command[1..])
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .spawn()
+                    {
+                        Ok(child) => {
+                            self.temp_files.push(p);
+                            context.children.push(child);
+                        }
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(format!(
+                                    "Failed to start `{}`: {}",
+                                    command.join(" "),
+                                    err
+                                )),
+                            ));
+                        }
+                    }
+                } else {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                            "Couldn't find a default application for type {}",
+                            attachment_type
+                        ))));
+                }
+            }
+            ContentType::OctetStream { ref name } => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Failed to open {}. application/octet-stream isn't supported yet",
+                        name.as_ref().map(|n| n.as_str()).unwrap_or("file")
+                    ))));
+            }
+        }
+    }
+
+    /// Save the decoded part in the current working directory, under its filename if it has one.
+    fn save_part(&self, idx: usize, context: &mut Context) {
+        let attachment = &self.entries[idx].attachment;
+        let mut path = std::env::current_dir().unwrap_or_default();
+        if let Some(filename) = attachment.filename() {
+            path.push(filename);
+        } else {
+            path.push(Uuid::new_v4().to_hyphenated().to_string());
+        }
+        match save_attachment(&path, &decode(attachment, None)) {
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(format!("Failed to create file at {}", path.display())),
+                    err.to_string(),
+                    Some(NotificationType::Error(melib::ErrorKind::External)),
+                ));
+            }
+            Ok(()) => {
+                context.replies.push_back(UIEvent::Notification(
+                    None,
+                    format!("Saved at {}", path.display()),
+                    Some(NotificationType::Info),
+                ));
+            }
+        }
+    }
+}
+
+impl Component for MimeTree {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if let Some(ref mut v) = self.viewing {
+            v.draw(grid, area, context);
+            return;
+        }
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let upper_left = upper_left!(area);
+        let bottom_right = bottom_right!(area);
+        clear_area(grid, area, self.theme_default);
+        write_string_to_grid(
+            "MIME structure. Press `r` to return.",
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            area,
+            None,
+        );
+        let visible = self.visible_entries();
+        self.cursor = std::cmp::min(self.cursor, visible.len().saturating_sub(1));
+        let rows = std::cmp::max(height!(area).saturating_sub(2), 1);
+        let top = (self.cursor / rows) * rows;
+        for (i, idx) in visible.iter().enumerate().skip(top).take(rows) {
+            let y = get_y(upper_left) + 2 + i - top;
+            if y > get_y(bottom_right) {
+                break;
+            }
+            let theme = if i == self.cursor {
+                self.highlight_theme
+            } else {
+                self.theme_default
+            };
+            let row_area = (set_y(upper_left, y), set_y(bottom_right, y));
+            let (x, _) = write_string_to_grid(
+                &self.entry_line(*idx),
+                grid,
+                theme.fg,
+                theme.bg,
+                theme.attrs,
+                row_area,
+                None,
+            );
+            if i == self.cursor {
+                change_colors(
+                    grid,
+                    (set_y(set_x(upper_left, x), y), set_y(bottom_right, y)),
+                    theme.fg,
+                    theme.bg,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if let Some(ref mut v) = self.viewing {
+            let return_key = context
+                .settings
+                .shortcuts
+                .envelope_view
+                .return_to_normal_view
+                .clone();
+            if let UIEvent::Input(ref key) = *event {
+                if *key == return_key {
+                    self.viewing = None;
+                    self.set_dirty(true);
+                    return true;
+                }
+            }
+            return v.process_event(event, context);
+        }
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "widgets.options.highlighted");
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.set_dirty(true);
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_up"]) =>
+            {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_down"]) =>
+            {
+                if self.cursor + 1 < self.visible_entries().len() {
+                    self.cursor += 1;
+                    self.set_dirty(true);
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["toggle_collapse"]) =>
+            {
+                if let Some(idx) = self.selected() {
+                    if self.entries[idx].has_children {
+                        self.entries[idx].collapsed = !self.entries[idx].collapsed;
+                        self.set_dirty(true);
+                    }
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["open_part"]) =>
+            {
+                if let Some(idx) = self.selected() {
+                    self.open_part(idx, context);
+                    self.set_dirty(true);
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["save_part"]) =>
+            {
+                if let Some(idx) = self.selected() {
+                    self.save_part(idx, context);
+                }
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        if let Some(ref v) = self.viewing {
+            return v.get_shortcuts(context);
+        }
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert(
+            Self::DESCRIPTION,
+            context.settings.shortcuts.mime_tree.key_values(),
+        );
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.viewing.as_ref().map(|v| v.is_dirty()).unwrap_or(false)
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+        if let Some(ref mut v) = self.viewing {
+            v.set_dirty(value);
+        }
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[test]
+fn test_mime_tree_uses_attachment_opener_settings() {
+    use crate::testing::Headless;
+    let dir = std::env::temp_dir().join(format!("meli-mime-tree-opener-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let sandbox = dir.join("sandbox.sh");
+    std::fs::write(
+        &sandbox,
+        format!("touch {}\nexec \"$@\"\n", dir.join("sandboxed").display()),
+    )
+    .unwrap();
+    let mut h = Headless::new(
+        &[
+            "From: Alice <alice@example.com>\r\nTo: test@example.com\r\nSubject: an attachment\r\n\
+           Date: Wed, 01 Jan 2020 10:00:00 +0000\r\n\
+           Message-ID: <attachment@headless.example.com>\r\nMIME-Version: 1.0\r\n\
+           Content-Type: multipart/mixed; boundary=\"b\"\r\n\r\n--b\r\n\
+           Content-Type: text/plain\r\n\r\nSee attached.\r\n--b\r\n\
+           Content-Type: application/x-meli-test; name=\"a.test\"\r\n\r\nattached\r\n--b--\r\n"
+                .to_string(),
+        ],
+        &format!(
+            "[pager]\nattachment_sandbox = \"sh {}\"\n\n[pager.attachment_openers]\n\
+             \"application/x-meli-test\" = \"cp %s {}\"\n",
+            sandbox.display(),
+            dir.join("opened").display()
+        ),
+    );
+    h.wait_for("an attachment");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("See attached.");
+    h.keys(&[Key::Char('M')]);
+    h.wait_for("application/x-meli-test");
+    h.keys(&[Key::Down, Key::Down, Key::Char('\n')]);
+    h.wait_until("the attachment to be opened", |_| {
+        dir.join("opened").exists() && dir.join("sandboxed").exists()
+    });
+    assert_eq!(
+        std::fs::read_to_string(dir.join("opened")).unwrap().trim(),
+        "attached"
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -21,6 +21,7 @@
 
 use super::*;
 use crate::components::PageMovement;
+use melib::backends::ResultFuture;
 use std::cmp;
 
 #[derive(Debug, Clone)]
@@ -35,6 +36,16 @@ struct ThreadEntry {
     heading: String,
 }
 
+/// A search of the thread that hasn't finished yet. Copies of the view don't wait for it.
+#[derive(Debug, Default)]
+struct PendingSearch(Option<(String, JoinHandle<Result<Vec<EnvelopeHash>>>)>);
+
+impl Clone for PendingSearch {
+    fn clone(&self) -> Self {
+        PendingSearch(None)
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ThreadView {
     new_cursor_pos: usize,
@@ -51,6 +62,9 @@ pub struct ThreadView {
     visible_entries: Vec<Vec<usize>>,
     indentation_colors: [ThemeAttribute; 6],
     use_color: bool,
+    /// The messages that contain the pattern of the `thread-search` command.
+    search_matches: Option<Vec<EnvelopeHash>>,
+    pending_search: PendingSearch,
 
     movement: Option<PageMovement>,
     dirty: bool,
@@ -95,10 +109,147 @@ impl ThreadView {
             use_color: context.settings.terminal.use_color(),
             ..Default::default()
         };
+        let expanded_hash = expanded_hash.or_else(|| view.remembered_position(context));
         view.initiate(expanded_hash, context);
         view.new_cursor_pos = view.new_expanded_pos;
         view
     }
+
+    /// Expand the first unseen message shown after the position `after`, or the first one.
+    fn select_unseen(&mut self, after: Option<usize>) -> bool {
+        let visibles: Vec<usize> = self
+            .visible_entries
+            .iter()
+            .flat_map(|v| v.iter())
+            .cloned()
+            .collect();
+        let start = after.map(|pos| pos + 1).unwrap_or(0);
+        if let Some(pos) = (start..visibles.len()).find(|&pos| !self.entries[visibles[pos]].seen) {
+            self.new_cursor_pos = pos;
+            self.new_expanded_pos = visibles[pos];
+            self.show_mailview = true;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Expand the message that matches the thread search shown after (or before, if not
+    /// `forward`) the position `after`, or the first one.
+    fn select_search_match(&mut self, after: Option<usize>, forward: bool) -> bool {
+        let matches = match self.search_matches {
+            Some(ref matches) => matches,
+            None => return false,
+        };
+        let visibles: Vec<usize> = self
+            .visible_entries
+            .iter()
+            .flat_map(|v| v.iter())
+            .cloned()
+            .collect();
+        let is_match = |pos: &usize| matches.contains(&self.entries[visibles[*pos]].msg_hash);
+        let found = match after {
+            None => (0..visibles.len()).find(is_match),
+            Some(pos) if forward => (pos + 1..visibles.len()).find(is_match),
+            Some(pos) => (0..pos).rev().find(is_match),
+        };
+        if let Some(pos) = found {
+            self.new_cursor_pos = pos;
+            self.new_expanded_pos = visibles[pos];
+            self.show_mailview = true;
+            self.set_dirty(true);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Start looking for `pattern` in the messages of the thread.
+    fn search(&mut self, pattern: String, context: &mut Context) {
+        let account = &context.accounts[&self.coordinates.0];
+        let messages = self
+            .entries
+            .iter()
+            .map(|e| {
+                (
+                    e.msg_hash,
+                    account
+                        .operation(e.msg_hash)
+                        .and_then(|mut op| op.as_bytes()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let handle = account
+            .job_executor
+            .spawn_specialized(search_messages(messages, pattern.clone()));
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        self.pending_search = PendingSearch(Some((pattern, handle)));
+    }
+
+    /// Show the result of the search of the thread.
+    fn finish_search(
+        &mut self,
+        pattern: String,
+        matches: Vec<EnvelopeHash>,
+        context: &mut Context,
+    ) {
+        let message = if matches.is_empty() {
+            format!("No messages of the thread contain `{}`.", pattern)
+        } else {
+            format!(
+                "{} of {} messages contain `{}`.",
+                matches.len(),
+                self.entries.len(),
+                pattern
+            )
+        };
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+        if matches.is_empty() {
+            self.clear_search();
+            return;
+        }
+        self.mailview.set_highlight(Some(pattern));
+        self.search_matches = Some(matches);
+        self.select_search_match(None, true);
+    }
+
+    fn clear_search(&mut self) {
+        self.search_matches = None;
+        self.mailview.set_highlight(None);
+    }
+
+    /// Message-ID of the first message in the thread, the key of the thread in
+    /// `Context::read_positions`.
+    fn thread_key(&self, context: &Context) -> Option<String> {
+        let account = &context.accounts[&self.coordinates.0];
+        let threads = account.collection.get_threads(self.coordinates.1);
+        if !threads.groups.contains_key(&self.thread_group) {
+            return None;
+        }
+        let env_hash = threads
+            .thread_group_iter(self.thread_group)
+            .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+            .next()?;
+        Some(ReadPositions::message_id(account, env_hash))
+    }
+
+    /// The message the user last read in this thread.
+    fn remembered_position(&self, context: &Context) -> Option<ThreadNodeHash> {
+        let key = self.thread_key(context)?;
+        let account = &context.accounts[&self.coordinates.0];
+        let message_id = context.read_positions.thread(account.name(), &key)?;
+        let threads = account.collection.get_threads(self.coordinates.1);
+        threads.find_message_id(message_id.as_bytes()).filter(|h| {
+            threads.thread_nodes()[h].message().is_some()
+                && threads.find_group(threads.thread_nodes()[h].group)
+                    == threads.find_group(self.thread_group)
+        })
+    }
     pub fn update(&mut self, context: &Context) {
         if self.entries.is_empty() {
             return;
@@ -198,6 +349,11 @@ impl ThreadView {
 
         let mut highlight_reply_subjects: Vec<Option<usize>> =
             Vec::with_capacity(self.entries.len());
+        let show_sender_timezone = *mailbox_settings!(
+            context[self.coordinates.0][&self.coordinates.1]
+                .pager
+                .show_sender_timezone
+        );
         for e in &mut self.entries {
             let envelope: EnvelopeRef = context.accounts[&self.coordinates.0]
                 .collection
@@ -208,7 +364,7 @@ impl ThreadView {
                 highlight_reply_subjects.push(Some(subject.grapheme_width()));
                 format!(
                     "  {} - {} {}{}",
-                    envelope.date_as_str(),
+                    display_date(&envelope, show_sender_timezone),
                     envelope.field_from_to_string(),
                     envelope.subject(),
                     if envelope.has_attachments() {
@@ -221,7 +377,7 @@ impl ThreadView {
                 highlight_reply_subjects.push(None);
                 format!(
                     "  {} - {}{}",
-                    envelope.date_as_str(),
+                    display_date(&envelope, show_sender_timezone),
                     envelope.field_from_to_string(),
                     if envelope.has_attachments() {
                         " 📎"
@@ -960,6 +1116,34 @@ impl ThreadView {
     }
 }
 
+/// The messages whose headers or text contain `pattern`.
+async fn search_messages(
+    messages: Vec<(EnvelopeHash, ResultFuture<Vec<u8>>)>,
+    pattern: String,
+) -> Result<Vec<EnvelopeHash>> {
+    let mut ret = vec![];
+    for (env_hash, fut) in messages {
+        if message_contains(&fut?.await?, &pattern) {
+            ret.push(env_hash);
+        }
+    }
+    Ok(ret)
+}
+
+/// Whether the decoded headers or the text of the message in `bytes` contain `pattern`. Like the
+/// search of the pager, the comparison is case sensitive.
+fn message_contains(bytes: &[u8], pattern: &str) -> bool {
+    let envelope = match Envelope::from_bytes(bytes, None) {
+        Ok(envelope) => envelope,
+        Err(_) => return false,
+    };
+    envelope
+        .other_headers()
+        .values()
+        .any(|value| value.contains(pattern))
+        || envelope.body_bytes(bytes).text().contains(pattern)
+}
+
 impl fmt::Display for ThreadView {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "view thread")
@@ -986,6 +1170,16 @@ impl Component for ThreadView {
                 self.entries[self.current_pos()].msg_hash,
             );
             self.mailview.update(coordinates, context);
+            if let Some(key) = self.thread_key(context) {
+                let account = &context.accounts[&self.coordinates.0];
+                let (account_name, message_id) = (
+                    account.name().to_string(),
+                    ReadPositions::message_id(account, coordinates.2),
+                );
+                context
+                    .read_positions
+                    .set_thread(&account_name, key, message_id);
+            }
         }
 
         if self.entries.len() == 1 {
@@ -1010,6 +1204,31 @@ impl Component for ThreadView {
                     .push_back(UIEvent::Action(Tab(New(Some(Box::new(self.clone()))))));
                 return true;
             }
+            UIEvent::Action(View(ViewAction::SearchThread(ref pattern))) => {
+                self.search(pattern.to_string(), context);
+                return true;
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id)) if matches!(self.pending_search.0, Some((_, ref handle)) if handle.job_id == *job_id) =>
+            {
+                let (pattern, mut handle) = self.pending_search.0.take().unwrap();
+                match handle.chan.try_recv() {
+                    Err(_) | Ok(None) => { /* search was canceled */ }
+                    Ok(Some(Err(err))) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Could not search the thread: {}",
+                                err
+                            )),
+                        ));
+                    }
+                    Ok(Some(Ok(matches))) => self.finish_search(pattern, matches, context),
+                }
+                return true;
+            }
+            UIEvent::Input(Key::Esc) if self.search_matches.is_some() => {
+                self.clear_search();
+                return true;
+            }
             _ => {}
         }
 
@@ -1023,7 +1242,9 @@ impl Component for ThreadView {
                 if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["scroll_up"]) =>
             {
                 if self.cursor_pos > 0 {
-                    self.new_cursor_pos = self.new_cursor_pos.saturating_sub(1);
+                    self.new_cursor_pos = self
+                        .new_cursor_pos
+                        .saturating_sub(context.count.unwrap_or(1));
                     self.dirty = true;
                 }
                 return true;
@@ -1033,7 +1254,8 @@ impl Component for ThreadView {
             {
                 let height = self.visible_entries.iter().flat_map(|v| v.iter()).count();
                 if height > 0 && self.new_cursor_pos + 1 < height {
-                    self.new_cursor_pos += 1;
+                    self.new_cursor_pos =
+                        std::cmp::min(self.new_cursor_pos + context.count.unwrap_or(1), height - 1);
                     self.dirty = true;
                 }
                 return true;
@@ -1041,13 +1263,13 @@ impl Component for ThreadView {
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["prev_page"]) =>
             {
-                self.movement = Some(PageMovement::PageUp(1));
+                self.movement = Some(PageMovement::PageUp(context.count.unwrap_or(1)));
                 self.dirty = true;
             }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["next_page"]) =>
             {
-                self.movement = Some(PageMovement::PageDown(1));
+                self.movement = Some(PageMovement::PageDown(context.count.unwrap_or(1)));
                 self.dirty = true;
             }
             UIEvent::Input(ref key) if *key == Key::Home => {
@@ -1081,6 +1303,50 @@ impl Component for ThreadView {
                 self.set_dirty(true);
                 return true;
             }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["first_unread"]) =>
+            {
+                if !self.select_unseen(None) {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No unread messages.".to_string(),
+                        )));
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["next_unread"]) =>
+            {
+                /* Let the listing go to the next unread thread if this is the last one */
+                return self.select_unseen(Some(self.new_cursor_pos));
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["next_search_match"])
+                    && self.search_matches.is_some() =>
+            {
+                if !self.select_search_match(Some(self.new_cursor_pos), true) {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No more matches.".to_string(),
+                        )));
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["prev_search_match"])
+                    && self.search_matches.is_some() =>
+            {
+                if !self.select_search_match(Some(self.new_cursor_pos), false) {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No more matches.".to_string(),
+                        )));
+                }
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[ThreadView::DESCRIPTION]["reverse_thread_order"]) =>
             {
@@ -1193,3 +1459,44 @@ impl Component for ThreadView {
             .push_back(UIEvent::Action(Tab(Kill(self.id))));
     }
 }
+
+#[test]
+fn test_thread_view_search() {
+    use crate::testing::{message, Headless};
+    let reply = |subject: &str, date: &str| {
+        message(subject, "Bob <bob@example.com>", date).replacen(
+            "\r\n\r\n",
+            "\r\nIn-Reply-To: <question@headless.example.com>\r\n\r\n",
+            1,
+        )
+    };
+    let mut h = Headless::new(
+        &[
+            message(
+                "question",
+                "Alice <alice@example.com>",
+                "Wed, 01 Jan 2020 10:00:00 +0000",
+            ),
+            reply("answer", "Thu, 02 Jan 2020 10:00:00 +0000"),
+            reply("thanks", "Fri, 03 Jan 2020 10:00:00 +0000"),
+        ],
+        "",
+    );
+    h.wait_for("question");
+    h.keys(&[Key::Char('\n')]);
+    h.wait_for("Body of thanks.");
+    h.command("thread-search Body of");
+    h.wait_for("3 of 3 messages contain `Body of`.");
+    h.wait_for("Subject: question");
+    h.keys(&[Key::Ctrl('n')]);
+    h.wait_for("Subject: answer");
+    h.keys(&[Key::Ctrl('p'), Key::Ctrl('p')]);
+    h.wait_for("No more matches.");
+    assert!(h.find("Subject: question").is_some(), "{}", h.text());
+
+    /* Headers are searched too. */
+    h.command("thread-search Alice");
+    h.wait_for("1 of 3 messages contain `Alice`.");
+    h.command("thread-search nowhere");
+    h.wait_for("No messages of the thread contain");
+}
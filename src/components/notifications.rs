@@ -118,7 +118,8 @@ mod dbus {
 
                 if let Err(err) = notification.show() {
                     debug!("Could not show dbus notification: {:?}", &err);
-                    melib::log(
+                    melib::log_target(
+                        "ui",
                         format!("Could not show dbus notification: {}", err),
                         melib::ERROR,
                     );
@@ -203,7 +204,8 @@ impl Component for NotificationCommand {
                             context.children.push(child);
                         }
                         Err(err) => {
-                            log(
+                            log_target(
+                                "ui",
                                 format!("Could not run notification script: {}.", err.to_string()),
                                 ERROR,
                             );
@@ -217,7 +219,11 @@ impl Component for NotificationCommand {
                 if let Some(ref path) = context.settings.notifications.xbiff_file_path {
                     if let Err(err) = update_xbiff(path) {
                         debug!("Could not update xbiff file: {:?}", &err);
-                        melib::log(format!("Could not update xbiff file: {}.", err), ERROR);
+                        melib::log_target(
+                            "ui",
+                            format!("Could not update xbiff file: {}.", err),
+                            ERROR,
+                        );
                     }
                 }
             }
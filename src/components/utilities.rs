@@ -36,6 +36,13 @@ pub use self::layouts::*;
 mod dialogs;
 pub use self::dialogs::*;
 
+mod log_viewer;
+pub use self::log_viewer::*;
+mod messages_viewer;
+pub use self::messages_viewer::*;
+mod help_viewer;
+pub use self::help_viewer::*;
+
 use crate::jobs::JobId;
 use std::collections::HashSet;
 
@@ -68,6 +75,8 @@ pub struct StatusBar {
 
     auto_complete: AutoComplete,
     cmd_history: Vec<String>,
+    /// The name of the profile meli was started with, shown first.
+    profile: Option<String>,
 }
 
 impl fmt::Display for StatusBar {
@@ -110,6 +119,7 @@ impl StatusBar {
             done_jobs: HashSet::default(),
             scroll_contexts: IndexMap::default(),
             cmd_history: crate::command::history::old_cmd_history(),
+            profile: melib::paths::profile().ok().flatten(),
         }
     }
 
@@ -118,13 +128,56 @@ impl StatusBar {
         if !context.settings.terminal.use_color() {
             attribute.attrs |= Attr::REVERSE;
         }
+        let area = if let Some(profile) = self.profile.as_ref() {
+            let (x, y) = write_string_to_grid(
+                &format!(" {} ", profile),
+                grid,
+                attribute.bg,
+                attribute.fg,
+                attribute.attrs | Attr::BOLD,
+                area,
+                None,
+            );
+            ((x, y), bottom_right!(area))
+        } else {
+            area
+        };
+        /* The account of the focused tab in its accent color. */
+        let accent = self
+            .container
+            .account_hash()
+            .and_then(|account_hash| context.accounts.get(&account_hash))
+            .and_then(|account| Some((account.name(), account.accent_color()?)));
+        let status_area = if let Some((name, color)) = accent {
+            let (x, y) = write_string_to_grid(
+                &format!(" {} ", name),
+                grid,
+                attribute.fg,
+                color,
+                attribute.attrs | Attr::BOLD,
+                area,
+                None,
+            );
+            ((x, y), bottom_right!(area))
+        } else {
+            area
+        };
+        /* Leave room for the progress spinner and the pending keys on the right. */
+        let reserved = self.display_buffer.grapheme_width()
+            + if self.progress_spinner.is_active() {
+                1 + self.progress_spinner.width
+            } else {
+                0
+            };
         let (x, y) = write_string_to_grid(
-            &self.status,
+            &self
+                .status
+                .trim_with_ellipsis(width!(status_area).saturating_sub(reserved)),
             grid,
             attribute.fg,
             attribute.bg,
             attribute.attrs,
-            area,
+            status_area,
             None,
         );
         for c in grid.row_iter(x..(get_x(bottom_right!(area)) + 1), y) {
@@ -136,9 +189,9 @@ impl StatusBar {
         }
         let offset = self.status.find('|').unwrap_or_else(|| self.status.len());
         if y < get_y(bottom_right!(area)) + 1 {
-            for x in get_x(upper_left!(area))
+            for x in get_x(upper_left!(status_area))
                 ..std::cmp::min(
-                    get_x(upper_left!(area)) + offset,
+                    get_x(upper_left!(status_area)) + offset,
                     get_x(bottom_right!(area)),
                 )
             {
@@ -151,10 +204,11 @@ impl StatusBar {
                 shown_lines,
                 total_lines,
                 has_more_lines,
+                columns,
             },
         )) = self.scroll_contexts.last()
         {
-            let s = format!(
+            let mut s = format!(
                 "| {shown_percentage}% {line_desc}{shown_lines}/{total_lines}{has_more_lines}",
                 line_desc = if grid.ascii_drawing { "lines:" } else { "☰ " },
                 shown_percentage = (*shown_lines as f32 / (*total_lines as f32) * 100.0) as usize,
@@ -162,6 +216,14 @@ impl StatusBar {
                 total_lines = *total_lines,
                 has_more_lines = if *has_more_lines { "(+)" } else { "" }
             );
+            if let Some((shown_columns, total_columns)) = columns {
+                s.push_str(&format!(
+                    " {column_desc}{shown_columns}/{total_columns}",
+                    column_desc = if grid.ascii_drawing { "cols:" } else { "↔ " },
+                    shown_columns = *shown_columns,
+                    total_columns = *total_columns,
+                ));
+            }
             write_string_to_grid(
                 &s,
                 grid,
@@ -200,17 +262,30 @@ impl StatusBar {
 
     fn draw_command_bar(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         clear_area(grid, area, crate::conf::value(context, "theme_default"));
+        let mut prompt_area = area;
+        if let Some(scope) = self.container.command_scope(context) {
+            let (x, _) = write_string_to_grid(
+                &format!("[{}] ", scope),
+                grid,
+                Color::Byte(219),
+                Color::Byte(88),
+                Attr::BOLD,
+                area,
+                None,
+            );
+            prompt_area = (set_x(upper_left!(area), x), bottom_right!(area));
+        }
         let (_, y) = write_string_to_grid(
             self.ex_buffer.as_str(),
             grid,
             Color::Byte(219),
             Color::Byte(88),
             Attr::DEFAULT,
-            area,
+            prompt_area,
             None,
         );
         if let Some(ref mut cell) = grid.get_mut(
-            pos_inc(upper_left!(area), (self.ex_buffer.cursor(), 0)).0,
+            pos_inc(upper_left!(prompt_area), (self.ex_buffer.cursor(), 0)).0,
             y,
         ) {
             cell.set_attrs(Attr::UNDERLINE);
@@ -789,6 +864,18 @@ impl Component for StatusBar {
     fn can_quit_cleanly(&mut self, context: &Context) -> bool {
         self.container.can_quit_cleanly(context)
     }
+
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        self.container.save_session(session, context);
+    }
+
+    fn command_scope(&self, context: &Context) -> Option<String> {
+        self.container.command_scope(context)
+    }
+
+    fn account_hash(&self) -> Option<AccountHash> {
+        self.container.account_hash()
+    }
 }
 
 #[derive(Debug)]
@@ -803,6 +890,8 @@ pub struct Tabbed {
     help_curr_views: ShortcutMaps,
     help_search: Option<SearchPattern>,
     theme_default: ThemeAttribute,
+    /// The current tab is maximized and the tab bar is hidden.
+    zoomed: bool,
 
     dirty: bool,
     id: ComponentId,
@@ -824,6 +913,7 @@ impl Tabbed {
             children,
             cursor_pos: 0,
             show_shortcuts: false,
+            zoomed: false,
             dirty: true,
             id: ComponentId::new_v4(),
         };
@@ -849,11 +939,26 @@ impl Tabbed {
         let mut x = get_x(upper_left);
         let y: usize = get_y(upper_left);
         for (idx, c) in self.children.iter().enumerate() {
-            let ThemeAttribute { fg, bg, attrs } = if idx == self.cursor_pos {
+            let ThemeAttribute {
+                mut fg,
+                mut bg,
+                attrs,
+            } = if idx == self.cursor_pos {
                 tab_focused_attribute
             } else {
                 tab_unfocused_attribute
             };
+            if let Some(color) = c
+                .account_hash()
+                .and_then(|account_hash| context.accounts.get(&account_hash))
+                .and_then(|account| account.accent_color())
+            {
+                if idx == self.cursor_pos {
+                    bg = color;
+                } else {
+                    fg = color;
+                }
+            }
             let (x_, _y_) = write_string_to_grid(
                 &format!(" {} ", c),
                 grid,
@@ -910,6 +1015,30 @@ impl Tabbed {
     pub fn add_component(&mut self, new: Box<dyn Component>) {
         self.children.push(new);
     }
+
+    /// Restore the layout of the current tab if it's maximized, before leaving it.
+    fn unzoom(&mut self, context: &mut Context) {
+        if self.zoomed {
+            self.zoomed = false;
+            self.children[self.cursor_pos].process_event(&mut UIEvent::ZoomChange(false), context);
+        }
+    }
+}
+
+/// The descriptions of the commands that apply to the components with shortcuts `maps`: those of
+/// their sections and the general ones.
+fn help_commands(maps: &ShortcutMaps) -> Vec<&'static str> {
+    let mut seen = HashSet::new();
+    /* There is an entry for each tag of a command, the section is that of the first. */
+    crate::command::COMMAND_COMPLETION
+        .iter()
+        .filter(|(_, desc, _)| seen.insert(*desc))
+        .filter(|(tag, _, _)| match crate::command::command_section(tag) {
+            Some(section) => maps.contains_key(section),
+            None => true,
+        })
+        .map(|(_, desc, _)| *desc)
+        .collect()
 }
 
 impl fmt::Display for Tabbed {
@@ -941,7 +1070,7 @@ impl Component for Tabbed {
 
         /* children should be drawn after the shortcuts/help panel lest they overwrite the panel on
          * the grid. the drawing order is determined by the dirty_areas queue which is LIFO */
-        if self.children.len() > 1 {
+        if self.children.len() > 1 && !self.zoomed {
             self.draw_tabs(
                 grid,
                 (
@@ -1037,6 +1166,7 @@ impl Component for Tabbed {
                                     ) + rows,
                                     total_lines: height,
                                     has_more_lines: false,
+                                    columns: None,
                                 },
                             },
                         )));
@@ -1066,7 +1196,7 @@ impl Component for Tabbed {
             }
             let mut max_length = 6;
             let mut max_width =
-                "Press ? to close, use COMMAND \"search\" to find shortcuts".len() + 3;
+                "Press ? to close, use COMMAND \"search\" to find shortcuts and commands".len() + 3;
 
             for (desc, shortcuts) in children_maps.iter() {
                 max_length += shortcuts.len() + 3;
@@ -1082,11 +1212,16 @@ impl Component for Tabbed {
                     ),
                 );
             }
+            let commands = help_commands(&children_maps)
+                .into_iter()
+                .map(|desc| desc.split_lines_reflow(Reflow::All, Some(max_width.saturating_sub(6))))
+                .collect::<Vec<Vec<String>>>();
+            max_length += commands.iter().map(Vec::len).sum::<usize>() + 3;
             self.help_content =
                 CellBuffer::new_with_context(max_width, max_length + 2, None, context);
             self.help_content.set_growable(true);
             write_string_to_grid(
-                "use COMMAND \"search\" to find shortcuts",
+                "use COMMAND \"search\" to find shortcuts and commands, \"help\" for the manual",
                 &mut self.help_content,
                 self.theme_default.fg,
                 self.theme_default.bg,
@@ -1129,6 +1264,36 @@ impl Component for Tabbed {
                 }
                 idx += 1;
             }
+            if !commands.is_empty() {
+                write_string_to_grid(
+                    "commands",
+                    &mut self.help_content,
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs,
+                    ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
+                    None,
+                );
+                idx += 2;
+                for lines in commands {
+                    for (i, line) in lines.iter().enumerate() {
+                        /* Continuation lines are indented. */
+                        write_string_to_grid(
+                            line,
+                            &mut self.help_content,
+                            self.theme_default.fg,
+                            self.theme_default.bg,
+                            self.theme_default.attrs,
+                            (
+                                (if i == 0 { 2 } else { 4 }, 2 + idx),
+                                (max_width.saturating_sub(2), max_length - 1),
+                            ),
+                            None,
+                        );
+                        idx += 1;
+                    }
+                }
+            }
             self.help_curr_views = children_maps;
             let dialog_area = align_area(
                 area,
@@ -1275,6 +1440,7 @@ impl Component for Tabbed {
                                 ) + rows,
                                 total_lines: height,
                                 has_more_lines: false,
+                                columns: None,
                             },
                         },
                     )));
@@ -1312,6 +1478,7 @@ impl Component for Tabbed {
             UIEvent::Input(Key::Alt(no)) if *no >= '1' && *no <= '9' => {
                 let no = *no as usize - '1' as usize;
                 if no < self.children.len() && self.cursor_pos != no % self.children.len() {
+                    self.unzoom(context);
                     self.children[self.cursor_pos]
                         .process_event(&mut UIEvent::VisibilityChange(false), context);
                     self.cursor_pos = no % self.children.len();
@@ -1328,6 +1495,7 @@ impl Component for Tabbed {
                 return true;
             }
             UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["next_tab"]) => {
+                self.unzoom(context);
                 self.children[self.cursor_pos]
                     .process_event(&mut UIEvent::VisibilityChange(false), context);
                 self.cursor_pos = (self.cursor_pos + 1) % self.children.len();
@@ -1342,6 +1510,14 @@ impl Component for Tabbed {
                 self.set_dirty(true);
                 return true;
             }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["toggle_zoom"]) => {
+                self.zoomed = !self.zoomed;
+                let zoomed = self.zoomed;
+                self.children[self.cursor_pos]
+                    .process_event(&mut UIEvent::ZoomChange(zoomed), context);
+                self.set_dirty(true);
+                return true;
+            }
             UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["toggle_help"]) => {
                 if self.show_shortcuts {
                     /* children below the shortcut overlay must be redrawn */
@@ -1358,6 +1534,7 @@ impl Component for Tabbed {
             }
             UIEvent::Action(Tab(New(ref mut e))) if e.is_some() => {
                 self.add_component(e.take().unwrap());
+                self.unzoom(context);
                 self.children[self.cursor_pos]
                     .process_event(&mut UIEvent::VisibilityChange(false), context);
                 self.cursor_pos = self.children.len() - 1;
@@ -1384,6 +1561,7 @@ impl Component for Tabbed {
                     return true;
                 }
                 if let Some(c_idx) = self.children.iter().position(|x| x.id() == *id) {
+                    self.unzoom(context);
                     self.children[c_idx]
                         .process_event(&mut UIEvent::VisibilityChange(false), context);
                     self.children.remove(c_idx);
@@ -1526,6 +1704,19 @@ impl Component for Tabbed {
         }
         true
     }
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        for c in self.children.iter() {
+            c.save_session(session, context);
+        }
+    }
+
+    fn command_scope(&self, context: &Context) -> Option<String> {
+        self.children[self.cursor_pos].command_scope(context)
+    }
+
+    fn account_hash(&self) -> Option<AccountHash> {
+        self.children[self.cursor_pos].account_hash()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -0,0 +1,179 @@
+/*
+ * meli - help tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::manpages::{self, ManPages};
+
+/// Tab with a manual page or the list of commands, opened with the `help` command. It's searched
+/// with the `search` command like the pager.
+#[derive(Debug)]
+pub struct HelpViewer {
+    /// `None` for the list of commands.
+    page: Option<ManPages>,
+    pager: Pager,
+    id: ComponentId,
+}
+
+impl fmt::Display for HelpViewer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.page {
+            Some(ManPages::Main) => write!(f, "{}: meli(1)", HelpViewer::DESCRIPTION),
+            Some(ManPages::Conf) => write!(f, "{}: meli.conf(5)", HelpViewer::DESCRIPTION),
+            Some(ManPages::Themes) => write!(f, "{}: meli-themes(5)", HelpViewer::DESCRIPTION),
+            None => write!(f, "{}: commands", HelpViewer::DESCRIPTION),
+        }
+    }
+}
+
+impl HelpViewer {
+    pub const DESCRIPTION: &'static str = "help";
+    pub fn new(page: Option<ManPages>, context: &Context) -> HelpViewer {
+        let text = match page.map(manpages::read) {
+            Some(Ok(text)) => strip_overstrike(&text),
+            /* The list of commands is still of help without the manual. */
+            Some(Err(err)) => format!("{}\n\n{}", err, commands_text()),
+            None => commands_text(),
+        };
+        let mut pager = Pager::new(context);
+        pager.update_from_str(&text, None);
+        HelpViewer {
+            page,
+            pager,
+            id: ComponentId::new_v4(),
+        }
+    }
+}
+
+/// The commands with their description, by the section of the components they apply to.
+pub fn commands_text() -> String {
+    let mut sections: IndexMap<&'static str, Vec<&'static str>> = IndexMap::default();
+    sections.insert("general", vec![]);
+    let mut seen = HashSet::new();
+    /* There is an entry for each tag of a command, the section is that of the first. */
+    for (tag, desc, _) in crate::command::COMMAND_COMPLETION.iter() {
+        if seen.insert(desc) {
+            let section = crate::command::command_section(tag).unwrap_or("general");
+            sections.entry(section).or_default().push(desc);
+        }
+    }
+    let mut ret = String::from("COMMANDS\n\nCommands are entered in COMMAND mode. Those listed under a view apply to it.\n");
+    for (section, descs) in sections {
+        ret.push('\n');
+        ret.push_str(section);
+        ret.push('\n');
+        for desc in descs {
+            ret.push_str("    ");
+            ret.push_str(desc);
+            ret.push('\n');
+        }
+    }
+    ret
+}
+
+/// Remove the backspace sequences `mandoc` renders bold and underlined text with.
+fn strip_overstrike(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\x08' {
+            ret.pop();
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+impl Component for HelpViewer {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        self.pager.draw(grid, area, context);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if let UIEvent::ConfigReload { old_settings: _ } = event {
+            self.pager
+                .set_colors(crate::conf::value(context, "theme_default"));
+            self.pager.set_dirty(true);
+        }
+        self.pager.process_event(event, context)
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret = self.pager.get_shortcuts(context);
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.pager.is_dirty()
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.pager.set_dirty(value);
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[test]
+fn test_help_viewer_text() {
+    assert_eq!(
+        strip_overstrike("N\x08NA\x08AME\n_\x08m_\x08eli"),
+        "NAME\nmeli"
+    );
+    use crate::command::parse_command;
+    assert!(matches!(
+        parse_command(b"help"),
+        Ok(Action::Help(Some(ManPages::Main)))
+    ));
+    assert!(matches!(
+        parse_command(b"help conf"),
+        Ok(Action::Help(Some(ManPages::Conf)))
+    ));
+    assert!(matches!(
+        parse_command(b"help commands"),
+        Ok(Action::Help(None))
+    ));
+    assert!(parse_command(b"help me").is_err());
+
+    let text = commands_text();
+    let sections = text
+        .split("\n\n")
+        .skip(2)
+        .map(|s| s.split_at(s.find('\n').unwrap()))
+        .collect::<IndexMap<&str, &str>>();
+    assert_eq!(sections.keys().next(), Some(&"general"));
+    assert!(sections["general"].contains("\n    help [meli|conf|themes|commands]"));
+    assert!(sections["composing"].contains("\n    save draft"));
+    assert!(!sections["general"].contains("save draft"));
+    /* Commands with several tags are listed once, in the section of their first tag. */
+    assert_eq!(
+        text.matches("set [plain/threaded/compact/conversations]")
+            .count(),
+        1
+    );
+    assert!(sections["listing"].contains("set [plain/threaded/compact/conversations]"));
+}
@@ -0,0 +1,300 @@
+/*
+ * meli - log viewer tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use melib::{LogRecord, LoggingLevel};
+
+/// Tab with the tail of the log, opened with the `view-log` command.
+#[derive(Debug)]
+pub struct LogViewer {
+    cursor: (usize, usize),
+    records: Vec<LogRecord>,
+    /// Show only records up to this level.
+    level: LoggingLevel,
+    /// Show only records containing this pattern.
+    search: String,
+    /// The search pattern is being typed.
+    searching: bool,
+    content: CellBuffer,
+    dirty: bool,
+    theme_default: ThemeAttribute,
+    id: ComponentId,
+}
+
+impl fmt::Display for LogViewer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", LogViewer::DESCRIPTION)
+    }
+}
+
+impl LogViewer {
+    pub const DESCRIPTION: &'static str = "log";
+    pub fn new(context: &Context) -> LogViewer {
+        let theme_default = crate::conf::value(context, "theme_default");
+        LogViewer {
+            /* Start at the newest records, like `tail` */
+            cursor: (0, usize::MAX),
+            records: melib::recent_log_records(),
+            level: LoggingLevel::TRACE,
+            search: String::new(),
+            searching: false,
+            content: CellBuffer::new_with_context(120, 5, None, context),
+            dirty: true,
+            theme_default,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    fn next_level(level: LoggingLevel) -> LoggingLevel {
+        use LoggingLevel::*;
+        match level {
+            TRACE => DEBUG,
+            DEBUG => INFO,
+            INFO => WARN,
+            WARN => ERROR,
+            OFF | FATAL | ERROR => TRACE,
+        }
+    }
+
+    fn matches(&self, record: &LogRecord) -> bool {
+        record.level <= self.level
+            && (self.search.is_empty()
+                || record
+                    .to_string()
+                    .to_lowercase()
+                    .contains(&self.search.to_lowercase()))
+    }
+
+    fn draw_content(&mut self) {
+        let mut default_cell = Cell::with_char(' ');
+        default_cell
+            .set_fg(self.theme_default.fg)
+            .set_bg(self.theme_default.bg)
+            .set_attrs(self.theme_default.attrs);
+        self.content = CellBuffer::new(120, 5, default_cell);
+        self.content.set_growable(true);
+
+        let records = self
+            .records
+            .iter()
+            .filter(|r| self.matches(r))
+            .collect::<Vec<&LogRecord>>();
+        let header = format!(
+            "Log messages up to {}{}{} ({} of {})",
+            self.level,
+            if self.search.is_empty() && !self.searching {
+                ""
+            } else {
+                ", matching: "
+            },
+            self.search,
+            records.len(),
+            self.records.len()
+        );
+        let header = if self.searching {
+            format!("{}▁", header)
+        } else {
+            header
+        };
+        let mut lines = vec![(header, self.theme_default.fg, Attr::BOLD)];
+        lines.extend(records.iter().map(|r| {
+            let fg = match r.level {
+                LoggingLevel::FATAL | LoggingLevel::ERROR => Color::Red,
+                LoggingLevel::WARN => Color::Yellow,
+                _ => self.theme_default.fg,
+            };
+            (r.to_string(), fg, self.theme_default.attrs)
+        }));
+        for (i, (text, fg, attrs)) in lines.into_iter().enumerate() {
+            let width = self.content.size().0;
+            write_string_to_grid(
+                &text,
+                &mut self.content,
+                fg,
+                self.theme_default.bg,
+                attrs,
+                ((0, i), (width - 1, i)),
+                None,
+            );
+        }
+    }
+}
+
+impl Component for LogViewer {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.draw_content();
+
+        /* self.content may have been resized with write_string_to_grid() calls above since it has
+         * growable set */
+        let (width, height) = self.content.size();
+        let (cols, rows) = (width!(area), height!(area));
+        self.cursor = (
+            std::cmp::min(width.saturating_sub(cols), self.cursor.0),
+            std::cmp::min(height.saturating_sub(rows), self.cursor.1),
+        );
+        clear_area(grid, area, self.theme_default);
+        copy_area(
+            grid,
+            &self.content,
+            area,
+            (
+                (
+                    std::cmp::min((width - 1).saturating_sub(cols), self.cursor.0),
+                    std::cmp::min((height - 1).saturating_sub(rows), self.cursor.1),
+                ),
+                (
+                    std::cmp::min(self.cursor.0 + cols, width - 1),
+                    std::cmp::min(self.cursor.1 + rows, height - 1),
+                ),
+            ),
+        );
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.dirty = true;
+            }
+            UIEvent::InsertInput(Key::Char('\n')) if self.searching => {
+                self.searching = false;
+                self.cursor.1 = usize::MAX;
+                context
+                    .replies
+                    .push_back(UIEvent::ChangeMode(UIMode::Normal));
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Backspace) if self.searching => {
+                self.search.pop();
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Char(c)) if self.searching => {
+                self.search.push(c);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::InsertInput(Key::Paste(ref s)) if self.searching => {
+                self.search.push_str(s);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::ChangeMode(UIMode::Normal) if self.searching => {
+                /* Escape cancels the search */
+                self.searching = false;
+                self.search.clear();
+                self.dirty = true;
+                return false;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[LogViewer::DESCRIPTION]["search"]) =>
+            {
+                self.searching = true;
+                self.search.clear();
+                context
+                    .replies
+                    .push_back(UIEvent::ChangeMode(UIMode::Insert));
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[LogViewer::DESCRIPTION]["cycle_level"]) =>
+            {
+                self.level = Self::next_level(self.level);
+                self.cursor.1 = usize::MAX;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[LogViewer::DESCRIPTION]["refresh"]) =>
+            {
+                self.records = melib::recent_log_records();
+                self.cursor.1 = usize::MAX;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts["general"]["scroll_left"]) && self.cursor.0 != 0 =>
+            {
+                self.cursor.0 -= 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_right"]) => {
+                self.cursor.0 += 1;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_up"]) => {
+                self.cursor.1 = self.cursor.1.saturating_sub(1);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_down"]) => {
+                self.cursor.1 = self.cursor.1.saturating_add(1);
+                self.dirty = true;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert(
+            LogViewer::DESCRIPTION,
+            context.settings.shortcuts.log.key_values(),
+        );
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn save_session(&self, session: &mut Session, _context: &Context) {
+        session.tabs.push(SessionTab::Log);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ * meli - status message history tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::state::StatusMessage;
+
+/// Tab with the status messages and notifications shown so far, opened with the `messages`
+/// command.
+#[derive(Debug)]
+pub struct MessagesViewer {
+    /// Index of the selected message in `Context::message_history`, `usize::MAX` to follow the
+    /// newest one.
+    cursor: usize,
+    dirty: bool,
+    theme_default: ThemeAttribute,
+    highlight: ThemeAttribute,
+    id: ComponentId,
+}
+
+impl fmt::Display for MessagesViewer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MessagesViewer::DESCRIPTION)
+    }
+}
+
+impl MessagesViewer {
+    pub const DESCRIPTION: &'static str = "messages";
+    pub fn new(context: &Context) -> MessagesViewer {
+        let mut ret = MessagesViewer {
+            cursor: usize::MAX,
+            dirty: true,
+            theme_default: ThemeAttribute::default(),
+            highlight: ThemeAttribute::default(),
+            id: ComponentId::new_v4(),
+        };
+        ret.set_theme(context);
+        ret
+    }
+
+    fn set_theme(&mut self, context: &Context) {
+        self.theme_default = crate::conf::value(context, "theme_default");
+        self.highlight = crate::conf::value(context, "widgets.options.highlighted");
+        self.highlight.attrs |= Attr::REVERSE;
+    }
+
+    fn selected<'a>(&self, context: &'a Context) -> Option<&'a StatusMessage> {
+        let history = &context.message_history;
+        history.get(std::cmp::min(self.cursor, history.len().saturating_sub(1)))
+    }
+
+    fn format(message: &StatusMessage) -> String {
+        let kind = match message.kind {
+            Some(kind) => kind.to_string(),
+            None => "status".to_string(),
+        };
+        let text = match message.title {
+            Some(ref title) => format!("{}: {}", title, message.text),
+            None => message.text.clone(),
+        };
+        format!(
+            "{} [{}] {}",
+            melib::datetime::timestamp_to_string(message.timestamp, Some("%Y-%m-%d %T"), false),
+            kind,
+            text.replace('\n', " ")
+        )
+    }
+}
+
+impl Component for MessagesViewer {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        clear_area(grid, area, self.theme_default);
+        let history = &context.message_history;
+        write_string_to_grid(
+            &format!("Status messages and notifications ({})", history.len()),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            Attr::BOLD,
+            area,
+            None,
+        );
+        let area = (pos_inc(upper_left!(area), (0, 1)), bottom_right!(area));
+        let rows = height!(area);
+        if history.is_empty() || rows == 0 || get_y(upper_left!(area)) > get_y(bottom_right!(area))
+        {
+            context.dirty_areas.push_back(area);
+            return;
+        }
+        let cursor = std::cmp::min(self.cursor, history.len() - 1);
+        let top = (cursor / rows) * rows;
+        for (i, message) in history.iter().enumerate().skip(top).take(rows) {
+            let row_area = (
+                pos_inc(upper_left!(area), (0, i - top)),
+                set_y(bottom_right!(area), get_y(upper_left!(area)) + i - top),
+            );
+            let mut attrs = if i == cursor {
+                self.highlight
+            } else {
+                self.theme_default
+            };
+            if let Some(NotificationType::Error(_)) = message.kind {
+                attrs.fg = Color::Red;
+            }
+            clear_area(grid, row_area, attrs);
+            write_string_to_grid(
+                &Self::format(message),
+                grid,
+                attrs.fg,
+                attrs.bg,
+                attrs.attrs,
+                row_area,
+                None,
+            );
+        }
+        context.dirty_areas.push_back(area);
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.set_theme(context);
+                self.set_dirty(true);
+            }
+            UIEvent::Resize
+            | UIEvent::StatusEvent(StatusEvent::DisplayMessage(_))
+            | UIEvent::Notification(_, _, _) => {
+                self.dirty = true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[MessagesViewer::DESCRIPTION]["copy"]) =>
+            {
+                if let Some(message) = self.selected(context) {
+                    let text = message.text.clone();
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::CopyToClipboard(text)));
+                }
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_up"]) => {
+                let len = context.message_history.len();
+                self.cursor = std::cmp::min(self.cursor, len.saturating_sub(1)).saturating_sub(1);
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key) if shortcut!(key == shortcuts["general"]["scroll_down"]) => {
+                let last = context.message_history.len().saturating_sub(1);
+                let cursor = std::cmp::min(self.cursor, last) + 1;
+                self.cursor = if cursor >= last { usize::MAX } else { cursor };
+                self.dirty = true;
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut ret: ShortcutMaps = Default::default();
+        ret.insert(
+            MessagesViewer::DESCRIPTION,
+            context.settings.shortcuts.messages.key_values(),
+        );
+        ret.insert("general", context.settings.shortcuts.general.key_values());
+        ret
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn save_session(&self, session: &mut Session, _context: &Context) {
+        session.tabs.push(SessionTab::Messages);
+    }
+}
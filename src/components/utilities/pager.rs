@@ -25,9 +25,12 @@ use melib::text_processing::LineBreakText;
 /// A pager for text.
 /// `Pager` holds its own content in its own `CellBuffer` and when `draw` is called, it draws the
 /// current view of the text. It is responsible for scrolling etc.
+///
+/// Text is broken into lines as the view reaches them, and only a window of the lines around the
+/// cursor is kept. Every `LINES_PER_CHUNK` lines, the index in the text where the next line starts
+/// is noted, so that the lines before the window can be broken again when scrolling back to them.
 #[derive(Default, Debug, Clone)]
 pub struct Pager {
-    text: String,
     cursor: (usize, usize),
     reflow: Reflow,
     height: usize,
@@ -41,6 +44,12 @@ pub struct Pager {
     show_scrollbar: bool,
     content: CellBuffer,
     text_lines: Vec<String>,
+    /// The line number of the first line in `text_lines`.
+    lines_offset: usize,
+    /// Line numbers with the index in the text of each line.
+    chunks: Vec<(usize, usize)>,
+    /// The width of the widest line broken so far.
+    lines_width: usize,
     line_breaker: LineBreakText,
     movement: Option<PageMovement>,
     id: ComponentId,
@@ -55,6 +64,8 @@ impl fmt::Display for Pager {
 impl Pager {
     pub const DESCRIPTION: &'static str = "pager";
     const PAGES_AHEAD_TO_RENDER_NO: usize = 16;
+    const LINES_PER_CHUNK: usize = 1024;
+    const LINES_IN_MEMORY: usize = 4 * Self::LINES_PER_CHUNK;
     pub fn new(context: &Context) -> Self {
         let mut ret = Pager::default();
         ret.minimum_width = context.settings.pager.minimum_width;
@@ -98,10 +109,8 @@ impl Pager {
             }
         }
 
-        self.text = text.to_string();
-        self.text_lines.clear();
-        self.line_breaker = LineBreakText::new(self.text.clone(), self.reflow, width);
-        self.height = 0;
+        self.line_breaker = LineBreakText::new(text.to_string(), self.reflow, width);
+        self.reset_lines();
         self.width = 0;
         self.search = None;
         self.set_dirty(true);
@@ -176,8 +185,7 @@ impl Pager {
             return Pager::from_buf(content, cursor_pos);
         }
         Pager {
-            text,
-            text_lines: vec![],
+            line_breaker: LineBreakText::new(text, reflow, None),
             reflow,
             cursor: (0, cursor_pos.unwrap_or(0)),
             height: 1,
@@ -194,7 +202,6 @@ impl Pager {
     pub fn from_buf(content: CellBuffer, cursor_pos: Option<usize>) -> Self {
         let (width, height) = content.size();
         Pager {
-            text: String::new(),
             cursor: (0, cursor_pos.unwrap_or(0)),
             height,
             width,
@@ -220,27 +227,13 @@ impl Pager {
             width = self.minimum_width;
         }
         if self.line_breaker.width() != Some(width.saturating_sub(4)) {
-            let line_breaker = LineBreakText::new(
-                self.text.clone(),
-                self.reflow,
-                Some(width.saturating_sub(4)),
-            );
-
-            self.line_breaker = line_breaker;
-            self.text_lines.clear();
+            self.line_breaker
+                .set_reflow(self.reflow)
+                .set_width(Some(width.saturating_sub(4)));
+            self.reset_lines();
         };
-        self.height = self.text_lines.len();
         self.width = width;
         if let Some(ref mut search) = self.search {
-            use melib::text_processing::search::KMP;
-            search.positions.clear();
-            for (y, l) in self.text_lines.iter().enumerate() {
-                search.positions.extend(
-                    l.kmp_search(&search.pattern)
-                        .into_iter()
-                        .map(|offset| (y, offset)),
-                );
-            }
             if let Some(pos) = search.positions.get(search.cursor) {
                 if self.cursor.1 > pos.0 || self.cursor.1 + height!(area) < pos.0 {
                     self.cursor.1 = pos.0.saturating_sub(3);
@@ -257,6 +250,21 @@ impl Pager {
         self.initialised = true;
     }
 
+    /// Forget the lines broken so far, to break the text again from its start.
+    fn reset_lines(&mut self) {
+        self.line_breaker.seek(0);
+        self.text_lines.clear();
+        self.lines_offset = 0;
+        self.chunks.clear();
+        self.height = 0;
+        self.lines_width = 0;
+        if let Some(ref mut search) = self.search {
+            search.positions.clear();
+        }
+    }
+
+    /// Break the lines from the cursor up to `up_to` and a page after it, or up to the end of the
+    /// text if `up_to` is zero.
     pub fn draw_lines_up_to(
         &mut self,
         _grid: &mut CellBuffer,
@@ -264,42 +272,79 @@ impl Pager {
         _context: &mut Context,
         up_to: usize,
     ) {
-        if self.line_breaker.is_finished() {
-            return;
+        if self.cursor.1 < self.lines_offset {
+            /* Scrolled back before the window: break again from the chunk of the cursor. */
+            let (line, index) = self
+                .chunks
+                .iter()
+                .rev()
+                .find(|(line, _)| *line <= self.cursor.1)
+                .cloned()
+                .unwrap_or((0, 0));
+            self.line_breaker.seek(index);
+            self.text_lines.clear();
+            self.lines_offset = line;
         }
-        let old_lines_no = self.text_lines.len();
-        if up_to == 0 {
-            self.text_lines.extend(self.line_breaker.by_ref());
+        let last_line = if up_to == 0 {
+            usize::MAX
         } else {
-            if old_lines_no >= up_to + height!(area) {
-                return;
-            }
-            let new_lines_no = (up_to + height!(area)) - old_lines_no;
-            self.text_lines
-                .extend(self.line_breaker.by_ref().take(new_lines_no));
+            up_to + height!(area)
         };
-        let new_lines_no = self.text_lines.len() - old_lines_no;
-        if let Some(ref mut search) = self.search {
-            use melib::text_processing::search::KMP;
-            for (y, l) in self.text_lines.iter().enumerate().skip(old_lines_no) {
-                search.positions.extend(
-                    l.kmp_search(&search.pattern)
-                        .into_iter()
-                        .map(|offset| (y, offset)),
-                );
+        let keep = (Self::PAGES_AHEAD_TO_RENDER_NO + 1) * height!(area);
+        while self.lines_offset + self.text_lines.len() < last_line {
+            let l = match self.line_breaker.next() {
+                Some(l) => l,
+                None => break,
+            };
+            let y = self.lines_offset + self.text_lines.len();
+            if y >= self.height {
+                if let Some(ref mut search) = self.search {
+                    use melib::text_processing::search::KMP;
+                    search.positions.extend(
+                        l.kmp_search(&search.pattern)
+                            .into_iter()
+                            .map(|offset| (y, offset)),
+                    );
+                }
+                self.height = y + 1;
+                self.lines_width = std::cmp::max(self.lines_width, l.grapheme_width());
+            }
+            self.text_lines.push(l);
+            let next_line = y + 1;
+            if next_line
+                >= self.chunks.last().map(|(line, _)| *line).unwrap_or(0) + Self::LINES_PER_CHUNK
+            {
+                if let Some(index) = self.line_breaker.position() {
+                    self.chunks.push((next_line, index));
+                }
+            }
+            if self.text_lines.len() > Self::LINES_IN_MEMORY {
+                /* Drop the chunks before the last `keep` lines. */
+                let lines_offset = self.lines_offset;
+                if let Some(line) = self
+                    .chunks
+                    .iter()
+                    .rev()
+                    .map(|(line, _)| *line)
+                    .find(|line| *line > lines_offset && *line + keep <= next_line)
+                {
+                    self.text_lines.drain(..line - lines_offset);
+                    self.lines_offset = line;
+                }
             }
         }
-        self.height += new_lines_no;
     }
 
     fn draw_page(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         let (mut upper_left, bottom_right) = area;
+        let cursor_x = self.cursor.0;
         for l in self
             .text_lines
             .iter()
-            .skip(self.cursor.1)
+            .skip(self.cursor.1.saturating_sub(self.lines_offset))
             .take(height!(area) + 1)
         {
+            let l = skip_columns(l, cursor_x);
             write_string_to_grid(
                 l,
                 grid,
@@ -333,12 +378,12 @@ impl Pager {
                 for (i, l) in self
                     .text_lines
                     .iter()
-                    .skip(self.cursor.1)
+                    .skip(self.cursor.1.saturating_sub(self.lines_offset))
                     .enumerate()
                     .take(height!(area) + 1)
                 {
                     let i = i + get_y(upper_left);
-                    for (start, end) in text_formatter.regexp.find_iter(l) {
+                    for (start, end) in text_formatter.regexp.find_iter(skip_columns(l, cursor_x)) {
                         let start = start + get_x(upper_left);
                         let end = end + get_x(upper_left);
                         grid.set_tag(t, (start, i), (end, i));
@@ -359,7 +404,10 @@ impl Pager {
                 .filter(|(_, (y, _))| *y >= cursor_line)
                 .take(height!(area) + 1)
             {
-                let x = *x + get_x(upper_left);
+                let x = match x.checked_sub(cursor_x) {
+                    Some(x) if x < width!(area) => x + get_x(upper_left),
+                    _ => continue,
+                };
                 let y = *y - cursor_line;
                 for c in grid.row_iter(
                     x..x + search.pattern.grapheme_width(),
@@ -435,10 +483,10 @@ impl Component for Pager {
                     );
                 }
                 PageMovement::Right(amount) => {
-                    if self.cursor.0 + amount + 1 < self.width {
+                    if self.cursor.0 + amount + 1 < self.lines_width {
                         self.cursor.0 += amount;
                     } else {
-                        self.cursor.0 = self.width.saturating_sub(1);
+                        self.cursor.0 = self.lines_width.saturating_sub(1);
                     }
                 }
                 PageMovement::Left(amount) => {
@@ -457,9 +505,11 @@ impl Component for Pager {
         if self.height == 0 || self.width == 0 {
             return;
         }
+        let mut show_search_result = false;
         if let Some(ref mut search) = self.search {
             if !search.positions.is_empty() {
                 if let Some(mvm) = search.movement.take() {
+                    show_search_result = true;
                     match mvm {
                         PageMovement::Up(_) => {
                             if self.cursor.1 > search.positions[search.cursor].0 {
@@ -482,7 +532,7 @@ impl Component for Pager {
         if cols < 2 || rows < 2 {
             return;
         }
-        let (width, height) = (self.line_breaker.width().unwrap_or(cols), self.height);
+        let (width, height) = (self.lines_width, self.height);
         if self.show_scrollbar && rows < height {
             cols -= 1;
             rows -= 1;
@@ -493,10 +543,28 @@ impl Component for Pager {
         if self.show_scrollbar && cols < width {
             rows -= 1;
         }
+        if show_search_result {
+            if let Some(&(_, x)) = self
+                .search
+                .as_ref()
+                .and_then(|search| search.positions.get(search.cursor))
+            {
+                if x < self.cursor.0 || x >= self.cursor.0 + cols {
+                    self.cursor.0 = x.saturating_sub(cols / 2);
+                }
+            }
+        }
         self.cursor = (
             std::cmp::min(width.saturating_sub(cols), self.cursor.0),
             std::cmp::min(height.saturating_sub(rows), self.cursor.1),
         );
+        /* The cursor may have moved out of the lines in memory. */
+        self.draw_lines_up_to(
+            grid,
+            area,
+            context,
+            self.cursor.1 + Self::PAGES_AHEAD_TO_RENDER_NO * height!(area),
+        );
         self.draw_page(
             grid,
             (upper_left!(area), pos_inc(upper_left!(area), (cols, rows))),
@@ -531,11 +599,11 @@ impl Component for Pager {
                 width,
             );
         }
-        if (rows < height) || self.search.is_some() {
+        if (rows < height) || (cols < width) || self.search.is_some() {
             const RESULTS_STR: &str = "Results for ";
-            let shown_lines = self.cursor.1 + rows;
+            let shown_lines = std::cmp::min(self.cursor.1 + rows, height);
             let total_lines = height;
-            if rows < height {
+            if (rows < height) || (cols < width) {
                 context
                     .replies
                     .push_back(UIEvent::StatusEvent(StatusEvent::ScrollUpdate(
@@ -545,6 +613,11 @@ impl Component for Pager {
                                 shown_lines,
                                 total_lines,
                                 has_more_lines: !self.line_breaker.is_finished(),
+                                columns: if cols < width {
+                                    Some((self.cursor.0 + cols, width))
+                                } else {
+                                    None
+                                },
                             },
                         },
                     )));
@@ -608,14 +681,29 @@ impl Component for Pager {
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_up"]) =>
             {
-                self.movement = Some(PageMovement::Up(1));
+                self.movement = Some(PageMovement::Up(context.count.unwrap_or(1)));
                 self.dirty = true;
                 return true;
             }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_down"]) =>
             {
-                self.movement = Some(PageMovement::Down(1));
+                self.movement = Some(PageMovement::Down(context.count.unwrap_or(1)));
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["toggle_wrap"]) =>
+            {
+                self.reflow = if self.reflow == Reflow::No {
+                    Reflow::All
+                } else {
+                    Reflow::No
+                };
+                self.line_breaker.set_reflow(self.reflow);
+                self.reset_lines();
+                self.cursor.0 = 0;
+                self.initialised = false;
                 self.dirty = true;
                 return true;
             }
@@ -630,26 +718,26 @@ impl Component for Pager {
                 return true;
             }
             UIEvent::Input(Key::Left) => {
-                self.movement = Some(PageMovement::Left(1));
+                self.movement = Some(PageMovement::Left(context.count.unwrap_or(1)));
                 self.dirty = true;
                 return true;
             }
             UIEvent::Input(Key::Right) => {
-                self.movement = Some(PageMovement::Right(1));
+                self.movement = Some(PageMovement::Right(context.count.unwrap_or(1)));
                 self.dirty = true;
                 return true;
             }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Self::DESCRIPTION]["page_up"]) =>
             {
-                self.movement = Some(PageMovement::PageUp(1));
+                self.movement = Some(PageMovement::PageUp(context.count.unwrap_or(1)));
                 self.dirty = true;
                 return true;
             }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Self::DESCRIPTION]["page_down"]) =>
             {
-                self.movement = Some(PageMovement::PageDown(1));
+                self.movement = Some(PageMovement::PageDown(context.count.unwrap_or(1)));
                 self.dirty = true;
                 return true;
             }
@@ -678,7 +766,7 @@ impl Component for Pager {
                 };
                 let stdin = command_obj.stdin.as_mut().expect("failed to open stdin");
                 stdin
-                    .write_all(self.text.as_bytes())
+                    .write_all(self.line_breaker.text().as_bytes())
                     .expect("Failed to write to stdin");
 
                 context
@@ -698,6 +786,7 @@ impl Component for Pager {
                     cursor: 0,
                     movement: Some(PageMovement::Home),
                 });
+                self.reset_lines();
                 self.initialised = false;
                 self.dirty = true;
                 return true;
@@ -773,3 +862,99 @@ impl Component for Pager {
         self.id = id;
     }
 }
+
+/// The part of `line` right of its first `columns` columns.
+fn skip_columns(line: &str, columns: usize) -> &str {
+    &line[line.trim_at_width(columns).len()..]
+}
+
+#[test]
+fn test_pager_large_text() {
+    let mut h = crate::testing::Headless::new(&[], "");
+    let context = &mut h.state.context;
+    let text = (0..20_000)
+        .map(|i| format!("{:06} log line", i))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut pager = Pager::from_string(text, None, None, None, ThemeAttribute::default());
+    let mut grid = CellBuffer::new(100, 30, Cell::default());
+    let area = ((0, 0), (99, 29));
+    let mut draw = |pager: &mut Pager, key: Option<Key>| {
+        if let Some(key) = key {
+            pager.process_event(&mut UIEvent::Input(key), context);
+        }
+        pager.draw(&mut grid, area, context);
+        assert!(pager.text_lines.len() <= Pager::LINES_IN_MEMORY);
+        (0..100).map(|x| grid[(x, 0)].ch()).collect::<String>()
+    };
+    /* Only the first pages are broken into lines before they are shown. */
+    assert!(draw(&mut pager, None).starts_with("000000 log line"));
+    assert!(pager.height < Pager::LINES_PER_CHUNK);
+    assert!(!pager.line_breaker.is_finished());
+
+    assert!(draw(&mut pager, Some(Key::End)).starts_with("019971 log line"));
+    assert_eq!(pager.height, 20_000);
+    assert!(pager.lines_offset > 0);
+    assert!(draw(&mut pager, Some(Key::PageUp)).starts_with("019942 log line"));
+    /* Scrolling back to lines that were dropped breaks their chunk again. */
+    assert!(draw(&mut pager, Some(Key::Home)).starts_with("000000 log line"));
+    assert!(draw(&mut pager, Some(Key::PageDown)).starts_with("000029 log line"));
+    assert_eq!(pager.height, 20_000);
+}
+
+#[test]
+fn test_pager_no_wrap() {
+    let mut h = crate::testing::Headless::new(&[], "");
+    let context = &mut h.state.context;
+    let text = format!("short line\n{}\nlast line", "0123456789".repeat(20));
+    let mut pager = Pager::from_string(text, None, None, None, ThemeAttribute::default());
+    let mut grid = CellBuffer::new(100, 30, Cell::default());
+    let area = ((0, 0), (99, 29));
+    /* The first rows and the columns of the scroll update. */
+    let mut draw = |pager: &mut Pager, key: Option<Key>| {
+        if let Some(key) = key {
+            pager.process_event(&mut UIEvent::Input(key), context);
+        }
+        context.replies.clear();
+        pager.draw(&mut grid, area, context);
+        let rows = (0..3)
+            .map(|y| {
+                (0..100)
+                    .map(|x| grid[(x, y)].ch())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>();
+        let columns = match context.replies.pop_front() {
+            Some(UIEvent::StatusEvent(StatusEvent::ScrollUpdate(ScrollUpdate::Update {
+                context: scroll_context,
+                ..
+            }))) => scroll_context.columns,
+            _ => None,
+        };
+        (rows, columns)
+    };
+    let (rows, columns) = draw(&mut pager, None);
+    assert!(rows[2].starts_with("⤷"), "{:?}", rows);
+    assert_eq!(columns, None);
+
+    let (rows, columns) = draw(&mut pager, Some(Key::Char('w')));
+    assert_eq!(rows[1], "0123456789".repeat(10));
+    assert_eq!(rows[2], "last line");
+    assert_eq!(columns, Some((99, 200)));
+    let (rows, columns) = draw(&mut pager, Some(Key::Right));
+    assert_eq!(rows[0], "hort line");
+    assert!(rows[1].starts_with("1234567890"));
+    assert_eq!(columns, Some((100, 200)));
+    /* Scrolling right stops at the end of the widest line. */
+    pager.movement = Some(PageMovement::Right(500));
+    pager.set_dirty(true);
+    let (rows, columns) = draw(&mut pager, None);
+    assert_eq!(rows[1], &"0123456789".repeat(20)[101..]);
+    assert_eq!(columns, Some((200, 200)));
+
+    let (rows, columns) = draw(&mut pager, Some(Key::Char('w')));
+    assert!(rows[2].starts_with("⤷"), "{:?}", rows);
+    assert_eq!(columns, None);
+}
@@ -50,7 +50,7 @@ impl Debug for Field {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Text(s, _) => fmt::Debug::fmt(s, f),
-            k => fmt::Debug::fmt(k, f),
+            Choice(v, cursor) => f.debug_tuple("Choice").field(v).field(cursor).finish(),
         }
     }
 }
@@ -102,6 +102,20 @@ impl Field {
         }
     }
 
+    /// Replace the text of a text field, with the cursor at its end.
+    pub fn set_text(&mut self, text: String) {
+        if let Text(ref mut s, _) = self {
+            *s = UText::new(text);
+            let len = s.as_str().len();
+            s.set_cursor(len);
+        }
+    }
+
+    /// Whether an autocompletion suggestion is highlighted, which `Tab` would pick.
+    pub fn has_selected_suggestion(&self) -> bool {
+        matches!(self, Text(_, Some((_, auto_complete))) if auto_complete.cursor() > 0)
+    }
+
     pub fn draw_cursor(
         &mut self,
         grid: &mut CellBuffer,
@@ -495,6 +509,13 @@ impl<T: 'static + std::fmt::Debug + Copy + Default + Send + Sync> FormWidget<T>
         &mut self.fields
     }
 
+    /// The name and the field under the cursor.
+    pub fn focused_field_mut(&mut self) -> Option<(Cow<'static, str>, &mut Field)> {
+        let name = self.layout.get(self.cursor)?.clone();
+        let field = self.fields.get_mut(&name)?;
+        Some((name, field))
+    }
+
     pub fn collect(self) -> Option<HashMap<Cow<'static, str>, Field>> {
         if self.buttons_result().is_some() {
             Some(self.fields)
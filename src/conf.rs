@@ -28,6 +28,7 @@ extern crate xdg;
 
 use crate::conf::deserializers::non_empty_string;
 use crate::terminal::Color;
+use crate::types::{CallbackFn, ThreadEvent, UIEvent};
 use melib::search::Query;
 use std::collections::HashSet;
 mod overrides;
@@ -55,10 +56,12 @@ pub use self::tags::*;
 
 use self::default_vals::*;
 use self::listing::ListingSettings;
+pub use self::listing::{FilterPreset, PreviewPaneOrientation};
 use self::notifications::NotificationsSettings;
 use self::terminal::TerminalSettings;
-use crate::pager::PagerSettings;
+use crate::pager::{MultipartAlternativePreference, PagerSettings};
 use melib::conf::{AccountSettings, MailboxConf, ToggleFlag};
+use melib::email::compose::mime::TransferEncoding;
 use melib::error::*;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -132,8 +135,8 @@ pub struct MailUIConf {
     pub pgp: PGPSettingsOverride,
 }
 
-#[serde(default)]
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FileMailboxConf {
     #[serde(flatten)]
     pub conf_override: MailUIConf,
@@ -170,8 +173,31 @@ pub struct FileAccount {
     search_backend: SearchBackend,
     #[serde(default = "false_val")]
     pub manual_refresh: bool,
+    /// How often backends that poll for changes check the account's mailboxes, in seconds.
+    #[serde(default = "none")]
+    pub refresh_interval_secs: Option<u64>,
     #[serde(default = "none")]
     pub refresh_command: Option<String>,
+    /// Command that synchronizes the account with an external tool, e.g. `mbsync inbox`. Its
+    /// output is shown in the status bar and mailboxes are refreshed when it finishes.
+    #[serde(default = "none")]
+    pub sync_command: Option<String>,
+    /// Show a warning when the storage quota usage of the account reaches this percentage.
+    #[serde(default = "ninety_val")]
+    pub quota_warning_threshold: usize,
+    /// Personal address aliases, expanded to their addresses in the composer's recipient fields.
+    #[serde(default)]
+    pub address_aliases: IndexMap<String, Vec<String>>,
+    /// Name of the group the account is listed under in the sidebar.
+    #[serde(default = "none")]
+    pub group: Option<String>,
+    /// Position of the account among the others; accounts without one follow in the order they
+    /// are configured.
+    #[serde(default = "none")]
+    pub display_order: Option<usize>,
+    /// Color that marks the account in the sidebar, the status bar and the tab bar.
+    #[serde(default = "none")]
+    pub accent_color: Option<Color>,
     #[serde(flatten)]
     pub conf_override: MailUIConf,
     #[serde(flatten)]
@@ -214,6 +240,8 @@ pub struct FileSettings {
     pub terminal: TerminalSettings,
     #[serde(default)]
     pub log: LogSettings,
+    #[serde(default)]
+    pub cache: CacheSettings,
     pub bindings: Bindings,
 }
 
@@ -262,6 +290,7 @@ impl From<FileAccount> for AccountConf {
             subscribed_mailboxes: x.subscribed_mailboxes.clone(),
             mailboxes,
             manual_refresh: x.manual_refresh,
+            refresh_interval_secs: x.refresh_interval_secs,
             extra: x.extra.clone().into_iter().collect(),
         };
 
@@ -286,7 +315,7 @@ pub fn get_config_file() -> Result<PathBuf> {
     match env::var("MELI_CONFIG") {
         Ok(path) => Ok(PathBuf::from(path)),
         Err(_) => Ok(xdg_dirs
-            .place_config_file("config.toml")
+            .place_config_file(config_file_name()?)
             .chain_err_summary(|| {
                 format!(
                     "Cannot create configuration directory in {}",
@@ -296,6 +325,110 @@ pub fn get_config_file() -> Result<PathBuf> {
     }
 }
 
+/// The path of the configuration file, like [`get_config_file`] but without creating its
+/// directory.
+pub fn config_file_path() -> Result<PathBuf> {
+    if let Ok(path) = env::var("MELI_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("meli").map_err(|err| {
+        MeliError::new(format!(
+            "Could not detect XDG directories for user: {}",
+            err
+        ))
+        .set_source(Some(std::sync::Arc::new(Box::new(err))))
+    })?;
+    Ok(xdg_dirs.get_config_home().join(config_file_name()?))
+}
+
+fn config_file_name() -> Result<String> {
+    Ok(match melib::paths::profile()? {
+        Some(profile) => format!("profiles/{}.toml", profile),
+        None => "config.toml".to_string(),
+    })
+}
+
+/// Watch the configuration file, the files it includes and the themes directories, and offer to
+/// reload the configuration when any of them changes.
+pub fn watch_config_files(sender: crossbeam::channel::Sender<ThreadEvent>) -> Result<()> {
+    use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+
+    let files = pp::config_files(get_config_file()?)?;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = watcher(tx, std::time::Duration::from_secs(2))
+        .map_err(|err| MeliError::new(err.to_string()))?;
+    /* Watch parent directories instead of the files themselves, since editors usually replace
+     * the file on save. */
+    let dirs = files
+        .iter()
+        .filter_map(|f| {
+            if f.is_dir() {
+                Some(f.as_path())
+            } else {
+                f.parent()
+            }
+        })
+        .collect::<HashSet<&Path>>();
+    for dir in dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+    }
+    std::thread::Builder::new()
+        .name("config-watcher".to_string())
+        .spawn(move || {
+            let _watcher = watcher;
+            loop {
+                let path = match rx.recv() {
+                    Ok(DebouncedEvent::Create(path))
+                    | Ok(DebouncedEvent::Write(path))
+                    | Ok(DebouncedEvent::Remove(path))
+                    | Ok(DebouncedEvent::Rename(_, path)) => path,
+                    Ok(_) => continue,
+                    Err(_) => return,
+                };
+                if !files
+                    .iter()
+                    .any(|f| *f == path || (f.is_dir() && path.parent() == Some(f.as_path())))
+                {
+                    continue;
+                }
+                let callback = move |context: &mut crate::Context| {
+                    let dialog = crate::components::UIConfirmationDialog::new(
+                        "Configuration has changed. Reload?",
+                        vec![(true, "yes".to_string()), (false, "no".to_string())],
+                        true,
+                        Some(Box::new(
+                            move |id: crate::components::ComponentId, result: bool| {
+                                Some(UIEvent::FinishedUIDialog(
+                                    id,
+                                    Box::new(if result {
+                                        Some(crate::command::Action::ReloadConfiguration)
+                                    } else {
+                                        None
+                                    }),
+                                ))
+                            },
+                        )),
+                        context,
+                    );
+                    context
+                        .replies
+                        .push_back(UIEvent::GlobalUIDialog(Box::new(dialog)));
+                };
+                if sender
+                    .send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+                        Box::new(callback),
+                    ))))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
+
 impl FileSettings {
     pub fn new() -> Result<FileSettings> {
         let config_path = get_config_file()?;
@@ -342,46 +475,18 @@ impl FileSettings {
     }
 
     pub fn validate(path: PathBuf) -> Result<Self> {
-        let s = pp::pp(&path)?;
-        let mut s: FileSettings = toml::from_str(&s).map_err(|e| {
-            MeliError::new(format!(
-                "{}:\nConfig file contains errors: {}",
-                path.display(),
-                e.to_string()
-            ))
+        let mut s: FileSettings = crate::profiling::time("config parse", || {
+            let s = pp::pp(&path)?;
+            toml::from_str(&s).map_err(|e| {
+                MeliError::new(format!(
+                    "{}:\nConfig file contains errors: {}",
+                    path.display(),
+                    e.to_string()
+                ))
+            })
         })?;
         let backends = melib::backends::Backends::new();
-        let Themes {
-            light: default_light,
-            dark: default_dark,
-            ..
-        } = Themes::default();
-        for (k, v) in default_light.keys.into_iter() {
-            if !s.terminal.themes.light.contains_key(&k) {
-                s.terminal.themes.light.insert(k, v);
-            }
-        }
-        for theme in s.terminal.themes.other_themes.values_mut() {
-            for (k, v) in default_dark.keys.clone().into_iter() {
-                if !theme.contains_key(&k) {
-                    theme.insert(k, v);
-                }
-            }
-        }
-        for (k, v) in default_dark.keys.into_iter() {
-            if !s.terminal.themes.dark.contains_key(&k) {
-                s.terminal.themes.dark.insert(k, v);
-            }
-        }
-        match s.terminal.theme.as_str() {
-            "dark" | "light" => {}
-            t if s.terminal.themes.other_themes.contains_key(t) => {}
-            t => {
-                return Err(MeliError::new(format!("Theme `{}` was not found.", t)));
-            }
-        }
-
-        s.terminal.themes.validate()?;
+        crate::profiling::time("theme load", || FileSettings::load_themes(&mut s))?;
         for (name, acc) in &s.accounts {
             let FileAccount {
                 root_mailbox,
@@ -393,7 +498,14 @@ impl FileSettings {
                 mailboxes,
                 extra,
                 manual_refresh,
+                refresh_interval_secs,
                 refresh_command: _,
+                sync_command: _,
+                quota_warning_threshold: _,
+                address_aliases: _,
+                group: _,
+                display_order: _,
+                accent_color: _,
                 search_backend: _,
                 conf_override: _,
             } = acc.clone();
@@ -408,6 +520,7 @@ impl FileSettings {
                 display_name,
                 subscribed_mailboxes,
                 manual_refresh,
+                refresh_interval_secs,
                 mailboxes: mailboxes
                     .into_iter()
                     .map(|(k, v)| (k, v.mailbox_conf))
@@ -419,6 +532,72 @@ impl FileSettings {
 
         Ok(s)
     }
+
+    /// Fill in missing theme keys from the default themes and check the selected themes exist.
+    fn load_themes(s: &mut FileSettings) -> Result<()> {
+        let Themes {
+            light: default_light,
+            dark: default_dark,
+            ..
+        } = Themes::default();
+        for (k, v) in default_light.keys.into_iter() {
+            if !s.terminal.themes.light.contains_key(&k) {
+                s.terminal.themes.light.insert(k, v);
+            }
+        }
+        for theme in s.terminal.themes.other_themes.values_mut() {
+            for (k, v) in default_dark.keys.clone().into_iter() {
+                if !theme.contains_key(&k) {
+                    theme.insert(k, v);
+                }
+            }
+        }
+        for (k, v) in default_dark.keys.into_iter() {
+            if !s.terminal.themes.dark.contains_key(&k) {
+                s.terminal.themes.dark.insert(k, v);
+            }
+        }
+        for t in &[
+            &s.terminal.theme,
+            &s.terminal.dark_theme,
+            &s.terminal.light_theme,
+        ] {
+            match t.as_str() {
+                "dark" | "light" => {}
+                t if s.terminal.themes.other_themes.contains_key(t) => {}
+                t => {
+                    return Err(MeliError::new(format!("Theme `{}` was not found.", t)));
+                }
+            }
+        }
+
+        s.terminal.themes.validate()
+    }
+}
+
+/// Sort accounts by `display_order`, then move the accounts of each group after the first
+/// account of the group.
+fn order_accounts(accounts: IndexMap<String, FileAccount>) -> Vec<(String, FileAccount)> {
+    let mut accounts: Vec<(String, FileAccount)> = accounts.into_iter().collect();
+    accounts.sort_by_key(|(_, a)| a.display_order.unwrap_or(usize::MAX));
+    let positions: Vec<usize> = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, (_, a))| {
+            if a.group.is_none() {
+                i
+            } else {
+                accounts
+                    .iter()
+                    .position(|(_, b)| b.group == a.group)
+                    .unwrap()
+            }
+        })
+        .collect();
+    let mut accounts: Vec<(usize, (String, FileAccount))> =
+        positions.into_iter().zip(accounts).collect();
+    accounts.sort_by_key(|(pos, _)| *pos);
+    accounts.into_iter().map(|(_, a)| a).collect()
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -433,15 +612,25 @@ pub struct Settings {
     pub pgp: PGPSettings,
     pub terminal: TerminalSettings,
     pub log: LogSettings,
+    pub cache: CacheSettings,
     pub bindings: Bindings,
 }
 
 impl Settings {
     pub fn new() -> Result<Settings> {
-        let fs = FileSettings::new()?;
+        Settings::from_file_settings(FileSettings::new()?)
+    }
+
+    /// Load the configuration file at `path` instead of the default one.
+    #[cfg(test)]
+    pub fn from_path(path: PathBuf) -> Result<Settings> {
+        Settings::from_file_settings(FileSettings::validate(path)?)
+    }
+
+    fn from_file_settings(fs: FileSettings) -> Result<Settings> {
         let mut s: IndexMap<String, AccountConf> = IndexMap::new();
 
-        for (id, x) in fs.accounts {
+        for (id, x) in order_accounts(fs.accounts) {
             let mut ac = AccountConf::from(x);
             ac.account.set_name(id.clone());
 
@@ -454,6 +643,14 @@ impl Settings {
         if fs.log.maximum_level != melib::LoggingLevel::default() {
             melib::change_log_level(fs.log.maximum_level);
         }
+        melib::reset_log_target_levels();
+        for (target, level) in fs.log.targets.iter() {
+            melib::change_log_target_level(target, *level);
+        }
+        melib::backends::ByteCache::set_budget(fs.cache.memory_budget_mb * 1024 * 1024);
+        if let Some(ref timezone) = fs.terminal.timezone {
+            melib::datetime::set_timezone(timezone)?;
+        }
 
         Ok(Settings {
             accounts: s,
@@ -466,6 +663,7 @@ impl Settings {
             pgp: fs.pgp,
             terminal: fs.terminal,
             log: fs.log,
+            cache: fs.cache,
             bindings: fs.bindings,
         })
     }
@@ -478,6 +676,14 @@ impl Settings {
         if fs.log.maximum_level != melib::LoggingLevel::default() {
             melib::change_log_level(fs.log.maximum_level);
         }
+        melib::reset_log_target_levels();
+        for (target, level) in fs.log.targets.iter() {
+            melib::change_log_target_level(target, *level);
+        }
+        melib::backends::ByteCache::set_budget(fs.cache.memory_budget_mb * 1024 * 1024);
+        if let Some(ref timezone) = fs.terminal.timezone {
+            melib::datetime::set_timezone(timezone)?;
+        }
 
         Ok(Settings {
             accounts: IndexMap::new(),
@@ -490,6 +696,7 @@ impl Settings {
             pgp: fs.pgp,
             terminal: fs.terminal,
             log: fs.log,
+            cache: fs.cache,
             bindings: fs.bindings,
         })
     }
@@ -530,6 +737,10 @@ mod default_vals {
         80.into()
     }
 
+    pub(in crate::conf) fn ninety_val<T: std::convert::From<usize>>() -> T {
+        90.into()
+    }
+
     pub(in crate::conf) fn none<T>() -> Option<T> {
         None
     }
@@ -688,7 +899,7 @@ pub fn create_config_file(p: &Path) -> Result<()> {
 }
 
 mod pp {
-    //! Preprocess configuration files by unfolding `include` macros.
+    //! Preprocess configuration files by unfolding `include` and `include_encrypted` macros.
     use melib::{
         error::{MeliError, Result},
         parsec::*,
@@ -696,9 +907,16 @@ mod pp {
     };
     use std::io::Read;
     use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+
+    enum Include<'a> {
+        Plain(&'a str),
+        /// A file encrypted with `gpg`, eg. one holding account credentials.
+        Encrypted(&'a str),
+    }
 
     /// Try to parse line into a path to be included.
-    fn include_directive<'a>() -> impl Parser<'a, Option<&'a str>> {
+    fn include_directive<'a>() -> impl Parser<'a, Option<Include<'a>>> {
         move |input: &'a str| {
             enum State {
                 Start,
@@ -706,6 +924,7 @@ mod pp {
             }
             use State::*;
             let mut state = State::Start;
+            let mut encrypted = false;
 
             let mut i = 0;
             while i < input.len() {
@@ -714,6 +933,12 @@ mod pp {
                         return Ok(("", None));
                     }
                     (Start, b) if (b as char).is_whitespace() => { /* consume */ }
+                    (Start, _) if input.as_bytes()[i..].starts_with(b"include_encrypted(") => {
+                        i += "include_encrypted(".len();
+                        encrypted = true;
+                        state = Path;
+                        continue;
+                    }
                     (Start, _) if input.as_bytes()[i..].starts_with(b"include(") => {
                         i += "include(".len();
                         state = Path;
@@ -744,7 +969,14 @@ mod pp {
                             }
                             end += 1;
                         }
-                        return Ok(("", Some(ret)));
+                        return Ok((
+                            "",
+                            Some(if encrypted {
+                                Include::Encrypted(ret)
+                            } else {
+                                Include::Plain(ret)
+                            }),
+                        ));
                     }
                     (Path, _) => return Err(input),
                 }
@@ -754,18 +986,70 @@ mod pp {
         }
     }
 
-    /// Expands `include` macros in path.
-    fn pp_helper(path: &Path, level: u8) -> Result<String> {
-        if level > 7 {
-            return Err(MeliError::new(format!("Maximum recursion limit reached while unfolding include directives in {}. Have you included a config file within itself?", path.display())));
+    /// Decrypts `path` with `gpg`. The passphrase, if needed, is asked by `gpg-agent`'s pinentry;
+    /// `gpg` never reads the terminal, since configuration is also reloaded while the UI is
+    /// running.
+    fn gpg_decrypt(path: &Path) -> Result<String> {
+        let output = Command::new("gpg")
+            .args(&["--batch", "--quiet", "--decrypt"])
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .map_err(|err| {
+                MeliError::new(format!(
+                    "Could not run gpg to decrypt {}: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(MeliError::new(format!(
+                "Could not decrypt {}: gpg returned {}: {}",
+                path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
         }
+        String::from_utf8(output.stdout).map_err(|_| {
+            MeliError::new(format!(
+                "Decrypted contents of {} are not valid UTF-8.",
+                path.display()
+            ))
+        })
+    }
+
+    /// Expands `include` macros in path. Every file read is appended to `files`. Encrypted
+    /// includes are only decrypted if `decrypt` is true.
+    fn pp_helper(
+        path: &Path,
+        level: u8,
+        files: &mut Vec<PathBuf>,
+        decrypt: bool,
+    ) -> Result<String> {
         let mut contents = String::new();
         let mut file = std::fs::File::open(path)?;
         file.read_to_string(&mut contents)?;
+        pp_contents(path, &contents, level, files, decrypt)
+    }
+
+    /// Expands `include` macros in `contents`, which were read from `path`.
+    fn pp_contents(
+        path: &Path,
+        contents: &str,
+        level: u8,
+        files: &mut Vec<PathBuf>,
+        decrypt: bool,
+    ) -> Result<String> {
+        if level > 7 {
+            return Err(MeliError::new(format!("Maximum recursion limit reached while unfolding include directives in {}. Have you included a config file within itself?", path.display())));
+        }
+        files.push(path.to_path_buf());
         let mut ret = String::with_capacity(contents.len());
 
         for (i, l) in contents.lines().enumerate() {
-            if let (_, Some(sub_path)) = include_directive().parse(l).map_err(|l| {
+            if let (_, Some(include)) = include_directive().parse(l).map_err(|l| {
                 MeliError::new(format!(
                     "Malformed include directive in line {} of file {}: {}\nConfiguration uses the standard m4 macro include(`filename`).",
                     i,
@@ -773,6 +1057,10 @@ mod pp {
                     l
                 ))
             })? {
+                let (sub_path, encrypted) = match include {
+                    Include::Plain(p) => (p, false),
+                    Include::Encrypted(p) => (p, true),
+                };
                 let mut p = Path::new(sub_path).expand();
                  if p.is_relative() {
                     /* We checked that path is ok above so we can do unwrap here */
@@ -780,7 +1068,14 @@ mod pp {
                     p = prefix.join(p)
                 }
 
-                ret.push_str(&pp_helper(&p, level + 1)?);
+                if !encrypted {
+                    ret.push_str(&pp_helper(&p, level + 1, files, decrypt)?);
+                } else if decrypt {
+                    let decrypted = gpg_decrypt(&p)?;
+                    ret.push_str(&pp_contents(&p, &decrypted, level + 1, files, decrypt)?);
+                } else {
+                    files.push(p);
+                }
             } else {
                 ret.push_str(l);
                 ret.push('\n');
@@ -793,23 +1088,36 @@ mod pp {
     /// Expands `include` macros in configuration file and other configuration files (eg. themes)
     /// in the filesystem.
     pub fn pp<P: AsRef<Path>>(path: P) -> Result<String> {
+        Ok(pp_with_files(path, true)?.0)
+    }
+
+    /// Returns the configuration file, the files it includes and the themes directories, i.e.
+    /// every path whose contents affect the configuration. Encrypted includes are listed but not
+    /// decrypted, so files included from within them are missing.
+    pub fn config_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+        Ok(pp_with_files(path, false)?.1)
+    }
+
+    fn pp_with_files<P: AsRef<Path>>(path: P, decrypt: bool) -> Result<(String, Vec<PathBuf>)> {
         let p_buf: PathBuf = if path.as_ref().is_relative() {
             path.as_ref().expand().canonicalize()?
         } else {
             path.as_ref().expand()
         };
 
-        let mut ret = pp_helper(&p_buf, 0)?;
+        let mut files = vec![];
+        let mut ret = pp_helper(&p_buf, 0, &mut files, decrypt)?;
         drop(p_buf);
         if let Ok(xdg_dirs) = xdg::BaseDirectories::with_prefix("meli") {
             for theme_mailbox in xdg_dirs.find_config_files("themes") {
-                let read_dir = std::fs::read_dir(theme_mailbox)?;
+                let read_dir = std::fs::read_dir(&theme_mailbox)?;
                 for theme in read_dir {
-                    ret.push_str(&pp_helper(&theme?.path(), 0)?);
+                    ret.push_str(&pp_helper(&theme?.path(), 0, &mut files, decrypt)?);
                 }
+                files.push(theme_mailbox);
             }
         }
-        Ok(ret)
+        Ok((ret, files))
     }
 }
 
@@ -820,6 +1128,30 @@ pub struct LogSettings {
     log_file: Option<PathBuf>,
     #[serde(default)]
     maximum_level: melib::LoggingLevel,
+    /// Levels of specific targets such as `imap`, `maildir`, `ui` or `jobs`, overriding
+    /// `maximum_level`.
+    #[serde(default)]
+    targets: IndexMap<String, melib::LoggingLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheSettings {
+    /// Memory for message bytes kept by remote accounts, shared by all accounts, in mebibytes.
+    #[serde(default = "default_memory_budget_mb")]
+    pub memory_budget_mb: usize,
+}
+
+fn default_memory_budget_mb() -> usize {
+    melib::backends::ByteCache::DEFAULT_BUDGET / (1024 * 1024)
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            memory_budget_mb: default_memory_budget_mb(),
+        }
+    }
 }
 
 pub use dotaddressable::*;
@@ -849,6 +1181,7 @@ mod dotaddressable {
     impl DotAddressable for crate::terminal::Attr {}
     impl DotAddressable for crate::terminal::Key {}
     impl DotAddressable for usize {}
+    impl DotAddressable for (u8, u8) {}
     impl DotAddressable for Query {}
     impl DotAddressable for melib::LoggingLevel {}
     impl DotAddressable for PathBuf {}
@@ -867,6 +1200,24 @@ mod dotaddressable {
     }
     impl<K: DotAddressable + std::cmp::Eq + std::hash::Hash> DotAddressable for HashSet<K> {}
 
+    impl DotAddressable for CacheSettings {
+        fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
+            match path.first() {
+                Some(field) => {
+                    let tail = &path[1..];
+                    match *field {
+                        "memory_budget_mb" => self.memory_budget_mb.lookup(field, tail),
+                        other => Err(MeliError::new(format!(
+                            "{} has no field named {}",
+                            parent_field, other
+                        ))),
+                    }
+                }
+                None => Ok(toml::to_string(self).map_err(|err| err.to_string())?),
+            }
+        }
+    }
+
     impl DotAddressable for LogSettings {
         fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
             match path.first() {
@@ -875,6 +1226,7 @@ mod dotaddressable {
                     match *field {
                         "log_file" => self.log_file.lookup(field, tail),
                         "maximum_level" => self.maximum_level.lookup(field, tail),
+                        "targets" => self.targets.lookup(field, tail),
 
                         other => Err(MeliError::new(format!(
                             "{} has no field named {}",
@@ -903,6 +1255,7 @@ mod dotaddressable {
                         "pgp" => Err(MeliError::new("unimplemented")),
                         "terminal" => self.terminal.lookup(field, tail),
                         "log" => self.log.lookup(field, tail),
+                        "cache" => self.cache.lookup(field, tail),
 
                         other => Err(MeliError::new(format!(
                             "{} has no field named {}",
@@ -997,7 +1350,13 @@ mod dotaddressable {
                         "mailboxes" => self.mailboxes.lookup(field, tail),
                         "search_backend" => self.search_backend.lookup(field, tail),
                         "manual_refresh" => self.manual_refresh.lookup(field, tail),
+                        "refresh_interval_secs" => self.refresh_interval_secs.lookup(field, tail),
                         "refresh_command" => self.refresh_command.lookup(field, tail),
+                        "sync_command" => self.sync_command.lookup(field, tail),
+                        "quota_warning_threshold" => {
+                            self.quota_warning_threshold.lookup(field, tail)
+                        }
+                        "address_aliases" => self.address_aliases.lookup(field, tail),
                         "conf_override" => self.conf_override.lookup(field, tail),
                         "extra" => self.extra.lookup(field, tail),
                         other => Err(MeliError::new(format!(
@@ -1026,6 +1385,7 @@ mod dotaddressable {
                         "subscribed_mailboxes" => self.subscribed_mailboxes.lookup(field, tail),
                         "mailboxes" => self.mailboxes.lookup(field, tail),
                         "manual_refresh" => self.manual_refresh.lookup(field, tail),
+                        "refresh_interval_secs" => self.refresh_interval_secs.lookup(field, tail),
                         "extra" => self.extra.lookup(field, tail),
                         other => Err(MeliError::new(format!(
                             "{} has no field named {}",
@@ -1049,6 +1409,8 @@ mod dotaddressable {
                         "subscribe" => self.subscribe.lookup(field, tail),
                         "ignore" => self.ignore.lookup(field, tail),
                         "usage" => self.usage.lookup(field, tail),
+                        "retention_days" => self.retention_days.lookup(field, tail),
+                        "refresh_interval_secs" => self.refresh_interval_secs.lookup(field, tail),
                         "extra" => self.extra.lookup(field, tail),
                         other => Err(MeliError::new(format!(
                             "{} has no field named {}",
@@ -24,13 +24,23 @@
  */
 
 use super::{AccountConf, FileMailboxConf};
+use crate::activity::{self, Journal};
+#[cfg(feature = "gpgme")]
+use crate::autocrypt::AutocryptPeers;
 use crate::jobs::{JobExecutor, JobId, JoinHandle};
+use crate::mailbox_properties::{MailboxProperties, PropertyOverrides};
+use crate::notes::Notes;
+use crate::phishing::Dismissals;
+use crate::recipients::RecipientHistory;
+use crate::thread_marks::ThreadMarks;
 use indexmap::IndexMap;
 use melib::backends::*;
+#[cfg(feature = "gpgme")]
+use melib::email::autocrypt::AutocryptHeader;
 use melib::email::*;
 use melib::error::{MeliError, Result};
 use melib::text_processing::GlobMatch;
-use melib::thread::{SortField, SortOrder, Threads};
+use melib::thread::{SortField, SortOrder, ThreadHash, Threads};
 use melib::AddressBook;
 use melib::Collection;
 use smallvec::SmallVec;
@@ -78,6 +88,8 @@ pub enum MailboxStatus {
     Failed(MeliError),
     /// first argument is done work, and second is total work
     Parsing(usize, usize),
+    /// `autoload` is off and the mailbox hasn't been opened yet, see `Account::load_deferred`.
+    Deferred,
     None,
 }
 
@@ -122,6 +134,7 @@ impl MailboxEntry {
             ),
             MailboxStatus::Failed(ref e) => e.to_string(),
             MailboxStatus::None => "Retrieving mailbox.".to_string(),
+            MailboxStatus::Deferred => "Mailbox not loaded.".to_string(),
             MailboxStatus::Parsing(done, total) => {
                 format!("Parsing messages. [{}/{}]", done, total)
             }
@@ -137,6 +150,19 @@ impl MailboxEntry {
     }
 }
 
+/// How the jobs an account starts with are scheduled, so that the mailbox shown on startup is
+/// listed first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupPriority {
+    /// The account shown on startup: its mailbox with this path, or its first mailbox, is
+    /// fetched first and its other mailboxes in the background.
+    Current(Option<String>),
+    /// Another account, whose jobs run in the background.
+    Background,
+    /// Startup is over.
+    Normal,
+}
+
 #[derive(Debug)]
 pub struct Account {
     name: String,
@@ -148,6 +174,20 @@ pub struct Account {
     sent_mailbox: Option<MailboxHash>,
     pub(crate) collection: Collection,
     pub(crate) address_book: AddressBook,
+    pub(crate) notes: Notes,
+    pub(crate) muted_threads: ThreadMarks,
+    pub(crate) watched_threads: ThreadMarks,
+    /// Senders whose phishing warnings the user dismissed.
+    pub(crate) dismissed_warnings: Dismissals,
+    /// What happened to the account's messages, see the `activity` command.
+    pub(crate) activity: Journal,
+    /// Mailbox properties edited in the UI.
+    mailbox_properties: PropertyOverrides,
+    /// Keys announced in the `Autocrypt` headers of received mail.
+    #[cfg(feature = "gpgme")]
+    pub(crate) autocrypt_peers: AutocryptPeers,
+    /// The mail sent to each address, counted from the Sent mailbox.
+    pub(crate) recipient_history: RecipientHistory,
     pub(crate) settings: AccountConf,
     pub(crate) backend: Arc<RwLock<Box<dyn MailBackend>>>,
 
@@ -157,6 +197,9 @@ pub struct Account {
     sender: Sender<ThreadEvent>,
     event_queue: VecDeque<(MailboxHash, RefreshEvent)>,
     pub backend_capabilities: MailBackendCapabilities,
+    /// Storage quota of the account, if its backend reports one.
+    pub quota: Option<Quota>,
+    startup_priority: StartupPriority,
 }
 
 pub enum JobRequest {
@@ -212,7 +255,11 @@ pub enum JobRequest {
         mailbox_hash: MailboxHash,
         handle: JoinHandle<Result<HashMap<MailboxHash, Mailbox>>>,
     },
-    //RenameMailbox,
+    RenameMailbox {
+        mailbox_hash: MailboxHash,
+        new_path: String,
+        handle: JoinHandle<Result<(MailboxHash, HashMap<MailboxHash, Mailbox>)>>,
+    },
     Search {
         handle: JoinHandle<Result<()>>,
     },
@@ -230,23 +277,32 @@ pub enum JobRequest {
     Watch {
         handle: JoinHandle<Result<()>>,
     },
+    Quota {
+        handle: JoinHandle<Result<Quota>>,
+    },
+    Synchronize {
+        handle: JoinHandle<Result<()>>,
+    },
+    BackendAction {
+        action: String,
+        handle: JoinHandle<Result<String>>,
+    },
 }
 
 impl Drop for JobRequest {
     fn drop(&mut self) {
         match self {
-            JobRequest::Generic { handle, .. } |
-            JobRequest::IsOnline { handle, .. } |
-            JobRequest::Refresh { handle, .. } |
-            JobRequest::SetFlags { handle, .. } |
-            JobRequest::SaveMessage { handle, .. } |
-            //JobRequest::RenameMailbox,
-            JobRequest::Search { handle, .. } |
-            JobRequest::AsBytes { handle, .. } |
-            JobRequest::SetMailboxPermissions { handle, .. } |
-            JobRequest::SetMailboxSubscription { handle, .. } |
-            JobRequest::Watch { handle, .. } |
-            JobRequest::SendMessageBackground { handle, .. } => {
+            JobRequest::Generic { handle, .. }
+            | JobRequest::IsOnline { handle, .. }
+            | JobRequest::Refresh { handle, .. }
+            | JobRequest::SetFlags { handle, .. }
+            | JobRequest::SaveMessage { handle, .. }
+            | JobRequest::Search { handle, .. }
+            | JobRequest::AsBytes { handle, .. }
+            | JobRequest::SetMailboxPermissions { handle, .. }
+            | JobRequest::SetMailboxSubscription { handle, .. }
+            | JobRequest::Watch { handle, .. }
+            | JobRequest::SendMessageBackground { handle, .. } => {
                 handle.cancel();
             }
             JobRequest::DeleteMessages { handle, .. } => {
@@ -258,13 +314,27 @@ impl Drop for JobRequest {
             JobRequest::DeleteMailbox { handle, .. } => {
                 handle.cancel();
             }
+            JobRequest::RenameMailbox { handle, .. } => {
+                handle.cancel();
+            }
             JobRequest::Fetch { handle, .. } => {
                 handle.cancel();
             }
             JobRequest::Mailboxes { handle, .. } => {
                 handle.cancel();
             }
-            JobRequest::CopyTo { handle, .. } => { handle.cancel(); }
+            JobRequest::CopyTo { handle, .. } => {
+                handle.cancel();
+            }
+            JobRequest::Quota { handle, .. } => {
+                handle.cancel();
+            }
+            JobRequest::Synchronize { handle, .. } => {
+                handle.cancel();
+            }
+            JobRequest::BackendAction { handle, .. } => {
+                handle.cancel();
+            }
             JobRequest::SendMessage => {}
         }
     }
@@ -288,7 +358,9 @@ impl core::fmt::Debug for JobRequest {
             JobRequest::DeleteMailbox { mailbox_hash, .. } => {
                 write!(f, "JobRequest::DeleteMailbox({})", mailbox_hash)
             }
-            //JobRequest::RenameMailbox,
+            JobRequest::RenameMailbox { mailbox_hash, .. } => {
+                write!(f, "JobRequest::RenameMailbox({})", mailbox_hash)
+            }
             JobRequest::Search { .. } => write!(f, "JobRequest::Search"),
             JobRequest::AsBytes { .. } => write!(f, "JobRequest::AsBytes"),
             JobRequest::SetMailboxPermissions { .. } => {
@@ -298,6 +370,11 @@ impl core::fmt::Debug for JobRequest {
                 write!(f, "JobRequest::SetMailboxSubscription")
             }
             JobRequest::Watch { .. } => write!(f, "JobRequest::Watch"),
+            JobRequest::Quota { .. } => write!(f, "JobRequest::Quota"),
+            JobRequest::Synchronize { .. } => write!(f, "JobRequest::Synchronize"),
+            JobRequest::BackendAction { action, .. } => {
+                write!(f, "JobRequest::BackendAction({})", action)
+            }
             JobRequest::SendMessage => write!(f, "JobRequest::SendMessage"),
             JobRequest::SendMessageBackground { .. } => {
                 write!(f, "JobRequest::SendMessageBackground")
@@ -330,12 +407,17 @@ impl core::fmt::Display for JobRequest {
             ),
             JobRequest::CreateMailbox { path, .. } => write!(f, "Create mailbox {}", path),
             JobRequest::DeleteMailbox { .. } => write!(f, "Delete mailbox"),
-            //JobRequest::RenameMailbox,
+            JobRequest::RenameMailbox { new_path, .. } => {
+                write!(f, "Rename mailbox to {}", new_path)
+            }
             JobRequest::Search { .. } => write!(f, "Search"),
             JobRequest::AsBytes { .. } => write!(f, "Message body fetch"),
             JobRequest::SetMailboxPermissions { .. } => write!(f, "Set mailbox permissions"),
             JobRequest::SetMailboxSubscription { .. } => write!(f, "Set mailbox subscription"),
             JobRequest::Watch { .. } => write!(f, "Background watch"),
+            JobRequest::Quota { .. } => write!(f, "Storage quota check"),
+            JobRequest::Synchronize { .. } => write!(f, "Synchronize account"),
+            JobRequest::BackendAction { action, .. } => write!(f, "Backend action {}", action),
             JobRequest::SendMessageBackground { .. } | JobRequest::SendMessage => {
                 write!(f, "Sending message")
             }
@@ -366,57 +448,69 @@ impl JobRequest {
             _ => false,
         }
     }
+
+    pub fn is_quota(&self) -> bool {
+        match self {
+            JobRequest::Quota { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_synchronize(&self) -> bool {
+        match self {
+            JobRequest::Synchronize { .. } => true,
+            _ => false,
+        }
+    }
 }
 
 impl Drop for Account {
     fn drop(&mut self) {
-        if let Ok(data_dir) = xdg::BaseDirectories::with_profile("meli", &self.name) {
-            if let Ok(data) = data_dir.place_data_file("addressbook") {
-                /* place result in cache directory */
-                let f = match fs::File::create(data) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        return;
-                    }
-                };
-                let metadata = f.metadata().unwrap();
-                let mut permissions = metadata.permissions();
-
-                permissions.set_mode(0o600); // Read/write for owner only.
-                f.set_permissions(permissions).unwrap();
-                let writer = io::BufWriter::new(f);
-                if let Err(err) = serde_json::to_writer(writer, &self.address_book) {
-                    eprintln!("{}", err);
+        if let Ok(data) = melib::paths::state_file(Some(&self.name), "addressbook") {
+            /* place result in cache directory */
+            let f = match fs::File::create(data) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("{}", e);
                     return;
-                };
+                }
             };
-            /*
-            if let Ok(data) = data_dir.place_data_file("mailbox") {
-                /* place result in cache directory */
-                let f = match fs::File::create(data) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        return;
-                    }
-                };
-                let metadata = f.metadata().unwrap();
-                let mut permissions = metadata.permissions();
-
-                permissions.set_mode(0o600); // Read/write for owner only.
-                f.set_permissions(permissions).unwrap();
-                let writer = io::BufWriter::new(f);
-                if let Err(err) = bincode::Options::serialize_into(
-                    bincode::config::DefaultOptions::new(),
-                    writer,
-                    &self.collection,
-                ) {
-                    eprintln!("{}", err);
-                };
+            let metadata = f.metadata().unwrap();
+            let mut permissions = metadata.permissions();
+
+            permissions.set_mode(0o600); // Read/write for owner only.
+            f.set_permissions(permissions).unwrap();
+            let writer = io::BufWriter::new(f);
+            if let Err(err) = serde_json::to_writer(writer, &self.address_book) {
+                eprintln!("{}", err);
+                return;
             };
-                */
-        }
+        };
+        /*
+        if let Ok(data) = data_dir.place_data_file("mailbox") {
+            /* place result in cache directory */
+            let f = match fs::File::create(data) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            let metadata = f.metadata().unwrap();
+            let mut permissions = metadata.permissions();
+
+            permissions.set_mode(0o600); // Read/write for owner only.
+            f.set_permissions(permissions).unwrap();
+            let writer = io::BufWriter::new(f);
+            if let Err(err) = bincode::Options::serialize_into(
+                bincode::config::DefaultOptions::new(),
+                writer,
+                &self.collection,
+            ) {
+                eprintln!("{}", err);
+            };
+        };
+            */
     }
 }
 
@@ -449,15 +543,31 @@ impl Account {
                     || s.account
                         .subscribed_mailboxes
                         .iter()
-                        .any(|m| path.matches_glob(m))
+                        .any(|m| subscribed_mailbox_matches(path, m))
             }),
             event_consumer,
         )?;
+        let backend = if settings.account.read_only() {
+            ReadOnlyBackend::new(backend)
+        } else {
+            backend
+        };
 
-        let data_dir = xdg::BaseDirectories::with_profile("meli", &name).unwrap();
         let mut address_book = AddressBook::with_account(&settings.account());
-
-        if let Ok(data) = data_dir.place_data_file("addressbook") {
+        for (alias, addresses) in &settings.conf().address_aliases {
+            address_book.add_alias(alias.clone(), addresses.clone());
+        }
+        let notes = Notes::new(&name);
+        let muted_threads = ThreadMarks::new(&name, "muted_threads");
+        let watched_threads = ThreadMarks::new(&name, "watched_threads");
+        let dismissed_warnings = Dismissals::new(&name);
+        let activity = Journal::new(&name);
+        let mailbox_properties = PropertyOverrides::new(&name);
+        #[cfg(feature = "gpgme")]
+        let autocrypt_peers = AutocryptPeers::new(&name);
+        let recipient_history = RecipientHistory::new(&name);
+
+        if let Ok(data) = melib::paths::state_file(Some(&name), "addressbook") {
             if data.exists() {
                 let reader = io::BufReader::new(fs::File::open(data).unwrap());
                 let result: result::Result<AddressBook, _> = serde_json::from_reader(reader);
@@ -486,25 +596,6 @@ impl Account {
             }
         }
 
-        let mut active_jobs = HashMap::default();
-        let mut active_job_instants = BTreeMap::default();
-        if let Ok(mailboxes_job) = backend.mailboxes() {
-            if let Ok(online_job) = backend.is_online() {
-                let handle = if backend.capabilities().is_async {
-                    job_executor.spawn_specialized(online_job.then(|_| mailboxes_job))
-                } else {
-                    job_executor.spawn_blocking(online_job.then(|_| mailboxes_job))
-                };
-                let job_id = handle.job_id;
-                active_jobs.insert(job_id, JobRequest::Mailboxes { handle });
-                active_job_instants.insert(std::time::Instant::now(), job_id);
-                sender
-                    .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
-                        StatusEvent::NewJob(job_id),
-                    )))
-                    .unwrap();
-            }
-        }
         Ok(Account {
             hash,
             name,
@@ -517,19 +608,59 @@ impl Account {
             mailboxes_order: Default::default(),
             tree: Default::default(),
             address_book,
+            notes,
+            muted_threads,
+            watched_threads,
+            dismissed_warnings,
+            activity,
+            mailbox_properties,
+            #[cfg(feature = "gpgme")]
+            autocrypt_peers,
+            recipient_history,
             sent_mailbox: Default::default(),
             collection: backend.collection(),
             settings,
             sender,
             job_executor,
-            active_jobs,
-            active_job_instants,
+            active_jobs: HashMap::default(),
+            active_job_instants: BTreeMap::default(),
             event_queue: VecDeque::with_capacity(8),
             backend_capabilities: backend.capabilities(),
             backend: Arc::new(RwLock::new(backend)),
+            quota: None,
+            startup_priority: StartupPriority::Normal,
         })
     }
 
+    /// Connect and list the account's mailboxes, which are then fetched according to `priority`.
+    pub fn start(&mut self, priority: StartupPriority) {
+        self.startup_priority = priority;
+        let backend = self.backend.read().unwrap();
+        if let (Ok(mailboxes_job), Ok(online_job)) = (backend.mailboxes(), backend.is_online()) {
+            drop(backend);
+            let handle = self.spawn_startup_job(online_job.then(|_| mailboxes_job), false);
+            self.insert_job(handle.job_id, JobRequest::Mailboxes { handle });
+        }
+    }
+
+    /// Spawn a job of the account's startup, in the background if `background` is set or this
+    /// isn't the account shown first.
+    fn spawn_startup_job<F, R>(&self, future: F, background: bool) -> JoinHandle<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        match (
+            background || self.startup_priority == StartupPriority::Background,
+            self.backend_capabilities.is_async,
+        ) {
+            (false, true) => self.job_executor.spawn_specialized(future),
+            (false, false) => self.job_executor.spawn_blocking(future),
+            (true, true) => self.job_executor.spawn_background(future),
+            (true, false) => self.job_executor.spawn_background_blocking(future),
+        }
+    }
+
     fn init(&mut self, mut ref_mailboxes: HashMap<MailboxHash, Mailbox>) -> Result<()> {
         self.backend_capabilities = self.backend.read().unwrap().capabilities();
         let mut mailbox_entries: IndexMap<MailboxHash, MailboxEntry> =
@@ -607,8 +738,22 @@ impl Account {
             }
         }
 
+        /* Properties edited in the UI take precedence over the configuration. */
+        for (&hash, entry) in mailbox_entries.iter_mut() {
+            if let Some(properties) = self.mailbox_properties.get(&entry.name) {
+                properties.apply(&mut entry.conf);
+                let _ = entry.ref_mailbox.set_special_usage(properties.usage);
+                if properties.usage == SpecialUsageMailbox::Sent {
+                    sent_mailbox = Some(hash);
+                } else if sent_mailbox == Some(hash) {
+                    sent_mailbox = None;
+                }
+            }
+        }
+
         for missing_mailbox in &mailbox_conf_hash_set {
-            melib::log(
+            melib::log_target(
+                "accounts",
                 format!(
                     "Account `{}` mailbox `{}` configured but not present in account's mailboxes. Is it misspelled?",
                     &self.name, missing_mailbox,
@@ -636,7 +781,8 @@ impl Account {
                     acc
                 });
             mailbox_comma_sep_list_string.drain(mailbox_comma_sep_list_string.len() - 2..);
-            melib::log(
+            melib::log_target(
+                "accounts",
                 format!(
                     "Account `{}` has the following mailboxes: [{}]",
                     &self.name, mailbox_comma_sep_list_string,
@@ -646,41 +792,24 @@ impl Account {
         }
 
         let mut tree: Vec<MailboxNode> = Vec::new();
+        let mut fetches: Vec<MailboxHash> = vec![];
         for (h, f) in ref_mailboxes.iter() {
             if !f.is_subscribed() {
                 /* Skip unsubscribed mailbox */
                 continue;
             }
             mailbox_entries.entry(*h).and_modify(|entry| {
-                if entry.conf.mailbox_conf.autoload
+                /* On battery, other mailboxes are fetched when they are opened. */
+                if (entry.conf.mailbox_conf.autoload.is_true() && !crate::power::is_throttled())
                     || (entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Inbox
                         || entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Sent)
                 {
                     let total = entry.ref_mailbox.count().ok().unwrap_or((0, 0)).1;
                     entry.status = MailboxStatus::Parsing(0, total);
-                    if let Ok(mailbox_job) = self.backend.write().unwrap().fetch(*h) {
-                        let mailbox_job = mailbox_job.into_future();
-                        let handle = if self.backend_capabilities.is_async {
-                            self.job_executor.spawn_specialized(mailbox_job)
-                        } else {
-                            self.job_executor.spawn_blocking(mailbox_job)
-                        };
-                        let job_id = handle.job_id;
-                        self.sender
-                            .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
-                                StatusEvent::NewJob(job_id),
-                            )))
-                            .unwrap();
-                        self.active_jobs.insert(
-                            job_id,
-                            JobRequest::Fetch {
-                                mailbox_hash: *h,
-                                handle,
-                            },
-                        );
-                        self.active_job_instants
-                            .insert(std::time::Instant::now(), job_id);
-                    }
+                    fetches.push(*h);
+                } else if entry.conf.mailbox_conf.autoload.is_false() {
+                    /* Fetched when it's opened with `load_deferred`. */
+                    entry.status = MailboxStatus::Deferred;
                 }
             });
             self.collection.new_mailbox(*h);
@@ -691,9 +820,54 @@ impl Account {
         self.mailbox_entries = mailbox_entries;
         self.tree = tree;
         self.sent_mailbox = sent_mailbox;
+
+        /* The mailbox shown first is fetched first, then the rest in the background. */
+        let first = match self.startup_priority {
+            StartupPriority::Current(Some(ref path)) => self.mailbox_by_path(path).ok(),
+            StartupPriority::Current(None) => self.mailboxes_order.first().copied(),
+            StartupPriority::Background | StartupPriority::Normal => None,
+        };
+        if let Some(pos) = first.and_then(|first| fetches.iter().position(|h| *h == first)) {
+            fetches[..=pos].rotate_right(1);
+        }
+        for mailbox_hash in fetches {
+            let background =
+                self.startup_priority != StartupPriority::Normal && first != Some(mailbox_hash);
+            let mailbox_job = self.backend.write().unwrap().fetch(mailbox_hash);
+            if let Ok(mailbox_job) = mailbox_job {
+                let handle = self.spawn_startup_job(mailbox_job.into_future(), background);
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::Fetch {
+                        mailbox_hash,
+                        handle,
+                    },
+                );
+            }
+        }
+        self.startup_priority = StartupPriority::Normal;
         Ok(())
     }
 
+    /// How many of the mailboxes that are loaded or being fetched are loaded, while any is being
+    /// fetched.
+    pub fn loading_progress(&self) -> Option<(usize, usize)> {
+        let fetching = self
+            .mailbox_entries
+            .keys()
+            .filter(|h| self.active_jobs.values().any(|j| j.is_fetch(**h)))
+            .count();
+        if fetching == 0 {
+            return None;
+        }
+        let loaded = self
+            .mailbox_entries
+            .values()
+            .filter(|entry| entry.status.is_available())
+            .count();
+        Some((loaded, loaded + fetching))
+    }
+
     pub fn reload(&mut self, event: RefreshEvent, mailbox_hash: MailboxHash) -> Option<UIEvent> {
         if !self.mailbox_entries[&mailbox_hash].status.is_available()
             && !self.mailbox_entries[&mailbox_hash].status.is_parsing()
@@ -726,7 +900,8 @@ impl Account {
                             )
                         }) {
                             Err(err) => {
-                                melib::log(
+                                melib::log_target(
+                                    "accounts",
                                     format!(
                                         "Failed to update envelope {} in cache: {}",
                                         envelope.message_id_display(),
@@ -753,6 +928,14 @@ impl Account {
                             }
                         }
                     }
+                    if self.collection.get_env(old_hash).flags() != envelope.flags() {
+                        let tags = self.tag_names(&envelope);
+                        let change = activity::Change::Flags(activity::describe_flags(
+                            envelope.flags(),
+                            &tags,
+                        ));
+                        self.record_change(&envelope, mailbox_hash, change);
+                    }
                     self.collection.update(old_hash, *envelope, mailbox_hash);
                     return Some(EnvelopeUpdate(old_hash));
                 }
@@ -760,6 +943,20 @@ impl Account {
                     if !self.collection.contains_key(&env_hash) {
                         return None;
                     }
+                    let changed = {
+                        let envelope = self.collection.get_env(env_hash);
+                        envelope.flags() != flags
+                            || envelope.labels().len() != tags.len()
+                            || tags
+                                .iter()
+                                .any(|t| !envelope.labels().contains(&tag_hash!(t)))
+                    };
+                    if changed {
+                        let change =
+                            activity::Change::Flags(activity::describe_flags(flags, &tags));
+                        let envelope = self.collection.get_env(env_hash).clone();
+                        self.record_change(&envelope, mailbox_hash, change);
+                    }
                     self.collection
                         .envelopes
                         .write()
@@ -799,7 +996,8 @@ impl Account {
                                 );
                             }
                             Err(err) => {
-                                melib::log(
+                                melib::log_target(
+                                    "accounts",
                                     format!(
                                         "Failed to update envelope {} in cache: {}",
                                         self.collection.envelopes.read().unwrap()[&env_hash]
@@ -829,7 +1027,8 @@ impl Account {
                             )
                         }) {
                             Err(err) => {
-                                melib::log(
+                                melib::log_target(
+                                    "accounts",
                                     format!(
                                         "Failed to update envelope {} in cache: {}",
                                         &self.collection.envelopes.read().unwrap()[&new_hash]
@@ -870,6 +1069,15 @@ impl Account {
                     {
                         return None;
                     }
+                    /* Don't record or cache a message that won't be added. */
+                    if self.report_collisions(std::iter::once(&*envelope)) {
+                        return None;
+                    }
+                    if self.sent_mailbox == Some(mailbox_hash) {
+                        if let Err(err) = self.recipient_history.index(Some(&*envelope)) {
+                            debug!("Could not store recipient history: {}", err);
+                        }
+                    }
                     let (is_seen, is_draft) =
                         { (envelope.is_seen(), envelope.flags().contains(Flag::DRAFT)) };
                     let (subject, from) = {
@@ -900,40 +1108,49 @@ impl Account {
                         );
                     }
 
+                    self.record_change(&envelope, mailbox_hash, activity::Change::Added);
                     if self.collection.insert(*envelope, mailbox_hash) {
                         /* is a duplicate */
                         return None;
                     }
 
+                    let thread_hash = self.collection.get_env(env_hash).thread();
+                    let (snoozed, watched) = if self
+                        .collection
+                        .get_threads(mailbox_hash)
+                        .thread_nodes()
+                        .contains_key(&thread_hash)
+                    {
+                        let threads = self.collection.get_threads(mailbox_hash);
+                        let thread = threads.thread_ref(threads[&thread_hash].group);
+                        (thread.snoozed(), thread.watched())
+                    } else {
+                        (false, false)
+                    };
+                    /* Watched threads notify of every new message, even seen ones and ones in
+                     * ignored mailboxes. */
+                    if watched && !is_draft {
+                        return Some(Notification(
+                            Some(format!("new e-mail in watched thread from: {}", from)),
+                            format!(
+                                "{}\n{} {}",
+                                subject,
+                                self.name,
+                                self.mailbox_entries[&mailbox_hash].name()
+                            ),
+                            Some(crate::types::NotificationType::NewMail),
+                        ));
+                    }
                     if self.mailbox_entries[&mailbox_hash]
                         .conf
                         .mailbox_conf
                         .ignore
                         .is_true()
+                        || !self.notifies(mailbox_hash)
                     {
                         return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
                     }
-
-                    let thread_hash = self.collection.get_env(env_hash).thread();
-                    if self
-                        .collection
-                        .get_threads(mailbox_hash)
-                        .thread_nodes()
-                        .contains_key(&thread_hash)
-                    {
-                        let thread = self.collection.get_threads(mailbox_hash).find_group(
-                            self.collection.get_threads(mailbox_hash)[&thread_hash].group,
-                        );
-                        if self
-                            .collection
-                            .get_threads(mailbox_hash)
-                            .thread_ref(thread)
-                            .snoozed()
-                        {
-                            return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
-                        }
-                    }
-                    if is_seen || is_draft {
+                    if snoozed || is_seen || is_draft {
                         return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
                     }
 
@@ -956,7 +1173,8 @@ impl Account {
                     if self.settings.conf.search_backend == crate::conf::SearchBackend::Sqlite3 {
                         if let Err(err) = crate::sqlite3::remove(env_hash) {
                             let envelopes = self.collection.envelopes.read().unwrap();
-                            melib::log(
+                            melib::log_target(
+                                "accounts",
                                 format!(
                                     "Failed to remove envelope {} [{}] in cache: {}",
                                     &envelopes[&env_hash].message_id_display(),
@@ -967,7 +1185,9 @@ impl Account {
                             );
                         }
                     }
-                    let thread_hash = self.collection.get_env(env_hash).thread();
+                    let envelope = self.collection.get_env(env_hash).clone();
+                    self.record_change(&envelope, mailbox_hash, activity::Change::Removed);
+                    let thread_hash = envelope.thread();
                     if !self
                         .collection
                         .get_threads(mailbox_hash)
@@ -1025,6 +1245,9 @@ impl Account {
         None
     }
     pub fn refresh(&mut self, mailbox_hash: MailboxHash) -> Result<()> {
+        if self.settings.conf().sync_command.is_some() {
+            return self.synchronize();
+        }
         if let Some(ref refresh_command) = self.settings.conf().refresh_command {
             let child = std::process::Command::new("sh")
                 .args(&["-c", refresh_command])
@@ -1044,6 +1267,108 @@ impl Account {
                 .unwrap();
             return Ok(());
         }
+        self.refresh_backend(mailbox_hash);
+        Ok(())
+    }
+
+    /// Refresh every loaded mailbox from the backend, e.g. after an external tool has synced the
+    /// account. Unlike `refresh`, this never runs `refresh_command`.
+    pub fn refresh_all(&mut self) {
+        let mailbox_hashes = self
+            .mailbox_entries
+            .iter()
+            .filter(|(_, entry)| entry.status.is_available())
+            .map(|(&mailbox_hash, _)| mailbox_hash)
+            .collect::<Vec<MailboxHash>>();
+        for mailbox_hash in mailbox_hashes {
+            self.refresh_backend(mailbox_hash);
+        }
+    }
+
+    /// Run the account's `sync_command` as a job, showing its output in the status bar, and
+    /// refresh all loaded mailboxes when it finishes successfully.
+    /// Run `action`, one of the backend's `MailBackendCapabilities::actions`, in the background.
+    pub fn backend_action(&mut self, action: &str, args: Vec<String>) -> Result<()> {
+        self.check_writable()?;
+        let job = self.backend.write().unwrap().perform_action(action, args)?;
+        let handle = if self.backend_capabilities.is_async {
+            self.job_executor.spawn_specialized(job)
+        } else {
+            self.job_executor.spawn_blocking(job)
+        };
+        self.insert_job(
+            handle.job_id,
+            JobRequest::BackendAction {
+                action: action.to_string(),
+                handle,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn synchronize(&mut self) -> Result<()> {
+        let sync_command = self.settings.conf().sync_command.clone().ok_or_else(|| {
+            MeliError::new(format!("Account {} has no sync_command set.", &self.name))
+        })?;
+        if self.active_jobs.values().any(JobRequest::is_synchronize) {
+            return Err(MeliError::new(format!(
+                "Account {} is already being synchronized.",
+                &self.name
+            )));
+        }
+        let sender = self.sender.clone();
+        let account_name = self.name.clone();
+        let job = async move {
+            use std::io::Read;
+            let mut child = std::process::Command::new("sh")
+                .args(&["-c", &format!("{} 2>&1", sync_command)])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::piped())
+                .spawn()?;
+            let mut stdout = child.stdout.take().unwrap();
+            let mut buf = [0; 4096];
+            let mut line = Vec::new();
+            let mut last_line = String::new();
+            /* Progress counters are usually redrawn with carriage returns, so both '\r' and '\n'
+             * end a status update. */
+            loop {
+                let n = stdout.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                for &b in &buf[..n] {
+                    if b != b'\r' && b != b'\n' {
+                        line.push(b);
+                        continue;
+                    }
+                    let status = String::from_utf8_lossy(&line).trim().to_string();
+                    line.clear();
+                    if status.is_empty() {
+                        continue;
+                    }
+                    sender
+                        .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                            StatusEvent::UpdateStatus(format!("{}: {}", account_name, status)),
+                        )))
+                        .unwrap();
+                    last_line = status;
+                }
+            }
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(MeliError::new(format!(
+                    "`{}` exited with {}: {}",
+                    sync_command, status, last_line
+                )));
+            }
+            Ok(())
+        };
+        let handle = self.job_executor.spawn_blocking(job);
+        self.insert_job(handle.job_id, JobRequest::Synchronize { handle });
+        Ok(())
+    }
+
+    fn refresh_backend(&mut self, mailbox_hash: MailboxHash) {
         let refresh_job = self.backend.write().unwrap().refresh(mailbox_hash);
         if let Ok(refresh_job) = refresh_job {
             let handle = if self.backend_capabilities.is_async {
@@ -1059,7 +1384,6 @@ impl Account {
                 },
             );
         }
-        Ok(())
     }
 
     pub fn watch(&mut self) {
@@ -1174,6 +1498,16 @@ impl Account {
         }
     }
 
+    /// Fetch `mailbox_hash` if its loading was deferred, because the user asked to open it.
+    pub fn load_deferred(&mut self, mailbox_hash: MailboxHash) -> result::Result<(), usize> {
+        if let Some(entry) = self.mailbox_entries.get_mut(&mailbox_hash) {
+            if let MailboxStatus::Deferred = entry.status {
+                entry.status = MailboxStatus::None;
+            }
+        }
+        self.load(mailbox_hash)
+    }
+
     pub fn save_special(
         &mut self,
         bytes: &[u8],
@@ -1189,7 +1523,8 @@ impl Account {
             if let Some(mailbox_hash) = mailbox {
                 if let Err(e) = self.save(bytes, *mailbox_hash, Some(flags)) {
                     debug!("{:?} could not save msg", e);
-                    melib::log(
+                    melib::log_target(
+                        "accounts",
                         format!(
                             "Could not save in '{}' mailbox: {}.",
                             *mailbox_hash,
@@ -1209,9 +1544,10 @@ impl Account {
         if let Some(mailbox_hash) = saved_at {
             Ok(mailbox_hash)
         } else {
-            let file = crate::types::create_temp_file(bytes, None, None, false);
+            let file = crate::types::create_temp_file(bytes, None, None, false)?;
             debug!("message saved in {}", file.path.display());
-            melib::log(
+            melib::log_target(
+                "accounts",
                 format!(
                     "Message was stored in {} so that you can restore it manually.",
                     file.path.display()
@@ -1232,17 +1568,17 @@ impl Account {
         mailbox_hash: MailboxHash,
         flags: Option<Flag>,
     ) -> Result<()> {
-        if self.settings.account.read_only() {
-            return Err(MeliError::new(format!(
-                "Account {} is read-only.",
-                self.name.as_str()
-            )));
-        }
+        self.check_writable()?;
         let job = self
             .backend
             .write()
             .unwrap()
             .save(bytes.to_vec(), mailbox_hash, flags)?;
+        if let Ok(envelope) = Envelope::from_bytes(bytes, flags) {
+            let mailbox = self.mailbox_path(mailbox_hash);
+            self.activity
+                .record_action(&envelope, &mailbox, "save".to_string());
+        }
 
         let handle = if self.backend_capabilities.is_async {
             self.job_executor.spawn_specialized(job)
@@ -1291,7 +1627,7 @@ impl Account {
                 }
                 let output = msmtp.wait().expect("Failed to wait on mailer");
                 if output.success() {
-                    melib::log("Message sent.", melib::LoggingLevel::TRACE);
+                    melib::log_target("accounts", "Message sent.", melib::LoggingLevel::TRACE);
                 } else {
                     let error_message = if let Some(exit_code) = output.code() {
                         format!(
@@ -1304,7 +1640,7 @@ impl Account {
                             command
                         )
                     };
-                    melib::log(&error_message, melib::LoggingLevel::ERROR);
+                    melib::log_target("accounts", &error_message, melib::LoggingLevel::ERROR);
                     return Err(
                         MeliError::new(error_message.clone()).set_summary("Message not sent.")
                     );
@@ -1326,6 +1662,22 @@ impl Account {
                 }
                 Ok(Some(handle))
             }
+            SendMail::ServerSubmission => {
+                if !self.backend_capabilities.supports_submission {
+                    return Err(MeliError::new(
+                        "send_mail is set to server_submission but this account's backend does not support it",
+                    ));
+                }
+                let job = self.backend.read().unwrap().submit(message.into_bytes())?;
+                let handle = self.job_executor.spawn_specialized(job);
+                if complete_in_background {
+                    self.insert_job(handle.job_id, JobRequest::SendMessageBackground { handle });
+                    return Ok(None);
+                } else {
+                    self.insert_job(handle.job_id, JobRequest::SendMessage);
+                }
+                Ok(Some(handle))
+            }
         }
     }
 
@@ -1333,7 +1685,8 @@ impl Account {
         &self,
         send_mail: crate::conf::composing::SendMail,
     ) -> impl FnOnce(Arc<String>) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send {
-        |message: Arc<String>| -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+        let backend = self.backend.clone();
+        move |message: Arc<String>| -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
             Box::pin(async move {
                 use crate::conf::composing::SendMail;
                 use std::io::Write;
@@ -1359,7 +1712,11 @@ impl Account {
                         }
                         let output = msmtp.wait().expect("Failed to wait on mailer");
                         if output.success() {
-                            melib::log("Message sent.", melib::LoggingLevel::TRACE);
+                            melib::log_target(
+                                "accounts",
+                                "Message sent.",
+                                melib::LoggingLevel::TRACE,
+                            );
                         } else {
                             let error_message = if let Some(exit_code) = output.code() {
                                 format!(
@@ -1372,7 +1729,11 @@ impl Account {
                             command
                         )
                             };
-                            melib::log(&error_message, melib::LoggingLevel::ERROR);
+                            melib::log_target(
+                                "accounts",
+                                &error_message,
+                                melib::LoggingLevel::ERROR,
+                            );
                             return Err(MeliError::new(error_message.clone())
                                 .set_summary("Message not sent."));
                         }
@@ -1386,6 +1747,13 @@ impl Account {
                             .mail_transaction(message.as_str(), None)
                             .await
                     }
+                    SendMail::ServerSubmission => {
+                        let job = backend
+                            .read()
+                            .unwrap()
+                            .submit(message.as_bytes().to_vec())?;
+                        job.await
+                    }
                 }
             })
         }
@@ -1395,12 +1763,29 @@ impl Account {
         self.collection.contains_key(&h)
     }
     pub fn operation(&self, h: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
-        let operation = self.backend.read().unwrap().operation(h)?;
-        Ok(if self.settings.account.read_only() {
-            ReadOnlyOp::new(operation)
-        } else {
-            operation
-        })
+        /* The backend of a read-only account wraps operations in `ReadOnlyOp` itself. */
+        self.backend.read().unwrap().operation(h)
+    }
+
+    /// Whether the `read_only` setting of the account is on.
+    pub fn is_read_only(&self) -> bool {
+        self.settings.account.read_only()
+    }
+
+    pub fn accent_color(&self) -> Option<crate::terminal::Color> {
+        self.settings.conf.accent_color
+    }
+
+    /// Fail with a message for the user if the account is read-only, before changing anything
+    /// in it.
+    pub fn check_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(MeliError::new(format!(
+                "Account {} is read-only.",
+                self.name.as_str()
+            )));
+        }
+        Ok(())
     }
 
     pub fn mailbox_operation(
@@ -1408,9 +1793,7 @@ impl Account {
         op: crate::command::actions::MailboxOperation,
     ) -> Result<()> {
         use crate::command::actions::MailboxOperation;
-        if self.settings.account.read_only() {
-            return Err(MeliError::new("Account is read-only."));
-        }
+        self.check_writable()?;
         match op {
             MailboxOperation::Create(path) => {
                 let job = self
@@ -1487,8 +1870,37 @@ impl Account {
                     .expect("Could not send event on main channel");
                 Ok(())
             }
-            MailboxOperation::Rename(_, _) => Err(MeliError::new("Not implemented.")),
+            MailboxOperation::Rename(path, new_path) => {
+                let mailbox_hash = self.mailbox_by_path(&path)?;
+                let rename_job = self
+                    .backend
+                    .write()
+                    .unwrap()
+                    .rename_mailbox(mailbox_hash, new_path.clone())?;
+                let mailboxes_job = self.backend.read().unwrap().mailboxes()?;
+                let job = async move {
+                    let new_mailbox = rename_job.await?;
+                    Ok((new_mailbox.hash(), mailboxes_job.await?))
+                };
+                let handle = if self.backend_capabilities.is_async {
+                    self.job_executor.spawn_specialized(job)
+                } else {
+                    self.job_executor.spawn_blocking(job)
+                };
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::RenameMailbox {
+                        mailbox_hash,
+                        new_path,
+                        handle,
+                    },
+                );
+                Ok(())
+            }
             MailboxOperation::SetPermissions(_) => Err(MeliError::new("Not implemented.")),
+            /* The dialog is opened by the state, properties are set with
+             * `set_mailbox_properties`. */
+            MailboxOperation::Properties(_) => Err(MeliError::new("Not implemented.")),
         }
     }
 
@@ -1504,6 +1916,217 @@ impl Account {
         }
     }
 
+    /// Whether new messages in `mailbox_hash` are notified of, going by the mailbox's and the
+    /// account's `notifications.enable` overrides.
+    fn notifies(&self, mailbox_hash: MailboxHash) -> bool {
+        self.mailbox_entries[&mailbox_hash]
+            .conf
+            .conf_override
+            .notifications
+            .enable
+            .or(self.settings.conf_override.notifications.enable)
+            .unwrap_or(true)
+    }
+
+    /// The properties of `mailbox_hash` that can be edited in the UI.
+    pub fn mailbox_properties(&self, mailbox_hash: MailboxHash) -> MailboxProperties {
+        let conf = &self.mailbox_entries[&mailbox_hash].conf.mailbox_conf;
+        MailboxProperties {
+            ignore: conf.ignore.is_true(),
+            notify: self.notifies(mailbox_hash),
+            autoload: conf.autoload.is_true(),
+            usage: conf.usage.unwrap_or_default(),
+        }
+    }
+
+    /// Update the Autocrypt state of the sender of `env_hash`, once its headers are loaded.
+    #[cfg(feature = "gpgme")]
+    pub fn harvest_autocrypt(&mut self, env_hash: EnvelopeHash) {
+        let (from, header, date) = {
+            let envelope = self.collection.get_env(env_hash);
+            let from = match envelope.from() {
+                [from] => from.get_email(),
+                _ => return,
+            };
+            (
+                from,
+                AutocryptHeader::from_envelope(&envelope),
+                envelope.date(),
+            )
+        };
+        if let Err(err) = self.autocrypt_peers.update(&from, header, date) {
+            debug!("Could not store Autocrypt state: {}", err);
+        }
+    }
+
+    /// Change the properties of `mailbox_hash` and remember them for the next start.
+    pub fn set_mailbox_properties(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        properties: MailboxProperties,
+    ) -> Result<()> {
+        let entry = self
+            .mailbox_entries
+            .get_mut(&mailbox_hash)
+            .ok_or_else(|| MeliError::new("Mailbox not found."))?;
+        properties.apply(&mut entry.conf);
+        let _ = entry.ref_mailbox.set_special_usage(properties.usage);
+        if properties.usage == SpecialUsageMailbox::Sent {
+            self.sent_mailbox = Some(mailbox_hash);
+        } else if self.sent_mailbox == Some(mailbox_hash) {
+            self.sent_mailbox = None;
+        }
+        let name = entry.name.clone();
+        if properties.autoload {
+            let _ = self.load_deferred(mailbox_hash);
+        }
+        self.mailbox_properties.set(&name, properties)
+    }
+
+    /// Delete all messages of `mailbox_hash` dated before `older_than`, or every message if it is
+    /// `None`. Returns the number of messages that are going to be deleted.
+    pub fn delete_mailbox_messages(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        older_than: Option<melib::UnixTimestamp>,
+    ) -> Result<usize> {
+        let env_hashes = {
+            let envelopes_lck = self.collection.envelopes.read().unwrap();
+            self.collection
+                .get_mailbox(mailbox_hash)
+                .iter()
+                .filter(|env_hash| {
+                    older_than
+                        .map(|timestamp| {
+                            envelopes_lck
+                                .get(env_hash)
+                                .map(|env| is_older_than(env.date(), timestamp))
+                                .unwrap_or(false)
+                        })
+                        .unwrap_or(true)
+                })
+                .cloned()
+                .collect::<Vec<EnvelopeHash>>()
+        };
+        let env_hashes = match EnvelopeHashBatch::try_from(env_hashes.as_slice()) {
+            Ok(env_hashes) => env_hashes,
+            Err(()) => return Ok(0),
+        };
+        let len = env_hashes.len();
+        self.check_writable()?;
+        let job = self
+            .backend
+            .write()
+            .unwrap()
+            .delete_messages(env_hashes.clone(), mailbox_hash)?;
+        self.record_action(env_hashes.iter(), mailbox_hash, "delete");
+        let handle = self.job_executor.spawn_specialized(job);
+        self.insert_job(
+            handle.job_id,
+            JobRequest::DeleteMessages { env_hashes, handle },
+        );
+        Ok(len)
+    }
+
+    /// Delete messages older than the `retention_days` setting of `mailbox_hash`, if it is set
+    /// and the mailbox has been loaded.
+    pub fn apply_retention_policy(&mut self, mailbox_hash: MailboxHash) {
+        let days = match self.mailbox_entries.get(&mailbox_hash) {
+            Some(entry) if entry.status.is_available() && !self.is_read_only() => {
+                match entry.conf.mailbox_conf().retention_days {
+                    Some(days) => days,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        let older_than = melib::datetime::now().saturating_sub(days.saturating_mul(24 * 60 * 60));
+        if let Err(err) = self.delete_mailbox_messages(mailbox_hash, Some(older_than)) {
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                    Some(format!(
+                        "{}: could not apply retention policy of {}",
+                        &self.name, &self.mailbox_entries[&mailbox_hash].name
+                    )),
+                    err.to_string(),
+                    Some(crate::types::NotificationType::Error(err.kind)),
+                )))
+                .expect("Could not send event on main channel");
+        }
+    }
+
+    /// Notifies the user about each envelope that can't be added to the collection because
+    /// another message already has its hash. Returns `true` if there were any.
+    fn report_collisions<'a>(&self, envelopes: impl Iterator<Item = &'a Envelope>) -> bool {
+        let mut ret = false;
+        for description in envelopes.filter_map(|e| self.collection.collision(e)) {
+            ret = true;
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                    Some(format!("{}: envelope hash collision", &self.name)),
+                    description,
+                    Some(crate::types::NotificationType::Error(
+                        melib::error::ErrorKind::Bug,
+                    )),
+                )))
+                .expect("Could not send event on main channel");
+        }
+        ret
+    }
+
+    pub fn apply_retention_policies(&mut self) {
+        let mailbox_hashes = self.mailbox_entries.keys().cloned().collect::<Vec<_>>();
+        for mailbox_hash in mailbox_hashes {
+            self.apply_retention_policy(mailbox_hash);
+        }
+    }
+
+    /// Query the storage quota of the account, if the backend supports it.
+    pub fn refresh_quota(&mut self) {
+        if self.is_online.is_err() || self.active_jobs.values().any(JobRequest::is_quota) {
+            return;
+        }
+        let quota_job = self.backend.read().unwrap().quota();
+        if let Ok(quota_job) = quota_job {
+            let handle = if self.backend_capabilities.is_async {
+                self.job_executor.spawn_specialized(quota_job)
+            } else {
+                self.job_executor.spawn_blocking(quota_job)
+            };
+            self.insert_job(handle.job_id, JobRequest::Quota { handle });
+        }
+    }
+
+    /// Update the connection status with one the backend reported on its own, eg while
+    /// reconnecting in the background.
+    pub fn set_connection_status(&mut self, status: Result<()>) {
+        if self.is_online.is_err()
+            && self
+                .is_online
+                .as_ref()
+                .unwrap_err()
+                .kind
+                .is_authentication()
+        {
+            return;
+        }
+        if let Err(ref err) = status {
+            melib::log_target(
+                "accounts",
+                format!("{}: {}", self.name, err.to_string()),
+                melib::LoggingLevel::WARN,
+            );
+        } else if self.is_online.is_err() {
+            self.watch();
+        }
+        self.is_online = status;
+        self.sender
+            .send(ThreadEvent::UIEvent(UIEvent::AccountStatusChange(
+                self.hash,
+            )))
+            .unwrap();
+    }
+
     /* Call only in Context::is_online, since only Context can launch the watcher threads if an
      * account goes from offline to online. */
     pub fn is_online(&mut self) -> Result<()> {
@@ -1538,6 +2161,16 @@ impl Account {
         return self.is_online.clone();
     }
 
+    /// Whether the account is offline only because its first connection attempt or mailbox list
+    /// request is still in progress.
+    pub fn is_connecting(&self) -> bool {
+        self.is_online.is_err()
+            && self
+                .active_jobs
+                .values()
+                .any(|j| j.is_online() || matches!(j, JobRequest::Mailboxes { .. }))
+    }
+
     pub fn search(
         &self,
         search_term: &str,
@@ -1545,6 +2178,20 @@ impl Account {
         mailbox_hash: MailboxHash,
     ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
         let query = melib::search::Query::try_from(search_term)?;
+        if query.has_note() || (query.has_size() && self.settings.account().format() == "notmuch") {
+            /* Notes are only known locally and notmuch doesn't index message sizes, so match
+             * against the loaded envelopes. */
+            let mut ret = SmallVec::new();
+            let envelopes = self.collection.envelopes.read().unwrap();
+            for &env_hash in self.collection.get_mailbox(mailbox_hash).iter() {
+                if let Some(envelope) = envelopes.get(&env_hash) {
+                    if self.is_match_with_notes(envelope, &query) {
+                        ret.push(env_hash);
+                    }
+                }
+            }
+            return Ok(Box::pin(async { Ok(ret) }));
+        }
         match self.settings.conf.search_backend {
             #[cfg(feature = "sqlite3")]
             crate::conf::SearchBackend::Sqlite3 => crate::sqlite3::search(&query, _sort),
@@ -1571,6 +2218,194 @@ impl Account {
         }
     }
 
+    fn is_match_with_notes(&self, envelope: &Envelope, query: &melib::search::Query) -> bool {
+        use melib::search::{Query::*, QueryTrait};
+        match query {
+            Note(s) => self.notes.is_match(envelope, s),
+            And(q_a, q_b) => {
+                self.is_match_with_notes(envelope, q_a) && self.is_match_with_notes(envelope, q_b)
+            }
+            Or(q_a, q_b) => {
+                self.is_match_with_notes(envelope, q_a) || self.is_match_with_notes(envelope, q_b)
+            }
+            Not(q) => !self.is_match_with_notes(envelope, q),
+            q => envelope.is_match(q),
+        }
+    }
+
+    /// Mute or unmute `thread` of `mailbox_hash` and remember it for the next start. Muted
+    /// threads don't issue notifications for new replies and their unseen messages aren't
+    /// counted. Returns whether the thread is now muted.
+    pub fn toggle_thread_mute(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        thread: ThreadHash,
+    ) -> Result<bool> {
+        let muted = !self
+            .collection
+            .get_threads(mailbox_hash)
+            .thread_ref(thread)
+            .snoozed();
+        self.set_thread_marks(mailbox_hash, thread, muted, false)?;
+        Ok(muted)
+    }
+
+    /// Watch or stop watching `thread` of `mailbox_hash` and remember it for the next start.
+    /// Watched threads always issue notifications for new replies, even in mailboxes with
+    /// `ignore` set. Returns whether the thread is now watched.
+    pub fn toggle_thread_watch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        thread: ThreadHash,
+    ) -> Result<bool> {
+        let watched = !self
+            .collection
+            .get_threads(mailbox_hash)
+            .thread_ref(thread)
+            .watched();
+        self.set_thread_marks(mailbox_hash, thread, false, watched)?;
+        Ok(watched)
+    }
+
+    /// A thread is either muted, watched or neither.
+    fn set_thread_marks(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        thread: ThreadHash,
+        muted: bool,
+        watched: bool,
+    ) -> Result<()> {
+        let env_hashes = {
+            let mut threads = self.collection.threads.write().unwrap();
+            let threads = match threads.get_mut(&mailbox_hash) {
+                Some(threads) => threads,
+                None => return Ok(()),
+            };
+            let thread_ref = threads.thread_ref_mut(thread);
+            thread_ref.set_snoozed(muted);
+            thread_ref.set_watched(watched);
+            threads
+                .thread_group_iter(thread)
+                .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+                .collect::<SmallVec<[EnvelopeHash; 16]>>()
+        };
+        let message_ids = env_hashes
+            .iter()
+            .map(|&env_hash| {
+                self.collection
+                    .get_env(env_hash)
+                    .message_id_raw()
+                    .into_owned()
+            })
+            .collect::<Vec<String>>();
+        let mailbox_path = self.mailbox_entries[&mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        for (marks, value) in vec![
+            (&mut self.muted_threads, muted),
+            (&mut self.watched_threads, watched),
+        ] {
+            if !value {
+                marks.remove(&mailbox_path, &message_ids)?;
+            } else if let Some(message_id) = message_ids.first() {
+                marks.add(&mailbox_path, message_id.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark the threads of `mailbox_hash` that were muted or watched in a previous session.
+    fn reapply_thread_marks(&self, mailbox_hash: MailboxHash) {
+        let mailbox_path = match self.mailbox_entries.get(&mailbox_hash) {
+            Some(entry) => entry.ref_mailbox.path(),
+            None => return,
+        };
+        let mut threads = self.collection.threads.write().unwrap();
+        let threads = match threads.get_mut(&mailbox_hash) {
+            Some(threads) => threads,
+            None => return,
+        };
+        for message_id in self.muted_threads.get(mailbox_path) {
+            if let Some(node) = threads.find_message_id(message_id.as_bytes()) {
+                let group = threads.thread_nodes()[&node].group;
+                threads.thread_ref_mut(group).set_snoozed(true);
+            }
+        }
+        for message_id in self.watched_threads.get(mailbox_path) {
+            if let Some(node) = threads.find_message_id(message_id.as_bytes()) {
+                let group = threads.thread_nodes()[&node].group;
+                threads.thread_ref_mut(group).set_watched(true);
+            }
+        }
+    }
+
+    /// The unseen messages of `mailbox_hash`, without the ones in muted threads.
+    pub fn unseen_count(&self, mailbox_hash: MailboxHash) -> Option<usize> {
+        let entry = self.mailbox_entries.get(&mailbox_hash)?;
+        let (unseen, _) = entry.ref_mailbox.count().ok()?;
+        let threads = self.collection.threads.read().unwrap();
+        let threads = match threads.get(&mailbox_hash) {
+            Some(threads) => threads,
+            None => return Some(unseen),
+        };
+        let mut groups = HashSet::new();
+        let muted: usize = self
+            .muted_threads
+            .get(entry.ref_mailbox.path())
+            .filter_map(|message_id| threads.find_message_id(message_id.as_bytes()))
+            .map(|node| threads.find_group(threads.thread_nodes()[&node].group))
+            .filter(|&group| groups.insert(group) && threads.thread_ref(group).snoozed())
+            .map(|group| threads.thread_ref(group).unseen())
+            .sum();
+        Some(unseen.saturating_sub(muted))
+    }
+
+    fn mailbox_path(&self, mailbox_hash: MailboxHash) -> String {
+        self.mailbox_entries
+            .get(&mailbox_hash)
+            .map(|entry| entry.ref_mailbox.path().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The names of the tags of `envelope`.
+    fn tag_names(&self, envelope: &Envelope) -> Vec<String> {
+        let tag_index = self.collection.tag_index.read().unwrap();
+        envelope
+            .labels()
+            .iter()
+            .filter_map(|h| tag_index.get(h).cloned())
+            .collect()
+    }
+
+    /// Record in the activity journal that the user asked for `action` on `env_hashes` of
+    /// `mailbox_hash`.
+    pub fn record_action(
+        &mut self,
+        env_hashes: impl IntoIterator<Item = EnvelopeHash>,
+        mailbox_hash: MailboxHash,
+        action: &str,
+    ) {
+        let mailbox = self.mailbox_path(mailbox_hash);
+        let envelopes = self.collection.envelopes.read().unwrap();
+        for env_hash in env_hashes {
+            if let Some(envelope) = envelopes.get(&env_hash) {
+                self.activity
+                    .record_action(envelope, &mailbox, action.to_string());
+            }
+        }
+    }
+
+    fn record_change(
+        &mut self,
+        envelope: &Envelope,
+        mailbox_hash: MailboxHash,
+        change: activity::Change,
+    ) {
+        let mailbox = self.mailbox_path(mailbox_hash);
+        self.activity.record_change(envelope, &mailbox, change);
+    }
+
     pub fn mailbox_by_path(&self, path: &str) -> Result<MailboxHash> {
         if let Some((mailbox_hash, _)) = self
             .mailbox_entries
@@ -1620,6 +2455,8 @@ impl Account {
                                 };
                                 self.insert_job(handle.job_id, JobRequest::Mailboxes { handle });
                             };
+                        } else {
+                            self.refresh_quota();
                         }
                     }
                 }
@@ -1639,11 +2476,13 @@ impl Account {
                         }
                         Ok(Some((None, _))) => {
                             debug!("finished in status for {}", mailbox_hash);
+                            crate::profiling::mark_once("first mailbox fetch");
                             self.mailbox_entries
                                 .entry(mailbox_hash)
                                 .and_modify(|entry| {
                                     entry.status = MailboxStatus::Available;
                                 });
+                            self.apply_retention_policy(mailbox_hash);
                             self.sender
                                 .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
                                     self.hash,
@@ -1690,6 +2529,12 @@ impl Account {
                                 .into_iter()
                                 .map(|e| (e.hash(), e))
                                 .collect::<HashMap<EnvelopeHash, Envelope>>();
+                            if self.sent_mailbox == Some(mailbox_hash) {
+                                if let Err(err) = self.recipient_history.index(envelopes.values()) {
+                                    debug!("Could not store recipient history: {}", err);
+                                }
+                            }
+                            self.report_collisions(envelopes.values());
                             if let Some(updated_mailboxes) =
                                 self.collection
                                     .merge(envelopes, mailbox_hash, self.sent_mailbox)
@@ -1702,6 +2547,7 @@ impl Account {
                                         .unwrap();
                                 }
                             }
+                            self.reapply_thread_marks(mailbox_hash);
                             self.sender
                                 .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
                                     self.hash,
@@ -1744,7 +2590,15 @@ impl Account {
                         self.insert_job(handle.job_id, JobRequest::IsOnline { handle });
                     };
                 }
-                JobRequest::Refresh { ref mut handle, .. } => {
+                JobRequest::Refresh {
+                    mailbox_hash,
+                    ref mut handle,
+                } => {
+                    let mailbox_name = self
+                        .mailbox_entries
+                        .get(&mailbox_hash)
+                        .map(|entry| entry.name().to_string())
+                        .unwrap_or_default();
                     match handle.chan.try_recv() {
                         Err(_) => { /* canceled */ }
                         Ok(None) => {}
@@ -1774,8 +2628,26 @@ impl Account {
                                     )))
                                     .unwrap();
                             }
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(format!(
+                                        "{}: {} refreshed",
+                                        &self.name, mailbox_name
+                                    )),
+                                )))
+                                .unwrap();
                         }
                         Ok(Some(Err(err))) => {
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!(
+                                        "{}: could not refresh {}",
+                                        &self.name, mailbox_name
+                                    )),
+                                    err.to_string(),
+                                    Some(crate::types::NotificationType::Error(err.kind)),
+                                )))
+                                .expect("Could not send event on main channel");
                             if !err.kind.is_authentication() {
                                 let online_job = self.backend.read().unwrap().is_online();
                                 if let Ok(online_job) = online_job {
@@ -1796,6 +2668,85 @@ impl Account {
                         }
                     }
                 }
+                JobRequest::Synchronize { ref mut handle } => match handle.chan.try_recv() {
+                    Err(_) | Ok(None) => { /* canceled */ }
+                    Ok(Some(Ok(()))) => {
+                        self.refresh_all();
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(format!(
+                                    "{}: synchronization finished",
+                                    &self.name
+                                )),
+                            )))
+                            .unwrap();
+                    }
+                    Ok(Some(Err(err))) => {
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                Some(format!("{}: synchronization failed", &self.name)),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            )))
+                            .expect("Could not send event on main channel");
+                    }
+                },
+                JobRequest::BackendAction {
+                    ref action,
+                    ref mut handle,
+                } => match handle.chan.try_recv() {
+                    Err(_) | Ok(None) => { /* canceled */ }
+                    Ok(Some(Ok(message))) => {
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                Some(format!("{}: {} succeeded", &self.name, action)),
+                                message,
+                                Some(crate::types::NotificationType::Info),
+                            )))
+                            .expect("Could not send event on main channel");
+                    }
+                    Ok(Some(Err(err))) => {
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                Some(format!("{}: {} failed", &self.name, action)),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            )))
+                            .expect("Could not send event on main channel");
+                    }
+                },
+                JobRequest::Quota { ref mut handle } => match handle.chan.try_recv() {
+                    Err(_) | Ok(None) => { /* canceled */ }
+                    Ok(Some(Ok(quota))) => {
+                        let threshold = self.settings.conf().quota_warning_threshold;
+                        let was_over_threshold = self
+                            .quota
+                            .map(|q| q.percentage() as usize >= threshold)
+                            .unwrap_or(false);
+                        if !was_over_threshold && quota.percentage() as usize >= threshold {
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!("{}: storage quota almost full", &self.name)),
+                                    format!("Using {} of available storage.", quota),
+                                    Some(crate::types::NotificationType::Info),
+                                )))
+                                .expect("Could not send event on main channel");
+                        }
+                        self.quota = Some(quota);
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::AccountStatusChange(
+                                self.hash,
+                            )))
+                            .unwrap();
+                    }
+                    Ok(Some(Err(err))) => {
+                        melib::log_target(
+                            "accounts",
+                            format!("{}: could not get storage quota: {}", &self.name, err),
+                            melib::DEBUG,
+                        );
+                    }
+                },
                 JobRequest::SetFlags { ref mut handle, .. } => {
                     if let Ok(Some(Err(err))) = handle.chan.try_recv() {
                         self.sender
@@ -1813,10 +2764,25 @@ impl Account {
                     ..
                 } => {
                     if let Ok(Some(Err(err))) = handle.chan.try_recv() {
-                        melib::log(format!("Could not save message: {}", err), melib::ERROR);
-                        let file = crate::types::create_temp_file(bytes, None, None, false);
+                        melib::log_target(
+                            "accounts",
+                            format!("Could not save message: {}", err),
+                            melib::ERROR,
+                        );
+                        let file = match crate::types::create_temp_file(bytes, None, None, false) {
+                            Ok(file) => file,
+                            Err(err) => {
+                                melib::log_target(
+                                    "accounts",
+                                    format!("Could not store the unsaved message: {}", err),
+                                    melib::ERROR,
+                                );
+                                return true;
+                            }
+                        };
                         debug!("message saved in {}", file.path.display());
-                        melib::log(
+                        melib::log_target(
+                            "accounts",
                             format!(
                                 "Message was stored in {} so that you can restore it manually.",
                                 file.path.display()
@@ -2041,7 +3007,108 @@ impl Account {
                         }
                     }
                 }
-                //JobRequest::RenameMailbox,
+                JobRequest::RenameMailbox {
+                    mailbox_hash,
+                    ref new_path,
+                    ref mut handle,
+                } => {
+                    match handle.chan.try_recv() {
+                        Err(_) => { /* canceled */ }
+                        Ok(None) => {}
+                        Ok(Some(Err(err))) => {
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!(
+                                        "{}: could not rename mailbox to {}",
+                                        &self.name, new_path
+                                    )),
+                                    err.to_string(),
+                                    Some(crate::types::NotificationType::Error(err.kind)),
+                                )))
+                                .expect("Could not send event on main channel");
+                        }
+                        Ok(Some(Ok((new_hash, mut mailboxes)))) => {
+                            /* The renamed mailbox gets a new hash, so it's replaced by a new
+                             * entry that keeps the old one's configuration. */
+                            let old_entry = match self.mailbox_entries.remove(&mailbox_hash) {
+                                Some(entry) => entry,
+                                None => return true,
+                            };
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::MailboxDelete((
+                                    self.hash,
+                                    mailbox_hash,
+                                ))))
+                                .unwrap();
+                            if self.sent_mailbox == Some(mailbox_hash) {
+                                self.sent_mailbox = Some(new_hash);
+                            }
+                            self.collection
+                                .threads
+                                .write()
+                                .unwrap()
+                                .remove(&mailbox_hash);
+                            self.collection
+                                .mailboxes
+                                .write()
+                                .unwrap()
+                                .remove(&mailbox_hash);
+                            let new_mailbox = match mailboxes.remove(&new_hash) {
+                                Some(mailbox) => mailbox,
+                                None => return true,
+                            };
+                            /* Both the old and the new parent have different children now. */
+                            for parent_hash in old_entry
+                                .ref_mailbox
+                                .parent()
+                                .into_iter()
+                                .chain(new_mailbox.parent())
+                            {
+                                if let Some(parent) = mailboxes.remove(&parent_hash) {
+                                    self.mailbox_entries
+                                        .entry(parent_hash)
+                                        .and_modify(|entry| entry.ref_mailbox = parent);
+                                }
+                            }
+                            self.mailbox_entries.insert(
+                                new_hash,
+                                MailboxEntry {
+                                    name: new_mailbox.path().to_string(),
+                                    status: MailboxStatus::default(),
+                                    conf: old_entry.conf,
+                                    ref_mailbox: new_mailbox,
+                                },
+                            );
+                            self.collection
+                                .threads
+                                .write()
+                                .unwrap()
+                                .insert(new_hash, Threads::default());
+                            self.collection
+                                .mailboxes
+                                .write()
+                                .unwrap()
+                                .insert(new_hash, Default::default());
+                            build_mailboxes_order(
+                                &mut self.tree,
+                                &self.mailbox_entries,
+                                &mut self.mailboxes_order,
+                            );
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::MailboxCreate((
+                                    self.hash, new_hash,
+                                ))))
+                                .unwrap();
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!("{}: mailbox renamed successfully", &self.name)),
+                                    format!("`{}` is now `{}`.", old_entry.name, new_path),
+                                    Some(crate::types::NotificationType::Info),
+                                )))
+                                .expect("Could not send event on main channel");
+                        }
+                    }
+                }
                 JobRequest::Search { .. } | JobRequest::AsBytes { .. } => {}
                 JobRequest::SetMailboxPermissions { ref mut handle, .. } => {
                     match handle.chan.try_recv() {
@@ -2327,3 +3394,43 @@ fn build_mailboxes_order(
         rec(node, &mailbox_entries, 0, 0, false);
     }
 }
+
+/// Whether the mailbox at `path` is selected by a `subscribed_mailboxes` entry. An entry equal to
+/// the path always matches, so that paths containing glob characters such as `[Gmail]/Sent Mail`
+/// don't have to be escaped.
+fn subscribed_mailbox_matches(path: &str, pattern: &str) -> bool {
+    path == pattern || path.matches_glob(pattern)
+}
+
+/// Whether a message dated `date` is older than `timestamp`. Messages without a (parsable) date
+/// have a `date` of 0 and are never considered old, so that a retention policy doesn't delete
+/// them.
+fn is_older_than(date: melib::UnixTimestamp, timestamp: melib::UnixTimestamp) -> bool {
+    date != 0 && date < timestamp
+}
+
+#[test]
+fn test_subscribed_mailbox_matches() {
+    assert!(subscribed_mailbox_matches(
+        "[Gmail]/Sent Mail",
+        "[Gmail]/Sent Mail"
+    ));
+    assert!(subscribed_mailbox_matches(
+        "[Gmail]/Sent Mail",
+        "\\[Gmail]/*"
+    ));
+    assert!(!subscribed_mailbox_matches(
+        "[Gmail]/Sent Mail",
+        "[Gmail]/*"
+    ));
+    assert!(subscribed_mailbox_matches("INBOX/Sent", "INBOX/*"));
+}
+
+#[test]
+fn test_is_older_than() {
+    assert!(is_older_than(1, 2));
+    assert!(!is_older_than(2, 2));
+    assert!(!is_older_than(3, 2));
+    /* Undated messages are kept. */
+    assert!(!is_older_than(0, 2));
+}
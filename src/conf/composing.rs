@@ -21,6 +21,8 @@
 
 //! Configuration for composing email.
 use super::default_vals::{false_val, none, true_val};
+use melib::email::compose::mime::TransferEncoding;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
 /// Settings for writing and sending new e-mail
@@ -72,6 +74,26 @@ pub struct ComposingSettings {
     /// Default: true
     #[serde(default = "true_val")]
     pub attribution_use_posix_locale: bool,
+    /// Named reply templates, used by the bulk-reply-template command.
+    /// The format specifiers for the replied envelope are:
+    /// - `%+f` — the sender's name and email address.
+    /// - `%+n` — the sender's name (or email address, if no name is included).
+    /// - `%+a` — the sender's email address.
+    /// - `%+s` — the subject.
+    /// - `%+d` — the date.
+    /// Default: empty
+    #[serde(default, alias = "reply-templates")]
+    pub reply_templates: HashMap<String, String>,
+    /// The Content-Transfer-Encoding of the text of sent mail: "auto", "8bit",
+    /// "quoted-printable" or "base64". "auto" sends 7bit text as is and encodes the rest.
+    /// Default: "auto"
+    #[serde(default, alias = "transfer-encoding")]
+    pub transfer_encoding: TransferEncoding,
+    /// Write the body of new drafts in Markdown, and send it as a multipart/alternative of the
+    /// text and its rendering to HTML. Requires the `markdown` build feature.
+    /// Default: false
+    #[serde(default = "false_val")]
+    pub markdown: bool,
 }
 
 impl Default for ComposingSettings {
@@ -86,14 +108,59 @@ impl Default for ComposingSettings {
             store_sent_mail: true,
             attribution_format_string: None,
             attribution_use_posix_locale: true,
+            reply_templates: HashMap::default(),
+            transfer_encoding: TransferEncoding::default(),
+            markdown: false,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(untagged)]
+#[derive(Debug, Clone)]
 pub enum SendMail {
+    #[cfg(feature = "smtp")]
+    Smtp(melib::smtp::SmtpServerConf),
+    /// Submit mail through the account's backend, for backends that support it. Set with the
+    /// value `"server_submission"`.
+    ServerSubmission,
+    ShellCommand(String),
+}
+
+/// `SendMail` as it is written in the configuration file.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SendMailValue {
     #[cfg(feature = "smtp")]
     Smtp(melib::smtp::SmtpServerConf),
     ShellCommand(String),
 }
+
+impl<'de> Deserialize<'de> for SendMail {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match SendMailValue::deserialize(deserializer)? {
+            #[cfg(feature = "smtp")]
+            SendMailValue::Smtp(conf) => SendMail::Smtp(conf),
+            SendMailValue::ShellCommand(s) if s == "server_submission" => {
+                SendMail::ServerSubmission
+            }
+            SendMailValue::ShellCommand(s) => SendMail::ShellCommand(s),
+        })
+    }
+}
+
+impl Serialize for SendMail {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            #[cfg(feature = "smtp")]
+            SendMail::Smtp(conf) => SendMailValue::Smtp(conf.clone()),
+            SendMail::ServerSubmission => SendMailValue::ShellCommand("server_submission".into()),
+            SendMail::ShellCommand(s) => SendMailValue::ShellCommand(s.clone()),
+        }
+        .serialize(serializer)
+    }
+}
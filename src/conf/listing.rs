@@ -20,8 +20,11 @@
  */
 
 use super::{default_vals::*, DotAddressable, IndexStyle};
+use crate::terminal::Key;
+use indexmap::IndexMap;
 use melib::search::Query;
 use melib::{MeliError, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Settings for mail listings
 ///
@@ -99,8 +102,106 @@ pub struct ListingSettings {
     ///Default: ' '
     #[serde(default = "default_divider")]
     pub sidebar_divider: char,
+
+    /// Show the account's tags with their colors and unread counts below its mailboxes in the
+    /// sidebar. Selecting a tag filters the current listing by it.
+    /// Default: true
+    #[serde(default = "true_val", alias = "sidebar-show-tags")]
+    pub sidebar_show_tags: bool,
+
+    /// Show the opened entry in a pane next to the listing instead of in its place.
+    /// Default: true for the conversations and threaded styles, false otherwise
+    #[serde(default = "none", alias = "preview-pane")]
+    pub preview_pane: Option<bool>,
+
+    /// Place the preview pane to the right of the listing ("vertical") or below it
+    /// ("horizontal").
+    /// Default: "horizontal" for the threaded style, "vertical" otherwise
+    #[serde(default = "none", alias = "preview-pane-orientation")]
+    pub preview_pane_orientation: Option<PreviewPaneOrientation>,
+
+    /// Percentage of the listing's width, or height if horizontal, given to the preview pane.
+    /// Default: 66 if vertical, the pager's `pager_ratio` if horizontal
+    #[serde(default = "none", alias = "preview-pane-ratio")]
+    pub preview_pane_ratio: Option<usize>,
+
+    /// Show the size of each message, or the total size of each thread in the compact style, in
+    /// the last column of the plain and compact listings.
+    /// Default: false
+    #[serde(default = "false_val", alias = "show-size")]
+    pub show_size: bool,
+
+    /// Locale whose alphabetical order sorting by subject or sender follows, e.g. "sv_SE". Case
+    /// and accents are ignored unless the strings are otherwise equal. "C" compares bytes.
+    /// Default: the locale of the `LC_ALL`, `LC_COLLATE` or `LANG` environment variables
+    #[serde(default = "none", alias = "collation-locale")]
+    pub collation_locale: Option<String>,
+
+    /// Put a row with "Today", "Yesterday", "Last week" or the month between entries of different
+    /// dates in the plain, compact and conversations listings when they are sorted by date.
+    /// Default: false
+    #[serde(default = "false_val", alias = "date-separators")]
+    pub date_separators: bool,
+
+    /// Indicator of a message or thread with attachments in the flag column.
+    /// Default: "📎", or "@" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "attachment-flag")]
+    pub attachment_flag: Option<String>,
+
+    /// Indicator of a snoozed thread in the flag column.
+    /// Default: "💤", or "z" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "snoozed-flag")]
+    pub snoozed_flag: Option<String>,
+
+    /// Indicator of a watched thread in the flag column.
+    /// Default: "🔔", or "w" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "watched-flag")]
+    pub watched_flag: Option<String>,
+
+    /// Indicator of a message with a note in the flag column.
+    /// Default: "📝", or "n" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "note-flag")]
+    pub note_flag: Option<String>,
+
+    /// Indicator of a replied message in the flag column.
+    /// Default: "↩", or "r" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "replied-flag")]
+    pub replied_flag: Option<String>,
+
+    /// Indicator of a flagged message in the flag column.
+    /// Default: "⚑", or "!" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "flagged-flag")]
+    pub flagged_flag: Option<String>,
+
+    /// Indicator of a signed message in the flag column.
+    /// Default: "🔏", or "s" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "signed-flag")]
+    pub signed_flag: Option<String>,
+
+    /// Indicator of an encrypted message in the flag column.
+    /// Default: "🔒", or "e" with `terminal.ascii_drawing`
+    #[serde(default = "none", alias = "encrypted-flag")]
+    pub encrypted_flag: Option<String>,
+
+    /// Named searches to filter the listing with, with the `filter-preset` command or their key.
+    /// Default: empty
+    #[serde(default, alias = "filter-presets")]
+    pub filter_presets: IndexMap<String, FilterPreset>,
 }
 
+/// A named search of `listing.filter_presets`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterPreset {
+    /// The search query, as given to the `search` command.
+    pub query: String,
+    /// Key that applies the preset in listings.
+    #[serde(default)]
+    pub key: Option<Key>,
+}
+
+impl DotAddressable for FilterPreset {}
+
 const fn default_divider() -> char {
     ' '
 }
@@ -119,6 +220,22 @@ impl Default for ListingSettings {
             sidebar_mailbox_tree_has_sibling_leaf: None,
             sidebar_mailbox_tree_no_sibling_leaf: None,
             sidebar_divider: default_divider(),
+            sidebar_show_tags: true,
+            preview_pane: None,
+            preview_pane_orientation: None,
+            preview_pane_ratio: None,
+            show_size: false,
+            collation_locale: None,
+            date_separators: false,
+            attachment_flag: None,
+            snoozed_flag: None,
+            watched_flag: None,
+            note_flag: None,
+            replied_flag: None,
+            flagged_flag: None,
+            signed_flag: None,
+            encrypted_flag: None,
+            filter_presets: IndexMap::default(),
         }
     }
 }
@@ -148,6 +265,22 @@ impl DotAddressable for ListingSettings {
                         .sidebar_mailbox_tree_no_sibling_leaf
                         .lookup(field, tail),
                     "sidebar_divider" => self.sidebar_divider.lookup(field, tail),
+                    "sidebar_show_tags" => self.sidebar_show_tags.lookup(field, tail),
+                    "preview_pane" => self.preview_pane.lookup(field, tail),
+                    "preview_pane_orientation" => self.preview_pane_orientation.lookup(field, tail),
+                    "preview_pane_ratio" => self.preview_pane_ratio.lookup(field, tail),
+                    "show_size" => self.show_size.lookup(field, tail),
+                    "collation_locale" => self.collation_locale.lookup(field, tail),
+                    "date_separators" => self.date_separators.lookup(field, tail),
+                    "attachment_flag" => self.attachment_flag.lookup(field, tail),
+                    "snoozed_flag" => self.snoozed_flag.lookup(field, tail),
+                    "watched_flag" => self.watched_flag.lookup(field, tail),
+                    "note_flag" => self.note_flag.lookup(field, tail),
+                    "replied_flag" => self.replied_flag.lookup(field, tail),
+                    "flagged_flag" => self.flagged_flag.lookup(field, tail),
+                    "signed_flag" => self.signed_flag.lookup(field, tail),
+                    "encrypted_flag" => self.encrypted_flag.lookup(field, tail),
+                    "filter_presets" => self.filter_presets.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -158,3 +291,39 @@ impl DotAddressable for ListingSettings {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PreviewPaneOrientation {
+    Vertical,
+    Horizontal,
+}
+
+impl<'de> Deserialize<'de> for PreviewPaneOrientation {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String>::deserialize(deserializer)?;
+        match s.as_str() {
+            "Vertical" | "vertical" => Ok(PreviewPaneOrientation::Vertical),
+            "Horizontal" | "horizontal" => Ok(PreviewPaneOrientation::Horizontal),
+            _ => Err(de::Error::custom(
+                "invalid `preview_pane_orientation` value",
+            )),
+        }
+    }
+}
+
+impl Serialize for PreviewPaneOrientation {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            PreviewPaneOrientation::Vertical => serializer.serialize_str("vertical"),
+            PreviewPaneOrientation::Horizontal => serializer.serialize_str("horizontal"),
+        }
+    }
+}
+
+impl DotAddressable for PreviewPaneOrientation {}
@@ -76,6 +76,38 @@ pub struct PagerSettingsOverride {
     #[serde(alias = "auto-choose-multipart-alternative")]
     #[serde(default)]
     pub auto_choose_multipart_alternative: Option<ToggleFlag>,
+    #[doc = " Which part of `multipart/alternative` attachments to display by default, `text/plain` or"]
+    #[doc = " `text/html`."]
+    #[doc = " Default: \"text/plain\""]
+    #[serde(alias = "multipart-alternative-preference")]
+    #[serde(default)]
+    pub multipart_alternative_preference: Option<MultipartAlternativePreference>,
+    #[doc = " A command to pass the events of `text/calendar` attachments to with the `export-ics`"]
+    #[doc = " command. `%s` is replaced by the path of a temporary `.ics` file; if it is missing the"]
+    #[doc = " events are piped to the command's standard input instead."]
+    #[doc = " Default: None"]
+    #[serde(deserialize_with = "non_empty_string", alias = "ics-export-command")]
+    #[serde(default)]
+    pub ics_export_command: Option<Option<String>>,
+    #[doc = " Show the dates of messages in the thread view and the Date header in the time zone of"]
+    #[doc = " their sender instead of the local one."]
+    #[doc = " Default: false"]
+    #[serde(alias = "show-sender-timezone")]
+    #[serde(default)]
+    pub show_sender_timezone: Option<bool>,
+    #[doc = " Commands to open attachments with instead of the default application of their content"]
+    #[doc = " type, keyed by content type or a glob such as `image/*`. The first matching entry is used."]
+    #[doc = " `%s` is replaced by the path of the attachment, which is otherwise appended to the command."]
+    #[doc = " Default: empty"]
+    #[serde(alias = "attachment-openers")]
+    #[serde(default)]
+    pub attachment_openers: Option<IndexMap<String, String>>,
+    #[doc = " A command to run attachment viewers in, such as `firejail --net=none`. The viewer and its"]
+    #[doc = " arguments are appended to it."]
+    #[doc = " Default: None"]
+    #[serde(deserialize_with = "non_empty_string", alias = "attachment-sandbox")]
+    #[serde(default)]
+    pub attachment_sandbox: Option<Option<String>>,
 }
 impl Default for PagerSettingsOverride {
     fn default() -> Self {
@@ -90,6 +122,11 @@ impl Default for PagerSettingsOverride {
             split_long_lines: None,
             minimum_width: None,
             auto_choose_multipart_alternative: None,
+            multipart_alternative_preference: None,
+            ics_export_command: None,
+            show_sender_timezone: None,
+            attachment_openers: None,
+            attachment_sandbox: None,
         }
     }
 }
@@ -138,6 +175,91 @@ pub struct ListingSettingsOverride {
     #[doc = "Default: ' '"]
     #[serde(default)]
     pub sidebar_divider: Option<char>,
+    #[doc = " Show the account's tags with their colors and unread counts below its mailboxes in the"]
+    #[doc = " sidebar. Selecting a tag filters the current listing by it."]
+    #[doc = " Default: true"]
+    #[serde(alias = "sidebar-show-tags")]
+    #[serde(default)]
+    pub sidebar_show_tags: Option<bool>,
+    #[doc = " Show the opened entry in a pane next to the listing instead of in its place."]
+    #[doc = " Default: true for the conversations and threaded styles, false otherwise"]
+    #[serde(alias = "preview-pane")]
+    #[serde(default)]
+    pub preview_pane: Option<Option<bool>>,
+    #[doc = " Place the preview pane to the right of the listing (\"vertical\") or below it"]
+    #[doc = " (\"horizontal\")."]
+    #[doc = " Default: \"horizontal\" for the threaded style, \"vertical\" otherwise"]
+    #[serde(alias = "preview-pane-orientation")]
+    #[serde(default)]
+    pub preview_pane_orientation: Option<Option<PreviewPaneOrientation>>,
+    #[doc = " Percentage of the listing's width, or height if horizontal, given to the preview pane."]
+    #[doc = " Default: 66 if vertical, the pager's `pager_ratio` if horizontal"]
+    #[serde(alias = "preview-pane-ratio")]
+    #[serde(default)]
+    pub preview_pane_ratio: Option<Option<usize>>,
+    #[doc = " Show the size of each message, or the total size of each thread in the compact style, in"]
+    #[doc = " the last column of the plain and compact listings."]
+    #[doc = " Default: false"]
+    #[serde(alias = "show-size")]
+    #[serde(default)]
+    pub show_size: Option<bool>,
+    #[doc = " Locale whose alphabetical order sorting by subject or sender follows, e.g. \"sv_SE\". Case"]
+    #[doc = " and accents are ignored unless the strings are otherwise equal. \"C\" compares bytes."]
+    #[doc = " Default: the locale of the `LC_ALL`, `LC_COLLATE` or `LANG` environment variables"]
+    #[serde(alias = "collation-locale")]
+    #[serde(default)]
+    pub collation_locale: Option<Option<String>>,
+    #[doc = " Put a row with \"Today\", \"Yesterday\", \"Last week\" or the month between entries of different"]
+    #[doc = " dates in the plain, compact and conversations listings when they are sorted by date."]
+    #[doc = " Default: false"]
+    #[serde(alias = "date-separators")]
+    #[serde(default)]
+    pub date_separators: Option<bool>,
+    #[doc = " Indicator of a message or thread with attachments in the flag column."]
+    #[doc = " Default: \"📎\", or \"@\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "attachment-flag")]
+    #[serde(default)]
+    pub attachment_flag: Option<Option<String>>,
+    #[doc = " Indicator of a snoozed thread in the flag column."]
+    #[doc = " Default: \"💤\", or \"z\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "snoozed-flag")]
+    #[serde(default)]
+    pub snoozed_flag: Option<Option<String>>,
+    #[doc = " Indicator of a watched thread in the flag column."]
+    #[doc = " Default: \"🔔\", or \"w\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "watched-flag")]
+    #[serde(default)]
+    pub watched_flag: Option<Option<String>>,
+    #[doc = " Indicator of a message with a note in the flag column."]
+    #[doc = " Default: \"📝\", or \"n\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "note-flag")]
+    #[serde(default)]
+    pub note_flag: Option<Option<String>>,
+    #[doc = " Indicator of a replied message in the flag column."]
+    #[doc = " Default: \"↩\", or \"r\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "replied-flag")]
+    #[serde(default)]
+    pub replied_flag: Option<Option<String>>,
+    #[doc = " Indicator of a flagged message in the flag column."]
+    #[doc = " Default: \"⚑\", or \"!\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "flagged-flag")]
+    #[serde(default)]
+    pub flagged_flag: Option<Option<String>>,
+    #[doc = " Indicator of a signed message in the flag column."]
+    #[doc = " Default: \"🔏\", or \"s\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "signed-flag")]
+    #[serde(default)]
+    pub signed_flag: Option<Option<String>>,
+    #[doc = " Indicator of an encrypted message in the flag column."]
+    #[doc = " Default: \"🔒\", or \"e\" with `terminal.ascii_drawing`"]
+    #[serde(alias = "encrypted-flag")]
+    #[serde(default)]
+    pub encrypted_flag: Option<Option<String>>,
+    #[doc = " Named searches to filter the listing with, with the `filter-preset` command or their key."]
+    #[doc = " Default: empty"]
+    #[serde(alias = "filter-presets")]
+    #[serde(default)]
+    pub filter_presets: Option<IndexMap<String, FilterPreset>>,
 }
 impl Default for ListingSettingsOverride {
     fn default() -> Self {
@@ -153,6 +275,22 @@ impl Default for ListingSettingsOverride {
             sidebar_mailbox_tree_has_sibling_leaf: None,
             sidebar_mailbox_tree_no_sibling_leaf: None,
             sidebar_divider: None,
+            sidebar_show_tags: None,
+            preview_pane: None,
+            preview_pane_orientation: None,
+            preview_pane_ratio: None,
+            show_size: None,
+            collation_locale: None,
+            date_separators: None,
+            attachment_flag: None,
+            snoozed_flag: None,
+            watched_flag: None,
+            note_flag: None,
+            replied_flag: None,
+            flagged_flag: None,
+            signed_flag: None,
+            encrypted_flag: None,
+            filter_presets: None,
         }
     }
 }
@@ -216,6 +354,17 @@ pub struct ShortcutsOverride {
     pub thread_view: Option<ThreadViewShortcuts>,
     #[serde(default)]
     pub pager: Option<PagerShortcuts>,
+    #[serde(default)]
+    pub stats: Option<StatsShortcuts>,
+    #[serde(alias = "mime-tree")]
+    #[serde(default)]
+    pub mime_tree: Option<MimeTreeShortcuts>,
+    #[serde(default)]
+    pub log: Option<LogShortcuts>,
+    #[serde(default)]
+    pub messages: Option<MessagesShortcuts>,
+    #[serde(default)]
+    pub activity: Option<ActivityShortcuts>,
 }
 impl Default for ShortcutsOverride {
     fn default() -> Self {
@@ -228,6 +377,11 @@ impl Default for ShortcutsOverride {
             envelope_view: None,
             thread_view: None,
             pager: None,
+            stats: None,
+            mime_tree: None,
+            log: None,
+            messages: None,
+            activity: None,
         }
     }
 }
@@ -280,6 +434,28 @@ pub struct ComposingSettingsOverride {
     #[doc = " Default: true"]
     #[serde(default)]
     pub attribution_use_posix_locale: Option<bool>,
+    #[doc = " Named reply templates, used by the bulk-reply-template command."]
+    #[doc = " The format specifiers for the replied envelope are:"]
+    #[doc = " - `%+f` — the sender's name and email address."]
+    #[doc = " - `%+n` — the sender's name (or email address, if no name is included)."]
+    #[doc = " - `%+a` — the sender's email address."]
+    #[doc = " - `%+s` — the subject."]
+    #[doc = " - `%+d` — the date."]
+    #[doc = " Default: empty"]
+    #[serde(alias = "reply-templates")]
+    #[serde(default)]
+    pub reply_templates: Option<HashMap<String, String>>,
+    #[doc = " The Content-Transfer-Encoding of the text of sent mail: \"auto\", \"8bit\","]
+    #[doc = " \"quoted-printable\" or \"base64\". \"auto\" sends 7bit text as is and encodes the rest."]
+    #[doc = " Default: \"auto\""]
+    #[serde(alias = "transfer-encoding")]
+    #[serde(default)]
+    pub transfer_encoding: Option<TransferEncoding>,
+    #[doc = " Write the body of new drafts in Markdown, and send it as a multipart/alternative of the"]
+    #[doc = " text and its rendering to HTML. Requires the `markdown` build feature."]
+    #[doc = " Default: false"]
+    #[serde(default)]
+    pub markdown: Option<bool>,
 }
 impl Default for ComposingSettingsOverride {
     fn default() -> Self {
@@ -293,6 +469,9 @@ impl Default for ComposingSettingsOverride {
             store_sent_mail: None,
             attribution_format_string: None,
             attribution_use_posix_locale: None,
+            reply_templates: None,
+            transfer_encoding: None,
+            markdown: None,
         }
     }
 }
@@ -362,6 +541,35 @@ pub struct PGPSettingsOverride {
     #[serde(alias = "remote-lookup-mechanisms")]
     #[serde(default)]
     pub remote_lookup_mechanisms: Option<melib::gpgme::LocateKey>,
+    #[doc = " Look up the keys of the recipients of new mail, and encrypt it when all of them have one."]
+    #[doc = " Keys are looked up in the keyring, with the remote lookup mechanisms if remote lookups are"]
+    #[doc = " allowed and in the `Autocrypt` headers of received mail."]
+    #[doc = " Default: true"]
+    #[serde(alias = "opportunistic-encryption")]
+    #[serde(default)]
+    pub opportunistic_encryption: Option<bool>,
+    #[doc = " Send the account's key in the `Autocrypt` header of sent mail, and encrypt new mail when"]
+    #[doc = " Autocrypt recommends it."]
+    #[doc = " Default: false"]
+    #[serde(default)]
+    pub autocrypt: Option<bool>,
+    #[doc = " Tell other Autocrypt clients to encrypt mail sent to this account."]
+    #[doc = " Default: false"]
+    #[serde(alias = "autocrypt-prefer-encrypt")]
+    #[serde(default)]
+    pub autocrypt_prefer_encrypt: Option<bool>,
+    #[doc = " Copy the headers of encrypted mail into its encrypted part, and replace its `Subject` with"]
+    #[doc = " \"...\" (\"protected headers\", as Thunderbird and Enigmail do)."]
+    #[doc = " Default: true"]
+    #[serde(alias = "protected-headers")]
+    #[serde(default)]
+    pub protected_headers: Option<bool>,
+    #[doc = " Overwrite the temporary files of decrypted attachments opened in external viewers with"]
+    #[doc = " zeros before deleting them."]
+    #[doc = " Default: false"]
+    #[serde(alias = "shred-temp-files")]
+    #[serde(default)]
+    pub shred_temp_files: Option<bool>,
 }
 #[cfg(feature = "gpgme")]
 impl Default for PGPSettingsOverride {
@@ -376,6 +584,11 @@ impl Default for PGPSettingsOverride {
             encrypt_key: None,
             allow_remote_lookup: None,
             remote_lookup_mechanisms: None,
+            opportunistic_encryption: None,
+            autocrypt: None,
+            autocrypt_prefer_encrypt: None,
+            protected_headers: None,
+            shred_temp_files: None,
         }
     }
 }
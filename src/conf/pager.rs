@@ -24,7 +24,9 @@
 use super::default_vals::*;
 use super::deserializers::*;
 use super::DotAddressable;
+use indexmap::IndexMap;
 use melib::{MeliError, Result, ToggleFlag};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Settings for the pager function.
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -87,6 +89,46 @@ pub struct PagerSettings {
         alias = "auto-choose-multipart-alternative"
     )]
     pub auto_choose_multipart_alternative: ToggleFlag,
+
+    /// Which part of `multipart/alternative` attachments to display by default, `text/plain` or
+    /// `text/html`.
+    /// Default: "text/plain"
+    #[serde(default, alias = "multipart-alternative-preference")]
+    pub multipart_alternative_preference: MultipartAlternativePreference,
+
+    /// A command to pass the events of `text/calendar` attachments to with the `export-ics`
+    /// command. `%s` is replaced by the path of a temporary `.ics` file; if it is missing the
+    /// events are piped to the command's standard input instead.
+    /// Default: None
+    #[serde(
+        default = "none",
+        deserialize_with = "non_empty_string",
+        alias = "ics-export-command"
+    )]
+    pub ics_export_command: Option<String>,
+
+    /// Show the dates of messages in the thread view and the Date header in the time zone of
+    /// their sender instead of the local one.
+    /// Default: false
+    #[serde(default = "false_val", alias = "show-sender-timezone")]
+    pub show_sender_timezone: bool,
+
+    /// Commands to open attachments with instead of the default application of their content
+    /// type, keyed by content type or a glob such as `image/*`. The first matching entry is used.
+    /// `%s` is replaced by the path of the attachment, which is otherwise appended to the command.
+    /// Default: empty
+    #[serde(default, alias = "attachment-openers")]
+    pub attachment_openers: IndexMap<String, String>,
+
+    /// A command to run attachment viewers in, such as `firejail --net=none`. The viewer and its
+    /// arguments are appended to it.
+    /// Default: None
+    #[serde(
+        default = "none",
+        deserialize_with = "non_empty_string",
+        alias = "attachment-sandbox"
+    )]
+    pub attachment_sandbox: Option<String>,
 }
 
 impl Default for PagerSettings {
@@ -102,6 +144,11 @@ impl Default for PagerSettings {
             split_long_lines: true,
             minimum_width: 80,
             auto_choose_multipart_alternative: ToggleFlag::InternalVal(true),
+            multipart_alternative_preference: MultipartAlternativePreference::Plain,
+            ics_export_command: None,
+            show_sender_timezone: false,
+            attachment_openers: IndexMap::default(),
+            attachment_sandbox: None,
         }
     }
 }
@@ -124,6 +171,13 @@ impl DotAddressable for PagerSettings {
                     "auto_choose_multipart_alternative" => {
                         self.auto_choose_multipart_alternative.lookup(field, tail)
                     }
+                    "multipart_alternative_preference" => {
+                        self.multipart_alternative_preference.lookup(field, tail)
+                    }
+                    "ics_export_command" => self.ics_export_command.lookup(field, tail),
+                    "show_sender_timezone" => self.show_sender_timezone.lookup(field, tail),
+                    "attachment_openers" => self.attachment_openers.lookup(field, tail),
+                    "attachment_sandbox" => self.attachment_sandbox.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -134,3 +188,58 @@ impl DotAddressable for PagerSettings {
         }
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MultipartAlternativePreference {
+    Plain,
+    Html,
+}
+
+impl Default for MultipartAlternativePreference {
+    fn default() -> Self {
+        MultipartAlternativePreference::Plain
+    }
+}
+
+impl MultipartAlternativePreference {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MultipartAlternativePreference::Plain => "text/plain",
+            MultipartAlternativePreference::Html => "text/html",
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MultipartAlternativePreference {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String>::deserialize(deserializer)?;
+        match s.as_str() {
+            plain
+                if plain.eq_ignore_ascii_case("text/plain")
+                    || plain.eq_ignore_ascii_case("plain") =>
+            {
+                Ok(MultipartAlternativePreference::Plain)
+            }
+            html if html.eq_ignore_ascii_case("text/html") || html.eq_ignore_ascii_case("html") => {
+                Ok(MultipartAlternativePreference::Html)
+            }
+            _ => Err(de::Error::custom(
+                "invalid `multipart_alternative_preference` value",
+            )),
+        }
+    }
+}
+
+impl Serialize for MultipartAlternativePreference {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.content_type())
+    }
+}
+
+impl DotAddressable for MultipartAlternativePreference {}
@@ -74,6 +74,36 @@ pub struct PGPSettings {
         alias = "remote-lookup-mechanisms"
     )]
     pub remote_lookup_mechanisms: melib::gpgme::LocateKey,
+
+    /// Look up the keys of the recipients of new mail, and encrypt it when all of them have one.
+    /// Keys are looked up in the keyring, with the remote lookup mechanisms if remote lookups are
+    /// allowed and in the `Autocrypt` headers of received mail.
+    /// Default: true
+    #[serde(default = "true_val", alias = "opportunistic-encryption")]
+    pub opportunistic_encryption: bool,
+
+    /// Send the account's key in the `Autocrypt` header of sent mail, and encrypt new mail when
+    /// Autocrypt recommends it.
+    /// Default: false
+    #[serde(default = "false_val")]
+    pub autocrypt: bool,
+
+    /// Tell other Autocrypt clients to encrypt mail sent to this account.
+    /// Default: false
+    #[serde(default = "false_val", alias = "autocrypt-prefer-encrypt")]
+    pub autocrypt_prefer_encrypt: bool,
+
+    /// Copy the headers of encrypted mail into its encrypted part, and replace its `Subject` with
+    /// "..." ("protected headers", as Thunderbird and Enigmail do).
+    /// Default: true
+    #[serde(default = "true_val", alias = "protected-headers")]
+    pub protected_headers: bool,
+
+    /// Overwrite the temporary files of decrypted attachments opened in external viewers with
+    /// zeros before deleting them.
+    /// Default: false
+    #[serde(default = "false_val", alias = "shred-temp-files")]
+    pub shred_temp_files: bool,
 }
 
 #[cfg(feature = "gpgme")]
@@ -94,6 +124,11 @@ impl Default for PGPSettings {
             encrypt_key: None,
             allow_remote_lookup: internal_value_false::<ToggleFlag>(),
             remote_lookup_mechanisms: default_lookup_mechanism(),
+            opportunistic_encryption: true,
+            autocrypt: false,
+            autocrypt_prefer_encrypt: false,
+            protected_headers: true,
+            shred_temp_files: false,
         }
     }
 }
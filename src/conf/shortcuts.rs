@@ -53,6 +53,16 @@ pub struct Shortcuts {
     pub thread_view: ThreadViewShortcuts,
     #[serde(default)]
     pub pager: PagerShortcuts,
+    #[serde(default)]
+    pub stats: StatsShortcuts,
+    #[serde(default, alias = "mime-tree")]
+    pub mime_tree: MimeTreeShortcuts,
+    #[serde(default)]
+    pub log: LogShortcuts,
+    #[serde(default)]
+    pub messages: MessagesShortcuts,
+    #[serde(default)]
+    pub activity: ActivityShortcuts,
 }
 
 impl Default for Shortcuts {
@@ -66,6 +76,11 @@ impl Default for Shortcuts {
             envelope_view: EnvelopeViewShortcuts::default(),
             thread_view: ThreadViewShortcuts::default(),
             pager: PagerShortcuts::default(),
+            stats: StatsShortcuts::default(),
+            mime_tree: MimeTreeShortcuts::default(),
+            log: LogShortcuts::default(),
+            messages: MessagesShortcuts::default(),
+            activity: ActivityShortcuts::default(),
         }
     }
 }
@@ -86,6 +101,11 @@ impl DotAddressable for Shortcuts {
                     "envelope_view" | "envelope-view" => self.envelope_view.lookup(field, tail),
                     "thread_view" | "thread-view" => self.thread_view.lookup(field, tail),
                     "pager" => self.pager.lookup(field, tail),
+                    "stats" => self.stats.lookup(field, tail),
+                    "mime_tree" | "mime-tree" => self.mime_tree.lookup(field, tail),
+                    "log" => self.log.lookup(field, tail),
+                    "messages" => self.messages.lookup(field, tail),
+                    "activity" => self.activity.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -174,16 +194,29 @@ shortcut_key_values! { "listing",
         next_mailbox |> "Go to next mailbox." |> Key::Char('J'),
         next_page |> "Go to next page." |> Key::PageDown,
         prev_account |> "Go to previous account." |> Key::Char('l'),
+        next_account_in_group |> "Go to the next account of the current account's group, wrapping around." |> Key::Alt('h'),
+        toggle_account_group |> "Collapse or expand the group of the account in the side menu." |> Key::Char('z'),
         prev_mailbox |> "Go to previous mailbox." |> Key::Char('K'),
         open_mailbox |> "Open selected mailbox" |> Key::Char('\n'),
         prev_page |> "Go to previous page." |> Key::PageUp,
         search |> "Search within list of e-mails." |> Key::Char('/'),
         refresh |> "Manually request a mailbox refresh." |> Key::F(5),
+        mailbox_properties |> "View and edit the properties of the mailbox." |> Key::Alt('p'),
         set_seen |> "Set thread as seen." |> Key::Char('n'),
+        first_unread |> "Go to the first entry with unread messages." |> Key::Char('U'),
+        next_unread |> "Go to the next entry with unread messages, wrapping around." |> Key::Char('\t'),
+        next_unread_mailbox |> "Go to the next mailbox with unread messages." |> Key::Alt('u'),
+        next_unread_all |> "Go to the next entry with unread messages in any mailbox of any account." |> Key::Alt('U'),
         union_modifier |> "Union modifier." |> Key::Ctrl('u'),
         diff_modifier |> "Difference modifier." |> Key::Ctrl('d'),
         intersection_modifier |> "Intersection modifier." |> Key::Ctrl('i'),
         select_entry |> "Select thread entry." |> Key::Char('v'),
+        visual_mode |> "Select the entries between the cursor and where it was when visual mode started." |> Key::Char('V'),
+        toggle_preview_pane |> "Toggle showing the opened entry next to the list." |> Key::Char('P'),
+        increase_preview_pane |> "Widen the preview pane." |> Key::Char('}'),
+        decrease_preview_pane |> "Narrow the preview pane." |> Key::Char('{'),
+        increase_sidebar |> "Widen the side menu." |> Key::Char(']'),
+        decrease_sidebar |> "Narrow the side menu." |> Key::Char('['),
         toggle_menu_visibility |> "Toggle visibility of side menu in mail list." |> Key::Char('`')
     }
 }
@@ -208,17 +241,19 @@ shortcut_key_values! { "pager",
         page_down |> "Go to next pager page" |>  Key::PageDown,
         page_up |> "Go to previous pager page" |>  Key::PageUp,
         scroll_down |> "Scroll down pager." |> Key::Char('j'),
-        scroll_up |> "Scroll up pager." |> Key::Char('k')
+        scroll_up |> "Scroll up pager." |> Key::Char('k'),
+        toggle_wrap |> "Toggle wrapping long lines, to scroll them horizontally instead." |> Key::Char('w')
     }
 }
 
 shortcut_key_values! { "general",
     pub struct GeneralShortcuts {
-        toggle_help |> "Toggle help and shortcuts view." |> Key::Char('?'),
+        toggle_help |> "Toggle help: the shortcuts and commands of the current view." |> Key::Char('?'),
         enter_command_mode |> "Enter COMMAND mode." |> Key::Char(':'),
         quit |> "Quit meli." |> Key::Char('q'),
         go_to_tab |> "Go to the nth tab" |> Key::Alt('n'),
         next_tab |> "Next tab." |> Key::Char('T'),
+        toggle_zoom |> "Maximize the focused pane, or restore the layout." |> Key::Char('Z'),
         scroll_right |> "Generic scroll right (catch-all setting)" |> Key::Right,
         scroll_left |> "Generic scroll left (catch-all setting)" |> Key::Left,
         scroll_up |> "Generic scroll up (catch-all setting)" |> Key::Up,
@@ -230,6 +265,8 @@ shortcut_key_values! { "composing",
     pub struct ComposingShortcuts {
         edit_mail |> "Edit mail." |> Key::Char('e'),
         send_mail |> "Deliver draft to mailer" |> Key::Char('s'),
+        toggle_markdown_preview |> "Toggle previewing the HTML rendering of a Markdown draft." |> Key::Char('p'),
+        preview_mail |> "View the message as it will be sent." |> Key::Char('v'),
         scroll_up |> "Change field focus." |> Key::Up,
         scroll_down |> "Change field focus." |> Key::Down
     }
@@ -238,17 +275,26 @@ shortcut_key_values! { "composing",
 shortcut_key_values! { "envelope-view",
     pub struct EnvelopeViewShortcuts {
         add_addresses_to_contacts |> "Select addresses from envelope to add to contacts." |> Key::Char('c'),
+        address_actions |> "Select an address from the headers to compose to, add to contacts, search for or copy." |> Key::Char('@'),
+        cycle_alternative |> "Cycle through the parts of multipart/alternative attachments." |> Key::Char('A'),
         edit |> "Open envelope in composer." |> Key::Char('e'),
+        forward |> "Forward envelope inline." |> Key::Char('f'),
+        go_to_parent |> "Open the message this one replies to, from any mailbox, in a new tab." |> Key::Char('<'),
         go_to_url |> "Go to url of given index" |> Key::Char('g'),
-        open_attachment |> "Opens selected attachment with xdg-open." |> Key::Char('a'),
+        open_attachment |> "Opens selected attachment with its default application." |> Key::Char('a'),
+        list_replies |> "List the known replies to the envelope, from any mailbox, to open one in a new tab." |> Key::Char('>'),
         open_mailcap |> "Opens selected attachment according to its mailcap entry." |> Key::Char('m'),
         reply |> "Reply to envelope." |> Key::Char('R'),
         reply_to_author |> "Reply to author." |> Key::Ctrl('r'),
         reply_to_all |> "Reply to all/Reply to list/Follow up." |> Key::Ctrl('g'),
+        retry |> "Try to open the envelope again after it failed to load." |> Key::F(5),
         return_to_normal_view |> "Return to envelope if viewing raw source or attachment." |> Key::Char('r'),
         toggle_expand_headers |> "Expand extra headers (References and others)." |> Key::Char('h'),
+        toggle_note |> "Expand/collapse the private note of the envelope." |> Key::Char('N'),
         toggle_url_mode |> "Toggles url open mode." |> Key::Char('u'),
-        view_raw_source |> "View envelope source in a pager. (toggles between raw and decoded source)" |> Key::Alt('r')
+        view_mime_tree |> "View the MIME structure of the envelope as a collapsible tree." |> Key::Char('M'),
+        view_raw_source |> "View envelope source in a pager. (toggles between raw and decoded source)" |> Key::Alt('r'),
+        yank_url |> "Copy url of given index to the clipboard." |> Key::Char('y')
     }
 }
 
@@ -257,6 +303,10 @@ shortcut_key_values! { "thread-view",
         scroll_up |> "Scroll up list." |> Key::Up,
         scroll_down |> "Scroll down list." |> Key::Down,
         collapse_subtree |> "collapse thread branches" |> Key::Char('h'),
+        first_unread |> "go to the first unread message" |> Key::Char('U'),
+        next_unread |> "go to the next unread message, or the next unread thread in the listing" |> Key::Char('\t'),
+        next_search_match |> "go to the next message that matches the thread search" |> Key::Ctrl('n'),
+        prev_search_match |> "go to the previous message that matches the thread search" |> Key::Ctrl('p'),
         next_page |> "Go to next page." |> Key::PageDown,
         prev_page |> "Go to previous page." |> Key::PageUp,
         reverse_thread_order |> "reverse thread order" |> Key::Ctrl('r'),
@@ -264,3 +314,48 @@ shortcut_key_values! { "thread-view",
         toggle_threadview |> "toggle thread view visibility" |> Key::Char('t')
     }
 }
+
+shortcut_key_values! { "stats",
+    /// Shortcut listing for the account storage statistics view
+    pub struct StatsShortcuts {
+        next_sort_column |> "Sort mailboxes by next column." |> Key::Char('s'),
+        toggle_sort_order |> "Toggle ascending/descending sort order." |> Key::Char('o'),
+        refresh |> "Recompute statistics." |> Key::F(5)
+    }
+}
+
+shortcut_key_values! { "mime-tree",
+    /// Shortcut listing for the MIME structure tree of an envelope
+    pub struct MimeTreeShortcuts {
+        scroll_up |> "Select previous part." |> Key::Up,
+        scroll_down |> "Select next part." |> Key::Down,
+        toggle_collapse |> "Collapse/expand the children of the selected part." |> Key::Char(' '),
+        open_part |> "View the selected part, or open attached e-mails in a new tab." |> Key::Char('\n'),
+        save_part |> "Save the selected part in the current working directory." |> Key::Char('s')
+    }
+}
+
+shortcut_key_values! { "log",
+    /// Shortcut listing for the log viewer
+    pub struct LogShortcuts {
+        cycle_level |> "Show only messages up to the next level." |> Key::Char('l'),
+        search |> "Search messages." |> Key::Char('/'),
+        refresh |> "Show new messages." |> Key::F(5)
+    }
+}
+
+shortcut_key_values! { "activity",
+    /// Shortcut listing for the activity journal of an account
+    pub struct ActivityShortcuts {
+        cycle_source |> "Show all entries, only local ones or only remote ones." |> Key::Char('l'),
+        search |> "Search entries." |> Key::Char('/'),
+        refresh |> "Show new entries." |> Key::F(5)
+    }
+}
+
+shortcut_key_values! { "messages",
+    /// Shortcut listing for the status message history
+    pub struct MessagesShortcuts {
+        copy |> "Copy the selected message to the clipboard." |> Key::Char('y')
+    }
+}
@@ -47,11 +47,39 @@ pub struct TerminalSettings {
     pub window_title: Option<String>,
     #[serde(deserialize_with = "non_empty_string")]
     pub file_picker_command: Option<String>,
+    /// Command that reads text to put in the clipboard from its standard input, run in addition
+    /// to the OSC 52 escape sequence. If unset, `wl-copy` or `xclip` is used in graphical
+    /// sessions.
+    /// Default: None
+    #[serde(deserialize_with = "non_empty_string")]
+    pub clipboard_command: Option<String>,
     /// Choose between 30-something built in sequences (integers between 0-30) or define your own
     /// list of strings for the progress spinner animation.
     /// Default: 0
     #[serde(default)]
     pub progress_spinner_sequence: Option<ProgressSpinnerSequence>,
+    /// Automatically choose between `dark_theme` and `light_theme` at startup and on `SIGUSR1`.
+    /// Default: none
+    pub theme_auto_switch: ThemeAutoSwitch,
+    /// Theme to use when a dark theme is selected automatically.
+    /// Default: "dark"
+    pub dark_theme: String,
+    /// Theme to use when a light theme is selected automatically.
+    /// Default: "light"
+    pub light_theme: String,
+    /// Local hours `[start, end)` during which the light theme is used when `theme_auto_switch`
+    /// is "schedule".
+    /// Default: [7, 19]
+    pub light_theme_hours: (u8, u8),
+    /// Save the open tabs, the selected mailbox, its cursor, sort and filter on exit and restore
+    /// them on the next startup.
+    /// Default: false
+    pub restore_session: bool,
+    /// Time zone to show dates in, such as "Europe/Athens" or "UTC", instead of the one of the
+    /// environment.
+    /// Default: None
+    #[serde(deserialize_with = "non_empty_string")]
+    pub timezone: Option<String>,
 }
 
 impl Default for TerminalSettings {
@@ -65,7 +93,14 @@ impl Default for TerminalSettings {
             mouse_flag: Some("🖱️ ".to_string()),
             window_title: Some("meli".to_string()),
             file_picker_command: None,
+            clipboard_command: None,
             progress_spinner_sequence: None,
+            theme_auto_switch: ThemeAutoSwitch::None,
+            dark_theme: "dark".to_string(),
+            light_theme: "light".to_string(),
+            light_theme_hours: (7, 19),
+            restore_session: false,
+            timezone: None,
         }
     }
 }
@@ -80,8 +115,47 @@ impl TerminalSettings {
             && (self.use_color.is_false() || self.use_color.is_internal()))
             || (self.use_color.is_false() && !self.use_color.is_internal()))
     }
+
+    /// The theme that should be active according to `theme_auto_switch`, if it can be determined.
+    ///
+    /// In "background" mode this queries the terminal, so the input thread must not be running.
+    pub fn auto_theme(&self) -> Option<&str> {
+        let dark = match self.theme_auto_switch {
+            ThemeAutoSwitch::None => return None,
+            ThemeAutoSwitch::Background => crate::terminal::is_dark_color(
+                crate::terminal::query_background_color(std::time::Duration::from_millis(200))?,
+            ),
+            ThemeAutoSwitch::Schedule => {
+                let hour =
+                    melib::datetime::timestamp_to_string(melib::datetime::now(), Some("%H"), false)
+                        .parse::<u8>()
+                        .ok()?;
+                let (start, end) = self.light_theme_hours;
+                !(start <= hour && hour < end)
+            }
+        };
+        Some(if dark {
+            self.dark_theme.as_str()
+        } else {
+            self.light_theme.as_str()
+        })
+    }
+}
+
+/// How to automatically select a dark or light theme.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeAutoSwitch {
+    /// Always use `theme`.
+    None,
+    /// Query the terminal's background color.
+    Background,
+    /// Use `light_theme_hours`.
+    Schedule,
 }
 
+impl DotAddressable for ThemeAutoSwitch {}
+
 impl DotAddressable for TerminalSettings {
     fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
         match path.first() {
@@ -96,9 +170,16 @@ impl DotAddressable for TerminalSettings {
                     "mouse_flag" => self.mouse_flag.lookup(field, tail),
                     "window_title" => self.window_title.lookup(field, tail),
                     "file_picker_command" => self.file_picker_command.lookup(field, tail),
+                    "clipboard_command" => self.clipboard_command.lookup(field, tail),
                     "progress_spinner_sequence" => {
                         self.progress_spinner_sequence.lookup(field, tail)
                     }
+                    "theme_auto_switch" => self.theme_auto_switch.lookup(field, tail),
+                    "dark_theme" => self.dark_theme.lookup(field, tail),
+                    "light_theme" => self.light_theme.lookup(field, tail),
+                    "light_theme_hours" => self.light_theme_hours.lookup(field, tail),
+                    "restore_session" => self.restore_session.lookup(field, tail),
+                    "timezone" => self.timezone.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -304,6 +304,7 @@ const DEFAULT_KEYS: &[&str] = &[
     "mail.listing.attachment_flag",
     "mail.listing.thread_snooze_flag",
     "mail.listing.tag_default",
+    "mail.listing.date_separator",
     "pager.highlight_search",
     "pager.highlight_search_current",
 ];
@@ -1703,6 +1704,15 @@ impl Default for Themes {
                 attrs: Attr::BOLD
             }
         );
+        add!(
+            "mail.listing.date_separator",
+            light = {
+                attrs: Attr::BOLD | Attr::UNDERLINE
+            },
+            dark = {
+                attrs: Attr::BOLD | Attr::UNDERLINE
+            }
+        );
 
         add!("pager.highlight_search", light = { fg: Color::White, bg: Color::Byte(6) /* Teal */, attrs: Attr::BOLD }, dark = { fg: Color::White, bg: Color::Byte(6) /* Teal */, attrs: Attr::BOLD });
         add!("pager.highlight_search_current", light = { fg: Color::White, bg: Color::Byte(17) /* NavyBlue */, attrs: Attr::BOLD }, dark = { fg: Color::White, bg: Color::Byte(17) /* NavyBlue */, attrs: Attr::BOLD });
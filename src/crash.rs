@@ -0,0 +1,235 @@
+/*
+ * meli - crash.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Panic hook that restores the terminal and writes a crash report.
+ *
+ * The report is written to the state directory, `$XDG_STATE_HOME/meli/` (`~/.local/state/meli/`
+ * by default) unless `--data-dir` is given, and holds
+ * the panic message and backtrace, the kinds of the last UI events State received and the
+ * configuration with secrets redacted. Only the kind of each event is kept, since their payloads
+ * carry keystrokes typed into password prompts and the composer.
+ */
+
+use crate::conf::Settings;
+use crate::terminal::*;
+use crate::types::UIEvent;
+use melib::{log_target, ERROR};
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+
+/// How many of the most recent UI events are kept for the report.
+const RECENT_EVENTS: usize = 100;
+
+static EVENTS: Lazy<Mutex<VecDeque<&'static str>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_EVENTS)));
+static CONFIG_SUMMARY: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::new()));
+/// Terminal attributes before entering raw mode.
+static ORIGINAL_TERMIOS: Lazy<Mutex<Option<libc::termios>>> = Lazy::new(|| Mutex::new(None));
+
+/// Remember the kind of `event` for the crash report.
+pub fn record_event(event: &UIEvent) {
+    let mut events = EVENTS.lock().unwrap_or_else(|err| err.into_inner());
+    if events.len() == RECENT_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event_kind(event));
+}
+
+fn event_kind(event: &UIEvent) -> &'static str {
+    match event {
+        UIEvent::Input(_) => "Input",
+        UIEvent::CmdInput(_) => "CmdInput",
+        UIEvent::InsertInput(_) => "InsertInput",
+        UIEvent::EmbedInput(_) => "EmbedInput",
+        UIEvent::Resize => "Resize",
+        UIEvent::Fork(_) => "Fork",
+        UIEvent::ChangeMailbox(_) => "ChangeMailbox",
+        UIEvent::ChangeMode(_) => "ChangeMode",
+        UIEvent::Command(_) => "Command",
+        UIEvent::Notification(_, _, _) => "Notification",
+        UIEvent::Action(_) => "Action",
+        UIEvent::StatusEvent(_) => "StatusEvent",
+        UIEvent::MailboxUpdate(_) => "MailboxUpdate",
+        UIEvent::MailboxDelete(_) => "MailboxDelete",
+        UIEvent::MailboxCreate(_) => "MailboxCreate",
+        UIEvent::AccountStatusChange(_) => "AccountStatusChange",
+        UIEvent::ComponentKill(_) => "ComponentKill",
+        UIEvent::BackendEvent(_, _) => "BackendEvent",
+        UIEvent::StartupCheck(_) => "StartupCheck",
+        UIEvent::RefreshEvent(_) => "RefreshEvent",
+        UIEvent::EnvelopeUpdate(_) => "EnvelopeUpdate",
+        UIEvent::EnvelopeRename(_, _) => "EnvelopeRename",
+        UIEvent::EnvelopeRemove(_, _) => "EnvelopeRemove",
+        UIEvent::Contacts(_) => "Contacts",
+        UIEvent::Compose(_) => "Compose",
+        UIEvent::FinishedUIDialog(_, _) => "FinishedUIDialog",
+        UIEvent::Callback(_) => "Callback",
+        UIEvent::GlobalUIDialog(_) => "GlobalUIDialog",
+        UIEvent::Timer(_) => "Timer",
+        UIEvent::ConfigReload { .. } => "ConfigReload",
+        UIEvent::VisibilityChange(_) => "VisibilityChange",
+        UIEvent::ZoomChange(_) => "ZoomChange",
+    }
+}
+
+/// Keep a copy of `settings` for the crash report, with the values of keys that look like they
+/// hold secrets replaced.
+pub fn set_config_summary(settings: &Settings) {
+    let summary = match toml::Value::try_from(settings) {
+        Ok(mut value) => {
+            redact(&mut value);
+            toml::to_string_pretty(&value).unwrap_or_else(|err| err.to_string())
+        }
+        Err(err) => format!("Could not serialize configuration: {}", err),
+    };
+    *CONFIG_SUMMARY.lock().unwrap_or_else(|err| err.into_inner()) = summary;
+}
+
+fn redact(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(ref mut table) => {
+            for (key, value) in table.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if ["password", "secret", "token", "passphrase"]
+                    .iter()
+                    .any(|s| key.contains(s))
+                {
+                    *value = toml::Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        toml::Value::Array(ref mut array) => array.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Install the panic hook. Call this before the terminal is switched to raw mode, so that the
+/// original terminal attributes can be restored.
+pub fn install_panic_hook() {
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } == 0 {
+        *ORIGINAL_TERMIOS.lock().unwrap() = Some(termios);
+    }
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = report(info);
+        let path = write_report(&report);
+        /* Panics in other threads don't bring down the UI: the job executor catches them and
+         * other threads just end, so leave the terminal alone. */
+        if thread::current().name() != Some("main") {
+            log_target(
+                "crash",
+                match path {
+                    Ok(ref path) => format!(
+                        "Panic in thread {}, crash report written to {}",
+                        thread::current().name().unwrap_or("<unnamed>"),
+                        path.display()
+                    ),
+                    Err(err) => format!("Panic, could not write crash report: {}", err),
+                },
+                ERROR,
+            );
+            return;
+        }
+        restore_terminal();
+        default_hook(info);
+        match path {
+            Ok(path) => eprintln!(
+                "meli crashed. A crash report was written to {}\nPlease consider attaching it to a bug report.",
+                path.display()
+            ),
+            Err(err) => eprintln!("meli crashed. Could not write crash report: {}", err),
+        }
+    }));
+}
+
+/// Leave the alternate screen and raw mode, undoing what `State::switch_to_alternate_screen`
+/// set up.
+fn restore_terminal() {
+    let mut stdout = std::io::stdout();
+    let _ = write!(
+        stdout,
+        "{}{}{}{}{}{}",
+        termion::screen::ToMainScreen,
+        termion::cursor::Show,
+        RestoreWindowTitleIconFromStack,
+        BracketModeEnd,
+        DisableSGRMouse,
+        DisableMouse,
+    );
+    let _ = stdout.flush();
+    if let Some(termios) = ORIGINAL_TERMIOS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+    {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+        }
+    }
+}
+
+/// `info` is the panic hook's argument, its `Display` output holds the message and location.
+fn report(info: &dyn std::fmt::Display) -> String {
+    let mut ret = String::new();
+    let _ = writeln!(ret, "meli {} crash report", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        ret,
+        "Date: {}",
+        melib::datetime::timestamp_to_string(melib::datetime::now(), None, false)
+    );
+    let _ = writeln!(
+        ret,
+        "Thread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    );
+    let _ = writeln!(ret, "Panic: {}", info);
+    let _ = writeln!(ret, "\nBacktrace:\n{:?}", backtrace::Backtrace::new());
+    let _ = writeln!(ret, "Last UI events, oldest first:");
+    for event in EVENTS.lock().unwrap_or_else(|err| err.into_inner()).iter() {
+        let _ = writeln!(ret, "  {}", event);
+    }
+    let _ = writeln!(
+        ret,
+        "\nConfiguration, secrets redacted:\n{}",
+        CONFIG_SUMMARY.lock().unwrap_or_else(|err| err.into_inner())
+    );
+    ret
+}
+
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    let mut path = melib::paths::state_dir()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+    path.push(format!(
+        "crash-{}.txt",
+        melib::datetime::timestamp_to_string(melib::datetime::now(), Some("%Y%m%d-%H%M%S"), false)
+    ));
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -27,13 +27,14 @@
 //! ```
 
 use melib::error::Result;
+use melib::{log_fields, LoggingLevel::TRACE};
 use melib::smol;
 use std::collections::HashMap;
 use std::future::Future;
 use std::panic::catch_unwind;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use crate::types::{ThreadEvent, UIEvent};
@@ -45,24 +46,43 @@ use std::iter;
 
 type AsyncTask = async_task::Task<()>;
 
-fn find_task<T>(local: &Worker<T>, global: &Injector<T>, stealers: &[Stealer<T>]) -> Option<T> {
+fn find_task<T>(
+    local: &Worker<T>,
+    global: &Injector<T>,
+    background: &Injector<T>,
+    stealers: &[Stealer<T>],
+) -> Option<T> {
     // Pop a task from the local queue, if not empty.
-    local.pop().or_else(|| {
-        // Otherwise, we need to look for a task elsewhere.
-        iter::repeat_with(|| {
-            // Try stealing a batch of tasks from the global queue.
-            global
-                .steal_batch_and_pop(local)
-                // Or try stealing a task from one of the other threads.
-                .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+    local
+        .pop()
+        .or_else(|| {
+            // Otherwise, we need to look for a task elsewhere.
+            iter::repeat_with(|| {
+                // Try stealing a batch of tasks from the global queue.
+                global
+                    .steal_batch_and_pop(local)
+                    // Or try stealing a task from one of the other threads.
+                    .or_else(|| stealers.iter().map(|s| s.steal()).collect())
+            })
+            // Loop while no task was stolen and any steal operation needs to be retried.
+            .find(|s| !s.is_retry())
+            // Extract the stolen task, if there is one.
+            .and_then(|s| s.success())
         })
-        // Loop while no task was stolen and any steal operation needs to be retried.
+        // Background tasks run one at a time, only when there's nothing else to do.
+        .or_else(|| steal_background(background))
+}
+
+fn steal_background<T>(background: &Injector<T>) -> Option<T> {
+    iter::repeat_with(|| background.steal())
         .find(|s| !s.is_retry())
-        // Extract the stolen task, if there is one.
         .and_then(|s| s.success())
-    })
 }
 
+/// A worker takes a background task, if any, after this many other tasks, so that periodic
+/// timers don't keep background tasks from ever running.
+const BACKGROUND_TURN: usize = 8;
+
 macro_rules! uuid_hash_type {
     ($n:ident) => {
         #[derive(
@@ -104,6 +124,8 @@ pub struct MeliTask {
 #[derive(Debug)]
 pub struct JobExecutor {
     global_queue: Arc<Injector<MeliTask>>,
+    /// Tasks that run only when `global_queue` and the workers' queues are empty.
+    background_queue: Arc<Injector<MeliTask>>,
     workers: Vec<Stealer<MeliTask>>,
     sender: Sender<ThreadEvent>,
     parkers: Vec<Unparker>,
@@ -156,6 +178,7 @@ impl JobExecutor {
         // Create a queue.
         let mut ret = JobExecutor {
             global_queue: Arc::new(Injector::new()),
+            background_queue: Arc::new(Injector::new()),
             workers: vec![],
             parkers: vec![],
             sender,
@@ -183,20 +206,50 @@ impl JobExecutor {
         // Spawn executor threads the first time the queue is created.
         for (i, (local, parker)) in workers.into_iter().enumerate() {
             let global = ret.global_queue.clone();
+            let background = ret.background_queue.clone();
             let stealers = ret.workers.clone();
             thread::Builder::new()
                 .name(format!("meli-executor-{}", i))
-                .spawn(move || loop {
-                    parker.park_timeout(Duration::from_millis(100));
-                    let task = find_task(&local, &global, stealers.as_slice());
-                    if let Some(meli_task) = task {
-                        let MeliTask { task, id, timer } = meli_task;
-                        if !timer {
-                            debug!("Worker {} got task {:?}", i, id);
+                .spawn(move || {
+                    let mut turns = 0;
+                    loop {
+                        parker.park_timeout(Duration::from_millis(100));
+                        let task = if turns >= BACKGROUND_TURN {
+                            turns = 0;
+                            steal_background(&background)
+                        } else {
+                            None
+                        }
+                        .or_else(|| find_task(&local, &global, &background, stealers.as_slice()));
+                        if task.is_some() {
+                            turns += 1;
                         }
-                        let _ = catch_unwind(|| task.run());
-                        if !timer {
-                            debug!("Worker {} returned after {:?}", i, id);
+                        if let Some(meli_task) = task {
+                            let MeliTask { task, id, timer } = meli_task;
+                            if !timer {
+                                debug!("Worker {} got task {:?}", i, id);
+                                log_fields(
+                                    "jobs",
+                                    TRACE,
+                                    "task started",
+                                    &[("worker", &i), ("id", &id)],
+                                );
+                            }
+                            let now = Instant::now();
+                            let _ = catch_unwind(|| task.run());
+                            if !timer {
+                                debug!("Worker {} returned after {:?}", i, id);
+                                log_fields(
+                                    "jobs",
+                                    TRACE,
+                                    "task returned",
+                                    &[
+                                        ("worker", &i),
+                                        ("id", &id),
+                                        ("elapsed_ms", &now.elapsed().as_millis()),
+                                    ],
+                                );
+                            }
                         }
                     }
                 })
@@ -207,6 +260,32 @@ impl JobExecutor {
 
     /// Spawns a future with a generic return value `R`
     pub fn spawn_specialized<F, R>(&self, future: F) -> JoinHandle<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_in(&self.global_queue, future)
+    }
+
+    /// Like `spawn_specialized`, but the future is only polled when no other task is waiting.
+    pub fn spawn_background<F, R>(&self, future: F) -> JoinHandle<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_in(&self.background_queue, future)
+    }
+
+    /// Like `spawn_blocking`, but the future is only started when no other task is waiting.
+    pub fn spawn_background_blocking<F, R>(&self, future: F) -> JoinHandle<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn_background(smol::unblock(move || futures::executor::block_on(future)))
+    }
+
+    fn spawn_in<F, R>(&self, queue: &Arc<Injector<MeliTask>>, future: F) -> JoinHandle<R>
     where
         F: Future<Output = R> + Send + 'static,
         R: Send + 'static,
@@ -214,7 +293,8 @@ impl JobExecutor {
         let (sender, receiver) = oneshot::channel();
         let finished_sender = self.sender.clone();
         let job_id = JobId::new();
-        let injector = self.global_queue.clone();
+        log_fields("jobs", TRACE, "job spawned", &[("id", &job_id)]);
+        let injector = queue.clone();
         // Create a task and schedule it for execution.
         let (task, handle) = async_task::spawn(
             async move {
@@ -384,3 +464,18 @@ impl Future for JoinHandle {
     }
 }
 */
+
+#[test]
+fn test_find_task_background_last() {
+    let local = Worker::new_fifo();
+    let global = Injector::new();
+    let background = Injector::new();
+    background.push("background");
+    global.push("global");
+    local.push("local");
+    let next = || find_task(&local, &global, &background, &[]);
+    assert_eq!(next(), Some("local"));
+    assert_eq!(next(), Some("global"));
+    assert_eq!(next(), Some("background"));
+    assert_eq!(next(), None);
+}
@@ -0,0 +1,129 @@
+/*
+ * meli - json_store.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Account state kept as JSON files in the account's state directory.
+ *
+ * A store that can't be read is loaded as empty but never saved over, so that a file damaged by
+ * hand or written by a newer version isn't lost; the user is told to fix or remove it instead.
+ * Saving replaces the file atomically.
+ */
+use melib::{MeliError, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct JsonStore {
+    path: Option<PathBuf>,
+    /// Why the existing file couldn't be loaded.
+    load_error: Option<String>,
+}
+
+impl JsonStore {
+    /// Load state file `name` of account `account_name`. A missing file is empty.
+    pub fn load<T: DeserializeOwned + Default>(account_name: &str, name: &str) -> (Self, T) {
+        match melib::paths::state_file(Some(account_name), name) {
+            Ok(path) => Self::load_path(path),
+            Err(err) => (
+                JsonStore {
+                    path: None,
+                    load_error: Some(err.to_string()),
+                },
+                T::default(),
+            ),
+        }
+    }
+
+    /// Load the store at `path`. A missing file is empty.
+    pub fn load_path<T: DeserializeOwned + Default>(path: PathBuf) -> (Self, T) {
+        let value = match fs::File::open(&path) {
+            Ok(f) => serde_json::from_reader(io::BufReader::new(f))
+                .map_err(|err| MeliError::new(err.to_string())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+            Err(err) => Err(err.into()),
+        };
+        match value {
+            Ok(value) => (
+                JsonStore {
+                    path: Some(path),
+                    load_error: None,
+                },
+                value,
+            ),
+            Err(err) => {
+                let load_error = format!(
+                    "Could not read {}: {}. Changes are not saved until it is fixed or removed.",
+                    path.display(),
+                    err
+                );
+                melib::log(&load_error, melib::ERROR);
+                (
+                    JsonStore {
+                        path: Some(path),
+                        load_error: Some(load_error),
+                    },
+                    T::default(),
+                )
+            }
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Replace the file with `value`. Fails without writing if the file couldn't be loaded.
+    pub fn save<T: Serialize>(&self, value: &T) -> Result<()> {
+        if let Some(ref err) = self.load_error {
+            return Err(MeliError::new(err.clone()));
+        }
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| MeliError::new("Could not locate the state directory."))?;
+        let contents = serde_json::to_vec(value).map_err(|err| MeliError::new(err.to_string()))?;
+        melib::paths::write_atomically(path, &contents)
+    }
+}
+
+#[test]
+fn test_json_store() {
+    let dir = std::env::temp_dir().join(format!("meli-json-store-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("store");
+
+    let (store, mut value): (_, Vec<String>) = JsonStore::load_path(path.clone());
+    assert!(value.is_empty());
+    value.push("a".to_string());
+    store.save(&value).unwrap();
+    let (store, value): (_, Vec<String>) = JsonStore::load_path(path.clone());
+    assert_eq!(value, vec!["a".to_string()]);
+    store.save(&value).unwrap();
+
+    /* A store that can't be parsed is not overwritten. */
+    fs::write(&path, "[\"a\",").unwrap();
+    let (store, value): (_, Vec<String>) = JsonStore::load_path(path.clone());
+    assert!(value.is_empty());
+    assert!(store.save(&value).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "[\"a\",");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,87 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Mailbox properties changed at runtime.
+ *
+ * The `ignore`, `notifications.enable`, `autoload` and `usage` settings of a mailbox can be
+ * edited from the UI. The edited values are kept in a JSON file in the account's data directory
+ * instead of the configuration file, and take precedence over the configuration when the
+ * account's mailboxes are loaded.
+ */
+use crate::conf::FileMailboxConf;
+use crate::json_store::JsonStore;
+use melib::backends::SpecialUsageMailbox;
+use melib::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MailboxProperties {
+    /// Don't notify of new messages.
+    pub ignore: bool,
+    /// Notify of new messages, unless `ignore` is set.
+    pub notify: bool,
+    /// Load the mailbox on start-up.
+    pub autoload: bool,
+    pub usage: SpecialUsageMailbox,
+}
+
+impl MailboxProperties {
+    /// Set `conf` to these properties.
+    pub fn apply(&self, conf: &mut FileMailboxConf) {
+        conf.mailbox_conf.ignore = self.ignore.into();
+        conf.conf_override.notifications.enable = Some(self.notify);
+        /* Unset doesn't load on startup either, but doesn't wait for the mailbox to be opened. */
+        if self.autoload != conf.mailbox_conf.autoload.is_true() {
+            conf.mailbox_conf.autoload = self.autoload.into();
+        }
+        conf.mailbox_conf.usage = Some(self.usage);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PropertyOverrides {
+    store: JsonStore,
+    /// Mailbox path to its edited properties.
+    mailboxes: HashMap<String, MailboxProperties>,
+}
+
+impl PropertyOverrides {
+    /// Load the edited properties of account `account_name`. A corrupt store is treated as empty
+    /// and isn't saved over.
+    pub fn new(account_name: &str) -> Self {
+        let (store, mailboxes) = JsonStore::load(account_name, "mailbox_properties");
+        PropertyOverrides { store, mailboxes }
+    }
+
+    pub fn get(&self, mailbox_path: &str) -> Option<&MailboxProperties> {
+        self.mailboxes.get(mailbox_path)
+    }
+
+    pub fn set(&mut self, mailbox_path: &str, properties: MailboxProperties) -> Result<()> {
+        self.mailboxes.insert(mailbox_path.to_string(), properties);
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.mailboxes)
+    }
+}
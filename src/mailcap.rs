@@ -154,30 +154,34 @@ impl MailcapEntry {
                  */
                 let args = args
                     .iter()
-                    .map(|arg| match *arg {
-                        "%s" => {
-                            needs_stdin = false;
-                            let _f = create_temp_file(&decode(a, None), None, None, true);
-                            let p = _f.path().display().to_string();
-                            f = Some(_f);
-                            p
-                        }
-                        "%t" => a.content_type().to_string(),
-                        param if param.starts_with("%{") && param.ends_with('}') => {
-                            let param = &param["%{".len()..param.len() - 1];
-                            if let Some(v) = params.iter().find(|(k, _)| *k == param.as_bytes()) {
-                                String::from_utf8_lossy(v.1).into()
-                            } else if param == "charset" {
-                                String::from("utf-8")
-                            } else {
-                                String::new()
+                    .map(|arg| -> Result<String> {
+                        Ok(match *arg {
+                            "%s" => {
+                                needs_stdin = false;
+                                let _f = create_temp_file(&decode(a, None), None, None, true)?;
+                                let p = _f.path().display().to_string();
+                                f = Some(_f);
+                                p
                             }
-                        }
-                        a => a.to_string(),
+                            "%t" => a.content_type().to_string(),
+                            param if param.starts_with("%{") && param.ends_with('}') => {
+                                let param = &param["%{".len()..param.len() - 1];
+                                if let Some(v) = params.iter().find(|(k, _)| *k == param.as_bytes())
+                                {
+                                    String::from_utf8_lossy(v.1).into()
+                                } else if param == "charset" {
+                                    String::from("utf-8")
+                                } else {
+                                    String::new()
+                                }
+                            }
+                            a => a.to_string(),
+                        })
                     })
-                    .collect::<Vec<String>>();
+                    .collect::<Result<Vec<String>>>()?;
                 let cmd_string = format!("{} {}", cmd, args.join(" "));
-                melib::log(
+                melib::log_target(
+                    "ui",
                     format!("Executing: sh -c \"{}\"", cmd_string.replace("\"", "\\\"")),
                     melib::DEBUG,
                 );
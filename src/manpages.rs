@@ -0,0 +1,78 @@
+/*
+ * meli - manpages.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! The manual pages, embedded when built with the `cli-docs` feature.
+ *
+ * `build.rs` renders them with `mandoc` and compresses them into `OUT_DIR`. They are read by the
+ * `man` subcommand and the `help` command.
+ */
+
+use melib::{MeliError, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// Choose manpage
+pub enum ManPages {
+    /// meli(1)
+    Main = 0,
+    /// meli.conf(5)
+    Conf = 1,
+    /// meli-themes(5)
+    Themes = 2,
+}
+
+pub fn parse_manpage(src: &str) -> Result<ManPages> {
+    match src {
+        "" | "meli" | "main" => Ok(ManPages::Main),
+        "meli.conf" | "conf" | "config" | "configuration" => Ok(ManPages::Conf),
+        "meli-themes" | "themes" | "theming" | "theme" => Ok(ManPages::Themes),
+        _ => Err(MeliError::new(format!(
+            "Invalid documentation page: {}",
+            src
+        ))),
+    }
+}
+
+/// The text of manual page `page`.
+#[cfg(feature = "cli-docs")]
+pub fn read(page: ManPages) -> Result<String> {
+    const MANPAGES: [&[u8]; 3] = [
+        include_bytes!(concat!(env!("OUT_DIR"), "/meli.txt.gz")),
+        include_bytes!(concat!(env!("OUT_DIR"), "/meli.conf.txt.gz")),
+        include_bytes!(concat!(env!("OUT_DIR"), "/meli-themes.txt.gz")),
+    ];
+    use flate2::bufread::GzDecoder;
+    use std::io::prelude::*;
+    let mut gz = GzDecoder::new(MANPAGES[page as usize]);
+    let mut v = String::with_capacity(
+        str::parse::<usize>(unsafe {
+            std::str::from_utf8_unchecked(gz.header().unwrap().comment().unwrap())
+        })
+        .unwrap_or_else(|_| panic!("{:?} was not compressed with size comment header", page)),
+    );
+    gz.read_to_string(&mut v)?;
+    Ok(v)
+}
+
+/// The text of manual page `page`.
+#[cfg(not(feature = "cli-docs"))]
+pub fn read(_page: ManPages) -> Result<String> {
+    Err(MeliError::new("error: this version of meli was not build with embedded documentation. You might have it installed as manpages (eg `man meli`), otherwise check https://meli.delivery"))
+}
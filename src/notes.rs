@@ -0,0 +1,84 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Private notes attached to messages.
+ *
+ * Notes never leave the local machine: they are kept in a JSON file in the account's data
+ * directory, keyed by the `Message-ID` of the message they refer to so that they survive
+ * moves between mailboxes and re-fetches.
+ */
+use crate::json_store::JsonStore;
+use melib::{Envelope, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Notes {
+    store: JsonStore,
+    notes: HashMap<String, String>,
+}
+
+impl Notes {
+    /// Load the notes of account `account_name`. A corrupt store is treated as empty and isn't
+    /// saved over.
+    pub fn new(account_name: &str) -> Self {
+        let (store, notes) = JsonStore::load(account_name, "notes");
+        Notes { store, notes }
+    }
+
+    pub fn get(&self, envelope: &Envelope) -> Option<&str> {
+        self.notes
+            .get(envelope.message_id_raw().as_ref())
+            .map(String::as_str)
+    }
+
+    pub fn contains(&self, envelope: &Envelope) -> bool {
+        self.notes.contains_key(envelope.message_id_raw().as_ref())
+    }
+
+    /// Case insensitive substring match of `needle` against the note of `envelope`.
+    pub fn is_match(&self, envelope: &Envelope, needle: &str) -> bool {
+        self.get(envelope)
+            .map(|n| n.to_lowercase().contains(&needle.to_lowercase()))
+            .unwrap_or(false)
+    }
+
+    pub fn set(&mut self, envelope: &Envelope, note: String) -> Result<()> {
+        self.notes
+            .insert(envelope.message_id_raw().into_owned(), note);
+        self.save()
+    }
+
+    pub fn remove(&mut self, envelope: &Envelope) -> Result<bool> {
+        if self
+            .notes
+            .remove(envelope.message_id_raw().as_ref())
+            .is_none()
+        {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.notes)
+    }
+}
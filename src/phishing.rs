@@ -0,0 +1,270 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Warnings about senders that may be impersonating someone.
+ *
+ * The checks are heuristics on the headers of a message: a display name that shows another
+ * address than the sender's, replies that go to another domain, and a sender domain that looks
+ * like the domain of a contact without being it. The user can dismiss the warnings of a sender;
+ * dismissed senders are kept in a JSON file in the account's data directory.
+ */
+use crate::json_store::JsonStore;
+use melib::{Address, Envelope, Result};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The display name of the sender contains this other address.
+    DisplayName(String),
+    /// Replies go to this address, on another domain than the sender's.
+    ReplyTo(String),
+    /// The domain of the sender looks like this domain of a contact.
+    Lookalike(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::DisplayName(address) => write!(
+                f,
+                "the sender's name shows {} but the message is from another address",
+                address
+            ),
+            Warning::ReplyTo(address) => write!(
+                f,
+                "replies go to {}, on another domain than the sender's",
+                address
+            ),
+            Warning::Lookalike(domain) => write!(
+                f,
+                "the sender's domain looks like {} of your contacts but is another one",
+                domain
+            ),
+        }
+    }
+}
+
+/// The warnings about the sender of `envelope`, whose domain is compared to the ones of the
+/// addresses of `contacts`.
+pub fn check<'a>(envelope: &Envelope, contacts: impl IntoIterator<Item = &'a str>) -> Vec<Warning> {
+    let mut ret = vec![];
+    let sender = match envelope.from().first() {
+        Some(sender) => sender,
+        None => return ret,
+    };
+    let email = sender.get_email().to_lowercase();
+    let domain = match domain_of(&email) {
+        Some(domain) => domain,
+        None => return ret,
+    };
+    if let Some(name) = sender.get_display_name() {
+        if let Some(address) = addresses_in(&name).find(|a| a.to_lowercase() != email) {
+            ret.push(Warning::DisplayName(address.to_string()));
+        }
+    }
+    /* Mailing lists send replies to themselves. */
+    let is_list = envelope.other_headers().contains_key("List-Id")
+        || envelope.other_headers().contains_key("List-Post");
+    if !is_list {
+        if let Some(reply_to) = envelope
+            .other_headers()
+            .get("Reply-To")
+            .and_then(|value| Address::list_try_from(value).ok())
+        {
+            for address in reply_to {
+                let reply_email = address.get_email().to_lowercase();
+                if let Some(reply_domain) = domain_of(&reply_email) {
+                    if organization(reply_domain) != organization(domain) {
+                        ret.push(Warning::ReplyTo(reply_email.clone()));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let contact_domains = contacts
+        .into_iter()
+        .filter_map(domain_of)
+        .map(str::to_lowercase)
+        .collect::<HashSet<String>>();
+    if !contact_domains.contains(domain) {
+        let sender_skeleton = skeleton(domain);
+        if let Some(lookalike) = contact_domains
+            .iter()
+            .find(|contact| skeleton(contact) == sender_skeleton)
+        {
+            ret.push(Warning::Lookalike(lookalike.clone()));
+        }
+    }
+    ret
+}
+
+fn domain_of(email: &str) -> Option<&str> {
+    let at = email.rfind('@')?;
+    Some(&email[at + 1..]).filter(|domain| domain.contains('.'))
+}
+
+/// The words of `text` that look like e-mail addresses.
+fn addresses_in(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c.is_whitespace() || "<>()[]\"',;:".contains(c))
+        .filter(|word| domain_of(word).is_some() && !word.starts_with('@'))
+}
+
+/// The domain an organization registered, going by its last two labels, or three for
+/// second-level domains such as `co.uk`.
+fn organization(domain: &str) -> &str {
+    let labels = domain.split('.').collect::<Vec<&str>>();
+    let n = if labels.len() > 2
+        && labels[labels.len() - 1].len() == 2
+        && labels[labels.len() - 2].len() <= 3
+    {
+        3
+    } else {
+        2
+    };
+    let skip = labels.len().saturating_sub(n);
+    let offset = labels[..skip].iter().map(|l| l.len() + 1).sum::<usize>();
+    &domain[offset..]
+}
+
+/// `domain` with the characters that can pass for others replaced by what they look like, so
+/// that lookalikes are equal.
+fn skeleton(domain: &str) -> String {
+    domain
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'а' | 'α' => 'a',
+            'с' | 'ϲ' => 'c',
+            'ԁ' => 'd',
+            'е' | 'ε' => 'e',
+            'ɡ' => 'g',
+            'һ' => 'h',
+            'і' | 'ı' | 'ι' | '1' | '|' | 'l' | 'ӏ' => 'i',
+            'ј' => 'j',
+            'κ' => 'k',
+            'ν' => 'v',
+            'о' | 'ο' | '0' => 'o',
+            'р' | 'ρ' => 'p',
+            'ѕ' => 's',
+            'τ' => 't',
+            'у' => 'y',
+            'х' | 'χ' => 'x',
+            c => c,
+        })
+        .collect::<String>()
+        .replace("rn", "m")
+        .replace("vv", "w")
+}
+
+/// Senders whose warnings the user dismissed.
+#[derive(Debug, Default)]
+pub struct Dismissals {
+    store: JsonStore,
+    senders: HashSet<String>,
+}
+
+impl Dismissals {
+    /// Load the dismissals of account `account_name`. A corrupt store is treated as empty and
+    /// isn't saved over.
+    pub fn new(account_name: &str) -> Self {
+        let (store, senders) = JsonStore::load(account_name, "dismissed_warnings");
+        Dismissals { store, senders }
+    }
+
+    pub fn contains(&self, envelope: &Envelope) -> bool {
+        sender(envelope)
+            .map(|sender| self.senders.contains(&sender))
+            .unwrap_or(false)
+    }
+
+    pub fn add(&mut self, envelope: &Envelope) -> Result<()> {
+        if let Some(sender) = sender(envelope) {
+            self.senders.insert(sender);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.senders)
+    }
+}
+
+fn sender(envelope: &Envelope) -> Option<String> {
+    envelope
+        .from()
+        .first()
+        .map(|address| address.get_email().to_lowercase())
+}
+
+#[test]
+fn test_phishing_check() {
+    fn envelope(headers: &str) -> Envelope {
+        Envelope::from_bytes(
+            format!("{}\r\nSubject: test\r\n\r\nbody\r\n", headers).as_bytes(),
+            None,
+        )
+        .unwrap()
+    }
+    let contacts = ["alice@bank.example.com", "bob@paypal.com"];
+
+    let e = envelope("From: \"support@bank.example.com\" <crook@evil.example>");
+    assert_eq!(
+        check(&e, contacts.iter().copied()),
+        vec![Warning::DisplayName("support@bank.example.com".to_string())]
+    );
+    /* The display name may repeat the sender's own address. */
+    let e = envelope("From: \"Alice (Alice@Bank.example.com)\" <alice@bank.example.com>");
+    assert!(check(&e, contacts.iter().copied()).is_empty());
+
+    let e = envelope("From: Bob <bob@paypal.com>\r\nReply-To: <refunds@paypa1-support.example>");
+    assert_eq!(
+        check(&e, contacts.iter().copied()),
+        vec![Warning::ReplyTo(
+            "refunds@paypa1-support.example".to_string()
+        )]
+    );
+    /* Another host of the same organization is fine, and so are mailing lists. */
+    let e = envelope("From: <a@mail.example.co.uk>\r\nReply-To: <b@lists.example.co.uk>");
+    assert!(check(&e, contacts.iter().copied()).is_empty());
+    let e = envelope(
+        "From: <a@example.com>\r\nReply-To: <list@lists.example.org>\r\nList-Id: <list.example.org>",
+    );
+    assert!(check(&e, contacts.iter().copied()).is_empty());
+
+    for lookalike in &["bob@paypa1.com", "bob@pаypal.com", "bob@PAYPAI.COM"] {
+        let e = envelope(&format!("From: <{}>", lookalike));
+        assert_eq!(
+            check(&e, contacts.iter().copied()),
+            vec![Warning::Lookalike("paypal.com".to_string())],
+            "{}",
+            lookalike
+        );
+    }
+    let e = envelope("From: <someone@rnicrosoft.com>");
+    assert_eq!(
+        check(&e, ["ceo@microsoft.com"].iter().copied()),
+        vec![Warning::Lookalike("microsoft.com".to_string())]
+    );
+    let e = envelope("From: <carol@paypal.com>");
+    assert!(check(&e, contacts.iter().copied()).is_empty());
+}
@@ -32,7 +32,8 @@ use std::sync::{Arc, Mutex, RwLock};
 // fields/interface/deserializing
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SimpleEnvelope {
-    hash: EnvelopeHash,
+    /// MessagePack integers are at most 64 bits wide.
+    hash: u64,
     subject: String,
     from: String,
     to: String,
@@ -70,6 +71,7 @@ impl MailBackend for PluginBackend {
             is_remote: false,
             supports_search: false,
             extensions: None,
+            actions: &[],
             supports_tags: false,
             supports_submission: false,
         };
@@ -122,7 +124,8 @@ impl MailBackend for PluginBackend {
                                          message_id,
                                          references,
                                      }| {
-                                        let mut env = melib::Envelope::new(hash);
+                                        let mut env =
+                                            melib::Envelope::new(EnvelopeHash::from(hash));
                                         env.set_date(date.as_bytes());
                                         if let Ok(d) =
                                             melib::email::parser::generic::date(date.as_bytes())
@@ -286,7 +289,7 @@ impl BackendOp for PluginOp {
             if let Ok(mut channel) = channel.try_lock() {
                 channel.write_ref(&rmpv::ValueRef::Ext(BACKEND_OP_FN, b"as_bytes"))?;
                 debug!(channel.expect_ack())?;
-                channel.write_ref(&rmpv::ValueRef::Integer(hash.into()))?;
+                channel.write_ref(&rmpv::ValueRef::Integer((hash as u64).into()))?;
                 debug!(channel.expect_ack())?;
                 let bytes: Result<PluginResult<String>> = channel.from_read();
                 Ok(bytes
@@ -0,0 +1,177 @@
+/*
+ * meli - power.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Background activity throttling on battery power.
+ *
+ * The power source is read from `/sys/class/power_supply`, or from `upower` where sysfs is not
+ * available. While throttled, polling intervals of accounts are stretched, mailboxes with
+ * `autoload` set are not fetched until they are opened and the UI pulse timer runs less often.
+ * The `power-saving` command overrides detection.
+ */
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How many times longer polling intervals are while throttled.
+const REFRESH_INTERVAL_FACTOR: u32 = 4;
+
+static THROTTLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether background activity is currently throttled.
+pub fn is_throttled() -> bool {
+    THROTTLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    Unknown,
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerSource::Ac => write!(f, "on AC power"),
+            PowerSource::Battery => write!(f, "on battery"),
+            PowerSource::Unknown => write!(f, "unknown power source"),
+        }
+    }
+}
+
+impl PowerSource {
+    pub fn detect() -> PowerSource {
+        match fs::read_dir("/sys/class/power_supply") {
+            Ok(entries) => {
+                let mut ret = PowerSource::Unknown;
+                for path in entries.flatten().map(|e| e.path()) {
+                    match (read(&path, "type").as_str(), read(&path, "online").as_str()) {
+                        ("Mains", "1") | ("USB", "1") => return PowerSource::Ac,
+                        /* Batteries of wireless mice and the like have device scope. */
+                        ("Battery", _) if read(&path, "scope") != "Device" => {
+                            if read(&path, "status") == "Discharging" {
+                                ret = PowerSource::Battery;
+                            } else if ret == PowerSource::Unknown {
+                                ret = PowerSource::Ac;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                ret
+            }
+            Err(_) => Self::upower(),
+        }
+    }
+
+    fn upower() -> PowerSource {
+        let output = match std::process::Command::new("upower")
+            .args(&["-i", "/org/freedesktop/UPower/devices/DisplayDevice"])
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(_) => return PowerSource::Unknown,
+        };
+        let output = String::from_utf8_lossy(&output.stdout);
+        match output
+            .lines()
+            .map(str::trim)
+            .find(|l| l.starts_with("state:"))
+            .map(|l| l["state:".len()..].trim())
+        {
+            Some("discharging") => PowerSource::Battery,
+            Some("charging") | Some("fully-charged") | Some("pending-charge") => PowerSource::Ac,
+            _ => PowerSource::Unknown,
+        }
+    }
+}
+
+fn read(dir: &Path, attribute: &str) -> String {
+    fs::read_to_string(dir.join(attribute))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub struct PowerState {
+    pub source: PowerSource,
+    /// Set with `power-saving on|off`, `None` follows the power source.
+    pub power_saving: Option<bool>,
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.power_saving {
+            None if self.is_throttled() => {
+                write!(f, "{}, background activity throttled", self.source)
+            }
+            None => write!(f, "{}", self.source),
+            Some(true) => write!(
+                f,
+                "{}, background activity throttled (power-saving on)",
+                self.source
+            ),
+            Some(false) => write!(f, "{}, not throttled (power-saving off)", self.source),
+        }
+    }
+}
+
+impl PowerState {
+    pub fn detect() -> Self {
+        let ret = PowerState {
+            source: PowerSource::detect(),
+            power_saving: None,
+        };
+        ret.apply();
+        ret
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.power_saving
+            .unwrap_or(self.source == PowerSource::Battery)
+    }
+
+    /// Detect the power source again. Returns whether throttling was turned on or off.
+    pub fn refresh(&mut self) -> bool {
+        self.source = PowerSource::detect();
+        self.apply()
+    }
+
+    /// Returns whether throttling was turned on or off.
+    pub fn set_power_saving(&mut self, value: Option<bool>) -> bool {
+        self.power_saving = value;
+        self.apply()
+    }
+
+    fn apply(&self) -> bool {
+        let throttled = self.is_throttled();
+        melib::backends::set_refresh_interval_factor(if throttled {
+            REFRESH_INTERVAL_FACTOR
+        } else {
+            1
+        });
+        THROTTLED.swap(throttled, Ordering::Relaxed) != throttled
+    }
+}
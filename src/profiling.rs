@@ -0,0 +1,117 @@
+/*
+ * meli - profiling.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Timing of start-up phases, enabled with `--profile-startup`.
+ *
+ * Phases are recorded only when profiling is enabled, and the report is printed to stderr on
+ * exit.
+ */
+
+use once_cell::sync::Lazy;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Phase {
+    name: String,
+    /// Time from start-up to the end of the phase.
+    at: Duration,
+    /// `None` for events that are only marked when they happen.
+    took: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Profile {
+    start: Instant,
+    phases: Vec<Phase>,
+}
+
+static PROFILE: Lazy<Mutex<Option<Profile>>> = Lazy::new(|| Mutex::new(None));
+
+fn profile() -> std::sync::MutexGuard<'static, Option<Profile>> {
+    PROFILE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Start recording. Phases are measured from this call.
+pub fn enable() {
+    *profile() = Some(Profile {
+        start: Instant::now(),
+        phases: vec![],
+    });
+}
+
+/// Run `f` and record how long it took as phase `name`.
+pub fn time<T>(name: impl Into<String>, f: impl FnOnce() -> T) -> T {
+    if profile().is_none() {
+        return f();
+    }
+    let before = Instant::now();
+    let ret = f();
+    if let Some(ref mut profile) = *profile() {
+        profile.phases.push(Phase {
+            name: name.into(),
+            at: profile.start.elapsed(),
+            took: Some(before.elapsed()),
+        });
+    }
+    ret
+}
+
+/// Record when `name` first happened. Later calls with the same name are ignored.
+pub fn mark_once(name: &str) {
+    if let Some(ref mut profile) = *profile() {
+        if profile.phases.iter().all(|p| p.name != name) {
+            profile.phases.push(Phase {
+                name: name.to_string(),
+                at: profile.start.elapsed(),
+                took: None,
+            });
+        }
+    }
+}
+
+/// The report of recorded phases, if profiling is enabled.
+pub fn report() -> Option<String> {
+    let profile = profile();
+    let profile = profile.as_ref()?;
+    let width = profile
+        .phases
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut ret = String::from("Start-up profile (milliseconds since start-up, duration):\n");
+    for phase in &profile.phases {
+        let _ = write!(
+            ret,
+            "  {:width$}  {:>9.1}",
+            phase.name,
+            phase.at.as_secs_f64() * 1000.0,
+            width = width
+        );
+        if let Some(took) = phase.took {
+            let _ = write!(ret, "  {:>9.1}", took.as_secs_f64() * 1000.0);
+        }
+        ret.push('\n');
+    }
+    Some(ret)
+}
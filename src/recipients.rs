@@ -0,0 +1,231 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! How often and how recently mail was sent to each address, to rank recipient suggestions.
+ *
+ * The index is built from the messages of the account's Sent mailbox as they are loaded, and kept
+ * in a JSON file in the account's data directory. Messages dated before the newest one indexed
+ * in an earlier session are not counted again.
+ */
+use crate::json_store::JsonStore;
+use melib::addressbook::AddressBook;
+use melib::email::{Address, Envelope};
+use melib::{EnvelopeHash, Result, UnixTimestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const DAY: UnixTimestamp = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Correspondence {
+    /// Number of messages sent to the address.
+    pub count: u64,
+    /// Date of the most recent one.
+    pub last_sent: UnixTimestamp,
+}
+
+impl Correspondence {
+    /// The message count weighted by how recently the last one was sent.
+    pub fn frecency(&self, now: UnixTimestamp) -> u64 {
+        let weight = match now.saturating_sub(self.last_sent) / DAY {
+            0..=3 => 100,
+            4..=14 => 70,
+            15..=31 => 50,
+            32..=90 => 30,
+            _ => 10,
+        };
+        self.count * weight
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    /// Date of the newest message indexed.
+    indexed_until: UnixTimestamp,
+    /// Lowercase address to the mail sent to it.
+    recipients: HashMap<String, Correspondence>,
+}
+
+#[derive(Debug, Default)]
+pub struct RecipientHistory {
+    store: JsonStore,
+    index: Index,
+    /// Messages dated up to this were indexed in an earlier session.
+    indexed_before: UnixTimestamp,
+    /// Messages indexed in this session, which are loaded again when the mailbox is refreshed.
+    indexed: HashSet<EnvelopeHash>,
+}
+
+impl RecipientHistory {
+    /// Load the index of account `account_name`. A corrupt index is treated as empty and isn't
+    /// saved over.
+    pub fn new(account_name: &str) -> Self {
+        let (store, index) = JsonStore::load(account_name, "recipient_history");
+        Self::with_store(store, index)
+    }
+
+    fn with_store(store: JsonStore, index: Index) -> Self {
+        RecipientHistory {
+            store,
+            indexed_before: index.indexed_until,
+            index,
+            indexed: HashSet::default(),
+        }
+    }
+
+    pub fn get(&self, address: &str) -> Option<&Correspondence> {
+        self.index.recipients.get(&address.to_lowercase())
+    }
+
+    /// The highest frecency of the addresses in the address list `addresses`, or 0 if there are
+    /// none or it can't be parsed.
+    pub fn score(&self, addresses: &str, now: UnixTimestamp) -> u64 {
+        Address::list_try_from(addresses)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|a| self.get(&a.get_email()))
+            .map(|c| c.frecency(now))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sort the `suggestions` of `book`, addresses and aliases, with the most written to first.
+    /// Suggestions never written to keep their order.
+    pub fn rank(&self, book: &AddressBook, suggestions: &mut [String]) {
+        let now = melib::datetime::now();
+        suggestions.sort_by_cached_key(|s| {
+            let addresses = book.alias(s).map(|a| a.join(", "));
+            std::cmp::Reverse(self.score(addresses.as_deref().unwrap_or(s.as_str()), now))
+        });
+    }
+
+    /// Count the recipients of `envelopes`, messages of the Sent mailbox. Returns whether the
+    /// index changed.
+    pub fn index<'a>(&mut self, envelopes: impl IntoIterator<Item = &'a Envelope>) -> Result<bool> {
+        let now = melib::datetime::now();
+        let mut changed = false;
+        for envelope in envelopes {
+            /* Dates in the future would keep newer messages from being indexed. */
+            let date = std::cmp::min(envelope.date(), now);
+            if date <= self.indexed_before || !self.indexed.insert(envelope.hash()) {
+                continue;
+            }
+            let recipients = envelope
+                .to()
+                .iter()
+                .chain(envelope.cc().iter())
+                .chain(envelope.bcc().iter())
+                .map(|a| a.get_email().to_lowercase())
+                .collect::<HashSet<String>>();
+            for address in recipients {
+                let entry = self.index.recipients.entry(address).or_default();
+                entry.count += 1;
+                entry.last_sent = std::cmp::max(entry.last_sent, date);
+                changed = true;
+            }
+            self.index.indexed_until = std::cmp::max(self.index.indexed_until, date);
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.index)
+    }
+}
+
+#[test]
+fn test_recipient_history() {
+    let now = melib::datetime::now();
+    let sent = |to: &str, cc: &str, age: UnixTimestamp| {
+        let mut envelope = Envelope::from_bytes(
+            format!(
+                "From: me@example.com\nTo: {}\nCc: {}\nSubject: {} days ago\n\nhello\n",
+                to,
+                cc,
+                age / DAY
+            )
+            .as_bytes(),
+            None,
+        )
+        .unwrap();
+        envelope.set_datetime(now - age);
+        envelope
+    };
+    let mut history = RecipientHistory::default();
+    let old = sent("Bob <bob@example.com>", "carol@example.com", 100 * DAY);
+    /* Without a data directory the history is kept but can't be saved. */
+    assert!(history.index(vec![&old]).is_err());
+    let dir = std::env::temp_dir().join(format!("meli-recipients-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    history.store = JsonStore::load_path::<Index>(dir.join("recipient_history")).0;
+    let envelopes = vec![
+        sent("bob@example.com", "", 99 * DAY),
+        sent("Carol <Carol@example.com>", "carol@example.com", DAY),
+    ];
+    assert!(history.index(&envelopes).unwrap());
+    /* Messages loaded again aren't counted twice. */
+    assert!(!history.index(&envelopes).unwrap());
+    assert_eq!(
+        history.get("bob@example.com"),
+        Some(&Correspondence {
+            count: 2,
+            last_sent: now - 99 * DAY
+        })
+    );
+    assert_eq!(history.get("carol@example.com").unwrap().count, 2);
+    /* Carol was written to as often as Bob but more recently. */
+    assert!(
+        history.score("Carol <carol@example.com>", now) > history.score("bob@example.com", now)
+    );
+    assert_eq!(
+        history.score("bob@example.com, carol@example.com", now),
+        history.score("carol@example.com", now)
+    );
+    assert_eq!(history.score("dave@example.com", now), 0);
+    assert_eq!(history.score("not an address", now), 0);
+
+    let mut book = AddressBook::new("test".to_string());
+    /* Aliases rank as the addresses they expand to. */
+    book.add_alias("team".to_string(), vec!["carol@example.com".to_string()]);
+    let mut suggestions = vec![
+        "Dave <dave@example.com>".to_string(),
+        "Bob <bob@example.com>".to_string(),
+        "team".to_string(),
+    ];
+    history.rank(&book, &mut suggestions);
+    assert_eq!(
+        suggestions,
+        ["team", "Bob <bob@example.com>", "Dave <dave@example.com>"]
+    );
+
+    /* The next session only counts newer messages. */
+    let (store, index) = JsonStore::load_path(dir.join("recipient_history"));
+    let mut history = RecipientHistory::with_store(store, index);
+    assert!(!history.index(&envelopes).unwrap());
+    assert!(history
+        .index(vec![&sent("bob@example.com", "", 0)])
+        .unwrap());
+    assert_eq!(history.get("bob@example.com").unwrap().count, 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,248 @@
+/*
+ * meli - recorder.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Event recording with `--record-events` and replay with `--replay`.
+ *
+ * The main loop records each event it handles as a line of JSON with the milliseconds since
+ * start-up. Only user input is replayed: everything else is described for whoever reads the file
+ * and is caused again by the accounts and components of the fresh `State`, so a replay reproduces
+ * a recording when it runs with the same configuration and mail.
+ */
+
+use super::*;
+use once_cell::sync::Lazy;
+use std::io::{BufRead, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use termion::event::{MouseButton, MouseEvent};
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MouseButton")]
+enum MouseButtonDef {
+    Left,
+    Right,
+    Middle,
+    WheelUp,
+    WheelDown,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MouseEvent")]
+enum MouseEventDef {
+    Press(#[serde(with = "MouseButtonDef")] MouseButton, u16, u16),
+    Release(u16, u16),
+    Hold(u16, u16),
+}
+
+/* `Key`'s own serialization is for shortcuts in the configuration and can't express every key. */
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Key")]
+enum KeyDef {
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+    Insert,
+    F(u8),
+    Char(char),
+    Alt(char),
+    Ctrl(char),
+    Null,
+    Esc,
+    Mouse(#[serde(with = "MouseEventDef")] MouseEvent),
+    Paste(String),
+    ChordTimeout,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    /// Input as the input thread sent it.
+    Input {
+        #[serde(with = "KeyDef")]
+        key: Key,
+        raw: Vec<u8>,
+    },
+    /// Any other event of the main loop.
+    Event(String),
+    /// An event a component sent.
+    Reply(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    ms: u64,
+    #[serde(flatten)]
+    kind: Kind,
+}
+
+struct Recorder {
+    start: Instant,
+    file: LineWriter<std::fs::File>,
+}
+
+static RECORDER: Lazy<Mutex<Option<Recorder>>> = Lazy::new(|| Mutex::new(None));
+
+fn recorder() -> std::sync::MutexGuard<'static, Option<Recorder>> {
+    RECORDER.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Start recording to `path`, replacing its contents.
+pub fn enable(path: &Path) -> Result<()> {
+    let file = std::fs::File::create(path)
+        .chain_err_summary(|| format!("Could not create `{}`", path.display()))?;
+    *recorder() = Some(Recorder {
+        start: Instant::now(),
+        file: LineWriter::new(file),
+    });
+    Ok(())
+}
+
+fn record(kind: impl FnOnce() -> Kind) {
+    if let Some(ref mut recorder) = *recorder() {
+        let record = Record {
+            ms: recorder.start.elapsed().as_millis() as u64,
+            kind: kind(),
+        };
+        /* Lines are written as they come so that the file is complete after a crash. */
+        if let Ok(line) = serde_json::to_string(&record) {
+            if let Err(err) = writeln!(recorder.file, "{}", line) {
+                debug!("Could not record event: {}", err);
+            }
+        }
+    }
+}
+
+/// Record an event the main loop received.
+pub fn record_thread_event(event: &ThreadEvent) {
+    record(|| match event {
+        ThreadEvent::Input((key, raw)) => Kind::Input {
+            key: key.clone(),
+            raw: raw.clone(),
+        },
+        event => Kind::Event(format!("{:?}", event)),
+    });
+}
+
+/// Record a reply event of a component.
+pub fn record_reply(event: &UIEvent) {
+    record(|| Kind::Reply(format!("{:?}", event)));
+}
+
+/// Record a signal the main loop received.
+pub fn record_signal(signal: i32) {
+    record(|| Kind::Event(format!("Signal({})", signal)));
+}
+
+/// The input recorded in a file written with `--record-events`, with when it happened.
+pub struct Replay {
+    input: Vec<(Duration, Key, Vec<u8>)>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay> {
+        let file = std::fs::File::open(path)
+            .chain_err_summary(|| format!("Could not open `{}`", path.display()))?;
+        let mut input = vec![];
+        for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line).map_err(|err| {
+                MeliError::new(format!(
+                    "`{}` line {} is not a recorded event: {}",
+                    path.display(),
+                    i + 1,
+                    err
+                ))
+            })?;
+            if let Kind::Input { key, raw } = record.kind {
+                input.push((Duration::from_millis(record.ms), key, raw));
+            }
+        }
+        Ok(Replay { input })
+    }
+
+    /// Send the input to `sender` in a thread, waiting between events as long as the recording
+    /// did.
+    pub fn start(self, sender: crossbeam::channel::Sender<ThreadEvent>) {
+        std::thread::Builder::new()
+            .name("replay".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                for (at, key, raw) in self.input {
+                    if let Some(wait) = at.checked_sub(start.elapsed()) {
+                        std::thread::sleep(wait);
+                    }
+                    if sender.send(ThreadEvent::Input((key, raw))).is_err() {
+                        return;
+                    }
+                }
+            })
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_record_roundtrip() {
+    let keys = vec![
+        Key::Char('j'),
+        Key::Char('\n'),
+        Key::Ctrl('r'),
+        Key::F(5),
+        Key::Mouse(MouseEvent::Press(MouseButton::WheelUp, 3, 4)),
+        Key::Paste("pasted text".to_string()),
+        Key::ChordTimeout,
+    ];
+    for key in keys {
+        let line = serde_json::to_string(&Record {
+            ms: 42,
+            kind: Kind::Input {
+                key: key.clone(),
+                raw: vec![1, 2],
+            },
+        })
+        .unwrap();
+        let record: Record = serde_json::from_str(&line).unwrap();
+        assert_eq!(record.ms, 42);
+        match record.kind {
+            Kind::Input { key: k, raw } => {
+                assert_eq!(k, key);
+                assert_eq!(raw, vec![1, 2]);
+            }
+            _ => panic!("{}", line),
+        }
+    }
+    let line = serde_json::to_string(&Record {
+        ms: 7,
+        kind: Kind::Reply("Timer(1)".to_string()),
+    })
+    .unwrap();
+    assert_eq!(line, r#"{"ms":7,"reply":"Timer(1)"}"#);
+}
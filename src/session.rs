@@ -0,0 +1,313 @@
+/*
+ * meli - session.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! UI state saved on exit and restored on startup.
+ *
+ * Components add their state to a `Session` in `Component::save_session`, which is restored when
+ * `terminal.restore_session` is set. `ReadPositions` remembers where the user left off in each
+ * mailbox and thread. Accounts are saved by name, so that the state survives changes in the
+ * configuration order.
+ */
+
+use crate::components::{
+    AccountStats, ActivityViewer, Component, LogViewer, MailView, MessagesViewer,
+};
+use crate::conf::accounts::Account;
+use crate::state::Context;
+use indexmap::IndexMap;
+use melib::backends::MailboxHash;
+use melib::email::{EnvelopeHash, StrBuild};
+use melib::error::{MeliError, Result, ResultIntoMeliError};
+use melib::thread::{SortField, SortOrder};
+use melib::{log_target, ERROR};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The mailbox selected in the mail listing.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListingSession {
+    pub account: String,
+    pub mailbox: String,
+    /// Index of the entry under the cursor.
+    #[serde(default)]
+    pub cursor: usize,
+    #[serde(default)]
+    pub sort: Option<(SortField, SortOrder)>,
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Width of the sidebar, if it was resized.
+    #[serde(default)]
+    pub sidebar_width: Option<usize>,
+    /// Percentage of the listing taken by the preview pane, if it was resized.
+    #[serde(default)]
+    pub preview_pane_ratio: Option<usize>,
+}
+
+/// A tab that can be opened again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum SessionTab {
+    /// Mailboxes are saved by hash instead of path, since the account might not have fetched
+    /// its mailbox list yet when the tab is opened.
+    Envelope {
+        account: String,
+        mailbox: MailboxHash,
+        #[serde(with = "envelope_hash_string")]
+        envelope: EnvelopeHash,
+    },
+    AccountStats {
+        account: String,
+    },
+    Activity {
+        account: String,
+    },
+    Log,
+    Messages,
+}
+
+/// Envelope hashes are 128 bits wide, which JSON numbers can't hold, so they are saved as
+/// strings.
+mod envelope_hash_string {
+    use melib::email::EnvelopeHash;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        hash: &EnvelopeHash,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(hash)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<EnvelopeHash, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub listing: Option<ListingSession>,
+    /// Tabs other than the mail listing and contacts, in order.
+    #[serde(default)]
+    pub tabs: Vec<SessionTab>,
+}
+
+/// How many threads per account `ReadPositions` remembers.
+const MAX_THREADS: usize = 1000;
+
+/// The last selected thread of each mailbox and the last read message of each thread, by
+/// Message-ID.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadPositions {
+    accounts: HashMap<String, AccountReadPositions>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct AccountReadPositions {
+    /// Mailbox path to the Message-ID of a message in the selected thread.
+    #[serde(default)]
+    mailboxes: HashMap<String, String>,
+    /// Message-ID of the first message of a thread to the Message-ID of the last read message.
+    /// Least recently read threads come first.
+    #[serde(default)]
+    threads: IndexMap<String, String>,
+}
+
+fn data_file_path(name: &str) -> Result<PathBuf> {
+    melib::paths::state_file(None, name)
+}
+
+fn session_path() -> Result<PathBuf> {
+    data_file_path("session.json")
+}
+
+impl ReadPositions {
+    pub fn load() -> ReadPositions {
+        let res: Result<ReadPositions> = data_file_path("read_positions.json").and_then(|path| {
+            if !path.exists() {
+                return Ok(ReadPositions::default());
+            }
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)
+                .map_err(|err| MeliError::new(err.to_string()))
+                .chain_err_summary(|| format!("Could not parse `{}`", path.display()))
+        });
+        res.unwrap_or_else(|err| {
+            log_target(
+                "ui",
+                format!("Could not load read positions: {}", err),
+                ERROR,
+            );
+            ReadPositions::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = data_file_path("read_positions.json")?;
+        let contents =
+            serde_json::to_string(self).map_err(|err| MeliError::new(err.to_string()))?;
+        fs::write(&path, contents)
+            .chain_err_summary(|| format!("Could not write `{}`", path.display()))?;
+        Ok(())
+    }
+
+    /// The raw Message-ID of `env_hash`, as used for keys and values.
+    pub fn message_id(account: &Account, env_hash: EnvelopeHash) -> String {
+        String::from_utf8_lossy(account.collection.get_env(env_hash).message_id().raw())
+            .into_owned()
+    }
+
+    pub fn mailbox(&self, account: &str, mailbox_path: &str) -> Option<&str> {
+        self.accounts
+            .get(account)
+            .and_then(|a| a.mailboxes.get(mailbox_path))
+            .map(String::as_str)
+    }
+
+    pub fn set_mailbox(&mut self, account: &str, mailbox_path: &str, message_id: String) {
+        self.accounts
+            .entry(account.to_string())
+            .or_default()
+            .mailboxes
+            .insert(mailbox_path.to_string(), message_id);
+    }
+
+    pub fn thread(&self, account: &str, thread: &str) -> Option<&str> {
+        self.accounts
+            .get(account)
+            .and_then(|a| a.threads.get(thread))
+            .map(String::as_str)
+    }
+
+    pub fn set_thread(&mut self, account: &str, thread: String, message_id: String) {
+        let threads = &mut self
+            .accounts
+            .entry(account.to_string())
+            .or_default()
+            .threads;
+        threads.shift_remove(&thread);
+        threads.insert(thread, message_id);
+        if threads.len() > MAX_THREADS {
+            threads.shift_remove_index(0);
+        }
+    }
+}
+
+impl Session {
+    /// Read the session saved on the last exit, if any.
+    pub fn load() -> Option<Session> {
+        let res: Result<Option<Session>> = session_path().and_then(|path| {
+            if !path.exists() {
+                return Ok(None);
+            }
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)
+                .map(Some)
+                .map_err(|err| MeliError::new(err.to_string()))
+                .chain_err_summary(|| format!("Could not parse `{}`", path.display()))
+        });
+        match res {
+            Ok(session) => session,
+            Err(err) => {
+                log_target("ui", format!("Could not restore session: {}", err), ERROR);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = session_path()?;
+        let contents =
+            serde_json::to_string_pretty(self).map_err(|err| MeliError::new(err.to_string()))?;
+        fs::write(&path, contents)
+            .chain_err_summary(|| format!("Could not write `{}`", path.display()))?;
+        Ok(())
+    }
+
+    /// Create the saved tabs. Tabs whose account doesn't exist anymore are skipped.
+    pub fn open_tabs(&self, context: &mut Context) -> Vec<Box<dyn Component>> {
+        let mut ret: Vec<Box<dyn Component>> = vec![];
+        for tab in &self.tabs {
+            match tab {
+                SessionTab::Envelope {
+                    account,
+                    mailbox,
+                    envelope,
+                } => {
+                    let account_hash = match context.accounts.values().find(|a| a.name() == account)
+                    {
+                        Some(a) => a.hash(),
+                        None => continue,
+                    };
+                    ret.push(Box::new(MailView::new(
+                        (account_hash, *mailbox, *envelope),
+                        None,
+                        None,
+                        context,
+                    )));
+                }
+                SessionTab::AccountStats { account } => {
+                    if let Some(stats) = context
+                        .accounts
+                        .values()
+                        .position(|a| a.name() == account)
+                        .and_then(|account_pos| AccountStats::new(account_pos, context).ok())
+                    {
+                        ret.push(Box::new(stats));
+                    }
+                }
+                SessionTab::Activity { account } => {
+                    if let Some(account_pos) =
+                        context.accounts.values().position(|a| a.name() == account)
+                    {
+                        ret.push(Box::new(ActivityViewer::new(account_pos, context)));
+                    }
+                }
+                SessionTab::Log => {
+                    ret.push(Box::new(LogViewer::new(context)));
+                }
+                SessionTab::Messages => {
+                    ret.push(Box::new(MessagesViewer::new(context)));
+                }
+            }
+        }
+        ret
+    }
+}
+
+#[test]
+fn test_session_roundtrip() {
+    let session = Session {
+        listing: None,
+        tabs: vec![SessionTab::Envelope {
+            account: "test".to_string(),
+            mailbox: 1,
+            envelope: EnvelopeHash::max_value(),
+        }],
+    };
+    let json = serde_json::to_string_pretty(&session).unwrap();
+    assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session);
+}
@@ -29,7 +29,7 @@ use melib::search::{
 use melib::{
     backends::{MailBackend, ResultFuture},
     email::{Envelope, EnvelopeHash},
-    log,
+    log_target,
     sqlite3::{
         self as melib_sqlite3,
         rusqlite::{self, params},
@@ -62,7 +62,8 @@ init_script:Some( "CREATE TABLE IF NOT EXISTS envelopes (
                     flags            INTEGER NOT NULL,
                     has_attachments  BOOLEAN NOT NULL,
                     body_text        TEXT NOT NULL,
-                    timestamp        BLOB NOT NULL
+                    timestamp        BLOB NOT NULL,
+                    size             INTEGER NOT NULL
                    );
         CREATE TABLE IF NOT EXISTS folders (
                     id               INTEGER PRIMARY KEY,
@@ -109,11 +110,11 @@ CREATE TRIGGER IF NOT EXISTS envelopes_au AFTER UPDATE ON envelopes BEGIN
   INSERT INTO fts(fts, rowid, subject, body_text) VALUES('delete', old.id, old.subject, old.body_text);
   INSERT INTO fts(rowid, subject, body_text) VALUES (new.id, new.subject, new.body_text);
 END; "),
-version: 1,
+version: 3,
 };
 
 pub fn db_path() -> Result<PathBuf> {
-    melib_sqlite3::db_path(DB.name)
+    melib_sqlite3::db_path(DB.name, None)
 }
 
 //#[inline(always)]
@@ -160,8 +161,11 @@ pub async fn insert(
         .operation(envelope.hash())?
         .as_bytes()?;
 
-    let body = match op.await.map(|bytes| envelope.body_bytes(&bytes)) {
-        Ok(body) => body.text(),
+    let (body, size) = match op
+        .await
+        .map(|bytes| (envelope.body_bytes(&bytes), bytes.len()))
+    {
+        Ok((body, size)) => (body.text(), size),
         Err(err) => {
             debug!(
                 "{}",
@@ -171,7 +175,8 @@ pub async fn insert(
                     err.to_string()
                 )
             );
-            log(
+            log_target(
+                "sqlite3",
                 format!(
                     "Failed to open envelope {}: {}",
                     envelope.message_id_display(),
@@ -192,7 +197,8 @@ pub async fn insert(
             envelope.message_id_display(),
             err.to_string()
         );
-        log(
+        log_target(
+            "sqlite3",
             format!(
                 "Failed to insert envelope {}: {}",
                 envelope.message_id_display(),
@@ -215,9 +221,9 @@ pub async fn insert(
         x
     };
     if let Err(err) = conn.execute(
-            "INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, subject, message_id, in_reply_to, _references, flags, has_attachments, body_text, timestamp)
-              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
-              params![account_id, envelope.hash().to_be_bytes().to_vec(), envelope.date_as_str(), envelope.field_from_to_string(), envelope.field_to_to_string(), envelope.field_cc_to_string(), envelope.field_bcc_to_string(), envelope.subject().into_owned().trim_end_matches('\u{0}'), envelope.message_id_display().to_string(), envelope.in_reply_to_display().map(|f| f.to_string()).unwrap_or(String::new()), envelope.field_references_to_string(), i64::from(envelope.flags().bits()), if envelope.has_attachments() { 1 } else { 0 }, body, envelope.date().to_be_bytes().to_vec()],
+            "INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, subject, message_id, in_reply_to, _references, flags, has_attachments, body_text, timestamp, size)
+              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+              params![account_id, envelope.hash().to_be_bytes().to_vec(), envelope.date_as_str(), envelope.field_from_to_string(), envelope.field_to_to_string(), envelope.field_cc_to_string(), envelope.field_bcc_to_string(), envelope.subject().into_owned().trim_end_matches('\u{0}'), envelope.message_id_display().to_string(), envelope.in_reply_to_display().map(|f| f.to_string()).unwrap_or(String::new()), envelope.field_references_to_string(), i64::from(envelope.flags().bits()), if envelope.has_attachments() { 1 } else { 0 }, body, envelope.date().to_be_bytes().to_vec(), size as i64],
         )
             .map_err(|e| MeliError::new(e.to_string())) {
                 debug!(
@@ -225,7 +231,8 @@ pub async fn insert(
                         envelope.message_id_display(),
                         err.to_string()
                     );
-                log(
+                log_target(
+                    "sqlite3",
                     format!(
                         "Failed to insert envelope {}: {}",
                         envelope.message_id_display(),
@@ -258,7 +265,8 @@ pub fn remove(env_hash: EnvelopeHash) -> Result<()> {
             env_hash,
             err.to_string()
         );
-        log(
+        log_target(
+            "sqlite3",
             format!(
                 "Failed to remove envelope {}: {}",
                 env_hash,
@@ -271,6 +279,23 @@ pub fn remove(env_hash: EnvelopeHash) -> Result<()> {
     Ok(())
 }
 
+/// Whether a message with Message-ID `message_id` is in the index, in any account and mailbox.
+pub fn contains_message_id(message_id: &str) -> Result<bool> {
+    let db_path = db_path()?;
+    if !db_path.exists() {
+        return Err(MeliError::new(
+            "Database hasn't been initialised. Run `reindex` command",
+        ));
+    }
+
+    let conn = melib_sqlite3::open_db(db_path)?;
+    let mut stmt = conn
+        .prepare("SELECT 1 FROM envelopes WHERE message_id = ? LIMIT 1")
+        .map_err(|e| MeliError::new(e.to_string()))?;
+    stmt.exists(params![message_id])
+        .map_err(|e| MeliError::new(e.to_string()))
+}
+
 pub fn index(context: &mut crate::state::Context, account_index: usize) -> ResultFuture<()> {
     let account = &context.accounts[account_index];
     let (acc_name, acc_mutex, backend_mutex): (String, Arc<RwLock<_>>, Arc<_>) = (
@@ -326,9 +351,9 @@ pub fn index(context: &mut crate::state::Context, account_index: usize) -> Resul
                 let envelopes_lck = acc_mutex.read().unwrap();
                 if let Some(e) = envelopes_lck.get(&env_hash) {
                     let body = e.body_bytes(&bytes).text().replace('\0', "");
-                    conn.execute("INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, subject, message_id, in_reply_to, _references, flags, has_attachments, body_text, timestamp)
-              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
-              params![account_id, e.hash().to_be_bytes().to_vec(), e.date_as_str(), e.field_from_to_string(), e.field_to_to_string(), e.field_cc_to_string(), e.field_bcc_to_string(), e.subject().into_owned().trim_end_matches('\u{0}'), e.message_id_display().to_string(), e.in_reply_to_display().map(|f| f.to_string()).unwrap_or(String::new()), e.field_references_to_string(), i64::from(e.flags().bits()), if e.has_attachments() { 1 } else { 0 }, body, e.date().to_be_bytes().to_vec()],
+                    conn.execute("INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, subject, message_id, in_reply_to, _references, flags, has_attachments, body_text, timestamp, size)
+              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+              params![account_id, e.hash().to_be_bytes().to_vec(), e.date_as_str(), e.field_from_to_string(), e.field_to_to_string(), e.field_cc_to_string(), e.field_bcc_to_string(), e.subject().into_owned().trim_end_matches('\u{0}'), e.message_id_display().to_string(), e.in_reply_to_display().map(|f| f.to_string()).unwrap_or(String::new()), e.field_references_to_string(), i64::from(e.flags().bits()), if e.has_attachments() { 1 } else { 0 }, body, e.date().to_be_bytes().to_vec(), bytes.len() as i64],
                         ).chain_err_summary(|| format!( "Failed to insert envelope {}", e.message_id_display()))?;
                 }
             }
@@ -355,6 +380,7 @@ pub fn search(
     let sort_field = match debug!(sort_field) {
         SortField::Subject => "subject",
         SortField::Date => "timestamp",
+        SortField::From => "_from",
     };
 
     let sort_order = match debug!(sort_order) {
@@ -378,7 +404,7 @@ pub fn search(
         .query_map(rusqlite::NO_PARAMS, |row| Ok(row.get(0)?))
         .map_err(|e| MeliError::new(e.to_string()))?
         .map(|r: std::result::Result<Vec<u8>, rusqlite::Error>| {
-            Ok(u64::from_be_bytes(
+            Ok(u128::from_be_bytes(
                 r.map_err(|e| MeliError::new(e.to_string()))?
                     .as_slice()
                     .try_into()
@@ -488,6 +514,12 @@ pub fn query_to_sql(q: &Query) -> String {
             HasAttachment => {
                 s.push_str("has_attachments == 1 ");
             }
+            Larger(n) => {
+                s.push_str(&format!("size > {} ", n));
+            }
+            Smaller(n) => {
+                s.push_str(&format!("size < {} ", n));
+            }
             _ => {}
         }
     }
@@ -515,4 +547,8 @@ fn test_query_to_sql() {
                 .1
         )
     );
+    assert_eq!(
+        "(size > 1048576 ) AND (NOT (size < 1024 ) ) ",
+        &query_to_sql(&query().parse_complete("size:>1M and not size:<1k").unwrap().1)
+    );
 }
@@ -32,6 +32,7 @@ use super::*;
 //use crate::plugins::PluginManager;
 use melib::backends::{AccountHash, BackendEventConsumer};
 
+use crate::conf::accounts::StartupPriority;
 use crate::jobs::JobExecutor;
 use crossbeam::channel::{after, unbounded, Receiver, Sender};
 use indexmap::IndexMap;
@@ -152,6 +153,16 @@ impl InputHandler {
         self.tx.send(InputCommand::Kill).unwrap();
     }
 
+    /// Kill the input thread and wait until it has stopped reading from stdin, or until `timeout`
+    /// has passed.
+    fn kill_and_wait(&self, timeout: std::time::Duration) {
+        self.kill();
+        let start = std::time::Instant::now();
+        while self.control.upgrade().is_some() && start.elapsed() < timeout {
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
     fn check(&mut self) {
         match self.control.upgrade() {
             Some(_) => {}
@@ -180,6 +191,15 @@ pub struct Context {
     pub children: Vec<std::process::Child>,
 
     pub temp_files: Vec<File>,
+    /// Where the user left off in mailboxes and threads, saved on exit
+    pub read_positions: ReadPositions,
+    /// Numeric prefix typed in normal mode before the key or command being processed, eg `5` in
+    /// `5j`.
+    pub count: Option<usize>,
+    keep_count: bool,
+    /// Status messages and notifications shown so far, oldest first, for the `messages` command.
+    pub message_history: VecDeque<StatusMessage>,
+    pub power: crate::power::PowerState,
 }
 
 impl Context {
@@ -195,6 +215,11 @@ impl Context {
         self.input_thread.restore();
     }
 
+    /// Don't clear `count` after the current key, so that it also applies to the next one.
+    pub fn keep_count(&mut self) {
+        self.keep_count = true;
+    }
+
     pub fn is_online_idx(&mut self, account_pos: usize) -> Result<()> {
         let Context {
             ref mut accounts,
@@ -230,6 +255,11 @@ impl Context {
     }
 }
 
+/// Columns and rows below which only a message asking for a bigger terminal is drawn.
+const MIN_TERMINAL_SIZE: (usize, usize) = (30, 8);
+/// Larger counts are capped, so that components can add and multiply them without overflowing.
+const MAX_COUNT: usize = 99_999;
+
 /// A State object to manage and own components and components of the UI. `State` is responsible for
 /// managing the terminal and interfacing with `melib`
 pub struct State {
@@ -239,7 +269,15 @@ pub struct State {
     grid: CellBuffer,
     overlay_grid: CellBuffer,
     draw_rate_limit: RateLimit,
+    /// Applies mailbox retention policies once a day.
+    retention_timer: crate::jobs::Timer,
+    /// Refreshes account storage quotas every ten minutes.
+    quota_timer: crate::jobs::Timer,
+    /// Checks whether the machine is running on battery.
+    power_timer: crate::jobs::Timer,
     stdout: Option<StateStdout>,
+    /// Stands in for the terminal in headless mode, see `State::new_headless`.
+    screen: Option<CellBuffer>,
     mouse: bool,
     child: Option<ForkType>,
     draw_horizontal_segment_fn: fn(&mut CellBuffer, &mut StateStdout, usize, usize, usize) -> (),
@@ -258,12 +296,25 @@ pub struct State {
     display_messages_area: Area,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct DisplayMessage {
     timestamp: UnixTimestamp,
     msg: String,
 }
 
+/// How many status messages `Context::message_history` keeps.
+const MAX_MESSAGE_HISTORY: usize = 1000;
+
+/// A status message or notification, as remembered in `Context::message_history`.
+#[derive(Debug, Clone)]
+pub struct StatusMessage {
+    pub timestamp: UnixTimestamp,
+    /// `None` for plain status messages.
+    pub kind: Option<NotificationType>,
+    pub title: Option<String>,
+    pub text: String,
+}
+
 impl Drop for State {
     fn drop(&mut self) {
         // When done, restore the defaults to avoid messing with the terminal.
@@ -291,6 +342,27 @@ impl State {
         settings: Option<Settings>,
         sender: Sender<ThreadEvent>,
         receiver: Receiver<ThreadEvent>,
+    ) -> Result<Self> {
+        Self::init(settings, sender, receiver, None)
+    }
+
+    /// A `State` that draws into an in-memory grid of `size` instead of the terminal and doesn't
+    /// read stdin, for driving the UI in tests. See `crate::testing`.
+    #[cfg(test)]
+    pub fn new_headless(
+        settings: Settings,
+        size: (usize, usize),
+        sender: Sender<ThreadEvent>,
+        receiver: Receiver<ThreadEvent>,
+    ) -> Result<Self> {
+        Self::init(Some(settings), sender, receiver, Some(size))
+    }
+
+    fn init(
+        settings: Option<Settings>,
+        sender: Sender<ThreadEvent>,
+        receiver: Receiver<ThreadEvent>,
+        headless_size: Option<(usize, usize)>,
     ) -> Result<Self> {
         /*
          * Create async channel to block the input-thread if we need to fork and stop it from reading
@@ -320,12 +392,16 @@ impl State {
         }
         */
 
-        let termsize = termion::terminal_size()?;
-        let cols = termsize.0 as usize;
-        let rows = termsize.1 as usize;
+        let (cols, rows) = match headless_size {
+            Some(size) => size,
+            None => {
+                let termsize = termion::terminal_size()?;
+                (termsize.0 as usize, termsize.1 as usize)
+            }
+        };
 
         let job_executor = Arc::new(JobExecutor::new(sender.clone()));
-        let accounts = {
+        let accounts = crate::profiling::time("account init", || {
             settings
                 .accounts
                 .iter()
@@ -338,27 +414,48 @@ impl State {
                         hasher.write(n.as_bytes());
                         hasher.finish()
                     };
-                    Account::new(
-                        account_hash,
-                        n.to_string(),
-                        a_s.clone(),
-                        &backends,
-                        job_executor.clone(),
-                        sender.clone(),
-                        BackendEventConsumer::new(Arc::new(
-                            move |account_hash: AccountHash, ev: BackendEvent| {
-                                sender
-                                    .send(ThreadEvent::UIEvent(UIEvent::BackendEvent(
-                                        account_hash,
-                                        ev,
-                                    )))
-                                    .unwrap();
-                            },
-                        )),
-                    )
+                    crate::profiling::time(format!("account init: {}", n), || {
+                        Account::new(
+                            account_hash,
+                            n.to_string(),
+                            a_s.clone(),
+                            &backends,
+                            job_executor.clone(),
+                            sender.clone(),
+                            BackendEventConsumer::new(Arc::new(
+                                move |account_hash: AccountHash, ev: BackendEvent| {
+                                    sender
+                                        .send(ThreadEvent::UIEvent(UIEvent::BackendEvent(
+                                            account_hash,
+                                            ev,
+                                        )))
+                                        .unwrap();
+                                },
+                            )),
+                        )
+                    })
                 })
-                .collect::<Result<Vec<Account>>>()?
+                .collect::<Result<Vec<Account>>>()
+        })?;
+        /* Start with the account and mailbox the mail listing shows first. */
+        let current = if settings.terminal.restore_session {
+            crate::session::Session::load()
+                .and_then(|s| s.listing)
+                .filter(|l| accounts.iter().any(|acc| acc.name() == l.account))
+        } else {
+            None
         };
+        let mut accounts = accounts;
+        for (i, acc) in accounts.iter_mut().enumerate() {
+            let priority = match current {
+                Some(ref l) if l.account == acc.name() => {
+                    StartupPriority::Current(Some(l.mailbox.clone()))
+                }
+                None if i == 0 => StartupPriority::Current(None),
+                _ => StartupPriority::Background,
+            };
+            acc.start(priority);
+        }
         let accounts = accounts.into_iter().map(|acc| (acc.hash(), acc)).collect();
 
         let timer = {
@@ -376,6 +473,7 @@ impl State {
 
         timer.thread().unpark();
 
+        crate::crash::set_config_summary(&settings);
         let working = Arc::new(());
         let control = Arc::downgrade(&working);
         let bindings = settings.bindings.clone();
@@ -385,6 +483,8 @@ impl State {
             grid: CellBuffer::new(cols, rows, Cell::with_char(' ')),
             overlay_grid: CellBuffer::new(cols, rows, Cell::with_char(' ')),
             stdout: None,
+            screen: headless_size
+                .map(|(cols, rows)| CellBuffer::new(cols, rows, Cell::with_char(' '))),
             mouse: settings.terminal.use_mouse.is_true(),
             child: None,
             mode: UIMode::Normal,
@@ -392,6 +492,18 @@ impl State {
             overlay: Vec::new(),
             timer,
             draw_rate_limit: RateLimit::new(1, 3, job_executor.clone()),
+            retention_timer: job_executor.clone().create_timer(
+                std::time::Duration::from_secs(24 * 60 * 60),
+                std::time::Duration::from_secs(24 * 60 * 60),
+            ),
+            quota_timer: job_executor.clone().create_timer(
+                std::time::Duration::from_secs(10 * 60),
+                std::time::Duration::from_secs(10 * 60),
+            ),
+            power_timer: job_executor.clone().create_timer(
+                std::time::Duration::from_secs(60),
+                std::time::Duration::from_secs(60),
+            ),
             draw_horizontal_segment_fn: if settings.terminal.use_color() {
                 State::draw_horizontal_segment
             } else {
@@ -410,6 +522,11 @@ impl State {
                 dirty_areas: VecDeque::with_capacity(5),
                 replies: VecDeque::with_capacity(5),
                 temp_files: Vec::new(),
+                read_positions: ReadPositions::load(),
+                count: None,
+                keep_count: false,
+                message_history: VecDeque::new(),
+                power: crate::power::PowerState::detect(),
                 job_executor,
                 children: vec![],
 
@@ -430,8 +547,13 @@ impl State {
             s.grid.set_ascii_drawing(true);
             s.overlay_grid.set_ascii_drawing(true);
         }
+        if let Some(theme) = s.context.settings.terminal.auto_theme() {
+            s.context.settings.terminal.theme = theme.to_string();
+        }
 
-        s.switch_to_alternate_screen();
+        if s.screen.is_none() {
+            s.switch_to_alternate_screen();
+        }
         for i in 0..s.context.accounts.len() {
             if !s.context.accounts[i].backend_capabilities.is_remote {
                 s.context.accounts[i].watch();
@@ -443,7 +565,9 @@ impl State {
                 //)));
             }
         }
-        s.context.restore_input();
+        if s.screen.is_none() {
+            s.context.restore_input();
+        }
         Ok(s)
     }
 
@@ -486,6 +610,10 @@ impl State {
     /// Switch back to the terminal's main screen (The command line the user sees before opening
     /// the application)
     pub fn switch_to_main_screen(&mut self) {
+        if self.stdout.is_none() {
+            /* Already switched, or headless. */
+            return;
+        }
         let mouse = self.mouse;
         write!(
             self.stdout(),
@@ -534,6 +662,79 @@ impl State {
         self.flush();
     }
 
+    /// Remember status messages and notifications for the `messages` command.
+    fn record_message(&mut self, event: &UIEvent) {
+        let message = match event {
+            UIEvent::StatusEvent(StatusEvent::DisplayMessage(ref msg)) => StatusMessage {
+                timestamp: melib::datetime::now(),
+                kind: None,
+                title: None,
+                text: msg.clone(),
+            },
+            UIEvent::Notification(ref title, ref body, ref kind) => StatusMessage {
+                timestamp: melib::datetime::now(),
+                kind: *kind,
+                title: title.clone(),
+                text: body.clone(),
+            },
+            _ => return,
+        };
+        let history = &mut self.context.message_history;
+        if history.len() == MAX_MESSAGE_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(message);
+    }
+
+    /// Put `text` in the system clipboard.
+    ///
+    /// The OSC 52 escape sequence works in most terminal emulators, including over SSH, but
+    /// whether it is supported can't be detected. So `terminal.clipboard_command` is run as well,
+    /// or `wl-copy`/`xclip` in graphical sessions if it isn't set.
+    pub fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        if let Some(stdout) = self.stdout.as_mut() {
+            write!(
+                stdout,
+                "\x1b]52;c;{}\x07",
+                data_encoding::BASE64.encode(text.as_bytes())
+            )
+            .unwrap();
+        }
+        self.flush();
+        let (command, configured) = match self.context.settings.terminal.clipboard_command {
+            Some(ref command) => (command.as_str(), true),
+            None if env::var_os("WAYLAND_DISPLAY").is_some() => ("wl-copy", false),
+            None if env::var_os("DISPLAY").is_some() => ("xclip -selection clipboard", false),
+            None => return Ok(()),
+        };
+        let res: Result<std::process::Child> = (|| {
+            let mut child = std::process::Command::new("sh")
+                .args(&["-c", command])
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()?;
+            /* Close stdin afterwards, the tool waits for the end of input. */
+            child.stdin.take().unwrap().write_all(text.as_bytes())?;
+            Ok(child)
+        })();
+        match res {
+            Ok(child) => {
+                /* wl-copy and xclip keep running to serve the selection, don't wait for them. */
+                self.context.children.push(child);
+            }
+            Err(err) if configured => {
+                return Err(err
+                    .set_summary(format!("Could not run `{}`", command))
+                    .set_kind(melib::ErrorKind::External));
+            }
+            Err(err) => {
+                log_target("ui", format!("Could not run `{}`: {}", command, err), DEBUG);
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_mouse(&mut self, value: bool) {
         if let Some(stdout) = self.stdout.as_mut() {
             write!(
@@ -567,9 +768,99 @@ impl State {
         self.context.restore_input();
     }
 
+    /// Reload the configuration file, unless account settings have changed.
+    pub fn reload_configuration(&mut self) {
+        match Settings::new().and_then(|new_settings| {
+            let old_accounts = self
+                .context
+                .settings
+                .accounts
+                .keys()
+                .collect::<std::collections::HashSet<&String>>();
+            let new_accounts = new_settings
+                .accounts
+                .keys()
+                .collect::<std::collections::HashSet<&String>>();
+            if old_accounts != new_accounts {
+                return Err(
+                    "cannot reload account configuration changes; restart meli instead.".into(),
+                );
+            }
+            for (key, acc) in new_settings.accounts.iter() {
+                if toml::Value::try_from(&acc)
+                    != toml::Value::try_from(&self.context.settings.accounts[key])
+                {
+                    return Err(
+                        "cannot reload account configuration changes; restart meli instead.".into(),
+                    );
+                }
+            }
+            if toml::Value::try_from(&new_settings) == toml::Value::try_from(&self.context.settings)
+            {
+                return Err("No changes detected.".into());
+            }
+            Ok(new_settings)
+        }) {
+            Ok(new_settings) => {
+                crate::crash::set_config_summary(&new_settings);
+                let old_settings =
+                    Box::new(std::mem::replace(&mut self.context.settings, new_settings));
+                self.context
+                    .replies
+                    .push_back(UIEvent::ConfigReload { old_settings });
+                self.context.replies.push_back(UIEvent::Resize);
+                self.update_auto_theme();
+            }
+            Err(err) => {
+                self.context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Could not load configuration: {}",
+                        err
+                    ))));
+            }
+        }
+    }
+
+    /// On `SIGUSR1` select the dark or light theme again according to `terminal.theme_auto_switch`,
+    /// and redraw everything if it changed.
+    pub fn update_auto_theme(&mut self) {
+        let query_terminal = self.context.settings.terminal.theme_auto_switch
+            == crate::conf::terminal::ThemeAutoSwitch::Background;
+        if query_terminal {
+            /* The terminal's reply must not be consumed by the input thread. */
+            self.context
+                .input_thread
+                .kill_and_wait(std::time::Duration::from_millis(100));
+        }
+        let theme = self
+            .context
+            .settings
+            .terminal
+            .auto_theme()
+            .map(str::to_string);
+        if query_terminal {
+            self.context.restore_input();
+        }
+        match theme {
+            Some(theme) if theme != self.context.settings.terminal.theme => {
+                let old_settings = Box::new(self.context.settings.clone());
+                self.context.settings.terminal.theme = theme;
+                self.rcv_event(UIEvent::ConfigReload { old_settings });
+                self.update_size();
+                self.render();
+            }
+            _ => {}
+        }
+    }
+
     /// On `SIGWNICH` the `State` redraws itself according to the new terminal size.
     pub fn update_size(&mut self) {
-        let termsize = termion::terminal_size().ok();
+        let termsize = if self.screen.is_some() {
+            Some((self.cols as u16, self.rows as u16))
+        } else {
+            termion::terminal_size().ok()
+        };
         let termcols = termsize.map(|(w, _)| w);
         let termrows = termsize.map(|(_, h)| h);
         if termcols.unwrap_or(72) as usize != self.cols
@@ -582,13 +873,24 @@ impl State {
         }
         self.cols = termcols.unwrap_or(72) as usize;
         self.rows = termrows.unwrap_or(120) as usize;
-        if !self.grid.resize(self.cols, self.rows, None) {
-            panic!(
-                "Terminal size too big: ({} cols, {} rows)",
-                self.cols, self.rows
+        if self.cols * self.rows >= CellBuffer::MAX_SIZE {
+            /* Only draw as many rows as the grid can hold */
+            let rows = (CellBuffer::MAX_SIZE - 1) / self.cols;
+            log_target(
+                "ui",
+                format!(
+                    "Terminal size too big: ({} cols, {} rows), only {} rows will be drawn.",
+                    self.cols, self.rows, rows
+                ),
+                ERROR,
             );
+            self.rows = rows;
         }
+        let _ = self.grid.resize(self.cols, self.rows, None);
         let _ = self.overlay_grid.resize(self.cols, self.rows, None);
+        if let Some(screen) = self.screen.as_mut() {
+            let _ = screen.resize(self.cols, self.rows, None);
+        }
 
         self.rcv_event(UIEvent::Resize);
         self.display_messages_dirty = true;
@@ -601,7 +903,13 @@ impl State {
 
     /// Force a redraw for all dirty components.
     pub fn redraw(&mut self) {
-        if !self.draw_rate_limit.tick() {
+        /* Tests look at every frame. */
+        if self.screen.is_none() && !self.draw_rate_limit.tick() {
+            return;
+        }
+
+        if self.cols < MIN_TERMINAL_SIZE.0 || self.rows < MIN_TERMINAL_SIZE.1 {
+            self.draw_too_small();
             return;
         }
 
@@ -651,36 +959,18 @@ impl State {
                     continue;
                 }
                 if let Some((x_start, x_end)) = segment.take() {
-                    (self.draw_horizontal_segment_fn)(
-                        &mut self.grid,
-                        self.stdout.as_mut().unwrap(),
-                        x_start,
-                        x_end,
-                        y,
-                    );
+                    self.draw_segment(false, x_start, x_end, y);
                 }
                 match segment {
                     ref mut s @ None => {
                         *s = Some((*x_start, *x_end));
                     }
                     ref mut s @ Some(_) if s.unwrap().1 < *x_start => {
-                        (self.draw_horizontal_segment_fn)(
-                            &mut self.grid,
-                            self.stdout.as_mut().unwrap(),
-                            s.unwrap().0,
-                            s.unwrap().1,
-                            y,
-                        );
+                        self.draw_segment(false, s.unwrap().0, s.unwrap().1, y);
                         *s = Some((*x_start, *x_end));
                     }
                     ref mut s @ Some(_) if s.unwrap().1 < *x_end => {
-                        (self.draw_horizontal_segment_fn)(
-                            &mut self.grid,
-                            self.stdout.as_mut().unwrap(),
-                            s.unwrap().0,
-                            s.unwrap().1,
-                            y,
-                        );
+                        self.draw_segment(false, s.unwrap().0, s.unwrap().1, y);
                         *s = Some((s.unwrap().1, *x_end));
                     }
                     Some((_, ref mut x)) => {
@@ -689,31 +979,23 @@ impl State {
                 }
             }
             if let Some((x_start, x_end)) = segment {
-                (self.draw_horizontal_segment_fn)(
-                    &mut self.grid,
-                    self.stdout.as_mut().unwrap(),
-                    x_start,
-                    x_end,
-                    y,
-                );
+                self.draw_segment(false, x_start, x_end, y);
             }
         }
 
         if self.display_messages_dirty && self.display_messages_active {
-            if let Some(DisplayMessage {
-                ref timestamp,
-                ref msg,
-                ..
-            }) = self.display_messages.get(self.display_messages_pos)
+            if let Some(DisplayMessage { timestamp, msg }) = self
+                .display_messages
+                .get(self.display_messages_pos)
+                .cloned()
             {
                 if !self.display_messages_initialised {
                     {
                         /* Clear area previously occupied by floating notification box */
                         let displ_area = self.display_messages_area;
                         for y in get_y(upper_left!(displ_area))..=get_y(bottom_right!(displ_area)) {
-                            (self.draw_horizontal_segment_fn)(
-                                &mut self.grid,
-                                self.stdout.as_mut().unwrap(),
+                            self.draw_segment(
+                                false,
                                 get_x(upper_left!(displ_area)),
                                 get_x(bottom_right!(displ_area)),
                                 y,
@@ -751,7 +1033,7 @@ impl State {
                     }
                     let ((x, mut y), box_displ_area_bottom_right) = box_displ_area;
                     for line in msg_lines.into_iter().chain(Some(String::new())).chain(Some(
-                        melib::datetime::timestamp_to_string(*timestamp, None, false),
+                        melib::datetime::timestamp_to_string(timestamp, None, false),
                     )) {
                         write_string_to_grid(
                             &line,
@@ -787,9 +1069,8 @@ impl State {
                 for y in get_y(upper_left!(self.display_messages_area))
                     ..=get_y(bottom_right!(self.display_messages_area))
                 {
-                    (self.draw_horizontal_segment_fn)(
-                        &mut self.overlay_grid,
-                        self.stdout.as_mut().unwrap(),
+                    self.draw_segment(
+                        true,
                         get_x(upper_left!(self.display_messages_area)),
                         get_x(bottom_right!(self.display_messages_area)),
                         y,
@@ -801,9 +1082,8 @@ impl State {
             /* Clear area previously occupied by floating notification box */
             let displ_area = self.display_messages_area;
             for y in get_y(upper_left!(displ_area))..=get_y(bottom_right!(displ_area)) {
-                (self.draw_horizontal_segment_fn)(
-                    &mut self.grid,
-                    self.stdout.as_mut().unwrap(),
+                self.draw_segment(
+                    false,
                     get_x(upper_left!(displ_area)),
                     get_x(bottom_right!(displ_area)),
                     y,
@@ -836,9 +1116,8 @@ impl State {
                 .unwrap()
                 .draw(&mut self.overlay_grid, area, &mut self.context);
             for y in get_y(upper_left!(area))..=get_y(bottom_right!(area)) {
-                (self.draw_horizontal_segment_fn)(
-                    &mut self.overlay_grid,
-                    self.stdout.as_mut().unwrap(),
+                self.draw_segment(
+                    true,
                     get_x(upper_left!(area)),
                     get_x(bottom_right!(area)),
                     y,
@@ -848,6 +1127,61 @@ impl State {
         self.flush();
     }
 
+    /// Components aren't drawn in terminals smaller than `MIN_TERMINAL_SIZE`, they will be once the
+    /// terminal is resized.
+    fn draw_too_small(&mut self) {
+        self.context.dirty_areas.clear();
+        if self.cols == 0 || self.rows == 0 {
+            return;
+        }
+        let area = ((0, 0), (self.cols - 1, self.rows - 1));
+        self.grid.clear(None);
+        write_string_to_grid(
+            &format!(
+                "terminal too small (need {}x{})",
+                MIN_TERMINAL_SIZE.0, MIN_TERMINAL_SIZE.1
+            ),
+            &mut self.grid,
+            Color::Default,
+            Color::Default,
+            Attr::BOLD,
+            area,
+            Some(0),
+        );
+        for y in 0..self.rows {
+            self.draw_segment(false, 0, self.cols - 1, y);
+        }
+        self.flush();
+    }
+
+    /// Draw row `y` of `grid`, or of `overlay_grid` if `overlay` is set, from `x_start` to `x_end`
+    /// on the screen.
+    fn draw_segment(&mut self, overlay: bool, x_start: usize, x_end: usize, y: usize) {
+        let grid = if overlay {
+            &mut self.overlay_grid
+        } else {
+            &mut self.grid
+        };
+        if let Some(screen) = self.screen.as_mut() {
+            let area = ((x_start, y), (x_end, y));
+            copy_area(screen, grid, area, area);
+        } else {
+            (self.draw_horizontal_segment_fn)(
+                grid,
+                self.stdout.as_mut().unwrap(),
+                x_start,
+                x_end,
+                y,
+            );
+        }
+    }
+
+    /// What a terminal would show, in headless mode.
+    #[cfg(test)]
+    pub fn screen(&self) -> &CellBuffer {
+        self.screen.as_ref().unwrap()
+    }
+
     /// Draw only a specific `area` on the screen.
     fn draw_horizontal_segment(
         grid: &mut CellBuffer,
@@ -913,6 +1247,30 @@ impl State {
         }
     }
 
+    /// Pass a key press to the components according to the current mode. The main loop handles
+    /// the keys that quit and suspend meli.
+    pub fn process_input(&mut self, key: Key, raw: Vec<u8>) {
+        match self.mode {
+            UIMode::Normal if key == self.context.settings.shortcuts.general.enter_command_mode => {
+                self.mode = UIMode::Command;
+                self.rcv_event(UIEvent::ChangeMode(UIMode::Command));
+            }
+            UIMode::Normal => self.rcv_event(UIEvent::Input(key)),
+            UIMode::Insert if key == Key::Esc => {
+                self.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
+            }
+            UIMode::Insert => self.rcv_event(UIEvent::InsertInput(key)),
+            UIMode::Command if key == Key::Char('\n') => {
+                self.mode = UIMode::Normal;
+                self.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
+            }
+            UIMode::Command => self.rcv_event(UIEvent::CmdInput(key)),
+            UIMode::Embed => self.rcv_event(UIEvent::EmbedInput((key, raw))),
+            UIMode::Fork => return,
+        }
+        self.redraw();
+    }
+
     /// Draw the entire screen from scratch.
     pub fn render(&mut self) {
         self.update_size();
@@ -920,7 +1278,7 @@ impl State {
         let rows = self.rows;
         self.context
             .dirty_areas
-            .push_back(((0, 0), (cols - 1, rows - 1)));
+            .push_back(((0, 0), (cols.saturating_sub(1), rows.saturating_sub(1))));
 
         self.redraw();
     }
@@ -948,6 +1306,27 @@ impl State {
         components.iter_mut().all(|c| c.can_quit_cleanly(context))
     }
 
+    /// Save the open tabs and the listing state, see `terminal.restore_session`.
+    pub fn save_session(&self) {
+        let mut session = Session::default();
+        for c in self.components.iter() {
+            c.save_session(&mut session, &self.context);
+        }
+        if let Err(err) = session.save() {
+            log_target("ui", format!("Could not save session: {}", err), ERROR);
+        }
+    }
+
+    pub fn save_read_positions(&self) {
+        if let Err(err) = self.context.read_positions.save() {
+            log_target(
+                "ui",
+                format!("Could not save read positions: {}", err),
+                ERROR,
+            );
+        }
+    }
+
     pub fn register_component(&mut self, component: Box<dyn Component>) {
         self.components.push(component);
     }
@@ -965,6 +1344,31 @@ impl State {
                         env::var(key.as_str()).unwrap_or_else(|e| e.to_string()),
                     )));
             }
+            Mailbox(account_name, crate::command::actions::MailboxOperation::Properties(path)) => {
+                let result = self
+                    .context
+                    .accounts
+                    .iter()
+                    .find(|(_, a)| a.name() == account_name)
+                    .ok_or_else(|| {
+                        MeliError::new(format!("Account with name `{}` not found.", account_name))
+                    })
+                    .and_then(|(&account_hash, account)| {
+                        Ok((account_hash, account.mailbox_by_path(&path)?))
+                    });
+                match result {
+                    Ok((account_hash, mailbox_hash)) => {
+                        let dialog =
+                            MailboxPropertiesDialog::new(account_hash, mailbox_hash, &self.context);
+                        self.overlay.push(Box::new(dialog));
+                    }
+                    Err(err) => {
+                        self.context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                    }
+                }
+            }
             Mailbox(account_name, op) => {
                 if let Some(account) = self
                     .context
@@ -1055,6 +1459,339 @@ impl State {
                     Some(NotificationType::Error(ErrorKind::None)),
                 ));
             }
+            AccountAction(ref account_name, action @ EmptyTrash)
+            | AccountAction(ref account_name, action @ EmptyJunk) => {
+                let usage = if let EmptyTrash = action {
+                    SpecialUsageMailbox::Trash
+                } else {
+                    SpecialUsageMailbox::Junk
+                };
+                let account = if let Some(a) = self
+                    .context
+                    .accounts
+                    .values_mut()
+                    .find(|acc| acc.name() == account_name)
+                {
+                    a
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                };
+                match account
+                    .special_use_mailbox(usage)
+                    .ok_or_else(|| {
+                        MeliError::new(format!(
+                            "Account {} has no {} mailbox.",
+                            account_name, usage
+                        ))
+                    })
+                    .and_then(|mailbox_hash| account.delete_mailbox_messages(mailbox_hash, None))
+                {
+                    Ok(len) => {
+                        self.context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Deleting {} message{} from {} mailbox.",
+                                len,
+                                if len == 1 { "" } else { "s" },
+                                usage
+                            )),
+                        ));
+                    }
+                    Err(err) => {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some(format!("Could not empty {} mailbox", usage)),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+            }
+            AccountAction(ref account_name, Synchronize) => {
+                if let Some(account) = self
+                    .context
+                    .accounts
+                    .values_mut()
+                    .find(|acc| acc.name() == account_name)
+                {
+                    if let Err(err) = account.synchronize() {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some("Could not synchronize account".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                }
+            }
+            AccountAction(ref account_name, Stats) => {
+                if let Some(account_pos) = self
+                    .context
+                    .accounts
+                    .iter()
+                    .position(|(_, acc)| acc.name() == account_name)
+                {
+                    match AccountStats::new(account_pos, &self.context) {
+                        Ok(stats) => {
+                            self.context
+                                .replies
+                                .push_back(UIEvent::Action(Tab(New(Some(Box::new(stats))))));
+                        }
+                        Err(err) => {
+                            self.context.replies.push_back(UIEvent::Notification(
+                                Some("Could not compute account statistics".to_string()),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                        }
+                    }
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                }
+            }
+            AccountAction(ref account_name, Activity) => {
+                if let Some(account_pos) = self
+                    .context
+                    .accounts
+                    .iter()
+                    .position(|(_, acc)| acc.name() == account_name)
+                {
+                    self.context
+                        .replies
+                        .push_back(UIEvent::Action(Tab(New(Some(Box::new(
+                            ActivityViewer::new(account_pos, &self.context),
+                        ))))));
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                }
+            }
+            AccountAction(ref account_name, Doctor) => {
+                if let Some(account_pos) = self
+                    .context
+                    .accounts
+                    .iter()
+                    .position(|(_, acc)| acc.name() == account_name)
+                {
+                    match AccountDoctor::new(account_pos, &self.context) {
+                        Ok(doctor) => {
+                            self.context
+                                .replies
+                                .push_back(UIEvent::Action(Tab(New(Some(Box::new(doctor))))));
+                        }
+                        Err(err) => {
+                            self.context.replies.push_back(UIEvent::Notification(
+                                Some("Could not run connection checks".to_string()),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                        }
+                    }
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                }
+            }
+            AccountAction(ref account_name, Backend(ref action, ref args)) => {
+                let account = if let Some(a) = self
+                    .context
+                    .accounts
+                    .values_mut()
+                    .find(|acc| acc.name() == account_name)
+                {
+                    a
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                };
+                let actions = account.backend_capabilities.actions;
+                let list = if actions.is_empty() {
+                    "none".to_string()
+                } else {
+                    actions
+                        .iter()
+                        .map(|a| {
+                            std::iter::once(a.name)
+                                .chain(a.args.iter().cloned())
+                                .collect::<Vec<&str>>()
+                                .join(" ")
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                };
+                let spec = match action {
+                    None => {
+                        self.context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Actions of account {}: {}",
+                                account_name, list
+                            )),
+                        ));
+                        return;
+                    }
+                    Some(action) => {
+                        if let Some(spec) = actions.iter().find(|a| a.name == action) {
+                            spec
+                        } else {
+                            self.context.replies.push_back(UIEvent::Notification(
+                                None,
+                                format!(
+                                    "Account {} has no action `{}`. Available actions: {}",
+                                    account_name, action, list
+                                ),
+                                Some(NotificationType::Error(ErrorKind::None)),
+                            ));
+                            return;
+                        }
+                    }
+                };
+                if args.len() > spec.args.len() {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!(
+                            "`{}` takes {} argument{}.",
+                            spec.name,
+                            spec.args.len(),
+                            if spec.args.len() == 1 { "" } else { "s" }
+                        ),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                }
+                if args.len() < spec.args.len() {
+                    /* Ask for the next argument on the command line, which is filled in with
+                     * what has been given so far. */
+                    let quote = |s: &str| {
+                        if s.is_empty() || s.contains(' ') {
+                            format!("\"{}\"", s)
+                        } else {
+                            s.to_string()
+                        }
+                    };
+                    let mut input = format!("backend {} {} ", quote(account_name), spec.name);
+                    for arg in args {
+                        input.push_str(&quote(arg));
+                        input.push(' ');
+                    }
+                    self.context
+                        .replies
+                        .push_back(UIEvent::CmdInput(Key::Paste(input)));
+                    self.context
+                        .replies
+                        .push_back(UIEvent::ChangeMode(UIMode::Command));
+                    self.context.replies.push_back(UIEvent::StatusEvent(
+                        StatusEvent::DisplayMessage(format!(
+                            "{}: {}. Enter {}.",
+                            spec.name,
+                            spec.description,
+                            spec.args[args.len()]
+                        )),
+                    ));
+                    return;
+                }
+                if let Err(err) = account.backend_action(spec.name, args.clone()) {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        Some(format!("Could not run {}", spec.name)),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    ));
+                }
+            }
+            #[cfg(feature = "gpgme")]
+            AccountAction(ref account_name, AutocryptSetupMessage) => {
+                use melib::email::autocrypt;
+                let account_hash = if let Some(a) = self
+                    .context
+                    .accounts
+                    .values()
+                    .find(|acc| acc.name() == account_name)
+                {
+                    a.hash()
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                };
+                let context = &self.context;
+                let sign_key = account_settings!(context[account_hash].pgp.sign_key).clone();
+                let account = &mut self.context.accounts[&account_hash];
+                let address = account.settings.account.identity().to_string();
+                let setup_code = autocrypt::gen_setup_code();
+                let payload = crate::components::mail::pgp::autocrypt_setup_payload(
+                    address.clone(),
+                    sign_key,
+                    setup_code.clone(),
+                );
+                let sender = self.context.sender.clone();
+                let handle = account.job_executor.spawn_specialized(async move {
+                    let payload = payload.await?;
+                    sender
+                        .send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+                            Box::new(move |context| {
+                                let composer = Composer::with_autocrypt_setup_message(
+                                    account_hash,
+                                    autocrypt::setup_message(&address, payload),
+                                    context,
+                                );
+                                context
+                                    .replies
+                                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+                                context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(format!(
+                                        "Autocrypt Setup Code: {}. Write it down, it is needed \
+                                         to import the Setup Message.",
+                                        setup_code
+                                    )),
+                                ));
+                            }),
+                        ))))
+                        .unwrap();
+                    Ok(())
+                });
+                account.insert_job(
+                    handle.job_id,
+                    crate::conf::accounts::JobRequest::Generic {
+                        name: "Autocrypt Setup Message".into(),
+                        handle,
+                        on_finish: None,
+                        logging_level: melib::LoggingLevel::DEBUG,
+                    },
+                );
+            }
+            #[cfg(not(feature = "gpgme"))]
+            AccountAction(_, AutocryptSetupMessage) => {
+                self.context.replies.push_back(UIEvent::Notification(
+                    None,
+                    "Autocrypt Setup Message failed: meli is not built with gpgme support."
+                        .to_string(),
+                    Some(NotificationType::Error(ErrorKind::None)),
+                ));
+            }
             AccountAction(ref account_name, PrintAccountSetting(ref setting)) => {
                 let path = setting.split(".").collect::<SmallVec<[&str; 16]>>();
                 if let Some(pos) = self
@@ -1098,6 +1835,65 @@ impl State {
                 self.set_mouse(self.mouse);
                 self.rcv_event(UIEvent::StatusEvent(StatusEvent::SetMouse(self.mouse)));
             }
+            ReloadConfiguration => {
+                self.reload_configuration();
+            }
+            ViewLog => {
+                self.context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(LogViewer::new(
+                        &self.context,
+                    )))))));
+            }
+            ViewMessages => {
+                self.context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(
+                        MessagesViewer::new(&self.context),
+                    ))))));
+            }
+            Help(page) => {
+                self.context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(HelpViewer::new(
+                        page,
+                        &self.context,
+                    )))))));
+            }
+            CacheStats => {
+                let stats = melib::backends::ByteCache::stats();
+                let mut message = format!(
+                    "Message cache: {} of {} in {} messages, {} hits, {} misses, {} evictions.",
+                    melib::Bytes(stats.used),
+                    melib::Bytes(stats.budget),
+                    stats.messages,
+                    stats.hits,
+                    stats.misses,
+                    stats.evictions
+                );
+                for account in self.context.accounts.values() {
+                    if let Some((used, messages)) = stats.accounts.get(&account.hash()) {
+                        message.push_str(&format!(
+                            "\n{}: {} in {} messages",
+                            account.name(),
+                            melib::Bytes(*used),
+                            messages
+                        ));
+                    }
+                }
+                self.context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+            }
+            PowerSaving(value) => {
+                self.context.power.set_power_saving(value);
+                self.context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Power: {}.",
+                        self.context.power
+                    ))));
+            }
             Quit => {
                 self.context
                     .sender
@@ -1115,11 +1911,21 @@ impl State {
 
     /// The application's main loop sends `UIEvents` to state via this method.
     pub fn rcv_event(&mut self, mut event: UIEvent) {
+        crate::crash::record_event(&event);
+        self.record_message(&event);
         if let UIEvent::Input(_) = event {
             if self.display_messages_expiration_start.is_none() {
                 self.display_messages_expiration_start = Some(melib::datetime::now());
             }
         }
+        if self.mode == UIMode::Normal && self.count_input(&event) {
+            return;
+        }
+        /* The count applies to the next key, or to the command entered after it. */
+        let clear_count = matches!(
+            event,
+            UIEvent::Input(_) | UIEvent::ChangeMode(UIMode::Normal)
+        );
 
         match event {
             // Command type is handled only by State.
@@ -1138,39 +1944,6 @@ impl State {
                             })),
                             &mut self.context,
                         )));
-                    } else if let Action::ReloadConfiguration = action {
-                        match Settings::new().and_then(|new_settings| {
-                            let old_accounts = self.context.settings.accounts.keys().collect::<std::collections::HashSet<&String>>();
-                            let new_accounts = new_settings.accounts.keys().collect::<std::collections::HashSet<&String>>();
-                            if old_accounts != new_accounts {
-                                return Err("cannot reload account configuration changes; restart meli instead.".into());
-                            }
-                            for (key, acc) in new_settings.accounts.iter() {
-                                if toml::Value::try_from(&acc) != toml::Value::try_from(&self.context.settings.accounts[key]) {
-                                    return Err("cannot reload account configuration changes; restart meli instead.".into());
-                                }
-                            }
-                            if toml::Value::try_from(&new_settings) == toml::Value::try_from(&self.context.settings) {
-                                return Err("No changes detected.".into());
-                            }
-                            Ok(new_settings)
-                        }) {
-                            Ok(new_settings) => {
-                                let old_settings = std::mem::replace(&mut self.context.settings, new_settings);
-                                self.context.replies.push_back(UIEvent::ConfigReload {
-                                    old_settings
-                                });
-                                self.context.replies.push_back(UIEvent::Resize);
-                            }
-                            Err(err) => {
-                                self.context.replies.push_back(UIEvent::StatusEvent(
-                                        StatusEvent::DisplayMessage(format!(
-                                                "Could not load configuration: {}",
-                                                err
-                                        )),
-                                ));
-                            }
-                        }
                     } else {
                         self.exec_command(action);
                     }
@@ -1211,7 +1984,8 @@ impl State {
                     level,
                 },
             ) => {
-                log(
+                log_target(
+                    "ui",
                     format!(
                         "{}: {}{}{}",
                         self.context.accounts[&account_hash].name(),
@@ -1230,6 +2004,58 @@ impl State {
                 self.refresh_event(refresh_event);
                 return;
             }
+            UIEvent::BackendEvent(account_hash, BackendEvent::ConnectionStatus(status)) => {
+                self.context.accounts[&account_hash].set_connection_status(status);
+                return;
+            }
+            UIEvent::BackendEvent(account_hash, BackendEvent::UntrustedCertificate(cert)) => {
+                let account_name = self.context.accounts[&account_hash].name().to_string();
+                let title = format!(
+                    "{}: {} presented {} certificate with SHA-256 fingerprint {}. Trust it?",
+                    account_name,
+                    cert.address(),
+                    if cert.changed {
+                        "a changed"
+                    } else {
+                        "an unknown"
+                    },
+                    melib::connections::certificates::format_fingerprint(&cert.fingerprint)
+                );
+                log_target("ui", title.clone(), melib::LoggingLevel::WARN);
+                self.overlay.push(Box::new(UIConfirmationDialog::new(
+                    &title,
+                    vec![(true, "yes".to_string()), (false, "no".to_string())],
+                    true,
+                    Some(Box::new(move |_: ComponentId, result: bool| {
+                        let message = if !result {
+                            format!(
+                                "{}: certificate of {} was not trusted.",
+                                account_name, cert.address()
+                            )
+                        } else if let Err(err) =
+                            melib::connections::certificates::trust_certificate(
+                                &account_name,
+                                &cert.host,
+                                cert.port,
+                                &cert.fingerprint,
+                            )
+                        {
+                            format!(
+                                "{}: could not save trusted certificate: {}",
+                                account_name, err
+                            )
+                        } else {
+                            format!(
+                                "{}: certificate of {} is trusted, it will be used on the next connection attempt.",
+                                account_name, cert.address()
+                            )
+                        };
+                        Some(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)))
+                    })),
+                    &self.context,
+                )));
+                return;
+            }
             UIEvent::ChangeMode(m) => {
                 self.context
                     .sender
@@ -1241,6 +2067,26 @@ impl State {
                 self.redraw();
                 return;
             }
+            UIEvent::Timer(id) if id == self.retention_timer.id() => {
+                for account in self.context.accounts.values_mut() {
+                    account.apply_retention_policies();
+                }
+                return;
+            }
+            UIEvent::Timer(id) if id == self.quota_timer.id() => {
+                for account in self.context.accounts.values_mut() {
+                    account.refresh_quota();
+                }
+                return;
+            }
+            UIEvent::Timer(id) if id == self.power_timer.id() => {
+                if self.context.power.refresh() {
+                    self.context.replies.push_back(UIEvent::StatusEvent(
+                        StatusEvent::DisplayMessage(format!("Power: {}.", self.context.power)),
+                    ));
+                }
+                return;
+            }
             UIEvent::Input(Key::Alt('<')) => {
                 self.display_messages_expiration_start = Some(melib::datetime::now());
                 self.display_messages_active = true;
@@ -1260,6 +2106,19 @@ impl State {
                 );
                 return;
             }
+            UIEvent::StatusEvent(StatusEvent::CopyToClipboard(ref text)) => {
+                match self.copy_to_clipboard(text) {
+                    Ok(()) => self.rcv_event(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        "Copied to clipboard.".to_string(),
+                    ))),
+                    Err(err) => self.rcv_event(UIEvent::Notification(
+                        Some("Could not copy to clipboard".to_string()),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    )),
+                }
+                return;
+            }
             UIEvent::StatusEvent(StatusEvent::DisplayMessage(ref msg)) => {
                 self.display_messages.push(DisplayMessage {
                     timestamp: melib::datetime::now(),
@@ -1315,6 +2174,38 @@ impl State {
             // Pass replies to self and call count on the map iterator to force evaluation
             replies.into_iter().map(|r| self.rcv_event(r)).count();
         }
+        if clear_count {
+            self.clear_count();
+        }
+    }
+
+    /// Accumulate digits typed in normal mode into `Context::count`. Returns true if `event` was
+    /// consumed.
+    fn count_input(&mut self, event: &UIEvent) -> bool {
+        let count = match (event, self.context.count) {
+            (UIEvent::Input(Key::Char(c)), count) if c.is_ascii_digit() => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                Some(std::cmp::min(count.unwrap_or(0) * 10 + digit, MAX_COUNT))
+            }
+            (UIEvent::Input(Key::Backspace), Some(count)) => Some(count / 10).filter(|c| *c > 0),
+            (UIEvent::Input(Key::Esc), Some(_)) | (UIEvent::Input(Key::Alt('')), Some(_)) => None,
+            _ => return false,
+        };
+        self.context.count = count;
+        self.rcv_event(UIEvent::StatusEvent(match count {
+            Some(count) => StatusEvent::BufSet(count.to_string()),
+            None => StatusEvent::BufClear,
+        }));
+        true
+    }
+
+    fn clear_count(&mut self) {
+        if std::mem::replace(&mut self.context.keep_count, false) {
+            return;
+        }
+        if self.context.count.take().is_some() {
+            self.rcv_event(UIEvent::StatusEvent(StatusEvent::BufClear));
+        }
     }
 
     pub fn try_wait_on_child(&mut self) -> Option<bool> {
@@ -1325,7 +2216,8 @@ impl State {
                     Ok(Some(_)) => true,
                     Ok(None) => false,
                     Err(e) => {
-                        log(
+                        log_target(
+                            "ui",
                             format!("Failed to wait on editor process: {}", e.to_string()),
                             ERROR,
                         );
@@ -1339,7 +2231,8 @@ impl State {
                     Ok(Some(_)) => true,
                     Ok(None) => false,
                     Err(e) => {
-                        log(
+                        log_target(
+                            "ui",
                             format!("Failed to wait on child process: {}", e.to_string()),
                             ERROR,
                         );
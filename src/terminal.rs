@@ -28,6 +28,8 @@ extern crate unicode_segmentation;
 
 mod color;
 pub use self::color::*;
+mod background;
+pub use self::background::*;
 #[macro_use]
 mod position;
 #[macro_use]
@@ -0,0 +1,127 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Query the terminal's background color with an `OSC 11` escape sequence.
+
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+const OSC_11_QUERY: &[u8] = b"\x1b]11;?\x07";
+
+/// Ask the terminal for its background color and return it as an RGB triplet.
+///
+/// The reply is read directly from `/dev/tty`, so the input thread must not be running. Returns
+/// `None` if the terminal does not reply within `timeout`.
+pub fn query_background_color(timeout: Duration) -> Option<(u8, u8, u8)> {
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+    let orig_termios = tcgetattr(fd).ok()?;
+    let mut raw_termios = orig_termios.clone();
+    cfmakeraw(&mut raw_termios);
+    tcsetattr(fd, SetArg::TCSANOW, &raw_termios).ok()?;
+
+    let mut reply = Vec::with_capacity(32);
+    if tty
+        .write_all(OSC_11_QUERY)
+        .and_then(|_| tty.flush())
+        .is_ok()
+    {
+        let start = Instant::now();
+        let mut buf = [0; 64];
+        while let Some(remaining) = timeout.checked_sub(start.elapsed()) {
+            match poll(
+                &mut [PollFd::new(fd, PollFlags::POLLIN)],
+                remaining.as_millis() as i32,
+            ) {
+                Ok(n) if n > 0 => {}
+                _ => break,
+            }
+            match tty.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => reply.extend_from_slice(&buf[..n]),
+            }
+            /* The reply is terminated either by BEL or by ST (ESC \) */
+            if reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\") {
+                break;
+            }
+        }
+    }
+    let _ = tcsetattr(fd, SetArg::TCSANOW, &orig_termios);
+    parse_osc_11_reply(&reply)
+}
+
+/// Parse a reply of the form `ESC ] 11 ; rgb:RRRR/GGGG/BBBB BEL`. Each component may have one to
+/// four hex digits; only the most significant byte is kept.
+fn parse_osc_11_reply(reply: &[u8]) -> Option<(u8, u8, u8)> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let start = reply.find("rgb:")? + "rgb:".len();
+    let rgb = reply[start..].trim_end_matches(|c| c == '\x07' || c == '\x1b' || c == '\\');
+    let mut components = rgb.split('/').map(|c| {
+        if c.is_empty() || c.len() > 4 {
+            return None;
+        }
+        let val = u16::from_str_radix(c, 16).ok()?;
+        /* Scale to 16 bits, e.g. `f` to `ffff`, and keep the high byte. */
+        let scaled = (u32::from(val) * 0xffff) / ((1 << (4 * c.len() as u32)) - 1);
+        Some((scaled >> 8) as u8)
+    });
+    let ret = (
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+    if components.next().is_some() {
+        return None;
+    }
+    Some(ret)
+}
+
+/// Whether a background color is dark, using its relative luminance.
+pub fn is_dark_color((r, g, b): (u8, u8, u8)) -> bool {
+    (299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b)) / 1000 < 128
+}
+
+#[test]
+fn test_parse_osc_11_reply() {
+    assert_eq!(
+        parse_osc_11_reply(b"\x1b]11;rgb:ffff/ffff/ffff\x07"),
+        Some((255, 255, 255))
+    );
+    assert_eq!(
+        parse_osc_11_reply(b"\x1b]11;rgb:1c1c/2020/2626\x1b\\"),
+        Some((0x1c, 0x20, 0x26))
+    );
+    assert_eq!(
+        parse_osc_11_reply(b"\x1b]11;rgb:f/0/8\x07"),
+        Some((255, 0, 136))
+    );
+    assert_eq!(parse_osc_11_reply(b""), None);
+    assert_eq!(parse_osc_11_reply(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+    assert!(is_dark_color((0x1c, 0x20, 0x26)));
+    assert!(!is_dark_color((255, 255, 255)));
+}
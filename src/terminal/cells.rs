@@ -1041,11 +1041,15 @@ pub fn copy_area(grid_dest: &mut CellBuffer, grid_src: &CellBuffer, dest: Area,
         src_x = get_x(upper_left!(src));
         src_y += 1;
         if src_y > get_y(bottom_right!(src)) {
-            for row in
-                grid_dest.bounds_iter(((get_x(upper_left!(dest)), y + 1), bottom_right!(dest)))
-            {
-                for c in row {
-                    grid_dest[c].set_ch(' ');
+            /* Clear the rest of `dest`, if any: `bounds_iter` would clamp a row past the end of
+             * the grid to its last one. */
+            if y < get_y(bottom_right!(dest)) {
+                for row in
+                    grid_dest.bounds_iter(((get_x(upper_left!(dest)), y + 1), bottom_right!(dest)))
+                {
+                    for c in row {
+                        grid_dest[c].set_ch(' ');
+                    }
                 }
             }
             ret.1 = y;
@@ -20,7 +20,7 @@
  */
 
 use crate::terminal::position::*;
-use melib::{error::*, log, ERROR};
+use melib::{error::*, log_target, ERROR};
 use smallvec::SmallVec;
 
 use nix::fcntl::{open, OFlag};
@@ -93,7 +93,8 @@ pub fn create_pty(width: usize, height: usize, command: String) -> Result<Arc<Mu
             nix::unistd::setsid().unwrap();
             match unsafe { set_controlling_terminal(slave_fd) } {
                 Ok(c) if c < 0 => {
-                    log(
+                    log_target(
+                        "ui",
                         format!(
                             "Could not execute `{}`: ioctl(fd, TIOCSCTTY, NULL) returned {}",
                             command, c,
@@ -104,7 +105,8 @@ pub fn create_pty(width: usize, height: usize, command: String) -> Result<Arc<Mu
                 }
                 Ok(_) => {}
                 Err(err) => {
-                    log(
+                    log_target(
+                        "ui",
                         format!(
                             "Could not execute `{}`: ioctl(fd, TIOCSCTTY, NULL) returned {}",
                             command, err,
@@ -130,12 +132,17 @@ pub fn create_pty(width: usize, height: usize, command: String) -> Result<Arc<Mu
                             &CString::new(command.as_bytes()).unwrap(),
                         ],
                     ) {
-                        log(format!("Could not execute `{}`: {}", command, e,), ERROR);
+                        log_target(
+                            "ui",
+                            format!("Could not execute `{}`: {}", command, e,),
+                            ERROR,
+                        );
                         std::process::exit(-1);
                     }
                 }
             }
-            log(
+            log_target(
+                "ui",
                 format!(
                     "Could not execute `{}`: did not find the standard POSIX sh shell in PATH = {}",
                     command,
@@ -99,7 +99,11 @@ impl EmbedGrid {
         }
     }
 
-    pub fn set_terminal_size(&mut self, new_val: (usize, usize)) {
+    pub fn set_terminal_size(&mut self, mut new_val: (usize, usize)) {
+        if new_val.0 * new_val.1 >= CellBuffer::MAX_SIZE {
+            /* Give the embedded program as many rows as the grid can hold */
+            new_val.1 = (CellBuffer::MAX_SIZE - 1) / new_val.0;
+        }
         if new_val == self.terminal_size {
             return;
         }
@@ -108,12 +112,7 @@ impl EmbedGrid {
         self.scroll_region.bottom = new_val.1.saturating_sub(1);
 
         self.terminal_size = new_val;
-        if !self.grid.resize(new_val.0, new_val.1, None) {
-            panic!(
-                "Terminal size too big: ({} cols, {} rows)",
-                new_val.0, new_val.1
-            );
-        }
+        let _ = self.grid.resize(new_val.0, new_val.1, None);
         self.grid.clear(Some(Cell::default()));
         self.cursor = (0, 0);
         self.wrap_next = false;
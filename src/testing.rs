@@ -0,0 +1,263 @@
+/*
+ * meli - testing.rs
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Drive the UI without a terminal in tests.
+ *
+ * `Headless` runs a `State` with the same components as `meli` itself over a maildir account in
+ * a temporary directory. Keys are fed to it as if typed, and it draws into an in-memory grid
+ * that tests inspect. Events from accounts and jobs are processed like the main loop does while
+ * waiting for the screen to show something.
+ */
+
+use super::*;
+use crossbeam::channel::Receiver;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+pub const COLS: usize = 100;
+pub const ROWS: usize = 30;
+
+/// A message for `Headless::new`.
+pub fn message(subject: &str, from: &str, date: &str) -> String {
+    format!(
+        "From: {from}\r\nTo: test@example.com\r\nSubject: {subject}\r\nDate: {date}\r\n\
+         Message-ID: <{id}@headless.example.com>\r\n\r\nBody of {subject}.\r\n",
+        from = from,
+        subject = subject,
+        date = date,
+        id = subject.replace(' ', ".")
+    )
+}
+
+pub struct Headless {
+    pub state: State,
+    receiver: Receiver<ThreadEvent>,
+    dir: PathBuf,
+}
+
+impl Headless {
+    /// A maildir account with `messages` in it. `config` is appended to the configuration file,
+    /// after the account's section.
+    pub fn new(messages: &[String], config: &str) -> Headless {
+        Headless::with_account(messages, "", config)
+    }
+
+    /// Like `new`, with `account` appended to the account's section.
+    pub fn with_account(messages: &[String], account: &str, config: &str) -> Headless {
+        Headless::with_account_name("test", messages, account, config)
+    }
+
+    /// Like `with_account`, with the account named `name` instead of `test`. The state
+    /// directory is shared by all instances, so tests that keep state in it use their own name.
+    pub fn with_account_name(
+        name: &str,
+        messages: &[String],
+        account: &str,
+        config: &str,
+    ) -> Headless {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        static XDG: Once = Once::new();
+        let base = std::env::temp_dir().join(format!("meli-headless-{}", std::process::id()));
+        /* Keep caches and read positions out of the user's state directory. */
+        XDG.call_once(|| {
+            std::env::set_var("XDG_DATA_HOME", base.join("data"));
+            std::env::set_var("XDG_CACHE_HOME", base.join("cache"));
+            std::env::set_var(melib::paths::DATA_DIR_VAR, base.join("state"));
+        });
+        let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = base.join(id.to_string());
+        let maildir = dir.join("mail");
+        for sub in &["cur", "new", "tmp"] {
+            std::fs::create_dir_all(maildir.join(sub)).unwrap();
+        }
+        for (i, message) in messages.iter().enumerate() {
+            std::fs::write(
+                /* Maildir caches envelopes by account and file name in the shared cache
+                 * directory. */
+                maildir
+                    .join("cur")
+                    .join(format!("{}.{}.headless:2,", id, i)),
+                message,
+            )
+            .unwrap();
+        }
+        let config_path = dir.join("config.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                "[accounts.{}]\nroot_mailbox = \"{}\"\nformat = \"Maildir\"\n\
+                 identity = \"test@example.com\"\nsearch_backend = \"none\"\n{}\n\
+                 [composing]\nsend_mail = \"false\"\n\n[bindings.normal]\n\n{}",
+                name,
+                maildir.display(),
+                account,
+                config
+            ),
+        )
+        .unwrap();
+        let settings = Settings::from_path(config_path).unwrap();
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let mut state =
+            State::new_headless(settings, (COLS, ROWS), sender, receiver.clone()).unwrap();
+        let listing = listing::Listing::new(&mut state.context);
+        let children: Vec<Box<dyn Component>> = vec![
+            Box::new(listing),
+            Box::new(ContactList::new(&state.context)),
+        ];
+        let window = Box::new(Tabbed::new(children, &state.context));
+        let status_bar = Box::new(StatusBar::new(&state.context, window));
+        state.register_component(status_bar);
+        state.render();
+        Headless {
+            state,
+            receiver,
+            dir,
+        }
+    }
+
+    /// The directory of the account's messages.
+    pub fn maildir(&self) -> PathBuf {
+        self.dir.join("mail")
+    }
+
+    /// Type `keys`, processing what they cause before each next one.
+    pub fn keys(&mut self, keys: &[Key]) {
+        for key in keys {
+            self.state.process_input(key.clone(), vec![]);
+            self.settle();
+        }
+    }
+
+    /// Type each character of `input`.
+    pub fn type_str(&mut self, input: &str) {
+        self.keys(&input.chars().map(Key::Char).collect::<Vec<Key>>());
+    }
+
+    /// Run `command` from command mode.
+    pub fn command(&mut self, command: &str) {
+        let enter_command_mode = self
+            .state
+            .context
+            .settings
+            .shortcuts
+            .general
+            .enter_command_mode
+            .clone();
+        self.keys(&[enter_command_mode]);
+        self.type_str(command);
+        self.keys(&[Key::Char('\n')]);
+    }
+
+    fn process(&mut self, event: ThreadEvent) {
+        match event {
+            /* There is no input thread. */
+            ThreadEvent::Input(_) => {}
+            ThreadEvent::RefreshMailbox(event) => self.state.refresh_event(*event),
+            ThreadEvent::UIEvent(UIEvent::ChangeMode(mode)) => self.state.mode = mode,
+            ThreadEvent::UIEvent(event) => self.state.rcv_event(event),
+            ThreadEvent::Pulse => self.state.check_accounts(),
+            ThreadEvent::JobFinished(id) => {
+                for account in self.state.context.accounts.values_mut() {
+                    if account.process_event(&id) {
+                        break;
+                    }
+                }
+            }
+        }
+        self.settle();
+    }
+
+    /// Handle the replies components have queued and redraw.
+    fn settle(&mut self) {
+        loop {
+            let replies = self.state.context.replies();
+            if replies.is_empty() {
+                break;
+            }
+            for event in replies {
+                self.state.rcv_event(event);
+            }
+        }
+        self.state.redraw();
+    }
+
+    /// Process events until `cond` holds, or panic with the screen's contents after ten seconds.
+    pub fn wait_until(&mut self, what: &str, cond: impl Fn(&Headless) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            while let Ok(event) = self.receiver.try_recv() {
+                self.process(event);
+            }
+            if cond(self) {
+                return;
+            }
+            if Instant::now() >= deadline {
+                panic!("timed out waiting for {}, screen:\n{}", what, self.text());
+            }
+            if let Ok(event) = self.receiver.recv_timeout(Duration::from_millis(20)) {
+                self.process(event);
+            }
+        }
+    }
+
+    /// Wait until `text` is on screen.
+    pub fn wait_for(&mut self, text: &str) {
+        self.wait_until(&format!("`{}`", text), |h| h.find(text).is_some());
+    }
+
+    /// Row `y` of the screen, without trailing whitespace.
+    pub fn row(&self, y: usize) -> String {
+        let screen = self.state.screen();
+        (0..screen.cols())
+            .map(|x| &screen[(x, y)])
+            .filter(|c| !c.empty())
+            .map(|c| c.ch())
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// The whole screen, one line per row.
+    pub fn text(&self) -> String {
+        (0..self.state.screen().rows())
+            .map(|y| self.row(y))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The first row that contains `text`.
+    pub fn find(&self, text: &str) -> Option<usize> {
+        (0..self.state.screen().rows()).find(|y| self.row(*y).contains(text))
+    }
+
+    /// The cell at `(x, y)`, to check colors and attributes.
+    pub fn cell(&self, x: usize, y: usize) -> &Cell {
+        &self.state.screen()[(x, y)]
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
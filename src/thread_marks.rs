@@ -0,0 +1,85 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Muted and watched threads.
+ *
+ * Marks are per mailbox. Each marked thread is remembered by the `Message-ID` of its first
+ * message, so that the mark can be reapplied after the mailbox is fetched again on the next
+ * start.
+ */
+use crate::json_store::JsonStore;
+use melib::Result;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct ThreadMarks {
+    store: JsonStore,
+    /// Mailbox path to the raw Message-IDs of the marked threads.
+    mailboxes: HashMap<String, HashSet<String>>,
+}
+
+impl ThreadMarks {
+    /// Load the marks of account `account_name` stored in data file `file_name`. A corrupt store
+    /// is treated as empty and isn't saved over.
+    pub fn new(account_name: &str, file_name: &str) -> Self {
+        let (store, mailboxes) = JsonStore::load(account_name, file_name);
+        ThreadMarks { store, mailboxes }
+    }
+
+    /// The raw Message-IDs of the marked threads of `mailbox_path`.
+    pub fn get(&self, mailbox_path: &str) -> impl Iterator<Item = &str> {
+        self.mailboxes
+            .get(mailbox_path)
+            .into_iter()
+            .flat_map(|set| set.iter().map(String::as_str))
+    }
+
+    pub fn add(&mut self, mailbox_path: &str, message_id: String) -> Result<()> {
+        self.mailboxes
+            .entry(mailbox_path.to_string())
+            .or_default()
+            .insert(message_id);
+        self.save()
+    }
+
+    /// Forget the marked threads of `mailbox_path` remembered by any of `message_ids`.
+    pub fn remove(&mut self, mailbox_path: &str, message_ids: &[String]) -> Result<()> {
+        let set = match self.mailboxes.get_mut(mailbox_path) {
+            Some(set) => set,
+            None => return Ok(()),
+        };
+        let len = set.len();
+        for message_id in message_ids {
+            set.remove(message_id);
+        }
+        if set.len() == len {
+            return Ok(());
+        }
+        if set.is_empty() {
+            self.mailboxes.remove(mailbox_path);
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        self.store.save(&self.mailboxes)
+    }
+}
@@ -57,6 +57,8 @@ pub enum StatusEvent {
     JobFinished(JobId),
     JobCanceled(JobId),
     SetMouse(bool),
+    /// Put the text in the system clipboard.
+    CopyToClipboard(String),
     ScrollUpdate(ScrollUpdate),
 }
 
@@ -149,9 +151,11 @@ pub enum UIEvent {
     GlobalUIDialog(Box<dyn Component>),
     Timer(Uuid),
     ConfigReload {
-        old_settings: crate::conf::Settings,
+        old_settings: Box<crate::conf::Settings>,
     },
     VisibilityChange(bool),
+    /// The component is maximized to the whole screen, or restored.
+    ZoomChange(bool),
 }
 
 pub struct CallbackFn(pub Box<dyn FnOnce(&mut crate::Context) -> () + Send + 'static>);
@@ -19,23 +19,35 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::ffi::OsString;
 use std::fs;
 use std::fs::OpenOptions;
-use std::io::{Read, Write};
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 
+use melib::{MeliError, Result};
+use once_cell::sync::OnceCell;
 use uuid::Uuid;
 
+const TEMP_DIR_PREFIX: &str = "meli-";
+
+static TEMP_DIR: OnceCell<PathBuf> = OnceCell::new();
+
 #[derive(Debug)]
 pub struct File {
     pub path: PathBuf,
     delete_on_drop: bool,
+    shred: bool,
 }
 
 impl Drop for File {
     fn drop(&mut self) {
         if self.delete_on_drop {
+            if self.shred {
+                shred(self.path()).unwrap_or_else(|_| {});
+            }
             std::fs::remove_file(self.path()).unwrap_or_else(|_| {});
         }
     }
@@ -62,44 +74,185 @@ impl File {
             .unwrap_or_else(|_| panic!("Can't read {}", &self.path.display()));
         String::from_utf8(buf).unwrap()
     }
+
+    /// Overwrite the file with zeros before deleting it, for sensitive content such as decrypted
+    /// attachments.
+    pub fn set_shred(&mut self, shred: bool) -> &mut Self {
+        self.shred = shred;
+        self
+    }
+}
+
+/// Overwrite the contents of `path` with zeros and flush them to disk.
+fn shred(path: &Path) -> std::io::Result<()> {
+    let mut f = OpenOptions::new().write(true).open(path)?;
+    let mut len = f.metadata()?.len();
+    f.seek(SeekFrom::Start(0))?;
+    let zeros = [0; 4096];
+    while len > 0 {
+        let n = std::cmp::min(len, zeros.len() as u64);
+        f.write_all(&zeros[..n as usize])?;
+        len -= n;
+    }
+    f.sync_all()
+}
+
+/// The temporary directory of this session, readable by its owner only, created on first use.
+/// Its files are deleted when the session exits, with [`remove_temp_dir`].
+pub fn temp_dir() -> Result<&'static Path> {
+    TEMP_DIR
+        .get_or_try_init(|| {
+            /* The random suffix keeps other users from creating the directory before us. */
+            let template = std::env::temp_dir().join(format!(
+                "{}{}-XXXXXX",
+                TEMP_DIR_PREFIX,
+                std::process::id()
+            ));
+            let mut bytes = template.as_os_str().as_bytes().to_vec();
+            bytes.push(0);
+            if unsafe { libc::mkdtemp(bytes.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+                let err = std::io::Error::last_os_error();
+                return Err(MeliError::new(format!(
+                    "Could not create temporary directory in {}: {}",
+                    std::env::temp_dir().display(),
+                    err
+                ))
+                .set_source(Some(std::sync::Arc::new(err))));
+            }
+            bytes.pop();
+            Ok(PathBuf::from(OsString::from_vec(bytes)))
+        })
+        .map(PathBuf::as_path)
+}
+
+/// Delete the temporary directory of this session and its files.
+pub fn remove_temp_dir() {
+    if let Some(dir) = TEMP_DIR.get() {
+        std::fs::remove_dir_all(dir).unwrap_or_else(|_| {});
+    }
+}
+
+/// Delete the temporary directories of earlier sessions of the user that didn't exit cleanly.
+pub fn remove_stale_temp_dirs() {
+    let entries = match std::fs::read_dir(std::env::temp_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let uid = nix::unistd::getuid().as_raw();
+    for entry in entries.flatten() {
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .filter(|name| name.starts_with(TEMP_DIR_PREFIX))
+            .map(|name| &name[TEMP_DIR_PREFIX.len()..])
+            .and_then(|name| name.split('-').next())
+            .and_then(|pid| pid.parse::<i32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let is_own_dir = entry
+            .metadata()
+            .map(|m| m.is_dir() && m.uid() == uid)
+            .unwrap_or(false);
+        /* The session is still running. */
+        if !is_own_dir
+            || nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None)
+                != Err(nix::Error::Sys(nix::errno::Errno::ESRCH))
+        {
+            continue;
+        }
+        std::fs::remove_dir_all(entry.path()).unwrap_or_else(|_| {});
+    }
 }
 
-/// Returned `File` will be deleted when dropped if delete_on_drop is set, so make sure to add it on `context.temp_files`
-/// to reap it later.
+/// Returned `File` will be deleted when dropped if delete_on_drop is set, so make sure to keep it
+/// in the component that uses it or in `context.temp_files` to reap it later. Such files are
+/// created in the session's [`temp_dir`], and the others in a `meli` directory that outlives it.
 pub fn create_temp_file(
     bytes: &[u8],
     filename: Option<&str>,
     path: Option<&PathBuf>,
     delete_on_drop: bool,
-) -> File {
-    let mut dir = std::env::temp_dir();
-
-    let path = path.unwrap_or_else(|| {
-        dir.push("meli");
-        std::fs::DirBuilder::new()
-            .recursive(true)
-            .create(&dir)
-            .unwrap();
-        if let Some(filename) = filename {
-            dir.push(filename)
-        } else {
-            let u = Uuid::new_v4();
-            dir.push(u.to_hyphenated().to_string());
+) -> Result<File> {
+    let path = match path {
+        Some(path) => path.clone(),
+        None => {
+            let mut dir = if delete_on_drop {
+                temp_dir()?.to_path_buf()
+            } else {
+                let dir = std::env::temp_dir().join("meli");
+                std::fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700) // Owner only.
+                    .create(&dir)?;
+                /* Another user could have created the directory before us. */
+                let metadata = std::fs::symlink_metadata(&dir)?;
+                if !metadata.is_dir() || metadata.uid() != nix::unistd::getuid().as_raw() {
+                    return Err(MeliError::new(format!(
+                        "{} is not a directory owned by the user.",
+                        dir.display()
+                    )));
+                }
+                dir
+            };
+            /* Attachment filenames come from the message, so keep only their last component
+             * lest they point outside the directory. */
+            match filename.and_then(|f| Path::new(f).file_name()) {
+                Some(filename) => dir.push(filename),
+                None => dir.push(Uuid::new_v4().to_hyphenated().to_string()),
+            }
+            dir
         }
-        &dir
-    });
+    };
 
-    let mut f = std::fs::File::create(path).unwrap();
-    let metadata = f.metadata().unwrap();
+    let mut f = std::fs::File::create(&path)?;
+    let metadata = f.metadata()?;
     let mut permissions = metadata.permissions();
 
     permissions.set_mode(0o600); // Read/write for owner only.
-    f.set_permissions(permissions).unwrap();
+    f.set_permissions(permissions)?;
 
-    f.write_all(bytes).unwrap();
-    f.flush().unwrap();
-    File {
-        path: path.clone(),
+    f.write_all(bytes)?;
+    f.flush()?;
+    Ok(File {
+        path,
         delete_on_drop,
+        shred: false,
+    })
+}
+
+#[test]
+fn test_temp_files() {
+    let mut f = create_temp_file(b"secret", None, None, true).unwrap();
+    let dir = temp_dir().unwrap();
+    assert!(f.path().starts_with(dir));
+    /* The directory name can't be guessed by other users. */
+    assert_ne!(
+        dir,
+        std::env::temp_dir().join(format!("{}{}", TEMP_DIR_PREFIX, std::process::id()))
+    );
+    let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(dir), 0o700);
+    assert_eq!(mode(f.path()), 0o600);
+    shred(f.path()).unwrap();
+    assert_eq!(std::fs::read(f.path()).unwrap(), [0; 6]);
+    let path = f.set_shred(true).path().clone();
+    drop(f);
+    assert!(!path.exists());
+
+    /* Filenames can't point outside the directory. */
+    for filename in &["../../escaped", "/tmp/escaped", ".."] {
+        let f = create_temp_file(b"", Some(filename), None, true).unwrap();
+        assert_eq!(f.path().parent(), Some(dir));
     }
+    let f = create_temp_file(b"", Some("../report.pdf"), None, true).unwrap();
+    assert_eq!(f.path(), &dir.join("report.pdf"));
+
+    /* Directories of sessions that are no longer running are removed. */
+    let stale = std::env::temp_dir().join(format!("{}{}", TEMP_DIR_PREFIX, i32::MAX));
+    std::fs::create_dir_all(&stale).unwrap();
+    remove_stale_temp_dirs();
+    assert!(!stale.exists());
+    assert!(dir.exists());
 }
@@ -13,11 +13,15 @@ fn main() -> Result<()> {
             danger_accept_invalid_certs: false,
         },
         extensions: SmtpExtensionSupport::default(),
+        tls: SmtpTlsConf::default(),
+        proxy: None,
         auth: SmtpAuth::Auto {
             username: "username".into(),
             password: Password::CommandEval("gpg2 --no-tty -q -d ~/.passwords/password.gpg".into()),
             require_auth: true,
             auth_type: Default::default(),
+            password_cache_timeout: None,
+            password_cache: Default::default(),
         },
         envelope_from: String::new(),
     };